@@ -0,0 +1,75 @@
+//! Throughput of sending batches and of queueing points for a background sender, against a mock
+//! transport answering right away.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use nextcloud_analytics_rs::testing::MockTransport;
+use nextcloud_analytics_rs::{
+    ApiVersion, BackgroundSender, DataPoint, Overflow, SenderOptions, SyncClient,
+};
+
+fn points(len: usize) -> Vec<DataPoint> {
+    (0..len)
+        .map(|i| DataPoint::new("sensors/temperature", &i.to_string(), i as f64 / 10.0))
+        .collect()
+}
+
+fn client(mock: &MockTransport, version: ApiVersion) -> SyncClient {
+    SyncClient::builder("https://nextcloud.invalid/", 3, "user", "password")
+        .api_version(version)
+        .build()
+        .unwrap()
+        .with_transport(mock.clone())
+}
+
+fn send_batch(c: &mut Criterion) {
+    let mock = MockTransport::new();
+    let mut group = c.benchmark_group("send_batch");
+    for (name, version) in [("v3", ApiVersion::V3), ("v1", ApiVersion::V1)] {
+        let client = client(&mock, version);
+        for len in [10, 100, 1000] {
+            let points = points(len);
+            group.throughput(Throughput::Elements(len as u64));
+            group.bench_with_input(BenchmarkId::new(name, len), &points, |b, points| {
+                b.iter(|| {
+                    let _ = client.send_batch(black_box(points)).unwrap();
+                    mock.clear();
+                })
+            });
+        }
+    }
+    group.finish();
+}
+
+fn background_sender(c: &mut Criterion) {
+    let mock = MockTransport::new();
+    let client = Arc::new(client(&mock, ApiVersion::V3));
+    let points = points(1000);
+    let mut group = c.benchmark_group("background_sender");
+    group.throughput(Throughput::Elements(points.len() as u64));
+    for capacity in [100, 1000] {
+        group.bench_with_input(
+            BenchmarkId::new("enqueue_and_stop", capacity),
+            &capacity,
+            |b, &capacity| {
+                b.iter(|| {
+                    let options = SenderOptions::new(capacity, Duration::from_millis(1))
+                        .overflow(Overflow::Block);
+                    let sender = BackgroundSender::start_with(Arc::clone(&client), options);
+                    let handle = sender.handle();
+                    for point in &points {
+                        handle.enqueue_point(point.clone()).unwrap();
+                    }
+                    sender.stop();
+                    mock.clear();
+                })
+            },
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, send_batch, background_sender);
+criterion_main!(benches);
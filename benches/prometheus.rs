@@ -0,0 +1,26 @@
+//! Cost of parsing a Prometheus text exposition, per scrape.
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use nextcloud_analytics_rs::prometheus;
+
+fn exposition(samples: usize) -> String {
+    let mut text = String::from("# HELP node_cpu_seconds_total CPU time.\n");
+    text += "# TYPE node_cpu_seconds_total counter\n";
+    for i in 0..samples {
+        text += &format!(
+            "node_cpu_seconds_total{{cpu=\"{}\",mode=\"idle\"}} {}.25 1625000000000\n",
+            i, i
+        );
+    }
+    text
+}
+
+fn parse(c: &mut Criterion) {
+    let text = exposition(1000);
+    c.bench_function("prometheus_parse_1000", |b| {
+        b.iter(|| prometheus::parse(black_box(&text)).unwrap())
+    });
+}
+
+criterion_group!(benches, parse);
+criterion_main!(benches);
@@ -0,0 +1,37 @@
+//! Cost of preparing data points for sending, without any network.
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use nextcloud_analytics_rs::{parse_number, DataPoint, NumberFormat, Template, ValueFormat};
+
+fn serialization(c: &mut Criterion) {
+    let point = DataPoint::new(
+        "sensors/living-room/temperature",
+        "Tue, 1 Jul 2003 10:52:37 +0200",
+        21.5,
+    );
+    c.bench_function("to_json", |b| b.iter(|| black_box(&point).to_json()));
+    c.bench_function("to_json_number", |b| {
+        b.iter(|| black_box(&point).to_json_with(ValueFormat::Number))
+    });
+
+    let escaped = DataPoint::new("a \"quoted\"\tkey\n", "line\\break", 1.0);
+    c.bench_function("to_json_escaped", |b| {
+        b.iter(|| black_box(&escaped).to_json())
+    });
+}
+
+fn templates(c: &mut Criterion) {
+    let template = Template::new("{site}/{metric}").var("site", "paris");
+    c.bench_function("template_expand", |b| {
+        b.iter(|| template.expand(black_box("temperature")).unwrap())
+    });
+}
+
+fn parsing(c: &mut Criterion) {
+    c.bench_function("parse_number", |b| {
+        b.iter(|| parse_number(black_box("1.234.567,89"), NumberFormat::DecimalComma))
+    });
+}
+
+criterion_group!(benches, serialization, templates, parsing);
+criterion_main!(benches);
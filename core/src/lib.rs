@@ -0,0 +1,134 @@
+//! # nextcloud_analytics_core
+//!
+//! Core types of [nextcloud_analytics_rs](https://github.com/bnjbvr/nextcloud_analytics_rs),
+//! without any dependency on the standard library or on an HTTP client.
+//!
+//! This allows firmware to build and serialize data points, and to hand them over to a gateway
+//! process owning the HTTP client.
+
+#![no_std]
+
+extern crate alloc;
+
+use alloc::string::String;
+use core::fmt::Write;
+
+/// A single row of data for the Nextcloud Analytics API.
+///
+/// The two first dimensions are text, while the last one is a numerical value. For timeline
+/// data, `dimension2` is the date.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DataPoint {
+    pub dimension1: String,
+    pub dimension2: String,
+    pub dimension3: f64,
+}
+
+impl DataPoint {
+    /// Create a new data point.
+    pub fn new<S: Into<String>, F: Into<f64>>(dimension1: S, dimension2: S, dimension3: F) -> Self {
+        Self {
+            dimension1: dimension1.into(),
+            dimension2: dimension2.into(),
+            dimension3: dimension3.into(),
+        }
+    }
+
+    /// Serializes the data point to the JSON body expected by the API, with the value as a
+    /// string.
+    pub fn to_json(&self) -> String {
+        self.to_json_with(ValueFormat::String)
+    }
+
+    /// Serializes the data point to the JSON body expected by the API, with the value in the
+    /// given format.
+    pub fn to_json_with(&self, format: ValueFormat) -> String {
+        let mut out = String::new();
+        out += "{\"dimension1\":";
+        write_json_string(&mut out, &self.dimension1);
+        out += ",\"dimension2\":";
+        write_json_string(&mut out, &self.dimension2);
+        // JSON has no representation of NaN or infinities as numbers.
+        if format == ValueFormat::Number && self.dimension3.is_finite() {
+            let _ = write!(out, ",\"dimension3\":{}}}", self.dimension3);
+        } else {
+            let _ = write!(out, ",\"dimension3\":\"{}\"}}", self.dimension3);
+        }
+        out
+    }
+}
+
+/// How the value of a data point is serialized in the JSON sent to the API.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ValueFormat {
+    /// A JSON string, e.g. `"21.5"`, as expected by the Analytics API 1.0.
+    #[default]
+    String,
+    /// A bare JSON number, e.g. `21.5`, preferred by some Analytics versions. Values that aren't
+    /// finite are still sent as strings.
+    Number,
+}
+
+/// Appends `s` to `out` as a JSON string literal, quotes included.
+pub fn write_json_string(out: &mut String, s: &str) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+/// How numbers are formatted in the values returned by the server, which depends on the dataset
+/// settings.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum NumberFormat {
+    /// A dot separates decimals, e.g. `1234.56` or `1,234.56`.
+    #[default]
+    DecimalPoint,
+    /// A comma separates decimals, e.g. `1234,56` or `1.234,56`.
+    DecimalComma,
+}
+
+/// Parses a number formatted according to `format`, ignoring thousands separators (the other
+/// punctuation sign, spaces and apostrophes).
+///
+/// Returns `None` if `s` isn't a number in this format.
+pub fn parse_number(s: &str, format: NumberFormat) -> Option<f64> {
+    let (decimal, thousands) = match format {
+        NumberFormat::DecimalPoint => ('.', ','),
+        NumberFormat::DecimalComma => (',', '.'),
+    };
+
+    let mut normalized = String::with_capacity(s.len());
+    let mut seen_decimal = false;
+    for c in s.trim().chars() {
+        match c {
+            c if c == decimal => {
+                if seen_decimal {
+                    return None;
+                }
+                seen_decimal = true;
+                normalized.push('.');
+            }
+            // Thousands separators are only valid in the integral part.
+            c if c == thousands || c == ' ' || c == '\'' || c == '\u{a0}' || c == '\u{202f}' => {
+                if seen_decimal || normalized.is_empty() {
+                    return None;
+                }
+            }
+            c => normalized.push(c),
+        }
+    }
+
+    normalized.parse().ok()
+}
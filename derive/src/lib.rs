@@ -0,0 +1,81 @@
+//! # nextcloud_analytics_derive
+//!
+//! `#[derive(ToAnalyticsRow)]` for
+//! [nextcloud_analytics_rs](https://github.com/bnjbvr/nextcloud_analytics_rs), re-exported by
+//! its `derive` feature.
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{parse_macro_input, Data, DeriveInput, Error, Fields, Ident};
+
+/// Implements `ToAnalyticsRow` for a struct with named fields, one of them annotated with each
+/// of `#[dimension1]`, `#[dimension2]` and `#[value]`.
+///
+/// The dimensions are formatted with `ToString`, and the value converted with `Into<f64>`.
+#[proc_macro_derive(ToAnalyticsRow, attributes(dimension1, dimension2, value))]
+pub fn derive_to_analytics_row(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(&input)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+fn expand(input: &DeriveInput) -> Result<TokenStream2, Error> {
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => {
+                return Err(Error::new_spanned(
+                    &input.ident,
+                    "ToAnalyticsRow can only be derived for structs with named fields",
+                ))
+            }
+        },
+        _ => {
+            return Err(Error::new_spanned(
+                &input.ident,
+                "ToAnalyticsRow can only be derived for structs",
+            ))
+        }
+    };
+
+    let find = |attribute: &str| -> Result<&Ident, Error> {
+        let mut found = fields.iter().filter(|field| {
+            field
+                .attrs
+                .iter()
+                .any(|attr| attr.path().is_ident(attribute))
+        });
+        let field = found.next().ok_or_else(|| {
+            Error::new_spanned(
+                &input.ident,
+                format!("no field annotated with #[{}]", attribute),
+            )
+        })?;
+        if let Some(duplicate) = found.next() {
+            return Err(Error::new_spanned(
+                duplicate,
+                format!("several fields annotated with #[{}]", attribute),
+            ));
+        }
+        Ok(field.ident.as_ref().expect("named fields have a name"))
+    };
+    let dimension1 = find("dimension1")?;
+    let dimension2 = find("dimension2")?;
+    let value = find("value")?;
+
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::nextcloud_analytics_rs::ToAnalyticsRow for #name #ty_generics #where_clause {
+            fn to_row(&self) -> ::nextcloud_analytics_rs::DataPoint {
+                ::nextcloud_analytics_rs::DataPoint::new(
+                    ::std::string::ToString::to_string(&self.#dimension1),
+                    ::std::string::ToString::to_string(&self.#dimension2),
+                    ::std::convert::Into::<f64>::into(::std::clone::Clone::clone(&self.#value)),
+                )
+            }
+        }
+    })
+}
@@ -0,0 +1,140 @@
+//! Checks the performance budget of the README: the throughput of sending batches and of
+//! queueing points for a background sender, against a mock transport answering right away, and
+//! the peak memory of the process. Exits with an error if one of them misses its target.
+//!
+//! Run on the device with `cargo run --release --example budget`; the targets default to the
+//! ones of a Raspberry Pi Zero, and can be changed with `--min-send-rate`, `--min-queue-rate`
+//! (data points per second) and `--max-rss-mib`.
+
+use std::process;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use nextcloud_analytics_rs::testing::MockTransport;
+use nextcloud_analytics_rs::{
+    ApiVersion, BackgroundSender, DataPoint, Overflow, SenderOptions, SyncClient,
+};
+
+/// How long each throughput is measured.
+const MEASURE: Duration = Duration::from_secs(2);
+
+struct Targets {
+    send_rate: f64,
+    queue_rate: f64,
+    rss_mib: f64,
+}
+
+impl Targets {
+    fn from_args() -> Result<Self, String> {
+        let mut targets = Targets {
+            send_rate: 20_000.0,
+            queue_rate: 50_000.0,
+            rss_mib: 16.0,
+        };
+        let mut args = std::env::args().skip(1);
+        while let Some(arg) = args.next() {
+            let target = match arg.as_str() {
+                "--min-send-rate" => &mut targets.send_rate,
+                "--min-queue-rate" => &mut targets.queue_rate,
+                "--max-rss-mib" => &mut targets.rss_mib,
+                _ => return Err(format!("unknown argument {:?}", arg)),
+            };
+            *target = args
+                .next()
+                .and_then(|value| value.parse().ok())
+                .ok_or_else(|| format!("{} needs a number", arg))?;
+        }
+        Ok(targets)
+    }
+}
+
+fn points(len: usize) -> Vec<DataPoint> {
+    (0..len)
+        .map(|i| DataPoint::new("sensors/temperature", &i.to_string(), i as f64 / 10.0))
+        .collect()
+}
+
+/// Data points per second sent in batches of 1,000 with the 3.0 API.
+fn send_rate(client: &SyncClient, mock: &MockTransport) -> f64 {
+    let batch = points(1000);
+    let started = Instant::now();
+    let mut sent = 0;
+    while started.elapsed() < MEASURE {
+        let _ = client.send_batch(&batch).unwrap();
+        mock.clear();
+        sent += batch.len();
+    }
+    sent as f64 / started.elapsed().as_secs_f64()
+}
+
+/// Data points per second queued for, and sent by, a background sender.
+fn queue_rate(client: &Arc<SyncClient>, mock: &MockTransport) -> f64 {
+    let points = points(10_000);
+    let started = Instant::now();
+    let mut queued = 0;
+    while started.elapsed() < MEASURE {
+        let options = SenderOptions::new(1000, Duration::from_millis(1)).overflow(Overflow::Block);
+        let sender = BackgroundSender::start_with(Arc::clone(client), options);
+        let handle = sender.handle();
+        for point in &points {
+            handle.enqueue_point(point.clone()).unwrap();
+        }
+        sender.stop();
+        mock.clear();
+        queued += points.len();
+    }
+    queued as f64 / started.elapsed().as_secs_f64()
+}
+
+/// Peak resident memory of the process, in MiB, on Linux.
+fn peak_rss_mib() -> Option<f64> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find(|line| line.starts_with("VmHWM:"))?;
+    let kib: f64 = line.split_whitespace().nth(1)?.parse().ok()?;
+    Some(kib / 1024.0)
+}
+
+fn main() {
+    let targets = Targets::from_args().unwrap_or_else(|err| {
+        eprintln!("{}", err);
+        process::exit(2);
+    });
+
+    let mock = MockTransport::new();
+    let client = SyncClient::builder("https://nextcloud.invalid/", 3, "user", "password")
+        .api_version(ApiVersion::V3)
+        .build()
+        .unwrap()
+        .with_transport(mock.clone());
+    let client = Arc::new(client);
+
+    let mut failed = false;
+    let mut check = |name: &str, value: f64, target: f64, ok: bool| {
+        println!(
+            "{:<12} {:>12.0}  (target {:.0}) {}",
+            name,
+            value,
+            target,
+            if ok { "ok" } else { "MISSED" }
+        );
+        failed |= !ok;
+    };
+
+    let send = send_rate(&client, &mock);
+    check("send/s", send, targets.send_rate, send >= targets.send_rate);
+    let queue = queue_rate(&client, &mock);
+    check(
+        "queue/s",
+        queue,
+        targets.queue_rate,
+        queue >= targets.queue_rate,
+    );
+    match peak_rss_mib() {
+        Some(rss) => check("peak MiB", rss, targets.rss_mib, rss <= targets.rss_mib),
+        None => println!("peak MiB     unknown, /proc/self/status can't be read"),
+    }
+
+    if failed {
+        process::exit(1);
+    }
+}
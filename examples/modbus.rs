@@ -0,0 +1,22 @@
+//! Pushes the readings of a Modbus TCP energy meter to Nextcloud Analytics every minute.
+//!
+//! Run with `cargo run --example modbus --features modbus`.
+
+use std::time::Duration;
+
+use nextcloud_analytics_rs::collectors::modbus::{ModbusCollector, Register, RegisterFormat};
+use nextcloud_analytics_rs::SyncClient;
+
+fn main() {
+    let client = SyncClient::new("https://example.com/nextcloud", 42, "myself", "hunter2");
+
+    let meter = ModbusCollector::new("192.168.1.20:502")
+        .unit_id(1)
+        .register(Register::input("power_w", 0x0c, RegisterFormat::F32))
+        .register(Register::input("energy_kwh", 0x156, RegisterFormat::F32))
+        .register(Register::holding("voltage_v", 0, RegisterFormat::U16).scale(0.1));
+
+    meter.run(&client, Duration::from_secs(60), |err| {
+        eprintln!("couldn't push the meter readings: {}", err)
+    });
+}
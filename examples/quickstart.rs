@@ -0,0 +1,28 @@
+//! Connects to a Nextcloud instance and sends a value to a dataset chosen by name.
+//!
+//! Run with `cargo run --example quickstart -- <nextcloud url> <user> <app password> <dataset>`.
+
+use std::env;
+use std::process;
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.len() != 4 {
+        eprintln!("usage: quickstart <nextcloud url> <user> <app password> <dataset>");
+        process::exit(2);
+    }
+
+    let client = match nextcloud_analytics_rs::quickstart(&args[0], &args[1], &args[2], &args[3]) {
+        Ok(client) => client,
+        Err(err) => {
+            eprintln!("couldn't connect: {}", err);
+            process::exit(1);
+        }
+    };
+    println!("{}", client.describe());
+
+    if let Err(err) = client.send_data("quickstart", "hello", 1) {
+        eprintln!("couldn't send the data: {}", err);
+        process::exit(1);
+    }
+}
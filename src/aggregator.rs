@@ -0,0 +1,271 @@
+use std::collections::btree_map::Entry;
+use std::collections::BTreeMap;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use crate::{AnalyticsError, Receipt, SyncClient};
+
+/// How the contributions to a key are combined by an [`Aggregator`] between two flushes.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Aggregation {
+    /// Sum of the contributions, e.g. for counts of events.
+    #[default]
+    Sum,
+    /// Mean of the contributions, e.g. for latencies.
+    Mean,
+    /// Smallest contribution.
+    Min,
+    /// Largest contribution.
+    Max,
+    /// Number of contributions.
+    Count,
+    /// The given percentile of the contributions, from 0 to 100, e.g. 95 for the value that
+    /// 95% of the contributions don't exceed. This keeps all the contributions until the next
+    /// flush.
+    Percentile(u8),
+}
+
+impl Aggregation {
+    /// Suffix of the key of this aggregation, with [`Aggregator::start_summary`].
+    fn suffix(self) -> String {
+        match self {
+            Aggregation::Sum => "sum".to_string(),
+            Aggregation::Mean => "mean".to_string(),
+            Aggregation::Min => "min".to_string(),
+            Aggregation::Max => "max".to_string(),
+            Aggregation::Count => "count".to_string(),
+            Aggregation::Percentile(percentile) => format!("p{}", percentile),
+        }
+    }
+}
+
+/// Contributions to a key since the last flush.
+struct Accumulator {
+    sum: f64,
+    count: u64,
+    min: f64,
+    max: f64,
+    /// All the contributions, only if a percentile is computed.
+    samples: Vec<f64>,
+}
+
+impl Accumulator {
+    fn new() -> Self {
+        Self {
+            sum: 0.0,
+            count: 0,
+            min: f64::INFINITY,
+            max: f64::NEG_INFINITY,
+            samples: Vec::new(),
+        }
+    }
+
+    fn push(&mut self, value: f64, keep_samples: bool) {
+        self.sum += value;
+        self.count += 1;
+        self.min = self.min.min(value);
+        self.max = self.max.max(value);
+        if keep_samples {
+            self.samples.push(value);
+        }
+    }
+
+    fn merge(&mut self, other: Accumulator) {
+        self.sum += other.sum;
+        self.count += other.count;
+        self.min = self.min.min(other.min);
+        self.max = self.max.max(other.max);
+        self.samples.extend(other.samples);
+    }
+
+    /// The combined value; the samples must be sorted for percentiles.
+    fn value(&self, aggregation: Aggregation) -> f64 {
+        match aggregation {
+            Aggregation::Sum => self.sum,
+            Aggregation::Mean => self.sum / self.count as f64,
+            Aggregation::Min => self.min,
+            Aggregation::Max => self.max,
+            Aggregation::Count => self.count as f64,
+            Aggregation::Percentile(percentile) => {
+                // Nearest rank.
+                let rank = (f64::from(percentile.min(100)) / 100.0 * self.samples.len() as f64)
+                    .ceil() as usize;
+                self.samples[rank.clamp(1, self.samples.len()) - 1]
+            }
+        }
+    }
+}
+
+/// State shared by the handles of an aggregator and its flushing thread.
+struct Shared {
+    client: Arc<SyncClient>,
+    aggregations: Vec<Aggregation>,
+    /// Whether the keys of the aggregations are suffixed, see [`Aggregator::start_summary`].
+    suffixed: bool,
+    /// Whether the contributions must be kept, for percentiles.
+    keep_samples: bool,
+    values: Mutex<BTreeMap<String, Accumulator>>,
+}
+
+impl Shared {
+    fn flush(&self) -> Result<Option<Receipt>, AnalyticsError> {
+        let mut values = std::mem::take(&mut *self.values.lock().unwrap());
+        if values.is_empty() {
+            return Ok(None);
+        }
+
+        let mut batch = self.client.timeline_batch();
+        for (key, accumulator) in &mut values {
+            accumulator.samples.sort_by(f64::total_cmp);
+            for &aggregation in &self.aggregations {
+                let value = accumulator.value(aggregation);
+                if self.suffixed {
+                    batch.add(format!("{}.{}", key, aggregation.suffix()), value);
+                } else {
+                    batch.add(key.as_str(), value);
+                }
+            }
+        }
+        match batch.send() {
+            Ok(receipt) => Ok(Some(receipt)),
+            Err(err) => {
+                // Keep the contributions for the next flush, with the ones made meanwhile.
+                let mut current = self.values.lock().unwrap();
+                for (key, accumulator) in values {
+                    match current.entry(key) {
+                        Entry::Occupied(mut newer) => newer.get_mut().merge(accumulator),
+                        Entry::Vacant(entry) => {
+                            entry.insert(accumulator);
+                        }
+                    }
+                }
+                Err(err)
+            }
+        }
+    }
+}
+
+/// Stops the flushing thread of an aggregator once its last handle is dropped.
+struct Flusher {
+    stop: Option<mpsc::Sender<()>>,
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+impl Drop for Flusher {
+    fn drop(&mut self) {
+        // Dropping the sender wakes the thread up.
+        self.stop.take();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Combines the values contributed by many threads for the same keys, and sends one timeline
+/// point per key on an interval through a single client, instead of a client and a row per
+/// thread.
+///
+/// The aggregator is a cheap handle: clone it into every thread. It flushes one last time,
+/// from its own thread, when the last handle is dropped. Failed flushes are reported through
+/// the client's callbacks and failure notifier, see [`SyncClient::on_error`], and their
+/// contributions are kept for the next flush.
+///
+/// Requires the `chrono` feature.
+#[derive(Clone)]
+pub struct Aggregator {
+    shared: Arc<Shared>,
+    _flusher: Arc<Flusher>,
+}
+
+impl Aggregator {
+    /// Starts aggregating values with `aggregation`, flushing them through `client` every
+    /// `interval`.
+    pub fn start(client: Arc<SyncClient>, interval: Duration, aggregation: Aggregation) -> Self {
+        Self::spawn(client, interval, vec![aggregation], false)
+    }
+
+    /// Starts summarizing high-frequency values, e.g. latencies sampled at 100 Hz, flushing
+    /// them through `client` every `interval` as one timeline point per key and aggregation,
+    /// with the aggregation as a suffix of the key: `latency_ms.min`, `latency_ms.mean`,
+    /// `latency_ms.p95`, ...
+    ///
+    /// ```no_run
+    /// # use std::sync::Arc;
+    /// # use std::time::Duration;
+    /// # use nextcloud_analytics_rs::{Aggregation, Aggregator, SyncClient};
+    /// let client = Arc::new(SyncClient::new("https://example.com/nextcloud", 3, "myself", "hunter2"));
+    /// let latencies = Aggregator::start_summary(
+    ///     client,
+    ///     Duration::from_secs(60),
+    ///     &[Aggregation::Min, Aggregation::Max, Aggregation::Mean, Aggregation::Percentile(95)],
+    /// );
+    /// latencies.add("latency_ms", 12.5);
+    /// ```
+    pub fn start_summary(
+        client: Arc<SyncClient>,
+        interval: Duration,
+        aggregations: &[Aggregation],
+    ) -> Self {
+        Self::spawn(client, interval, aggregations.to_vec(), true)
+    }
+
+    fn spawn(
+        client: Arc<SyncClient>,
+        interval: Duration,
+        aggregations: Vec<Aggregation>,
+        suffixed: bool,
+    ) -> Self {
+        let keep_samples = aggregations
+            .iter()
+            .any(|aggregation| matches!(aggregation, Aggregation::Percentile(_)));
+        let shared = Arc::new(Shared {
+            client,
+            aggregations,
+            suffixed,
+            keep_samples,
+            values: Mutex::new(BTreeMap::new()),
+        });
+        let (stop, stopped) = mpsc::channel();
+
+        let flushed = shared.clone();
+        let thread = thread::spawn(move || loop {
+            let stop = match stopped.recv_timeout(interval) {
+                Err(RecvTimeoutError::Timeout) => false,
+                Ok(()) | Err(RecvTimeoutError::Disconnected) => true,
+            };
+            let _ = flushed.flush();
+            if stop {
+                break;
+            }
+        });
+
+        Self {
+            shared,
+            _flusher: Arc::new(Flusher {
+                stop: Some(stop),
+                thread: Some(thread),
+            }),
+        }
+    }
+
+    /// Contributes `value` to `key`, to be combined with the other contributions to it until
+    /// the next flush.
+    pub fn add<S: Into<String>, F: Into<f64>>(&self, key: S, value: F) {
+        self.shared
+            .values
+            .lock()
+            .unwrap()
+            .entry(key.into())
+            .or_insert_with(Accumulator::new)
+            .push(value.into(), self.shared.keep_samples);
+    }
+
+    /// Sends the combined values of all the keys with contributions since the last flush
+    /// right away, in a single batch stamped with the current time. Returns `None` if there
+    /// was nothing to send.
+    pub fn flush(&self) -> Result<Option<Receipt>, AnalyticsError> {
+        self.shared.flush()
+    }
+}
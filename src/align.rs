@@ -0,0 +1,88 @@
+use chrono::{DateTime, TimeZone, Utc};
+use serde_json::Value;
+
+use crate::reports::Report;
+use crate::{AnalyticsError, DatasetTimeZone, SyncClient};
+
+/// Grouping of a timeline report, to which timestamps can be aligned with
+/// [`SyncClient::align_to`](crate::SyncClient::align_to).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Resolution {
+    Minute,
+    Hour,
+    /// Days start at midnight in the time zone of the dataset, see
+    /// [`SyncClient::with_time_zone`](crate::SyncClient::with_time_zone).
+    Day,
+}
+
+impl Resolution {
+    fn seconds(self) -> i64 {
+        match self {
+            Resolution::Minute => 60,
+            Resolution::Hour => 60 * 60,
+            Resolution::Day => 24 * 60 * 60,
+        }
+    }
+
+    /// Truncates `time` to the start of its minute, hour or day, in UTC.
+    pub fn align(self, time: DateTime<Utc>) -> DateTime<Utc> {
+        self.align_in(time, DatasetTimeZone::Utc)
+    }
+
+    /// Truncates `time` to the start of its minute, hour or day in `zone`, e.g. to local
+    /// midnight. A start which doesn't exist in the zone, skipped when clocks go forward, leaves
+    /// `time` as it is.
+    pub fn align_in(self, time: DateTime<Utc>, zone: DatasetTimeZone) -> DateTime<Utc> {
+        let seconds = self.seconds();
+        let local = Utc.from_utc_datetime(&zone.local_time(time)).timestamp();
+        Utc.timestamp_opt(local.div_euclid(seconds) * seconds, 0)
+            .single()
+            .and_then(|aligned| zone.read_local(&aligned.naive_utc()))
+            .unwrap_or(time)
+    }
+
+    /// The grouping of the timeline of a report: the time unit of the x axis of its chart
+    /// options, as `scales.x.time.unit`, or `scales.xAxes[0].time.unit` for older charts, or
+    /// else the `timeAggregation` of its filters. Other units than minutes, hours and days
+    /// aren't supported.
+    pub fn of_report(report: &Report) -> Option<Self> {
+        let chart_unit = report.chart_options.as_ref().and_then(|options| {
+            let scales = &options["scales"];
+            let axis = match &scales["x"] {
+                Value::Null => &scales["xAxes"][0],
+                axis => axis,
+            };
+            axis["time"]["unit"].as_str()
+        });
+        let filter_unit = report
+            .filters
+            .as_ref()
+            .and_then(|filters| filters["timeAggregation"].as_str());
+        chart_unit.into_iter().chain(filter_unit).find_map(|unit| {
+            match unit.to_ascii_lowercase().as_str() {
+                "minute" => Some(Resolution::Minute),
+                "hour" => Some(Resolution::Hour),
+                "day" => Some(Resolution::Day),
+                _ => None,
+            }
+        })
+    }
+}
+
+impl SyncClient {
+    /// Fetches the configuration of a report and aligns the timestamps of timeline sends to
+    /// its grouping, see [`Resolution::of_report`] and [`SyncClient::align_to`]. Fails if the
+    /// report has no minute, hour or day grouping.
+    ///
+    /// Requires the `chrono` feature.
+    pub fn align_to_report(self, report: u64) -> Result<Self, AnalyticsError> {
+        let config = self.get_report(report)?;
+        let resolution = Resolution::of_report(&config).ok_or_else(|| {
+            AnalyticsError::local(format!(
+                "report {} has no minute, hour or day grouping to align to",
+                report
+            ))
+        })?;
+        Ok(self.align_to(resolution))
+    }
+}
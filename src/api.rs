@@ -0,0 +1,74 @@
+#[cfg(feature = "blocking")]
+use crate::AnalyticsError;
+
+/// Version of the Analytics REST API a client talks to, set with
+/// [`SyncClientBuilder::api_version`](crate::SyncClientBuilder::api_version).
+///
+/// With the 2.0 and 3.0 APIs, the collection index passed to the client is the dataset ID.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ApiVersion {
+    /// The legacy `api/1.0` API, only able to add data one point at a time.
+    #[default]
+    V1,
+    /// The `api/2.0` API, adding data in batches and able to delete data.
+    V2,
+    /// The `api/3.0` API, with the same features as 2.0 under newer endpoints.
+    V3,
+}
+
+impl ApiVersion {
+    /// Version number, as it appears in the endpoints' paths.
+    pub fn name(self) -> &'static str {
+        match self {
+            ApiVersion::V1 => "1.0",
+            ApiVersion::V2 => "2.0",
+            ApiVersion::V3 => "3.0",
+        }
+    }
+
+    /// Path of the endpoint adding data to a dataset, relative to the Nextcloud base URL.
+    pub(crate) fn add_path(self, dataset: u32) -> String {
+        match self {
+            ApiVersion::V1 | ApiVersion::V2 => {
+                format!("apps/analytics/api/{}/adddata/{}", self.name(), dataset)
+            }
+            ApiVersion::V3 => format!("apps/analytics/api/3.0/data/{}/add", dataset),
+        }
+    }
+
+    /// Path of the endpoint adding several data points at once, if the API has one: the 1.0
+    /// API doesn't, and its collection indexes aren't the dataset IDs of the newer APIs.
+    pub(crate) fn batch_path(self, dataset: u32) -> Option<String> {
+        match self {
+            ApiVersion::V1 => None,
+            _ => Some(self.add_path(dataset)),
+        }
+    }
+
+    /// Path of the endpoint deleting data from a dataset; the 1.0 API can't delete data.
+    #[cfg(feature = "blocking")]
+    pub(crate) fn delete_path(self, dataset: u32) -> Result<String, AnalyticsError> {
+        match self {
+            ApiVersion::V1 => Err(AnalyticsError::local(
+                "the 1.0 API can't delete data: use the 2.0 or 3.0 API",
+            )),
+            ApiVersion::V2 => Ok(format!("apps/analytics/api/2.0/deletedata/{}", dataset)),
+            ApiVersion::V3 => Ok(format!("apps/analytics/api/3.0/data/{}/delete", dataset)),
+        }
+    }
+
+    /// Path of the endpoint listing the datasets.
+    #[cfg(feature = "blocking")]
+    pub(crate) fn datasets_path(self) -> &'static str {
+        match self {
+            ApiVersion::V1 | ApiVersion::V2 => "apps/analytics/api/2.0/datasets",
+            ApiVersion::V3 => "apps/analytics/api/3.0/datasets",
+        }
+    }
+
+    /// Whether points are sent wrapped in a `data` array, with the value as `value`, rather
+    /// than as a single object with the value as `dimension3`.
+    pub(crate) fn wraps_points(self) -> bool {
+        self != ApiVersion::V1
+    }
+}
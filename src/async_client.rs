@@ -0,0 +1,681 @@
+#[cfg(feature = "chrono")]
+use chrono::{DateTime, Utc};
+use std::future::Future;
+use std::time::Duration;
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::Instant;
+
+use futures_util::future::{self, Either};
+use futures_util::stream::{self, Stream, StreamExt};
+use reqwest as http;
+
+use crate::batch::batch_payload;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::builder::{HttpOptions, DEFAULT_TIMEOUT};
+use crate::stream::{DEFAULT_CHUNK_SIZE, DEFAULT_IN_FLIGHT};
+#[cfg(any(feature = "native-tls", feature = "rustls"))]
+use crate::Security;
+use crate::{
+    parse_add_response, AnalyticsError, ApiVersion, BatchReport, Credentials, DataPoint, Outcome,
+    RateLimit, Receipt, RetryPolicy, SendReport, Validation, ValueFormat,
+};
+#[cfg(feature = "chrono")]
+use crate::{DatasetTimeZone, TimestampFormat};
+
+/// An asynchronous client to call the Nextcloud Analytics API, with the same sending methods as
+/// [`SyncClient`](crate::SyncClient).
+///
+/// It uses the 1.0 API unless another one is set with [`AsyncClientBuilder::api_version`]. The
+/// futures must be polled from a tokio 0.2 runtime, as required by `reqwest`.
+///
+/// On `wasm32-unknown-unknown`, built without the default features, requests go through the
+/// browser's `fetch` and the futures are polled by its event loop, e.g. with
+/// `wasm-bindgen-futures`. The browser handles the connections there, and there's no clock nor
+/// timer: the builder has no timeout, proxy, TLS, user agent or retry settings, and the client
+/// has no rate limit, `_with_timeout` nor `_before` methods.
+///
+/// Dropping a future cancels its request, e.g. with `tokio::select!`; the data may or may not
+/// have been recorded by the server then, as with a timeout. The `_until` and `_before`
+/// variants of the send methods stop on a cancellation signal or a deadline, rate limit waits
+/// and retries included, and fail with a [`Local`](AnalyticsError::Local) error then, see
+/// [`AsyncClient::send_point_until`].
+///
+/// The client is cheap to clone, e.g. to move it into spawned tasks: clones share their
+/// connection pool, credentials and rate limit.
+///
+/// Requires the `async` feature.
+#[derive(Clone)]
+pub struct AsyncClient {
+    client: http::Client,
+    url: String,
+    /// URL of the bulk endpoint, if the API has one.
+    batch_url: Option<String>,
+    api_version: ApiVersion,
+    credentials: Credentials,
+    rate_limit: Option<RateLimit>,
+    retry: Option<RetryPolicy>,
+    validation: Validation,
+    value_format: ValueFormat,
+    #[cfg(feature = "chrono")]
+    timestamp_format: TimestampFormat,
+    #[cfg(feature = "chrono")]
+    time_zone: DatasetTimeZone,
+}
+
+impl AsyncClient {
+    /// Create a new asynchronous client to call the Nextcloud Analytics API, with the same
+    /// arguments as [`SyncClient::new`](crate::SyncClient::new).
+    pub fn new<S: Into<String>>(nextcloud_url: &str, collection: u32, user: S, passwd: S) -> Self {
+        Self::builder(nextcloud_url, collection, user, passwd)
+            .build()
+            .unwrap()
+    }
+
+    /// Create a builder for an asynchronous client, to tune its HTTP settings. The arguments are
+    /// the same as for [`AsyncClient::new`].
+    pub fn builder<S: Into<String>>(
+        nextcloud_url: &str,
+        collection: u32,
+        user: S,
+        passwd: S,
+    ) -> AsyncClientBuilder {
+        AsyncClientBuilder {
+            nextcloud_url: nextcloud_url.to_string(),
+            collection,
+            user: user.into(),
+            passwd: passwd.into(),
+            credentials: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            timeout: DEFAULT_TIMEOUT,
+            #[cfg(not(target_arch = "wasm32"))]
+            connect_timeout: None,
+            retry: None,
+            api_version: ApiVersion::default(),
+            #[cfg(not(target_arch = "wasm32"))]
+            options: HttpOptions::default(),
+            #[cfg(all(
+                any(feature = "native-tls", feature = "rustls"),
+                not(target_arch = "wasm32")
+            ))]
+            security: Security::default(),
+        }
+    }
+
+    /// Wait for a token of the given bucket before every request, see
+    /// [`SyncClient::with_rate_limit`](crate::SyncClient::with_rate_limit). The bucket can be
+    /// shared with synchronous clients.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn with_rate_limit(mut self, limit: RateLimit) -> Self {
+        self.rate_limit = Some(limit);
+        self
+    }
+
+    /// Check the data points with `validation` before sending them, see
+    /// [`SyncClient::with_validation`](crate::SyncClient::with_validation).
+    pub fn with_validation(mut self, validation: Validation) -> Self {
+        self.validation = validation;
+        self
+    }
+
+    /// Send the values as JSON numbers rather than strings, see
+    /// [`SyncClient::with_value_format`](crate::SyncClient::with_value_format).
+    pub fn with_value_format(mut self, format: ValueFormat) -> Self {
+        self.value_format = format;
+        self
+    }
+
+    /// Format the dates of timeline data with `format`, instead of RFC 2822, see
+    /// [`SyncClient::with_timestamp_format`](crate::SyncClient::with_timestamp_format).
+    ///
+    /// Requires the `chrono` feature.
+    #[cfg(feature = "chrono")]
+    pub fn with_timestamp_format(mut self, format: TimestampFormat) -> Self {
+        self.timestamp_format = format;
+        self
+    }
+
+    /// Write the dates of timeline data in `zone` instead of UTC, see
+    /// [`SyncClient::with_time_zone`](crate::SyncClient::with_time_zone).
+    ///
+    /// Requires the `chrono` feature.
+    #[cfg(feature = "chrono")]
+    pub fn with_time_zone(mut self, zone: DatasetTimeZone) -> Self {
+        self.time_zone = zone;
+        self
+    }
+
+    /// The credentials of the client, shared with its clones, to rotate them, see
+    /// [`Credentials`].
+    pub fn credentials(&self) -> &Credentials {
+        &self.credentials
+    }
+
+    /// Sends some data to the API, see [`SyncClient::send_data`](crate::SyncClient::send_data).
+    pub async fn send_data<S: Into<String>, F: Into<f64>>(
+        &self,
+        dimension1: S,
+        dimension2: S,
+        dimension3: F,
+    ) -> Result<Receipt, AnalyticsError> {
+        self.send_point(DataPoint::new(dimension1, dimension2, dimension3))
+            .await
+    }
+
+    /// Sends an existing data point to the API.
+    pub async fn send_point(&self, mut point: DataPoint) -> Result<Receipt, AnalyticsError> {
+        self.validation.apply(&mut point)?;
+        self.post(&self.url, self.point_payload(&point), 1, None)
+            .await
+    }
+
+    /// Sends a data point like [`AsyncClient::send_point`], unless `cancel` completes first,
+    /// e.g. a shutdown signal: the request is then dropped, and this fails with a
+    /// [`Local`](AnalyticsError::Local) error. The data may or may not have been recorded by
+    /// the server, as with a timeout.
+    pub async fn send_point_until<C: Future>(
+        &self,
+        point: DataPoint,
+        cancel: C,
+    ) -> Result<Receipt, AnalyticsError> {
+        until(self.send_point(point), cancel, "cancelled").await
+    }
+
+    /// Sends a data point like [`AsyncClient::send_point`], failing if it isn't done by
+    /// `deadline`, waiting for the rate limit and retries included, see
+    /// [`AsyncClient::send_point_until`].
+    #[cfg(not(target_arch = "wasm32"))]
+    pub async fn send_point_before(
+        &self,
+        point: DataPoint,
+        deadline: Instant,
+    ) -> Result<Receipt, AnalyticsError> {
+        until(self.send_point(point), at(deadline), "deadline passed").await
+    }
+
+    /// Sends some data like [`AsyncClient::send_data`], with the request timing out after
+    /// `timeout` instead of the client's timeout, see
+    /// [`SyncClient::send_data_with_timeout`](crate::SyncClient::send_data_with_timeout).
+    #[cfg(not(target_arch = "wasm32"))]
+    pub async fn send_data_with_timeout<S: Into<String>, F: Into<f64>>(
+        &self,
+        dimension1: S,
+        dimension2: S,
+        dimension3: F,
+        timeout: Duration,
+    ) -> Result<Receipt, AnalyticsError> {
+        self.send_point_with_timeout(DataPoint::new(dimension1, dimension2, dimension3), timeout)
+            .await
+    }
+
+    /// Sends an existing data point like [`AsyncClient::send_point`], with the given timeout.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub async fn send_point_with_timeout(
+        &self,
+        mut point: DataPoint,
+        timeout: Duration,
+    ) -> Result<Receipt, AnalyticsError> {
+        self.validation.apply(&mut point)?;
+        self.post(&self.url, self.point_payload(&point), 1, Some(timeout))
+            .await
+    }
+
+    /// Sends many data points in a single request to the bulk endpoint, or one at a time with
+    /// the 1.0 API, see [`SyncClient::send_batch`](crate::SyncClient::send_batch). Unlike the
+    /// synchronous client's, the data points are sent as they are, once validated. As with it,
+    /// an empty batch isn't sent, and gets an [`Outcome::Filtered`] receipt.
+    pub async fn send_batch(&self, points: &[DataPoint]) -> Result<Receipt, AnalyticsError> {
+        if points.is_empty() {
+            return Ok(Receipt::new("", Outcome::Filtered));
+        }
+        let mut points = points.to_vec();
+        for point in &mut points {
+            self.validation.apply(point)?;
+        }
+        let batch_url = match &self.batch_url {
+            Some(url) => url,
+            None => return self.post_each(points).await,
+        };
+        let payload = batch_payload(&points, self.value_format);
+        self.post(batch_url, payload, points.len(), None).await
+    }
+
+    /// Sends many data points like [`AsyncClient::send_batch`], unless `cancel` completes
+    /// first, see [`AsyncClient::send_point_until`]. With the 1.0 API, the points sent before
+    /// are kept by the server.
+    pub async fn send_batch_until<C: Future>(
+        &self,
+        points: &[DataPoint],
+        cancel: C,
+    ) -> Result<Receipt, AnalyticsError> {
+        until(self.send_batch(points), cancel, "cancelled").await
+    }
+
+    /// Sends many data points like [`AsyncClient::send_batch`], failing if it isn't done by
+    /// `deadline`, see [`AsyncClient::send_point_until`].
+    #[cfg(not(target_arch = "wasm32"))]
+    pub async fn send_batch_before(
+        &self,
+        points: &[DataPoint],
+        deadline: Instant,
+    ) -> Result<Receipt, AnalyticsError> {
+        until(self.send_batch(points), at(deadline), "deadline passed").await
+    }
+
+    /// Body of a request adding a single data point, in the format of the client's API version.
+    fn point_payload(&self, point: &DataPoint) -> String {
+        if self.api_version.wraps_points() {
+            batch_payload(std::slice::from_ref(point), self.value_format)
+        } else {
+            point.to_json_with(self.value_format)
+        }
+    }
+
+    /// Sends validated data points one at a time, in order, to an API without bulk endpoint,
+    /// stopping at the first failure, like the synchronous client.
+    async fn post_each(&self, points: Vec<DataPoint>) -> Result<Receipt, AnalyticsError> {
+        let payloads = points
+            .iter()
+            .map(|point| point.to_json_with(self.value_format))
+            .collect::<Vec<_>>();
+        let mut report: Option<SendReport> = None;
+        for payload in &payloads {
+            let receipt = self.post(&self.url, payload.clone(), 1, None).await?;
+            if let Outcome::Sent(sent) = receipt.outcome {
+                report = Some(match report {
+                    Some(report) => report.merge(sent),
+                    None => sent,
+                });
+            }
+        }
+        Ok(Receipt::new(
+            &payloads.join("\n"),
+            Outcome::Sent(report.unwrap_or_default()),
+        ))
+    }
+
+    /// Sends the data points of `points` as they're produced, in chunks of `chunk_size` with
+    /// [`AsyncClient::send_batch`], with up to `max_in_flight` requests at once, and returns the
+    /// result of each chunk, in order; see
+    /// [`SyncClient::send_stream_with`](crate::SyncClient::send_stream_with).
+    ///
+    /// The stream is only polled while a request slot is free.
+    pub async fn send_stream_with<St>(
+        &self,
+        points: St,
+        chunk_size: usize,
+        max_in_flight: usize,
+    ) -> Vec<Result<Receipt, AnalyticsError>>
+    where
+        St: Stream<Item = DataPoint>,
+    {
+        points
+            .chunks(chunk_size.max(1))
+            .map(|chunk| async move { self.send_batch(&chunk).await })
+            .buffered(max_in_flight.max(1))
+            .collect()
+            .await
+    }
+
+    /// Sends a large batch split into chunks of `chunk_size` data points, with up to `workers`
+    /// requests at once, and reports what happened to each chunk; see
+    /// [`SyncClient::send_batch_parallel`](crate::SyncClient::send_batch_parallel).
+    pub async fn send_batch_parallel(
+        &self,
+        points: &[DataPoint],
+        chunk_size: usize,
+        workers: usize,
+    ) -> BatchReport {
+        #[cfg(not(target_arch = "wasm32"))]
+        let started = Instant::now();
+        let chunk_size = chunk_size.max(1);
+        let results = stream::iter(points.chunks(chunk_size))
+            .map(|chunk| self.send_batch(chunk))
+            .buffered(workers.max(1))
+            .collect()
+            .await;
+        #[cfg(not(target_arch = "wasm32"))]
+        let duration = started.elapsed();
+        #[cfg(target_arch = "wasm32")]
+        let duration = Duration::default();
+        BatchReport::new(results, chunk_size, points.len(), duration)
+    }
+
+    /// Sends the data points of `points` as they're produced, in batches of 500 with up to 4
+    /// requests in flight; see [`AsyncClient::send_stream_with`].
+    pub async fn send_stream<St>(&self, points: St) -> Vec<Result<Receipt, AnalyticsError>>
+    where
+        St: Stream<Item = DataPoint>,
+    {
+        self.send_stream_with(points, DEFAULT_CHUNK_SIZE, DEFAULT_IN_FLIGHT)
+            .await
+    }
+
+    /// Posts `payload` to `url`, retrying according to the client's retry policy, if any, like
+    /// the synchronous client.
+    async fn post(
+        &self,
+        url: &str,
+        payload: String,
+        rows: usize,
+        timeout: Option<Duration>,
+    ) -> Result<Receipt, AnalyticsError> {
+        let max_attempts = self.retry.as_ref().map_or(1, RetryPolicy::max_attempts);
+        let mut attempt = 1;
+        let (status, headers, body) = loop {
+            let result = self.post_once(url, &payload, timeout).await;
+            let retry = match (&self.retry, &result) {
+                _ if attempt >= max_attempts => None,
+                (Some(policy), Err(AnalyticsError::Http(_))) => Some(policy),
+                (Some(policy), Ok((status, _, _))) if policy.retries_status(*status) => {
+                    Some(policy)
+                }
+                _ => None,
+            };
+            match retry {
+                Some(policy) => tokio::time::delay_for(policy.delay(attempt)).await,
+                None => break result?,
+            }
+            attempt += 1;
+        };
+
+        if !status.is_success() {
+            return Err(AnalyticsError::from_response(status, &headers, &body));
+        }
+
+        let report = parse_add_response(&body, rows, false)?;
+        Ok(Receipt::new(&payload, Outcome::Sent(report)))
+    }
+
+    // Requests can't time out on wasm32, where the methods taking a timeout don't exist.
+    #[cfg_attr(target_arch = "wasm32", allow(unused_variables))]
+    async fn post_once(
+        &self,
+        url: &str,
+        payload: &str,
+        timeout: Option<Duration>,
+    ) -> Result<(http::StatusCode, http::header::HeaderMap, String), AnalyticsError> {
+        if let Some(limit) = &self.rate_limit {
+            while let Some(wait) = limit.try_acquire() {
+                tokio::time::delay_for(wait).await;
+            }
+        }
+
+        let req = self
+            .credentials
+            .authorize_async(self.client.post(url))
+            .body(payload.to_string());
+        #[cfg(not(target_arch = "wasm32"))]
+        let req = match timeout {
+            Some(timeout) => req.timeout(timeout),
+            None => req,
+        };
+        let resp = req.send().await?;
+
+        let status = resp.status();
+        let headers = resp.headers().clone();
+        let body = resp.text().await?;
+        Ok((status, headers, body))
+    }
+
+    /// Sends some timeline data to the API: the `key` is the index of this piece of data,
+    /// associated to the given `value` at the given `time`, formatted with the client's
+    /// timestamp format, see [`AsyncClient::with_timestamp_format`].
+    ///
+    /// Requires the `chrono` feature.
+    #[cfg(feature = "chrono")]
+    pub async fn send_timeline_data<S: Into<String>, F: Into<f64>>(
+        &self,
+        key: S,
+        time: DateTime<Utc>,
+        value: F,
+    ) -> Result<Receipt, AnalyticsError> {
+        let time = self.time_zone.format(&self.timestamp_format, time)?;
+        self.send_data(key.into(), time, value.into()).await
+    }
+
+    /// Sends some timeline data to the API: the `key` is the index of this piece of data,
+    /// associated to the given `value` at the current UTC time.
+    ///
+    /// Requires the `chrono` feature.
+    #[cfg(feature = "chrono")]
+    pub async fn send_timeline_now_data<S: Into<String>, F: Into<f64>>(
+        &self,
+        key: S,
+        value: F,
+    ) -> Result<Receipt, AnalyticsError> {
+        self.send_timeline_data(key, Utc::now(), value).await
+    }
+}
+
+/// A builder for [`AsyncClient`], with the same HTTP settings as
+/// [`SyncClientBuilder`](crate::SyncClientBuilder).
+///
+/// Requires the `async` feature.
+pub struct AsyncClientBuilder {
+    nextcloud_url: String,
+    collection: u32,
+    user: String,
+    passwd: String,
+    credentials: Option<Credentials>,
+    #[cfg(not(target_arch = "wasm32"))]
+    timeout: Duration,
+    #[cfg(not(target_arch = "wasm32"))]
+    connect_timeout: Option<Duration>,
+    retry: Option<RetryPolicy>,
+    api_version: ApiVersion,
+    #[cfg(not(target_arch = "wasm32"))]
+    options: HttpOptions,
+    #[cfg(all(
+        any(feature = "native-tls", feature = "rustls"),
+        not(target_arch = "wasm32")
+    ))]
+    security: Security,
+}
+
+impl AsyncClientBuilder {
+    /// Version of the Analytics API to use, see
+    /// [`SyncClientBuilder::api_version`](crate::SyncClientBuilder::api_version). Defaults to
+    /// [`ApiVersion::V1`].
+    pub fn api_version(mut self, version: ApiVersion) -> Self {
+        self.api_version = version;
+        self
+    }
+
+    /// Retry requests failing because of transient errors according to the given policy, see
+    /// [`SyncClientBuilder::retry`](crate::SyncClientBuilder::retry). Disabled by default.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn retry(mut self, policy: RetryPolicy) -> Self {
+        self.retry = Some(policy);
+        self
+    }
+
+    /// Timeout for a whole request, from connection to the end of the response. Defaults to 30
+    /// seconds.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Timeout for the connection phase only.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = Some(timeout);
+        self
+    }
+
+    /// Authenticate with the given credentials, e.g. shared with synchronous clients, instead
+    /// of the user and password given to the builder, see [`Credentials`].
+    pub fn credentials(mut self, credentials: Credentials) -> Self {
+        self.credentials = Some(credentials);
+        self
+    }
+
+    /// Value of the `User-Agent` header sent with every request, instead of
+    /// `nextcloud_analytics_rs/<version>`.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn user_agent<S: Into<String>>(mut self, user_agent: S) -> Self {
+        self.options.user_agent = Some(user_agent.into());
+        self
+    }
+
+    /// Send all the requests through the proxy at the given URL, see
+    /// [`SyncClientBuilder::proxy`](crate::SyncClientBuilder::proxy).
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn proxy<S: Into<String>>(mut self, url: S) -> Self {
+        self.options.proxy = Some(url.into());
+        self
+    }
+
+    /// Reach the given hosts directly rather than through the proxy, see
+    /// [`SyncClientBuilder::no_proxy`](crate::SyncClientBuilder::no_proxy).
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn no_proxy<I, S>(mut self, hosts: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.options
+            .no_proxy
+            .extend(hosts.into_iter().map(Into::into));
+        self
+    }
+
+    /// Whether to use the proxies of the environment, see
+    /// [`SyncClientBuilder::system_proxy`](crate::SyncClientBuilder::system_proxy).
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn system_proxy(mut self, enabled: bool) -> Self {
+        self.options.ignore_system_proxy = !enabled;
+        self
+    }
+
+    /// Trust the given PEM-encoded certificate authority, in addition to the system's.
+    ///
+    /// Requires the `native-tls` or the `rustls` feature.
+    #[cfg(all(
+        any(feature = "native-tls", feature = "rustls"),
+        not(target_arch = "wasm32")
+    ))]
+    pub fn add_root_certificate(mut self, pem: &[u8]) -> Self {
+        self.options.root_certificates.push(pem.to_vec());
+        self
+    }
+
+    /// Accept any TLS certificate, even invalid or self-signed ones. See
+    /// [`SyncClientBuilder::danger_accept_invalid_certs`](crate::SyncClientBuilder::danger_accept_invalid_certs).
+    ///
+    /// Requires the `native-tls` or the `rustls` feature.
+    #[cfg(all(
+        any(feature = "native-tls", feature = "rustls"),
+        not(target_arch = "wasm32")
+    ))]
+    pub fn danger_accept_invalid_certs(mut self, accept: bool) -> Self {
+        self.options.accept_invalid_certs = accept;
+        self
+    }
+
+    /// Restrict the connections of the client, see
+    /// [`SyncClientBuilder::security`](crate::SyncClientBuilder::security). Building the
+    /// client fails if the Nextcloud URL isn't allowed by the policy.
+    ///
+    /// Requires the `native-tls` or the `rustls` feature.
+    #[cfg(all(
+        any(feature = "native-tls", feature = "rustls"),
+        not(target_arch = "wasm32")
+    ))]
+    pub fn security(mut self, security: Security) -> Self {
+        self.security = security;
+        self
+    }
+
+    /// Creates the client.
+    pub fn build(self) -> Result<AsyncClient, AnalyticsError> {
+        let mut base_url = self.nextcloud_url;
+
+        // Add trailing slash if necessary.
+        if !base_url.ends_with('/') {
+            base_url += "/";
+        }
+
+        let url = base_url.clone() + &self.api_version.add_path(self.collection);
+        let batch_url = self
+            .api_version
+            .batch_path(self.collection)
+            .map(|path| base_url.clone() + &path);
+
+        let mut headers = http::header::HeaderMap::new();
+
+        headers.insert(
+            http::header::CONTENT_TYPE,
+            http::header::HeaderValue::from_static("application/json"),
+        );
+
+        let builder = http::Client::builder().default_headers(headers);
+        // The browser handles the connections on wasm32.
+        #[cfg(not(target_arch = "wasm32"))]
+        let builder = {
+            let mut builder = builder
+                .timeout(self.timeout)
+                .user_agent(self.options.user_agent());
+            if let Some(connect_timeout) = self.connect_timeout {
+                builder = builder.connect_timeout(connect_timeout);
+            }
+            if let Some(proxy) = self.options.proxy()? {
+                builder = builder.proxy(proxy);
+            } else if self.options.ignore_system_proxy {
+                builder = builder.no_proxy();
+            }
+            #[cfg(any(feature = "native-tls", feature = "rustls"))]
+            {
+                for cert in self.options.root_certificates()? {
+                    builder = builder.add_root_certificate(cert);
+                }
+                builder = builder.danger_accept_invalid_certs(self.options.accept_invalid_certs);
+                self.security.check_url(&base_url)?;
+                builder = self.security.apply(builder, &self.options)?;
+            }
+            builder
+        };
+
+        Ok(AsyncClient {
+            client: builder.build()?,
+            url,
+            batch_url,
+            api_version: self.api_version,
+            credentials: match self.credentials {
+                Some(credentials) => credentials,
+                None => Credentials::password(self.user, self.passwd),
+            },
+            rate_limit: None,
+            retry: self.retry,
+            validation: Validation::default(),
+            value_format: ValueFormat::default(),
+            #[cfg(feature = "chrono")]
+            timestamp_format: TimestampFormat::default(),
+            #[cfg(feature = "chrono")]
+            time_zone: DatasetTimeZone::default(),
+        })
+    }
+}
+
+/// Runs `call` unless `cancel` completes first, failing with `reason` then.
+async fn until<T, F, C>(call: F, cancel: C, reason: &str) -> Result<T, AnalyticsError>
+where
+    F: Future<Output = Result<T, AnalyticsError>>,
+    C: Future,
+{
+    futures_util::pin_mut!(call, cancel);
+    match future::select(call, cancel).await {
+        Either::Left((result, _)) => result,
+        Either::Right(_) => Err(AnalyticsError::local(format!(
+            "{} before the request completed",
+            reason
+        ))),
+    }
+}
+
+/// A future completing at `deadline`.
+#[cfg(not(target_arch = "wasm32"))]
+fn at(deadline: Instant) -> impl Future<Output = ()> {
+    tokio::time::delay_until(tokio::time::Instant::from_std(deadline))
+}
@@ -0,0 +1,40 @@
+//! Provenance notes of data loads.
+//!
+//! Analytics' data API has no field for a note or comment on the rows it receives: notes are
+//! recorded as rows of an audit key instead, which shows up in the data maintenance view like
+//! any other key.
+
+use crate::{AnalyticsError, DataPoint, Receipt, SyncClient};
+
+impl SyncClient {
+    /// Record the notes of [`SyncClient::send_with_metadata`] as rows of the given key, e.g.
+    /// `"_audit"`.
+    pub fn with_audit_key<S: Into<String>>(mut self, key: S) -> Self {
+        self.audit_key = Some(key.into());
+        self
+    }
+
+    /// Sends many data points like [`SyncClient::send_batch`], along with a free-form note on
+    /// their provenance, e.g. `"backfilled from CSV v3"`.
+    ///
+    /// The note is the second dimension of a row of the [audit key](SyncClient::with_audit_key),
+    /// whose value is the number of data points, sent in the same request: the server accepts or
+    /// rejects both together. The audit row goes through the key filter and the key template
+    /// like the others.
+    ///
+    /// Fails without any request if the client has no audit key.
+    pub fn send_with_metadata<S: Into<String>>(
+        &self,
+        points: &[DataPoint],
+        note: S,
+    ) -> Result<Receipt, AnalyticsError> {
+        let key = self.audit_key.clone().ok_or_else(|| {
+            AnalyticsError::local("the client has no audit key to record the note")
+        })?;
+
+        let mut points = points.to_vec();
+        let count = points.len() as f64;
+        points.push(DataPoint::new(key, note.into(), count));
+        self.send_batch(&points)
+    }
+}
@@ -0,0 +1,166 @@
+use std::thread;
+use std::time::Instant;
+
+use chrono::{DateTime, Duration, NaiveTime, Timelike, Utc};
+
+use crate::progress::Tracker;
+use crate::{AnalyticsError, BulkSummary, DataPoint, Progress, SyncClient};
+
+const SECONDS_PER_DAY: i64 = 24 * 60 * 60;
+
+/// A schedule for large imports, only sending during the configured windows of the day, e.g. at
+/// night, so that multi-day backfills don't slow the Nextcloud instance down during working
+/// hours.
+///
+/// Requires the `chrono` feature.
+#[derive(Clone, Debug)]
+pub struct BackfillPlan {
+    /// Start and end of each window, in seconds since midnight UTC.
+    windows: Vec<(i64, i64)>,
+    batch_size: usize,
+}
+
+impl Default for BackfillPlan {
+    fn default() -> Self {
+        Self {
+            windows: Vec::new(),
+            batch_size: 500,
+        }
+    }
+}
+
+impl BackfillPlan {
+    /// A plan sending batches of 500 data points at any time, until windows are added.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Allow sending between `start` and `end`, in UTC. The window wraps around midnight if
+    /// `end` is before `start`, e.g. from 23:00 to 05:00.
+    pub fn window(mut self, start: NaiveTime, end: NaiveTime) -> Self {
+        self.windows.push((
+            i64::from(start.num_seconds_from_midnight()),
+            i64::from(end.num_seconds_from_midnight()),
+        ));
+        self
+    }
+
+    /// Number of data points sent per request.
+    pub fn batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    /// Whether sending is allowed at `time`.
+    pub fn is_open(&self, time: DateTime<Utc>) -> bool {
+        let now = i64::from(time.num_seconds_from_midnight());
+        self.windows.is_empty()
+            || self.windows.iter().any(|&(start, end)| {
+                if start <= end {
+                    start <= now && now < end
+                } else {
+                    now >= start || now < end
+                }
+            })
+    }
+
+    /// How long to wait from `time` until sending is allowed, zero if it already is.
+    pub fn wait_time(&self, time: DateTime<Utc>) -> Duration {
+        if self.is_open(time) {
+            return Duration::zero();
+        }
+        let now = i64::from(time.num_seconds_from_midnight());
+        let wait = self
+            .windows
+            .iter()
+            .map(|&(start, _)| (start - now).rem_euclid(SECONDS_PER_DAY))
+            .min()
+            .unwrap_or(0);
+        Duration::seconds(wait)
+    }
+
+    /// Sends all the `points` with [`SyncClient::send_batch`], pausing outside of the windows.
+    ///
+    /// The time of day is the server's if [`SyncClient::with_server_time`] has been enabled. This
+    /// blocks until all the points are sent, possibly for days, and stops at the first failed
+    /// batch; the batches before it were sent.
+    ///
+    /// Returns the number of data points sent.
+    pub fn run<I>(&self, client: &SyncClient, points: I) -> Result<usize, AnalyticsError>
+    where
+        I: IntoIterator<Item = DataPoint>,
+    {
+        self.run_with_progress(client, points, |_| {})
+    }
+
+    /// Like [`BackfillPlan::run`], calling `progress` with the rows sent so far after every
+    /// batch. Their total, and thus an [ETA](Progress::eta), is known if the iterator of the
+    /// points knows its exact length, e.g. for a `Vec`; the ETA includes the pauses outside of
+    /// the windows at the average rate so far.
+    pub fn run_with_progress<I, F>(
+        &self,
+        client: &SyncClient,
+        points: I,
+        progress: F,
+    ) -> Result<usize, AnalyticsError>
+    where
+        I: IntoIterator<Item = DataPoint>,
+        F: FnMut(&Progress),
+    {
+        let start = Instant::now();
+        let result = self.send_all(client, points.into_iter(), progress);
+
+        let mut summary = BulkSummary::new("backfill");
+        match &result {
+            Ok(sent) => summary.added = *sent as u64,
+            Err((sent, _)) => {
+                summary.added = *sent as u64;
+                summary.failures = 1;
+            }
+        }
+        summary.duration = start.elapsed();
+        client.report_summary(&summary);
+
+        result.map_err(|(_, err)| err)
+    }
+
+    /// Sends the points, returning the number of points sent so far along with the error.
+    fn send_all<I, F>(
+        &self,
+        client: &SyncClient,
+        points: I,
+        mut progress: F,
+    ) -> Result<usize, (usize, AnalyticsError)>
+    where
+        I: Iterator<Item = DataPoint>,
+        F: FnMut(&Progress),
+    {
+        let total = match points.size_hint() {
+            (lower, Some(upper)) if lower == upper => Some(upper as u64),
+            _ => None,
+        };
+        let tracker = Tracker::new(total, None);
+        let mut points = points.peekable();
+        let mut sent = 0;
+
+        while points.peek().is_some() {
+            loop {
+                let wait = self.wait_time(client.now());
+                if wait <= Duration::zero() {
+                    break;
+                }
+                // The clock may have been adjusted in the meantime, so check again after waking.
+                thread::sleep(wait.to_std().unwrap_or_default());
+            }
+
+            let batch: Vec<DataPoint> = points.by_ref().take(self.batch_size).collect();
+            if let Err(err) = client.send_batch(&batch) {
+                return Err((sent, err));
+            }
+            sent += batch.len();
+            progress(&tracker.progress(sent as u64, 0));
+        }
+
+        Ok(sent)
+    }
+}
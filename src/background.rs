@@ -0,0 +1,544 @@
+use std::collections::VecDeque;
+use std::error::Error;
+use std::fmt;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::{AnalyticsError, DataPoint, SyncClient};
+
+/// Maximum number of data points sent in a single batch.
+const MAX_BATCH: usize = 500;
+
+/// Adjusts the batch size and flush interval of a [`BackgroundSender`] to the server, so that
+/// small instances aren't overwhelmed without tuning, see [`SenderOptions::adaptive`].
+///
+/// The sender starts with small batches, and grows them by the minimum batch size after each
+/// send that succeeds within the target latency. A failed or slower send halves the batch size
+/// and doubles the flush interval, up to the maximum; successful sends bring the interval back
+/// to the one given to the sender.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AdaptiveBatching {
+    target_latency: Duration,
+    min_batch: usize,
+    max_batch: usize,
+    max_flush_interval: Option<Duration>,
+}
+
+impl AdaptiveBatching {
+    /// Adapts the batches so that sending one takes at most `target_latency`, with batches of
+    /// 10 to 500 points, and flush intervals of up to 8 times the sender's.
+    pub fn new(target_latency: Duration) -> Self {
+        Self {
+            target_latency,
+            min_batch: 10,
+            max_batch: MAX_BATCH,
+            max_flush_interval: None,
+        }
+    }
+
+    /// Bounds of the batch size.
+    pub fn batch_size(mut self, min: usize, max: usize) -> Self {
+        self.min_batch = min.max(1);
+        self.max_batch = max.max(self.min_batch);
+        self
+    }
+
+    /// Longest flush interval, when the server is struggling.
+    pub fn max_flush_interval(mut self, interval: Duration) -> Self {
+        self.max_flush_interval = Some(interval);
+        self
+    }
+}
+
+/// Current batch size and flush interval of a sender.
+struct Sizing {
+    adaptive: Option<AdaptiveBatching>,
+    batch_size: Arc<AtomicUsize>,
+    base_interval: Duration,
+    interval: Duration,
+}
+
+impl Sizing {
+    fn batch_size(&self) -> usize {
+        self.batch_size.load(Ordering::Relaxed)
+    }
+
+    /// Sends the points of `batch`, in batches of the current size, counting what happened to
+    /// them in `stats`. Stops at the first batch failing with a transient error, and returns
+    /// the points from there, to send them again.
+    fn send_all(
+        &mut self,
+        client: &SyncClient,
+        mut batch: Vec<Queued>,
+        stats: &mut SenderStats,
+    ) -> Vec<Queued> {
+        while !batch.is_empty() {
+            let len = self.batch_size().min(batch.len());
+            let chunk: Vec<_> = batch[..len].iter().map(|q| q.point.clone()).collect();
+            let start = Instant::now();
+            let result = client.send_batch(&chunk);
+            self.observe(start.elapsed(), result.is_ok());
+            match result {
+                Ok(_) => stats.sent += len as u64,
+                Err(err) if err.is_transient() => {
+                    stats.retries += 1;
+                    return batch;
+                }
+                Err(_) => stats.failed += len as u64,
+            }
+            batch.drain(..len);
+        }
+        batch
+    }
+
+    fn observe(&mut self, latency: Duration, sent: bool) {
+        let adaptive = match self.adaptive {
+            Some(adaptive) => adaptive,
+            None => return,
+        };
+        let size = self.batch_size();
+        let max_interval = adaptive
+            .max_flush_interval
+            .unwrap_or_else(|| self.base_interval * 8)
+            .max(self.base_interval);
+
+        let size = if sent && latency <= adaptive.target_latency {
+            self.interval = (self.interval / 2).max(self.base_interval);
+            size + adaptive.min_batch
+        } else {
+            self.interval = (self.interval * 2).min(max_interval);
+            size / 2
+        };
+        self.batch_size.store(
+            size.max(adaptive.min_batch).min(adaptive.max_batch),
+            Ordering::Relaxed,
+        );
+    }
+}
+
+/// What a [`BackgroundSender`] does with a new point when its queue is full, see
+/// [`SenderOptions::overflow`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Overflow {
+    /// Refuse the new point, giving it back with [`EnqueueError::Full`]. The default.
+    #[default]
+    DropNewest,
+    /// Drop the oldest waiting point to make room, e.g. for live readings where only the
+    /// latest ones matter.
+    DropOldest,
+    /// Wait for room, blocking the caller until the worker takes the next batch, e.g. for a
+    /// backfill which mustn't lose any point.
+    Block,
+}
+
+/// Settings of a [`BackgroundSender`], see [`BackgroundSender::start_with`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SenderOptions {
+    capacity: usize,
+    flush_interval: Duration,
+    overflow: Overflow,
+    ttl: Option<Duration>,
+    adaptive: Option<AdaptiveBatching>,
+}
+
+impl SenderOptions {
+    /// Room for `capacity` waiting points, at least one, sent at least every `flush_interval`,
+    /// in batches of up to 500 points; new points are refused when the queue is full.
+    pub fn new(capacity: usize, flush_interval: Duration) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            flush_interval,
+            overflow: Overflow::default(),
+            ttl: None,
+            adaptive: None,
+        }
+    }
+
+    /// Drop the points which couldn't be sent within `ttl` of being queued, e.g. live readings
+    /// which are useless later, instead of sending them again until they are. Applies to the
+    /// points queued without their own TTL, see [`SenderHandle::enqueue_point_with_ttl`].
+    pub fn ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = Some(ttl);
+        self
+    }
+
+    /// What to do with new points when the queue is full.
+    pub fn overflow(mut self, overflow: Overflow) -> Self {
+        self.overflow = overflow;
+        self
+    }
+
+    /// Adjust the batch size and the flush interval to the latency and errors of the server.
+    pub fn adaptive(mut self, batching: AdaptiveBatching) -> Self {
+        self.adaptive = Some(batching);
+        self
+    }
+}
+
+/// Counters of a [`BackgroundSender`], see [`BackgroundSender::stats`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SenderStats {
+    /// Number of points waiting in the queue.
+    pub queued: usize,
+    /// Number of points sent successfully.
+    pub sent: u64,
+    /// Number of points dropped because the queue was full: refused with
+    /// [`Overflow::DropNewest`], or evicted with [`Overflow::DropOldest`].
+    pub dropped: u64,
+    /// Number of points dropped because their TTL expired before they could be sent.
+    pub expired: u64,
+    /// Number of points dropped because the server rejected their batch, or because it
+    /// couldn't be reached for the last flush, when the sender stopped.
+    pub failed: u64,
+    /// Number of batches which failed with a transient error, and were kept to be sent again.
+    pub retries: u64,
+}
+
+/// The queue and counters shared by a sender, its worker and its handles.
+struct Shared {
+    queue: Mutex<Queue>,
+    /// Notified when points are queued, or the sender stops.
+    filled: Condvar,
+    /// Notified when the worker takes points from the queue, or the sender stops.
+    drained: Condvar,
+    capacity: usize,
+    overflow: Overflow,
+    ttl: Option<Duration>,
+}
+
+#[derive(Default)]
+struct Queue {
+    points: VecDeque<Queued>,
+    stopped: bool,
+    stats: SenderStats,
+}
+
+impl Queue {
+    /// Drops the points whose TTL expired.
+    fn expire(&mut self, now: Instant) {
+        let len = self.points.len();
+        self.points.retain(|queued| !queued.expired(now));
+        self.stats.expired += (len - self.points.len()) as u64;
+    }
+}
+
+/// A point waiting in the queue, with when it stops being worth sending, if ever.
+struct Queued {
+    point: DataPoint,
+    expires: Option<Instant>,
+}
+
+impl Queued {
+    fn expired(&self, now: Instant) -> bool {
+        self.expires.is_some_and(|expires| expires <= now)
+    }
+}
+
+impl Shared {
+    fn stats(&self) -> SenderStats {
+        let queue = self.queue.lock().unwrap();
+        SenderStats {
+            queued: queue.points.len(),
+            ..queue.stats
+        }
+    }
+}
+
+/// Sends data points from a worker thread, so that recording a metric never blocks on the
+/// network.
+///
+/// Points are queued with a [`SenderHandle`], and sent with [`SyncClient::send_batch`] every
+/// flush interval, or as soon as 500 of them are waiting, unless the batches are adapted to the
+/// server with [`SenderOptions::adaptive`]. The queue is bounded: when it's full, new points are
+/// refused rather than blocking the caller, unless another [`Overflow`] policy is set. What was
+/// sent or dropped is counted, see [`BackgroundSender::stats`].
+///
+/// A batch failing with a transient error, as the server can't be reached, is sent again with
+/// the next flush, before the points queued since, and so on until its points expire, if they
+/// have a TTL, see [`SenderOptions::ttl`]; with the 1.0 API, which sends the points of a batch
+/// one by one, its points sent before the failure are sent again too, which the server counts
+/// as updates of the same rows. A batch the server rejects is dropped. Failures are
+/// counted, and reported through the client's callbacks and failure notifier, see
+/// [`SyncClient::on_error`]. Stopping the sender, or dropping it, sends the points still in the
+/// queue first, once.
+///
+/// A single worker sends the batches one after the other, in the order their points were
+/// queued, a batch sent again included: the points of a key are never reordered, as long as
+/// they're queued in order. To send several keys at the same time, start a sender for each.
+pub struct BackgroundSender {
+    shared: Arc<Shared>,
+    #[cfg(feature = "chrono")]
+    client: Arc<SyncClient>,
+    batch_size: Arc<AtomicUsize>,
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+/// A cheap handle to queue data points for a [`BackgroundSender`], which can be cloned and
+/// moved to other threads.
+#[derive(Clone)]
+pub struct SenderHandle {
+    shared: Arc<Shared>,
+    #[cfg(feature = "chrono")]
+    client: Arc<SyncClient>,
+}
+
+impl BackgroundSender {
+    /// Starts a worker thread sending the points queued for `client`, with room for `capacity`
+    /// waiting points, and sending them at least every `flush_interval`.
+    pub fn start(client: Arc<SyncClient>, capacity: usize, flush_interval: Duration) -> Self {
+        Self::start_with(client, SenderOptions::new(capacity, flush_interval))
+    }
+
+    /// Like [`BackgroundSender::start`], adjusting the batch size and the flush interval to the
+    /// latency and errors of the server.
+    pub fn start_adaptive(
+        client: Arc<SyncClient>,
+        capacity: usize,
+        flush_interval: Duration,
+        batching: AdaptiveBatching,
+    ) -> Self {
+        Self::start_with(
+            client,
+            SenderOptions::new(capacity, flush_interval).adaptive(batching),
+        )
+    }
+
+    /// Starts a worker thread sending the points queued for `client`, with the given settings.
+    pub fn start_with(client: Arc<SyncClient>, options: SenderOptions) -> Self {
+        let shared = Arc::new(Shared {
+            queue: Mutex::default(),
+            filled: Condvar::new(),
+            drained: Condvar::new(),
+            capacity: options.capacity,
+            overflow: options.overflow,
+            ttl: options.ttl,
+        });
+        let batch_size = Arc::new(AtomicUsize::new(
+            options
+                .adaptive
+                .map_or(MAX_BATCH, |adaptive| adaptive.min_batch),
+        ));
+
+        let worker_client = Arc::clone(&client);
+        let worker_shared = Arc::clone(&shared);
+        let mut sizing = Sizing {
+            adaptive: options.adaptive,
+            batch_size: Arc::clone(&batch_size),
+            base_interval: options.flush_interval,
+            interval: options.flush_interval,
+        };
+        let thread = thread::spawn(move || {
+            let (client, shared) = (worker_client, worker_shared);
+            let mut next_flush = Instant::now() + options.flush_interval;
+            // Whether the last flush failed: the next one then waits for the flush interval,
+            // however many points are waiting.
+            let mut retrying = false;
+            loop {
+                let mut queue = shared.queue.lock().unwrap();
+                loop {
+                    let now = Instant::now();
+                    if queue.stopped
+                        || (!retrying && queue.points.len() >= sizing.batch_size())
+                        || now >= next_flush
+                    {
+                        break;
+                    }
+                    queue = shared
+                        .filled
+                        .wait_timeout(queue, next_flush - now)
+                        .unwrap()
+                        .0;
+                }
+
+                // Once stopped, no point is queued anymore: this is the last batch.
+                let stop = queue.stopped;
+                queue.expire(Instant::now());
+                let batch: Vec<_> = queue.points.drain(..).collect();
+                let mut stats = queue.stats;
+                drop(queue);
+                shared.drained.notify_all();
+
+                let unsent = sizing.send_all(&client, batch, &mut stats);
+                retrying = !unsent.is_empty();
+                let mut queue = shared.queue.lock().unwrap();
+                // Only the worker changes these counters.
+                queue.stats.sent = stats.sent;
+                queue.stats.failed = stats.failed;
+                queue.stats.retries = stats.retries;
+                if stop {
+                    queue.stats.failed += unsent.len() as u64;
+                    break;
+                }
+                for queued in unsent.into_iter().rev() {
+                    queue.points.push_front(queued);
+                }
+                drop(queue);
+                next_flush = Instant::now() + sizing.interval;
+            }
+        });
+
+        Self {
+            shared,
+            #[cfg(feature = "chrono")]
+            client,
+            batch_size,
+            thread: Some(thread),
+        }
+    }
+
+    /// Current maximum number of points per batch.
+    pub fn batch_size(&self) -> usize {
+        self.batch_size.load(Ordering::Relaxed)
+    }
+
+    /// What the sender queued, sent and dropped so far.
+    pub fn stats(&self) -> SenderStats {
+        self.shared.stats()
+    }
+
+    /// A handle to queue points.
+    pub fn handle(&self) -> SenderHandle {
+        SenderHandle {
+            shared: Arc::clone(&self.shared),
+            #[cfg(feature = "chrono")]
+            client: Arc::clone(&self.client),
+        }
+    }
+
+    /// Stops the worker thread, waiting for the remaining points to be sent. Same as dropping
+    /// the sender.
+    pub fn stop(self) {}
+}
+
+impl Drop for BackgroundSender {
+    fn drop(&mut self) {
+        self.shared.queue.lock().unwrap().stopped = true;
+        self.shared.filled.notify_all();
+        self.shared.drained.notify_all();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl SenderHandle {
+    /// Queues a data point, or gives it back if the sender stopped, or if the queue is full
+    /// with [`Overflow::DropNewest`].
+    pub fn enqueue_point(&self, point: DataPoint) -> Result<(), EnqueueError> {
+        self.push(point, self.shared.ttl)
+    }
+
+    /// Queues a data point like [`SenderHandle::enqueue_point`], dropping it if it can't be
+    /// sent within `ttl`, instead of the sender's TTL, if any.
+    pub fn enqueue_point_with_ttl(
+        &self,
+        point: DataPoint,
+        ttl: Duration,
+    ) -> Result<(), EnqueueError> {
+        self.push(point, Some(ttl))
+    }
+
+    fn push(&self, point: DataPoint, ttl: Option<Duration>) -> Result<(), EnqueueError> {
+        let shared = &self.shared;
+        let now = Instant::now();
+        let mut queue = shared.queue.lock().unwrap();
+        loop {
+            if queue.stopped {
+                return Err(EnqueueError::Stopped(point));
+            }
+            if queue.points.len() >= shared.capacity {
+                // Make room first with points that wouldn't be sent anyway.
+                queue.expire(now);
+            }
+            if queue.points.len() < shared.capacity {
+                break;
+            }
+            match shared.overflow {
+                Overflow::DropNewest => {
+                    queue.stats.dropped += 1;
+                    return Err(EnqueueError::Full(point));
+                }
+                Overflow::DropOldest => {
+                    queue.points.pop_front();
+                    queue.stats.dropped += 1;
+                }
+                Overflow::Block => queue = shared.drained.wait(queue).unwrap(),
+            }
+        }
+        queue.points.push_back(Queued {
+            point,
+            expires: ttl.map(|ttl| now + ttl),
+        });
+        drop(queue);
+        shared.filled.notify_one();
+        Ok(())
+    }
+
+    /// What the sender queued, sent and dropped so far.
+    pub fn stats(&self) -> SenderStats {
+        self.shared.stats()
+    }
+
+    /// Queues the `value` of the timeline `key` at the current time, as with
+    /// [`SyncClient::send_timeline_now_data`]. The time is the one of this call, not the one of
+    /// the actual send.
+    ///
+    /// Fails without queueing anything if the time can't be formatted, see
+    /// [`TimestampFormat::format`](crate::TimestampFormat::format).
+    ///
+    /// Requires the `chrono` feature.
+    #[cfg(feature = "chrono")]
+    pub fn enqueue<S: Into<String>, F: Into<f64>>(
+        &self,
+        key: S,
+        value: F,
+    ) -> Result<(), EnqueueError> {
+        let time = self
+            .client
+            .format_time(self.client.now())
+            .map_err(EnqueueError::Time)?;
+        self.enqueue_point(DataPoint::new(key.into(), time, value.into()))
+    }
+}
+
+/// A data point couldn't be queued for a [`BackgroundSender`].
+#[derive(Debug)]
+pub enum EnqueueError {
+    /// The queue is full: the point is given back.
+    Full(DataPoint),
+    /// The sender stopped: the point is given back.
+    Stopped(DataPoint),
+    /// The time of a point queued with [`SenderHandle::enqueue`] couldn't be formatted.
+    Time(AnalyticsError),
+}
+
+impl EnqueueError {
+    /// The data point that wasn't queued, if it was given back.
+    pub fn into_point(self) -> Option<DataPoint> {
+        match self {
+            EnqueueError::Full(point) | EnqueueError::Stopped(point) => Some(point),
+            EnqueueError::Time(_) => None,
+        }
+    }
+}
+
+impl fmt::Display for EnqueueError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EnqueueError::Full(_) => write!(f, "the queue of the background sender is full"),
+            EnqueueError::Stopped(_) => write!(f, "the background sender stopped"),
+            EnqueueError::Time(err) => write!(f, "invalid time: {}", err),
+        }
+    }
+}
+
+impl Error for EnqueueError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            EnqueueError::Time(err) => Some(err),
+            _ => None,
+        }
+    }
+}
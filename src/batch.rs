@@ -0,0 +1,322 @@
+#[cfg(all(feature = "blocking", feature = "compression"))]
+use std::sync::atomic::Ordering;
+
+#[cfg(all(feature = "blocking", feature = "chrono"))]
+use chrono::{DateTime, Utc};
+#[cfg(feature = "blocking")]
+use reqwest as http;
+use serde::Serialize;
+
+#[cfg(feature = "blocking")]
+use crate::dedupe::Dedupe;
+#[cfg(feature = "blocking")]
+use crate::{parse_add_response, trace, AnalyticsError, Outcome, Receipt, SendReport, SyncClient};
+use crate::{DataPoint, ValueFormat};
+
+#[cfg(feature = "blocking")]
+impl SyncClient {
+    /// Sends many data points in a single request, which is much faster than one request per
+    /// point for backfills.
+    ///
+    /// This uses the bulk endpoint of the API the client is configured for. The 1.0 API has
+    /// none: the points are then sent one request at a time, in order, stopping at the first
+    /// failure; the points sent before it are kept by the server.
+    ///
+    /// The data points go through the same key filter, key template and enrichments as with
+    /// [`SyncClient::send_point`], then duplicates are resolved according to
+    /// [`SyncClient::with_duplicate_policy`]; if none passes the filter, nothing is sent. With
+    /// the 2.0 and 3.0 APIs, the server accepts or rejects the batch as a whole.
+    pub fn send_batch(&self, points: &[DataPoint]) -> Result<Receipt, AnalyticsError> {
+        self.send_batch_to(points, self.collection)
+    }
+
+    /// Sends many data points to the given collection in a single request.
+    pub(crate) fn send_batch_to(
+        &self,
+        points: &[DataPoint],
+        collection: u32,
+    ) -> Result<Receipt, AnalyticsError> {
+        self.check_writable()?;
+        let mut prepared = Vec::with_capacity(points.len());
+        let mut claims = self.dedupe.as_ref().map(Dedupe::claims);
+        let mut duplicates = false;
+        for point in points {
+            let mut point = point.clone();
+            self.default_dimensions.fill(&mut point, collection);
+            if !self.allows_key(&point.dimension1) {
+                continue;
+            }
+            if let Some(claims) = &mut claims {
+                if !claims.claim(&point, collection) {
+                    duplicates = true;
+                    continue;
+                }
+            }
+            prepared.push(self.prepare_point(point)?);
+        }
+        let prepared = self
+            .duplicate_policy
+            .resolve(prepared)
+            .map_err(|err| self.redactions.error(err))?;
+
+        if prepared.is_empty() {
+            let outcome = if duplicates {
+                Outcome::Duplicate
+            } else {
+                Outcome::Filtered
+            };
+            return Ok(Receipt::new("", outcome));
+        }
+
+        let payload = self.points_payload(&prepared, collection);
+        if self.dry_run {
+            return Ok(self.dry_run_receipt(payload, collection));
+        }
+        let result = self.send_or_queue(&prepared, collection, &payload, || {
+            let trace = trace::SendTrace::start(collection, prepared.len());
+            let (collection, result) = self.send_provisioned(collection, |collection| {
+                self.delete_before_insert(&prepared, collection)
+                    .and_then(|()| self.post_points(&prepared, &payload, collection))
+                    .map(|report| Receipt::new(&payload, Outcome::Sent(report)))
+                    .map_err(|err| self.redactions.error(err))
+            });
+            trace.finish(&result, self);
+            self.track_delivery(&prepared, collection, &result);
+            result
+        });
+        if let (Some(claims), true) = (claims, result.as_ref().is_ok_and(Receipt::was_sent)) {
+            claims.keep();
+        }
+        result
+    }
+
+    /// The `(dimension1, dimension2)` rows which sending `points` to `collection` stores, after
+    /// the default dimensions, key filter, key template and enrichments, e.g. to delete them.
+    pub(crate) fn stored_rows(
+        &self,
+        points: &[DataPoint],
+        collection: u32,
+    ) -> Result<Vec<(String, String)>, AnalyticsError> {
+        let mut rows = Vec::with_capacity(points.len());
+        for point in points {
+            let mut point = point.clone();
+            self.default_dimensions.fill(&mut point, collection);
+            if self.allows_key(&point.dimension1) {
+                let point = self.prepare_point(point)?;
+                rows.push((point.dimension1, point.dimension2));
+            }
+        }
+        Ok(rows)
+    }
+
+    /// The body of the requests sending prepared data points to `collection`: the body of the
+    /// bulk endpoint, or the bodies of each point, one per line, with the 1.0 API.
+    fn points_payload(&self, points: &[DataPoint], collection: u32) -> String {
+        match self.api_version.batch_path(collection) {
+            Some(_) => self.batch_payload(points),
+            None => points
+                .iter()
+                .map(|point| self.point_payload(point))
+                .collect::<Vec<_>>()
+                .join("\n"),
+        }
+    }
+
+    /// Posts prepared data points, with their body, to the bulk endpoint of `collection`, or
+    /// one at a time to the 1.0 API, which has none. The points sent before a failing one are
+    /// then stored by the server.
+    fn post_points(
+        &self,
+        points: &[DataPoint],
+        payload: &str,
+        collection: u32,
+    ) -> Result<SendReport, AnalyticsError> {
+        if self.api_version.batch_path(collection).is_some() {
+            return self.post_batch(
+                payload.to_string(),
+                self.payload_content_type(),
+                collection,
+                points.len(),
+            );
+        }
+
+        let mut report: Option<SendReport> = None;
+        for point in points {
+            let sent = self.post_data(self.point_payload(point), collection)?;
+            report = Some(match report {
+                Some(report) => report.merge(sent),
+                None => sent,
+            });
+        }
+        Ok(report.unwrap_or_default())
+    }
+
+    /// Posts a body of `content_type` with `rows` rows to the bulk endpoint of `collection`.
+    ///
+    /// Fails with the 1.0 API, which has no bulk endpoint.
+    pub(crate) fn post_batch(
+        &self,
+        data: String,
+        content_type: &str,
+        collection: u32,
+        rows: usize,
+    ) -> Result<SendReport, AnalyticsError> {
+        let path = self.api_version.batch_path(collection).ok_or_else(|| {
+            AnalyticsError::local("the 1.0 API can't send batches: use the 2.0 or 3.0 API")
+        })?;
+        let url = self.base_url.clone() + &path;
+
+        #[cfg(feature = "compression")]
+        if let Some(compression) = self.compression {
+            if data.len() >= MIN_COMPRESSED && !self.compression_rejected.load(Ordering::Relaxed) {
+                let req = self
+                    .client
+                    .post(&url)
+                    .header(http::header::CONTENT_ENCODING, compression.name())
+                    .header(http::header::CONTENT_TYPE, content_type)
+                    .body(compression.compress(data.as_bytes()));
+                match self
+                    .send_request(req)
+                    .and_then(|body| parse_add_response(&body, rows, self.strict))
+                {
+                    Err(err) if rejects_compression(&err) => {
+                        self.compression_rejected.store(true, Ordering::Relaxed);
+                    }
+                    result => return result,
+                }
+            }
+        }
+
+        let req = self
+            .client
+            .post(&url)
+            .header(http::header::CONTENT_TYPE, content_type)
+            .body(data);
+        let body = self.send_request(req)?;
+        parse_add_response(&body, rows, self.strict)
+    }
+}
+
+/// Timeline data points sharing the same time, captured when the batch is created, e.g. metrics
+/// from the same sample, so that they land in the same bucket of a timeline report; see
+/// [`SyncClient::timeline_batch`].
+///
+/// Requires the `chrono` feature.
+#[cfg(all(feature = "blocking", feature = "chrono"))]
+pub struct TimelineBatch<'a> {
+    client: &'a SyncClient,
+    time: DateTime<Utc>,
+    /// The time in the client's timestamp format, or why it can't be formatted, reported when
+    /// sending.
+    formatted: Result<String, AnalyticsError>,
+    points: Vec<DataPoint>,
+}
+
+#[cfg(all(feature = "blocking", feature = "chrono"))]
+impl TimelineBatch<'_> {
+    /// Adds the `value` of `key` to the batch.
+    pub fn add<S: Into<String>, F: Into<f64>>(&mut self, key: S, value: F) -> &mut Self {
+        let time = self.formatted.as_ref().cloned().unwrap_or_default();
+        self.points
+            .push(DataPoint::new(key.into(), time, value.into()));
+        self
+    }
+
+    /// The time shared by all the points of the batch.
+    pub fn time(&self) -> DateTime<Utc> {
+        self.time
+    }
+
+    /// Sends all the points in a single request, with [`SyncClient::send_batch`].
+    ///
+    /// Fails if the time can't be formatted, see
+    /// [`TimestampFormat::format`](crate::TimestampFormat::format).
+    pub fn send(self) -> Result<Receipt, AnalyticsError> {
+        self.formatted?;
+        self.client.send_batch(&self.points)
+    }
+}
+
+#[cfg(all(feature = "blocking", feature = "chrono"))]
+impl SyncClient {
+    /// Starts a batch of timeline data points all stamped with the current time, as with
+    /// [`SyncClient::send_timeline_now_data`].
+    ///
+    /// Requires the `chrono` feature.
+    pub fn timeline_batch(&self) -> TimelineBatch<'_> {
+        self.timeline_batch_at(self.now())
+    }
+
+    /// Starts a batch of timeline data points all stamped with the given time, truncated to the
+    /// resolution set with [`SyncClient::align_to`], if any.
+    ///
+    /// Requires the `chrono` feature.
+    pub fn timeline_batch_at(&self, time: DateTime<Utc>) -> TimelineBatch<'_> {
+        let time = self.aligned(time);
+        TimelineBatch {
+            time,
+            formatted: self.format_time(time),
+            client: self,
+            points: Vec::new(),
+        }
+    }
+}
+
+/// Size of the smallest body worth compressing.
+#[cfg(all(feature = "blocking", feature = "compression"))]
+const MIN_COMPRESSED: usize = 1024;
+
+/// Whether a failure to send a compressed body comes from the server not decompressing it:
+/// `415 Unsupported Media Type`, or a `400 Bad Request` without any explanation, as PHP answers
+/// when it can't read the body at all. Other errors are about the data itself, and are
+/// returned as they are.
+#[cfg(all(feature = "blocking", feature = "compression"))]
+fn rejects_compression(err: &AnalyticsError) -> bool {
+    match err {
+        AnalyticsError::Api { status, message } => match status.as_u16() {
+            415 => true,
+            400 => message
+                .split_once('\n')
+                .is_none_or(|(_, body)| body.trim().is_empty()),
+            _ => false,
+        },
+        _ => false,
+    }
+}
+
+/// Body of a request to the bulk endpoint, with values formatted according to `format`.
+pub(crate) fn batch_payload(points: &[DataPoint], format: ValueFormat) -> String {
+    let data = points
+        .iter()
+        .map(|point| BatchRow {
+            dimension1: &point.dimension1,
+            dimension2: &point.dimension2,
+            value: if format == ValueFormat::Number && point.dimension3.is_finite() {
+                BatchValue::Number(point.dimension3)
+            } else {
+                BatchValue::String(point.dimension3.to_string())
+            },
+        })
+        .collect();
+    // Serializing strings and finite numbers can't fail.
+    serde_json::to_string(&BatchPayload { data }).unwrap_or_default()
+}
+
+#[derive(Serialize)]
+struct BatchPayload<'a> {
+    data: Vec<BatchRow<'a>>,
+}
+
+#[derive(Serialize)]
+struct BatchRow<'a> {
+    dimension1: &'a str,
+    dimension2: &'a str,
+    value: BatchValue,
+}
+
+#[derive(Serialize)]
+#[serde(untagged)]
+enum BatchValue {
+    Number(f64),
+    String(String),
+}
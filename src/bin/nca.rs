@@ -0,0 +1,803 @@
+//! `nca`: pushes data to Nextcloud Analytics from shell scripts and cron jobs.
+//!
+//! The client is configured by the file given with `--config`, or by the
+//! `NEXTCLOUD_ANALYTICS_*` environment variables, see `SyncClient::from_config_file` and
+//! `SyncClient::from_env`. Without either, the settings are read from `config.toml` in the
+//! configuration directory of the platform: `$XDG_CONFIG_HOME/nca` (`~/.config/nca`) on Linux,
+//! `~/Library/Application Support/nca` on macOS and `%APPDATA%\nca\config` on Windows.
+//!
+//! With `send --spool`, values which can't be sent are kept in a spool, `spool.jsonl` in the
+//! data directory of the platform unless a file is given (`~/.local/share/nca` on Linux), and
+//! sent before the next ones. `nca paths` prints these locations.
+//!
+//! ```sh
+//! nca send --collection 42 --key temp --value 21.5
+//! nca import data.csv
+//! nca check
+//! nca doctor
+//! some-tool | nca pipe
+//! nca scrape http://localhost:9100/metrics --every 60s
+//! nca paths
+//! nca --output json check
+//! ```
+//!
+//! With `--output json`, the result of the command, or its error, is printed on stdout as a
+//! single JSON object, with a `success` field, and an `error` object with the stable `code` of
+//! the error (e.g. `"E_AUTH"`, see `ErrorClass::code`) and its `message` on failure.
+//!
+//! The exit code tells what went wrong:
+//!
+//! - 0: the command succeeded;
+//! - 1: a step of `check` or `doctor` failed;
+//! - 2: the command line is invalid;
+//! - 3: the server couldn't be reached (`E_NETWORK`);
+//! - 4: the credentials were refused (`E_AUTH`);
+//! - 5: the endpoint or collection doesn't exist (`E_NOT_FOUND`);
+//! - 6: the server answered with an unexpected HTTP status (`E_HTTP`);
+//! - 7: the Analytics API reported an error (`E_API`);
+//! - 8: the server's answer couldn't be understood (`E_BAD_RESPONSE`);
+//! - 9: the settings or the input are invalid (`E_LOCAL`);
+//! - 10: the server didn't store the data as sent (`E_UNVERIFIED`);
+//! - 11: the server is in maintenance mode (`E_MAINTENANCE`);
+//! - 12: a data point was rejected before sending (`E_VALIDATION`).
+
+use std::io::{self, BufRead, BufReader};
+use std::path::PathBuf;
+use std::process;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use clap::{value_parser, Arg, ArgAction, ArgMatches, Command};
+use directories::ProjectDirs;
+use nextcloud_analytics_rs::datasets::DatasetKind;
+use nextcloud_analytics_rs::prometheus;
+use nextcloud_analytics_rs::{
+    AnalyticsError, BackgroundSender, BufferedClient, CheckStatus, Column, ConfigWarning,
+    CsvMapping, DataPoint, Diagnosis, ErrorClass, Outcome, Overflow, Progress, Receipt,
+    SenderHandle, SenderOptions, SyncClient, SyncClientBuilder,
+};
+use serde_json::{json, Map, Value};
+
+/// Clock difference with the server from which timeline data lands in the wrong buckets.
+const MAX_CLOCK_SKEW_SECS: i64 = 60;
+
+fn command() -> Command {
+    Command::new("nca")
+        .about("Pushes data to Nextcloud Analytics")
+        .version(env!("CARGO_PKG_VERSION"))
+        .subcommand_required(true)
+        .arg(
+            Arg::new("config")
+                .long("config")
+                .global(true)
+                .value_name("FILE")
+                .help(
+                    "Settings file, instead of the NEXTCLOUD_ANALYTICS_* variables or the \
+                     default file",
+                ),
+        )
+        .arg(
+            Arg::new("output")
+                .long("output")
+                .global(true)
+                .value_name("FORMAT")
+                .value_parser(["text", "json"])
+                .default_value("text")
+                .help("Prints the result and errors as text, or as a JSON object"),
+        )
+        .arg(
+            Arg::new("collection")
+                .long("collection")
+                .global(true)
+                .value_name("INDEX")
+                .value_parser(value_parser!(u32))
+                .help("Collection to use, instead of the configured one"),
+        )
+        .subcommand(
+            Command::new("send")
+                .about("Sends a single value, at the current time unless --dimension2 is given")
+                .arg(Arg::new("key").long("key").required(true))
+                .arg(
+                    Arg::new("value")
+                        .long("value")
+                        .required(true)
+                        .value_parser(value_parser!(f64)),
+                )
+                .arg(
+                    Arg::new("dimension2")
+                        .long("dimension2")
+                        .help("Second dimension, e.g. a date or a category"),
+                )
+                .arg(
+                    Arg::new("spool")
+                        .long("spool")
+                        .value_name("FILE")
+                        .num_args(0..=1)
+                        .default_missing_value("")
+                        .help(
+                            "Spools the value if the server can't be reached, to send it \
+                             with the next one, in the default spool unless a file is given",
+                        ),
+                ),
+        )
+        .subcommand(
+            Command::new("import")
+                .about("Sends the rows of a CSV file")
+                .arg(Arg::new("file").required(true).value_name("FILE"))
+                .arg(
+                    Arg::new("columns")
+                        .long("columns")
+                        .value_name("D1,D2,VALUE")
+                        .default_value("0,1,2")
+                        .value_parser(columns)
+                        .help("Columns of the two dimensions and the value, by position or name"),
+                )
+                .arg(
+                    Arg::new("delimiter")
+                        .long("delimiter")
+                        .default_value(",")
+                        .value_parser(value_parser!(char)),
+                )
+                .arg(
+                    Arg::new("no-headers")
+                        .long("no-headers")
+                        .action(ArgAction::SetTrue)
+                        .help("The first row is data, not column names"),
+                )
+                .arg(
+                    Arg::new("date-format")
+                        .long("date-format")
+                        .help("chrono format of the dates of the second dimension"),
+                )
+                .arg(
+                    Arg::new("progress")
+                        .long("progress")
+                        .action(ArgAction::SetTrue)
+                        .help("Print the rows sent, the progress and the time left on stderr"),
+                ),
+        )
+        .subcommand(
+            Command::new("pipe")
+                .about(
+                    "Sends the `key value [time]` lines of stdin, or of the connections to a \
+                     Unix socket, in batches",
+                )
+                .arg(
+                    Arg::new("socket")
+                        .long("socket")
+                        .value_name("PATH")
+                        .help("Unix socket to listen on, until killed, instead of reading stdin"),
+                )
+                .arg(
+                    Arg::new("capacity")
+                        .long("capacity")
+                        .value_name("POINTS")
+                        .default_value("1000")
+                        .value_parser(value_parser!(u32).range(1..))
+                        .help("Points waiting to be sent, from which reading pauses"),
+                )
+                .arg(
+                    Arg::new("flush-interval")
+                        .long("flush-interval")
+                        .value_name("DURATION")
+                        .default_value("1s")
+                        .value_parser(duration)
+                        .help("Longest time a point waits to be sent, e.g. 500ms or 10s"),
+                ),
+        )
+        .subcommand(
+            Command::new("scrape")
+                .about("Sends the samples of a Prometheus endpoint as timeline data")
+                .arg(Arg::new("url").required(true).value_name("URL"))
+                .arg(
+                    Arg::new("every")
+                        .long("every")
+                        .value_name("DURATION")
+                        .value_parser(duration)
+                        .help("Scrapes again on this interval, until killed, e.g. 60s"),
+                ),
+        )
+        .subcommand(
+            Command::new("paths").about("Prints the default locations of the settings and spool"),
+        )
+        .subcommand(
+            Command::new("check").about("Checks the server, the credentials and the collection"),
+        )
+        .subcommand(Command::new("doctor").about(
+            "Runs all the checks, plus the settings, clock, TLS and payload, to find out why \
+             data doesn't appear",
+        ))
+}
+
+/// The directories of `nca`, following the conventions of the platform, if the home directory
+/// is known.
+fn project_dirs() -> Option<ProjectDirs> {
+    ProjectDirs::from("", "", "nca")
+}
+
+/// The settings file read when neither `--config` nor the environment variables are given.
+fn default_config() -> Option<PathBuf> {
+    project_dirs().map(|dirs| dirs.config_dir().join("config.toml"))
+}
+
+/// The spool of `send --spool` when no file is given.
+fn default_spool() -> Option<PathBuf> {
+    project_dirs().map(|dirs| dirs.data_dir().join("spool.jsonl"))
+}
+
+/// The progress of an import, e.g. `500 rows sent, 25%, 3m 10s left`.
+fn progress_line(progress: &Progress) -> String {
+    let mut line = format!("{} rows sent", progress.rows);
+    if let Some(fraction) = progress.fraction() {
+        line += &format!(", {:.0}%", fraction * 100.0);
+    }
+    if let Some(eta) = progress.eta() {
+        let secs = eta.as_secs();
+        let left = match secs {
+            0..=59 => format!("{}s", secs),
+            60..=3599 => format!("{}m {}s", secs / 60, secs % 60),
+            _ => format!("{}h {}m", secs / 3600, secs % 3600 / 60),
+        };
+        line += &format!(", {} left", left);
+    }
+    line
+}
+
+fn client(matches: &ArgMatches) -> Result<(SyncClient, Vec<ConfigWarning>), AnalyticsError> {
+    let config = match matches.get_one::<String>("config") {
+        Some(path) => Some(PathBuf::from(path)),
+        None if std::env::var_os("NEXTCLOUD_ANALYTICS_URL").is_some() => None,
+        None => default_config().filter(|path| path.exists()),
+    };
+    let mut builder = match config {
+        Some(path) => SyncClientBuilder::from_config_file(path)?,
+        None => SyncClientBuilder::from_env()?,
+    };
+    if let Some(&collection) = matches.get_one::<u32>("collection") {
+        builder = builder.collection(collection);
+    }
+    builder.build_with_warnings()
+}
+
+/// The columns of the two dimensions and the value, each by position if it's a number, by
+/// name otherwise.
+fn columns(arg: &str) -> Result<(Column, Column, Column), String> {
+    let column = |column: &str| match column.trim().parse::<usize>() {
+        Ok(index) => Column::Index(index),
+        Err(_) => Column::Header(column.trim().to_string()),
+    };
+    match arg.split(',').collect::<Vec<_>>()[..] {
+        [d1, d2, value] => Ok((column(d1), column(d2), column(value))),
+        _ => Err("expected three comma-separated columns".to_string()),
+    }
+}
+
+/// A duration given as a number of milliseconds, seconds, minutes or hours, e.g. `500ms` or
+/// `60s`; seconds without a unit.
+fn duration(arg: &str) -> Result<Duration, String> {
+    let (number, unit) = match arg.find(|c: char| !c.is_ascii_digit()) {
+        Some(index) => arg.split_at(index),
+        None => (arg, "s"),
+    };
+    let number: u64 = number
+        .parse()
+        .map_err(|_| format!("expected a number followed by ms, s, m or h, not {:?}", arg))?;
+    match unit {
+        "ms" => Ok(Duration::from_millis(number)),
+        "s" => Ok(Duration::from_secs(number)),
+        "m" => Ok(Duration::from_secs(number * 60)),
+        "h" => Ok(Duration::from_secs(number * 3600)),
+        _ => Err(format!("unknown unit {:?}, expected ms, s, m or h", unit)),
+    }
+}
+
+/// What a command did: whether it succeeded, and its result as text and as JSON fields.
+struct Done {
+    success: bool,
+    text: Vec<String>,
+    fields: Map<String, Value>,
+}
+
+impl Done {
+    fn new(success: bool) -> Self {
+        Done {
+            success,
+            text: Vec::new(),
+            fields: Map::new(),
+        }
+    }
+
+    fn line(mut self, line: String) -> Self {
+        self.text.push(line);
+        self
+    }
+
+    fn field(mut self, name: &str, value: Value) -> Self {
+        self.fields.insert(name.to_string(), value);
+        self
+    }
+
+    fn print(self, output: &str) {
+        if output == "json" {
+            let mut fields = self.fields;
+            fields.insert("success".to_string(), Value::Bool(self.success));
+            println!("{}", Value::Object(fields));
+        } else {
+            for line in self.text {
+                println!("{}", line);
+            }
+        }
+    }
+}
+
+/// A step of `check` or `doctor`, as JSON.
+fn status_json(name: &str, status: &CheckStatus) -> Value {
+    match status {
+        CheckStatus::Passed => json!({ "name": name, "status": "passed" }),
+        CheckStatus::Failed(reason) => {
+            json!({ "name": name, "status": "failed", "reason": reason })
+        }
+        CheckStatus::Skipped => json!({ "name": name, "status": "skipped" }),
+    }
+}
+
+/// The steps of `check`.
+fn diagnosis_steps(diagnosis: &Diagnosis) -> Vec<(&'static str, &CheckStatus)> {
+    vec![
+        ("server", &diagnosis.server),
+        ("credentials", &diagnosis.credentials),
+        ("analytics app", &diagnosis.analytics),
+        ("collection", &diagnosis.collection),
+    ]
+}
+
+fn check(client: &SyncClient) -> Done {
+    let diagnosis = client.check();
+    let steps = diagnosis_steps(&diagnosis)
+        .into_iter()
+        .map(|(name, status)| status_json(name, status))
+        .collect();
+    Done::new(diagnosis.is_healthy())
+        .line(diagnosis.to_string())
+        .field("server_version", json!(diagnosis.server_version))
+        .field("checks", Value::Array(steps))
+}
+
+/// Prints `err`, on stdout with its code in JSON, or on stderr.
+fn print_error(err: &AnalyticsError, output: &str) {
+    if output == "json" {
+        let error = json!({ "code": err.code(), "message": err.to_string() });
+        println!("{}", json!({ "success": false, "error": error }));
+    } else {
+        eprintln!("nca: {}", err);
+    }
+}
+
+/// The exit code of a command failing with an error of `class`, see the module documentation.
+fn exit_code(class: ErrorClass) -> i32 {
+    match class {
+        ErrorClass::Network => 3,
+        ErrorClass::Auth => 4,
+        ErrorClass::NotFound => 5,
+        ErrorClass::Http => 6,
+        ErrorClass::Api => 7,
+        ErrorClass::InvalidResponse => 8,
+        ErrorClass::Local => 9,
+        ErrorClass::Unverified => 10,
+        ErrorClass::Maintenance => 11,
+        ErrorClass::Validation => 12,
+    }
+}
+
+/// Name of what happened to a data point, in JSON.
+fn outcome_name(outcome: &Outcome) -> &'static str {
+    match outcome {
+        Outcome::Sent(_) => "sent",
+        Outcome::Filtered => "filtered",
+        Outcome::Coalesced => "coalesced",
+        Outcome::Spooled => "spooled",
+        Outcome::DryRun(_) => "dry_run",
+        Outcome::Duplicate => "duplicate",
+        Outcome::Queued => "queued",
+    }
+}
+
+/// Difference between the local clock and the `Date` header of the server, in seconds.
+fn clock_skew(client: &SyncClient) -> Result<i64, String> {
+    let url = client.describe().base_url + "status.php";
+    let resp = client
+        .http_client()
+        .head(&url)
+        .send()
+        .map_err(|err| err.to_string())?;
+    let date = resp
+        .headers()
+        .get(reqwest::header::DATE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| DateTime::parse_from_rfc2822(value).ok())
+        .ok_or("the server didn't send its date")?;
+    Ok((Utc::now() - date.with_timezone(&Utc)).num_seconds())
+}
+
+/// Connects to `url` with `http`, returning why the connection or its TLS handshake failed, if
+/// it did, with the causes of the error.
+fn handshake(http: &reqwest::blocking::Client, url: &str) -> Result<(), String> {
+    let err = match http.head(url).send() {
+        Ok(_) => return Ok(()),
+        Err(err) => err,
+    };
+    let mut reason = err.to_string();
+    let mut source = std::error::Error::source(&err);
+    while let Some(cause) = source {
+        let cause_text = cause.to_string();
+        if !reason.contains(&cause_text) {
+            reason = format!("{}: {}", reason, cause_text);
+        }
+        source = cause.source();
+    }
+    Err(reason)
+}
+
+/// Runs the checks of `check`, then those of the settings, the dataset, the clock and of a TLS
+/// handshake, and shows the request a send of the current time would make.
+fn doctor(client: &SyncClient, warnings: &[ConfigWarning]) -> Done {
+    let mut steps = Vec::new();
+    let mut text = Vec::new();
+    if warnings.is_empty() {
+        text.push(format!("settings: {}", CheckStatus::Passed));
+    }
+    for warning in warnings {
+        text.push(format!("settings: warning: {}", warning));
+    }
+    steps.push(json!({
+        "name": "settings",
+        "status": if warnings.is_empty() { "passed" } else { "warning" },
+        "warnings": warnings.iter().map(ToString::to_string).collect::<Vec<_>>(),
+    }));
+
+    let diagnosis = client.check();
+    text.push(diagnosis.to_string());
+    for (name, status) in diagnosis_steps(&diagnosis) {
+        steps.push(status_json(name, status));
+    }
+    let mut healthy = diagnosis.is_healthy();
+    let mut step = |name: &str, status: CheckStatus| {
+        text.push(format!("{}: {}", name, status));
+        steps.push(status_json(name, &status));
+        healthy &= !matches!(status, CheckStatus::Failed(_));
+    };
+
+    let description = client.describe();
+    let collection = u64::from(description.collection);
+    let writable = if !diagnosis.collection.passed() {
+        CheckStatus::Skipped
+    } else {
+        match client.list_datasets() {
+            Ok(datasets) => match datasets.iter().find(|dataset| dataset.id == collection) {
+                Some(dataset) if dataset.kind != DatasetKind::InternalDatabase => {
+                    CheckStatus::Failed(format!(
+                        "{:?} is backed by an external data source",
+                        dataset.name
+                    ))
+                }
+                _ => CheckStatus::Passed,
+            },
+            Err(err) => CheckStatus::Failed(err.to_string()),
+        }
+    };
+    step("writable", writable);
+
+    let clock = if !diagnosis.server.passed() {
+        CheckStatus::Skipped
+    } else {
+        match clock_skew(client) {
+            Ok(skew) if skew.abs() > MAX_CLOCK_SKEW_SECS => CheckStatus::Failed(format!(
+                "the local clock is {}s {} the server's, timeline data lands in the wrong buckets",
+                skew.abs(),
+                if skew > 0 { "ahead of" } else { "behind" }
+            )),
+            Ok(_) => CheckStatus::Passed,
+            Err(reason) => CheckStatus::Failed(reason),
+        }
+    };
+    step("clock", clock);
+
+    let status_url = description.base_url.clone() + "status.php";
+    let tls = if !description.base_url.starts_with("https://") {
+        CheckStatus::Failed("not used, the URL is plain HTTP".to_string())
+    } else if let Err(reason) = handshake(client.http_client(), &status_url) {
+        CheckStatus::Failed(format!("the handshake failed: {}", reason))
+    } else if warnings.contains(&ConfigWarning::InvalidCertsAccepted) {
+        // The client accepts any certificate: tell whether the chain would be accepted too.
+        let verifying = reqwest::blocking::Client::new();
+        match handshake(&verifying, &status_url) {
+            Ok(()) => CheckStatus::Failed(
+                "the certificate chain is valid, but the client doesn't verify it".to_string(),
+            ),
+            Err(reason) => CheckStatus::Failed(format!(
+                "the certificate chain isn't verified, and wouldn't be accepted: {}",
+                reason
+            )),
+        }
+    } else {
+        CheckStatus::Passed
+    };
+    step("tls", tls);
+
+    let payload = match client.format_time(Utc::now()) {
+        Ok(time) => client.explain(&DataPoint::new("nca_doctor".to_string(), time, 1.0)),
+        Err(err) => format!("the current time can't be formatted: {}", err),
+    };
+    text.push(format!("payload: {}", payload));
+    Done {
+        success: healthy,
+        text,
+        fields: Map::new(),
+    }
+    .field("checks", Value::Array(steps))
+    .field("payload", Value::String(payload))
+}
+
+/// Parses a `key value [time]` line of `pipe`, the time being a Unix timestamp in seconds or
+/// an RFC 3339 date, and the current time if it's missing.
+fn pipe_point(client: &SyncClient, line: &str) -> Result<DataPoint, String> {
+    let mut fields = line.split_whitespace();
+    let (key, value) = match (fields.next(), fields.next()) {
+        (Some(key), Some(value)) => (key, value),
+        _ => return Err("expected `key value [time]`".to_string()),
+    };
+    let value: f64 = value
+        .parse()
+        .map_err(|_| format!("{:?} isn't a number", value))?;
+    let time = match fields.next() {
+        None => Utc::now(),
+        Some(time) => match time.parse::<i64>() {
+            Ok(secs) => DateTime::from_timestamp(secs, 0)
+                .ok_or_else(|| format!("{} is out of range", secs))?,
+            Err(_) => DateTime::parse_from_rfc3339(time)
+                .map_err(|_| {
+                    format!(
+                        "{:?} is neither a Unix timestamp nor an RFC 3339 date",
+                        time
+                    )
+                })?
+                .with_timezone(&Utc),
+        },
+    };
+    if fields.next().is_some() {
+        return Err("expected `key value [time]`".to_string());
+    }
+    let time = client.format_time(time).map_err(|err| err.to_string())?;
+    Ok(DataPoint::new(key.to_string(), time, value))
+}
+
+/// Queues the points of the lines of `input`, warning about the invalid ones, and returns the
+/// number of lines read and of invalid ones.
+fn pipe_lines(client: &SyncClient, handle: &SenderHandle, input: impl BufRead) -> (u64, u64) {
+    let (mut read, mut invalid) = (0, 0);
+    for (number, line) in input.lines().enumerate() {
+        let line = match line {
+            Ok(line) => line,
+            Err(err) => {
+                eprintln!("nca: {}", err);
+                break;
+            }
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+        read += 1;
+        let queued = pipe_point(client, &line)
+            .and_then(|point| handle.enqueue_point(point).map_err(|err| err.to_string()));
+        if let Err(reason) = queued {
+            eprintln!("nca: line {}: {}", number + 1, reason);
+            invalid += 1;
+        }
+    }
+    (read, invalid)
+}
+
+/// Accepts connections on the Unix socket at `path` forever, queueing the lines of each.
+#[cfg(unix)]
+fn listen(client: &Arc<SyncClient>, handle: &SenderHandle, path: &str) -> io::Result<()> {
+    use std::os::unix::net::UnixListener;
+
+    // A socket left over by a previous run.
+    let _ = std::fs::remove_file(path);
+    let listener = UnixListener::bind(path)?;
+    for stream in listener.incoming() {
+        let stream = stream?;
+        let (client, handle) = (Arc::clone(client), handle.clone());
+        thread::spawn(move || pipe_lines(&client, &handle, BufReader::new(stream)));
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn listen(_: &Arc<SyncClient>, _: &SenderHandle, _: &str) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "Unix sockets aren't supported on this platform",
+    ))
+}
+
+fn pipe(client: SyncClient, args: &ArgMatches) -> Result<Done, AnalyticsError> {
+    let client = Arc::new(client);
+    let options = SenderOptions::new(
+        *args.get_one::<u32>("capacity").unwrap() as usize,
+        *args.get_one::<Duration>("flush-interval").unwrap(),
+    )
+    .overflow(Overflow::Block);
+    let sender = BackgroundSender::start_with(Arc::clone(&client), options);
+
+    let (read, invalid) = match args.get_one::<String>("socket") {
+        Some(path) => {
+            listen(&client, &sender.handle(), path)?;
+            (0, 0)
+        }
+        None => pipe_lines(&client, &sender.handle(), io::stdin().lock()),
+    };
+    let handle = sender.handle();
+    sender.stop();
+    let stats = handle.stats();
+    let unsent = stats.dropped + stats.expired + stats.failed;
+    Ok(Done::new(unsent == 0)
+        .line(format!(
+            "{} lines read, {} invalid, {} points sent, {} not sent",
+            read, invalid, stats.sent, unsent
+        ))
+        .field("read", json!(read))
+        .field("invalid", json!(invalid))
+        .field("sent", json!(stats.sent))
+        .field("failed", json!(unsent)))
+}
+
+/// Scrapes a Prometheus endpoint once, or forever on an interval, printing the result of each
+/// scrape.
+fn scrape(client: &SyncClient, args: &ArgMatches, output: &str) -> Result<Done, AnalyticsError> {
+    let url = args.get_one::<String>("url").unwrap();
+    let scrape = || {
+        client
+            .scrape_prometheus(url, prometheus::default_key)
+            .map(|sent| {
+                Done::new(true)
+                    .line(format!("{} samples sent", sent))
+                    .field("sent", json!(sent))
+            })
+    };
+    let every = match args.get_one::<Duration>("every") {
+        Some(&every) => every,
+        None => return scrape(),
+    };
+    loop {
+        match scrape() {
+            Ok(done) => done.print(output),
+            Err(err) => print_error(&err, output),
+        }
+        thread::sleep(every);
+    }
+}
+
+/// The default locations, and whether there's a file at each.
+fn paths() -> Done {
+    let spool = default_spool();
+    let quarantine = spool.as_ref().map(|spool| spool.with_extension("corrupt"));
+    let mut done = Done::new(true);
+    for (name, path) in [
+        ("config", default_config()),
+        ("spool", spool),
+        ("quarantine", quarantine),
+    ] {
+        let exists = path.as_ref().is_some_and(|path| path.exists());
+        let text = match &path {
+            Some(path) if exists => path.display().to_string(),
+            Some(path) => format!("{} (missing)", path.display()),
+            None => "unknown, the home directory wasn't found".to_string(),
+        };
+        done = done
+            .line(format!("{}: {}", name, text))
+            .field(name, json!({ "path": path, "exists": exists }));
+    }
+    done
+}
+
+/// Sends a value, through the spool if `--spool` is given.
+fn send(client: SyncClient, args: &ArgMatches) -> Result<Receipt, AnalyticsError> {
+    let key = args.get_one::<String>("key").unwrap().clone();
+    let value = *args.get_one::<f64>("value").unwrap();
+    let dimension2 = args.get_one::<String>("dimension2");
+    let spool = match args.get_one::<String>("spool").map(String::as_str) {
+        None => {
+            return match dimension2 {
+                Some(dimension2) => client.send_data(key, dimension2.clone(), value),
+                None => client.send_timeline_now_data(key, value),
+            }
+        }
+        Some("") => default_spool().ok_or_else(|| {
+            AnalyticsError::Local("no default spool, the home directory wasn't found".into())
+        })?,
+        Some(path) => PathBuf::from(path),
+    };
+    if let Some(dir) = spool.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+
+    let buffered = BufferedClient::new(client, spool);
+    match dimension2 {
+        Some(dimension2) => buffered.send_data(key, dimension2.clone(), value),
+        None => buffered.send_timeline_now_data(key, value),
+    }
+}
+
+fn run(matches: &ArgMatches) -> Result<Done, AnalyticsError> {
+    if let Some(("paths", _)) = matches.subcommand() {
+        return Ok(paths());
+    }
+    let (client, warnings) = client(matches)?;
+    match matches.subcommand() {
+        Some(("send", args)) => {
+            let receipt = send(client, args)?;
+            let validation = receipt
+                .report()
+                .and_then(|report| report.validation.clone());
+            if let Some(validation) = &validation {
+                if matches.get_one::<String>("output").unwrap() == "text" {
+                    eprintln!("nca: warning: {}", validation);
+                }
+            }
+            Ok(Done::new(true)
+                .field("outcome", json!(outcome_name(&receipt.outcome)))
+                .field("validation", json!(validation)))
+        }
+        Some(("import", args)) => {
+            let (d1, d2, value) = args
+                .get_one::<(Column, Column, Column)>("columns")
+                .unwrap()
+                .clone();
+            let mut mapping = CsvMapping::new(d1, d2, value)
+                .delimiter(*args.get_one::<char>("delimiter").unwrap())
+                .headers(!args.get_flag("no-headers"));
+            if let Some(format) = args.get_one::<String>("date-format") {
+                mapping = mapping.date_format(format.as_str());
+            }
+
+            let path = args.get_one::<String>("file").unwrap();
+            let show_progress = args.get_flag("progress");
+            let rows = client.send_csv_file(path, &mapping, |progress| {
+                if show_progress {
+                    eprintln!("nca: {}", progress_line(progress));
+                }
+            })?;
+            Ok(Done::new(true)
+                .line(format!("{} rows sent", rows))
+                .field("rows", json!(rows)))
+        }
+        Some(("pipe", args)) => pipe(client, args),
+        Some(("scrape", args)) => {
+            scrape(&client, args, matches.get_one::<String>("output").unwrap())
+        }
+        Some(("check", _)) => Ok(check(&client)),
+        Some(("doctor", _)) => Ok(doctor(&client, &warnings)),
+        _ => unreachable!("a subcommand is required"),
+    }
+}
+
+fn main() {
+    let matches = command().get_matches();
+    let output = matches.get_one::<String>("output").unwrap().clone();
+    match run(&matches) {
+        Ok(done) => {
+            let success = done.success;
+            done.print(&output);
+            if !success {
+                process::exit(1);
+            }
+        }
+        Err(err) => {
+            print_error(&err, &output);
+            process::exit(exit_code(err.class()));
+        }
+    }
+}
@@ -0,0 +1,189 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use reqwest as http;
+
+use crate::{parse_json, AnalyticsError, SyncClient};
+
+/// A response of a read endpoint, kept with its validators to ask the server whether it
+/// changed, see [`SyncClient::with_response_cache`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CachedResponse {
+    /// Body of the response.
+    pub body: String,
+    /// Value of the `ETag` header of the response, if any.
+    pub etag: Option<String>,
+    /// Value of the `Last-Modified` header of the response, if any.
+    pub last_modified: Option<String>,
+}
+
+/// Where a client keeps the responses of read endpoints, by URL, see
+/// [`SyncClient::with_response_cache`], e.g. to share them between processes.
+pub trait CacheStore: Send + Sync {
+    /// The response cached for `url`, if any.
+    fn get(&self, url: &str) -> Option<CachedResponse>;
+
+    /// Caches the response of `url`, replacing the previous one.
+    fn put(&self, url: &str, response: CachedResponse);
+
+    /// Forgets all the cached responses.
+    fn clear(&self);
+}
+
+/// Cached responses by URL, with when they were last read or cached, and the current time of
+/// the cache.
+type Entries = (HashMap<String, (CachedResponse, u64)>, u64);
+
+/// A [`CacheStore`] in memory, keeping the last response of the URLs read most recently, up to
+/// its capacity.
+#[derive(Debug)]
+pub struct MemoryCache {
+    capacity: usize,
+    responses: Mutex<Entries>,
+}
+
+impl MemoryCache {
+    /// The number of responses kept by [`MemoryCache::new`].
+    pub const DEFAULT_CAPACITY: usize = 128;
+
+    /// Create an empty cache keeping up to [`MemoryCache::DEFAULT_CAPACITY`] responses.
+    pub fn new() -> Self {
+        Self::with_capacity(Self::DEFAULT_CAPACITY)
+    }
+
+    /// Create an empty cache keeping up to `capacity` responses, forgetting the least recently
+    /// used one beyond it.
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            capacity,
+            responses: Mutex::new((HashMap::new(), 0)),
+        }
+    }
+
+    /// The number of responses cached.
+    pub fn len(&self) -> usize {
+        self.responses.lock().unwrap().0.len()
+    }
+
+    /// Whether no response is cached.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl Default for MemoryCache {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CacheStore for MemoryCache {
+    fn get(&self, url: &str) -> Option<CachedResponse> {
+        let mut guard = self.responses.lock().unwrap();
+        let (responses, clock) = &mut *guard;
+        *clock += 1;
+        let (response, used) = responses.get_mut(url)?;
+        *used = *clock;
+        Some(response.clone())
+    }
+
+    fn put(&self, url: &str, response: CachedResponse) {
+        if self.capacity == 0 {
+            return;
+        }
+        let mut guard = self.responses.lock().unwrap();
+        let (responses, clock) = &mut *guard;
+        *clock += 1;
+        responses.insert(url.to_string(), (response, *clock));
+        if responses.len() > self.capacity {
+            let oldest = responses
+                .iter()
+                .min_by_key(|(_, (_, used))| *used)
+                .map(|(url, _)| url.clone());
+            if let Some(oldest) = oldest {
+                responses.remove(&oldest);
+            }
+        }
+    }
+
+    fn clear(&self) {
+        self.responses.lock().unwrap().0.clear();
+    }
+}
+
+impl SyncClient {
+    /// Cache the responses of the read endpoints in `store`, e.g. a [`MemoryCache`], and send
+    /// conditional requests for them, with `If-None-Match` and `If-Modified-Since`, so that
+    /// unchanged data isn't downloaded again, e.g. by dashboards polling a report.
+    ///
+    /// Only responses with an `ETag` or a `Last-Modified` header are cached, and they are
+    /// always revalidated with the server: a cached response is never used without asking.
+    /// Any successful write through the client, or its clones, clears the whole cache, as the
+    /// reports a dataset feeds aren't known.
+    pub fn with_response_cache<C: CacheStore + 'static>(mut self, store: C) -> Self {
+        self.response_cache = Some(Arc::new(store));
+        self
+    }
+
+    /// Clears the response cache after a successful write.
+    pub(crate) fn invalidate_cache(&self) {
+        if let Some(cache) = &self.response_cache {
+            cache.clear();
+        }
+    }
+
+    /// Reads a JSON endpoint with a conditional request, answering from the cache if the
+    /// server says the response didn't change.
+    pub(crate) fn get_json_cached(
+        &self,
+        cache: &dyn CacheStore,
+        req: http::blocking::RequestBuilder,
+        url: &str,
+    ) -> Result<serde_json::Value, AnalyticsError> {
+        let cached = cache.get(url);
+        let mut req = req;
+        if let Some(cached) = &cached {
+            if let Some(etag) = &cached.etag {
+                req = req.header(http::header::IF_NONE_MATCH, etag.as_str());
+            }
+            if let Some(last_modified) = &cached.last_modified {
+                req = req.header(http::header::IF_MODIFIED_SINCE, last_modified.as_str());
+            }
+        }
+
+        let resp = self.execute(req)?;
+        if resp.status == http::StatusCode::NOT_MODIFIED {
+            if let Some(cached) = cached {
+                return parse_json(&cached.body);
+            }
+        }
+        if !resp.status.is_success() {
+            return Err(self.redactions.error(AnalyticsError::from_response(
+                resp.status,
+                &resp.headers,
+                &resp.body,
+            )));
+        }
+
+        let header = |name| {
+            resp.headers
+                .get(name)
+                .and_then(|value: &http::header::HeaderValue| value.to_str().ok())
+                .map(str::to_string)
+        };
+        let etag = header(http::header::ETAG);
+        let last_modified = header(http::header::LAST_MODIFIED);
+        let value = parse_json(&resp.body)?;
+        if etag.is_some() || last_modified.is_some() {
+            cache.put(
+                url,
+                CachedResponse {
+                    body: resp.body,
+                    etag,
+                    last_modified,
+                },
+            );
+        }
+        Ok(value)
+    }
+}
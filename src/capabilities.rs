@@ -0,0 +1,112 @@
+//! Detection of what a server supports, for agents running against servers of mixed vintage.
+
+use reqwest as http;
+
+#[cfg(feature = "chrono")]
+use crate::TimestampFormat;
+use crate::{AnalyticsError, ApiVersion, ErrorClass, SyncClient};
+
+/// What a server supports, as detected by [`SyncClient::detect_capabilities`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Capabilities {
+    /// Version of Nextcloud, e.g. `"27.1.3"`.
+    pub nextcloud_version: Option<String>,
+    /// Version of the Analytics app, if the server publishes it in its OCS capabilities.
+    pub analytics_version: Option<String>,
+    /// Most recent Analytics API the server answers to.
+    pub api_version: ApiVersion,
+    /// Format of the dates of the rows already in the client's dataset, if there are any and
+    /// their format is recognized.
+    ///
+    /// Requires the `chrono` feature.
+    #[cfg(feature = "chrono")]
+    pub timestamp_format: Option<TimestampFormat>,
+}
+
+/// Formats tried on existing dates, most specific first.
+#[cfg(feature = "chrono")]
+fn candidate_formats() -> Vec<TimestampFormat> {
+    vec![
+        TimestampFormat::Rfc2822,
+        TimestampFormat::Rfc3339,
+        TimestampFormat::Custom("%Y-%m-%d %H:%M:%S".to_string()),
+        TimestampFormat::Custom("%Y-%m-%d %H:%M".to_string()),
+        TimestampFormat::Custom("%Y-%m-%d".to_string()),
+        TimestampFormat::UnixEpoch,
+    ]
+}
+
+impl SyncClient {
+    /// Queries the server's status, its OCS capabilities and the Analytics endpoints, to find
+    /// out its versions, the most recent API it supports and the date format of the client's
+    /// dataset; see [`SyncClient::negotiate`] to use them.
+    ///
+    /// The API version is found by listing the datasets with the 3.0 API, then with the 2.0 one
+    /// if the former isn't found; servers that have neither only get the 1.0 API. The date
+    /// format is the first one parsing all the dates of the dataset, whose data is read with
+    /// the 3.0 API; it isn't detected with older servers.
+    pub fn detect_capabilities(&self) -> Result<Capabilities, AnalyticsError> {
+        let status = self.request_json(http::Method::GET, "status.php", None)?;
+        let nextcloud_version = status["versionstring"].as_str().map(str::to_string);
+
+        // Missing capabilities aren't an error: old servers don't publish the app's version.
+        let analytics_version = self
+            .request_json(http::Method::GET, "ocs/v2.php/cloud/capabilities", None)
+            .ok()
+            .and_then(|resp| {
+                resp["ocs"]["data"]["capabilities"]["analytics"]["version"]
+                    .as_str()
+                    .map(str::to_string)
+            });
+
+        let mut api_version = ApiVersion::V1;
+        for version in [ApiVersion::V3, ApiVersion::V2] {
+            match self.request_json(http::Method::GET, version.datasets_path(), None) {
+                Ok(_) => {
+                    api_version = version;
+                    break;
+                }
+                Err(err) if err.class() == ErrorClass::NotFound => continue,
+                Err(err) => return Err(err),
+            }
+        }
+
+        Ok(Capabilities {
+            nextcloud_version,
+            analytics_version,
+            api_version,
+            #[cfg(feature = "chrono")]
+            timestamp_format: if api_version == ApiVersion::V3 {
+                self.detect_timestamp_format()
+            } else {
+                None
+            },
+        })
+    }
+
+    /// Detects the capabilities of the server, and uses its most recent API and the date format
+    /// of the dataset, if detected.
+    pub fn negotiate(mut self) -> Result<Self, AnalyticsError> {
+        let capabilities = self.detect_capabilities()?;
+        self.api_version = capabilities.api_version;
+        #[cfg(feature = "chrono")]
+        if let Some(format) = capabilities.timestamp_format {
+            self.timestamp_format = format;
+        }
+        Ok(self)
+    }
+
+    /// The first of the candidate formats parsing all the dates of the dataset, if it has any
+    /// data.
+    #[cfg(feature = "chrono")]
+    fn detect_timestamp_format(&self) -> Option<TimestampFormat> {
+        let rows = self.get_data().ok()?;
+        if rows.is_empty() {
+            return None;
+        }
+        candidate_formats().into_iter().find(|format| {
+            rows.iter()
+                .all(|row| format.parse(&row.dimension2).is_some())
+        })
+    }
+}
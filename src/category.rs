@@ -0,0 +1,147 @@
+//! Categorical datasets, whose second dimension is a group rather than a date, e.g. the
+//! processes using the most memory.
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, HashMap};
+use std::hash::{Hash, Hasher};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::{AnalyticsError, DataPoint, Receipt, SyncClient};
+
+/// What [`SyncClient::replace_category_snapshot`] changed.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SnapshotChanges {
+    /// Number of labels that weren't in the dataset.
+    pub added: usize,
+    /// Number of labels whose value changed.
+    pub updated: usize,
+    /// Number of labels removed from the dataset.
+    pub deleted: usize,
+    /// Whether the snapshot was skipped without any request, as it didn't change since the last
+    /// push, see [`SyncClient::with_snapshot_change_detection`].
+    pub skipped: bool,
+}
+
+/// The hash of the last snapshot pushed for each group, with when it was pushed, shared by the
+/// clones of a client.
+#[derive(Clone)]
+pub(crate) struct SnapshotDetection {
+    heartbeat: Duration,
+    pushed: Arc<Mutex<HashMap<String, (u64, Instant)>>>,
+}
+
+/// A hash of the labels and values of a snapshot, whatever their order.
+fn snapshot_hash(snapshot: &HashMap<String, f64>) -> u64 {
+    let sorted: BTreeMap<_, _> = snapshot
+        .iter()
+        .map(|(label, value)| (label, value.to_bits()))
+        .collect();
+    let mut hasher = DefaultHasher::new();
+    sorted.hash(&mut hasher);
+    hasher.finish()
+}
+
+impl SyncClient {
+    /// Skip [`SyncClient::replace_category_snapshot`] without any request when the snapshot of
+    /// a group is the same as the last one pushed by the client or its clones, to save the
+    /// bandwidth of mostly static datasets, unless `heartbeat` elapsed since that push, which
+    /// then goes through as usual, e.g. to restore rows changed on the server meanwhile.
+    /// Disabled by default.
+    ///
+    /// Snapshots are compared with a hash of their labels and values, kept in memory.
+    pub fn with_snapshot_change_detection(mut self, heartbeat: Duration) -> Self {
+        self.snapshot_detection = Some(SnapshotDetection {
+            heartbeat,
+            pushed: Arc::default(),
+        });
+        self
+    }
+
+    /// Sends the `value` of `label` in `group` to a categorical dataset, e.g. the memory used by
+    /// a process: the label is the first dimension and the group the second one.
+    pub fn send_category<S: Into<String>, F: Into<f64>>(
+        &self,
+        label: S,
+        group: S,
+        value: F,
+    ) -> Result<Receipt, AnalyticsError> {
+        self.send_point(DataPoint::new(label, group, value))
+    }
+
+    /// Makes the rows of `group` in the client's dataset match `snapshot`, a value per label,
+    /// e.g. to maintain a "top processes by memory" report: labels missing from the dataset are
+    /// added, changed values are updated, and labels missing from the snapshot are deleted.
+    ///
+    /// The current rows are read back with [`SyncClient::get_data`], and compared with the
+    /// labels as given: this doesn't work with a key template. New and changed values are sent
+    /// in a single batch, then the stale rows are deleted, so that a failure leaves extra rows
+    /// rather than missing ones. Nothing is sent if the dataset already matches.
+    pub fn replace_category_snapshot(
+        &self,
+        group: &str,
+        snapshot: &HashMap<String, f64>,
+    ) -> Result<SnapshotChanges, AnalyticsError> {
+        self.check_writable()?;
+        let detection = self
+            .snapshot_detection
+            .as_ref()
+            .map(|detection| (detection, snapshot_hash(snapshot)));
+        if let Some((detection, hash)) = &detection {
+            let pushed = detection.pushed.lock().unwrap();
+            if let Some(&(last, at)) = pushed.get(group) {
+                if last == *hash && at.elapsed() < detection.heartbeat {
+                    return Ok(SnapshotChanges {
+                        skipped: true,
+                        ..SnapshotChanges::default()
+                    });
+                }
+            }
+        }
+
+        let changes = self.push_category_snapshot(group, snapshot)?;
+        if let Some((detection, hash)) = detection {
+            let mut pushed = detection.pushed.lock().unwrap();
+            pushed.insert(group.to_string(), (hash, Instant::now()));
+        }
+        Ok(changes)
+    }
+
+    fn push_category_snapshot(
+        &self,
+        group: &str,
+        snapshot: &HashMap<String, f64>,
+    ) -> Result<SnapshotChanges, AnalyticsError> {
+        let current: BTreeMap<String, f64> = self
+            .get_data()?
+            .into_iter()
+            .filter(|point| point.dimension2 == group)
+            .map(|point| (point.dimension1, point.dimension3))
+            .collect();
+
+        let mut changes = SnapshotChanges::default();
+        let mut points = Vec::new();
+        for (label, &value) in snapshot {
+            match current.get(label) {
+                Some(&existing) if existing == value => continue,
+                Some(_) => changes.updated += 1,
+                None => changes.added += 1,
+            }
+            points.push(DataPoint::new(label.as_str(), group, value));
+        }
+        if !points.is_empty() {
+            let _ = self.send_batch(&points)?;
+        }
+
+        let stale: Vec<(&str, &str)> = current
+            .keys()
+            .filter(|label| !snapshot.contains_key(*label))
+            .map(|label| (label.as_str(), group))
+            .collect();
+        if !stale.is_empty() {
+            self.delete_rows(&stale, self.collection)?;
+            changes.deleted = stale.len();
+        }
+        Ok(changes)
+    }
+}
@@ -0,0 +1,186 @@
+use core::fmt;
+
+use reqwest as http;
+use serde::Deserialize;
+
+use crate::{AnalyticsError, ErrorClass, SyncClient};
+
+/// Result of one of the steps of [`SyncClient::check`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CheckStatus {
+    /// The step succeeded.
+    Passed,
+    /// The step failed, for the given reason.
+    Failed(String),
+    /// The step couldn't be run, because a previous one failed.
+    Skipped,
+}
+
+impl CheckStatus {
+    /// Whether the step succeeded.
+    pub fn passed(&self) -> bool {
+        *self == CheckStatus::Passed
+    }
+}
+
+impl fmt::Display for CheckStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CheckStatus::Passed => write!(f, "ok"),
+            CheckStatus::Failed(reason) => write!(f, "FAILED: {}", reason),
+            CheckStatus::Skipped => write!(f, "skipped"),
+        }
+    }
+}
+
+/// What's wrong, if anything, with the configuration of a client, as found by
+/// [`SyncClient::check`]. Each step is only run if the previous ones passed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Diagnosis {
+    /// The base URL is reachable, and is the one of a Nextcloud instance which isn't in
+    /// maintenance mode.
+    pub server: CheckStatus,
+    /// Version of Nextcloud, as reported by the server.
+    pub server_version: Option<String>,
+    /// The server accepts the client's credentials.
+    pub credentials: CheckStatus,
+    /// The Analytics app is installed and enabled.
+    pub analytics: CheckStatus,
+    /// The client's collection is one of the user's datasets.
+    pub collection: CheckStatus,
+}
+
+impl Diagnosis {
+    /// Whether all the steps passed.
+    pub fn is_healthy(&self) -> bool {
+        self.server.passed()
+            && self.credentials.passed()
+            && self.analytics.passed()
+            && self.collection.passed()
+    }
+}
+
+impl fmt::Display for Diagnosis {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "server: {}", self.server)?;
+        if let Some(version) = &self.server_version {
+            write!(f, " (Nextcloud {})", version)?;
+        }
+        writeln!(f)?;
+        writeln!(f, "credentials: {}", self.credentials)?;
+        writeln!(f, "analytics app: {}", self.analytics)?;
+        write!(f, "collection: {}", self.collection)
+    }
+}
+
+/// Response of `status.php`.
+#[derive(Deserialize)]
+struct ServerStatus {
+    #[serde(default)]
+    installed: bool,
+    #[serde(default)]
+    maintenance: bool,
+    versionstring: Option<String>,
+}
+
+impl SyncClient {
+    /// Checks step by step that the base URL is reachable, that the credentials are accepted,
+    /// that the Analytics app is installed, and that the collection exists, e.g. at startup, so
+    /// that a wrong password and a wrong collection index can be told apart before the first
+    /// send.
+    pub fn check(&self) -> Diagnosis {
+        let mut diagnosis = Diagnosis {
+            server: CheckStatus::Skipped,
+            server_version: None,
+            credentials: CheckStatus::Skipped,
+            analytics: CheckStatus::Skipped,
+            collection: CheckStatus::Skipped,
+        };
+
+        let (server, version) = self.check_server();
+        diagnosis.server = server;
+        diagnosis.server_version = version;
+        if !diagnosis.server.passed() {
+            return diagnosis;
+        }
+
+        diagnosis.credentials =
+            match self.request_json(http::Method::GET, "ocs/v2.php/cloud/user", None) {
+                Ok(_) => CheckStatus::Passed,
+                Err(err) => CheckStatus::Failed(err.to_string()),
+            };
+        if !diagnosis.credentials.passed() {
+            return diagnosis;
+        }
+
+        let datasets = match self.list_datasets() {
+            Ok(datasets) => datasets,
+            Err(err) => {
+                let reason = if err.class() == ErrorClass::NotFound {
+                    "the Analytics API wasn't found, is the app installed and enabled?".to_string()
+                } else {
+                    err.to_string()
+                };
+                diagnosis.analytics = CheckStatus::Failed(reason);
+                return diagnosis;
+            }
+        };
+        diagnosis.analytics = CheckStatus::Passed;
+
+        diagnosis.collection = if datasets
+            .iter()
+            .any(|dataset| dataset.id == u64::from(self.collection))
+        {
+            CheckStatus::Passed
+        } else {
+            CheckStatus::Failed(format!(
+                "no dataset with index {} among the {} of the user",
+                self.collection,
+                datasets.len()
+            ))
+        };
+        diagnosis
+    }
+
+    /// Fetches the public status of the server, without credentials.
+    fn check_server(&self) -> (CheckStatus, Option<String>) {
+        let url = self.base_url.clone() + "status.php";
+        let resp = match self
+            .client
+            .get(&url)
+            .build()
+            .map_err(AnalyticsError::from)
+            .and_then(|req| self.dispatch(req))
+        {
+            Ok(resp) => resp,
+            Err(err) => {
+                let reason = self.redactions.apply(&format!("unreachable: {}", err));
+                return (CheckStatus::Failed(reason), None);
+            }
+        };
+        if !resp.status.is_success() {
+            return (
+                CheckStatus::Failed(format!("unexpected status code: {:?}", resp.status)),
+                None,
+            );
+        }
+
+        let status: ServerStatus = match serde_json::from_str(&resp.body) {
+            Ok(status) => status,
+            Err(_) => {
+                return (
+                    CheckStatus::Failed("not a Nextcloud instance".to_string()),
+                    None,
+                )
+            }
+        };
+        let check = if !status.installed {
+            CheckStatus::Failed("Nextcloud isn't installed".to_string())
+        } else if status.maintenance {
+            CheckStatus::Failed("the server is in maintenance mode".to_string())
+        } else {
+            CheckStatus::Passed
+        };
+        (check, status.versionstring)
+    }
+}
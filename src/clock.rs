@@ -0,0 +1,42 @@
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use chrono::{DateTime, Duration, Utc};
+use reqwest as http;
+
+/// Last server time seen, with the local instant it was seen at.
+type LastSeen = Option<(DateTime<Utc>, Instant)>;
+
+/// Keeps track of the server's time, as seen in the `Date` header of the API responses.
+///
+/// The current server time is estimated from the last captured `Date` header plus the
+/// (monotonic) time elapsed since then, which makes it independent of the local clock.
+#[derive(Clone, Default)]
+pub(crate) struct ServerClock {
+    last_seen: Arc<Mutex<LastSeen>>,
+}
+
+impl ServerClock {
+    /// Records the server time from the response headers, if there's a valid `Date` header.
+    pub fn observe(&self, headers: &http::header::HeaderMap) {
+        let date = headers
+            .get(http::header::DATE)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| DateTime::parse_from_rfc2822(value).ok());
+
+        if let Some(date) = date {
+            *self.last_seen.lock().unwrap() = Some((date.with_timezone(&Utc), Instant::now()));
+        }
+    }
+
+    /// Estimated current server time, or the local time if no server time has been seen yet.
+    pub fn now(&self) -> DateTime<Utc> {
+        match *self.last_seen.lock().unwrap() {
+            Some((date, seen_at)) => match Duration::from_std(seen_at.elapsed()) {
+                Ok(elapsed) => date.checked_add_signed(elapsed).unwrap_or_else(Utc::now),
+                Err(_) => Utc::now(),
+            },
+            None => Utc::now(),
+        }
+    }
+}
@@ -0,0 +1,157 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::{AnalyticsError, DataPoint, Outcome, Receipt, SyncClient, ValueFormat};
+
+/// How the values of data points arriving too fast for a key are combined, see
+/// [`SyncClient::with_min_interval`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CoalescePolicy {
+    /// Keep the most recent value.
+    Last,
+    /// Average all the values.
+    Mean,
+    /// Add up all the values, e.g. counts of events, see [`SyncClient::send_event`].
+    Sum,
+}
+
+#[derive(Clone, Copy)]
+struct Rule {
+    interval: Duration,
+    policy: CoalescePolicy,
+}
+
+/// Values held back for a key since its last send.
+struct Pending {
+    last_sent: Option<Instant>,
+    /// Latest data point held back, whose other dimensions are kept.
+    latest: Option<DataPoint>,
+    sum: f64,
+    count: u32,
+}
+
+impl Pending {
+    fn push(&mut self, point: DataPoint) {
+        self.sum += point.dimension3;
+        self.count += 1;
+        self.latest = Some(point);
+    }
+
+    /// Combines the held-back values, resetting them.
+    fn take(&mut self, policy: CoalescePolicy) -> Option<DataPoint> {
+        let mut point = self.latest.take()?;
+        match policy {
+            CoalescePolicy::Last => {}
+            CoalescePolicy::Mean => point.dimension3 = self.sum / f64::from(self.count),
+            CoalescePolicy::Sum => point.dimension3 = self.sum,
+        }
+        self.sum = 0.0;
+        self.count = 0;
+        Some(point)
+    }
+}
+
+/// Per-key minimum intervals between sends.
+#[derive(Clone, Default)]
+pub(crate) struct Coalescer {
+    rules: HashMap<String, Rule>,
+    pending: Arc<Mutex<HashMap<String, Pending>>>,
+}
+
+impl Coalescer {
+    /// Returns the data point to send now, combining it with the values held back for its key,
+    /// or the payload it would have had if it must be held back.
+    fn admit(&self, point: DataPoint, format: ValueFormat) -> Result<DataPoint, String> {
+        let rule = match self.rules.get(&point.dimension1) {
+            Some(rule) => *rule,
+            None => return Ok(point),
+        };
+        let payload = point.to_json_with(format);
+
+        let mut pending = self.pending.lock().unwrap();
+        let state = pending
+            .entry(point.dimension1.clone())
+            .or_insert_with(|| Pending {
+                last_sent: None,
+                latest: None,
+                sum: 0.0,
+                count: 0,
+            });
+
+        state.push(point);
+        let now = Instant::now();
+        match state.last_sent {
+            Some(last) if now.duration_since(last) < rule.interval => Err(payload),
+            _ => {
+                state.last_sent = Some(now);
+                // There's at least the point just pushed.
+                Ok(state.take(rule.policy).unwrap())
+            }
+        }
+    }
+
+    /// Keys with values held back, with the number of values, sorted by key.
+    pub(crate) fn held_back(&self) -> Vec<(String, u32)> {
+        let pending = self.pending.lock().unwrap();
+        let mut held_back: Vec<(String, u32)> = pending
+            .iter()
+            .filter(|(_, state)| state.count > 0)
+            .map(|(key, state)| (key.clone(), state.count))
+            .collect();
+        held_back.sort();
+        held_back
+    }
+
+    /// Takes all the values held back, combined per key.
+    fn drain(&self) -> Vec<DataPoint> {
+        let mut pending = self.pending.lock().unwrap();
+        pending
+            .iter_mut()
+            .filter_map(|(key, state)| {
+                let policy = self.rules.get(key)?.policy;
+                state.take(policy)
+            })
+            .collect()
+    }
+}
+
+impl SyncClient {
+    /// Send data points for `key` at most once per `interval`.
+    ///
+    /// Data points arriving sooner are held back, and their values are combined according to
+    /// `policy` into the next data point sent for that key: nothing is dropped, but the send
+    /// methods return an [`Outcome::Coalesced`] receipt for them.
+    /// Call [`SyncClient::flush_coalesced`] before exiting to send the values still held back.
+    ///
+    /// The key is the first dimension as passed to the send methods, before any key template is
+    /// applied.
+    pub fn with_min_interval<S: Into<String>>(
+        mut self,
+        key: S,
+        interval: Duration,
+        policy: CoalescePolicy,
+    ) -> Self {
+        self.coalescer
+            .rules
+            .insert(key.into(), Rule { interval, policy });
+        self
+    }
+
+    /// Sends the values held back by [`SyncClient::with_min_interval`], combined per key, now.
+    pub fn flush_coalesced(&self) -> Result<Vec<Receipt>, AnalyticsError> {
+        self.coalescer
+            .drain()
+            .into_iter()
+            .map(|point| self.send_prepared(point))
+            .collect()
+    }
+
+    /// Applies the minimum intervals to a data point about to be sent, returning the point to
+    /// send, or the receipt of a point held back.
+    pub(crate) fn coalesce(&self, point: DataPoint) -> Result<DataPoint, Box<Receipt>> {
+        self.coalescer
+            .admit(point, self.value_format)
+            .map_err(|payload| Box::new(Receipt::new(&payload, Outcome::Coalesced)))
+    }
+}
@@ -0,0 +1,212 @@
+use std::marker::PhantomData;
+
+#[cfg(feature = "chrono")]
+use chrono::{DateTime, Utc};
+
+use crate::{AnalyticsError, DataPoint, Dimension, NumberFormat, Outcome, Receipt, SyncClient};
+
+/// A handle to send data to another collection than the client's, with the same base URL,
+/// credentials and connections, see [`SyncClient::collection`].
+///
+/// Data points go through the client's key filter, key template and enrichments, and its
+/// upsert mode applies; minimum intervals and derived metrics only apply to the client's own
+/// collection.
+#[derive(Clone, Copy)]
+pub struct CollectionHandle<'a> {
+    client: &'a SyncClient,
+    id: u32,
+}
+
+impl SyncClient {
+    /// A handle to the collection with the given index.
+    pub fn collection(&self, id: u32) -> CollectionHandle<'_> {
+        CollectionHandle { client: self, id }
+    }
+}
+
+impl CollectionHandle<'_> {
+    /// Index of the collection.
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+
+    /// Sends some data to the collection, like [`SyncClient::send_data`].
+    pub fn send_data<S: Into<String>, F: Into<f64>>(
+        &self,
+        dimension1: S,
+        dimension2: S,
+        dimension3: F,
+    ) -> Result<Receipt, AnalyticsError> {
+        self.send_point(DataPoint::new(dimension1, dimension2, dimension3))
+    }
+
+    /// Sends `value` for `key` to the collection, with its default second dimension, like
+    /// [`SyncClient::send_value`].
+    pub fn send_value<S: Into<String>, F: Into<f64>>(
+        &self,
+        key: S,
+        value: F,
+    ) -> Result<Receipt, AnalyticsError> {
+        self.client.check_default_dimension2(self.id)?;
+        self.send_data(key.into(), String::new(), value.into())
+    }
+
+    /// Sends a single data point to the collection, like [`SyncClient::send_point`].
+    pub fn send_point(&self, mut point: DataPoint) -> Result<Receipt, AnalyticsError> {
+        self.client.default_dimensions.fill(&mut point, self.id);
+        if !self.client.allows_key(&point.dimension1) {
+            let payload = point.to_json_with(self.client.value_format);
+            return Ok(Receipt::new(&payload, Outcome::Filtered));
+        }
+        let point = self.client.prepare_point(point)?;
+        self.client.post_point(point, self.id)
+    }
+
+    /// Sends many data points to the collection in a single request, like
+    /// [`SyncClient::send_batch`].
+    pub fn send_batch(&self, points: &[DataPoint]) -> Result<Receipt, AnalyticsError> {
+        self.client.send_batch_to(points, self.id)
+    }
+
+    /// Sends some timeline data to the collection, like [`SyncClient::send_timeline_data`].
+    ///
+    /// Requires the `chrono` feature.
+    #[cfg(feature = "chrono")]
+    pub fn send_timeline_data<S: Into<String>, F: Into<f64>>(
+        &self,
+        key: S,
+        time: DateTime<Utc>,
+        value: F,
+    ) -> Result<Receipt, AnalyticsError> {
+        let time = self.client.format_time(time)?;
+        self.send_data(key.into(), time, value.into())
+    }
+
+    /// Sends some timeline data to the collection at the current time, like
+    /// [`SyncClient::send_timeline_now_data`].
+    ///
+    /// Requires the `chrono` feature.
+    #[cfg(feature = "chrono")]
+    pub fn send_timeline_now_data<S: Into<String>, F: Into<f64>>(
+        &self,
+        key: S,
+        value: F,
+    ) -> Result<Receipt, AnalyticsError> {
+        self.send_timeline_data(key, self.client.now(), value)
+    }
+
+    /// Deletes the data of the collection matching `dimension1` and `dimension2`, like
+    /// [`SyncClient::delete_data`].
+    pub fn delete_data(&self, dimension1: &str, dimension2: &str) -> Result<(), AnalyticsError> {
+        self.client
+            .delete_rows(&[(dimension1, dimension2)], self.id)
+    }
+
+    /// Reads back the data of the report with the same index as the collection, like
+    /// [`SyncClient::get_data`].
+    pub fn get_data(&self) -> Result<Vec<DataPoint>, AnalyticsError> {
+        self.client
+            .get_report_data(self.id, NumberFormat::default())
+    }
+}
+
+/// A handle to a collection whose dimensions have fixed types, see
+/// [`SyncClient::typed_collection`], so that sending a row with the dimensions in the wrong
+/// order, e.g. a date as the key, doesn't compile.
+///
+/// Dimensions are formatted as with [`SyncClient::send_row`], e.g. dates in the client's
+/// timestamp format, and data points go through the same steps as with
+/// [`CollectionHandle::send_point`].
+///
+/// ```no_run
+/// # #[cfg(feature = "chrono")]
+/// # fn main() -> Result<(), nextcloud_analytics_rs::AnalyticsError> {
+/// # use chrono::{DateTime, Utc};
+/// # use nextcloud_analytics_rs::SyncClient;
+/// # let client = SyncClient::new("https://example.com/nextcloud", 3, "myself", "hunter2");
+/// let temperatures = client.typed_collection::<&str, DateTime<Utc>, f64>(3);
+/// temperatures.send("kitchen", Utc::now(), 21.5)?;
+/// # Ok(())
+/// # }
+/// # #[cfg(not(feature = "chrono"))]
+/// # fn main() {}
+/// ```
+pub struct Collection<'a, D1, D2, V> {
+    handle: CollectionHandle<'a>,
+    // Only the types matter, `fn` keeps the handle `Send`, `Sync` and `Copy` whatever they are.
+    schema: PhantomData<fn(D1, D2, V)>,
+}
+
+impl<D1, D2, V> Clone for Collection<'_, D1, D2, V> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<D1, D2, V> Copy for Collection<'_, D1, D2, V> {}
+
+impl SyncClient {
+    /// A handle to the collection with the given index, whose first and second dimensions
+    /// have the types `D1` and `D2`, and whose values have the type `V`.
+    pub fn typed_collection<D1, D2, V>(&self, id: u32) -> Collection<'_, D1, D2, V>
+    where
+        D1: Into<Dimension>,
+        D2: Into<Dimension>,
+        V: Into<f64>,
+    {
+        Collection {
+            handle: self.collection(id),
+            schema: PhantomData,
+        }
+    }
+}
+
+impl<D1, D2, V> Collection<'_, D1, D2, V>
+where
+    D1: Into<Dimension>,
+    D2: Into<Dimension>,
+    V: Into<f64>,
+{
+    /// Index of the collection.
+    pub fn id(&self) -> u32 {
+        self.handle.id
+    }
+
+    /// The untyped handle to the collection.
+    pub fn handle(&self) -> CollectionHandle<'_> {
+        self.handle
+    }
+
+    /// Sends a row to the collection.
+    pub fn send(
+        &self,
+        dimension1: D1,
+        dimension2: D2,
+        value: V,
+    ) -> Result<Receipt, AnalyticsError> {
+        let point = self.point(dimension1, dimension2, value)?;
+        self.handle.send_point(point)
+    }
+
+    /// Sends many rows to the collection in a single request, like
+    /// [`CollectionHandle::send_batch`].
+    pub fn send_batch<I>(&self, rows: I) -> Result<Receipt, AnalyticsError>
+    where
+        I: IntoIterator<Item = (D1, D2, V)>,
+    {
+        let points = rows
+            .into_iter()
+            .map(|(dimension1, dimension2, value)| self.point(dimension1, dimension2, value))
+            .collect::<Result<Vec<_>, _>>()?;
+        self.handle.send_batch(&points)
+    }
+
+    fn point(&self, dimension1: D1, dimension2: D2, value: V) -> Result<DataPoint, AnalyticsError> {
+        let client = self.handle.client;
+        Ok(DataPoint::new(
+            dimension1.into().into_text(client)?,
+            dimension2.into().into_text(client)?,
+            value.into(),
+        ))
+    }
+}
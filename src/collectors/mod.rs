@@ -0,0 +1,4 @@
+//! Helpers reading values from common data sources and pushing them to Nextcloud Analytics.
+
+#[cfg(feature = "modbus")]
+pub mod modbus;
@@ -0,0 +1,317 @@
+//! Reads Modbus TCP registers on an interval and pushes their values as timeline data, e.g. for
+//! energy meters and solar inverters.
+//!
+//! Requires the `modbus` feature.
+
+use core::fmt;
+use std::error::Error;
+use std::io::{Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::thread;
+use std::time::Duration;
+
+use crate::{AnalyticsError, SyncClient};
+
+/// Modbus TCP port used when none is given.
+pub const DEFAULT_PORT: u16 = 502;
+
+/// The table a register is read from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RegisterKind {
+    /// Read with function code 0x03.
+    Holding,
+    /// Read with function code 0x04.
+    Input,
+}
+
+impl RegisterKind {
+    fn function_code(self) -> u8 {
+        match self {
+            RegisterKind::Holding => 0x03,
+            RegisterKind::Input => 0x04,
+        }
+    }
+}
+
+/// How the raw contents of one or more registers are interpreted.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RegisterFormat {
+    /// One register, unsigned.
+    U16,
+    /// One register, two's complement.
+    I16,
+    /// Two registers, unsigned.
+    U32,
+    /// Two registers, two's complement.
+    I32,
+    /// Two registers, IEEE 754 single precision float.
+    F32,
+}
+
+impl RegisterFormat {
+    fn len(self) -> u16 {
+        match self {
+            RegisterFormat::U16 | RegisterFormat::I16 => 1,
+            RegisterFormat::U32 | RegisterFormat::I32 | RegisterFormat::F32 => 2,
+        }
+    }
+}
+
+/// A value to read from the device, and the key it is sent as.
+#[derive(Clone, Debug)]
+pub struct Register {
+    key: String,
+    kind: RegisterKind,
+    address: u16,
+    format: RegisterFormat,
+    scale: f64,
+    swap_words: bool,
+}
+
+impl Register {
+    /// A holding register at the given (zero-based) `address`, sent with the given `key`.
+    pub fn holding<S: Into<String>>(key: S, address: u16, format: RegisterFormat) -> Self {
+        Self {
+            key: key.into(),
+            kind: RegisterKind::Holding,
+            address,
+            format,
+            scale: 1.0,
+            swap_words: false,
+        }
+    }
+
+    /// An input register at the given (zero-based) `address`, sent with the given `key`.
+    pub fn input<S: Into<String>>(key: S, address: u16, format: RegisterFormat) -> Self {
+        Self {
+            kind: RegisterKind::Input,
+            ..Self::holding(key, address, format)
+        }
+    }
+
+    /// Multiply the raw value by `scale` before sending it, e.g. `0.1` for a value in tenths.
+    pub fn scale(mut self, scale: f64) -> Self {
+        self.scale = scale;
+        self
+    }
+
+    /// For two-register values, the low word comes first instead of the high word.
+    pub fn swap_words(mut self, swap: bool) -> Self {
+        self.swap_words = swap;
+        self
+    }
+
+    fn decode(&self, words: &[u16]) -> f64 {
+        let as_u32 = || {
+            let (high, low) = if self.swap_words {
+                (words[1], words[0])
+            } else {
+                (words[0], words[1])
+            };
+            (u32::from(high) << 16) | u32::from(low)
+        };
+
+        let raw = match self.format {
+            RegisterFormat::U16 => f64::from(words[0]),
+            RegisterFormat::I16 => f64::from(words[0] as i16),
+            RegisterFormat::U32 => f64::from(as_u32()),
+            RegisterFormat::I32 => f64::from(as_u32() as i32),
+            RegisterFormat::F32 => f64::from(f32::from_bits(as_u32())),
+        };
+
+        raw * self.scale
+    }
+}
+
+/// Reads a set of registers from a Modbus TCP device.
+pub struct ModbusCollector {
+    addr: String,
+    unit_id: u8,
+    timeout: Duration,
+    registers: Vec<Register>,
+    stream: Option<TcpStream>,
+    transaction_id: u16,
+}
+
+impl ModbusCollector {
+    /// A collector for the device at `addr`, e.g. `"192.168.1.20:502"`.
+    pub fn new<S: Into<String>>(addr: S) -> Self {
+        Self {
+            addr: addr.into(),
+            unit_id: 1,
+            timeout: Duration::from_secs(5),
+            registers: Vec::new(),
+            stream: None,
+            transaction_id: 0,
+        }
+    }
+
+    /// Modbus unit identifier (slave id) of the device. Defaults to 1.
+    pub fn unit_id(mut self, unit_id: u8) -> Self {
+        self.unit_id = unit_id;
+        self
+    }
+
+    /// Timeout for connecting to the device and for each read. Defaults to 5 seconds.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Adds a register to read.
+    pub fn register(mut self, register: Register) -> Self {
+        self.registers.push(register);
+        self
+    }
+
+    /// Reads all the registers, returning a key and its scaled value for each.
+    ///
+    /// The connection is kept open between reads, and reopened after a failure.
+    pub fn read_all(&mut self) -> Result<Vec<(String, f64)>, AnalyticsError> {
+        let result = self.try_read_all();
+        if result.is_err() {
+            self.stream = None;
+        }
+        result
+    }
+
+    /// Reads all the registers and sends their values as timeline data for the current time.
+    pub fn poll(&mut self, client: &SyncClient) -> Result<(), AnalyticsError> {
+        for (key, value) in self.read_all()? {
+            let _ = client.send_timeline_now_data(key, value)?;
+        }
+        Ok(())
+    }
+
+    /// Polls the device every `interval`, forever.
+    ///
+    /// Errors don't stop the loop: they are handed to `on_error`, and the next poll happens as
+    /// usual.
+    pub fn run<F: FnMut(AnalyticsError)>(
+        mut self,
+        client: &SyncClient,
+        interval: Duration,
+        mut on_error: F,
+    ) -> ! {
+        loop {
+            if let Err(err) = self.poll(client) {
+                on_error(err);
+            }
+            thread::sleep(interval);
+        }
+    }
+
+    fn try_read_all(&mut self) -> Result<Vec<(String, f64)>, AnalyticsError> {
+        let mut values = Vec::with_capacity(self.registers.len());
+        for i in 0..self.registers.len() {
+            let (kind, address, len) = {
+                let reg = &self.registers[i];
+                (reg.kind, reg.address, reg.format.len())
+            };
+            let words = self.read_registers(kind, address, len)?;
+            let reg = &self.registers[i];
+            values.push((reg.key.clone(), reg.decode(&words)));
+        }
+        Ok(values)
+    }
+
+    fn connect(&mut self) -> Result<&mut TcpStream, AnalyticsError> {
+        if self.stream.is_none() {
+            let addr = self
+                .addr
+                .to_socket_addrs()?
+                .next()
+                .ok_or_else(|| ModbusError(format!("couldn't resolve {}", self.addr)))?;
+            let stream = TcpStream::connect_timeout(&addr, self.timeout)?;
+            stream.set_read_timeout(Some(self.timeout))?;
+            stream.set_write_timeout(Some(self.timeout))?;
+            self.stream = Some(stream);
+        }
+        Ok(self.stream.as_mut().unwrap())
+    }
+
+    fn read_registers(
+        &mut self,
+        kind: RegisterKind,
+        address: u16,
+        count: u16,
+    ) -> Result<Vec<u16>, AnalyticsError> {
+        self.transaction_id = self.transaction_id.wrapping_add(1);
+        let transaction_id = self.transaction_id;
+        let unit_id = self.unit_id;
+        let function = kind.function_code();
+
+        // MBAP header (transaction id, protocol id, length, unit id), then the PDU.
+        let mut request = Vec::with_capacity(12);
+        request.extend_from_slice(&transaction_id.to_be_bytes());
+        request.extend_from_slice(&0u16.to_be_bytes());
+        request.extend_from_slice(&6u16.to_be_bytes());
+        request.push(unit_id);
+        request.push(function);
+        request.extend_from_slice(&address.to_be_bytes());
+        request.extend_from_slice(&count.to_be_bytes());
+
+        let stream = self.connect()?;
+        stream.write_all(&request)?;
+
+        let mut header = [0u8; 7];
+        stream.read_exact(&mut header)?;
+        let len = u16::from_be_bytes([header[4], header[5]]) as usize;
+        if len < 2 {
+            return Err(ModbusError(format!("invalid response length {}", len)).into());
+        }
+
+        let mut pdu = vec![0u8; len - 1];
+        stream.read_exact(&mut pdu)?;
+
+        if u16::from_be_bytes([header[0], header[1]]) != transaction_id {
+            return Err(ModbusError("response to another transaction".to_string()).into());
+        }
+
+        if pdu[0] == function | 0x80 {
+            return Err(ModbusError(format!(
+                "device returned exception code {} reading address {}",
+                pdu.get(1).copied().unwrap_or(0),
+                address
+            ))
+            .into());
+        }
+
+        if pdu[0] != function || pdu.len() < 2 || pdu[1] as usize != count as usize * 2 {
+            return Err(ModbusError("malformed response".to_string()).into());
+        }
+
+        let data = &pdu[2..];
+        if data.len() != count as usize * 2 {
+            return Err(ModbusError("truncated response".to_string()).into());
+        }
+
+        Ok(data
+            .chunks(2)
+            .map(|word| u16::from_be_bytes([word[0], word[1]]))
+            .collect())
+    }
+}
+
+/// An error in the Modbus exchange with the device.
+pub struct ModbusError(String);
+
+impl fmt::Debug for ModbusError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl fmt::Display for ModbusError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl Error for ModbusError {}
+
+impl From<ModbusError> for AnalyticsError {
+    fn from(err: ModbusError) -> Self {
+        AnalyticsError::local(err)
+    }
+}
@@ -0,0 +1,44 @@
+//! Compression of request bodies, with `flate2`.
+
+use std::io::Write;
+
+use flate2::write::{GzEncoder, ZlibEncoder};
+
+/// Compression of the bodies of batch requests, see [`SyncClientBuilder::compression`].
+///
+/// [`SyncClientBuilder::compression`]: crate::SyncClientBuilder::compression
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Compression {
+    /// `Content-Encoding: gzip`.
+    Gzip,
+    /// `Content-Encoding: deflate`, i.e. zlib-wrapped DEFLATE.
+    Deflate,
+}
+
+impl Compression {
+    /// Value of the `Content-Encoding` header.
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            Compression::Gzip => "gzip",
+            Compression::Deflate => "deflate",
+        }
+    }
+
+    pub(crate) fn compress(self, data: &[u8]) -> Vec<u8> {
+        // The fastest level already shrinks the repetitive JSON of batches a lot.
+        let level = flate2::Compression::fast();
+        let out = Vec::with_capacity(data.len() / 4);
+        // Writing to a vector can't fail.
+        match self {
+            Compression::Gzip => {
+                let mut encoder = GzEncoder::new(out, level);
+                encoder.write_all(data).and_then(|_| encoder.finish())
+            }
+            Compression::Deflate => {
+                let mut encoder = ZlibEncoder::new(out, level);
+                encoder.write_all(data).and_then(|_| encoder.finish())
+            }
+        }
+        .unwrap_or_default()
+    }
+}
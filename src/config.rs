@@ -0,0 +1,384 @@
+use core::fmt;
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+
+use serde::Deserialize;
+
+use crate::{AnalyticsError, ApiVersion, Profile, RetryPolicy, SyncClient, SyncClientBuilder};
+
+/// Prefix of the environment variables read by [`SyncClient::from_env`].
+const ENV_PREFIX: &str = "NEXTCLOUD_ANALYTICS_";
+
+/// Origin of the settings read by [`SyncClient::from_env`], in errors.
+const ENV_ORIGIN: &str = "the environment";
+
+/// The configuration of a client couldn't be read, see [`SyncClient::from_env`] and
+/// [`SyncClient::from_config_file`].
+pub struct ConfigError {
+    /// Where the faulty setting comes from, e.g. a file and line number.
+    origin: String,
+    message: String,
+}
+
+impl ConfigError {
+    fn new<S: Into<String>>(origin: &str, message: S) -> Self {
+        Self {
+            origin: origin.to_string(),
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Debug for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "invalid configuration in {}: {}",
+            self.origin, self.message
+        )
+    }
+}
+
+impl Error for ConfigError {}
+
+impl From<ConfigError> for AnalyticsError {
+    fn from(err: ConfigError) -> Self {
+        AnalyticsError::local(err)
+    }
+}
+
+/// The API version of a file, as a number, e.g. `3.0`, or a string, e.g. `"v3"`.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Version {
+    Number(f64),
+    Text(String),
+}
+
+/// The hosts of `no_proxy`, as a list or a comma-separated string.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Hosts {
+    List(Vec<String>),
+    Text(String),
+}
+
+/// Settings read from the environment or a file.
+#[derive(Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Settings {
+    /// Where the settings come from, e.g. a file.
+    #[serde(skip)]
+    origin: String,
+    url: Option<String>,
+    collection: Option<u32>,
+    user: Option<String>,
+    password: Option<String>,
+    password_file: Option<PathBuf>,
+    password_env: Option<String>,
+    token: Option<String>,
+    token_file: Option<PathBuf>,
+    api_version: Option<Version>,
+    profile: Option<String>,
+    timeout: Option<f64>,
+    connect_timeout: Option<f64>,
+    retries: Option<u32>,
+    user_agent: Option<String>,
+    proxy: Option<String>,
+    no_proxy: Option<Hosts>,
+}
+
+/// The value of the environment variable of a setting, if set.
+fn env_var(key: &str) -> Option<String> {
+    std::env::var(format!("{}{}", ENV_PREFIX, key.to_uppercase())).ok()
+}
+
+/// The parsed value of the environment variable of a setting, if set.
+fn env_parsed<T: FromStr>(key: &str) -> Result<Option<T>, ConfigError> {
+    env_var(key)
+        .map(|value| {
+            value
+                .parse()
+                .map_err(|_| ConfigError::new(ENV_ORIGIN, format!("invalid {}: {:?}", key, value)))
+        })
+        .transpose()
+}
+
+impl Settings {
+    fn from_env() -> Result<Self, ConfigError> {
+        Ok(Self {
+            origin: ENV_ORIGIN.to_string(),
+            url: env_var("url"),
+            collection: env_parsed("collection")?,
+            user: env_var("user"),
+            password: env_var("password"),
+            password_file: env_var("password_file").map(PathBuf::from),
+            password_env: env_var("password_env"),
+            token: env_var("token"),
+            token_file: env_var("token_file").map(PathBuf::from),
+            api_version: env_var("api_version").map(Version::Text),
+            profile: env_var("profile"),
+            timeout: env_parsed("timeout")?,
+            connect_timeout: env_parsed("connect_timeout")?,
+            retries: env_parsed("retries")?,
+            user_agent: env_var("user_agent"),
+            proxy: env_var("proxy"),
+            no_proxy: env_var("no_proxy").map(Hosts::Text),
+        })
+    }
+
+    /// Reads the settings of a file, in TOML or YAML depending on its extension.
+    fn from_file(path: &Path) -> Result<Self, ConfigError> {
+        let origin = path.display().to_string();
+        let source = fs::read_to_string(path)
+            .map_err(|err| ConfigError::new(&origin, format!("can't read the file: {}", err)))?;
+        let extension = path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+        let mut settings = match extension.as_str() {
+            "toml" => Self::from_toml(&source, &origin)?,
+            "yaml" | "yml" => Self::from_yaml(&source, &origin)?,
+            _ => {
+                return Err(ConfigError::new(
+                    &origin,
+                    "unknown format, expected a .toml, .yaml or .yml file",
+                ))
+            }
+        };
+        settings.origin = origin;
+        Ok(settings)
+    }
+
+    #[cfg(feature = "toml")]
+    fn from_toml(source: &str, origin: &str) -> Result<Self, ConfigError> {
+        toml::from_str(source).map_err(|err| ConfigError::new(origin, err.to_string()))
+    }
+
+    #[cfg(not(feature = "toml"))]
+    fn from_toml(_: &str, origin: &str) -> Result<Self, ConfigError> {
+        Err(ConfigError::new(
+            origin,
+            "TOML files require the `toml` feature",
+        ))
+    }
+
+    #[cfg(feature = "yaml")]
+    fn from_yaml(source: &str, origin: &str) -> Result<Self, ConfigError> {
+        // An empty document is `null`, rather than a map without any setting.
+        if source.trim().is_empty() {
+            return Ok(Self::default());
+        }
+        serde_yaml::from_str(source).map_err(|err| ConfigError::new(origin, err.to_string()))
+    }
+
+    #[cfg(not(feature = "yaml"))]
+    fn from_yaml(_: &str, origin: &str) -> Result<Self, ConfigError> {
+        Err(ConfigError::new(
+            origin,
+            "YAML files require the `yaml` feature",
+        ))
+    }
+
+    fn required<'a>(&self, key: &str, value: &'a Option<String>) -> Result<&'a str, ConfigError> {
+        value.as_deref().ok_or_else(|| self.missing(key))
+    }
+
+    fn missing(&self, key: &str) -> ConfigError {
+        ConfigError::new(&self.origin, format!("missing setting {:?}", key))
+    }
+
+    fn seconds(&self, key: &str, value: Option<f64>) -> Result<Option<Duration>, ConfigError> {
+        match value {
+            Some(seconds) if seconds.is_finite() && seconds >= 0.0 => {
+                Ok(Some(Duration::from_secs_f64(seconds)))
+            }
+            Some(seconds) => Err(ConfigError::new(
+                &self.origin,
+                format!("invalid {}: {} seconds", key, seconds),
+            )),
+            None => Ok(None),
+        }
+    }
+
+    /// A secret given directly, in a file, or in another environment variable.
+    fn secret(
+        &self,
+        secret: &Option<String>,
+        file: &Option<PathBuf>,
+        env: &Option<String>,
+    ) -> Result<Option<String>, ConfigError> {
+        if let Some(secret) = secret {
+            return Ok(Some(secret.clone()));
+        }
+        if let Some(path) = file {
+            let secret = fs::read_to_string(path).map_err(|err| {
+                ConfigError::new(
+                    &self.origin,
+                    format!("can't read {}: {}", path.display(), err),
+                )
+            })?;
+            // Files written by `echo` or editors end with a newline.
+            return Ok(Some(secret.trim_end_matches(&['\r', '\n'][..]).to_string()));
+        }
+        match env {
+            Some(var) => std::env::var(var).map(Some).map_err(|err| {
+                ConfigError::new(&self.origin, format!("can't read ${}: {}", var, err))
+            }),
+            None => Ok(None),
+        }
+    }
+
+    /// The client of these settings, which must include the collection.
+    fn client(&self) -> Result<SyncClient, AnalyticsError> {
+        if self.collection.is_none() {
+            return Err(self.missing("collection").into());
+        }
+        self.builder()?.build()
+    }
+
+    fn builder(&self) -> Result<SyncClientBuilder, ConfigError> {
+        let url = self.required("url", &self.url)?;
+        let user = self.required("user", &self.user)?;
+        let token = self.secret(&self.token, &self.token_file, &None)?;
+        let password = match self.secret(&self.password, &self.password_file, &self.password_env)? {
+            Some(password) => password,
+            None if token.is_some() => String::new(),
+            None => {
+                return Err(ConfigError::new(
+                    &self.origin,
+                    "missing setting \"password\", \"password_file\" or \"password_env\"",
+                ))
+            }
+        };
+
+        let mut builder = SyncClientBuilder::new(url, 0, user.to_string(), password);
+        builder.collection = self.collection;
+        if let Some(token) = token {
+            builder = builder.bearer_token(token);
+        }
+        if let Some(version) = &self.api_version {
+            builder = builder.api_version(self.api_version(version)?);
+        }
+        if let Some(profile) = &self.profile {
+            builder = builder.profile(self.profile(profile)?);
+        }
+        if let Some(timeout) = self.seconds("timeout", self.timeout)? {
+            builder = builder.timeout(timeout);
+        }
+        if let Some(timeout) = self.seconds("connect_timeout", self.connect_timeout)? {
+            builder = builder.connect_timeout(timeout);
+        }
+        if let Some(attempts) = self.retries {
+            builder = builder.retry(if attempts > 1 {
+                Some(RetryPolicy::new(attempts))
+            } else {
+                None
+            });
+        }
+        if let Some(user_agent) = &self.user_agent {
+            builder = builder.user_agent(user_agent.as_str());
+        }
+        if let Some(proxy) = &self.proxy {
+            builder = builder.proxy(proxy.as_str());
+        }
+        match &self.no_proxy {
+            Some(Hosts::List(hosts)) => {
+                builder = builder.no_proxy(hosts.iter().map(String::as_str))
+            }
+            Some(Hosts::Text(hosts)) => builder = builder.no_proxy(hosts.split(',')),
+            None => {}
+        }
+        Ok(builder)
+    }
+
+    fn api_version(&self, version: &Version) -> Result<ApiVersion, ConfigError> {
+        let text = match version {
+            Version::Number(number) => number.to_string(),
+            Version::Text(text) => text.clone(),
+        };
+        match text.trim_start_matches(&['v', 'V'][..]) {
+            "1" | "1.0" => Ok(ApiVersion::V1),
+            "2" | "2.0" => Ok(ApiVersion::V2),
+            "3" | "3.0" => Ok(ApiVersion::V3),
+            _ => Err(ConfigError::new(
+                &self.origin,
+                format!("unknown API version {:?}", text),
+            )),
+        }
+    }
+
+    fn profile(&self, profile: &str) -> Result<Profile, ConfigError> {
+        match profile {
+            "iot" => Ok(Profile::Iot),
+            "backfill" => Ok(Profile::Backfill),
+            "interactive" => Ok(Profile::Interactive),
+            _ => Err(ConfigError::new(
+                &self.origin,
+                format!("unknown profile {:?}", profile),
+            )),
+        }
+    }
+}
+
+impl SyncClient {
+    /// Creates a client configured by `NEXTCLOUD_ANALYTICS_*` environment variables, e.g. in a
+    /// container: `NEXTCLOUD_ANALYTICS_URL`, `NEXTCLOUD_ANALYTICS_COLLECTION`,
+    /// `NEXTCLOUD_ANALYTICS_USER` and `NEXTCLOUD_ANALYTICS_PASSWORD`, plus the optional settings
+    /// described in [`SyncClient::from_config_file`], in upper case.
+    pub fn from_env() -> Result<SyncClient, AnalyticsError> {
+        Settings::from_env()?.client()
+    }
+
+    /// Creates a client configured by a TOML file, or a YAML file if its extension is `.yaml` or
+    /// `.yml`:
+    ///
+    /// ```toml
+    /// url = "https://example.com/nextcloud"
+    /// collection = 3
+    /// user = "myself"
+    /// password_file = "/run/secrets/nextcloud"
+    /// timeout = 10
+    /// retries = 3
+    /// ```
+    ///
+    /// The password is given by `password`, read from the file at `password_file` without its
+    /// trailing newline, or read from the environment variable named by `password_env`; a
+    /// bearer token can be given instead by `token` or `token_file`. The optional settings are
+    /// `api_version` (`1.0`, `2.0` or `3.0`), `profile` (`iot`, `backfill` or `interactive`),
+    /// `timeout` and `connect_timeout` in seconds, `retries` (the maximum number of attempts per
+    /// request), `user_agent`, `proxy` and `no_proxy` (a list of hosts, or a comma-separated
+    /// string), matching the methods of [`SyncClientBuilder`].
+    ///
+    /// Unknown settings are rejected. TOML files require the `toml` feature, and YAML files the
+    /// `yaml` feature.
+    pub fn from_config_file<P: AsRef<Path>>(path: P) -> Result<SyncClient, AnalyticsError> {
+        Settings::from_file(path.as_ref())?.client()
+    }
+}
+
+impl SyncClientBuilder {
+    /// A builder configured by environment variables like [`SyncClient::from_env`], to tune
+    /// the configuration further. The collection is optional, and can then be set with
+    /// [`SyncClientBuilder::collection`].
+    pub fn from_env() -> Result<SyncClientBuilder, AnalyticsError> {
+        Ok(Settings::from_env()?.builder()?)
+    }
+
+    /// A builder configured by a file like [`SyncClient::from_config_file`], to tune the
+    /// configuration further. The collection is optional, and can then be set with
+    /// [`SyncClientBuilder::collection`].
+    pub fn from_config_file<P: AsRef<Path>>(path: P) -> Result<SyncClientBuilder, AnalyticsError> {
+        Ok(Settings::from_file(path.as_ref())?.builder()?)
+    }
+}
@@ -0,0 +1,218 @@
+use std::fmt;
+use std::sync::{Arc, RwLock};
+
+#[cfg(any(feature = "blocking", feature = "async"))]
+use reqwest as http;
+
+#[cfg(feature = "blocking")]
+use crate::login::AppCredentials;
+#[cfg(feature = "blocking")]
+use crate::SyncClient;
+
+/// The user and secret a client authenticates with, shared by all the clients given the same
+/// credentials and by their clones, so that they can be rotated at runtime, e.g. after logging
+/// in again, without rebuilding the clients.
+///
+/// ```no_run
+/// # use nextcloud_analytics_rs::{Credentials, SyncClient};
+/// let credentials = Credentials::password("myself", "hunter2");
+/// let client = SyncClient::builder("https://example.com/nextcloud", 3, "myself", "")
+///     .credentials(credentials.clone())
+///     .build()
+///     .unwrap();
+/// let worker = client.clone();
+///
+/// // Later, once a new app password has been issued.
+/// credentials.set_password("correct horse battery staple");
+/// ```
+///
+/// With the `zeroize` feature, the secrets are wiped from memory when they are replaced, and
+/// when the last clone of the credentials is dropped.
+#[derive(Clone)]
+pub struct Credentials {
+    secret: Arc<RwLock<Secret>>,
+}
+
+pub(crate) struct Secret {
+    user: String,
+    pub(crate) passwd: String,
+    /// Sent instead of the password if set.
+    pub(crate) bearer_token: Option<String>,
+}
+
+impl Credentials {
+    /// Authenticate as `user` with a password, preferably an app password, see
+    /// [`login`](crate::login).
+    pub fn password<U: Into<String>, P: Into<String>>(user: U, passwd: P) -> Self {
+        Self::from_secret(Secret {
+            user: user.into(),
+            passwd: passwd.into(),
+            bearer_token: None,
+        })
+    }
+
+    /// Authenticate with a bearer token, see
+    /// [`SyncClientBuilder::bearer_token`](crate::SyncClientBuilder::bearer_token); the name
+    /// of the `user` is still needed for the paths of Nextcloud Files.
+    pub fn bearer_token<U: Into<String>, T: Into<String>>(user: U, token: T) -> Self {
+        Self::from_secret(Secret {
+            user: user.into(),
+            passwd: String::new(),
+            bearer_token: Some(token.into()),
+        })
+    }
+
+    fn from_secret(secret: Secret) -> Self {
+        Self {
+            secret: Arc::new(RwLock::new(secret)),
+        }
+    }
+
+    /// Name of the user to authenticate as.
+    pub fn user(&self) -> String {
+        self.secret.read().unwrap().user.clone()
+    }
+
+    /// Whether requests are authenticated with a bearer token rather than a password.
+    pub fn uses_bearer_token(&self) -> bool {
+        self.secret.read().unwrap().bearer_token.is_some()
+    }
+
+    /// Authenticate with a new password from now on, instead of the previous password or
+    /// bearer token.
+    pub fn set_password<P: Into<String>>(&self, passwd: P) {
+        self.secret.write().unwrap().set_password(passwd.into());
+    }
+
+    /// Authenticate with a new bearer token from now on, e.g. after refreshing an expired one,
+    /// instead of the previous bearer token or password.
+    pub fn set_bearer_token<T: Into<String>>(&self, token: T) {
+        let mut secret = self.secret.write().unwrap();
+        if let Some(token) = secret.bearer_token.replace(token.into()) {
+            discard(token);
+        }
+        discard(std::mem::take(&mut secret.passwd));
+    }
+
+    /// Authenticate with the user and app password obtained by logging in again, see
+    /// [`LoginFlow`](crate::login::LoginFlow).
+    #[cfg(feature = "blocking")]
+    pub fn set_app_credentials(&self, app: &AppCredentials) {
+        let mut secret = self.secret.write().unwrap();
+        secret.user = app.login_name.clone();
+        secret.set_password(app.app_password.clone());
+    }
+
+    /// Adds the credentials to a blocking request.
+    #[cfg(feature = "blocking")]
+    pub(crate) fn authorize(
+        &self,
+        req: http::blocking::RequestBuilder,
+    ) -> http::blocking::RequestBuilder {
+        let secret = self.secret.read().unwrap();
+        match &secret.bearer_token {
+            Some(token) => req.bearer_auth(token),
+            None => req.basic_auth(&secret.user, Some(&secret.passwd)),
+        }
+    }
+
+    /// Adds the credentials to an asynchronous request.
+    #[cfg(feature = "async")]
+    pub(crate) fn authorize_async(&self, req: http::RequestBuilder) -> http::RequestBuilder {
+        let secret = self.secret.read().unwrap();
+        match &secret.bearer_token {
+            Some(token) => req.bearer_auth(token),
+            #[cfg(not(target_arch = "wasm32"))]
+            None => req.basic_auth(&secret.user, Some(&secret.passwd)),
+            // The wasm32 request builder has no basic authentication.
+            #[cfg(target_arch = "wasm32")]
+            None => req.header(
+                http::header::AUTHORIZATION,
+                basic_auth(&secret.user, &secret.passwd),
+            ),
+        }
+    }
+}
+
+/// The value of an `Authorization` header for basic authentication, as `reqwest` sends it.
+#[cfg(all(feature = "async", target_arch = "wasm32"))]
+fn basic_auth(user: &str, passwd: &str) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let bytes = format!("{}:{}", user, passwd).into_bytes();
+    let mut header = String::from("Basic ");
+    for chunk in bytes.chunks(3) {
+        let group = chunk.iter().enumerate().fold(0u32, |group, (i, &byte)| {
+            group | u32::from(byte) << (16 - 8 * i)
+        });
+        for i in 0..4 {
+            if i <= chunk.len() {
+                header.push(ALPHABET[(group >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                header.push('=');
+            }
+        }
+    }
+    header
+}
+
+impl Secret {
+    fn set_password(&mut self, passwd: String) {
+        discard(std::mem::replace(&mut self.passwd, passwd));
+        if let Some(token) = self.bearer_token.take() {
+            discard(token);
+        }
+    }
+}
+
+#[cfg(feature = "blocking")]
+impl From<AppCredentials> for Credentials {
+    fn from(mut app: AppCredentials) -> Self {
+        // Taken rather than moved, as the credentials may wipe themselves when dropped.
+        Self::password(
+            std::mem::take(&mut app.login_name),
+            std::mem::take(&mut app.app_password),
+        )
+    }
+}
+
+/// Shows the user and the kind of authentication, never the secrets.
+impl fmt::Debug for Credentials {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let secret = self.secret.read().unwrap();
+        f.debug_struct("Credentials")
+            .field("user", &secret.user)
+            .field(
+                "auth",
+                &if secret.bearer_token.is_some() {
+                    "bearer"
+                } else {
+                    "basic"
+                },
+            )
+            .finish()
+    }
+}
+
+/// Drops a secret that has been replaced, wiping it first with the `zeroize` feature.
+#[cfg_attr(not(feature = "zeroize"), allow(unused_mut))]
+fn discard(mut secret: String) {
+    #[cfg(feature = "zeroize")]
+    zeroize::Zeroize::zeroize(&mut secret);
+    drop(secret);
+}
+
+#[cfg(feature = "blocking")]
+impl SyncClient {
+    /// Authenticate with the given credentials instead of the user and password given when
+    /// creating the client, e.g. to share them with other clients.
+    pub fn with_credentials(mut self, credentials: Credentials) -> Self {
+        self.credentials = credentials;
+        self
+    }
+
+    /// The credentials of the client, shared with its clones, to rotate them, see
+    /// [`Credentials`].
+    pub fn credentials(&self) -> &Credentials {
+        &self.credentials
+    }
+}
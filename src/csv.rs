@@ -0,0 +1,387 @@
+use core::fmt;
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read};
+use std::path::Path;
+use std::time::Instant;
+
+#[cfg(feature = "chrono")]
+use chrono::{NaiveDate, NaiveDateTime, TimeZone, Utc};
+
+use crate::progress::{CountingReader, Tracker};
+use crate::{
+    parse_number, AnalyticsError, BulkSummary, DataPoint, NumberFormat, Progress, SyncClient,
+};
+
+/// A column of a CSV file, by position or by header name.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Column {
+    /// Position of the column, starting at 0.
+    Index(usize),
+    /// Name of the column, in the header row.
+    Header(String),
+}
+
+impl From<usize> for Column {
+    fn from(index: usize) -> Self {
+        Column::Index(index)
+    }
+}
+
+impl From<&str> for Column {
+    fn from(name: &str) -> Self {
+        Column::Header(name.to_string())
+    }
+}
+
+/// Which columns of a CSV file go into which dimension, and how to read them, see
+/// [`SyncClient::send_csv`].
+#[derive(Clone, Debug)]
+pub struct CsvMapping {
+    dimension1: Column,
+    dimension2: Column,
+    value: Column,
+    delimiter: char,
+    headers: bool,
+    number_format: NumberFormat,
+    batch_size: usize,
+    #[cfg(feature = "chrono")]
+    date_format: Option<String>,
+}
+
+impl CsvMapping {
+    /// Maps the given columns to `dimension1`, `dimension2` and the value, for a file with a
+    /// header row and comma-separated fields.
+    pub fn new<C1, C2, C3>(dimension1: C1, dimension2: C2, value: C3) -> Self
+    where
+        C1: Into<Column>,
+        C2: Into<Column>,
+        C3: Into<Column>,
+    {
+        Self {
+            dimension1: dimension1.into(),
+            dimension2: dimension2.into(),
+            value: value.into(),
+            delimiter: ',',
+            headers: true,
+            number_format: NumberFormat::default(),
+            batch_size: 500,
+            #[cfg(feature = "chrono")]
+            date_format: None,
+        }
+    }
+
+    /// Character separating the fields, e.g. `';'`. Defaults to `','`.
+    pub fn delimiter(mut self, delimiter: char) -> Self {
+        self.delimiter = delimiter;
+        self
+    }
+
+    /// Whether the first row holds the column names. Defaults to `true`; columns can only be
+    /// referred to by position otherwise.
+    pub fn headers(mut self, headers: bool) -> Self {
+        self.headers = headers;
+        self
+    }
+
+    /// How numbers are formatted in the value column. Defaults to a decimal point.
+    pub fn number_format(mut self, format: NumberFormat) -> Self {
+        self.number_format = format;
+        self
+    }
+
+    /// Number of rows sent per request. Defaults to 500.
+    pub fn batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    /// Parse the `dimension2` column as a date or a date and time in UTC, with the given
+    /// [`chrono` format](chrono::format::strftime), e.g. `"%d/%m/%Y %H:%M"`, to send it as
+    /// timeline data, truncated to the resolution set with [`SyncClient::align_to`], if any.
+    ///
+    /// Requires the `chrono` feature.
+    #[cfg(feature = "chrono")]
+    pub fn date_format<S: Into<String>>(mut self, format: S) -> Self {
+        self.date_format = Some(format.into());
+        self
+    }
+
+    fn position(&self, column: &Column, headers: &[String]) -> Result<usize, CsvError> {
+        match column {
+            Column::Index(index) => Ok(*index),
+            Column::Header(name) => headers
+                .iter()
+                .position(|header| header == name)
+                .ok_or_else(|| CsvError::new(1, format!("no column named {:?}", name))),
+        }
+    }
+}
+
+/// Positions of the mapped columns in a file.
+struct Positions {
+    dimension1: usize,
+    dimension2: usize,
+    value: usize,
+}
+
+impl SyncClient {
+    /// Streams a CSV file, turning its rows into data points according to `mapping`, and sends
+    /// them in batches with [`SyncClient::send_batch`], e.g. to backfill historical data from
+    /// an export.
+    ///
+    /// `progress` is called with the rows sent and bytes read so far after every batch.
+    /// Returns the number of rows sent; the summary of the import is reported to the targets
+    /// set with [`SyncClient::with_summary_target`]. A malformed row stops the import with a
+    /// [`CsvError`], after the previous batches have been sent.
+    pub fn send_csv<R, F>(
+        &self,
+        reader: R,
+        mapping: &CsvMapping,
+        progress: F,
+    ) -> Result<u64, AnalyticsError>
+    where
+        R: Read,
+        F: FnMut(&Progress),
+    {
+        self.import_csv(reader, None, mapping, progress)
+    }
+
+    /// Like [`SyncClient::send_csv`], for the CSV file at `path`, whose size gives the progress
+    /// reports a [fraction](Progress::fraction) and an [ETA](Progress::eta).
+    pub fn send_csv_file<P, F>(
+        &self,
+        path: P,
+        mapping: &CsvMapping,
+        progress: F,
+    ) -> Result<u64, AnalyticsError>
+    where
+        P: AsRef<Path>,
+        F: FnMut(&Progress),
+    {
+        let file = File::open(path)?;
+        let size = file.metadata()?.len();
+        self.import_csv(file, Some(size), mapping, progress)
+    }
+
+    fn import_csv<R, F>(
+        &self,
+        reader: R,
+        size: Option<u64>,
+        mapping: &CsvMapping,
+        mut progress: F,
+    ) -> Result<u64, AnalyticsError>
+    where
+        R: Read,
+        F: FnMut(&Progress),
+    {
+        let start = Instant::now();
+        let tracker = Tracker::new(None, size);
+        let mut sent = 0;
+        let result = self.send_records(reader, mapping, |rows, bytes| {
+            sent = rows;
+            progress(&tracker.progress(rows, bytes))
+        });
+
+        let mut summary = BulkSummary::new("CSV import");
+        summary.added = sent;
+        if result.is_err() {
+            summary.failures = 1;
+        }
+        summary.duration = start.elapsed();
+        self.report_summary(&summary);
+
+        result.map(|()| sent)
+    }
+
+    fn send_records<R, F>(
+        &self,
+        reader: R,
+        mapping: &CsvMapping,
+        mut progress: F,
+    ) -> Result<(), AnalyticsError>
+    where
+        R: Read,
+        F: FnMut(u64, u64),
+    {
+        let mut records = Records {
+            reader: BufReader::new(CountingReader::new(reader)),
+            delimiter: mapping.delimiter,
+            line: 0,
+        };
+
+        let headers = if mapping.headers {
+            records.next_record()?.unwrap_or_default()
+        } else {
+            Vec::new()
+        };
+        let positions = Positions {
+            dimension1: mapping.position(&mapping.dimension1, &headers)?,
+            dimension2: mapping.position(&mapping.dimension2, &headers)?,
+            value: mapping.position(&mapping.value, &headers)?,
+        };
+
+        let mut sent = 0;
+        let mut batch = Vec::with_capacity(mapping.batch_size);
+        while let Some(record) = records.next_record()? {
+            if record.iter().all(|field| field.is_empty()) {
+                continue;
+            }
+            batch.push(self.csv_point(&record, &positions, mapping, records.line)?);
+
+            if batch.len() == mapping.batch_size {
+                let _ = self.send_batch(&batch)?;
+                sent += batch.len() as u64;
+                batch.clear();
+                progress(sent, records.bytes_read());
+            }
+        }
+
+        if !batch.is_empty() {
+            let _ = self.send_batch(&batch)?;
+            sent += batch.len() as u64;
+            progress(sent, records.bytes_read());
+        }
+        Ok(())
+    }
+
+    fn csv_point(
+        &self,
+        record: &[String],
+        positions: &Positions,
+        mapping: &CsvMapping,
+        line: usize,
+    ) -> Result<DataPoint, CsvError> {
+        let field = |position: usize| {
+            record
+                .get(position)
+                .ok_or_else(|| CsvError::new(line, format!("missing column {}", position)))
+        };
+
+        let dimension1 = field(positions.dimension1)?.clone();
+        let raw_value = field(positions.value)?;
+        let value = parse_number(raw_value, mapping.number_format).ok_or_else(|| {
+            CsvError::new(line, format!("the value isn't a number: {:?}", raw_value))
+        })?;
+
+        let dimension2 = field(positions.dimension2)?;
+        #[cfg(feature = "chrono")]
+        let dimension2 = match &mapping.date_format {
+            Some(format) => {
+                let time = NaiveDateTime::parse_from_str(dimension2, format)
+                    .or_else(|_| {
+                        NaiveDate::parse_from_str(dimension2, format)
+                            .map(|date| date.and_hms_opt(0, 0, 0).unwrap_or_default())
+                    })
+                    .map_err(|err| {
+                        CsvError::new(line, format!("invalid date {:?}: {}", dimension2, err))
+                    })?;
+                self.format_time(Utc.from_utc_datetime(&time))
+                    .map_err(|err| CsvError::new(line, err.to_string()))?
+            }
+            None => dimension2.clone(),
+        };
+        #[cfg(not(feature = "chrono"))]
+        let dimension2 = dimension2.clone();
+
+        Ok(DataPoint::new(dimension1, dimension2, value))
+    }
+}
+
+/// Reads the records of a CSV file, as described by RFC 4180: fields may be quoted, with
+/// doubled quotes inside, and contain delimiters and line breaks.
+struct Records<R> {
+    reader: BufReader<R>,
+    delimiter: char,
+    /// Line number where the last record read ends.
+    line: usize,
+}
+
+impl<R: Read> Records<CountingReader<R>> {
+    /// Number of bytes of the records read so far.
+    fn bytes_read(&self) -> u64 {
+        self.reader.get_ref().count - self.reader.buffer().len() as u64
+    }
+}
+
+impl<R: Read> Records<R> {
+    fn next_record(&mut self) -> Result<Option<Vec<String>>, AnalyticsError> {
+        let mut fields = Vec::new();
+        let mut field = String::new();
+        let mut quoted = false;
+        let mut line = String::new();
+        let mut started = false;
+
+        loop {
+            line.clear();
+            if self.reader.read_line(&mut line)? == 0 {
+                if quoted {
+                    return Err(CsvError::new(self.line, "unclosed quoted field").into());
+                }
+                if !started {
+                    return Ok(None);
+                }
+                fields.push(field);
+                return Ok(Some(fields));
+            }
+            self.line += 1;
+            started = true;
+
+            let mut chars = line.chars().peekable();
+            while let Some(c) = chars.next() {
+                match c {
+                    '"' if quoted && chars.peek() == Some(&'"') => {
+                        chars.next();
+                        field.push('"');
+                    }
+                    '"' if quoted => quoted = false,
+                    '"' if field.is_empty() => quoted = true,
+                    c if quoted => field.push(c),
+                    c if c == self.delimiter => fields.push(std::mem::take(&mut field)),
+                    '\r' | '\n' => {}
+                    c => field.push(c),
+                }
+            }
+
+            if !quoted {
+                fields.push(field);
+                return Ok(Some(fields));
+            }
+        }
+    }
+}
+
+/// A CSV file couldn't be read according to its [`CsvMapping`].
+pub struct CsvError {
+    line: usize,
+    message: String,
+}
+
+impl CsvError {
+    fn new<S: Into<String>>(line: usize, message: S) -> Self {
+        Self {
+            line,
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Debug for CsvError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+impl fmt::Display for CsvError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid CSV at line {}: {}", self.line, self.message)
+    }
+}
+
+impl Error for CsvError {}
+
+impl From<CsvError> for AnalyticsError {
+    fn from(err: CsvError) -> Self {
+        AnalyticsError::local(err)
+    }
+}
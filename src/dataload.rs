@@ -0,0 +1,247 @@
+//! Server-side scheduled data loads of the Analytics app.
+//!
+//! Data loads import data into a dataset from a data source configured on the server (a file in
+//! Nextcloud Files, a web page, an external database...). They use the Analytics app's own
+//! routes rather than its versioned API, so they may change between Analytics releases.
+//!
+//! The same goes for [`SyncClient::import_via_files`], which imports a whole file at once.
+
+use std::fs::File;
+use std::path::Path;
+use std::time::Instant;
+
+use reqwest as http;
+use serde_json::{json, Value};
+
+use crate::{as_u64, AnalyticsError, BulkSummary, SyncClient};
+
+/// A data load, as configured in the dataset's maintenance view.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DataLoad {
+    /// Identifier of the data load.
+    pub id: u64,
+    /// Dataset the data load imports into.
+    pub dataset: u64,
+    /// Name of the data load.
+    pub name: String,
+    /// Kind of data source, as an Analytics identifier.
+    pub datasource: u64,
+    /// Schedule of the data load, e.g. `"d"` for daily, if it's scheduled.
+    pub schedule: Option<String>,
+    /// Options of the data source, as a raw JSON string.
+    pub options: String,
+}
+
+impl DataLoad {
+    fn from_json(value: &Value) -> Option<Self> {
+        let schedule = value["schedule"]
+            .as_str()
+            .filter(|schedule| !schedule.is_empty() && *schedule != "none")
+            .map(str::to_string);
+
+        Some(Self {
+            id: as_u64(&value["id"])?,
+            dataset: as_u64(&value["dataset"]).unwrap_or(0),
+            name: value["name"].as_str().unwrap_or_default().to_string(),
+            datasource: as_u64(&value["datasource"]).unwrap_or(0),
+            schedule,
+            options: value["option"].as_str().unwrap_or_default().to_string(),
+        })
+    }
+}
+
+/// Outcome of the execution of a data load.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DataLoadReport {
+    /// Number of rows inserted.
+    pub inserted: u64,
+    /// Number of existing rows updated.
+    pub updated: u64,
+    /// Number of rows that couldn't be imported.
+    pub errors: u64,
+}
+
+impl DataLoadReport {
+    fn from_json(resp: &Value) -> Self {
+        Self {
+            inserted: as_u64(&resp["insert"]).unwrap_or(0),
+            updated: as_u64(&resp["update"]).unwrap_or(0),
+            errors: as_u64(&resp["error"]).unwrap_or(0),
+        }
+    }
+}
+
+/// Folder of the user's Nextcloud Files where [`SyncClient::import_via_files`] uploads files.
+pub const IMPORT_FOLDER: &str = "Analytics imports";
+
+impl SyncClient {
+    /// Lists the data loads configured for the client's collection.
+    pub fn list_data_loads(&self) -> Result<Vec<DataLoad>, AnalyticsError> {
+        let resp = self.request_json(
+            http::Method::GET,
+            &format!("apps/analytics/dataload/{}", self.collection),
+            None,
+        )?;
+
+        Ok(resp["dataloads"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(DataLoad::from_json)
+            .collect())
+    }
+
+    /// Creates a new data load for the client's collection, importing from the given kind of
+    /// data source, and returns its identifier. Its options have to be configured in the web
+    /// interface, or with [`SyncClient::update_data_load`].
+    pub fn create_data_load(&self, datasource: u64) -> Result<u64, AnalyticsError> {
+        let resp = self.request_json(
+            http::Method::POST,
+            "apps/analytics/dataload",
+            Some(json!({
+                "datasetId": self.collection,
+                "datasourceId": datasource,
+            })),
+        )?;
+
+        as_u64(&resp).ok_or_else(|| {
+            AnalyticsError::Parse(format!("unexpected data load creation response: {}", resp))
+        })
+    }
+
+    /// Updates a data load: its name, data source options (a JSON string, as displayed by the
+    /// web interface) and optional schedule.
+    pub fn update_data_load(
+        &self,
+        id: u64,
+        name: &str,
+        datasource: u64,
+        options: &str,
+        schedule: Option<&str>,
+    ) -> Result<(), AnalyticsError> {
+        self.request_json(
+            http::Method::PUT,
+            &format!("apps/analytics/dataload/{}", id),
+            Some(json!({
+                "name": name,
+                "datasourceId": datasource,
+                "option": options,
+                "schedule": schedule.unwrap_or("none"),
+            })),
+        )?;
+        Ok(())
+    }
+
+    /// Runs a data load now, e.g. right after uploading its source file to Nextcloud Files.
+    pub fn execute_data_load(&self, id: u64) -> Result<DataLoadReport, AnalyticsError> {
+        let start = Instant::now();
+        let result = self
+            .request_json(
+                http::Method::POST,
+                "apps/analytics/dataload/execute",
+                Some(json!({ "dataloadId": id })),
+            )
+            .map(|resp| DataLoadReport::from_json(&resp));
+        self.report_load(format!("execution of data load {}", id), start, &result);
+        result
+    }
+
+    /// Imports a whole CSV file into the given dataset, by uploading it to Nextcloud Files and
+    /// asking Analytics to import it server-side, which is much faster than sending rows one by
+    /// one for very large datasets.
+    ///
+    /// The file is uploaded to the [`IMPORT_FOLDER`] folder of the user's files, with the same
+    /// name as the local file, replacing any previous upload.
+    pub fn import_via_files<P: AsRef<Path>>(
+        &self,
+        path: P,
+        dataset: u32,
+    ) -> Result<DataLoadReport, AnalyticsError> {
+        let path = path.as_ref();
+        let start = Instant::now();
+        let result = self.upload_and_import(path, dataset);
+        self.report_load(format!("import of {}", path.display()), start, &result);
+        result
+    }
+
+    fn upload_and_import(
+        &self,
+        path: &Path,
+        dataset: u32,
+    ) -> Result<DataLoadReport, AnalyticsError> {
+        let file_name = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .ok_or_else(|| {
+                AnalyticsError::local(format!("invalid file name for upload: {}", path.display()))
+            })?;
+
+        let invalid_base_url =
+            || AnalyticsError::local(format!("invalid base URL: {}", self.base_url));
+        let mut folder_url = http::Url::parse(&self.base_url).map_err(|_| invalid_base_url())?;
+        folder_url
+            .path_segments_mut()
+            .map_err(|()| invalid_base_url())?
+            .pop_if_empty()
+            .extend(&[
+                "remote.php",
+                "dav",
+                "files",
+                &self.credentials.user(),
+                IMPORT_FOLDER,
+            ]);
+
+        // Create the folder; it fails with 405 Method Not Allowed if it already exists.
+        let mkcol = http::Method::from_bytes(b"MKCOL").map_err(AnalyticsError::local)?;
+        let resp = self.execute(self.client.request(mkcol, folder_url.clone()))?;
+        if !resp.status.is_success() && resp.status != http::StatusCode::METHOD_NOT_ALLOWED {
+            return Err(AnalyticsError::from_response(
+                resp.status,
+                &resp.headers,
+                &resp.body,
+            ));
+        }
+
+        let mut file_url = folder_url;
+        file_url
+            .path_segments_mut()
+            .map_err(|()| AnalyticsError::local("the Nextcloud URL can't have a path"))?
+            .push(file_name);
+
+        let upload = self
+            .client
+            .put(file_url)
+            .header(http::header::CONTENT_TYPE, "text/csv")
+            .body(File::open(path)?);
+        self.send_request(upload)?;
+
+        let resp = self.request_json(
+            http::Method::POST,
+            &format!("apps/analytics/data/importfile/{}", dataset),
+            Some(json!({
+                "path": format!("/{}/{}", IMPORT_FOLDER, file_name),
+            })),
+        )?;
+
+        Ok(DataLoadReport::from_json(&resp))
+    }
+
+    fn report_load(
+        &self,
+        operation: String,
+        start: Instant,
+        result: &Result<DataLoadReport, AnalyticsError>,
+    ) {
+        let mut summary = BulkSummary::new(operation);
+        match result {
+            Ok(report) => {
+                summary.added = report.inserted;
+                summary.updated = report.updated;
+                summary.failures = report.errors;
+            }
+            Err(_) => summary.failures = 1,
+        }
+        summary.duration = start.elapsed();
+        self.report_summary(&summary);
+    }
+}
@@ -0,0 +1,280 @@
+//! Managing datasets and deleting data.
+//!
+//! Data can only be pushed to datasets stored in the Analytics internal database. Datasets
+//! backed by an external data source, e.g. a file, a web page or a realtime JSON API, are read
+//! by the server from their source: configure the client with [`DatasetKind::External`] to read
+//! them, and refresh them with [data loads](crate::dataload).
+//!
+//! Listing datasets and deleting data use the versioned API. Creating, renaming and deleting
+//! datasets use the Analytics app's own routes, like [data loads](crate::dataload), so they may
+//! change between Analytics releases.
+
+use reqwest as http;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::as_u64;
+use crate::{deserialize_flag, AnalyticsError, DataPoint, ResponseError, SyncClient};
+
+/// Type of the datasets stored in the internal database.
+const INTERNAL_DATABASE_TYPE: u64 = 2;
+
+/// Where the data of a dataset comes from, see [`SyncClient::with_dataset_kind`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DatasetKind {
+    /// Stored in the Analytics internal database, the only kind of dataset data can be pushed to.
+    #[default]
+    InternalDatabase,
+    /// Read by the server from an external data source, with the kind of data source as an
+    /// Analytics identifier, as in [`DataLoad::datasource`](crate::dataload::DataLoad).
+    External(u64),
+}
+
+impl DatasetKind {
+    /// Datasets without a type are those of Analytics 4, always in the internal database.
+    fn from_json(value: &Value) -> Self {
+        match as_u64(value) {
+            None | Some(INTERNAL_DATABASE_TYPE) => DatasetKind::InternalDatabase,
+            Some(datasource) => DatasetKind::External(datasource),
+        }
+    }
+}
+
+/// A dataset, as listed by [`SyncClient::list_datasets`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Dataset {
+    /// Identifier of the dataset, to pass as the collection index of a client.
+    pub id: u64,
+    /// Name of the dataset, as displayed in the web interface.
+    pub name: String,
+    /// Where the data of the dataset comes from.
+    pub kind: DatasetKind,
+}
+
+impl Dataset {
+    fn from_json(value: &Value) -> Option<Self> {
+        Some(Self {
+            id: as_u64(&value["id"])?,
+            name: value["name"].as_str().unwrap_or_default().to_string(),
+            kind: DatasetKind::from_json(&value["type"]),
+        })
+    }
+}
+
+impl SyncClient {
+    /// Lists the datasets the user has access to.
+    ///
+    /// This uses the 2.0 API, unless the client has been configured for the 3.0 API.
+    pub fn list_datasets(&self) -> Result<Vec<Dataset>, AnalyticsError> {
+        let resp = self.request_json(http::Method::GET, self.api_version.datasets_path(), None)?;
+
+        // Some versions wrap the list in a `data` field.
+        let list = if resp.is_array() {
+            &resp
+        } else {
+            &resp["data"]
+        };
+        Ok(list
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(Dataset::from_json)
+            .collect())
+    }
+
+    /// Creates a new dataset, with names for its two dimensions and its value as displayed in
+    /// reports, and returns its identifier, e.g. to bootstrap collections from a deployment
+    /// script.
+    pub fn create_dataset(
+        &self,
+        name: &str,
+        dimension1: &str,
+        dimension2: &str,
+        value: &str,
+    ) -> Result<u64, AnalyticsError> {
+        let resp = self.request_json(
+            http::Method::POST,
+            "apps/analytics/dataset",
+            Some(json!({
+                "name": name,
+                "dimension1": dimension1,
+                "dimension2": dimension2,
+                "value": value,
+            })),
+        )?;
+
+        as_u64(&resp).ok_or_else(|| {
+            AnalyticsError::Parse(format!("unexpected dataset creation response: {}", resp))
+        })
+    }
+
+    /// Renames the dataset with the given identifier.
+    pub fn rename_dataset(&self, id: u64, name: &str) -> Result<(), AnalyticsError> {
+        self.request_json(
+            http::Method::PUT,
+            &format!("apps/analytics/dataset/{}", id),
+            Some(json!({ "name": name })),
+        )?;
+        Ok(())
+    }
+
+    /// Deletes the dataset with the given identifier, with all its data.
+    pub fn delete_dataset(&self, id: u64) -> Result<(), AnalyticsError> {
+        self.request_json(
+            http::Method::DELETE,
+            &format!("apps/analytics/dataset/{}", id),
+            None,
+        )?;
+        Ok(())
+    }
+
+    /// Deletes the data of the client's dataset matching `dimension1` and `dimension2`. Either
+    /// can be `"*"` to match any value.
+    ///
+    /// This uses the 2.0 API, unless the client has been configured for the 3.0 API.
+    pub fn delete_data(&self, dimension1: &str, dimension2: &str) -> Result<(), AnalyticsError> {
+        self.check_writable()?;
+        self.delete_rows(&[(dimension1, dimension2)], self.collection)
+    }
+
+    /// Moves the rows of `old_key` in the given collection to `new_key`, keeping their second
+    /// dimension and value, e.g. when the naming of metrics changes after months of collection,
+    /// and returns how many were moved.
+    ///
+    /// The rows are read back, sent again under the new key in one batch, then deleted; nothing
+    /// is deleted if the batch isn't sent, e.g. in dry-run mode. If the deletion fails, both
+    /// keys hold the rows: renaming again moves the remaining ones, and updates the rows
+    /// already moved. Rows which existed under the new key with the same second dimension are
+    /// replaced.
+    ///
+    /// Reading uses the 3.0 API, and deleting the 2.0 API, unless the client has been
+    /// configured for the 3.0 API; the 1.0 API fails without any request.
+    pub fn rename_key(
+        &self,
+        collection: u32,
+        old_key: &str,
+        new_key: &str,
+    ) -> Result<usize, AnalyticsError> {
+        self.check_writable()?;
+        self.api_version.delete_path(collection)?;
+        let rows = self.read_key_rows(collection, old_key)?;
+        if rows.is_empty() || old_key == new_key {
+            return Ok(0);
+        }
+
+        let renamed: Vec<_> = rows
+            .iter()
+            .map(|row| DataPoint::new(new_key, &row.dimension2, row.dimension3))
+            .collect();
+        if !self.send_batch_to(&renamed, collection)?.was_sent() {
+            return Ok(0);
+        }
+        let old: Vec<_> = rows
+            .iter()
+            .map(|row| (old_key, row.dimension2.as_str()))
+            .collect();
+        self.delete_rows(&old, collection)?;
+        Ok(rows.len())
+    }
+
+    /// Replace existing data instead of adding to it: before sending a data point, delete the
+    /// data with the same first two dimensions, e.g. the same key and date, so that re-running a
+    /// job doesn't duplicate rows.
+    ///
+    /// This costs an extra request per send, or per batch with [`SyncClient::send_batch`]. The
+    /// deletion uses the 2.0 API, unless the client has been configured for the 3.0 API.
+    pub fn with_upsert(mut self, enabled: bool) -> Self {
+        self.upsert = enabled;
+        self
+    }
+
+    /// Declare where the data of the client's collection comes from. Defaults to
+    /// [`DatasetKind::InternalDatabase`].
+    ///
+    /// The data of an [external](DatasetKind::External) dataset is read as usual, e.g. with
+    /// [`SyncClient::get_data`], but sending or deleting data fails without any request, since
+    /// the server would refuse it.
+    pub fn with_dataset_kind(mut self, kind: DatasetKind) -> Self {
+        self.dataset_kind = kind;
+        self
+    }
+
+    /// Fails if the client's collection doesn't accept data from the API.
+    pub(crate) fn check_writable(&self) -> Result<(), AnalyticsError> {
+        match self.dataset_kind {
+            DatasetKind::InternalDatabase => Ok(()),
+            DatasetKind::External(_) => Err(AnalyticsError::local(
+                "the dataset is backed by an external data source and can't be written to, \
+                 refresh it with a data load instead",
+            )),
+        }
+    }
+
+    /// Deletes the data of the points' first two dimensions from the given collection, if in
+    /// upsert mode.
+    pub(crate) fn delete_before_insert(
+        &self,
+        points: &[DataPoint],
+        collection: u32,
+    ) -> Result<(), AnalyticsError> {
+        if !self.upsert {
+            return Ok(());
+        }
+        let rows: Vec<(&str, &str)> = points
+            .iter()
+            .map(|point| (point.dimension1.as_str(), point.dimension2.as_str()))
+            .collect();
+        self.delete_rows(&rows, collection)
+    }
+
+    /// Deletes the data of a collection matching each of the `(dimension1, dimension2)` rows, in
+    /// one request.
+    pub(crate) fn delete_rows(
+        &self,
+        rows: &[(&str, &str)],
+        collection: u32,
+    ) -> Result<(), AnalyticsError> {
+        let delete = rows
+            .iter()
+            .map(|&(dimension1, dimension2)| DeleteRow {
+                dimension1,
+                dimension2,
+            })
+            .collect();
+
+        let resp = self.request_json(
+            http::Method::POST,
+            &self.api_version.delete_path(collection)?,
+            Some(serde_json::to_value(DeleteRequest { delete })?),
+        )?;
+
+        let resp: DeleteResponse = serde_json::from_value(resp)?;
+        if resp.success == Some(false) {
+            let message = resp.error.and_then(|error| error.message);
+            return Err(AnalyticsError::api(format!(
+                "unexpected API response: {}",
+                message.as_deref().unwrap_or("unknown error")
+            )));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Serialize)]
+struct DeleteRequest<'a> {
+    delete: Vec<DeleteRow<'a>>,
+}
+
+#[derive(Serialize)]
+struct DeleteRow<'a> {
+    dimension1: &'a str,
+    dimension2: &'a str,
+}
+
+/// Response of the deletion endpoints, which don't always have a success flag.
+#[derive(Deserialize)]
+struct DeleteResponse {
+    #[serde(default, deserialize_with = "deserialize_flag")]
+    success: Option<bool>,
+    error: Option<ResponseError>,
+}
@@ -0,0 +1,297 @@
+use core::fmt;
+use std::error::Error;
+use std::fmt::Write;
+
+use chrono::{
+    DateTime, Datelike, FixedOffset, Local, NaiveDate, NaiveDateTime, SecondsFormat, TimeZone, Utc,
+};
+
+use crate::AnalyticsError;
+#[cfg(feature = "blocking")]
+use crate::{DataPoint, Receipt, SyncClient};
+
+/// How the dates of timeline data are formatted, see [`SyncClient::with_timestamp_format`].
+///
+/// It must match the date format configured for the dataset in Analytics, or the server rejects
+/// the rows.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum TimestampFormat {
+    /// RFC 2822, e.g. `Tue, 1 Jul 2003 10:52:37 +0000`, the default.
+    #[default]
+    Rfc2822,
+    /// RFC 3339, e.g. `2003-07-01T10:52:37+00:00`.
+    Rfc3339,
+    /// Number of seconds since the Unix epoch, e.g. `1057056757`.
+    UnixEpoch,
+    /// A [`chrono` pattern](chrono::format::strftime), e.g. `"%Y-%m-%d %H:%M"`, in the time
+    /// zone of the client, UTC by default, see [`SyncClient::with_time_zone`].
+    Custom(String),
+}
+
+/// The time zone the dates of timeline data are written in, see
+/// [`SyncClient::with_time_zone`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DatasetTimeZone {
+    /// Coordinated Universal Time, the default.
+    #[default]
+    Utc,
+    /// A fixed offset from UTC, without daylight saving time.
+    Fixed(FixedOffset),
+    /// The time zone of the machine running the client, with its daylight saving time rules.
+    Local,
+}
+
+impl DatasetTimeZone {
+    /// Formats `time` in this time zone with `format`.
+    pub(crate) fn format(
+        &self,
+        format: &TimestampFormat,
+        time: DateTime<Utc>,
+    ) -> Result<String, AnalyticsError> {
+        match self {
+            DatasetTimeZone::Utc => format.format_in(&time),
+            DatasetTimeZone::Fixed(offset) => format.format_in(&time.with_timezone(offset)),
+            DatasetTimeZone::Local => format.format_in(&time.with_timezone(&Local)),
+        }
+    }
+
+    /// The date and time of `time` in this time zone, without its offset.
+    #[cfg(feature = "blocking")]
+    pub(crate) fn local_time(&self, time: DateTime<Utc>) -> NaiveDateTime {
+        match self {
+            DatasetTimeZone::Utc => time.naive_utc(),
+            DatasetTimeZone::Fixed(offset) => time.with_timezone(offset).naive_local(),
+            DatasetTimeZone::Local => time.with_timezone(&Local).naive_local(),
+        }
+    }
+
+    /// The time of a date and time without an offset, read in this time zone; an ambiguous
+    /// local time, when clocks go back, is the earliest of the two.
+    #[cfg(feature = "blocking")]
+    pub(crate) fn read_local(&self, time: &NaiveDateTime) -> Option<DateTime<Utc>> {
+        match self {
+            DatasetTimeZone::Utc => Some(Utc.from_utc_datetime(time)),
+            DatasetTimeZone::Fixed(offset) => offset
+                .from_local_datetime(time)
+                .earliest()
+                .map(|time| time.with_timezone(&Utc)),
+            DatasetTimeZone::Local => Local
+                .from_local_datetime(time)
+                .earliest()
+                .map(|time| time.with_timezone(&Utc)),
+        }
+    }
+}
+
+impl TimestampFormat {
+    /// Formats `time`. Fails with a [`DateRangeError`] for RFC 2822 and RFC 3339 outside of
+    /// years 0 to 9999, and if a custom pattern is invalid.
+    pub fn format(&self, time: DateTime<Utc>) -> Result<String, AnalyticsError> {
+        self.format_in(&time)
+    }
+
+    /// Formats `time` with its own offset, e.g. `Tue, 1 Jul 2003 12:52:37 +0200` for a time in
+    /// Central European Summer Time; the Unix epoch doesn't depend on the offset.
+    pub fn format_in<Tz: TimeZone>(&self, time: &DateTime<Tz>) -> Result<String, AnalyticsError>
+    where
+        Tz::Offset: fmt::Display,
+    {
+        match self {
+            TimestampFormat::Rfc2822 => Ok(rfc2822(time)?),
+            TimestampFormat::Rfc3339 => {
+                check_range(time)?;
+                Ok(time.to_rfc3339_opts(SecondsFormat::AutoSi, false))
+            }
+            TimestampFormat::UnixEpoch => Ok(time.timestamp().to_string()),
+            TimestampFormat::Custom(pattern) => {
+                // Formatting fails, instead of panicking, on invalid patterns only this way.
+                let mut text = String::new();
+                write!(text, "{}", time.format(pattern)).map_err(|_| {
+                    AnalyticsError::local(format!("invalid timestamp format: {:?}", pattern))
+                })?;
+                Ok(text)
+            }
+        }
+    }
+
+    /// Parses a date formatted with this format, e.g. the second dimension of a row read from a
+    /// timeline dataset; dates without a time are at midnight with custom patterns, which are
+    /// read in UTC.
+    pub fn parse(&self, text: &str) -> Option<DateTime<Utc>> {
+        match self {
+            TimestampFormat::Rfc2822 => DateTime::parse_from_rfc2822(text)
+                .ok()
+                .map(|time| time.with_timezone(&Utc)),
+            TimestampFormat::Rfc3339 => DateTime::parse_from_rfc3339(text)
+                .ok()
+                .map(|time| time.with_timezone(&Utc)),
+            TimestampFormat::UnixEpoch => text
+                .trim()
+                .parse()
+                .ok()
+                .and_then(|secs| Utc.timestamp_opt(secs, 0).single()),
+            TimestampFormat::Custom(pattern) => NaiveDateTime::parse_from_str(text, pattern)
+                .or_else(|_| {
+                    NaiveDate::parse_from_str(text, pattern)
+                        .map(|date| date.and_hms_opt(0, 0, 0).unwrap_or_default())
+                })
+                .ok()
+                .map(|time| Utc.from_utc_datetime(&time)),
+        }
+    }
+}
+
+#[cfg(feature = "blocking")]
+impl SyncClient {
+    /// Format the dates of timeline data with `format`, instead of RFC 2822, to match the date
+    /// format of the dataset.
+    pub fn with_timestamp_format(mut self, format: TimestampFormat) -> Self {
+        self.timestamp_format = format;
+        self
+    }
+
+    /// Sends some timeline data like [`SyncClient::send_timeline_data`], formatting the time
+    /// with `format` instead of the client's format.
+    pub fn send_timeline_data_with_format<S: Into<String>, F: Into<f64>>(
+        &self,
+        key: S,
+        time: DateTime<Utc>,
+        value: F,
+        format: &TimestampFormat,
+    ) -> Result<Receipt, AnalyticsError> {
+        let time = self.time_zone.format(format, self.aligned(time))?;
+        self.send_point(DataPoint::new(key.into(), time, value.into()))
+    }
+
+    /// Write the dates of timeline data in `zone` instead of UTC, to match a dataset configured
+    /// in local time: the same instant is then sent with the zone's offset, or as its local
+    /// time with a custom pattern, see [`TimestampFormat::Custom`].
+    ///
+    /// Dates read back with a custom pattern, e.g. by [`SyncClient::latest`], are read in the
+    /// same zone.
+    pub fn with_time_zone(mut self, zone: DatasetTimeZone) -> Self {
+        self.time_zone = zone;
+        self
+    }
+
+    /// Sends some timeline data like [`SyncClient::send_timeline_data`], with a time in any
+    /// time zone, e.g. a `DateTime<FixedOffset>` parsed from a device's log or a time from
+    /// `chrono-tz`; it's converted to the client's time zone, see
+    /// [`SyncClient::with_time_zone`].
+    pub fn send_timeline_data_local<S, Tz, F>(
+        &self,
+        key: S,
+        time: DateTime<Tz>,
+        value: F,
+    ) -> Result<Receipt, AnalyticsError>
+    where
+        S: Into<String>,
+        Tz: TimeZone,
+        F: Into<f64>,
+    {
+        self.send_timeline_data(key, time.with_timezone(&Utc), value)
+    }
+
+    /// Truncates `time` to the resolution set with [`SyncClient::align_to`], if any, and
+    /// formats it with the client's timestamp format, in its time zone, as the second dimension
+    /// of timeline data is sent, e.g. to build a [`DataPoint`] by hand.
+    pub fn format_time(&self, time: DateTime<Utc>) -> Result<String, AnalyticsError> {
+        self.time_zone
+            .format(&self.timestamp_format, self.aligned(time))
+    }
+
+    /// Parses the date dimension of a row of timeline data, in the client's timestamp format
+    /// and time zone.
+    pub(crate) fn parse_time(&self, text: &str) -> Option<DateTime<Utc>> {
+        match &self.timestamp_format {
+            TimestampFormat::Custom(pattern) => NaiveDateTime::parse_from_str(text, pattern)
+                .ok()
+                .or_else(|| {
+                    NaiveDate::parse_from_str(text, pattern)
+                        .ok()
+                        .and_then(|date| date.and_hms_opt(0, 0, 0))
+                })
+                .and_then(|time| self.time_zone.read_local(&time)),
+            format => format.parse(text),
+        }
+    }
+}
+
+/// Formats `time` as the date dimension of timeline data, like the client does with
+/// [`SyncClient::with_timestamp_format`], e.g. to build custom payloads; see
+/// [`TimestampFormat::format`].
+///
+/// Requires the `chrono` feature.
+pub fn format_timeline_date(
+    time: DateTime<Utc>,
+    format: &TimestampFormat,
+) -> Result<String, AnalyticsError> {
+    format.format(time)
+}
+
+/// Parses the date dimension of timeline data, e.g. read back with [`SyncClient::get_data`],
+/// see [`TimestampFormat::parse`].
+///
+/// Requires the `chrono` feature.
+pub fn parse_timeline_date(text: &str, format: &TimestampFormat) -> Option<DateTime<Utc>> {
+    format.parse(text)
+}
+
+/// A date can't be sent as timeline data, because RFC 2822 and RFC 3339 only represent years 0
+/// to 9999.
+pub struct DateRangeError {
+    time: DateTime<Utc>,
+}
+
+impl DateRangeError {
+    /// The date that couldn't be formatted.
+    pub fn time(&self) -> DateTime<Utc> {
+        self.time
+    }
+}
+
+impl fmt::Debug for DateRangeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+impl fmt::Display for DateRangeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "date out of the range of timeline data (years 0 to 9999): {:?}",
+            self.time
+        )
+    }
+}
+
+impl Error for DateRangeError {}
+
+impl From<DateRangeError> for AnalyticsError {
+    fn from(err: DateRangeError) -> Self {
+        AnalyticsError::local(err)
+    }
+}
+
+/// Formats `time` in the RFC 2822 format of timeline data, which chrono refuses to do, by
+/// panicking, outside of years 0 to 9999.
+pub(crate) fn rfc2822<Tz: TimeZone>(time: &DateTime<Tz>) -> Result<String, DateRangeError>
+where
+    Tz::Offset: fmt::Display,
+{
+    check_range(time)?;
+    Ok(time.to_rfc2822())
+}
+
+/// Checks that the year of `time`, in its own time zone, has the four digits of the RFC 2822
+/// and RFC 3339 formats; chrono writes other years with a sign, which can't be parsed back.
+fn check_range<Tz: TimeZone>(time: &DateTime<Tz>) -> Result<(), DateRangeError> {
+    if (0..=9999).contains(&time.year()) {
+        Ok(())
+    } else {
+        Err(DateRangeError {
+            time: time.with_timezone(&Utc),
+        })
+    }
+}
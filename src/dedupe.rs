@@ -0,0 +1,106 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::{DataPoint, SyncClient};
+
+/// Collection, dimensions and value of a data point, with the value's bits so that it can be
+/// compared.
+type Row = (u32, String, String, u64);
+
+fn row(point: &DataPoint, collection: u32) -> Row {
+    (
+        collection,
+        point.dimension1.clone(),
+        point.dimension2.clone(),
+        point.dimension3.to_bits(),
+    )
+}
+
+/// The most recent data points sent, to skip exact duplicates, see [`SyncClient::with_dedupe`].
+#[derive(Clone)]
+pub(crate) struct Dedupe {
+    capacity: usize,
+    window: Duration,
+    recent: Arc<Mutex<VecDeque<(Row, Instant)>>>,
+}
+
+impl Dedupe {
+    /// Data points claimed for sending, to be kept once they were sent.
+    pub fn claims(&self) -> Claims<'_> {
+        Claims {
+            dedupe: self,
+            rows: Vec::new(),
+        }
+    }
+}
+
+/// Data points claimed for sending with [`Claims::claim`]: they are forgotten when dropped,
+/// unless [`Claims::keep`] was called after they were sent.
+pub(crate) struct Claims<'a> {
+    dedupe: &'a Dedupe,
+    rows: Vec<(Row, Instant)>,
+}
+
+impl Claims<'_> {
+    /// Claims a data point for sending to `collection`, unless the same one was sent, or is
+    /// being sent, within the window: checking and recording it is one step, so that two
+    /// threads sending the same data point can't both send it.
+    pub fn claim(&mut self, point: &DataPoint, collection: u32) -> bool {
+        let dedupe = self.dedupe;
+        let mut recent = dedupe.recent.lock().unwrap();
+        let now = Instant::now();
+        while let Some((_, sent)) = recent.front() {
+            if now.duration_since(*sent) <= dedupe.window {
+                break;
+            }
+            recent.pop_front();
+        }
+        let row = row(point, collection);
+        if recent.iter().any(|(sent, _)| *sent == row) {
+            return false;
+        }
+        recent.push_back((row.clone(), now));
+        while recent.len() > dedupe.capacity {
+            recent.pop_front();
+        }
+        self.rows.push((row, now));
+        true
+    }
+
+    /// Keeps the claimed data points, which were sent.
+    pub fn keep(mut self) {
+        self.rows.clear();
+    }
+}
+
+impl Drop for Claims<'_> {
+    fn drop(&mut self) {
+        if self.rows.is_empty() {
+            return;
+        }
+        let mut recent = self.dedupe.recent.lock().unwrap();
+        recent.retain(|claimed| !self.rows.contains(claimed));
+    }
+}
+
+impl SyncClient {
+    /// Skip data points identical to one of the last `capacity` ones sent, if it was sent less
+    /// than `window` ago, e.g. for a sensor loop that sometimes reads the same sample twice:
+    /// the send methods return an [`Outcome::Duplicate`](crate::Outcome::Duplicate) receipt for
+    /// them.
+    ///
+    /// Data points are identical if they go to the same collection with the same three
+    /// dimensions, as given to the send methods with the default dimensions filled in, before
+    /// the key template and enrichments. Duplicates don't count for derived metrics. Only
+    /// successful sends are remembered, so sending again after a failure isn't skipped; a data
+    /// point being sent by another thread is a duplicate, even if that send fails afterwards.
+    pub fn with_dedupe(mut self, capacity: usize, window: Duration) -> Self {
+        self.dedupe = Some(Dedupe {
+            capacity,
+            window,
+            recent: Arc::new(Mutex::new(VecDeque::with_capacity(capacity))),
+        });
+        self
+    }
+}
@@ -0,0 +1,96 @@
+use std::collections::HashMap;
+
+use crate::{AnalyticsError, DataPoint, Receipt, SyncClient};
+
+/// Default values of the first two dimensions of a collection.
+#[derive(Clone, Debug, Default)]
+struct Defaults {
+    dimension1: Option<String>,
+    dimension2: Option<String>,
+}
+
+/// Per-collection default dimensions, see [`SyncClient::with_default_dimension2`].
+#[derive(Clone, Debug, Default)]
+pub(crate) struct DefaultDimensions {
+    collections: HashMap<u32, Defaults>,
+}
+
+impl DefaultDimensions {
+    /// Replaces the empty dimensions of `point` by the defaults of `collection`, if any.
+    pub fn fill(&self, point: &mut DataPoint, collection: u32) {
+        let defaults = match self.collections.get(&collection) {
+            Some(defaults) => defaults,
+            None => return,
+        };
+        if let (true, Some(default)) = (point.dimension1.is_empty(), &defaults.dimension1) {
+            point.dimension1.clone_from(default);
+        }
+        if let (true, Some(default)) = (point.dimension2.is_empty(), &defaults.dimension2) {
+            point.dimension2.clone_from(default);
+        }
+    }
+}
+
+impl SyncClient {
+    /// Use `value` as the first dimension of the data points sent to `collection` with an empty
+    /// one, e.g. for a dataset whose rows all have the same key.
+    ///
+    /// Defaults apply to sends to the client's collection and through
+    /// [`SyncClient::collection`] handles, before the key filter, the key template and the
+    /// enrichments.
+    pub fn with_default_dimension1<S: Into<String>>(mut self, collection: u32, value: S) -> Self {
+        self.default_dimensions
+            .collections
+            .entry(collection)
+            .or_default()
+            .dimension1 = Some(value.into());
+        self
+    }
+
+    /// Use `value` as the second dimension of the data points sent to `collection` with an
+    /// empty one, e.g. the hostname of the machine for a dataset that isn't a timeline, so that
+    /// call sites only pass the key and the value, see [`SyncClient::send_value`].
+    ///
+    /// ```no_run
+    /// # use nextcloud_analytics_rs::SyncClient;
+    /// let hostname = std::env::var("HOSTNAME")?;
+    /// let client = SyncClient::new("https://example.com/nextcloud", 3, "myself", "hunter2")
+    ///     .with_default_dimension2(3, hostname);
+    /// client.send_value("load_average", 0.42)?;
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    ///
+    /// Defaults apply like those of [`SyncClient::with_default_dimension1`].
+    pub fn with_default_dimension2<S: Into<String>>(mut self, collection: u32, value: S) -> Self {
+        self.default_dimensions
+            .collections
+            .entry(collection)
+            .or_default()
+            .dimension2 = Some(value.into());
+        self
+    }
+
+    /// Sends `value` for `key`, with the default second dimension of the client's collection.
+    ///
+    /// Fails without any request if the collection has no default second dimension.
+    pub fn send_value<S: Into<String>, F: Into<f64>>(
+        &self,
+        key: S,
+        value: F,
+    ) -> Result<Receipt, AnalyticsError> {
+        self.check_default_dimension2(self.collection)?;
+        self.send_data(key.into(), String::new(), value.into())
+    }
+
+    /// Fails if `collection` has no default second dimension.
+    pub(crate) fn check_default_dimension2(&self, collection: u32) -> Result<(), AnalyticsError> {
+        let defaults = self.default_dimensions.collections.get(&collection);
+        match defaults.and_then(|defaults| defaults.dimension2.as_ref()) {
+            Some(_) => Ok(()),
+            None => Err(AnalyticsError::local(format!(
+                "collection {} has no default second dimension",
+                collection
+            ))),
+        }
+    }
+}
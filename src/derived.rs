@@ -0,0 +1,104 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::{DataPoint, SyncClient};
+
+type Formula = Arc<dyn Fn(&[f64]) -> f64 + Send + Sync>;
+
+/// A metric computed by the client from the values of other keys, see
+/// [`SyncClient::with_derived`].
+#[derive(Clone)]
+pub struct Derived {
+    key: String,
+    inputs: Vec<String>,
+    window: Duration,
+    formula: Formula,
+}
+
+impl Derived {
+    /// Defines `key` as the result of `formula`, called with the latest values of `inputs`, in
+    /// the same order, e.g. `efficiency` from `output` and `input`:
+    ///
+    /// ```
+    /// # use std::time::Duration;
+    /// # use nextcloud_analytics_rs::Derived;
+    /// let efficiency = Derived::new(
+    ///     "efficiency",
+    ///     &["output", "input"],
+    ///     Duration::from_secs(60),
+    ///     |values| values[0] / values[1],
+    /// );
+    /// ```
+    ///
+    /// The derived key is only computed when all its inputs have been sent within `window` of
+    /// each other.
+    pub fn new<S, F>(key: S, inputs: &[&str], window: Duration, formula: F) -> Self
+    where
+        S: Into<String>,
+        F: Fn(&[f64]) -> f64 + Send + Sync + 'static,
+    {
+        Self {
+            key: key.into(),
+            inputs: inputs.iter().map(|input| input.to_string()).collect(),
+            window,
+            formula: Arc::new(formula),
+        }
+    }
+}
+
+/// Derived metrics of a client, with the latest values of their inputs.
+#[derive(Clone, Default)]
+pub(crate) struct Derivations {
+    rules: Vec<Derived>,
+    latest: Arc<Mutex<HashMap<String, (f64, Instant)>>>,
+}
+
+impl Derivations {
+    /// Records the value of a data point, and returns the derived data points it updates, with
+    /// the same second dimension.
+    pub fn observe(&self, point: &DataPoint) -> Vec<DataPoint> {
+        let key = &point.dimension1;
+        if !self.rules.iter().any(|rule| rule.inputs.contains(key)) {
+            return Vec::new();
+        }
+
+        let now = Instant::now();
+        let mut latest = self.latest.lock().unwrap();
+        latest.insert(key.clone(), (point.dimension3, now));
+
+        self.rules
+            .iter()
+            .filter(|rule| rule.inputs.contains(key))
+            .filter_map(|rule| {
+                let values = rule
+                    .inputs
+                    .iter()
+                    .map(|input| match latest.get(input) {
+                        Some(&(value, at)) if now.duration_since(at) <= rule.window => Some(value),
+                        _ => None,
+                    })
+                    .collect::<Option<Vec<f64>>>()?;
+                Some(DataPoint::new(
+                    rule.key.clone(),
+                    point.dimension2.clone(),
+                    (rule.formula)(&values),
+                ))
+            })
+            .collect()
+    }
+}
+
+impl SyncClient {
+    /// Compute and send a derived metric whenever one of its inputs is sent with
+    /// [`SyncClient::send_point`] or the methods built on it, so that producers don't all
+    /// duplicate the computation.
+    ///
+    /// Inputs are the keys as passed to the send methods, before any key template is applied.
+    /// The derived data point is sent right after the input that updated it, when that one
+    /// succeeded; it isn't itself an input for other derived metrics.
+    pub fn with_derived(mut self, derived: Derived) -> Self {
+        self.derivations.rules.push(derived);
+        self
+    }
+}
@@ -0,0 +1,123 @@
+use core::fmt;
+use std::time::Duration;
+
+use serde::{Serialize, Serializer};
+use serde_json::Value;
+
+use crate::RetryPolicy;
+
+/// The effective configuration of a client, as returned by
+/// [`SyncClient::describe`](crate::SyncClient::describe).
+///
+/// It never contains secrets, so it is safe to log or to attach to a bug report. It serializes
+/// with durations as seconds, e.g. `timeout_secs`.
+#[derive(Clone, Debug, Serialize)]
+pub struct ClientDescription {
+    /// Base URL of the Nextcloud instance, with a trailing slash.
+    pub base_url: String,
+    /// Collection index data is sent to.
+    pub collection: u32,
+    /// Full URL of the API endpoint being called.
+    pub endpoint: String,
+    /// Version of the Analytics API, e.g. `"1.0"`.
+    pub api_version: &'static str,
+    /// Nextcloud user's name.
+    pub user: String,
+    /// Kind of authentication used for the API calls.
+    pub auth: &'static str,
+    /// Name of the builder profile the client was created with, if any.
+    pub profile: Option<&'static str>,
+    /// Timeout of a whole request.
+    #[serde(rename = "timeout_secs", serialize_with = "secs")]
+    pub timeout: Duration,
+    /// Timeout of the connection phase, if any.
+    #[serde(rename = "connect_timeout_secs", serialize_with = "optional_secs")]
+    pub connect_timeout: Option<Duration>,
+    /// Interval of the TCP keepalive probes, if idle connections are kept open.
+    #[serde(rename = "keep_alive_secs", serialize_with = "optional_secs")]
+    pub keep_alive: Option<Duration>,
+    /// Maximum number of attempts of a request, 1 if failed requests aren't retried.
+    pub max_attempts: u32,
+    /// The policy retrying failed requests, if any.
+    pub retry: Option<RetryPolicy>,
+    /// Pattern of the key template, if any.
+    pub key_template: Option<String>,
+    /// Number of data points per request of streams, staged commits and matrices.
+    pub batch_size: usize,
+    /// Requests per second and burst of the rate limit of the client, if any.
+    #[serde(serialize_with = "rate_limit")]
+    pub rate_limit: Option<(f64, u32)>,
+    /// The optional features of the crate compiled in, e.g. `"chrono"`.
+    pub features: Vec<&'static str>,
+}
+
+impl ClientDescription {
+    /// Serializes the description to a JSON value.
+    pub fn to_json(&self) -> Value {
+        // Serializing strings, numbers and durations can't fail.
+        serde_json::to_value(self).unwrap_or_default()
+    }
+}
+
+impl fmt::Display for ClientDescription {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.to_json())
+    }
+}
+
+/// The optional features of the crate compiled in.
+#[cfg(feature = "blocking")]
+pub(crate) fn features() -> Vec<&'static str> {
+    [
+        ("chrono", cfg!(feature = "chrono")),
+        ("compression", cfg!(feature = "compression")),
+        ("hostname", cfg!(feature = "hostname")),
+        ("native-tls", cfg!(feature = "native-tls")),
+        ("native-tls-vendored", cfg!(feature = "native-tls-vendored")),
+        ("rustls", cfg!(feature = "rustls")),
+        ("toml", cfg!(feature = "toml")),
+        ("yaml", cfg!(feature = "yaml")),
+        ("socks", cfg!(feature = "socks")),
+        ("derive", cfg!(feature = "derive")),
+        ("zeroize", cfg!(feature = "zeroize")),
+        ("regex", cfg!(feature = "regex")),
+        ("minimal", cfg!(feature = "minimal")),
+        ("privacy", cfg!(feature = "privacy")),
+        ("async", cfg!(feature = "async")),
+        ("tracing", cfg!(feature = "tracing")),
+        ("gateway", cfg!(feature = "gateway")),
+        ("prometheus", cfg!(feature = "prometheus")),
+        ("metrics-exporter", cfg!(feature = "metrics-exporter")),
+        ("mirror", cfg!(feature = "mirror")),
+        ("modbus", cfg!(feature = "modbus")),
+        ("cli", cfg!(feature = "cli")),
+    ]
+    .iter()
+    .filter(|(_, enabled)| *enabled)
+    .map(|(name, _)| *name)
+    .collect()
+}
+
+/// Serializes a duration as a number of seconds.
+pub(crate) fn secs<S: Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_f64(duration.as_secs_f64())
+}
+
+fn optional_secs<S: Serializer>(
+    duration: &Option<Duration>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    duration.map(|d| d.as_secs_f64()).serialize(serializer)
+}
+
+#[derive(Serialize)]
+struct RateLimitDescription {
+    rate: f64,
+    burst: u32,
+}
+
+fn rate_limit<S: Serializer>(limit: &Option<(f64, u32)>, serializer: S) -> Result<S::Ok, S::Error> {
+    limit
+        .map(|(rate, burst)| RateLimitDescription { rate, burst })
+        .serialize(serializer)
+}
@@ -0,0 +1,196 @@
+use std::collections::HashMap;
+
+#[cfg(feature = "chrono")]
+use chrono::{DateTime, Utc};
+use serde_json::{Map, Number, Value};
+
+use crate::{trace, AnalyticsError, DataPoint, Outcome, Receipt, SyncClient, ValueFormat};
+
+/// A typed value of a dataset column, see [`SyncClient::send_row`].
+#[derive(Clone, Debug, PartialEq)]
+pub enum Dimension {
+    Text(String),
+    Number(f64),
+    /// A date, sent in the timestamp format of the client, RFC 2822 by default.
+    ///
+    /// Requires the `chrono` feature.
+    #[cfg(feature = "chrono")]
+    DateTime(DateTime<Utc>),
+}
+
+impl Dimension {
+    /// Formats the dimension the way Analytics expects it for a text column.
+    #[cfg_attr(not(feature = "chrono"), allow(unused_variables))]
+    pub(crate) fn into_text(self, client: &SyncClient) -> Result<String, AnalyticsError> {
+        match self {
+            Dimension::Text(text) => Ok(text),
+            Dimension::Number(number) => Ok(number.to_string()),
+            #[cfg(feature = "chrono")]
+            Dimension::DateTime(time) => client.format_time(time),
+        }
+    }
+
+    fn into_value(self) -> Result<f64, AnalyticsError> {
+        match self {
+            Dimension::Number(number) => Ok(number),
+            Dimension::Text(text) => text.trim().parse().map_err(|_| {
+                AnalyticsError::local(format!("the value of a row isn't a number: {:?}", text))
+            }),
+            #[cfg(feature = "chrono")]
+            Dimension::DateTime(time) => Err(AnalyticsError::local(format!(
+                "the value of a row can't be a date: {}",
+                time
+            ))),
+        }
+    }
+}
+
+impl From<&str> for Dimension {
+    fn from(text: &str) -> Self {
+        Dimension::Text(text.to_string())
+    }
+}
+
+impl From<String> for Dimension {
+    fn from(text: String) -> Self {
+        Dimension::Text(text)
+    }
+}
+
+impl From<f64> for Dimension {
+    fn from(number: f64) -> Self {
+        Dimension::Number(number)
+    }
+}
+
+#[cfg(feature = "chrono")]
+impl From<DateTime<Utc>> for Dimension {
+    fn from(time: DateTime<Utc>) -> Self {
+        Dimension::DateTime(time)
+    }
+}
+
+impl SyncClient {
+    /// Sends a row of typed values, formatting each of them the way Analytics expects, e.g.
+    /// dates in the client's timestamp format, truncated to the resolution set with
+    /// [`SyncClient::align_to`], if any.
+    ///
+    /// The last dimension is the value, which must be a number, or a text containing one.
+    pub fn send_row(&self, row: [Dimension; 3]) -> Result<Receipt, AnalyticsError> {
+        let [dimension1, dimension2, value] = row;
+        let point = DataPoint::new(
+            dimension1.into_text(self)?,
+            dimension2.into_text(self)?,
+            value.into_value()?,
+        );
+        self.send_point(point)
+    }
+}
+
+/// Name of the value column of a row with named dimensions, see [`SyncClient::send_named_rows`].
+const VALUE_COLUMN: &str = "value";
+
+/// Builds a row of a dataset with any number of dimensions, for [`SyncClient::send_named_rows`].
+///
+/// ```no_run
+/// # use nextcloud_analytics_rs::{RowBuilder, SyncClient};
+/// # let client = SyncClient::new("https://example.com/nextcloud", 3, "myself", "hunter2");
+/// let row = RowBuilder::new()
+///     .dimension("dimension1", "disk")
+///     .dimension("dimension2", "2024-01-01")
+///     .dimension("dimension3", "sda")
+///     .dimension("dimension4", "read")
+///     .value(1234.0)
+///     .build();
+/// client.send_named_rows(&[row])?;
+/// # Ok::<(), nextcloud_analytics_rs::AnalyticsError>(())
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct RowBuilder {
+    columns: HashMap<String, Dimension>,
+}
+
+impl RowBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the dimension called `name`, e.g. `dimension4`.
+    pub fn dimension<S: Into<String>, D: Into<Dimension>>(mut self, name: S, value: D) -> Self {
+        self.columns.insert(name.into(), value.into());
+        self
+    }
+
+    /// Sets the value of the row, which must be a number, or a text containing one.
+    pub fn value<D: Into<Dimension>>(mut self, value: D) -> Self {
+        self.columns.insert(VALUE_COLUMN.to_string(), value.into());
+        self
+    }
+
+    pub fn build(self) -> HashMap<String, Dimension> {
+        self.columns
+    }
+}
+
+impl SyncClient {
+    /// Sends rows with named dimensions in a single request to the bulk endpoint, for datasets
+    /// with more than three dimensions. Each row maps the names of its dimensions, e.g.
+    /// `dimension4`, to their values, and `value` to its value; see [`RowBuilder`].
+    ///
+    /// The dimensions are formatted as with [`SyncClient::send_row`]. Since the dimensions
+    /// have no fixed meaning, the rows don't go through the key filter, key template,
+    /// enrichments, payload encoder or duplicate detection of the client.
+    pub fn send_named_rows(
+        &self,
+        rows: &[HashMap<String, Dimension>],
+    ) -> Result<Receipt, AnalyticsError> {
+        self.check_writable()?;
+        if rows.is_empty() {
+            return Ok(Receipt::new("", Outcome::Filtered));
+        }
+        let data = rows
+            .iter()
+            .map(|row| self.named_row(row).map(Value::Object))
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut body = Map::new();
+        body.insert("data".to_string(), Value::Array(data));
+        let payload = Value::Object(body).to_string();
+
+        let collection = self.collection;
+        if self.dry_run {
+            return Ok(self.dry_run_receipt(payload, collection));
+        }
+        let trace = trace::SendTrace::start(collection, rows.len());
+        let result = self
+            .post_batch(payload.clone(), "application/json", collection, rows.len())
+            .map(|report| Receipt::new(&payload, Outcome::Sent(report)))
+            .map_err(|err| self.redactions.error(err));
+        trace.finish(&result, self);
+        result
+    }
+
+    /// The JSON object of a row with named dimensions.
+    fn named_row(
+        &self,
+        row: &HashMap<String, Dimension>,
+    ) -> Result<Map<String, Value>, AnalyticsError> {
+        let mut object = Map::new();
+        for (name, dimension) in row {
+            if name != VALUE_COLUMN {
+                let text = dimension.clone().into_text(self)?;
+                object.insert(name.clone(), Value::String(text));
+            }
+        }
+        let value = row
+            .get(VALUE_COLUMN)
+            .ok_or_else(|| AnalyticsError::local("a row has no value"))?
+            .clone()
+            .into_value()?;
+        let value = match Number::from_f64(value) {
+            Some(number) if self.value_format == ValueFormat::Number => Value::Number(number),
+            _ => Value::String(value.to_string()),
+        };
+        object.insert(VALUE_COLUMN.to_string(), value);
+        Ok(object)
+    }
+}
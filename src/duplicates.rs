@@ -0,0 +1,66 @@
+use std::collections::HashMap;
+
+use crate::{AnalyticsError, DataPoint, SyncClient};
+
+/// What to do with data points of a batch having the same first two dimensions, e.g. the same
+/// key and time once aligned with [`SyncClient::align_to`], see
+/// [`SyncClient::with_duplicate_policy`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DuplicatePolicy {
+    /// Keep the first data point.
+    KeepFirst,
+    /// Keep the last data point, as the server does for separate sends.
+    #[default]
+    KeepLast,
+    /// Send the sum of the values.
+    Sum,
+    /// Refuse to send the batch.
+    Error,
+}
+
+impl DuplicatePolicy {
+    /// Merges the data points with the same first two dimensions, each at the position of the
+    /// first one.
+    pub(crate) fn resolve(self, points: Vec<DataPoint>) -> Result<Vec<DataPoint>, AnalyticsError> {
+        let mut positions: HashMap<(String, String), usize> = HashMap::new();
+        let mut resolved: Vec<DataPoint> = Vec::with_capacity(points.len());
+
+        for point in points {
+            let cell = (point.dimension1.clone(), point.dimension2.clone());
+            let index = match positions.get(&cell) {
+                Some(&index) => index,
+                None => {
+                    positions.insert(cell, resolved.len());
+                    resolved.push(point);
+                    continue;
+                }
+            };
+
+            match self {
+                DuplicatePolicy::KeepFirst => {}
+                DuplicatePolicy::KeepLast => resolved[index] = point,
+                DuplicatePolicy::Sum => resolved[index].dimension3 += point.dimension3,
+                DuplicatePolicy::Error => {
+                    return Err(AnalyticsError::local(format!(
+                        "duplicate data points for {:?} at {:?}",
+                        point.dimension1, point.dimension2
+                    )))
+                }
+            }
+        }
+
+        Ok(resolved)
+    }
+}
+
+impl SyncClient {
+    /// How to resolve the data points of a batch with the same first two dimensions, before
+    /// sending it with [`SyncClient::send_batch`] or the helpers built on it. Defaults to
+    /// [`DuplicatePolicy::KeepLast`].
+    ///
+    /// Dimensions are compared after the key template and alignment are applied.
+    pub fn with_duplicate_policy(mut self, policy: DuplicatePolicy) -> Self {
+        self.duplicate_policy = policy;
+        self
+    }
+}
@@ -0,0 +1,200 @@
+use core::fmt;
+use std::error::Error;
+
+use crate::{DataPoint, SyncClient, WriterTag};
+
+const BASE64_URL: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+
+/// An encoding of dimension values, for identifiers containing characters the server
+/// mishandles, see [`SyncClient::with_dimension1_encoding`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Encoding {
+    /// URL-safe base64 without padding, as described by RFC 4648.
+    Base64Url,
+    /// Percent-encoding of all the bytes but ASCII letters, digits, `-`, `_`, `.` and `~`.
+    Percent,
+}
+
+impl Encoding {
+    /// Encodes a dimension value.
+    pub fn encode(self, text: &str) -> String {
+        match self {
+            Encoding::Base64Url => base64_encode(text.as_bytes()),
+            Encoding::Percent => percent_encode(text),
+        }
+    }
+
+    /// Decodes a dimension value encoded with this encoding.
+    pub fn decode(self, text: &str) -> Result<String, DecodeError> {
+        let bytes = match self {
+            Encoding::Base64Url => base64_decode(text),
+            Encoding::Percent => percent_decode(text),
+        }
+        .ok_or_else(|| DecodeError::new(self, text))?;
+        String::from_utf8(bytes).map_err(|_| DecodeError::new(self, text))
+    }
+}
+
+/// Encodes a query parameter's value, or a dimension with [`Encoding::Percent`].
+pub(crate) fn percent_encode(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                encoded.push(byte as char)
+            }
+            byte => encoded += &format!("%{:02X}", byte),
+        }
+    }
+    encoded
+}
+
+fn percent_decode(text: &str) -> Option<Vec<u8>> {
+    let mut bytes = Vec::with_capacity(text.len());
+    let mut input = text.bytes();
+    while let Some(byte) = input.next() {
+        if byte == b'%' {
+            let high = (input.next()? as char).to_digit(16)?;
+            let low = (input.next()? as char).to_digit(16)?;
+            bytes.push((high * 16 + low) as u8);
+        } else {
+            bytes.push(byte);
+        }
+    }
+    Some(bytes)
+}
+
+fn base64_encode(bytes: &[u8]) -> String {
+    let mut encoded = String::with_capacity((bytes.len() * 4).div_ceil(3));
+    for chunk in bytes.chunks(3) {
+        let group = chunk.iter().enumerate().fold(0u32, |group, (i, &byte)| {
+            group | u32::from(byte) << (16 - 8 * i)
+        });
+        for i in 0..=chunk.len() {
+            encoded.push(BASE64_URL[(group >> (18 - 6 * i) & 0x3f) as usize] as char);
+        }
+    }
+    encoded
+}
+
+fn base64_decode(text: &str) -> Option<Vec<u8>> {
+    let text = text.trim_end_matches('=');
+    let mut bytes = Vec::with_capacity(text.len() * 3 / 4);
+    for chunk in text.as_bytes().chunks(4) {
+        if chunk.len() == 1 {
+            return None;
+        }
+        let mut group = 0u32;
+        for (i, &c) in chunk.iter().enumerate() {
+            let sextet = BASE64_URL.iter().position(|&b| b == c)? as u32;
+            group |= sextet << (18 - 6 * i);
+        }
+        for i in 0..chunk.len() - 1 {
+            bytes.push((group >> (16 - 8 * i)) as u8);
+        }
+    }
+    Some(bytes)
+}
+
+/// A dimension value couldn't be decoded, see [`Encoding::decode`].
+pub struct DecodeError {
+    encoding: Encoding,
+    text: String,
+}
+
+impl DecodeError {
+    fn new(encoding: Encoding, text: &str) -> Self {
+        Self {
+            encoding,
+            text: text.to_string(),
+        }
+    }
+}
+
+impl fmt::Debug for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid {:?} value: {:?}", self.encoding, self.text)
+    }
+}
+
+impl Error for DecodeError {}
+
+/// Encodings of the dimensions of a client.
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct Encodings {
+    dimension1: Option<Encoding>,
+    dimension2: Option<Encoding>,
+}
+
+impl Encodings {
+    pub fn encode(&self, point: &mut DataPoint) {
+        if let Some(encoding) = self.dimension1 {
+            point.dimension1 = encoding.encode(&point.dimension1);
+        }
+        if let Some(encoding) = self.dimension2 {
+            point.dimension2 = encoding.encode(&point.dimension2);
+        }
+    }
+
+    /// Encodes a key as it's sent to the server.
+    pub fn encode_key(&self, key: &str) -> String {
+        match self.dimension1 {
+            Some(encoding) => encoding.encode(key),
+            None => key.to_string(),
+        }
+    }
+
+    /// Decodes the dimensions of a row read back, leaving the ones that can't be decoded, e.g.
+    /// written before the encoding was set, as they are. A writer identity appended to a
+    /// dimension is kept as is.
+    pub fn decode(&self, point: &mut DataPoint, writer: Option<WriterTag>) {
+        let decode = |dimension: &mut String, encoding: Option<Encoding>, tagged: bool| {
+            let encoding = match encoding {
+                Some(encoding) => encoding,
+                None => return,
+            };
+            // Encoded values never contain the separator of the writer identity.
+            let (value, suffix) = match dimension.rfind('@') {
+                Some(at) if tagged => dimension.split_at(at),
+                _ => (dimension.as_str(), ""),
+            };
+            if let Ok(decoded) = encoding.decode(value) {
+                *dimension = decoded + suffix;
+            }
+        };
+        decode(
+            &mut point.dimension1,
+            self.dimension1,
+            writer == Some(WriterTag::Dimension1),
+        );
+        decode(
+            &mut point.dimension2,
+            self.dimension2,
+            writer == Some(WriterTag::Dimension2),
+        );
+    }
+}
+
+impl SyncClient {
+    /// Encode the first dimension of every data point, after the key template and enrichments
+    /// have been applied; rows read back with [`SyncClient::get_data`] and the other reading
+    /// methods are decoded.
+    pub fn with_dimension1_encoding(mut self, encoding: Encoding) -> Self {
+        self.encodings.dimension1 = Some(encoding);
+        self
+    }
+
+    /// Encode the second dimension of every data point, like
+    /// [`SyncClient::with_dimension1_encoding`]. Don't use it for timeline data, whose second
+    /// dimension must be a date.
+    pub fn with_dimension2_encoding(mut self, encoding: Encoding) -> Self {
+        self.encodings.dimension2 = Some(encoding);
+        self
+    }
+}
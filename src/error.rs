@@ -0,0 +1,349 @@
+use core::fmt;
+use std::error::Error;
+use std::time::Duration;
+
+use reqwest as http;
+
+use crate::{MappingError, TemplateError, ValidationError};
+
+/// The broad kind of a failure, e.g. to decide how to alert about it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ErrorClass {
+    /// The server couldn't be reached, or didn't answer in time.
+    Network,
+    /// The server refused the credentials.
+    Auth,
+    /// The endpoint or the collection doesn't exist.
+    NotFound,
+    /// The server answered with an unexpected HTTP status.
+    Http,
+    /// The server answered, but the Analytics API reported an error.
+    Api,
+    /// The server's answer couldn't be understood.
+    InvalidResponse,
+    /// The data couldn't be prepared for sending, e.g. because of a template or mapping error.
+    Local,
+    /// The server accepted the data, but didn't store it as sent.
+    Unverified,
+    /// The server is in maintenance mode, e.g. during an upgrade.
+    Maintenance,
+    /// A data point was rejected before sending, e.g. because its value isn't a number.
+    Validation,
+}
+
+impl ErrorClass {
+    /// A stable machine-readable code for this class of errors, independent of the
+    /// human-readable messages, e.g. `"E_AUTH"`.
+    pub fn code(self) -> &'static str {
+        match self {
+            ErrorClass::Network => "E_NETWORK",
+            ErrorClass::Auth => "E_AUTH",
+            ErrorClass::NotFound => "E_NOT_FOUND",
+            ErrorClass::Http => "E_HTTP",
+            ErrorClass::Api => "E_API",
+            ErrorClass::InvalidResponse => "E_BAD_RESPONSE",
+            ErrorClass::Local => "E_LOCAL",
+            ErrorClass::Unverified => "E_UNVERIFIED",
+            ErrorClass::Maintenance => "E_MAINTENANCE",
+            ErrorClass::Validation => "E_VALIDATION",
+        }
+    }
+
+    /// Classifies an error, e.g. the [`source`](Error::source) of another error. Errors that
+    /// don't come from this crate are considered network errors.
+    pub fn of(err: &(dyn Error + 'static)) -> Self {
+        if let Some(err) = err.downcast_ref::<AnalyticsError>() {
+            err.class()
+        } else if err.is::<serde_json::Error>() {
+            ErrorClass::InvalidResponse
+        } else {
+            ErrorClass::Network
+        }
+    }
+}
+
+/// An error returned by this crate.
+#[non_exhaustive]
+pub enum AnalyticsError {
+    /// The request couldn't be sent or its response couldn't be read, e.g. because of a DNS
+    /// failure or a timeout.
+    Http(http::Error),
+    /// The server refused the credentials, e.g. because of a revoked app password.
+    Auth {
+        status: http::StatusCode,
+        message: String,
+    },
+    /// The server answered with an unexpected HTTP status, or the Analytics API reported an
+    /// error in a successful response.
+    Api {
+        status: http::StatusCode,
+        message: String,
+    },
+    /// The server's answer couldn't be understood.
+    Parse(String),
+    /// The data couldn't be prepared for sending, or a local resource couldn't be used, e.g.
+    /// because of a template error or a missing file.
+    Local(Box<dyn Error + Send + Sync>),
+    /// A data point sent with [`SyncClient::send_verified`](crate::SyncClient::send_verified)
+    /// was read back with another value, `None` meaning it couldn't be found at all.
+    Unverified {
+        stored: Option<f64>,
+        message: String,
+    },
+    /// The server is in maintenance mode, e.g. during an upgrade, and asked to retry after the
+    /// given delay, if any; see
+    /// [`SyncClientBuilder::queue_during_maintenance`](crate::SyncClientBuilder::queue_during_maintenance).
+    Maintenance {
+        retry_after: Option<Duration>,
+        message: String,
+    },
+    /// A data point was rejected before sending, see
+    /// [`SyncClient::with_validation`](crate::SyncClient::with_validation).
+    Validation(ValidationError),
+}
+
+/// Former name of [`AnalyticsError`].
+#[deprecated(note = "use AnalyticsError instead")]
+pub type ApiError = AnalyticsError;
+
+impl AnalyticsError {
+    /// An error reported by the Analytics API in a successful response.
+    pub(crate) fn api(message: String) -> Self {
+        AnalyticsError::Api {
+            status: http::StatusCode::OK,
+            message,
+        }
+    }
+
+    /// An error for an unexpected HTTP status, with the response's body.
+    #[cfg(feature = "blocking")]
+    pub(crate) fn from_status(status: http::StatusCode, body: &str) -> Self {
+        Self::from_response(status, &http::header::HeaderMap::new(), body)
+    }
+
+    /// An error for an unexpected HTTP status, with the response's headers and body.
+    pub(crate) fn from_response(
+        status: http::StatusCode,
+        headers: &http::header::HeaderMap,
+        body: &str,
+    ) -> Self {
+        let maintenance = headers
+            .get(MAINTENANCE_HEADER)
+            .is_some_and(|value| value == "1")
+            || matches!(unexpected_body(body), Some(UnexpectedBody::Maintenance));
+        if !maintenance {
+            let message = format!("unexpected status code: {:?}\n{}", status, body);
+            return Self::for_status(status, message);
+        }
+
+        let message = format!(
+            "unexpected status code: {:?}: the server is in maintenance mode",
+            status
+        );
+        if status != http::StatusCode::SERVICE_UNAVAILABLE {
+            return Self::for_status(status, message);
+        }
+        AnalyticsError::Maintenance {
+            retry_after: headers
+                .get(http::header::RETRY_AFTER)
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.trim().parse().ok())
+                .map(Duration::from_secs),
+            message,
+        }
+    }
+
+    /// The error of a response with the given status.
+    fn for_status(status: http::StatusCode, message: String) -> Self {
+        match status {
+            http::StatusCode::UNAUTHORIZED | http::StatusCode::FORBIDDEN => {
+                AnalyticsError::Auth { status, message }
+            }
+            _ => AnalyticsError::Api { status, message },
+        }
+    }
+
+    /// An error for a response body that isn't the expected JSON, telling apart the pages
+    /// Nextcloud serves instead of the API's answers.
+    pub(crate) fn invalid_body(body: &str, err: serde_json::Error) -> Self {
+        AnalyticsError::Parse(match unexpected_body(body) {
+            Some(UnexpectedBody::Empty) => "the server answered with an empty body".to_string(),
+            Some(UnexpectedBody::Maintenance) => {
+                "the server answered with its maintenance page".to_string()
+            }
+            Some(UnexpectedBody::Login) => "the server answered with its login page: the \
+                 credentials were refused, or dropped by a proxy"
+                .to_string(),
+            Some(UnexpectedBody::Html) => "the server answered with an HTML page instead of \
+                 JSON, check the Nextcloud URL"
+                .to_string(),
+            None => format!("invalid JSON response: {}", err),
+        })
+    }
+
+    /// A local error, from anything that can be turned into an error.
+    pub(crate) fn local<E: Into<Box<dyn Error + Send + Sync>>>(err: E) -> Self {
+        AnalyticsError::Local(err.into())
+    }
+
+    /// Whether the server couldn't be reached, or failed in a way that may not last, so that
+    /// the same request may succeed later: a server error, a timeout or a rate limit, but not
+    /// a request the server rejected.
+    #[cfg(feature = "blocking")]
+    pub(crate) fn is_transient(&self) -> bool {
+        match self {
+            AnalyticsError::Api { status, .. } if self.class() == ErrorClass::Http => {
+                status.is_server_error()
+                    || *status == http::StatusCode::REQUEST_TIMEOUT
+                    || *status == http::StatusCode::TOO_MANY_REQUESTS
+            }
+            _ => matches!(
+                self.class(),
+                ErrorClass::Network | ErrorClass::Http | ErrorClass::Maintenance
+            ),
+        }
+    }
+
+    /// Kind of this error.
+    pub fn class(&self) -> ErrorClass {
+        match self {
+            AnalyticsError::Http(_) => ErrorClass::Network,
+            AnalyticsError::Auth { .. } => ErrorClass::Auth,
+            AnalyticsError::Api { status, .. } if *status == http::StatusCode::NOT_FOUND => {
+                ErrorClass::NotFound
+            }
+            AnalyticsError::Api { status, .. } if status.is_success() => ErrorClass::Api,
+            AnalyticsError::Api { .. } => ErrorClass::Http,
+            AnalyticsError::Parse(_) => ErrorClass::InvalidResponse,
+            AnalyticsError::Local(_) => ErrorClass::Local,
+            AnalyticsError::Unverified { .. } => ErrorClass::Unverified,
+            AnalyticsError::Maintenance { .. } => ErrorClass::Maintenance,
+            AnalyticsError::Validation(_) => ErrorClass::Validation,
+        }
+    }
+
+    /// Stable machine-readable code of this error, see [`ErrorClass::code`].
+    pub fn code(&self) -> &'static str {
+        self.class().code()
+    }
+
+    /// The same error with its message replaced, keeping its class.
+    #[cfg(feature = "blocking")]
+    pub(crate) fn with_message(self, message: String) -> Self {
+        match self {
+            AnalyticsError::Auth { status, .. } => AnalyticsError::Auth { status, message },
+            AnalyticsError::Api { status, .. } => AnalyticsError::Api { status, message },
+            AnalyticsError::Parse(_) => AnalyticsError::Parse(message),
+            AnalyticsError::Local(_) => AnalyticsError::local(message),
+            AnalyticsError::Unverified { stored, .. } => {
+                AnalyticsError::Unverified { stored, message }
+            }
+            AnalyticsError::Maintenance { retry_after, .. } => AnalyticsError::Maintenance {
+                retry_after,
+                message,
+            },
+            // HTTP errors can't be rebuilt, and only carry the URL and the cause of the failure.
+            err @ AnalyticsError::Http(_) => err,
+            // Validation errors don't carry the data points' dimensions.
+            err @ AnalyticsError::Validation(_) => err,
+        }
+    }
+}
+
+/// Header set by Nextcloud on its answers while in maintenance mode.
+const MAINTENANCE_HEADER: &str = "x-nextcloud-maintenance-mode";
+
+/// Bodies Nextcloud answers with instead of the Analytics API's JSON.
+enum UnexpectedBody {
+    Empty,
+    Maintenance,
+    Login,
+    Html,
+}
+
+fn unexpected_body(body: &str) -> Option<UnexpectedBody> {
+    let body = body.trim_start();
+    if body.is_empty() {
+        return Some(UnexpectedBody::Empty);
+    }
+    if !body.starts_with('<') {
+        return None;
+    }
+
+    let html = body.to_lowercase();
+    Some(if html.contains("maintenance") {
+        UnexpectedBody::Maintenance
+    } else if html.contains("name=\"login\"") || html.contains("<title>login") {
+        UnexpectedBody::Login
+    } else {
+        UnexpectedBody::Html
+    })
+}
+
+impl fmt::Debug for AnalyticsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+impl fmt::Display for AnalyticsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AnalyticsError::Http(err) => write!(f, "{}", err),
+            AnalyticsError::Auth { message, .. }
+            | AnalyticsError::Api { message, .. }
+            | AnalyticsError::Unverified { message, .. }
+            | AnalyticsError::Maintenance { message, .. } => write!(f, "{}", message),
+            AnalyticsError::Parse(message) => write!(f, "{}", message),
+            AnalyticsError::Local(err) => write!(f, "{}", err),
+            AnalyticsError::Validation(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl Error for AnalyticsError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            AnalyticsError::Http(err) => Some(err),
+            AnalyticsError::Local(err) => Some(err.as_ref()),
+            AnalyticsError::Validation(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<http::Error> for AnalyticsError {
+    fn from(err: http::Error) -> Self {
+        AnalyticsError::Http(err)
+    }
+}
+
+impl From<serde_json::Error> for AnalyticsError {
+    fn from(err: serde_json::Error) -> Self {
+        AnalyticsError::Parse(format!("invalid JSON response: {}", err))
+    }
+}
+
+impl From<ValidationError> for AnalyticsError {
+    fn from(err: ValidationError) -> Self {
+        AnalyticsError::Validation(err)
+    }
+}
+
+impl From<std::io::Error> for AnalyticsError {
+    fn from(err: std::io::Error) -> Self {
+        AnalyticsError::local(err)
+    }
+}
+
+impl From<TemplateError> for AnalyticsError {
+    fn from(err: TemplateError) -> Self {
+        AnalyticsError::local(err)
+    }
+}
+
+impl From<MappingError> for AnalyticsError {
+    fn from(err: MappingError) -> Self {
+        AnalyticsError::local(err)
+    }
+}
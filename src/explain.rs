@@ -0,0 +1,39 @@
+use crate::{DataPoint, SyncClient};
+
+/// Quotes `text` for a POSIX shell.
+fn shell_quote(text: &str) -> String {
+    format!("'{}'", text.replace('\'', r"'\''"))
+}
+
+impl SyncClient {
+    /// Returns a `curl` command sending the same request as [`SyncClient::send_point`] would
+    /// for `point`, e.g. to reproduce a server-side issue outside of Rust.
+    ///
+    /// The key template and enrichments are applied as when sending; the key filter and minimum
+    /// intervals aren't, and with [`SyncClient::with_upsert`] only the request adding the point
+    /// is shown. The password or token is replaced by a placeholder, to be filled in before
+    /// running the command.
+    pub fn explain(&self, point: &DataPoint) -> String {
+        let point = self
+            .prepare_point(point.clone())
+            .unwrap_or_else(|_| point.clone());
+        let payload = self.point_payload(&point);
+        let url = self.base_url.clone() + &self.api_version.add_path(self.collection);
+
+        let auth = if self.credentials.uses_bearer_token() {
+            format!("-H {}", shell_quote("Authorization: Bearer <token>"))
+        } else {
+            format!(
+                "-u {}",
+                shell_quote(&format!("{}:<app password>", self.credentials.user()))
+            )
+        };
+        format!(
+            "curl -X POST {} -H {} --data {} {}",
+            auth,
+            shell_quote(&format!("Content-Type: {}", self.payload_content_type())),
+            shell_quote(&payload),
+            shell_quote(&url)
+        )
+    }
+}
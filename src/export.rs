@@ -0,0 +1,88 @@
+use std::io::{self, Write};
+
+use crate::encoding::percent_encode;
+use crate::{AnalyticsError, Request, Response, SyncClient};
+
+/// Where to download an exported report from, see [`SyncClient::download_export`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ExportSource {
+    /// The export of a report by the Analytics app, with its own route rather than its
+    /// versioned API, which may change between Analytics releases.
+    Report(u64),
+    /// A file of the user's Files, e.g. a CSV or XLSX export saved there, by its path from the
+    /// root of the user's files, such as `Reports/sales.xlsx`.
+    WebDav(String),
+}
+
+impl ExportSource {
+    /// Path of the download, relative to the Nextcloud base URL.
+    fn path(&self, user: &str) -> String {
+        match self {
+            ExportSource::Report(report) => format!("apps/analytics/report/export/{}", report),
+            ExportSource::WebDav(path) => {
+                let segments = path
+                    .split('/')
+                    .filter(|segment| !segment.is_empty())
+                    .map(percent_encode)
+                    .collect::<Vec<_>>();
+                format!(
+                    "remote.php/dav/files/{}/{}",
+                    percent_encode(user),
+                    segments.join("/")
+                )
+            }
+        }
+    }
+}
+
+impl SyncClient {
+    /// Downloads an exported report into `writer`, e.g. to attach it to an email after
+    /// pushing its data, and returns the number of bytes written.
+    ///
+    /// The body is copied as it is received, without being buffered, so binary formats such
+    /// as XLSX are kept intact. Downloads through a custom transport, see
+    /// [`SyncClient::with_transport`], are text only.
+    pub fn download_export<W: Write>(
+        &self,
+        source: &ExportSource,
+        writer: &mut W,
+    ) -> Result<u64, AnalyticsError> {
+        let url = self.base_url.clone() + &source.path(&self.credentials.user());
+        let req = self.client.get(&url);
+
+        if self.transport.is_some() {
+            let body = self.send_request(req)?;
+            writer.write_all(body.as_bytes())?;
+            return Ok(body.len() as u64);
+        }
+
+        let mut request = Request::from_reqwest(self.authorize(req).build()?);
+        self.intercept_request(&mut request)?;
+        let sent = request.clone();
+        let mut resp = request.into_reqwest(&self.client).send()?;
+        let status = resp.status();
+        let headers = resp.headers().clone();
+        self.intercept_response(
+            &sent,
+            &Response {
+                status,
+                headers: headers.clone(),
+                body: String::new(),
+            },
+        );
+        if !status.is_success() {
+            let body = resp.text().unwrap_or_default();
+            return Err(self
+                .redactions
+                .error(AnalyticsError::from_response(status, &headers, &body)));
+        }
+        Ok(io::copy(&mut resp, writer)?)
+    }
+
+    /// Downloads an exported report in memory, see [`SyncClient::download_export`].
+    pub fn download_export_bytes(&self, source: &ExportSource) -> Result<Vec<u8>, AnalyticsError> {
+        let mut bytes = Vec::new();
+        self.download_export(source, &mut bytes)?;
+        Ok(bytes)
+    }
+}
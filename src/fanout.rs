@@ -0,0 +1,90 @@
+use crate::{AnalyticsError, DataPoint, Outcome, Receipt, SyncClient};
+
+/// Results of sending a data point to several collections, see [`SyncClient::send_fanout`].
+#[derive(Debug)]
+pub struct Fanout {
+    /// Result of the send to each collection, in the order they were given.
+    pub results: Vec<(u32, Result<Receipt, AnalyticsError>)>,
+    /// Result of the deletion of the data point from each collection it was sent to, when
+    /// rolling back after a failure with [`SyncClient::send_fanout_with_rollback`].
+    pub rollbacks: Vec<(u32, Result<(), AnalyticsError>)>,
+}
+
+impl Fanout {
+    /// Whether the data point was sent to all the collections.
+    pub fn is_complete(&self) -> bool {
+        self.results.iter().all(|(_, result)| result.is_ok())
+    }
+}
+
+impl SyncClient {
+    /// Sends the same data point to several collections with the client's credentials, e.g. for
+    /// a metric feeding several dashboards, returning the result for each of them.
+    ///
+    /// The point goes through the key filter, key template and enrichments once, so that all
+    /// the collections get the same row; minimum intervals and derived metrics don't apply.
+    /// Fails without sending anything if the point can't be prepared.
+    pub fn send_fanout(
+        &self,
+        point: DataPoint,
+        collections: &[u32],
+    ) -> Result<Fanout, AnalyticsError> {
+        self.fanout(point, collections).map(|(fanout, _)| fanout)
+    }
+
+    /// Like [`SyncClient::send_fanout`], but if sending to one of the collections fails,
+    /// deletes the data point from the ones it was sent to, on a best-effort basis.
+    ///
+    /// Rows are deleted by their two first dimensions: a row that the data point replaced in
+    /// upsert mode is deleted too, not restored.
+    pub fn send_fanout_with_rollback(
+        &self,
+        point: DataPoint,
+        collections: &[u32],
+    ) -> Result<Fanout, AnalyticsError> {
+        let (mut fanout, sent) = self.fanout(point, collections)?;
+        let sent = match sent {
+            Some(sent) if !fanout.is_complete() => sent,
+            _ => return Ok(fanout),
+        };
+
+        let row = [(sent.dimension1.as_str(), sent.dimension2.as_str())];
+        fanout.rollbacks = fanout
+            .results
+            .iter()
+            .filter(|(_, result)| matches!(result, Ok(receipt) if receipt.was_sent()))
+            .map(|&(id, _)| (id, self.delete_rows(&row, id)))
+            .collect();
+        Ok(fanout)
+    }
+
+    /// Sends a data point to several collections, returning the results, and the point as it
+    /// was sent unless it was filtered out.
+    fn fanout(
+        &self,
+        point: DataPoint,
+        collections: &[u32],
+    ) -> Result<(Fanout, Option<DataPoint>), AnalyticsError> {
+        if !self.allows_key(&point.dimension1) {
+            let payload = point.to_json_with(self.value_format);
+            let fanout = Fanout {
+                results: collections
+                    .iter()
+                    .map(|&id| (id, Ok(Receipt::new(&payload, Outcome::Filtered))))
+                    .collect(),
+                rollbacks: Vec::new(),
+            };
+            return Ok((fanout, None));
+        }
+
+        let point = self.prepare_point(point)?;
+        let fanout = Fanout {
+            results: collections
+                .iter()
+                .map(|&id| (id, self.post_point(point.clone(), id)))
+                .collect(),
+            rollbacks: Vec::new(),
+        };
+        Ok((fanout, Some(point)))
+    }
+}
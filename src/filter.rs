@@ -0,0 +1,60 @@
+/// A pattern matched against keys by a [`KeyFilter`].
+#[derive(Clone, Debug)]
+pub enum KeyPattern {
+    /// The key is exactly this string.
+    Exact(String),
+    /// The key starts with this string.
+    Prefix(String),
+    /// The key matches this regular expression, anywhere unless anchored.
+    ///
+    /// Requires the `regex` feature.
+    #[cfg(feature = "regex")]
+    Regex(regex::Regex),
+}
+
+impl KeyPattern {
+    fn matches(&self, key: &str) -> bool {
+        match self {
+            KeyPattern::Exact(exact) => key == exact,
+            KeyPattern::Prefix(prefix) => key.starts_with(prefix.as_str()),
+            #[cfg(feature = "regex")]
+            KeyPattern::Regex(regex) => regex.is_match(key),
+        }
+    }
+}
+
+/// Allow and deny lists of keys, to exclude noisy or sensitive metrics without changing the
+/// producers, see [`SyncClient::with_key_filter`](crate::SyncClient::with_key_filter).
+///
+/// A key passes the filter if it matches one of the allowed patterns (or if there are none), and
+/// none of the denied patterns.
+#[derive(Clone, Debug, Default)]
+pub struct KeyFilter {
+    allow: Vec<KeyPattern>,
+    deny: Vec<KeyPattern>,
+}
+
+impl KeyFilter {
+    /// A filter letting all keys through, until patterns are added.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only allow keys matching this pattern, or one of the other allowed patterns.
+    pub fn allow(mut self, pattern: KeyPattern) -> Self {
+        self.allow.push(pattern);
+        self
+    }
+
+    /// Reject keys matching this pattern.
+    pub fn deny(mut self, pattern: KeyPattern) -> Self {
+        self.deny.push(pattern);
+        self
+    }
+
+    /// Whether a key passes the filter.
+    pub fn allows(&self, key: &str) -> bool {
+        (self.allow.is_empty() || self.allow.iter().any(|pattern| pattern.matches(key)))
+            && !self.deny.iter().any(|pattern| pattern.matches(key))
+    }
+}
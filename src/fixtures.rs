@@ -0,0 +1,95 @@
+use std::fs;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use reqwest as http;
+use serde_json::{json, Map, Value};
+
+use crate::redact::Redactions;
+
+/// Headers whose values are never written to fixtures.
+const SECRET_HEADERS: &[http::header::HeaderName] = &[
+    http::header::AUTHORIZATION,
+    http::header::COOKIE,
+    http::header::SET_COOKIE,
+    http::header::PROXY_AUTHORIZATION,
+];
+
+/// Writes request/response pairs to a directory, one JSON file per exchange.
+#[derive(Clone)]
+pub(crate) struct FixtureRecorder {
+    dir: PathBuf,
+    counter: Arc<AtomicU64>,
+}
+
+impl FixtureRecorder {
+    pub fn new(dir: PathBuf) -> Self {
+        Self {
+            dir,
+            counter: Arc::default(),
+        }
+    }
+
+    /// Captures the parts of a request to record, before it's consumed by sending it.
+    pub fn capture_request(&self, req: &http::blocking::Request, redactions: &Redactions) -> Value {
+        let body = match req.body() {
+            Some(body) => match body.as_bytes() {
+                Some(bytes) => redactions.apply(&String::from_utf8_lossy(bytes)).into(),
+                None => "<streamed body>".into(),
+            },
+            None => Value::Null,
+        };
+
+        json!({
+            "method": req.method().as_str(),
+            "url": redactions.apply(req.url().as_str()),
+            "headers": sanitized_headers(req.headers()),
+            "body": body,
+        })
+    }
+
+    pub fn record(
+        &self,
+        request: Value,
+        status: http::StatusCode,
+        headers: &http::header::HeaderMap,
+        body: &str,
+    ) {
+        let fixture = json!({
+            "request": request,
+            "response": {
+                "status": status.as_u16(),
+                "headers": sanitized_headers(headers),
+                "body": body,
+            },
+        });
+
+        let millis = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis())
+            .unwrap_or(0);
+        let index = self.counter.fetch_add(1, Ordering::Relaxed);
+        let path = self
+            .dir
+            .join(format!("fixture-{}-{:04}.json", millis, index));
+
+        // Best effort: recording must never make the API call fail.
+        let _ =
+            fs::create_dir_all(&self.dir).and_then(|()| fs::write(path, format!("{:#}", fixture)));
+    }
+}
+
+fn sanitized_headers(headers: &http::header::HeaderMap) -> Value {
+    let mut result = Map::new();
+    for (name, value) in headers {
+        let value = if SECRET_HEADERS.contains(name) {
+            "<redacted>".to_string()
+        } else {
+            String::from_utf8_lossy(value.as_bytes()).into_owned()
+        };
+        result.insert(name.as_str().to_string(), value.into());
+    }
+    Value::Object(result)
+}
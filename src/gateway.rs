@@ -0,0 +1,383 @@
+//! A minimal HTTP ingestion endpoint forwarding data points to Nextcloud Analytics, turning the
+//! crate into a small metrics gateway for other services on the LAN.
+//!
+//! Services `POST /points` a JSON data point, or an array of them:
+//!
+//! ```text
+//! [{"dimension1": "temp_c", "dimension2": "kitchen", "dimension3": 21.5}]
+//! ```
+//!
+//! The points of a request are forwarded in a single batch with [`SyncClient::send_batch`],
+//! and the gateway answers `{"success": true, "sent": <count>}`; or they're queued for a
+//! [`BackgroundSender`](crate::BackgroundSender) shared with the rest of the process, see
+//! [`Gateway::with_sender`], and it answers `{"success": true, "queued": <count>}`. Errors are
+//! answered with an error status and `{"success": false, "error": {"code": ..., "message":
+//! ...}}`, where `code` is one of the [`ErrorClass`](crate::ErrorClass) codes, or
+//! `E_BAD_REQUEST`/`E_UNAUTHORIZED`/`E_BUSY` for requests the gateway itself refuses.
+//!
+//! Requests are served by a fixed number of worker threads, and must be received whole within
+//! a deadline, so that slow or malicious clients can't exhaust the gateway.
+//!
+//! Requires the `gateway` feature.
+
+use std::error::Error;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use serde_json::{json, Value};
+
+use crate::{DataPoint, SenderHandle, SyncClient};
+
+/// Error code for invalid requests to the gateway.
+const BAD_REQUEST: &str = "E_BAD_REQUEST";
+
+/// Error code for points refused because the background sender can't queue them.
+const BUSY: &str = "E_BUSY";
+
+/// Largest request body accepted, in bytes.
+const MAX_BODY_LEN: usize = 4 * 1024 * 1024;
+
+/// Longest request or header line accepted, in bytes.
+const MAX_LINE_LEN: usize = 8 * 1024;
+
+/// Largest number of headers accepted.
+const MAX_HEADERS: usize = 100;
+
+/// Default number of requests served at once.
+const DEFAULT_WORKERS: usize = 16;
+
+/// Default time to receive a whole request.
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// An HTTP server accepting data points and forwarding them with a shared client.
+pub struct Gateway {
+    listener: TcpListener,
+    client: Arc<SyncClient>,
+    sender: Option<SenderHandle>,
+    token: Option<String>,
+    workers: usize,
+    timeout: Duration,
+}
+
+impl Gateway {
+    /// Binds the gateway to the given address, e.g. `"0.0.0.0:8125"`.
+    pub fn bind<A: ToSocketAddrs>(addr: A, client: Arc<SyncClient>) -> std::io::Result<Self> {
+        Ok(Self {
+            listener: TcpListener::bind(addr)?,
+            client,
+            sender: None,
+            token: None,
+            workers: DEFAULT_WORKERS,
+            timeout: DEFAULT_TIMEOUT,
+        })
+    }
+
+    /// The address the gateway is bound to, e.g. to find the port picked for `"127.0.0.1:0"`.
+    pub fn local_addr(&self) -> std::io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// Only accept requests with an `Authorization: Bearer <token>` header.
+    pub fn with_token<S: Into<String>>(mut self, token: S) -> Self {
+        self.token = Some(token.into());
+        self
+    }
+
+    /// Queue the points received for a background sender instead of sending them right away,
+    /// so that they're batched with the points of other requests and the rest of the process.
+    /// Points refused because its queue is full are answered with a `503` status.
+    pub fn with_sender(mut self, sender: SenderHandle) -> Self {
+        self.sender = Some(sender);
+        self
+    }
+
+    /// Serve up to `workers` requests at once, 16 by default; the next connections wait to be
+    /// accepted meanwhile.
+    pub fn with_workers(mut self, workers: usize) -> Self {
+        self.workers = workers.max(1);
+        self
+    }
+
+    /// Time for a client to send a whole request, and to receive the answer, 30 seconds by
+    /// default; the connection is closed beyond.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Serves requests forever.
+    pub fn serve(self) -> std::io::Result<()> {
+        // Handing connections over without buffering makes accepting wait for a free worker.
+        let (connections, pending) = mpsc::sync_channel::<TcpStream>(0);
+        let pending = Arc::new(Mutex::new(pending));
+        let forward = Arc::new(Forward {
+            client: self.client,
+            sender: self.sender,
+            token: self.token.map(|token| format!("Bearer {}", token)),
+            timeout: self.timeout,
+        });
+        for _ in 0..self.workers {
+            let pending = Arc::clone(&pending);
+            let forward = Arc::clone(&forward);
+            thread::spawn(move || loop {
+                let stream = match pending.lock().unwrap().recv() {
+                    Ok(stream) => stream,
+                    Err(_) => break,
+                };
+                let _ = handle(stream, &forward);
+            });
+        }
+
+        for stream in self.listener.incoming() {
+            match stream {
+                Ok(stream) => {
+                    if connections.send(stream).is_err() {
+                        break;
+                    }
+                }
+                // Failing to accept a connection, e.g. out of file descriptors, shouldn't stop
+                // the gateway, but give the workers time to close theirs.
+                Err(_) => thread::sleep(Duration::from_millis(10)),
+            }
+        }
+        Ok(())
+    }
+}
+
+/// What the workers of a gateway share.
+struct Forward {
+    client: Arc<SyncClient>,
+    sender: Option<SenderHandle>,
+    /// Expected value of the `Authorization` header, if any.
+    token: Option<String>,
+    timeout: Duration,
+}
+
+/// A stream whose reads fail once a deadline is over, however slowly the data arrives.
+struct Deadline<'a> {
+    stream: &'a TcpStream,
+    deadline: Instant,
+}
+
+impl Read for Deadline<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let left = self.deadline.saturating_duration_since(Instant::now());
+        if left.is_zero() {
+            return Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                "request not received in time",
+            ));
+        }
+        self.stream.set_read_timeout(Some(left))?;
+        self.stream.read(buf)
+    }
+}
+
+struct Request {
+    method: String,
+    path: String,
+    authorization: Option<String>,
+    body: Vec<u8>,
+}
+
+/// Reads a line of at most [`MAX_LINE_LEN`] bytes into `line`, returning its length.
+fn read_line<R: BufRead>(reader: &mut R, line: &mut String) -> Result<usize, Box<dyn Error>> {
+    line.clear();
+    let len = reader.by_ref().take(MAX_LINE_LEN as u64).read_line(line)?;
+    if len == MAX_LINE_LEN && !line.ends_with('\n') {
+        return Err(format!("request line longer than {} bytes", MAX_LINE_LEN).into());
+    }
+    Ok(len)
+}
+
+fn read_request<R: Read>(stream: R) -> Result<Request, Box<dyn Error>> {
+    let mut reader = BufReader::new(stream);
+
+    let mut line = String::new();
+    read_line(&mut reader, &mut line)?;
+    let mut parts = line.split_whitespace();
+    let method = parts.next().unwrap_or_default().to_string();
+    let path = parts.next().unwrap_or_default().to_string();
+
+    let mut content_length = 0;
+    let mut authorization = None;
+    for headers in 0.. {
+        if headers > MAX_HEADERS {
+            return Err(format!("more than {} headers", MAX_HEADERS).into());
+        }
+        if read_line(&mut reader, &mut line)? == 0 {
+            break;
+        }
+        let header = line.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            let value = value.trim();
+            if name.eq_ignore_ascii_case("content-length") {
+                content_length = value.parse()?;
+            } else if name.eq_ignore_ascii_case("authorization") {
+                authorization = Some(value.to_string());
+            }
+        }
+    }
+
+    if content_length > MAX_BODY_LEN {
+        return Err(format!("request body too large ({} bytes)", content_length).into());
+    }
+
+    let mut body = vec![0; content_length];
+    reader.read_exact(&mut body)?;
+
+    Ok(Request {
+        method,
+        path,
+        authorization,
+        body,
+    })
+}
+
+fn parse_point(value: &Value) -> Option<DataPoint> {
+    let dimension3 = value["dimension3"]
+        .as_f64()
+        .or_else(|| value["dimension3"].as_str()?.parse().ok())?;
+    Some(DataPoint::new(
+        value["dimension1"].as_str()?,
+        value["dimension2"].as_str()?,
+        dimension3,
+    ))
+}
+
+fn parse_points(body: &[u8]) -> Result<Vec<DataPoint>, String> {
+    let body = std::str::from_utf8(body).map_err(|err| err.to_string())?;
+    let value: Value = serde_json::from_str(body).map_err(|err| err.to_string())?;
+
+    let values: Vec<&Value> = match &value {
+        Value::Array(values) => values.iter().collect(),
+        value => vec![value],
+    };
+
+    values
+        .into_iter()
+        .map(|value| {
+            parse_point(value).ok_or_else(|| {
+                format!(
+                    "invalid data point, expected dimension1, dimension2 and dimension3: {}",
+                    value
+                )
+            })
+        })
+        .collect()
+}
+
+/// Compares two strings in a time which doesn't depend on where they differ, not to give away
+/// the length of the valid prefix of a guessed token.
+fn constant_time_eq(a: &str, b: &str) -> bool {
+    a.len() == b.len()
+        && a.bytes()
+            .zip(b.bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+fn handle(mut stream: TcpStream, forward: &Forward) -> Result<(), Box<dyn Error>> {
+    let deadline = Instant::now() + forward.timeout;
+    stream.set_write_timeout(Some(forward.timeout))?;
+
+    let request = read_request(Deadline {
+        stream: &stream,
+        deadline,
+    });
+    let (status, body) = match request {
+        Err(err) => {
+            let timed_out = err.downcast_ref::<io::Error>().is_some_and(|err| {
+                matches!(
+                    err.kind(),
+                    io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock
+                )
+            });
+            let status = if timed_out {
+                "408 Request Timeout"
+            } else {
+                "400 Bad Request"
+            };
+            (status, error_body(BAD_REQUEST, &err.to_string()))
+        }
+        Ok(req) => {
+            let authorized = match (&forward.token, &req.authorization) {
+                (None, _) => true,
+                (Some(expected), Some(given)) => constant_time_eq(expected, given),
+                (Some(_), None) => false,
+            };
+            if !authorized {
+                (
+                    "401 Unauthorized",
+                    error_body("E_UNAUTHORIZED", "invalid or missing token"),
+                )
+            } else if req.path != "/points" {
+                (
+                    "404 Not Found",
+                    error_body(BAD_REQUEST, "unknown path, use /points"),
+                )
+            } else if req.method != "POST" {
+                (
+                    "405 Method Not Allowed",
+                    error_body(BAD_REQUEST, "use POST"),
+                )
+            } else {
+                match parse_points(&req.body) {
+                    Err(err) => ("400 Bad Request", error_body(BAD_REQUEST, &err)),
+                    Ok(points) => match &forward.sender {
+                        Some(sender) => enqueue(sender, points),
+                        None => send(&forward.client, &points),
+                    },
+                }
+            }
+        }
+    };
+
+    let body = body.to_string();
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    )?;
+    stream.flush()?;
+    Ok(())
+}
+
+fn send(client: &SyncClient, points: &[DataPoint]) -> (&'static str, Value) {
+    match client.send_batch(points) {
+        Ok(receipt) => {
+            let sent = receipt.report().map_or(0, |report| report.rows);
+            ("200 OK", json!({ "success": true, "sent": sent }))
+        }
+        Err(err) => ("502 Bad Gateway", error_body(err.code(), &err.to_string())),
+    }
+}
+
+fn enqueue(sender: &SenderHandle, points: Vec<DataPoint>) -> (&'static str, Value) {
+    let mut queued = 0;
+    for point in points {
+        // The queue is full, or the sender stopped as the process shuts down.
+        if let Err(err) = sender.enqueue_point(point) {
+            let mut body = error_body(BUSY, &err.to_string());
+            body["queued"] = queued.into();
+            return ("503 Service Unavailable", body);
+        }
+        queued += 1;
+    }
+    ("202 Accepted", json!({ "success": true, "queued": queued }))
+}
+
+fn error_body(code: &str, message: &str) -> Value {
+    json!({
+        "success": false,
+        "error": { "code": code, "message": message },
+    })
+}
@@ -0,0 +1,107 @@
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::retry::random_fraction;
+use crate::SyncClient;
+
+/// What a [`Heartbeat`] sends on every beat.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HeartbeatValue {
+    /// Always 1.
+    One,
+    /// Number of seconds since the heartbeat was started.
+    Uptime,
+}
+
+/// Pushes a timeline point on an interval, as a device-alive signal for dashboards.
+///
+/// The heartbeat runs on its own thread, and stops when dropped. Failed sends are ignored: the
+/// missing points are the signal.
+pub struct Heartbeat {
+    stop: Option<mpsc::Sender<()>>,
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+impl Heartbeat {
+    /// Starts sending `1` for `key` every `interval`, the first one right away.
+    pub fn start<S: Into<String>>(client: Arc<SyncClient>, key: S, interval: Duration) -> Self {
+        Self::start_with(client, key, interval, HeartbeatValue::One)
+    }
+
+    /// Starts sending the given kind of value for `key` every `interval`, the first one right
+    /// away.
+    pub fn start_with<S: Into<String>>(
+        client: Arc<SyncClient>,
+        key: S,
+        interval: Duration,
+        value: HeartbeatValue,
+    ) -> Self {
+        Self::start_with_jitter(client, key, interval, value, Duration::from_secs(0))
+    }
+
+    /// Starts sending the given kind of value for `key` every `interval`, each beat delayed by
+    /// a random time up to `jitter`, the first one included, so that machines started at the
+    /// same time, e.g. by cron, don't all send at once.
+    ///
+    /// Combined with a threshold on `key` in Analytics, this makes a dead man's switch:
+    ///
+    /// ```no_run
+    /// # use std::sync::Arc;
+    /// # use std::time::Duration;
+    /// # use nextcloud_analytics_rs::{Heartbeat, HeartbeatValue, SyncClient};
+    /// let client = SyncClient::new("https://example.com/nextcloud", 3, "myself", "hunter2");
+    /// let minute = Duration::from_secs(60);
+    /// let _heartbeat = Heartbeat::start_with_jitter(
+    ///     Arc::new(client),
+    ///     "backup-box",
+    ///     minute,
+    ///     HeartbeatValue::Uptime,
+    ///     minute / 4,
+    /// );
+    /// ```
+    pub fn start_with_jitter<S: Into<String>>(
+        client: Arc<SyncClient>,
+        key: S,
+        interval: Duration,
+        value: HeartbeatValue,
+        jitter: Duration,
+    ) -> Self {
+        let key = key.into();
+        let (stop, stopped) = mpsc::channel();
+        let started = Instant::now();
+        let delay = move || jitter.mul_f64(random_fraction());
+
+        let thread = thread::spawn(move || {
+            let mut wait = delay();
+            // Stops when told to, or when the sender is dropped.
+            while let Err(RecvTimeoutError::Timeout) = stopped.recv_timeout(wait) {
+                let value = match value {
+                    HeartbeatValue::One => 1.0,
+                    HeartbeatValue::Uptime => started.elapsed().as_secs() as f64,
+                };
+                let _ = client.send_timeline_now_data(key.as_str(), value);
+                wait = interval + delay();
+            }
+        });
+
+        Self {
+            stop: Some(stop),
+            thread: Some(thread),
+        }
+    }
+
+    /// Stops the heartbeat, waiting for an ongoing send to finish. Same as dropping it.
+    pub fn stop(self) {}
+}
+
+impl Drop for Heartbeat {
+    fn drop(&mut self) {
+        // Dropping the sender wakes the thread up.
+        self.stop.take();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
@@ -0,0 +1,180 @@
+use std::collections::BTreeMap;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use crate::{AnalyticsError, Receipt, SyncClient};
+
+enum Instrument {
+    /// Last value set.
+    Gauge(f64),
+    /// Sum of the increments since the last flush.
+    Counter(f64),
+}
+
+/// Gauges and counters of a client, waiting to be flushed.
+#[derive(Clone, Default)]
+pub(crate) struct Instruments {
+    values: Arc<Mutex<BTreeMap<String, Instrument>>>,
+}
+
+/// A value that goes up and down, e.g. a temperature, see [`SyncClient::gauge`].
+pub struct Gauge<'a> {
+    client: &'a SyncClient,
+    key: String,
+}
+
+impl Gauge<'_> {
+    /// Sets the value, sent at every flush until it's set again.
+    pub fn set<F: Into<f64>>(&self, value: F) {
+        self.client
+            .instruments
+            .values
+            .lock()
+            .unwrap()
+            .insert(self.key.clone(), Instrument::Gauge(value.into()));
+    }
+}
+
+/// A count of events, e.g. handled requests, see [`SyncClient::counter`].
+pub struct Counter<'a> {
+    client: &'a SyncClient,
+    key: String,
+}
+
+impl Counter<'_> {
+    /// Counts one event.
+    pub fn inc(&self) {
+        self.inc_by(1);
+    }
+
+    /// Counts `count` events. Negative and NaN counts are ignored, as counters only go up.
+    pub fn inc_by<F: Into<f64>>(&self, count: F) {
+        let count = count.into();
+        if count.is_nan() || count < 0.0 {
+            return;
+        }
+        let mut values = self.client.instruments.values.lock().unwrap();
+        match values.get_mut(&self.key) {
+            Some(Instrument::Counter(sum)) => *sum += count,
+            _ => {
+                values.insert(self.key.clone(), Instrument::Counter(count));
+            }
+        }
+    }
+}
+
+impl SyncClient {
+    /// A gauge for `key`, whose last value is sent as timeline data by
+    /// [`SyncClient::flush_metrics`].
+    ///
+    /// Gauges and counters share the same keys: using a key for both replaces one by the other.
+    ///
+    /// Requires the `chrono` feature.
+    pub fn gauge<S: Into<String>>(&self, key: S) -> Gauge<'_> {
+        Gauge {
+            client: self,
+            key: key.into(),
+        }
+    }
+
+    /// A counter for `key`, accumulating events locally; the number of events since the last
+    /// flush is sent as timeline data by [`SyncClient::flush_metrics`], 0 if there was none.
+    ///
+    /// Requires the `chrono` feature.
+    pub fn counter<S: Into<String>>(&self, key: S) -> Counter<'_> {
+        let key = key.into();
+        self.instruments
+            .values
+            .lock()
+            .unwrap()
+            .entry(key.clone())
+            .or_insert(Instrument::Counter(0.0));
+        Counter { client: self, key }
+    }
+
+    /// Sends the values of all the gauges and counters in a single batch stamped with the
+    /// current time, and resets the counters.
+    ///
+    /// If the send fails, the counts are kept for the next flush.
+    ///
+    /// Requires the `chrono` feature.
+    pub fn flush_metrics(&self) -> Result<Receipt, AnalyticsError> {
+        let mut batch = self.timeline_batch();
+        let mut counts = Vec::new();
+        {
+            let mut values = self.instruments.values.lock().unwrap();
+            for (key, instrument) in values.iter_mut() {
+                match instrument {
+                    Instrument::Gauge(value) => {
+                        batch.add(key.as_str(), *value);
+                    }
+                    Instrument::Counter(sum) => {
+                        batch.add(key.as_str(), *sum);
+                        counts.push((key.clone(), *sum));
+                        *sum = 0.0;
+                    }
+                }
+            }
+        }
+
+        let result = batch.send();
+        if result.is_err() {
+            let mut values = self.instruments.values.lock().unwrap();
+            for (key, count) in counts {
+                if let Some(Instrument::Counter(sum)) = values.get_mut(&key) {
+                    *sum += count;
+                }
+            }
+        }
+        result
+    }
+}
+
+/// Calls [`SyncClient::flush_metrics`] on an interval, from its own thread.
+///
+/// The flusher flushes one last time when stopped or dropped. Failed flushes are reported
+/// through the client's callbacks and failure notifier, see [`SyncClient::on_error`].
+///
+/// Requires the `chrono` feature.
+pub struct MetricsFlusher {
+    stop: Option<mpsc::Sender<()>>,
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+impl MetricsFlusher {
+    /// Starts flushing the metrics of `client` every `interval`.
+    pub fn start(client: Arc<SyncClient>, interval: Duration) -> Self {
+        let (stop, stopped) = mpsc::channel();
+
+        let thread = thread::spawn(move || loop {
+            let stop = match stopped.recv_timeout(interval) {
+                Err(RecvTimeoutError::Timeout) => false,
+                Ok(()) | Err(RecvTimeoutError::Disconnected) => true,
+            };
+            let _ = client.flush_metrics();
+            if stop {
+                break;
+            }
+        });
+
+        Self {
+            stop: Some(stop),
+            thread: Some(thread),
+        }
+    }
+
+    /// Stops the flusher, after a last flush. Same as dropping it.
+    pub fn stop(self) {}
+}
+
+impl Drop for MetricsFlusher {
+    fn drop(&mut self) {
+        // Dropping the sender wakes the thread up.
+        self.stop.take();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
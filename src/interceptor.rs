@@ -0,0 +1,66 @@
+use std::sync::Arc;
+
+use crate::{AnalyticsError, Request, Response, SyncClient};
+
+/// Hooks called around every request of a [`SyncClient`], see
+/// [`SyncClient::with_interceptor`], e.g. to add the headers required by a single sign-on
+/// proxy, or to sign requests.
+///
+/// ```no_run
+/// # use nextcloud_analytics_rs::{AnalyticsError, Interceptor, Request, SyncClient};
+/// struct ProxyHeader;
+///
+/// impl Interceptor for ProxyHeader {
+///     fn on_request(&self, request: &mut Request) -> Result<(), AnalyticsError> {
+///         request
+///             .headers
+///             .insert("X-Proxy-Token", "s3cr3t".parse().unwrap());
+///         Ok(())
+///     }
+/// }
+///
+/// let client = SyncClient::new("https://example.com/nextcloud", 3, "myself", "hunter2")
+///     .with_interceptor(ProxyHeader);
+/// ```
+pub trait Interceptor: Send + Sync {
+    /// Called before a request is handed to the transport, with the credentials and headers
+    /// already set, to modify it. Returning an error fails the request without sending it.
+    fn on_request(&self, request: &mut Request) -> Result<(), AnalyticsError> {
+        let _ = request;
+        Ok(())
+    }
+
+    /// Called with the response to a request, as modified by the interceptors, whatever its
+    /// status.
+    fn on_response(&self, request: &Request, response: &Response) {
+        let _ = (request, response);
+    }
+}
+
+impl SyncClient {
+    /// Add an interceptor called around every request of the client, sends as well as reads,
+    /// after the interceptors added before it.
+    ///
+    /// Interceptors run on every attempt of a retried request, after the rate limits, and
+    /// before fixture recording sees the response. The body of the responses to
+    /// [`SyncClient::download_export`] isn't given to them.
+    pub fn with_interceptor<I: Interceptor + 'static>(mut self, interceptor: I) -> Self {
+        self.interceptors.push(Arc::new(interceptor));
+        self
+    }
+
+    /// Runs the request hooks of the interceptors.
+    pub(crate) fn intercept_request(&self, request: &mut Request) -> Result<(), AnalyticsError> {
+        for interceptor in &self.interceptors {
+            interceptor.on_request(request)?;
+        }
+        Ok(())
+    }
+
+    /// Runs the response hooks of the interceptors.
+    pub(crate) fn intercept_response(&self, request: &Request, response: &Response) {
+        for interceptor in &self.interceptors {
+            interceptor.on_response(request, response);
+        }
+    }
+}
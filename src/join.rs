@@ -0,0 +1,91 @@
+use chrono::{DateTime, Duration, Utc};
+
+/// How unmatched points are handled by [`join_series`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum JoinKind {
+    /// Only keep the timestamps present in both series.
+    Inner,
+    /// Keep all the timestamps, with `None` for the series missing a point.
+    Outer,
+}
+
+/// Aligns two timeline series on their timestamps, e.g. to compute the ratio of energy produced
+/// to energy consumed.
+///
+/// Two points match when their timestamps are at most `tolerance` apart, preferring the next
+/// point of either series if it's closer; the matched tuple has the timestamp of the point of
+/// `a`. Each point is matched at most once. The series don't need to be sorted, and the result
+/// is sorted by timestamp.
+///
+/// Requires the `chrono` feature.
+pub fn join_series(
+    a: &[(DateTime<Utc>, f64)],
+    b: &[(DateTime<Utc>, f64)],
+    kind: JoinKind,
+    tolerance: Duration,
+) -> Vec<(DateTime<Utc>, Option<f64>, Option<f64>)> {
+    let mut a = a.to_vec();
+    let mut b = b.to_vec();
+    a.sort_by_key(|&(time, _)| time);
+    b.sort_by_key(|&(time, _)| time);
+
+    let outer = kind == JoinKind::Outer;
+    let distance = |a: DateTime<Utc>, b: DateTime<Utc>| (a - b).num_milliseconds().abs();
+    let mut result = Vec::with_capacity(a.len().max(b.len()));
+    let (mut i, mut j) = (0, 0);
+
+    while i < a.len() && j < b.len() {
+        let (time_a, value_a) = a[i];
+        let (time_b, value_b) = b[j];
+
+        let apart = distance(time_a, time_b);
+        if apart <= tolerance.num_milliseconds() {
+            if b.get(j + 1)
+                .is_some_and(|&(next, _)| distance(time_a, next) < apart)
+            {
+                if outer {
+                    result.push((time_b, None, Some(value_b)));
+                }
+                j += 1;
+                continue;
+            }
+            if a.get(i + 1)
+                .is_some_and(|&(next, _)| distance(next, time_b) < apart)
+            {
+                if outer {
+                    result.push((time_a, Some(value_a), None));
+                }
+                i += 1;
+                continue;
+            }
+            result.push((time_a, Some(value_a), Some(value_b)));
+            i += 1;
+            j += 1;
+        } else if time_a < time_b {
+            if outer {
+                result.push((time_a, Some(value_a), None));
+            }
+            i += 1;
+        } else {
+            if outer {
+                result.push((time_b, None, Some(value_b)));
+            }
+            j += 1;
+        }
+    }
+
+    if outer {
+        result.extend(
+            a[i..]
+                .iter()
+                .map(|&(time, value)| (time, Some(value), None)),
+        );
+        result.extend(
+            b[j..]
+                .iter()
+                .map(|&(time, value)| (time, None, Some(value))),
+        );
+    }
+
+    result
+}
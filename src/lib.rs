@@ -2,6 +2,7 @@
 //!
 //! A small Rust wrapper to call the [Nextcloud Analytics
 //! API](https://github.com/rello/analytics/wiki/API), for databases of type "internal database".
+//! Datasets backed by external data sources can be read, see [`datasets::DatasetKind`].
 //!
 //! Example of usage:
 //!
@@ -12,66 +13,697 @@
 //!   let passwd = "hunter2";
 //!
 //!   let client = nextcloud_analytics_rs::SyncClient::new(base_url, collection, user, passwd);
-//!   client.send_timeline_now_data("speed_kmh", 180).unwrap_or_else(|_| println!("api or network error"));
-//!   client.send_timeline_now_data("power_level", 9001).unwrap_or_else(|_| println!("api or network error"));
+//! # #[cfg(feature = "chrono")]
+//! # {
+//!   if client.send_timeline_now_data("speed_kmh", 180).is_err() {
+//!       println!("api or network error");
+//!   }
+//!   if client.send_timeline_now_data("power_level", 9001).is_err() {
+//!       println!("api or network error");
+//!   }
+//! # }
 //!
 //!   let other_collection = 3;
 //!   let client = nextcloud_analytics_rs::SyncClient::new(base_url, other_collection, user, passwd);
-//!   client.send_data("age", "alice", 25).unwrap_or_else(|_| println!("api or network error"));
-//!   client.send_data("age", "bob", 20).unwrap_or_else(|_| println!("api or network error"));
+//!   if client.send_data("age", "alice", 25).is_err() {
+//!       println!("api or network error");
+//!   }
+//!   if client.send_data("age", "bob", 20).is_err() {
+//!       println!("api or network error");
+//!   }
 //! ```
 
-use core::fmt;
-use std::error::Error;
+// Without a client, the helpers they share are unused.
+#![cfg_attr(
+    not(any(feature = "blocking", feature = "async")),
+    allow(dead_code, unused_imports)
+)]
 
+#[cfg(feature = "blocking")]
+use std::collections::HashMap;
+#[cfg(feature = "blocking")]
+use std::path::PathBuf;
+#[cfg(all(feature = "blocking", feature = "compression"))]
+use std::sync::atomic::AtomicBool;
+#[cfg(feature = "blocking")]
+use std::sync::atomic::{AtomicU32, Ordering};
+#[cfg(feature = "blocking")]
+use std::sync::{Arc, Mutex};
+#[cfg(feature = "blocking")]
+use std::time::{Duration, SystemTime};
+
+#[cfg(all(feature = "blocking", feature = "chrono"))]
 use chrono::{DateTime, Utc};
+#[cfg(feature = "blocking")]
 use reqwest as http;
+use serde::Deserialize;
+
+#[cfg(all(feature = "blocking", feature = "chrono"))]
+mod aggregator;
+#[cfg(all(feature = "blocking", feature = "chrono"))]
+mod align;
+mod api;
+#[cfg(feature = "async")]
+mod async_client;
+#[cfg(feature = "blocking")]
+mod audit;
+#[cfg(all(feature = "blocking", feature = "chrono"))]
+mod backfill;
+#[cfg(feature = "blocking")]
+mod background;
+mod batch;
+mod builder;
+#[cfg(feature = "blocking")]
+mod cache;
+#[cfg(feature = "blocking")]
+mod capabilities;
+#[cfg(feature = "blocking")]
+mod category;
+#[cfg(feature = "blocking")]
+mod check;
+#[cfg(all(feature = "blocking", feature = "chrono"))]
+mod clock;
+#[cfg(feature = "blocking")]
+mod coalesce;
+#[cfg(feature = "blocking")]
+mod collection;
+#[cfg(feature = "blocking")]
+pub mod collectors;
+#[cfg(feature = "compression")]
+mod compress;
+#[cfg(feature = "blocking")]
+mod config;
+mod credentials;
+#[cfg(feature = "blocking")]
+mod csv;
+#[cfg(feature = "blocking")]
+pub mod dataload;
+#[cfg(feature = "blocking")]
+pub mod datasets;
+#[cfg(feature = "chrono")]
+mod dates;
+#[cfg(feature = "blocking")]
+mod dedupe;
+#[cfg(feature = "blocking")]
+mod defaults;
+#[cfg(feature = "blocking")]
+mod derived;
+mod describe;
+#[cfg(feature = "blocking")]
+mod dimension;
+#[cfg(feature = "blocking")]
+mod duplicates;
+#[cfg(feature = "blocking")]
+mod encoding;
+mod error;
+#[cfg(feature = "blocking")]
+mod explain;
+#[cfg(feature = "blocking")]
+mod export;
+#[cfg(feature = "blocking")]
+mod fanout;
+#[cfg(feature = "blocking")]
+mod filter;
+#[cfg(feature = "blocking")]
+mod fixtures;
+#[cfg(feature = "gateway")]
+pub mod gateway;
+#[cfg(all(feature = "blocking", feature = "chrono"))]
+mod heartbeat;
+#[cfg(all(feature = "blocking", feature = "chrono"))]
+mod instruments;
+#[cfg(feature = "blocking")]
+mod interceptor;
+#[cfg(all(feature = "blocking", feature = "chrono"))]
+mod join;
+#[cfg(feature = "blocking")]
+mod lockfile;
+#[cfg(feature = "blocking")]
+pub mod login;
+#[cfg(feature = "blocking")]
+mod maintenance;
+mod mapping;
+#[cfg(feature = "blocking")]
+mod matrix;
+#[cfg(feature = "metrics-exporter")]
+pub mod metrics_exporter;
+#[cfg(feature = "mirror")]
+pub mod mirror;
+#[cfg(feature = "blocking")]
+mod multi;
+#[cfg(feature = "blocking")]
+mod notify;
+#[cfg(feature = "blocking")]
+mod payload;
+#[cfg(feature = "privacy")]
+pub mod privacy;
+#[cfg(feature = "blocking")]
+mod progress;
+#[cfg(feature = "prometheus")]
+pub mod prometheus;
+#[cfg(feature = "blocking")]
+mod provision;
+#[cfg(feature = "blocking")]
+mod purge;
+#[cfg(feature = "blocking")]
+mod quickstart;
+mod ratelimit;
+#[cfg(feature = "blocking")]
+mod raw;
+#[cfg(feature = "blocking")]
+mod read;
+mod receipt;
+#[cfg(feature = "blocking")]
+mod redact;
+#[cfg(feature = "blocking")]
+pub mod reports;
+#[cfg(feature = "blocking")]
+mod resolve;
+mod retry;
+mod row;
+#[cfg(feature = "zeroize")]
+mod secret;
+#[cfg(any(feature = "native-tls", feature = "rustls"))]
+mod security;
+#[cfg(feature = "blocking")]
+pub mod shares;
+#[cfg(feature = "blocking")]
+mod spool;
+#[cfg(feature = "blocking")]
+mod staging;
+#[cfg(feature = "blocking")]
+mod state;
+#[cfg(feature = "blocking")]
+mod stats;
+mod stream;
+#[cfg(feature = "blocking")]
+mod summary;
+#[cfg(feature = "blocking")]
+mod tags;
+mod template;
+#[cfg(feature = "blocking")]
+pub mod testing;
+#[cfg(feature = "blocking")]
+pub mod thresholds;
+#[cfg(feature = "blocking")]
+mod timeout;
+#[cfg(feature = "blocking")]
+mod trace;
+#[cfg(feature = "blocking")]
+mod transport;
+mod validate;
+#[cfg(feature = "blocking")]
+mod verify;
+#[cfg(feature = "blocking")]
+mod writer;
+
+#[cfg(all(feature = "blocking", feature = "chrono"))]
+pub use aggregator::{Aggregation, Aggregator};
+#[cfg(all(feature = "blocking", feature = "chrono"))]
+pub use align::Resolution;
+pub use api::ApiVersion;
+#[cfg(feature = "async")]
+pub use async_client::{AsyncClient, AsyncClientBuilder};
+#[cfg(all(feature = "blocking", feature = "chrono"))]
+pub use backfill::BackfillPlan;
+#[cfg(feature = "blocking")]
+pub use background::{
+    AdaptiveBatching, BackgroundSender, EnqueueError, Overflow, SenderHandle, SenderOptions,
+    SenderStats,
+};
+#[cfg(all(feature = "blocking", feature = "chrono"))]
+pub use batch::TimelineBatch;
+#[cfg(feature = "blocking")]
+pub use builder::{ConfigWarning, Profile, SyncClientBuilder};
+#[cfg(feature = "blocking")]
+pub use cache::{CacheStore, CachedResponse, MemoryCache};
+#[cfg(feature = "blocking")]
+pub use capabilities::Capabilities;
+#[cfg(feature = "blocking")]
+pub use category::SnapshotChanges;
+#[cfg(feature = "blocking")]
+pub use check::{CheckStatus, Diagnosis};
+#[cfg(feature = "blocking")]
+pub use coalesce::CoalescePolicy;
+#[cfg(feature = "blocking")]
+pub use collection::{Collection, CollectionHandle};
+#[cfg(feature = "compression")]
+pub use compress::Compression;
+#[cfg(feature = "blocking")]
+pub use config::ConfigError;
+pub use credentials::Credentials;
+#[cfg(feature = "blocking")]
+pub use csv::{Column, CsvError, CsvMapping};
+#[cfg(feature = "chrono")]
+pub use dates::{
+    format_timeline_date, parse_timeline_date, DatasetTimeZone, DateRangeError, TimestampFormat,
+};
+#[cfg(feature = "blocking")]
+pub use derived::Derived;
+pub use describe::ClientDescription;
+#[cfg(feature = "blocking")]
+pub use dimension::{Dimension, RowBuilder};
+#[cfg(feature = "blocking")]
+pub use duplicates::DuplicatePolicy;
+#[cfg(feature = "blocking")]
+pub use encoding::{DecodeError, Encoding};
+#[allow(deprecated)]
+pub use error::ApiError;
+pub use error::{AnalyticsError, ErrorClass};
+#[cfg(feature = "blocking")]
+pub use export::ExportSource;
+#[cfg(feature = "blocking")]
+pub use fanout::Fanout;
+#[cfg(feature = "blocking")]
+pub use filter::{KeyFilter, KeyPattern};
+#[cfg(all(feature = "blocking", feature = "chrono"))]
+pub use heartbeat::{Heartbeat, HeartbeatValue};
+#[cfg(all(feature = "blocking", feature = "chrono"))]
+pub use instruments::{Counter, Gauge, MetricsFlusher};
+#[cfg(feature = "blocking")]
+pub use interceptor::Interceptor;
+#[cfg(all(feature = "blocking", feature = "chrono"))]
+pub use join::{join_series, JoinKind};
+pub use mapping::{ColumnMapping, MappingError};
+#[cfg(feature = "blocking")]
+pub use multi::{CredentialProvider, MultiClient, MultiMode};
+pub use nextcloud_analytics_core::{parse_number, DataPoint, NumberFormat, ValueFormat};
+#[cfg(feature = "derive")]
+pub use nextcloud_analytics_derive::ToAnalyticsRow;
+#[cfg(feature = "blocking")]
+pub use notify::FailureNotifier;
+#[cfg(feature = "blocking")]
+pub use payload::{JsonEncoder, PayloadEncoder};
+#[cfg(feature = "blocking")]
+pub use progress::Progress;
+#[cfg(feature = "blocking")]
+pub use provision::ProvisionTemplate;
+#[cfg(feature = "blocking")]
+pub use purge::PurgePlan;
+#[cfg(feature = "blocking")]
+pub use quickstart::quickstart;
+pub use ratelimit::RateLimit;
+#[cfg(feature = "blocking")]
+pub use ratelimit::SharedRateLimit;
+#[cfg(feature = "blocking")]
+pub use raw::Payload;
+pub use receipt::{Outcome, Receipt, SendReport};
+pub use retry::{Backoff, RetryPolicy};
+pub use row::ToAnalyticsRow;
+#[cfg(any(feature = "native-tls", feature = "rustls"))]
+pub use security::{Security, TlsVersion};
+#[cfg(feature = "blocking")]
+pub use spool::{BufferedClient, SpoolFormat};
+#[cfg(feature = "blocking")]
+pub use staging::{CommitError, Staging};
+#[cfg(feature = "blocking")]
+pub use state::ClientState;
+#[cfg(feature = "blocking")]
+pub use stats::Stats;
+pub use stream::BatchReport;
+#[cfg(feature = "blocking")]
+pub use summary::{BulkSummary, SummaryTarget};
+#[cfg(feature = "blocking")]
+pub use tags::TagEncoding;
+pub use template::{Template, TemplateError};
+#[cfg(feature = "blocking")]
+pub use transport::{Request, Response, Transport};
+pub use validate::{Validation, ValidationError};
+#[cfg(feature = "blocking")]
+pub use writer::WriterTag;
 
-static URL_PREFIX: &'static str = "apps/analytics/api/1.0/adddata/{COLLECTION_ID}";
+#[cfg(feature = "blocking")]
+type ErrorCallback = Arc<dyn Fn(ErrorClass, &DataPoint) + Send + Sync>;
+#[cfg(feature = "blocking")]
+type RecoveredCallback = Arc<dyn Fn(u32) + Send + Sync>;
+#[cfg(feature = "blocking")]
+type Enrichment = Arc<dyn Fn(&mut DataPoint) + Send + Sync>;
 
 /// A synchronous client to call the Nextcloud Analytics API.
+///
+/// The client is `Send` and `Sync`: it can be shared between threads, e.g. in an `Arc`, and all
+/// its methods take `&self`. It's also cheap to clone, e.g. to move it into spawned threads:
+/// clones share their connection pool, [`Credentials`], and state such as rate limits, held
+/// back values, metrics and the maintenance queue. Configuring a clone with the `with_*`
+/// methods leaves the other clones as they were.
+///
+/// Each client owns a single HTTP client and connection pool, reused for all the calls: the
+/// underlying blocking `reqwest` client runs one background runtime thread for its whole
+/// lifetime, not one per call. Keep a client around rather than creating one per data point,
+/// and share its HTTP client with [`SyncClient::with_http_client`].
+#[cfg(feature = "blocking")]
+#[derive(Clone)]
 pub struct SyncClient {
     client: http::blocking::Client,
-    url: String,
-    user: String,
-    passwd: String,
+    base_url: String,
+    collection: u32,
+    api_version: ApiVersion,
+    #[cfg(feature = "compression")]
+    compression: Option<Compression>,
+    /// Whether the server rejected a compressed body.
+    #[cfg(feature = "compression")]
+    compression_rejected: Arc<AtomicBool>,
+    credentials: Credentials,
+    profile: Option<&'static str>,
+    timeout: Duration,
+    connect_timeout: Option<Duration>,
+    keep_alive: Option<Duration>,
+    retry: Option<RetryPolicy>,
+    /// Number of data points per request of streams, staged commits and matrices.
+    batch_size: usize,
+    key_template: Option<Template>,
+    key_filter: Option<KeyFilter>,
+    enrichments: Vec<Enrichment>,
+    failure_notifier: Option<FailureNotifier>,
+    consecutive_failures: Arc<AtomicU32>,
+    last_sends: Arc<Mutex<HashMap<String, SystemTime>>>,
+    error_callback: Option<ErrorCallback>,
+    recovered_callback: Option<RecoveredCallback>,
+    fixture_recorder: Option<fixtures::FixtureRecorder>,
+    transport: Option<Arc<dyn Transport>>,
+    /// Sends with `client`, see [`SyncClientBuilder::last_good_address`].
+    last_good: Option<Arc<resolve::LastGoodAddress>>,
+    interceptors: Vec<Arc<dyn Interceptor>>,
+    maintenance: Option<maintenance::MaintenanceQueue>,
+    redactions: redact::Redactions,
+    value_format: ValueFormat,
+    column_mapping: Option<ColumnMapping>,
+    summary_targets: Vec<SummaryTarget>,
+    coalescer: coalesce::Coalescer,
+    derivations: derived::Derivations,
+    upsert: bool,
+    strict: bool,
+    dry_run: bool,
+    dedupe: Option<dedupe::Dedupe>,
+    payload_encoder: Arc<dyn PayloadEncoder>,
+    tag_encoding: TagEncoding,
+    validation: Validation,
+    response_cache: Option<Arc<dyn CacheStore>>,
+    default_dimensions: defaults::DefaultDimensions,
+    dataset_kind: datasets::DatasetKind,
+    audit_key: Option<String>,
+    duplicate_policy: DuplicatePolicy,
+    rate_limit: Option<RateLimit>,
+    shared_rate_limit: Option<SharedRateLimit>,
+    encodings: encoding::Encodings,
+    writer: Option<writer::Writer>,
+    provisioning: Option<provision::Provisioning>,
+    snapshot_detection: Option<category::SnapshotDetection>,
+    #[cfg(feature = "mirror")]
+    mirror: Option<Arc<mirror::Mirror>>,
+    #[cfg(feature = "privacy")]
+    noise: Option<privacy::LaplaceNoise>,
+    #[cfg(feature = "tracing")]
+    log_sampling: trace::LogSampling,
+    #[cfg(feature = "chrono")]
+    server_clock: Option<clock::ServerClock>,
+    #[cfg(feature = "chrono")]
+    alignment: Option<Resolution>,
+    #[cfg(feature = "chrono")]
+    timestamp_format: TimestampFormat,
+    #[cfg(feature = "chrono")]
+    time_zone: DatasetTimeZone,
+    #[cfg(feature = "chrono")]
+    instruments: instruments::Instruments,
 }
 
+#[cfg(feature = "blocking")]
 impl SyncClient {
     /// Create a new synchronous client to call the Nextcloud Analytics API.
     ///
     /// - `nextcloud_url` is the base URL of the Nextcloud instance.
     /// - `collection` is the collection index, as presented by Nextcloud Analytics' interface
-    /// (number in the URL).
+    ///   (number in the URL).
     /// - `user` is the Nextcloud user's name.
     /// - `passwd` is an app password associaetd to the Nextcloud user's account.
     pub fn new<S: Into<String>>(nextcloud_url: &str, collection: u32, user: S, passwd: S) -> Self {
-        let mut url = nextcloud_url.to_string();
+        Self::builder(nextcloud_url, collection, user, passwd)
+            .build()
+            .unwrap()
+    }
 
-        // Add trailing slash if necessary.
-        if !url.ends_with("/") {
-            url += "/";
-        }
+    /// Create a builder for a synchronous client, to tune its configuration beyond the
+    /// defaults of [`SyncClient::new`]. The arguments are the same as for [`SyncClient::new`].
+    pub fn builder<S: Into<String>>(
+        nextcloud_url: &str,
+        collection: u32,
+        user: S,
+        passwd: S,
+    ) -> SyncClientBuilder {
+        SyncClientBuilder::new(nextcloud_url, collection, user.into(), passwd.into())
+    }
+
+    /// Use the given template to build the first dimension of every data point sent by this
+    /// client, from the key passed to the send methods. See [`Template`] for details.
+    pub fn with_key_template(mut self, template: Template) -> Self {
+        self.key_template = Some(template);
+        self
+    }
 
-        url += &URL_PREFIX.replace("{COLLECTION_ID}", &collection.to_string());
+    /// Silently skip data points whose key doesn't pass the given filter.
+    ///
+    /// The filter applies to the first dimension as passed to the send methods, before any key
+    /// template is applied. Skipped data points are reported as successful, with a
+    /// [`Outcome::Filtered`] receipt.
+    pub fn with_key_filter(mut self, filter: KeyFilter) -> Self {
+        self.key_filter = Some(filter);
+        self
+    }
 
-        let mut headers = http::header::HeaderMap::new();
+    /// Serialize the values of the data points in the given format. Defaults to
+    /// [`ValueFormat::String`], which is what the Analytics API 1.0 expects.
+    pub fn with_value_format(mut self, format: ValueFormat) -> Self {
+        self.value_format = format;
+        self
+    }
 
-        headers.insert(
-            http::header::CONTENT_TYPE,
-            http::header::HeaderValue::from_static("application/json"),
+    /// Treat responses to sends that don't say whether the data was stored, e.g. an empty JSON
+    /// object from a proxy, as [invalid](ErrorClass::InvalidResponse), rather than successful.
+    ///
+    /// Disabled by default, since some Analytics versions answer without a success flag when
+    /// everything went well. Responses that aren't JSON, e.g. the login or maintenance pages,
+    /// are errors either way.
+    pub fn with_strict_responses(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    /// Prepare and serialize the data points as usual, but don't send them: the send methods
+    /// return an [`Outcome::DryRun`] receipt with the payload that would have been sent, e.g.
+    /// to test a pipeline in CI without polluting the production collection.
+    ///
+    /// The key filter, key template, enrichments and duplicate policy apply, and sends fail as
+    /// usual if a data point is invalid. Nothing is deleted in upsert mode, and the minimum
+    /// intervals and failure callbacks ignore dry runs. With the `tracing` feature, payloads are
+    /// logged at the info level.
+    pub fn with_dry_run(mut self, enabled: bool) -> Self {
+        self.dry_run = enabled;
+        self
+    }
+
+    /// The receipt of a payload not sent in dry-run mode.
+    pub(crate) fn dry_run_receipt(&self, payload: String, collection: u32) -> Receipt {
+        #[cfg(feature = "tracing")]
+        tracing::info!(
+            collection,
+            payload = %self.redactions.apply(&payload),
+            "dry run: not sending"
         );
+        #[cfg(not(feature = "tracing"))]
+        let _ = collection;
+        Receipt::new(&payload, Outcome::DryRun(payload.clone()))
+    }
 
-        let client = http::blocking::Client::builder()
-            .default_headers(headers)
-            .build()
-            .unwrap();
+    /// Call `enrichment` on every data point just before it's sent, after the key template has
+    /// been applied, e.g. to append a firmware version to `dimension2` or to scale values
+    /// according to runtime configuration.
+    ///
+    /// Enrichments are applied in the order they were added.
+    pub fn with_enrichment<F>(mut self, enrichment: F) -> Self
+    where
+        F: Fn(&mut DataPoint) + Send + Sync + 'static,
+    {
+        self.enrichments.push(Arc::new(enrichment));
+        self
+    }
+
+    /// Notify operators with the given notifier when sends keep failing.
+    pub fn with_failure_notifier(mut self, notifier: FailureNotifier) -> Self {
+        self.failure_notifier = Some(notifier);
+        self
+    }
+
+    /// Use the server's time instead of the local time for [`SyncClient::send_timeline_now_data`].
+    ///
+    /// The server time is estimated from the `Date` header of the last API response plus the
+    /// time elapsed since then, which helps devices with a wrong clock. Until a first response
+    /// has been received, the local time is used.
+    ///
+    /// Requires the `chrono` feature.
+    #[cfg(feature = "chrono")]
+    pub fn with_server_time(mut self, enabled: bool) -> Self {
+        self.server_clock = if enabled {
+            Some(clock::ServerClock::default())
+        } else {
+            None
+        };
+        self
+    }
+
+    /// Call `callback` with the class of the error and the data point that couldn't be sent,
+    /// every time a send fails. For a failed batch, it's called once per data point of the batch.
+    ///
+    /// The callback runs on the sending thread once the request is over, so it should be quick.
+    pub fn on_error<F>(mut self, callback: F) -> Self
+    where
+        F: Fn(ErrorClass, &DataPoint) + Send + Sync + 'static,
+    {
+        self.error_callback = Some(Arc::new(callback));
+        self
+    }
+
+    /// Call `callback` with the number of preceding consecutive failures, when a send succeeds
+    /// after one or more failures.
+    pub fn on_recovered<F>(mut self, callback: F) -> Self
+    where
+        F: Fn(u32) + Send + Sync + 'static,
+    {
+        self.recovered_callback = Some(Arc::new(callback));
+        self
+    }
+
+    /// Add random noise to every value before sending it. See [`privacy::LaplaceNoise`].
+    ///
+    /// Requires the `privacy` feature.
+    #[cfg(feature = "privacy")]
+    pub fn with_laplace_noise(mut self, noise: privacy::LaplaceNoise) -> Self {
+        self.noise = Some(noise);
+        self
+    }
+
+    /// Store every row successfully sent to the server in `mirror`, e.g. to query them locally.
+    /// See [`mirror::Mirror`].
+    ///
+    /// Mirroring is best effort: failing to store the rows doesn't fail the send.
+    ///
+    /// Requires the `mirror` feature.
+    #[cfg(feature = "mirror")]
+    pub fn with_mirror(mut self, mirror: Arc<mirror::Mirror>) -> Self {
+        self.mirror = Some(mirror);
+        self
+    }
+
+    /// Starts staging data points: nothing is sent until [`Staging::commit`] is called.
+    pub fn begin(&self) -> Staging<'_> {
+        Staging::new(self)
+    }
+
+    /// Truncate the timestamps of timeline sends to the given resolution, which should be the
+    /// grouping configured for the report, so that series group as expected; see
+    /// [`SyncClient::align_to_report`] to read it from the report. Days are aligned in the time
+    /// zone of the dataset, see [`SyncClient::with_time_zone`].
+    ///
+    /// Requires the `chrono` feature.
+    #[cfg(feature = "chrono")]
+    pub fn align_to(mut self, resolution: Resolution) -> Self {
+        self.alignment = Some(resolution);
+        self
+    }
+
+    /// Write every request and its response to a JSON file in `dir`, with secrets stripped, to
+    /// attach exact reproductions to bug reports.
+    ///
+    /// Recording is best effort: failing to write a fixture doesn't fail the API call.
+    pub fn record_fixtures<P: Into<PathBuf>>(mut self, dir: P) -> Self {
+        self.fixture_recorder = Some(fixtures::FixtureRecorder::new(dir.into()));
+        self
+    }
+
+    /// Send the requests of this client with the given transport instead of its `reqwest`
+    /// client, e.g. a [`testing::MockTransport`] in unit tests. The HTTP settings of the
+    /// builder, such as timeouts and proxies, then only apply if the transport applies them.
+    pub fn with_transport<T: Transport + 'static>(mut self, transport: T) -> Self {
+        self.transport = Some(Arc::new(transport));
+        self
+    }
+
+    /// Replace every match of `pattern` with `<redacted>` in the error messages returned by this
+    /// client, the failure notifications it posts and the fixtures it records, e.g. to hide
+    /// serial numbers embedded in keys.
+    ///
+    /// Can be called several times to register several patterns.
+    ///
+    /// Requires the `regex` feature.
+    #[cfg(feature = "regex")]
+    pub fn with_redaction(mut self, pattern: regex::Regex) -> Self {
+        self.redactions.add(pattern);
+        self
+    }
+
+    /// The HTTP client of this client, e.g. to share its connection pool with clients of other
+    /// collections or users of the same instance, see [`SyncClient::with_http_client`].
+    pub fn http_client(&self) -> &http::blocking::Client {
+        &self.client
+    }
 
-        Self {
-            client,
-            url,
-            user: user.into(),
-            passwd: passwd.into(),
+    /// Send the requests with the given HTTP client, sharing its connection pool, instead of the
+    /// one created by the builder, so that clients of several collections don't each pay their
+    /// own TLS handshakes:
+    ///
+    /// ```no_run
+    /// # use nextcloud_analytics_rs::SyncClient;
+    /// let temperatures = SyncClient::new("https://example.com/nextcloud", 3, "myself", "hunter2");
+    /// let humidity = SyncClient::new("https://example.com/nextcloud", 4, "sensors", "hunter3")
+    ///     .with_http_client(temperatures.http_client().clone());
+    /// ```
+    ///
+    /// The connection settings of the builder, e.g. the timeouts, the proxy and TLS, are then
+    /// those of the given client, without [`SyncClientBuilder::last_good_address`]. For another collection with the same credentials, a
+    /// [`SyncClient::collection`] handle is simpler.
+    pub fn with_http_client(mut self, client: http::blocking::Client) -> Self {
+        self.client = client;
+        self.last_good = None;
+        self
+    }
+
+    /// Sends a lightweight `HEAD` request to the server's `status.php`, e.g. from a timer to keep
+    /// the connection warm during long idle gaps, along with [`SyncClientBuilder::keep_alive`].
+    pub fn ping(&self) -> Result<(), AnalyticsError> {
+        let req = self.client.head(&(self.base_url.clone() + "status.php"));
+        self.send_request(req)?;
+        Ok(())
+    }
+
+    /// Describes the effective configuration of this client, without any secrets, for inclusion
+    /// in logs or support bundles.
+    pub fn describe(&self) -> ClientDescription {
+        ClientDescription {
+            base_url: self.base_url.clone(),
+            collection: self.collection,
+            endpoint: self.base_url.clone() + &self.api_version.add_path(self.collection),
+            api_version: self.api_version.name(),
+            user: self.credentials.user(),
+            auth: if self.credentials.uses_bearer_token() {
+                "bearer"
+            } else {
+                "basic"
+            },
+            profile: self.profile,
+            timeout: self.timeout,
+            connect_timeout: self.connect_timeout,
+            keep_alive: self.keep_alive,
+            max_attempts: self.retry.as_ref().map_or(1, RetryPolicy::max_attempts),
+            retry: self.retry.clone(),
+            key_template: self
+                .key_template
+                .as_ref()
+                .map(|template| template.pattern().to_string()),
+            batch_size: self.batch_size,
+            rate_limit: self
+                .rate_limit
+                .as_ref()
+                .map(|limit| (limit.rate(), limit.burst())),
+            features: describe::features(),
         }
     }
 
@@ -79,90 +711,574 @@ impl SyncClient {
     /// last dimension must be a numerical value.
     ///
     /// For timeline data, `dimension2` must be the date in the RFC2822 format.
+    ///
+    /// If a key template has been set with [`SyncClient::with_key_template`], `dimension1` is
+    /// expanded with it first.
+    ///
+    /// Returns a [`Receipt`] describing what happened to the data point.
     pub fn send_data<S: Into<String>, F: Into<f64>>(
         &self,
         dimension1: S,
         dimension2: S,
         dimension3: F,
-    ) -> Result<(), Box<dyn Error>> {
-        let data = format!(
-            r#"{{
-    "dimension1": {:?},
-    "dimension2": {:?},
-    "dimension3": "{}"
-}}"#,
-            dimension1.into(),
-            dimension2.into(),
-            dimension3.into()
-        );
+    ) -> Result<Receipt, AnalyticsError> {
+        self.send_point(DataPoint::new(dimension1, dimension2, dimension3))
+    }
+
+    /// Sends a single data point to the API, like [`SyncClient::send_data`].
+    pub fn send_point(&self, mut point: DataPoint) -> Result<Receipt, AnalyticsError> {
+        self.default_dimensions.fill(&mut point, self.collection);
+        let mut claims = self.dedupe.as_ref().map(dedupe::Dedupe::claims);
+        if let Some(claims) = &mut claims {
+            if !claims.claim(&point, self.collection) {
+                let payload = point.to_json_with(self.value_format);
+                return Ok(Receipt::new(&payload, Outcome::Duplicate));
+            }
+        }
+        let derived = self.derivations.observe(&point);
+        let result = self.send_input(point);
+        if let (Some(claims), true) = (claims, result.as_ref().is_ok_and(Receipt::was_sent)) {
+            claims.keep();
+        }
+        if result.is_ok() {
+            for point in derived {
+                // Failures are reported through the callbacks, like any other send.
+                let _ = self.send_input(point);
+            }
+        }
+        result
+    }
+
+    /// Sends a data point through the key filter and the minimum intervals.
+    fn send_input(&self, point: DataPoint) -> Result<Receipt, AnalyticsError> {
+        if !self.allows_key(&point.dimension1) {
+            let payload = point.to_json_with(self.value_format);
+            return Ok(Receipt::new(&payload, Outcome::Filtered));
+        }
+
+        let point = match self.coalesce(point) {
+            Ok(point) => point,
+            Err(receipt) => return Ok(*receipt),
+        };
+
+        self.send_prepared(point)
+    }
+
+    /// Sends a data point that went through the key filter and the minimum intervals.
+    pub(crate) fn send_prepared(&self, point: DataPoint) -> Result<Receipt, AnalyticsError> {
+        let point = self.prepare_point(point)?;
+        self.post_point(point, self.collection)
+    }
+
+    /// Posts a data point that has already been through [`SyncClient::prepare_point`] to the
+    /// given collection.
+    pub(crate) fn post_point(
+        &self,
+        point: DataPoint,
+        collection: u32,
+    ) -> Result<Receipt, AnalyticsError> {
+        self.check_writable()?;
+        let payload = self.point_payload(&point);
+        if self.dry_run {
+            return Ok(self.dry_run_receipt(payload, collection));
+        }
+        self.send_or_queue(std::slice::from_ref(&point), collection, &payload, || {
+            self.deliver_point(&point, &payload, collection)
+        })
+    }
+
+    /// Sends a prepared data point, with its payload, to the given collection.
+    pub(crate) fn deliver_point(
+        &self,
+        point: &DataPoint,
+        payload: &str,
+        collection: u32,
+    ) -> Result<Receipt, AnalyticsError> {
+        let trace = trace::SendTrace::start(collection, 1);
+        let (collection, result) = self.send_provisioned(collection, |collection| {
+            self.delete_before_insert(std::slice::from_ref(point), collection)
+                .and_then(|()| self.post_data(payload.to_string(), collection))
+                .map(|report| Receipt::new(payload, Outcome::Sent(report)))
+                .map_err(|err| self.redactions.error(err))
+        });
+        trace.finish(&result, self);
+        self.track_delivery(std::slice::from_ref(point), collection, &result);
+        result
+    }
+
+    /// Whether data points with the given key pass the key filter, if any.
+    pub(crate) fn allows_key(&self, key: &str) -> bool {
+        match &self.key_filter {
+            Some(filter) => filter.allows(key),
+            None => true,
+        }
+    }
+
+    /// Applies the key template, enrichments, encodings, writer identity and noise to a data point about to be sent.
+    pub(crate) fn prepare_point(&self, mut point: DataPoint) -> Result<DataPoint, AnalyticsError> {
+        if let Some(template) = &self.key_template {
+            point.dimension1 = template
+                .expand(&point.dimension1)
+                .map_err(|err| self.redactions.error(err.into()))?;
+        }
+
+        for enrichment in &self.enrichments {
+            enrichment(&mut point);
+        }
+
+        self.encodings.encode(&mut point);
+
+        if let Some(writer) = &self.writer {
+            writer.apply(&mut point);
+        }
+
+        #[cfg(feature = "privacy")]
+        if let Some(noise) = &self.noise {
+            point.dimension3 = noise.apply(point.dimension3);
+        }
 
+        self.validation.apply(&mut point)?;
+        Ok(point)
+    }
+
+    /// Sends a JSON payload with a single row to the adddata endpoint of a collection,
+    /// returning the server's report.
+    fn post_data(&self, data: String, collection: u32) -> Result<SendReport, AnalyticsError> {
+        let url = self.base_url.clone() + &self.api_version.add_path(collection);
         let req = self
             .client
-            .post(&self.url)
-            .basic_auth(self.user.clone(), Some(self.passwd.clone()));
+            .post(&url)
+            .header(http::header::CONTENT_TYPE, self.payload_content_type())
+            .body(data);
+        let body = self.send_request(req)?;
+        parse_add_response(&body, 1, self.strict)
+    }
 
-        let resp = req.body(data).send()?;
+    /// Adds the client's credentials to a request.
+    pub(crate) fn authorize(
+        &self,
+        req: http::blocking::RequestBuilder,
+    ) -> http::blocking::RequestBuilder {
+        self.credentials.authorize(req)
+    }
 
-        if resp.status() != http::StatusCode::OK {
-            let status = resp.status();
-            let message = resp.text()?;
-            return Err(Box::new(ApiError(format!(
-                "unexpected status code: {:?}\n{}",
-                status, message
-            ))));
+    /// Sends a request with the client's credentials, returning the response whatever its
+    /// status.
+    ///
+    /// Transient failures are retried according to the client's retry policy, if any, unless the
+    /// request's body is streamed.
+    pub(crate) fn execute(
+        &self,
+        req: http::blocking::RequestBuilder,
+    ) -> Result<transport::Response, AnalyticsError> {
+        let mut req = self.authorize(req).build()?;
+
+        let policy = match &self.retry {
+            Some(policy) => policy,
+            None => return self.execute_once(req),
+        };
+
+        let mut attempt = 1;
+        loop {
+            let retry = if attempt < policy.max_attempts() {
+                req.try_clone()
+            } else {
+                None
+            };
+
+            let result = self.execute_once(req);
+            req = match (retry, &result) {
+                (Some(next), Err(AnalyticsError::Http(_))) => next,
+                (Some(next), Ok(resp)) if policy.retries_status(resp.status) => next,
+                _ => return result,
+            };
+
+            std::thread::sleep(policy.delay(attempt));
+            attempt += 1;
         }
+    }
 
-        let json_resp = json::parse(&resp.text()?)?;
-        if !json_resp["success"]
-            .as_bool()
-            .expect("There should be a success field in the API response")
-        {
-            return Err(Box::new(ApiError(format!(
-                "unexpected API response: {}",
-                json_resp["error"]["message"]
-                    .as_str()
-                    .expect("There should be an error.message in the API response")
-            ))));
+    fn execute_once(
+        &self,
+        mut req: http::blocking::Request,
+    ) -> Result<transport::Response, AnalyticsError> {
+        if let Some(timeout) = timeout::call_timeout() {
+            *req.timeout_mut() = Some(timeout);
+        }
+        if let Some(limit) = &self.rate_limit {
+            limit.acquire();
+        }
+        if let Some(limit) = &self.shared_rate_limit {
+            limit.acquire()?;
         }
 
-        Ok(())
+        let recorded = self
+            .fixture_recorder
+            .as_ref()
+            .map(|recorder| (recorder, recorder.capture_request(&req, &self.redactions)));
+
+        let writes = !req.method().is_safe();
+        let trace = trace::RequestTrace::start(&req, &self.redactions);
+        let resp = self.dispatch(req);
+        trace.finish(&resp);
+        let resp = resp?;
+        if writes && resp.status.is_success() {
+            self.invalidate_cache();
+        }
+
+        #[cfg(feature = "chrono")]
+        if let Some(clock) = &self.server_clock {
+            clock.observe(&resp.headers);
+        }
+
+        if let Some((recorder, request)) = recorded {
+            recorder.record(
+                request,
+                resp.status,
+                &resp.headers,
+                &self.redactions.apply(&resp.body),
+            );
+        }
+
+        Ok(resp)
+    }
+
+    /// Sends a request through the client's interceptors and transport.
+    pub(crate) fn dispatch(
+        &self,
+        req: http::blocking::Request,
+    ) -> Result<transport::Response, AnalyticsError> {
+        let transport: &dyn Transport = match &self.transport {
+            Some(transport) => transport.as_ref(),
+            None => match &self.last_good {
+                Some(last_good) => last_good.as_ref(),
+                None => &self.client,
+            },
+        };
+        let mut request = transport::Request::from_reqwest(req);
+        if self.interceptors.is_empty() {
+            return transport.send(request);
+        }
+
+        self.intercept_request(&mut request)?;
+        let sent = request.clone();
+        let resp = transport.send(request)?;
+        self.intercept_response(&sent, &resp);
+        Ok(resp)
+    }
+
+    /// Sends a request with the client's credentials, checking that the response has a success
+    /// status, and returns the response's body.
+    pub(crate) fn send_request(
+        &self,
+        req: http::blocking::RequestBuilder,
+    ) -> Result<String, AnalyticsError> {
+        let resp = self.execute(req)?;
+
+        if !resp.status.is_success() {
+            return Err(self.redactions.error(AnalyticsError::from_response(
+                resp.status,
+                &resp.headers,
+                &resp.body,
+            )));
+        }
+
+        Ok(resp.body)
+    }
+
+    /// Calls one of the Analytics app's endpoints, at `path` relative to the Nextcloud base URL,
+    /// and parses the JSON response.
+    pub(crate) fn request_json(
+        &self,
+        method: http::Method,
+        path: &str,
+        body: Option<serde_json::Value>,
+    ) -> Result<serde_json::Value, AnalyticsError> {
+        let url = self.base_url.clone() + path;
+        let read = method == http::Method::GET;
+        let mut req = self
+            .client
+            .request(method, &url)
+            .header("OCS-APIRequest", "true")
+            .header(http::header::ACCEPT, "application/json");
+
+        if let (Some(cache), true) = (&self.response_cache, read) {
+            return self.get_json_cached(cache.as_ref(), req, &url);
+        }
+        if let Some(body) = body {
+            req = req.body(body.to_string());
+        }
+
+        let body = self.send_request(req)?;
+        parse_json(&body)
+    }
+
+    /// Keeps track of consecutive failures, notifying operators when there are too many, and
+    /// mirrors the points sent to `collection`.
+    pub(crate) fn track_delivery(
+        &self,
+        points: &[DataPoint],
+        collection: u32,
+        result: &Result<Receipt, AnalyticsError>,
+    ) {
+        let err = match result {
+            Ok(_) => {
+                #[cfg(feature = "mirror")]
+                if let Some(mirror) = &self.mirror {
+                    let _ = mirror.record(points, collection);
+                }
+
+                let now = SystemTime::now();
+                let mut last_sends = self.last_sends.lock().unwrap();
+                for point in points {
+                    last_sends.insert(point.dimension1.clone(), now);
+                }
+                drop(last_sends);
+
+                let failures = self.consecutive_failures.swap(0, Ordering::Relaxed);
+                if failures > 0 {
+                    if let Some(callback) = &self.recovered_callback {
+                        callback(failures);
+                    }
+                }
+                return;
+            }
+            Err(err) => err,
+        };
+
+        if let Some(callback) = &self.error_callback {
+            for point in points {
+                callback(err.class(), point);
+            }
+        }
+
+        let failures = self.consecutive_failures.fetch_add(1, Ordering::Relaxed) + 1;
+
+        if let Some(notifier) = &self.failure_notifier {
+            if failures == notifier.threshold() {
+                let message = format!(
+                    "nextcloud_analytics_rs: {} consecutive failures sending data to collection {}; last error: {}",
+                    failures, collection, err
+                );
+                // Notifying is best effort: the original error is what matters to the caller.
+                let _ = notifier.notify(self, &message);
+            }
+        }
     }
 
     /// Sends some timeline data to the API: the `key` is the index of this piece of data,
-    /// associated to the given `value` at the given `time`. for the given `time`.
+    /// associated to the given `value` at the given `time`.
+    ///
+    /// The time is truncated to the resolution set with [`SyncClient::align_to`], if any, and
+    /// formatted in RFC 2822 unless another format is set with
+    /// [`SyncClient::with_timestamp_format`]. Times outside of years 0 to 9999 are refused with a
+    /// [`DateRangeError`] in RFC 2822 and RFC 3339.
+    ///
+    /// Requires the `chrono` feature.
+    #[cfg(feature = "chrono")]
     pub fn send_timeline_data<S: Into<String>, F: Into<f64>>(
         &self,
         key: S,
         time: DateTime<Utc>,
         value: F,
-    ) -> Result<(), Box<dyn Error>> {
-        self.send_data(key.into(), time.to_rfc2822(), value.into())
+    ) -> Result<Receipt, AnalyticsError> {
+        let time = self.format_time(time)?;
+        self.send_data(key.into(), time, value.into())
     }
 
     /// Sends some timeline data to the API: the `key` is the index of this piece of data,
     /// associated to the given `value` at the current UTC time.
+    ///
+    /// The current time is the server's if [`SyncClient::with_server_time`] has been enabled.
+    ///
+    /// Requires the `chrono` feature.
+    #[cfg(feature = "chrono")]
     pub fn send_timeline_now_data<S: Into<String>, F: Into<f64>>(
         &self,
         key: S,
         value: F,
-    ) -> Result<(), Box<dyn Error>> {
-        self.send_timeline_data(key, Utc::now(), value)
+    ) -> Result<Receipt, AnalyticsError> {
+        self.send_timeline_data(key, self.now(), value)
+    }
+
+    /// Sends a count of 1 for `key` at the current time, to record a discrete event, e.g. a
+    /// button press or a job run.
+    ///
+    /// To send at most one count per interval, with the events of the interval added up, use
+    /// [`SyncClient::with_min_interval`] with [`CoalescePolicy::Sum`] for the key.
+    ///
+    /// Requires the `chrono` feature.
+    #[cfg(feature = "chrono")]
+    pub fn send_event<S: Into<String>>(&self, key: S) -> Result<Receipt, AnalyticsError> {
+        self.send_event_n(key, 1)
+    }
+
+    /// Sends a count of `count` events for `key` at the current time, like
+    /// [`SyncClient::send_event`].
+    ///
+    /// Requires the `chrono` feature.
+    #[cfg(feature = "chrono")]
+    pub fn send_event_n<S: Into<String>>(
+        &self,
+        key: S,
+        count: u32,
+    ) -> Result<Receipt, AnalyticsError> {
+        self.send_timeline_now_data(key, count)
+    }
+
+    /// The current time, from the server's clock if [`SyncClient::with_server_time`] has been
+    /// enabled.
+    #[cfg(feature = "chrono")]
+    pub(crate) fn now(&self) -> DateTime<Utc> {
+        match &self.server_clock {
+            Some(clock) => clock.now(),
+            None => Utc::now(),
+        }
+    }
+
+    /// Truncates `time` to the resolution set with [`SyncClient::align_to`], if any.
+    #[cfg(feature = "chrono")]
+    pub(crate) fn aligned(&self, time: DateTime<Utc>) -> DateTime<Utc> {
+        match self.alignment {
+            Some(resolution) => resolution.align_in(time, self.time_zone),
+            None => time,
+        }
     }
 }
 
-/// A simple error wrapper for API errors.
-pub struct ApiError(String);
+/// Parses a JSON response, with a helpful error if Nextcloud answered with something else.
+pub(crate) fn parse_json<T: serde::de::DeserializeOwned>(body: &str) -> Result<T, AnalyticsError> {
+    serde_json::from_str(body).map_err(|err| AnalyticsError::invalid_body(body, err))
+}
+
+/// Integers come either as JSON numbers or strings, depending on the database backend.
+pub(crate) fn as_u64(value: &serde_json::Value) -> Option<u64> {
+    value
+        .as_u64()
+        .or_else(|| value.as_str().and_then(|s| s.parse().ok()))
+}
+
+/// Parses the response of the endpoints adding data. In `strict` mode, the response must say
+/// that the data was stored, with a success flag or a count of rows.
+fn parse_add_response(body: &str, rows: usize, strict: bool) -> Result<SendReport, AnalyticsError> {
+    let AddResponse {
+        success,
+        message,
+        error,
+        insert,
+        update,
+        validate,
+    } = parse_json(body)?;
 
-impl fmt::Debug for ApiError {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}", self.0)
+    if strict && success.is_none() && error.is_none() && insert.is_none() && update.is_none() {
+        return Err(AnalyticsError::Parse(format!(
+            "the response doesn't say whether the data was stored: {}",
+            body
+        )));
     }
+
+    // Depending on the version, `error` is either the description of a failure, or the number
+    // of rows that couldn't be stored.
+    let (failure, errors) = match error {
+        None | Some(serde_json::Value::Null) => (None, None),
+        Some(error) => match as_u64(&error) {
+            Some(count) => (None, Some(count)),
+            None => (Some(error), None),
+        },
+    };
+
+    // Some versions omit the success flag when everything went well.
+    if !success.unwrap_or_else(|| failure.is_none()) {
+        let reason = failure
+            .as_ref()
+            .and_then(|error| error["message"].as_str())
+            .or(message.as_deref());
+        return Err(AnalyticsError::api(format!(
+            "unexpected API response: {}",
+            reason.unwrap_or("unknown error")
+        )));
+    }
+
+    Ok(SendReport {
+        rows,
+        message,
+        inserted: insert.as_ref().and_then(as_u64),
+        updated: update.as_ref().and_then(as_u64),
+        errors,
+        validation: match validate {
+            None | Some(serde_json::Value::Null) => None,
+            Some(serde_json::Value::String(text)) if text.is_empty() => None,
+            Some(serde_json::Value::String(text)) => Some(text),
+            Some(other) => Some(other.to_string()),
+        },
+    })
+}
+
+/// Response of the endpoints adding data.
+#[derive(Deserialize)]
+struct AddResponse {
+    #[serde(default, deserialize_with = "deserialize_flag")]
+    success: Option<bool>,
+    message: Option<String>,
+    error: Option<serde_json::Value>,
+    insert: Option<serde_json::Value>,
+    update: Option<serde_json::Value>,
+    validate: Option<serde_json::Value>,
 }
 
-impl fmt::Display for ApiError {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}", self.0)
+/// Error reported by the Analytics API in a response.
+#[cfg(feature = "blocking")]
+#[derive(Deserialize)]
+pub(crate) struct ResponseError {
+    pub message: Option<String>,
+}
+
+/// Deserializes a boolean flag, which the API 1.0 of some Analytics versions sends as a string,
+/// e.g. `"true"`, or as a number.
+pub(crate) fn deserialize_flag<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<bool>, D::Error> {
+    use serde::de::Error;
+
+    match Option::<serde_json::Value>::deserialize(deserializer)? {
+        None | Some(serde_json::Value::Null) => Ok(None),
+        Some(serde_json::Value::Bool(flag)) => Ok(Some(flag)),
+        Some(serde_json::Value::String(flag)) => match flag.trim() {
+            "true" | "1" => Ok(Some(true)),
+            "false" | "0" | "" => Ok(Some(false)),
+            other => Err(D::Error::custom(format!(
+                "invalid success flag: {:?}",
+                other
+            ))),
+        },
+        Some(serde_json::Value::Number(flag)) => Ok(Some(flag.as_f64() != Some(0.0))),
+        Some(other) => Err(D::Error::custom(format!("invalid success flag: {}", other))),
     }
 }
 
-impl Error for ApiError {}
+// Static checks of the thread-safety guarantees documented on the public types.
+#[cfg(feature = "blocking")]
+const _: () = {
+    fn assert_send_sync<T: Send + Sync>() {}
+
+    #[allow(dead_code)]
+    fn assert_public_types() {
+        assert_send_sync::<SyncClient>();
+        assert_send_sync::<SyncClientBuilder>();
+        assert_send_sync::<DataPoint>();
+        assert_send_sync::<Template>();
+        assert_send_sync::<FailureNotifier>();
+        assert_send_sync::<ClientDescription>();
+        assert_send_sync::<ClientState>();
+        assert_send_sync::<AnalyticsError>();
+        assert_send_sync::<Receipt>();
+        assert_send_sync::<BufferedClient>();
+        assert_send_sync::<SenderHandle>();
+        assert_send_sync::<BackgroundSender>();
+        assert_send_sync::<MultiClient>();
+        assert_send_sync::<BatchReport>();
+        assert_send_sync::<ColumnMapping>();
+        #[cfg(feature = "async")]
+        assert_send_sync::<AsyncClient>();
+    }
+};
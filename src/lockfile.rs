@@ -0,0 +1,27 @@
+use std::fs::OpenOptions;
+use std::io;
+use std::path::Path;
+
+use fs2::FileExt;
+
+/// Runs `f` while holding an exclusive advisory lock on the lock file of `path`, next to it
+/// with the `.lock` extension, so that the processes of a host sharing the file access it one
+/// at a time, however long `f` takes.
+///
+/// The lock is released by the OS when the file is closed, including when the process
+/// crashes, so it's never left behind. The lock file itself is kept, as removing it would let
+/// another process lock a new file while a third one still waits on the old one.
+pub(crate) fn with_lock<T, E: From<io::Error>>(
+    path: &Path,
+    f: impl FnOnce() -> Result<T, E>,
+) -> Result<T, E> {
+    let lock = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(path.with_extension("lock"))?;
+    lock.lock_exclusive()?;
+    let result = f();
+    let _ = lock.unlock();
+    result
+}
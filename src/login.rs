@@ -0,0 +1,212 @@
+//! Obtaining app passwords, so that users' main passwords never end up in configuration files.
+//!
+//! - [`LoginFlow`] implements Nextcloud's Login Flow v2: the user logs in and grants access in
+//!   their browser, and the program receives a new app password.
+//! - [`LoginFlowProvider`] runs a login flow to rotate the credentials of an instance of a
+//!   [`MultiClient`](crate::MultiClient).
+//! - [`app_password`] exchanges a user's name and password for an app password, e.g. once at
+//!   install time.
+
+use std::thread;
+use std::time::{Duration, Instant};
+
+use reqwest as http;
+use serde::Deserialize;
+
+use crate::builder::DEFAULT_USER_AGENT;
+use crate::{parse_json, AnalyticsError, CredentialProvider, SyncClient};
+
+/// Credentials obtained at the end of a [`LoginFlow`].
+///
+/// With the `zeroize` feature, the app password is wiped from memory when they are dropped.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+pub struct AppCredentials {
+    /// Base URL of the Nextcloud instance, as reported by the server.
+    pub server: String,
+    /// Name of the user to authenticate as.
+    #[serde(rename = "loginName")]
+    pub login_name: String,
+    /// App password to authenticate with.
+    #[serde(rename = "appPassword")]
+    pub app_password: String,
+}
+
+/// A Login Flow v2 in progress, see [`LoginFlow::start`].
+#[derive(Debug)]
+pub struct LoginFlow {
+    client: http::blocking::Client,
+    login_url: String,
+    poll_endpoint: String,
+    pub(crate) poll_token: String,
+}
+
+#[derive(Deserialize)]
+struct FlowStart {
+    poll: FlowPoll,
+    login: String,
+}
+
+#[derive(Deserialize)]
+struct FlowPoll {
+    token: String,
+    endpoint: String,
+}
+
+impl LoginFlow {
+    /// Starts a login flow on the Nextcloud instance at `nextcloud_url`. The user then has to
+    /// open [`LoginFlow::login_url`] in a browser while the program waits for them with
+    /// [`LoginFlow::wait`].
+    pub fn start(nextcloud_url: &str) -> Result<Self, AnalyticsError> {
+        let client = http::blocking::Client::builder()
+            .user_agent(DEFAULT_USER_AGENT)
+            .build()?;
+
+        let url = nextcloud_url.trim_end_matches('/').to_string() + "/index.php/login/v2";
+        let resp = client.post(&url).send()?;
+        let status = resp.status();
+        let body = resp.text()?;
+        if !status.is_success() {
+            return Err(AnalyticsError::from_status(status, &body));
+        }
+
+        let start: FlowStart = parse_json(&body)?;
+        Ok(Self {
+            client,
+            login_url: start.login,
+            poll_endpoint: start.poll.endpoint,
+            poll_token: start.poll.token,
+        })
+    }
+
+    /// URL the user has to open to log in and grant access.
+    pub fn login_url(&self) -> &str {
+        &self.login_url
+    }
+
+    /// Checks once whether the user granted access, returning the new credentials if so.
+    pub fn poll(&self) -> Result<Option<AppCredentials>, AnalyticsError> {
+        let resp = self
+            .client
+            .post(&self.poll_endpoint)
+            .form(&[("token", self.poll_token.as_str())])
+            .send()?;
+        let status = resp.status();
+        let body = resp.text()?;
+
+        // The endpoint answers 404 until access is granted.
+        if status == http::StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        if !status.is_success() {
+            return Err(AnalyticsError::from_status(status, &body));
+        }
+        Ok(Some(parse_json(&body)?))
+    }
+
+    /// Polls every `interval` until the user granted access, or `timeout` elapsed. Nextcloud
+    /// expires login flows after 20 minutes.
+    pub fn wait(
+        &self,
+        interval: Duration,
+        timeout: Duration,
+    ) -> Result<AppCredentials, AnalyticsError> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            if let Some(credentials) = self.poll()? {
+                return Ok(credentials);
+            }
+            if Instant::now() + interval > deadline {
+                return Err(AnalyticsError::local(
+                    "timed out waiting for the login flow to be completed",
+                ));
+            }
+            thread::sleep(interval);
+        }
+    }
+}
+
+/// New credentials from a [`LoginFlow`] on the instance of a client, to rotate its credentials,
+/// see [`MultiClient::with_credential_provider`](crate::MultiClient::with_credential_provider).
+///
+/// The URL the user has to open is given to a callback, e.g. to send it to an administrator,
+/// then the flow is polled every 5 seconds for up to 20 minutes, blocking the rotation.
+pub struct LoginFlowProvider {
+    prompt: Box<dyn Fn(&str) + Send + Sync>,
+    interval: Duration,
+    timeout: Duration,
+}
+
+impl LoginFlowProvider {
+    /// A provider calling `prompt` with the URL to open for each login flow.
+    pub fn new<F: Fn(&str) + Send + Sync + 'static>(prompt: F) -> Self {
+        Self {
+            prompt: Box::new(prompt),
+            interval: Duration::from_secs(5),
+            timeout: Duration::from_secs(20 * 60),
+        }
+    }
+
+    /// How often to check whether the user granted access. Defaults to 5 seconds.
+    pub fn interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// How long to wait for the user to grant access. Defaults to 20 minutes.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+}
+
+impl CredentialProvider for LoginFlowProvider {
+    fn new_credentials(&self, client: &SyncClient) -> Result<AppCredentials, AnalyticsError> {
+        let flow = LoginFlow::start(&client.base_url)?;
+        (self.prompt)(flow.login_url());
+        flow.wait(self.interval, self.timeout)
+    }
+}
+
+#[derive(Deserialize)]
+struct OcsResponse {
+    ocs: OcsData,
+}
+
+#[derive(Deserialize)]
+struct OcsData {
+    data: AppPasswordData,
+}
+
+#[derive(Deserialize)]
+struct AppPasswordData {
+    apppassword: String,
+}
+
+/// Exchanges the name and main password of a user for a new app password, with the OCS
+/// `getapppassword` endpoint. The app password then shows up in the user's security settings,
+/// where it can be revoked.
+pub fn app_password(
+    nextcloud_url: &str,
+    user: &str,
+    password: &str,
+) -> Result<String, AnalyticsError> {
+    let client = http::blocking::Client::builder()
+        .user_agent(DEFAULT_USER_AGENT)
+        .build()?;
+
+    let url = nextcloud_url.trim_end_matches('/').to_string() + "/ocs/v2.php/core/getapppassword";
+    let resp = client
+        .get(&url)
+        .basic_auth(user, Some(password))
+        .header("OCS-APIRequest", "true")
+        .header(http::header::ACCEPT, "application/json")
+        .send()?;
+    let status = resp.status();
+    let body = resp.text()?;
+    if !status.is_success() {
+        return Err(AnalyticsError::from_status(status, &body));
+    }
+
+    let resp: OcsResponse = parse_json(&body)?;
+    Ok(resp.ocs.data.apppassword)
+}
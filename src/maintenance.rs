@@ -0,0 +1,147 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::{AnalyticsError, DataPoint, ErrorClass, Outcome, Receipt, SyncClient};
+
+/// Maximum number of data points queued while the server is in maintenance mode, the oldest
+/// being dropped beyond.
+const MAX_QUEUED: usize = 100_000;
+
+/// Data points kept in memory while the server is in maintenance mode, see
+/// [`SyncClientBuilder::queue_during_maintenance`](crate::SyncClientBuilder::queue_during_maintenance).
+#[derive(Clone)]
+pub(crate) struct MaintenanceQueue {
+    retry_delay: Duration,
+    state: Arc<Mutex<State>>,
+}
+
+#[derive(Default)]
+struct State {
+    /// Prepared data points, with their collection, in the order they were sent.
+    points: VecDeque<(DataPoint, u32)>,
+    /// When to try sending again, if the server was last seen in maintenance mode.
+    retry_at: Option<Instant>,
+}
+
+impl State {
+    fn waiting(&self) -> bool {
+        self.retry_at
+            .is_some_and(|retry_at| Instant::now() < retry_at)
+    }
+
+    fn push(&mut self, points: &[DataPoint], collection: u32) {
+        self.points
+            .extend(points.iter().map(|point| (point.clone(), collection)));
+        let excess = self.points.len().saturating_sub(MAX_QUEUED);
+        self.points.drain(..excess);
+    }
+
+    /// Waits for the larger of `retry_delay` and the delay asked by the server.
+    fn pause(&mut self, retry_delay: Duration, err: &AnalyticsError) {
+        let delay = match err {
+            AnalyticsError::Maintenance {
+                retry_after: Some(retry_after),
+                ..
+            } => retry_delay.max(*retry_after),
+            _ => retry_delay,
+        };
+        self.retry_at = Some(Instant::now() + delay);
+    }
+}
+
+impl MaintenanceQueue {
+    pub fn new(retry_delay: Duration) -> Self {
+        Self {
+            retry_delay,
+            state: Arc::default(),
+        }
+    }
+}
+
+impl SyncClient {
+    /// Sends prepared data points to `collection` with `send`, or queues them if the server is
+    /// in maintenance mode and the client is configured to wait for it, sending the points
+    /// queued before first.
+    pub(crate) fn send_or_queue<F>(
+        &self,
+        points: &[DataPoint],
+        collection: u32,
+        payload: &str,
+        send: F,
+    ) -> Result<Receipt, AnalyticsError>
+    where
+        F: FnOnce() -> Result<Receipt, AnalyticsError>,
+    {
+        let queue = match &self.maintenance {
+            Some(queue) => queue,
+            None => return send(),
+        };
+
+        let mut state = queue.state.lock().unwrap();
+        if !state.waiting() && !state.points.is_empty() {
+            self.send_queued(queue, &mut state);
+        }
+        if state.waiting() {
+            state.push(points, collection);
+            return Ok(Receipt::new(payload, Outcome::Queued));
+        }
+        drop(state);
+
+        match send() {
+            Err(err) if err.class() == ErrorClass::Maintenance => {
+                let mut state = queue.state.lock().unwrap();
+                state.pause(queue.retry_delay, &err);
+                state.push(points, collection);
+                Ok(Receipt::new(payload, Outcome::Queued))
+            }
+            result => result,
+        }
+    }
+
+    /// Sends the data points queued while the server was in maintenance mode right away, in
+    /// order, e.g. before exiting, and returns how many were sent, or 0 if the client doesn't
+    /// queue them; see [`SyncClientBuilder::queue_during_maintenance`].
+    ///
+    /// Sending stops at the first point failing because the server is still in maintenance
+    /// mode, or can't be reached, and the points left are kept for later. Points the server
+    /// rejects for another reason are dropped.
+    ///
+    /// [`SyncClientBuilder::queue_during_maintenance`]: crate::SyncClientBuilder::queue_during_maintenance
+    pub fn flush_maintenance_queue(&self) -> usize {
+        match &self.maintenance {
+            Some(queue) => self.send_queued(queue, &mut queue.state.lock().unwrap()),
+            None => 0,
+        }
+    }
+
+    /// Number of data points queued while the server is in maintenance mode, waiting to be
+    /// sent, see [`SyncClientBuilder::queue_during_maintenance`].
+    ///
+    /// [`SyncClientBuilder::queue_during_maintenance`]: crate::SyncClientBuilder::queue_during_maintenance
+    pub fn maintenance_queued(&self) -> usize {
+        self.maintenance
+            .as_ref()
+            .map_or(0, |queue| queue.state.lock().unwrap().points.len())
+    }
+
+    fn send_queued(&self, queue: &MaintenanceQueue, state: &mut State) -> usize {
+        state.retry_at = None;
+        let mut sent = 0;
+        while let Some((point, collection)) = state.points.pop_front() {
+            let payload = self.point_payload(&point);
+            match self.deliver_point(&point, &payload, collection) {
+                Ok(_) => sent += 1,
+                Err(err) => match err.class() {
+                    ErrorClass::Maintenance | ErrorClass::Network | ErrorClass::Http => {
+                        state.points.push_front((point, collection));
+                        state.pause(queue.retry_delay, &err);
+                        break;
+                    }
+                    _ => {}
+                },
+            }
+        }
+        sent
+    }
+}
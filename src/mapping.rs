@@ -0,0 +1,106 @@
+use core::fmt;
+use std::error::Error;
+
+use serde_json::Value;
+
+use crate::DataPoint;
+#[cfg(feature = "blocking")]
+use crate::{AnalyticsError, Receipt, SyncClient};
+
+/// Which fields of a record go into which dimension of a collection, see
+/// [`SyncClient::with_column_mapping`].
+///
+/// Reports don't all use the dimensions the same way, e.g. `dimension2` is the date of a
+/// timeline, but the category of a cross-tab dataset: naming the fields avoids mixing them up.
+#[derive(Clone, Debug)]
+pub struct ColumnMapping {
+    dimension1: String,
+    dimension2: String,
+    value: String,
+}
+
+impl ColumnMapping {
+    /// Maps the record fields with the given names to `dimension1`, `dimension2` and the value.
+    pub fn new<S: Into<String>>(dimension1: S, dimension2: S, value: S) -> Self {
+        Self {
+            dimension1: dimension1.into(),
+            dimension2: dimension2.into(),
+            value: value.into(),
+        }
+    }
+
+    /// Builds a data point from a JSON object record.
+    ///
+    /// Text fields can be strings or numbers; the value field can be a number or a string
+    /// containing a number.
+    pub fn apply(&self, record: &Value) -> Result<DataPoint, MappingError> {
+        let dimension1 = self.text(record, &self.dimension1)?;
+        let dimension2 = self.text(record, &self.dimension2)?;
+
+        let field = self.field(record, &self.value)?;
+        let value = field
+            .as_f64()
+            .or_else(|| field.as_str().and_then(|s| s.trim().parse().ok()))
+            .ok_or_else(|| {
+                MappingError(format!("field {:?} isn't a number: {}", self.value, field))
+            })?;
+
+        Ok(DataPoint::new(dimension1, dimension2, value))
+    }
+
+    fn field<'a>(&self, record: &'a Value, name: &str) -> Result<&'a Value, MappingError> {
+        if !record.is_object() {
+            return Err(MappingError(format!("record isn't an object: {}", record)));
+        }
+        match &record[name] {
+            Value::Null => Err(MappingError(format!("missing field {:?}", name))),
+            field => Ok(field),
+        }
+    }
+
+    fn text(&self, record: &Value, name: &str) -> Result<String, MappingError> {
+        match self.field(record, name)? {
+            Value::String(s) => Ok(s.clone()),
+            Value::Number(n) => Ok(n.to_string()),
+            field => Err(MappingError(format!(
+                "field {:?} isn't a string: {}",
+                name, field
+            ))),
+        }
+    }
+}
+
+#[cfg(feature = "blocking")]
+impl SyncClient {
+    /// Use the given mapping for [`SyncClient::send_mapped`].
+    pub fn with_column_mapping(mut self, mapping: ColumnMapping) -> Self {
+        self.column_mapping = Some(mapping);
+        self
+    }
+
+    /// Sends a record, turned into a data point with the mapping set with
+    /// [`SyncClient::with_column_mapping`].
+    pub fn send_mapped(&self, record: &Value) -> Result<Receipt, AnalyticsError> {
+        let mapping = self.column_mapping.as_ref().ok_or_else(|| {
+            MappingError("no column mapping configured for this client".to_string())
+        })?;
+        self.send_point(mapping.apply(record)?)
+    }
+}
+
+/// An error happening when mapping a record to a data point.
+pub struct MappingError(String);
+
+impl fmt::Debug for MappingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl fmt::Display for MappingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl Error for MappingError {}
@@ -0,0 +1,47 @@
+use std::collections::HashMap;
+
+use crate::stream::DEFAULT_IN_FLIGHT;
+use crate::{AnalyticsError, DataPoint, SyncClient};
+
+impl SyncClient {
+    /// Sends a cross-tab dataset, e.g. "age by person", given as `(row, column, value)` cells.
+    ///
+    /// The cells are deduplicated first: when a `(row, column)` cell appears several times, only
+    /// its last value is sent, at the position of its first occurrence. The remaining cells are
+    /// then sent in chunks with [`SyncClient::send_stream`], a single request for up to the
+    /// [batch size](crate::SyncClientBuilder::batch_size) of the client, 500 cells by default,
+    /// with the 2.0 and 3.0 APIs.
+    ///
+    /// Returns the number of cells sent, or the error of the first failed chunk; the other
+    /// chunks are sent anyway, and sending the matrix again only updates their rows.
+    pub fn send_matrix<I>(&self, rows: I) -> Result<usize, AnalyticsError>
+    where
+        I: IntoIterator<Item = (String, String, f64)>,
+    {
+        let mut cells: Vec<DataPoint> = Vec::new();
+        let mut index: HashMap<(String, String), usize> = HashMap::new();
+
+        for (row, column, value) in rows {
+            match index.get(&(row.clone(), column.clone())) {
+                Some(&i) => cells[i].dimension3 = value,
+                None => {
+                    index.insert((row.clone(), column.clone()), cells.len());
+                    cells.push(DataPoint::new(row, column, value));
+                }
+            }
+        }
+
+        let lengths: Vec<_> = cells.chunks(self.batch_size).map(<[_]>::len).collect();
+        let mut sent = 0;
+        for (result, len) in self
+            .send_stream_with(cells, self.batch_size, DEFAULT_IN_FLIGHT)
+            .into_iter()
+            .zip(lengths)
+        {
+            if result?.was_sent() {
+                sent += len;
+            }
+        }
+        Ok(sent)
+    }
+}
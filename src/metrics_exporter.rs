@@ -0,0 +1,276 @@
+//! Stores the metrics of the [`metrics`] façade in Nextcloud Analytics timelines, with an
+//! [`AnalyticsRecorder`] pushed on an interval by an [`Exporter`].
+//!
+//! ```no_run
+//! use std::sync::Arc;
+//! use std::time::Duration;
+//!
+//! use nextcloud_analytics_rs::metrics_exporter::AnalyticsRecorder;
+//! use nextcloud_analytics_rs::SyncClient;
+//!
+//! let client = SyncClient::new("https://example.com/nextcloud", 3, "myself", "app password");
+//! let recorder = AnalyticsRecorder::new();
+//! let _exporter = recorder.start_exporting(Arc::new(client), Duration::from_secs(60));
+//! metrics::set_global_recorder(recorder).unwrap();
+//!
+//! metrics::counter!("jobs_done", "queue" => "emails").increment(1);
+//! ```
+//!
+//! Requires the `metrics-exporter` feature.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use metrics::{
+    Counter, CounterFn, Gauge, GaugeFn, Histogram, HistogramFn, Key, KeyName, Metadata, Recorder,
+    SharedString, Unit,
+};
+
+use crate::{AnalyticsError, DataPoint, Receipt, SyncClient};
+
+/// A counter, as its total.
+struct CounterValue(AtomicU64);
+
+impl CounterFn for CounterValue {
+    fn increment(&self, value: u64) {
+        self.0.fetch_add(value, Ordering::Relaxed);
+    }
+
+    fn absolute(&self, value: u64) {
+        self.0.fetch_max(value, Ordering::Relaxed);
+    }
+}
+
+/// A gauge, as the bits of its `f64` value.
+struct GaugeValue(AtomicU64);
+
+impl GaugeValue {
+    fn update(&self, update: impl Fn(f64) -> f64) {
+        let _ = self
+            .0
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |bits| {
+                Some(update(f64::from_bits(bits)).to_bits())
+            });
+    }
+}
+
+impl GaugeFn for GaugeValue {
+    fn increment(&self, value: f64) {
+        self.update(|gauge| gauge + value);
+    }
+
+    fn decrement(&self, value: f64) {
+        self.update(|gauge| gauge - value);
+    }
+
+    fn set(&self, value: f64) {
+        self.0.store(value.to_bits(), Ordering::Relaxed);
+    }
+}
+
+/// The values recorded by a histogram since the last export.
+#[derive(Clone, Copy, Default)]
+struct Summary {
+    count: u64,
+    sum: f64,
+    min: f64,
+    max: f64,
+}
+
+struct HistogramValue(Mutex<Summary>);
+
+impl HistogramFn for HistogramValue {
+    fn record(&self, value: f64) {
+        self.record_many(value, 1);
+    }
+
+    fn record_many(&self, value: f64, count: usize) {
+        let mut summary = self.0.lock().unwrap_or_else(|err| err.into_inner());
+        if summary.count == 0 {
+            summary.min = value;
+            summary.max = value;
+        } else {
+            summary.min = summary.min.min(value);
+            summary.max = summary.max.max(value);
+        }
+        summary.count += count as u64;
+        summary.sum += value * count as f64;
+    }
+}
+
+#[derive(Default)]
+struct Registry {
+    counters: Mutex<HashMap<Key, Arc<CounterValue>>>,
+    gauges: Mutex<HashMap<Key, Arc<GaugeValue>>>,
+    histograms: Mutex<HashMap<Key, Arc<HistogramValue>>>,
+}
+
+/// The timeline key of a metric: its name, with `suffix`, followed by its labels in braces if
+/// it has any, like the default keys of the Prometheus scraper, e.g. `jobs_done{queue=emails}`.
+fn timeline_key(key: &Key, suffix: &str) -> String {
+    let labels = key
+        .labels()
+        .map(|label| format!("{}={}", label.key(), label.value()))
+        .collect::<Vec<_>>();
+    if labels.is_empty() {
+        format!("{}{}", key.name(), suffix)
+    } else {
+        format!("{}{}{{{}}}", key.name(), suffix, labels.join(","))
+    }
+}
+
+/// A [`Recorder`] keeping the metrics of the `metrics` façade in memory, to push them as timeline
+/// data with [`AnalyticsRecorder::push`] or periodically with
+/// [`AnalyticsRecorder::start_exporting`].
+///
+/// Each push sends, at the time of the push:
+///
+/// - counters as their total;
+/// - gauges as their value;
+/// - histograms as the `_count`, `_sum`, `_min` and `_max` of the values recorded since the
+///   previous push, e.g. `request_seconds_max`, or nothing if there were none.
+///
+/// Metrics are keyed by their name and their labels, e.g. `jobs_done{queue=emails}`. Their
+/// descriptions and units can't be stored by Analytics, and are ignored.
+#[derive(Clone, Default)]
+pub struct AnalyticsRecorder {
+    registry: Arc<Registry>,
+}
+
+impl AnalyticsRecorder {
+    /// A recorder without any metric yet.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The data points of the metrics, at `time`, resetting the summaries of the histograms.
+    fn points(&self, time: &str) -> Vec<DataPoint> {
+        let point = |key: String, value: f64| DataPoint::new(key, time.to_string(), value);
+        let mut points = Vec::new();
+        for (key, counter) in self.registry.counters.lock().unwrap().iter() {
+            let value = counter.0.load(Ordering::Relaxed) as f64;
+            points.push(point(timeline_key(key, ""), value));
+        }
+        for (key, gauge) in self.registry.gauges.lock().unwrap().iter() {
+            let value = f64::from_bits(gauge.0.load(Ordering::Relaxed));
+            points.push(point(timeline_key(key, ""), value));
+        }
+        for (key, histogram) in self.registry.histograms.lock().unwrap().iter() {
+            let summary = {
+                let mut summary = histogram.0.lock().unwrap_or_else(|err| err.into_inner());
+                std::mem::take(&mut *summary)
+            };
+            if summary.count == 0 {
+                continue;
+            }
+            points.push(point(timeline_key(key, "_count"), summary.count as f64));
+            points.push(point(timeline_key(key, "_sum"), summary.sum));
+            points.push(point(timeline_key(key, "_min"), summary.min));
+            points.push(point(timeline_key(key, "_max"), summary.max));
+        }
+        points.sort_by(|a, b| a.dimension1.cmp(&b.dimension1));
+        points
+    }
+
+    /// Sends the current values of the metrics to `client` in a batch, as timeline data at the
+    /// current time, see [`SyncClient::send_batch`]. Non-finite values can't be stored by
+    /// Analytics and are skipped.
+    ///
+    /// The summaries of the histograms are reset even if the send fails.
+    pub fn push(&self, client: &SyncClient) -> Result<Receipt, AnalyticsError> {
+        let time = client.format_time(client.now())?;
+        let points: Vec<_> = self
+            .points(&time)
+            .into_iter()
+            .filter(|point| point.dimension3.is_finite())
+            .collect();
+        client.send_batch(&points)
+    }
+
+    /// Starts pushing the metrics to `client` every `interval`, on its own thread, until the
+    /// returned exporter is dropped. Failed pushes are reported through the client's callbacks,
+    /// see [`SyncClient::on_error`].
+    ///
+    /// The recorder can then be installed, e.g. with [`metrics::set_global_recorder`].
+    pub fn start_exporting(&self, client: Arc<SyncClient>, interval: Duration) -> Exporter {
+        let recorder = self.clone();
+        let (stop, stopped) = mpsc::channel();
+
+        let thread = thread::spawn(move || loop {
+            let stop = match stopped.recv_timeout(interval) {
+                Err(RecvTimeoutError::Timeout) => false,
+                Ok(()) | Err(RecvTimeoutError::Disconnected) => true,
+            };
+            let _ = recorder.push(&client);
+            if stop {
+                break;
+            }
+        });
+
+        Exporter {
+            stop: Some(stop),
+            thread: Some(thread),
+        }
+    }
+}
+
+impl Recorder for AnalyticsRecorder {
+    fn describe_counter(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+
+    fn describe_gauge(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+
+    fn describe_histogram(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+
+    fn register_counter(&self, key: &Key, _: &Metadata<'_>) -> Counter {
+        let mut counters = self.registry.counters.lock().unwrap();
+        let counter = counters
+            .entry(key.clone())
+            .or_insert_with(|| Arc::new(CounterValue(AtomicU64::new(0))));
+        Counter::from_arc(Arc::clone(counter))
+    }
+
+    fn register_gauge(&self, key: &Key, _: &Metadata<'_>) -> Gauge {
+        let mut gauges = self.registry.gauges.lock().unwrap();
+        let gauge = gauges
+            .entry(key.clone())
+            .or_insert_with(|| Arc::new(GaugeValue(AtomicU64::new(0f64.to_bits()))));
+        Gauge::from_arc(Arc::clone(gauge))
+    }
+
+    fn register_histogram(&self, key: &Key, _: &Metadata<'_>) -> Histogram {
+        let mut histograms = self.registry.histograms.lock().unwrap();
+        let histogram = histograms
+            .entry(key.clone())
+            .or_insert_with(|| Arc::new(HistogramValue(Mutex::default())));
+        Histogram::from_arc(Arc::clone(histogram))
+    }
+}
+
+/// Pushes the metrics of an [`AnalyticsRecorder`] on an interval, see
+/// [`AnalyticsRecorder::start_exporting`].
+///
+/// The exporter stops when dropped, after a last push, so that the values recorded since the
+/// previous one aren't lost.
+pub struct Exporter {
+    stop: Option<mpsc::Sender<()>>,
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+impl Exporter {
+    /// Stops exporting, after a last push. Same as dropping the exporter.
+    pub fn stop(self) {}
+}
+
+impl Drop for Exporter {
+    fn drop(&mut self) {
+        // Dropping the sender wakes the thread up.
+        self.stop.take();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
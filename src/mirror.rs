@@ -0,0 +1,164 @@
+//! A local SQLite copy of the data sent by a client, to query it without the server and to find
+//! the rows the server lost, see [`Mirror`].
+//!
+//! Requires the `mirror` feature.
+
+use std::path::Path;
+use std::sync::{Mutex, MutexGuard};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use rusqlite::{params, Connection, Row};
+
+use crate::{AnalyticsError, DataPoint, SyncClient};
+
+/// The table of the mirrored rows, with the columns of the facts table of Analytics.
+const SCHEMA: &str = "CREATE TABLE IF NOT EXISTS facts (
+    dataset INTEGER NOT NULL,
+    dimension1 TEXT NOT NULL,
+    dimension2 TEXT NOT NULL,
+    value REAL NOT NULL,
+    timestamp INTEGER NOT NULL,
+    PRIMARY KEY (dataset, dimension1, dimension2)
+)";
+
+/// The mirrored rows of a dataset which aren't among the rows of the server, loaded into the
+/// `remote` table.
+const MISSING_REMOTELY: &str = "SELECT facts.dimension1, facts.dimension2, facts.value
+    FROM facts
+    LEFT JOIN remote
+        ON remote.dimension1 = facts.dimension1 AND remote.dimension2 = facts.dimension2
+    WHERE facts.dataset = ?1 AND remote.dimension1 IS NULL
+    ORDER BY facts.dimension1, facts.dimension2";
+
+fn sqlite_error(err: rusqlite::Error) -> AnalyticsError {
+    AnalyticsError::local(format!("mirror: {}", err))
+}
+
+/// The data point of a row with the dimensions and value of a fact.
+fn data_point(row: &Row<'_>) -> rusqlite::Result<DataPoint> {
+    Ok(DataPoint {
+        dimension1: row.get(0)?,
+        dimension2: row.get(1)?,
+        dimension3: row.get(2)?,
+    })
+}
+
+/// A SQLite database holding every row successfully sent by the clients it's attached to, see
+/// [`SyncClient::with_mirror`], e.g. for a logger to keep its own history next to Analytics.
+///
+/// Rows are stored in the `facts` table, with the columns of Analytics: `dataset`,
+/// `dimension1`, `dimension2`, `value`, and the `timestamp` of the send, in seconds since the
+/// Unix epoch. Like on the server, sending a row with the same dimensions as a stored one
+/// replaces its value. Deleting data on the server doesn't delete it from the mirror.
+///
+/// The mirror also records what the server accepted but may have lost since, e.g. after a
+/// restore from backup: [`Mirror::missing_remotely`] returns those rows, to send them again.
+pub struct Mirror {
+    connection: Mutex<Connection>,
+}
+
+impl Mirror {
+    /// Opens the mirror stored in the SQLite database at `path`, creating it if needed.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, AnalyticsError> {
+        Self::with_connection(Connection::open(path).map_err(sqlite_error)?)
+    }
+
+    /// A mirror kept in memory, lost when dropped, e.g. for tests.
+    pub fn in_memory() -> Result<Self, AnalyticsError> {
+        Self::with_connection(Connection::open_in_memory().map_err(sqlite_error)?)
+    }
+
+    fn with_connection(connection: Connection) -> Result<Self, AnalyticsError> {
+        connection.execute(SCHEMA, []).map_err(sqlite_error)?;
+        Ok(Self {
+            connection: Mutex::new(connection),
+        })
+    }
+
+    /// The connection to the database, to query the mirrored rows with SQL.
+    ///
+    /// Sends of the attached clients wait for it to be released to record their rows.
+    pub fn connection(&self) -> MutexGuard<'_, Connection> {
+        self.connection
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+    }
+
+    /// The rows mirrored for `dataset`, ordered by their dimensions.
+    pub fn rows(&self, dataset: u32) -> Result<Vec<DataPoint>, AnalyticsError> {
+        let connection = self.connection();
+        let mut statement = connection
+            .prepare(
+                "SELECT dimension1, dimension2, value FROM facts WHERE dataset = ?1
+                ORDER BY dimension1, dimension2",
+            )
+            .map_err(sqlite_error)?;
+        let rows = statement
+            .query_map([dataset], data_point)
+            .map_err(sqlite_error)?;
+        rows.collect::<Result<_, _>>().map_err(sqlite_error)
+    }
+
+    /// Stores rows sent to `dataset`, replacing those with the same dimensions.
+    pub(crate) fn record(&self, points: &[DataPoint], dataset: u32) -> Result<(), AnalyticsError> {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_secs() as i64);
+        let mut connection = self.connection();
+        let transaction = connection.transaction().map_err(sqlite_error)?;
+        {
+            let mut statement = transaction
+                .prepare_cached("INSERT OR REPLACE INTO facts VALUES (?1, ?2, ?3, ?4, ?5)")
+                .map_err(sqlite_error)?;
+            for point in points {
+                statement
+                    .execute(params![
+                        dataset,
+                        point.dimension1,
+                        point.dimension2,
+                        point.dimension3,
+                        timestamp
+                    ])
+                    .map_err(sqlite_error)?;
+            }
+        }
+        transaction.commit().map_err(sqlite_error)
+    }
+
+    /// The rows mirrored for the collection of `client` which the server doesn't have, read
+    /// back with [`SyncClient::get_data`]: rows are matched by their dimensions, whatever
+    /// their values.
+    pub fn missing_remotely(&self, client: &SyncClient) -> Result<Vec<DataPoint>, AnalyticsError> {
+        let remote = client.get_data()?;
+
+        let mut connection = self.connection();
+        let transaction = connection.transaction().map_err(sqlite_error)?;
+        transaction
+            .execute_batch(
+                "CREATE TEMP TABLE IF NOT EXISTS remote (dimension1 TEXT, dimension2 TEXT);
+                DELETE FROM remote;",
+            )
+            .map_err(sqlite_error)?;
+        let missing = {
+            let mut insert = transaction
+                .prepare("INSERT INTO remote VALUES (?1, ?2)")
+                .map_err(sqlite_error)?;
+            for point in &remote {
+                insert
+                    .execute([&point.dimension1, &point.dimension2])
+                    .map_err(sqlite_error)?;
+            }
+
+            let mut statement = transaction
+                .prepare(MISSING_REMOTELY)
+                .map_err(sqlite_error)?;
+            let rows = statement
+                .query_map([client.collection], data_point)
+                .map_err(sqlite_error)?;
+            rows.collect::<Result<Vec<_>, _>>().map_err(sqlite_error)?
+        };
+        // Rolled back, to drop the rows of the server.
+        drop(transaction);
+        Ok(missing)
+    }
+}
@@ -0,0 +1,412 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::login::AppCredentials;
+use crate::{AnalyticsError, DataPoint, ErrorClass, Receipt, SyncClient};
+
+/// Default time after which an instance that failed is tried again first.
+const DEFAULT_RETRY_AFTER: Duration = Duration::from_secs(30);
+
+/// How a [`MultiClient`] uses its instances.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MultiMode {
+    /// Send to the first instance that is up, in order.
+    Failover,
+    /// Send to all the instances.
+    Mirror,
+}
+
+/// Where a [`MultiClient`] gets new credentials for an instance when rotating them, see
+/// [`MultiClient::with_credential_provider`], e.g. a
+/// [`LoginFlowProvider`](crate::login::LoginFlowProvider) or a closure taking the client of the
+/// instance.
+pub trait CredentialProvider: Send + Sync {
+    /// New credentials for the instance of `client`.
+    fn new_credentials(&self, client: &SyncClient) -> Result<AppCredentials, AnalyticsError>;
+}
+
+impl<F> CredentialProvider for F
+where
+    F: Fn(&SyncClient) -> Result<AppCredentials, AnalyticsError> + Send + Sync,
+{
+    fn new_credentials(&self, client: &SyncClient) -> Result<AppCredentials, AnalyticsError> {
+        self(client)
+    }
+}
+
+/// An instance of a [`MultiClient`], with when it last went down.
+struct Member {
+    client: SyncClient,
+    down_since: Mutex<Option<Instant>>,
+    provider: Option<Arc<dyn CredentialProvider>>,
+    rotate_every: Option<Duration>,
+    /// When the credentials were last rotated, or the instance added; held while rotating.
+    rotated_at: Mutex<Instant>,
+}
+
+/// A client sending to several Nextcloud instances, e.g. the primary and the replicas of a
+/// replicated setup, so that data keeps flowing during an outage of the primary.
+///
+/// An instance is down after a send failed with a [network](ErrorClass::Network),
+/// [HTTP](ErrorClass::Http) or [maintenance](ErrorClass::Maintenance) error, as for a
+/// [`BufferedClient`](crate::BufferedClient); other errors, e.g. invalid data, are returned as
+/// they are, without trying the other instances. A down instance is tried again after a while,
+/// 30 seconds by default, and is back up as soon as a send to it succeeds.
+///
+/// Each instance is a [`SyncClient`] with its own URL, credentials and settings. Their
+/// credentials can be rotated with [`MultiClient::rotate_now`], from the
+/// [provider](MultiClient::with_credential_provider) of each instance.
+pub struct MultiClient {
+    members: Vec<Member>,
+    mode: MultiMode,
+    retry_after: Duration,
+    workers: usize,
+    max_in_flight: usize,
+}
+
+impl MultiClient {
+    /// Sends to the first of `clients` that is up; the first one is the primary, used again as
+    /// soon as it's back.
+    pub fn failover(clients: Vec<SyncClient>) -> Self {
+        Self::new(clients, MultiMode::Failover)
+    }
+
+    /// Sends to all of `clients`, succeeding if at least one of them accepted the data, e.g. for
+    /// instances that don't replicate their Analytics data. An instance that is down misses the
+    /// data sent meanwhile.
+    pub fn mirror(clients: Vec<SyncClient>) -> Self {
+        Self::new(clients, MultiMode::Mirror)
+    }
+
+    fn new(clients: Vec<SyncClient>, mode: MultiMode) -> Self {
+        Self {
+            members: clients
+                .into_iter()
+                .map(|client| Member {
+                    client,
+                    down_since: Mutex::new(None),
+                    provider: None,
+                    rotate_every: None,
+                    rotated_at: Mutex::new(Instant::now()),
+                })
+                .collect(),
+            mode,
+            retry_after: DEFAULT_RETRY_AFTER,
+            workers: 0,
+            max_in_flight: 1,
+        }
+    }
+
+    /// Time after which an instance that went down is tried again. Defaults to 30 seconds.
+    pub fn with_retry_after(mut self, retry_after: Duration) -> Self {
+        self.retry_after = retry_after;
+        self
+    }
+
+    /// Get new credentials for the instance `target`, counted from 0 in the order of the
+    /// clients, from `provider` when rotating them.
+    ///
+    /// Panics if there's no instance `target`.
+    pub fn with_credential_provider<P: CredentialProvider + 'static>(
+        mut self,
+        target: usize,
+        provider: P,
+    ) -> Self {
+        self.members[target].provider = Some(Arc::new(provider));
+        self
+    }
+
+    /// Rotate the credentials of the instance `target` every `every`, with
+    /// [`MultiClient::rotate_due`]. Requires a
+    /// [provider](MultiClient::with_credential_provider).
+    ///
+    /// Panics if there's no instance `target`.
+    pub fn with_rotation_schedule(mut self, target: usize, every: Duration) -> Self {
+        self.members[target].rotate_every = Some(every);
+        self
+    }
+
+    /// Replaces the credentials of the instance `target` with new ones from its
+    /// [provider](MultiClient::with_credential_provider), e.g. running a new Login Flow v2.
+    ///
+    /// The credentials are swapped at once in the [`Credentials`](crate::Credentials) of its
+    /// client, shared with its clones: the sends meanwhile use the previous ones, and the
+    /// clients queueing points for the instance, e.g. a [`BufferedClient`](crate::BufferedClient)
+    /// or a [`BackgroundSender`](crate::BackgroundSender) wrapping a clone, keep their queue and
+    /// use the new credentials from their next request. The previous credentials are kept if
+    /// the provider fails. Rotations of the same instance are done one at a time. The previous
+    /// app password isn't revoked.
+    pub fn rotate_now(&self, target: usize) -> Result<(), AnalyticsError> {
+        let member = self.members.get(target).ok_or_else(|| {
+            AnalyticsError::local(format!("no Nextcloud instance {} to rotate", target))
+        })?;
+        let provider = member.provider.as_ref().ok_or_else(|| {
+            AnalyticsError::local(format!("no credential provider for instance {}", target))
+        })?;
+
+        let mut rotated_at = member.rotated_at.lock().unwrap();
+        let credentials = provider.new_credentials(&member.client)?;
+        member
+            .client
+            .credentials()
+            .set_app_credentials(&credentials);
+        *rotated_at = Instant::now();
+        Ok(())
+    }
+
+    /// Rotates the credentials of the instances whose
+    /// [schedule](MultiClient::with_rotation_schedule) is due, e.g. from a periodic task, and
+    /// returns the result of each rotation, with the instance.
+    pub fn rotate_due(&self) -> Vec<(usize, Result<(), AnalyticsError>)> {
+        (0..self.members.len())
+            .filter(|&target| {
+                let member = &self.members[target];
+                member
+                    .rotate_every
+                    .is_some_and(|every| member.rotated_at.lock().unwrap().elapsed() >= every)
+            })
+            .map(|target| (target, self.rotate_now(target)))
+            .collect()
+    }
+
+    /// Number of threads of [`MultiClient::deliver`], and maximum number of requests it sends
+    /// to the same instance at once. Defaults to a thread per instance, and one request per
+    /// instance.
+    pub fn with_worker_pool(mut self, workers: usize, max_in_flight_per_instance: usize) -> Self {
+        self.workers = workers.max(1);
+        self.max_in_flight = max_in_flight_per_instance.max(1);
+        self
+    }
+
+    /// Sends each batch of `deliveries` to its instance, counted from 0 in the order of the
+    /// clients, with [`SyncClient::send_batch`], and returns their results in the same order,
+    /// e.g. to push the data of each site to its own Nextcloud instance.
+    ///
+    /// The batches are sent by the threads of the [worker pool](MultiClient::with_worker_pool),
+    /// taking the instances in turn, and skipping the ones with as many requests in flight as
+    /// allowed, so that a slow instance doesn't hold back the deliveries to the others: its
+    /// batches wait for its own requests instead. The batches of an instance are sent in
+    /// order, one after the other if it allows a single request at once. The health of the
+    /// instances is tracked as for the other sends, but a down instance isn't skipped.
+    pub fn deliver(
+        &self,
+        deliveries: Vec<(usize, Vec<DataPoint>)>,
+    ) -> Vec<Result<Receipt, AnalyticsError>> {
+        let mut schedule = Schedule {
+            queues: vec![VecDeque::new(); self.members.len()],
+            in_flight: vec![0; self.members.len()],
+            next: 0,
+        };
+        let mut results: Vec<Option<Result<Receipt, AnalyticsError>>> = Vec::new();
+        for (index, (target, _)) in deliveries.iter().enumerate() {
+            match schedule.queues.get_mut(*target) {
+                Some(queue) => {
+                    queue.push_back(index);
+                    results.push(None);
+                }
+                None => results.push(Some(Err(AnalyticsError::local(format!(
+                    "no Nextcloud instance {} to deliver to",
+                    target
+                ))))),
+            }
+        }
+
+        let workers = match self.workers {
+            0 => self.members.len(),
+            workers => workers,
+        };
+        let state = (Mutex::new((schedule, results)), Condvar::new());
+        thread::scope(|scope| {
+            for _ in 0..workers.min(deliveries.len()) {
+                scope.spawn(|| self.run_worker(&deliveries, &state));
+            }
+        });
+        let (_, results) = state.0.into_inner().unwrap();
+        results
+            .into_iter()
+            .map(|result| result.unwrap_or_else(|| Err(AnalyticsError::local("delivery not sent"))))
+            .collect()
+    }
+
+    /// Sends the batches of the schedule until none is left.
+    fn run_worker(&self, deliveries: &[(usize, Vec<DataPoint>)], state: &DeliveryState) {
+        let (lock, wake) = state;
+        let mut guard = lock.lock().unwrap();
+        loop {
+            let (target, index) = match guard.0.take(self.max_in_flight) {
+                Some(next) => next,
+                None if guard.0.is_empty() => return,
+                None => {
+                    guard = wake.wait(guard).unwrap();
+                    continue;
+                }
+            };
+            drop(guard);
+
+            let points = &deliveries[index].1;
+            let result = self.try_member(&self.members[target], &|client: &SyncClient| {
+                client.send_batch(points)
+            });
+
+            guard = lock.lock().unwrap();
+            guard.0.in_flight[target] -= 1;
+            guard.1[index] = Some(result);
+            wake.notify_all();
+        }
+    }
+
+    /// How the instances are used.
+    pub fn mode(&self) -> MultiMode {
+        self.mode
+    }
+
+    /// The client of each instance, in order.
+    pub fn clients(&self) -> impl Iterator<Item = &SyncClient> {
+        self.members.iter().map(|member| &member.client)
+    }
+
+    /// Whether each instance is up, in order, as far as the last sends tell.
+    pub fn health(&self) -> Vec<bool> {
+        self.members
+            .iter()
+            .map(|member| member.down_since.lock().unwrap().is_none())
+            .collect()
+    }
+
+    /// Sends some data, like [`SyncClient::send_data`].
+    pub fn send_data<S: Into<String>, F: Into<f64>>(
+        &self,
+        dimension1: S,
+        dimension2: S,
+        dimension3: F,
+    ) -> Result<Receipt, AnalyticsError> {
+        self.send_point(DataPoint::new(dimension1, dimension2, dimension3))
+    }
+
+    /// Sends a single data point, like [`SyncClient::send_point`].
+    pub fn send_point(&self, point: DataPoint) -> Result<Receipt, AnalyticsError> {
+        self.send_with(|client| client.send_point(point.clone()))
+    }
+
+    /// Sends many data points in a single request per instance, like
+    /// [`SyncClient::send_batch`].
+    pub fn send_batch(&self, points: &[DataPoint]) -> Result<Receipt, AnalyticsError> {
+        self.send_with(|client| client.send_batch(points))
+    }
+
+    /// Whether the instance should be tried before the ones that are down.
+    fn is_available(&self, member: &Member) -> bool {
+        match *member.down_since.lock().unwrap() {
+            Some(since) => since.elapsed() >= self.retry_after,
+            None => true,
+        }
+    }
+
+    /// Sends to an instance, keeping track of its health.
+    fn try_member<F>(&self, member: &Member, send: &F) -> Result<Receipt, AnalyticsError>
+    where
+        F: Fn(&SyncClient) -> Result<Receipt, AnalyticsError>,
+    {
+        let result = send(&member.client);
+        let mut down_since = member.down_since.lock().unwrap();
+        match &result {
+            Err(err) if is_outage(err) => *down_since = Some(Instant::now()),
+            _ => *down_since = None,
+        }
+        result
+    }
+
+    fn send_with<F>(&self, send: F) -> Result<Receipt, AnalyticsError>
+    where
+        F: Fn(&SyncClient) -> Result<Receipt, AnalyticsError>,
+    {
+        // Instances that are up or due for a retry first, then the others as a last resort.
+        let (available, down): (Vec<&Member>, Vec<&Member>) = self
+            .members
+            .iter()
+            .partition(|member| self.is_available(member));
+        let mut last_err = None;
+
+        match self.mode {
+            MultiMode::Failover => {
+                for member in available.into_iter().chain(down) {
+                    match self.try_member(member, &send) {
+                        Err(err) if is_outage(&err) => last_err = Some(err),
+                        result => return result,
+                    }
+                }
+            }
+            MultiMode::Mirror => {
+                let mut first_ok = None;
+                for member in &available {
+                    match self.try_member(member, &send) {
+                        Ok(receipt) => {
+                            first_ok.get_or_insert(receipt);
+                        }
+                        Err(err) => last_err = Some(err),
+                    }
+                }
+                if first_ok.is_none() {
+                    for member in down {
+                        match self.try_member(member, &send) {
+                            Ok(receipt) => return Ok(receipt),
+                            Err(err) => last_err = Some(err),
+                        }
+                    }
+                }
+                if let Some(receipt) = first_ok {
+                    return Ok(receipt);
+                }
+            }
+        }
+
+        Err(last_err.unwrap_or_else(|| AnalyticsError::local("no Nextcloud instance to send to")))
+    }
+}
+
+/// The batches waiting to be delivered by the worker pool, with its results.
+type DeliveryState = (
+    Mutex<(Schedule, Vec<Option<Result<Receipt, AnalyticsError>>>)>,
+    Condvar,
+);
+
+/// The batches of each instance waiting to be delivered, by index, and how many are in flight.
+struct Schedule {
+    queues: Vec<VecDeque<usize>>,
+    in_flight: Vec<usize>,
+    /// The instance to take a batch from first, so that they're taken in turn.
+    next: usize,
+}
+
+impl Schedule {
+    /// Takes the next batch of the first instance, in turn, below `max_in_flight` requests.
+    fn take(&mut self, max_in_flight: usize) -> Option<(usize, usize)> {
+        let count = self.queues.len();
+        for offset in 0..count {
+            let target = (self.next + offset) % count;
+            if self.in_flight[target] < max_in_flight {
+                if let Some(index) = self.queues[target].pop_front() {
+                    self.in_flight[target] += 1;
+                    self.next = (target + 1) % count;
+                    return Some((target, index));
+                }
+            }
+        }
+        None
+    }
+
+    /// Whether all the batches were delivered.
+    fn is_empty(&self) -> bool {
+        self.queues.iter().all(VecDeque::is_empty)
+    }
+}
+
+/// Whether the error means that the instance is down, rather than a problem with the data.
+fn is_outage(err: &AnalyticsError) -> bool {
+    matches!(
+        err.class(),
+        ErrorClass::Network | ErrorClass::Http | ErrorClass::Maintenance
+    )
+}
@@ -0,0 +1,89 @@
+use reqwest as http;
+use serde_json::json;
+
+use crate::encoding::percent_encode;
+use crate::{AnalyticsError, SyncClient};
+
+static TALK_CHAT_URL: &str = "ocs/v2.php/apps/spreed/api/v1/chat/{TOKEN}";
+static ADMIN_NOTIFICATION_URL: &str =
+    "ocs/v2.php/apps/notifications/api/v2/admin_notifications/{USER}";
+
+/// Where a notification gets delivered.
+#[derive(Clone, Debug)]
+pub(crate) enum Target {
+    /// A Nextcloud Talk room, identified by its token.
+    TalkRoom(String),
+    /// An OCS admin notification sent to the given user. Requires the client's user to be an
+    /// administrator.
+    AdminNotification(String),
+}
+
+/// Tells operators that their metrics pipeline is down, after a given number of consecutive
+/// delivery failures.
+///
+/// The notification is sent once when the threshold is reached; the failure counter is reset
+/// after a successful send. A threshold of 0 counts as 1, notifying on the first failure.
+///
+/// Notifications are sent like the other requests of the client, through its transport and
+/// interceptors, with its retry policy and rate limit, and with secrets redacted from errors.
+#[derive(Clone, Debug)]
+pub struct FailureNotifier {
+    target: Target,
+    threshold: u32,
+}
+
+impl FailureNotifier {
+    /// Post a message to the Nextcloud Talk room with the given `token` (last part of the room's
+    /// URL) after `threshold` consecutive failures.
+    pub fn talk_room<S: Into<String>>(token: S, threshold: u32) -> Self {
+        Self {
+            target: Target::TalkRoom(token.into()),
+            threshold: threshold.max(1),
+        }
+    }
+
+    /// Send a Nextcloud notification to `user` after `threshold` consecutive failures.
+    ///
+    /// This uses the OCS admin notifications API, so the client's user must be an administrator.
+    pub fn admin_notification<S: Into<String>>(user: S, threshold: u32) -> Self {
+        Self {
+            target: Target::AdminNotification(user.into()),
+            threshold: threshold.max(1),
+        }
+    }
+
+    /// Number of consecutive failures after which a notification is sent.
+    pub fn threshold(&self) -> u32 {
+        self.threshold
+    }
+
+    pub(crate) fn notify(&self, client: &SyncClient, message: &str) -> Result<(), AnalyticsError> {
+        self.target.post(client, message)
+    }
+}
+
+impl Target {
+    /// Posts `message` to the target, as the client's user.
+    pub(crate) fn post(&self, client: &SyncClient, message: &str) -> Result<(), AnalyticsError> {
+        let (path, body) = match self {
+            Target::TalkRoom(token) => (
+                TALK_CHAT_URL.replace("{TOKEN}", &percent_encode(token)),
+                json!({ "message": message }),
+            ),
+            Target::AdminNotification(target_user) => (
+                ADMIN_NOTIFICATION_URL.replace("{USER}", &percent_encode(target_user)),
+                json!({ "shortMessage": message }),
+            ),
+        };
+
+        let req = client
+            .client
+            .post(&(client.base_url.clone() + &path))
+            .header("OCS-APIRequest", "true")
+            .header(http::header::ACCEPT, "application/json")
+            .header(http::header::CONTENT_TYPE, "application/json")
+            .body(body.to_string());
+        client.send_request(req)?;
+        Ok(())
+    }
+}
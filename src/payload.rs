@@ -0,0 +1,86 @@
+//! Serialization of the data points sent to the server.
+
+use std::sync::Arc;
+
+use crate::batch::batch_payload;
+use crate::{DataPoint, SyncClient, ValueFormat};
+
+/// Serializes the data points of a [`SyncClient`] into request bodies, see
+/// [`SyncClient::with_payload_encoder`], e.g. to add a field required by a patched Analytics
+/// install:
+///
+/// ```
+/// use nextcloud_analytics_rs::{DataPoint, JsonEncoder, PayloadEncoder, ValueFormat};
+///
+/// struct WithSource;
+///
+/// impl PayloadEncoder for WithSource {
+///     fn encode_point(&self, point: &DataPoint, format: ValueFormat) -> String {
+///         let mut json: serde_json::Value =
+///             serde_json::from_str(&JsonEncoder.encode_point(point, format)).unwrap();
+///         json["source"] = "sensors".into();
+///         json.to_string()
+///     }
+///
+///     fn encode_batch(&self, points: &[DataPoint], format: ValueFormat) -> String {
+///         JsonEncoder.encode_batch(points, format)
+///     }
+/// }
+/// ```
+pub trait PayloadEncoder: Send + Sync {
+    /// Body of a request adding a single data point, with the 1.0 API.
+    fn encode_point(&self, point: &DataPoint, format: ValueFormat) -> String;
+
+    /// Body of a request adding several data points, with the 2.0 API, or a single one with the
+    /// 3.0 API.
+    fn encode_batch(&self, points: &[DataPoint], format: ValueFormat) -> String;
+
+    /// Value of the `Content-Type` header of the requests.
+    fn content_type(&self) -> &str {
+        "application/json"
+    }
+}
+
+/// The JSON bodies expected by the Analytics API, the default [`PayloadEncoder`].
+#[derive(Clone, Copy, Debug, Default)]
+pub struct JsonEncoder;
+
+impl PayloadEncoder for JsonEncoder {
+    fn encode_point(&self, point: &DataPoint, format: ValueFormat) -> String {
+        point.to_json_with(format)
+    }
+
+    fn encode_batch(&self, points: &[DataPoint], format: ValueFormat) -> String {
+        batch_payload(points, format)
+    }
+}
+
+impl SyncClient {
+    /// Serialize the data points with the given encoder instead of [`JsonEncoder`], e.g. for an
+    /// alternative encoding, or extra fields required by a patched Analytics install.
+    ///
+    /// The encoder applies to sends, their [dry runs](SyncClient::with_dry_run) and
+    /// [explanations](SyncClient::explain); receipts hash the encoded payloads.
+    pub fn with_payload_encoder<E: PayloadEncoder + 'static>(mut self, encoder: E) -> Self {
+        self.payload_encoder = Arc::new(encoder);
+        self
+    }
+
+    /// Body of a request adding a single data point, in the format of the client's API version.
+    pub(crate) fn point_payload(&self, point: &DataPoint) -> String {
+        if self.api_version.wraps_points() {
+            self.batch_payload(std::slice::from_ref(point))
+        } else {
+            self.payload_encoder.encode_point(point, self.value_format)
+        }
+    }
+
+    pub(crate) fn batch_payload(&self, points: &[DataPoint]) -> String {
+        self.payload_encoder.encode_batch(points, self.value_format)
+    }
+
+    /// Value of the `Content-Type` header of the requests adding data.
+    pub(crate) fn payload_content_type(&self) -> &str {
+        self.payload_encoder.content_type()
+    }
+}
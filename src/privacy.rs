@@ -0,0 +1,52 @@
+//! Differential privacy for sensitive metrics.
+//!
+//! Requires the `privacy` feature.
+
+use rand::Rng;
+
+/// Adds Laplace noise to every value before it is sent, so aggregates derived from individual
+/// behaviour can be published without exposing exact per-user values.
+///
+/// The noise has scale `sensitivity / epsilon`: a smaller `epsilon` means more privacy, and
+/// noisier values.
+#[derive(Clone, Copy, Debug)]
+pub struct LaplaceNoise {
+    epsilon: f64,
+    sensitivity: f64,
+}
+
+impl LaplaceNoise {
+    /// Noise for a privacy budget of `epsilon`, for values that a single individual can change
+    /// by at most `sensitivity`.
+    ///
+    /// Panics if `epsilon` or `sensitivity` isn't a positive finite number.
+    pub fn new(epsilon: f64, sensitivity: f64) -> Self {
+        assert!(
+            epsilon.is_finite() && epsilon > 0.0,
+            "epsilon must be a positive number"
+        );
+        assert!(
+            sensitivity.is_finite() && sensitivity > 0.0,
+            "sensitivity must be a positive number"
+        );
+        Self {
+            epsilon,
+            sensitivity,
+        }
+    }
+
+    /// Returns `value` with some random noise added.
+    pub fn apply(&self, value: f64) -> f64 {
+        let scale = self.sensitivity / self.epsilon;
+        // Inverse of the Laplace cumulative distribution function, for u in (-0.5, 0.5); -0.5
+        // would give an infinite value.
+        let mut rng = rand::thread_rng();
+        let u: f64 = loop {
+            let u = rng.gen_range(-0.5..0.5);
+            if u > -0.5 {
+                break u;
+            }
+        };
+        value - scale * u.signum() * (1.0 - 2.0 * u.abs()).ln()
+    }
+}
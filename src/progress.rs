@@ -0,0 +1,95 @@
+use std::io::{self, Read};
+use std::time::{Duration, Instant};
+
+/// How far a long import has gone, given to its progress callback after every batch, e.g. to
+/// display a progress bar, see [`SyncClient::send_csv`](crate::SyncClient::send_csv) and
+/// [`BackfillPlan::run_with_progress`](crate::BackfillPlan::run_with_progress).
+///
+/// To receive it on another thread, send it to a channel from the callback:
+/// `move |progress| { let _ = sender.send(progress.clone()); }`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Progress {
+    /// Number of rows sent so far.
+    pub rows: u64,
+    /// Number of rows to send in total, if known.
+    pub total_rows: Option<u64>,
+    /// Number of bytes of input read so far, e.g. of the CSV file, or 0 when sending data
+    /// points.
+    pub bytes: u64,
+    /// Size of the input, if known.
+    pub total_bytes: Option<u64>,
+    /// Time since the start of the import.
+    pub elapsed: Duration,
+}
+
+impl Progress {
+    /// The part of the import done so far, between 0 and 1, from the rows if their total is
+    /// known, or else from the bytes.
+    pub fn fraction(&self) -> Option<f64> {
+        let (done, total) = match (self.total_rows, self.total_bytes) {
+            (Some(total), _) => (self.rows, total),
+            (None, Some(total)) => (self.bytes, total),
+            (None, None) => return None,
+        };
+        if total == 0 {
+            return Some(1.0);
+        }
+        Some((done as f64 / total as f64).min(1.0))
+    }
+
+    /// Estimated time left, at the average rate so far, if the total is known.
+    pub fn eta(&self) -> Option<Duration> {
+        let fraction = self.fraction()?;
+        if fraction <= 0.0 {
+            return None;
+        }
+        Some(self.elapsed.mul_f64((1.0 - fraction) / fraction))
+    }
+}
+
+/// Tracks the progress of an import.
+pub(crate) struct Tracker {
+    start: Instant,
+    total_rows: Option<u64>,
+    total_bytes: Option<u64>,
+}
+
+impl Tracker {
+    pub fn new(total_rows: Option<u64>, total_bytes: Option<u64>) -> Self {
+        Self {
+            start: Instant::now(),
+            total_rows,
+            total_bytes,
+        }
+    }
+
+    pub fn progress(&self, rows: u64, bytes: u64) -> Progress {
+        Progress {
+            rows,
+            total_rows: self.total_rows,
+            bytes,
+            total_bytes: self.total_bytes,
+            elapsed: self.start.elapsed(),
+        }
+    }
+}
+
+/// A reader counting the bytes read from it.
+pub(crate) struct CountingReader<R> {
+    inner: R,
+    pub count: u64,
+}
+
+impl<R> CountingReader<R> {
+    pub fn new(inner: R) -> Self {
+        Self { inner, count: 0 }
+    }
+}
+
+impl<R: Read> Read for CountingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.count += read as u64;
+        Ok(read)
+    }
+}
@@ -0,0 +1,265 @@
+//! Mirrors Prometheus metrics, e.g. from node_exporter, into Nextcloud Analytics timelines, once
+//! with [`SyncClient::scrape_prometheus`] or periodically with a [`Scraper`].
+//!
+//! Requires the `prometheus` feature.
+
+use core::fmt;
+use std::error::Error;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use chrono::{TimeZone, Utc};
+
+use crate::{AnalyticsError, SyncClient};
+
+/// A sample of the Prometheus text exposition format.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Sample {
+    /// Name of the metric.
+    pub name: String,
+    /// Labels of the sample, in their order of appearance.
+    pub labels: Vec<(String, String)>,
+    /// Value of the sample.
+    pub value: f64,
+    /// Timestamp of the sample in milliseconds since the Unix epoch, if any.
+    pub timestamp_ms: Option<i64>,
+}
+
+/// The default mapping of samples to timeline keys: the metric name, followed by its labels in
+/// braces if it has any, e.g. `node_load1` or `node_cpu_seconds_total{cpu=0,mode=idle}`.
+pub fn default_key(sample: &Sample) -> Option<String> {
+    if sample.labels.is_empty() {
+        return Some(sample.name.clone());
+    }
+
+    let labels = sample
+        .labels
+        .iter()
+        .map(|(name, value)| format!("{}={}", name, value))
+        .collect::<Vec<_>>()
+        .join(",");
+    Some(format!("{}{{{}}}", sample.name, labels))
+}
+
+/// Parses metrics in the Prometheus text exposition format.
+pub fn parse(text: &str) -> Result<Vec<Sample>, ParseError> {
+    text.lines()
+        .enumerate()
+        .filter(|(_, line)| {
+            let line = line.trim();
+            !line.is_empty() && !line.starts_with('#')
+        })
+        .map(|(index, line)| {
+            parse_line(line.trim()).ok_or_else(|| ParseError {
+                line: index + 1,
+                content: line.to_string(),
+            })
+        })
+        .collect()
+}
+
+fn parse_value(value: &str) -> Option<f64> {
+    match value {
+        "+Inf" | "Inf" => Some(f64::INFINITY),
+        "-Inf" => Some(f64::NEG_INFINITY),
+        "NaN" => Some(f64::NAN),
+        value => value.parse().ok(),
+    }
+}
+
+fn parse_line(line: &str) -> Option<Sample> {
+    let name_end = line
+        .find(|c: char| c == '{' || c.is_whitespace())
+        .unwrap_or(line.len());
+    let name = &line[..name_end];
+    if name.is_empty() {
+        return None;
+    }
+
+    let mut rest = &line[name_end..];
+    let mut labels = Vec::new();
+
+    if let Some(after_brace) = rest.strip_prefix('{') {
+        let mut chars = after_brace.char_indices();
+        loop {
+            // Label name, up to '=' or the closing brace.
+            let mut label = String::new();
+            let closed = loop {
+                let (i, c) = chars.next()?;
+                match c {
+                    '}' => break Some(i),
+                    '=' => break None,
+                    ',' if label.trim().is_empty() => {}
+                    c => label.push(c),
+                }
+            };
+            if let Some(i) = closed {
+                rest = &after_brace[i + 1..];
+                break;
+            }
+
+            // Quoted label value, with escapes.
+            if chars.next()?.1 != '"' {
+                return None;
+            }
+            let mut value = String::new();
+            loop {
+                match chars.next()?.1 {
+                    '"' => break,
+                    '\\' => match chars.next()?.1 {
+                        'n' => value.push('\n'),
+                        c => value.push(c),
+                    },
+                    c => value.push(c),
+                }
+            }
+            labels.push((label.trim().to_string(), value));
+        }
+    }
+
+    let mut parts = rest.split_whitespace();
+    let value = parse_value(parts.next()?)?;
+    let timestamp_ms = match parts.next() {
+        Some(ts) => Some(ts.parse().ok()?),
+        None => None,
+    };
+
+    Some(Sample {
+        name: name.to_string(),
+        labels,
+        value,
+        timestamp_ms,
+    })
+}
+
+impl SyncClient {
+    /// Pushes the samples of a Prometheus text exposition as timeline data.
+    ///
+    /// `key` maps each sample to the key it's sent as, or `None` to skip it; see
+    /// [`default_key`]. Samples without a timestamp are sent for the current time. Non-finite
+    /// values can't be stored by Analytics and are skipped.
+    ///
+    /// Returns the number of samples sent, not counting those dropped by the key filter.
+    pub fn push_prometheus<F>(&self, text: &str, key: F) -> Result<usize, AnalyticsError>
+    where
+        F: Fn(&Sample) -> Option<String>,
+    {
+        let mut sent = 0;
+        for sample in parse(text)? {
+            if !sample.value.is_finite() {
+                continue;
+            }
+            let key = match key(&sample) {
+                Some(key) => key,
+                None => continue,
+            };
+
+            let time = sample
+                .timestamp_ms
+                .and_then(|ms| Utc.timestamp_millis_opt(ms).single());
+            let receipt = match time {
+                Some(time) => self.send_timeline_data(key, time, sample.value)?,
+                None => self.send_timeline_now_data(key, sample.value)?,
+            };
+
+            if receipt.was_sent() {
+                sent += 1;
+            }
+        }
+        Ok(sent)
+    }
+
+    /// Scrapes the Prometheus endpoint at `url`, e.g. `http://localhost:9100/metrics`, and pushes
+    /// its samples like [`SyncClient::push_prometheus`].
+    ///
+    /// The Nextcloud credentials aren't sent to the scraped endpoint.
+    pub fn scrape_prometheus<F>(&self, url: &str, key: F) -> Result<usize, AnalyticsError>
+    where
+        F: Fn(&Sample) -> Option<String>,
+    {
+        let text = self.client.get(url).send()?.error_for_status()?.text()?;
+        self.push_prometheus(&text, key)
+    }
+}
+
+/// Scrapes a Prometheus endpoint on an interval, pushing its samples as timeline data, e.g. to
+/// feed an existing exporter into Analytics without glue code.
+///
+/// The scraper runs on its own thread, and stops when dropped. Failed scrapes are skipped;
+/// failed sends are reported through the client's callbacks, see [`SyncClient::on_error`].
+pub struct Scraper {
+    stop: Option<mpsc::Sender<()>>,
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+impl Scraper {
+    /// Starts scraping `url` every `interval`, the first time right away, mapping samples to
+    /// keys with `key`, like [`SyncClient::scrape_prometheus`].
+    pub fn start<S, F>(client: Arc<SyncClient>, url: S, interval: Duration, key: F) -> Self
+    where
+        S: Into<String>,
+        F: Fn(&Sample) -> Option<String> + Send + 'static,
+    {
+        let url = url.into();
+        let (stop, stopped) = mpsc::channel();
+
+        let thread = thread::spawn(move || loop {
+            let _ = client.scrape_prometheus(&url, &key);
+
+            match stopped.recv_timeout(interval) {
+                Err(RecvTimeoutError::Timeout) => {}
+                Ok(()) | Err(RecvTimeoutError::Disconnected) => break,
+            }
+        });
+
+        Self {
+            stop: Some(stop),
+            thread: Some(thread),
+        }
+    }
+
+    /// Stops scraping, waiting for an ongoing scrape to finish. Same as dropping the scraper.
+    pub fn stop(self) {}
+}
+
+impl Drop for Scraper {
+    fn drop(&mut self) {
+        // Dropping the sender wakes the thread up.
+        self.stop.take();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// A line of a Prometheus text exposition couldn't be parsed.
+pub struct ParseError {
+    line: usize,
+    content: String,
+}
+
+impl fmt::Debug for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "invalid Prometheus sample at line {}: {}",
+            self.line, self.content
+        )
+    }
+}
+
+impl Error for ParseError {}
+
+impl From<ParseError> for AnalyticsError {
+    fn from(err: ParseError) -> Self {
+        AnalyticsError::local(err)
+    }
+}
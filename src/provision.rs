@@ -0,0 +1,220 @@
+//! Creating the dataset and report of a collection which doesn't exist yet, on its first send.
+
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use crate::{lockfile, AnalyticsError, ApiVersion, ErrorClass, Receipt, SyncClient};
+
+/// Collections provisioned on a host, by name.
+type Mapping = BTreeMap<String, u32>;
+
+/// How to create the dataset and report of a collection which doesn't exist, see
+/// [`SyncClient::with_auto_provision`].
+#[derive(Clone, Debug)]
+pub struct ProvisionTemplate {
+    name: String,
+    dimension1: String,
+    dimension2: String,
+    value: String,
+    chart: String,
+    mapping_file: Option<PathBuf>,
+}
+
+impl ProvisionTemplate {
+    /// A template creating a dataset and a report named `name`, with a line chart of
+    /// dimensions named `Key`, `Time` and `Value`.
+    pub fn new<S: Into<String>>(name: S) -> Self {
+        Self {
+            name: name.into(),
+            dimension1: "Key".to_string(),
+            dimension2: "Time".to_string(),
+            value: "Value".to_string(),
+            chart: "line".to_string(),
+            mapping_file: None,
+        }
+    }
+
+    /// Names of the two dimensions and of the value, as displayed in the report.
+    pub fn dimensions<S: Into<String>>(mut self, dimension1: S, dimension2: S, value: S) -> Self {
+        self.dimension1 = dimension1.into();
+        self.dimension2 = dimension2.into();
+        self.value = value.into();
+        self
+    }
+
+    /// Type of the chart of the report, e.g. `line`, `column` or `doughnut`.
+    pub fn chart<S: Into<String>>(mut self, chart: S) -> Self {
+        self.chart = chart.into();
+        self
+    }
+
+    /// Keep the collections provisioned in the JSON file at `path`, by name, e.g.
+    /// `{"Sensors": 12}`, so that the processes of a host provisioning the same name share
+    /// a single collection rather than each creating one, and find it again after restarting.
+    ///
+    /// The file is created if needed. Its access is serialized with an advisory lock on a file
+    /// next to it, with the `.lock` extension, held while the dataset and report are created
+    /// however long it takes, and released by the OS if the process crashes. Use one file per
+    /// Nextcloud server and user.
+    pub fn mapping_file<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.mapping_file = Some(path.into());
+        self
+    }
+
+    /// Name of the dataset and report.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+/// The template of a client and the collection provisioned with it, shared by its clones.
+#[derive(Clone)]
+pub(crate) struct Provisioning {
+    template: ProvisionTemplate,
+    state: Arc<Mutex<State>>,
+}
+
+#[derive(Default)]
+struct State {
+    collection: Option<u32>,
+    /// Whether the mapping file, if any, was read.
+    loaded: bool,
+}
+
+impl Provisioning {
+    /// The provisioned collection, looked up in the mapping file the first time if there's
+    /// one.
+    fn collection(&self) -> Option<u32> {
+        let mut state = self.state.lock().unwrap();
+        if !state.loaded {
+            state.loaded = true;
+            if let Some(path) = &self.template.mapping_file {
+                state.collection = read_mapping(path)
+                    .ok()
+                    .and_then(|mapping| mapping.get(&self.template.name).copied());
+            }
+        }
+        state.collection
+    }
+}
+
+/// The mapping stored at `path`, empty if the file doesn't exist yet.
+fn read_mapping(path: &Path) -> io::Result<Mapping> {
+    match fs::read_to_string(path) {
+        Ok(json) => serde_json::from_str(&json)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err)),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(Mapping::new()),
+        Err(err) => Err(err),
+    }
+}
+
+/// Writes a new file then renames it, so that readers never see a truncated mapping.
+fn write_mapping(path: &Path, mapping: &Mapping) -> io::Result<()> {
+    let tmp = path.with_extension("tmp");
+    fs::write(&tmp, serde_json::to_string_pretty(mapping)?)?;
+    fs::rename(&tmp, path)
+}
+
+impl SyncClient {
+    /// When a send fails because the client's collection doesn't exist, create a dataset and a
+    /// report built on it from `template`, and send again to them, so that new metrics appear
+    /// without creating their report in the web interface first. Disabled by default.
+    ///
+    /// The next sends of the client, and of its clones, then go to the provisioned collection:
+    /// the identifier of the report with the 1.0 API, and of the dataset with the newer ones,
+    /// see [`SyncClient::provisioned_collection`]. Other calls, e.g. reading data, still use the
+    /// collection the client was created with.
+    ///
+    /// Creating datasets and reports uses the Analytics app's own routes.
+    pub fn with_auto_provision(mut self, template: ProvisionTemplate) -> Self {
+        self.provisioning = Some(Provisioning {
+            template,
+            state: Arc::default(),
+        });
+        self
+    }
+
+    /// The collection created in place of the client's one, if any, see
+    /// [`SyncClient::with_auto_provision`].
+    pub fn provisioned_collection(&self) -> Option<u32> {
+        self.provisioning.as_ref()?.collection()
+    }
+
+    /// Sends prepared data with `send` to `collection`, or to the collection provisioned in its
+    /// place, provisioning it if the client's collection doesn't exist; returns the collection
+    /// it was sent to, with the result.
+    pub(crate) fn send_provisioned<F>(
+        &self,
+        collection: u32,
+        send: F,
+    ) -> (u32, Result<Receipt, AnalyticsError>)
+    where
+        F: Fn(u32) -> Result<Receipt, AnalyticsError>,
+    {
+        let provisioning = match &self.provisioning {
+            Some(provisioning) if collection == self.collection => provisioning,
+            _ => return (collection, send(collection)),
+        };
+        if let Some(provisioned) = provisioning.collection() {
+            return (provisioned, send(provisioned));
+        }
+
+        let result = send(collection);
+        match &result {
+            Err(err) if err.class() == ErrorClass::NotFound => {}
+            _ => return (collection, result),
+        }
+        match self.provision(provisioning) {
+            Ok(provisioned) => (provisioned, send(provisioned)),
+            Err(err) => (collection, Err(err)),
+        }
+    }
+
+    /// Creates the dataset and report of the template, unless another send did meanwhile, or
+    /// another process sharing the mapping file.
+    fn provision(&self, provisioning: &Provisioning) -> Result<u32, AnalyticsError> {
+        let mut state = provisioning.state.lock().unwrap();
+        if let Some(collection) = state.collection {
+            return Ok(collection);
+        }
+
+        let template = &provisioning.template;
+        let provisioned = match &template.mapping_file {
+            Some(path) => lockfile::with_lock(path, || -> Result<u32, AnalyticsError> {
+                let mut mapping = read_mapping(path)?;
+                if let Some(&provisioned) = mapping.get(&template.name) {
+                    return Ok(provisioned);
+                }
+                let provisioned = self.create_collection(template)?;
+                mapping.insert(template.name.clone(), provisioned);
+                write_mapping(path, &mapping)?;
+                Ok(provisioned)
+            })?,
+            None => self.create_collection(template)?,
+        };
+        state.collection = Some(provisioned);
+        Ok(provisioned)
+    }
+
+    /// Creates the dataset and report of `template`, and returns the collection to send to.
+    fn create_collection(&self, template: &ProvisionTemplate) -> Result<u32, AnalyticsError> {
+        let dataset = self.create_dataset(
+            &template.name,
+            &template.dimension1,
+            &template.dimension2,
+            &template.value,
+        )?;
+        let report = self.create_report(&template.name, dataset, &template.chart)?;
+        let provisioned = match self.api_version {
+            ApiVersion::V1 => report,
+            ApiVersion::V2 | ApiVersion::V3 => dataset,
+        };
+        u32::try_from(provisioned).map_err(|_| {
+            AnalyticsError::Parse(format!("unexpected collection identifier {}", provisioned))
+        })
+    }
+}
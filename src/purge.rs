@@ -0,0 +1,147 @@
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::progress::Tracker;
+use crate::{AnalyticsError, BulkSummary, Progress, SyncClient};
+
+/// A schedule for deleting many rows, e.g. a retention purge of tens of thousands of them, in
+/// bounded batches with a pause between them, so that no request runs into the server's PHP
+/// execution time limit and the Nextcloud instance stays responsive.
+#[derive(Clone, Debug)]
+pub struct PurgePlan {
+    batch_size: usize,
+    pause: Duration,
+}
+
+impl Default for PurgePlan {
+    fn default() -> Self {
+        Self {
+            batch_size: 500,
+            pause: Duration::from_secs(1),
+        }
+    }
+}
+
+impl PurgePlan {
+    /// A plan deleting batches of 500 rows, pausing for a second between them.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Maximum number of rows deleted per request.
+    pub fn batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    /// How long to wait between two requests.
+    pub fn pause(mut self, pause: Duration) -> Self {
+        self.pause = pause;
+        self
+    }
+
+    /// Deletes the data of the client's collection matching each of the
+    /// `(dimension1, dimension2)` rows, like [`SyncClient::delete_data`], a batch at a time.
+    ///
+    /// A batch failing with a transient error, e.g. a timeout, is retried after the pause,
+    /// with batches half as large from then on; the error of a single row is returned. This
+    /// blocks until all the rows are deleted, and stops at the first failure; the batches
+    /// before it were deleted.
+    ///
+    /// Returns the number of rows deleted.
+    pub fn run<I, D1, D2>(&self, client: &SyncClient, rows: I) -> Result<usize, AnalyticsError>
+    where
+        I: IntoIterator<Item = (D1, D2)>,
+        D1: AsRef<str>,
+        D2: AsRef<str>,
+    {
+        self.run_with_progress(client, rows, |_| {})
+    }
+
+    /// Like [`PurgePlan::run`], calling `progress` with the rows deleted so far after every
+    /// batch. Their total, and thus an [ETA](Progress::eta), is known if the iterator of the
+    /// rows knows its exact length, e.g. for a `Vec`.
+    pub fn run_with_progress<I, D1, D2, F>(
+        &self,
+        client: &SyncClient,
+        rows: I,
+        progress: F,
+    ) -> Result<usize, AnalyticsError>
+    where
+        I: IntoIterator<Item = (D1, D2)>,
+        D1: AsRef<str>,
+        D2: AsRef<str>,
+        F: FnMut(&Progress),
+    {
+        let start = Instant::now();
+        let result = self.delete_all(client, rows.into_iter(), progress);
+
+        let mut summary = BulkSummary::new("purge");
+        match &result {
+            Ok(deleted) => summary.deleted = *deleted as u64,
+            Err((deleted, _)) => {
+                summary.deleted = *deleted as u64;
+                summary.failures = 1;
+            }
+        }
+        summary.duration = start.elapsed();
+        client.report_summary(&summary);
+
+        result.map_err(|(_, err)| err)
+    }
+
+    /// Deletes the rows, returning the number of rows deleted so far along with the error.
+    fn delete_all<I, D1, D2, F>(
+        &self,
+        client: &SyncClient,
+        rows: I,
+        mut progress: F,
+    ) -> Result<usize, (usize, AnalyticsError)>
+    where
+        I: Iterator<Item = (D1, D2)>,
+        D1: AsRef<str>,
+        D2: AsRef<str>,
+        F: FnMut(&Progress),
+    {
+        if let Err(err) = client.check_writable() {
+            return Err((0, err));
+        }
+        let total = match rows.size_hint() {
+            (lower, Some(upper)) if lower == upper => Some(upper as u64),
+            _ => None,
+        };
+        let tracker = Tracker::new(total, None);
+        let mut rows = rows.peekable();
+        let mut batch_size = self.batch_size;
+        let mut batch: Vec<(D1, D2)> = Vec::with_capacity(batch_size);
+        let mut deleted = 0;
+
+        while rows.peek().is_some() || !batch.is_empty() {
+            if deleted > 0 || batch.len() > batch_size {
+                thread::sleep(self.pause);
+            }
+            let missing = batch_size.saturating_sub(batch.len());
+            batch.extend(rows.by_ref().take(missing));
+
+            let len = batch.len().min(batch_size);
+            let request: Vec<_> = batch[..len]
+                .iter()
+                .map(|(dimension1, dimension2)| (dimension1.as_ref(), dimension2.as_ref()))
+                .collect();
+            match client.delete_rows(&request, client.collection) {
+                Ok(()) => {}
+                // Smaller requests may finish in time.
+                Err(err) if err.is_transient() && len > 1 => {
+                    batch_size = (len / 2).max(1);
+                    continue;
+                }
+                Err(err) => return Err((deleted, err)),
+            }
+            batch.drain(..len);
+            deleted += len;
+            progress(&tracker.progress(deleted as u64, 0));
+        }
+
+        Ok(deleted)
+    }
+}
@@ -0,0 +1,85 @@
+use std::convert::TryFrom;
+
+use crate::datasets::Dataset;
+use crate::{AnalyticsError, ApiVersion, ErrorClass, SyncClient};
+
+/// Connects to a Nextcloud instance and returns a client sending data to one of the user's
+/// datasets, chosen by name or by identifier, in one call:
+///
+/// ```no_run
+/// let client = nextcloud_analytics_rs::quickstart(
+///     "https://example.com/nextcloud",
+///     "myself",
+///     "hunter2",
+///     "Weather station",
+/// )?;
+/// client.send_data("temperature", "kitchen", 21.5)?;
+/// # Ok::<(), nextcloud_analytics_rs::AnalyticsError>(())
+/// ```
+///
+/// The newest API version the server supports is detected by listing the datasets, falling
+/// back from 3.0 to 2.0. If no dataset matches `dataset`, the error lists the available ones.
+pub fn quickstart(
+    nextcloud_url: &str,
+    user: &str,
+    passwd: &str,
+    dataset: &str,
+) -> Result<SyncClient, AnalyticsError> {
+    let (api_version, datasets) = probe(nextcloud_url, user, passwd)?;
+
+    let chosen = datasets
+        .iter()
+        .find(|candidate| candidate.name == dataset)
+        .or_else(|| {
+            let id = dataset.parse::<u64>().ok()?;
+            datasets.iter().find(|candidate| candidate.id == id)
+        })
+        .ok_or_else(|| {
+            let available = datasets
+                .iter()
+                .map(|candidate| format!("{:?} ({})", candidate.name, candidate.id))
+                .collect::<Vec<_>>();
+            AnalyticsError::local(format!(
+                "no dataset named {:?}; available datasets: {}",
+                dataset,
+                if available.is_empty() {
+                    "none".to_string()
+                } else {
+                    available.join(", ")
+                }
+            ))
+        })?;
+
+    let collection = u32::try_from(chosen.id).map_err(|_| {
+        AnalyticsError::local(format!("dataset identifier out of range: {}", chosen.id))
+    })?;
+    SyncClient::builder(nextcloud_url, collection, user, passwd)
+        .api_version(api_version)
+        .build()
+}
+
+/// Finds the newest API version the server answers to, with the datasets it lists.
+fn probe(
+    nextcloud_url: &str,
+    user: &str,
+    passwd: &str,
+) -> Result<(ApiVersion, Vec<Dataset>), AnalyticsError> {
+    let mut not_found = None;
+    for &api_version in &[ApiVersion::V3, ApiVersion::V2] {
+        let client = SyncClient::builder(nextcloud_url, 0, user, passwd)
+            .api_version(api_version)
+            .build()?;
+        match client.list_datasets() {
+            Ok(datasets) => return Ok((api_version, datasets)),
+            Err(err) if err.class() == ErrorClass::NotFound => not_found = Some(err),
+            Err(err) => return Err(err),
+        }
+    }
+
+    let err = not_found.expect("at least one API version was probed");
+    let message = format!(
+        "the Analytics API wasn't found, is the app installed and enabled? {}",
+        err
+    );
+    Err(err.with_message(message))
+}
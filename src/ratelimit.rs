@@ -0,0 +1,162 @@
+#[cfg(feature = "blocking")]
+use std::fs;
+#[cfg(feature = "blocking")]
+use std::io;
+#[cfg(feature = "blocking")]
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+#[cfg(feature = "blocking")]
+use std::thread;
+use std::time::{Duration, Instant};
+#[cfg(feature = "blocking")]
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[cfg(feature = "blocking")]
+use crate::{lockfile, AnalyticsError, SyncClient};
+
+/// Refills a bucket holding `tokens` for `elapsed` seconds, and takes a token from it if there's
+/// one, returning the new number of tokens, and how long to wait for the next one otherwise.
+fn take(tokens: f64, elapsed: f64, rate: f64, burst: f64) -> (f64, Option<Duration>) {
+    let tokens = (tokens + elapsed.max(0.0) * rate).min(burst);
+    if tokens >= 1.0 {
+        (tokens - 1.0, None)
+    } else {
+        (tokens, Some(Duration::from_secs_f64((1.0 - tokens) / rate)))
+    }
+}
+
+/// A token bucket limiting the requests of a client, e.g. so that long backfills don't trip
+/// Nextcloud's brute-force protection or overload small instances, see
+/// [`SyncClient::with_rate_limit`].
+///
+/// The bucket holds up to `burst` tokens, refilled at `rate` tokens per second; each request,
+/// including the ones of batches, background senders and retries, takes one, waiting for it if
+/// the bucket is empty. Clones share the same bucket, so that several clients can share a quota.
+#[derive(Clone, Debug)]
+pub struct RateLimit {
+    rate: f64,
+    burst: f64,
+    bucket: Arc<Mutex<(f64, Instant)>>,
+}
+
+impl RateLimit {
+    /// A limit of `rate` requests per second, with bursts of up to `burst` requests.
+    pub fn new(rate: f64, burst: u32) -> Self {
+        let burst = f64::from(burst.max(1));
+        Self {
+            rate: rate.max(f64::MIN_POSITIVE),
+            burst,
+            bucket: Arc::new(Mutex::new((burst, Instant::now()))),
+        }
+    }
+
+    /// Number of requests per second of the limit.
+    pub fn rate(&self) -> f64 {
+        self.rate
+    }
+
+    /// Maximum number of requests of a burst.
+    pub fn burst(&self) -> u32 {
+        self.burst as u32
+    }
+
+    /// Takes a token if there's one, or returns how long to wait for the next one.
+    pub(crate) fn try_acquire(&self) -> Option<Duration> {
+        let mut bucket = self.bucket.lock().unwrap();
+        let now = Instant::now();
+        let elapsed = now.duration_since(bucket.1).as_secs_f64();
+        let (tokens, wait) = take(bucket.0, elapsed, self.rate, self.burst);
+        *bucket = (tokens, now);
+        wait
+    }
+
+    /// Takes a token from the bucket, waiting until one is available.
+    #[cfg(feature = "blocking")]
+    pub(crate) fn acquire(&self) {
+        while let Some(wait) = self.try_acquire() {
+            thread::sleep(wait);
+        }
+    }
+}
+
+/// A token bucket stored in a file, so that several processes of the same host share a request
+/// quota, see [`SyncClient::with_shared_rate_limit`].
+///
+/// The bucket holds up to `burst` tokens, refilled at `rate` tokens per second; each request
+/// takes one, waiting for it if the bucket is empty. Access to the file is serialized with a
+/// lock file next to it, with the `.lock` extension.
+#[cfg(feature = "blocking")]
+#[derive(Clone, Debug)]
+pub struct SharedRateLimit {
+    path: PathBuf,
+    rate: f64,
+    burst: f64,
+}
+
+#[cfg(feature = "blocking")]
+impl SharedRateLimit {
+    /// A limit of `rate` requests per second, with bursts of up to `burst` requests, shared by
+    /// all the processes using the same `path`.
+    pub fn new<P: Into<PathBuf>>(path: P, rate: f64, burst: u32) -> Self {
+        Self {
+            path: path.into(),
+            rate: rate.max(f64::MIN_POSITIVE),
+            burst: f64::from(burst.max(1)),
+        }
+    }
+
+    /// Takes a token from the bucket, waiting until one is available.
+    #[cfg(feature = "blocking")]
+    pub(crate) fn acquire(&self) -> Result<(), AnalyticsError> {
+        loop {
+            match lockfile::with_lock(&self.path, || self.take_token())? {
+                None => return Ok(()),
+                Some(wait) => thread::sleep(wait),
+            }
+        }
+    }
+
+    /// Takes a token if there's one, or returns how long to wait for the next one.
+    fn take_token(&self) -> io::Result<Option<Duration>> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs_f64();
+
+        // The state is the number of tokens and when it was computed; a missing or unreadable
+        // file is a full bucket.
+        let (tokens, at) = match fs::read_to_string(&self.path) {
+            Ok(state) => {
+                let mut parts = state.split_whitespace().map(str::parse::<f64>);
+                match (parts.next(), parts.next()) {
+                    (Some(Ok(tokens)), Some(Ok(at))) => (tokens, at),
+                    _ => (self.burst, now),
+                }
+            }
+            Err(err) if err.kind() == io::ErrorKind::NotFound => (self.burst, now),
+            Err(err) => return Err(err),
+        };
+
+        let (tokens, wait) = take(tokens, now - at, self.rate, self.burst);
+
+        fs::write(&self.path, format!("{} {}", tokens, now))?;
+        Ok(wait)
+    }
+}
+
+#[cfg(feature = "blocking")]
+impl SyncClient {
+    /// Wait for a token of the given bucket before every request, to limit the request rate of
+    /// this client, and of the other ones sharing the bucket.
+    pub fn with_rate_limit(mut self, limit: RateLimit) -> Self {
+        self.rate_limit = Some(limit);
+        self
+    }
+
+    /// Wait for a token of the given shared bucket before every request, so that all the
+    /// processes of a host using this crate respect a common quota on the server.
+    pub fn with_shared_rate_limit(mut self, limit: SharedRateLimit) -> Self {
+        self.shared_rate_limit = Some(limit);
+        self
+    }
+}
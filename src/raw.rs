@@ -0,0 +1,91 @@
+use reqwest as http;
+
+use crate::{AnalyticsError, Response, SyncClient};
+
+/// The body of a request sent with [`SyncClient::raw_post`], with its content type.
+///
+/// Text is sent as JSON, the format of the Analytics endpoints; bytes are sent as
+/// `application/octet-stream`.
+pub trait Payload {
+    /// Value of the `Content-Type` header of the request.
+    fn content_type(&self) -> &str {
+        "application/json"
+    }
+
+    /// The body of the request.
+    fn into_body(self) -> Vec<u8>;
+}
+
+impl Payload for serde_json::Value {
+    fn into_body(self) -> Vec<u8> {
+        self.to_string().into_bytes()
+    }
+}
+
+impl Payload for String {
+    fn into_body(self) -> Vec<u8> {
+        self.into_bytes()
+    }
+}
+
+impl Payload for &str {
+    fn into_body(self) -> Vec<u8> {
+        self.as_bytes().to_vec()
+    }
+}
+
+impl Payload for Vec<u8> {
+    fn content_type(&self) -> &str {
+        "application/octet-stream"
+    }
+
+    fn into_body(self) -> Vec<u8> {
+        self
+    }
+}
+
+impl SyncClient {
+    /// Posts `body` to `path`, relative to the Nextcloud base URL, e.g.
+    /// `apps/analytics/api/3.0/...`, to call an endpoint the crate doesn't wrap yet with the
+    /// client's credentials, interceptors, rate limits and retry policy.
+    ///
+    /// The request asks for JSON, as an OCS API request. Fails like the other calls if the
+    /// response doesn't have a success status; the response is returned as it is otherwise,
+    /// even if the Analytics API reports an error in it.
+    pub fn raw_post<P: Payload>(&self, path: &str, body: P) -> Result<Response, AnalyticsError> {
+        self.raw_request(http::Method::POST, path, Some(body))
+    }
+
+    /// Sends a request with any method to `path`, relative to the Nextcloud base URL, with an
+    /// optional body, like [`SyncClient::raw_post`].
+    pub fn raw_request<P: Payload>(
+        &self,
+        method: http::Method,
+        path: &str,
+        body: Option<P>,
+    ) -> Result<Response, AnalyticsError> {
+        let mut req = self
+            .client
+            .request(
+                method,
+                &(self.base_url.clone() + path.trim_start_matches('/')),
+            )
+            .header("OCS-APIRequest", "true")
+            .header(http::header::ACCEPT, "application/json");
+        if let Some(body) = body {
+            req = req
+                .header(http::header::CONTENT_TYPE, body.content_type())
+                .body(body.into_body());
+        }
+
+        let resp = self.execute(req)?;
+        if !resp.status.is_success() {
+            return Err(self.redactions.error(AnalyticsError::from_response(
+                resp.status,
+                &resp.headers,
+                &resp.body,
+            )));
+        }
+        Ok(resp)
+    }
+}
@@ -0,0 +1,136 @@
+#[cfg(feature = "chrono")]
+use chrono::{DateTime, NaiveDateTime, TimeZone, Utc};
+use reqwest as http;
+use serde_json::{json, Value};
+
+use crate::encoding::percent_encode;
+use crate::{parse_number, AnalyticsError, DataPoint, NumberFormat, SyncClient};
+
+impl SyncClient {
+    /// Reads back the data of the report with the same index as the client's collection, e.g. to
+    /// check on another machine what a device pushed.
+    ///
+    /// Values are expected with a decimal point; see [`SyncClient::get_report_data`] otherwise.
+    pub fn get_data(&self) -> Result<Vec<DataPoint>, AnalyticsError> {
+        self.get_report_data(self.collection, NumberFormat::default())
+    }
+
+    /// Reads back the data of the given report, parsing values formatted according to `format`,
+    /// which depends on the report's settings.
+    ///
+    /// This uses the 3.0 API, the only one able to read data, whatever the version the client
+    /// has been configured with.
+    pub fn get_report_data(
+        &self,
+        report: u32,
+        format: NumberFormat,
+    ) -> Result<Vec<DataPoint>, AnalyticsError> {
+        self.read_rows(report, format, None)
+    }
+
+    /// Returns the most recent time and value stored for `key` in the client's collection, if
+    /// any, e.g. for a controller to act on the last reading.
+    ///
+    /// The server is asked to only return the rows of `key`, which old Analytics versions
+    /// ignore; the whole data is then filtered on the client side. The second dimension must be
+    /// a date, in the timestamp format of the client, in RFC 2822, in RFC 3339 or as
+    /// `YYYY-MM-DD hh:mm:ss`, in UTC.
+    ///
+    /// Requires the `chrono` feature.
+    #[cfg(feature = "chrono")]
+    pub fn latest(&self, key: &str) -> Result<Option<(DateTime<Utc>, f64)>, AnalyticsError> {
+        let rows = self.read_key_rows(self.collection, key)?;
+
+        Ok(rows
+            .into_iter()
+            .filter_map(|row| {
+                let time = self
+                    .parse_time(&row.dimension2)
+                    .or_else(|| parse_time(&row.dimension2))?;
+                Some((time, row.dimension3))
+            })
+            .max_by_key(|&(time, _)| time))
+    }
+
+    /// Reads back the rows of `key` in the given report, asking the server to only return them,
+    /// and filtering them on the client side for old Analytics versions ignoring the filter.
+    pub(crate) fn read_key_rows(
+        &self,
+        report: u32,
+        key: &str,
+    ) -> Result<Vec<DataPoint>, AnalyticsError> {
+        let filter = json!({
+            "filter": { "dimension1": { "option": "EQ", "value": self.encodings.encode_key(key) } },
+        });
+        let rows = self.read_rows(report, NumberFormat::default(), Some(&filter))?;
+        Ok(rows
+            .into_iter()
+            .filter(|row| row.dimension1 == key)
+            .collect())
+    }
+
+    fn read_rows(
+        &self,
+        report: u32,
+        format: NumberFormat,
+        filter: Option<&Value>,
+    ) -> Result<Vec<DataPoint>, AnalyticsError> {
+        let mut path = format!("apps/analytics/api/3.0/data/{}", report);
+        if let Some(filter) = filter {
+            path += "?filteroptions=";
+            path += &percent_encode(&filter.to_string());
+        }
+        let resp = self.request_json(http::Method::GET, &path, None)?;
+
+        let writer = self.writer.as_ref().map(|writer| writer.tag());
+        resp["data"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .map(|row| {
+                let mut point = parse_row(row, format)?;
+                self.encodings.decode(&mut point, writer);
+                Ok(point)
+            })
+            .collect()
+    }
+}
+
+/// Parses the dates of timeline rows, as sent by this crate or reformatted by the server.
+#[cfg(feature = "chrono")]
+fn parse_time(text: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc2822(text)
+        .or_else(|_| DateTime::parse_from_rfc3339(text))
+        .map(|time| time.with_timezone(&Utc))
+        .ok()
+        .or_else(|| {
+            NaiveDateTime::parse_from_str(text, "%Y-%m-%d %H:%M:%S")
+                .ok()
+                .map(|time| Utc.from_utc_datetime(&time))
+        })
+}
+
+/// Parses a row of the read API, either an array of the three dimensions or an object.
+fn parse_row(row: &Value, format: NumberFormat) -> Result<DataPoint, AnalyticsError> {
+    let (dimension1, dimension2, value) = if row.is_array() {
+        (&row[0], &row[1], &row[2])
+    } else {
+        (&row["dimension1"], &row["dimension2"], &row["value"])
+    };
+
+    let value = value
+        .as_f64()
+        .or_else(|| value.as_str().and_then(|s| parse_number(s, format)))
+        .ok_or_else(|| AnalyticsError::Parse(format!("invalid data row: {}", row)))?;
+
+    Ok(DataPoint::new(text(dimension1), text(dimension2), value))
+}
+
+/// Dimensions come as strings, or as numbers for numeric categories.
+fn text(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(s) => s.clone(),
+        value => value.to_string(),
+    }
+}
@@ -0,0 +1,113 @@
+use std::time::SystemTime;
+
+/// What happened to a data point handed to a send method.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Outcome {
+    /// The server accepted the data point, with what it reported about the rows.
+    Sent(SendReport),
+    /// The data point was dropped by the client's key filter, and not sent.
+    Filtered,
+    /// The data point arrived too soon after the previous one for its key: it wasn't sent, but
+    /// its value will be combined into the next one.
+    Coalesced,
+    /// The server couldn't be reached: the data point was queued to the spool of a
+    /// [`BufferedClient`](crate::BufferedClient), to be sent later.
+    Spooled,
+    /// The client is in [dry-run mode](crate::SyncClient::with_dry_run): the data point wasn't
+    /// sent, and this is the JSON payload that would have been.
+    DryRun(String),
+    /// The same data point was sent recently, see
+    /// [`SyncClient::with_dedupe`](crate::SyncClient::with_dedupe): it wasn't sent again.
+    Duplicate,
+    /// The server is in maintenance mode: the data point was queued in memory, to be sent
+    /// with the next sends once it's over, see
+    /// [`SyncClientBuilder::queue_during_maintenance`](crate::SyncClientBuilder::queue_during_maintenance).
+    Queued,
+}
+
+/// What the server reported about the rows of a send, as far as its Analytics version tells.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SendReport {
+    /// Number of rows sent in the request.
+    pub rows: usize,
+    /// Message of the server, e.g. `"Data update successful"`.
+    pub message: Option<String>,
+    /// Number of rows added to the dataset.
+    pub inserted: Option<u64>,
+    /// Number of rows replacing existing ones, with the same dimensions.
+    pub updated: Option<u64>,
+    /// Number of rows the server couldn't store.
+    pub errors: Option<u64>,
+    /// Validation messages about the data, e.g. a value that isn't a number.
+    pub validation: Option<String>,
+}
+
+impl SendReport {
+    /// Number of rows that were sent, but neither inserted nor updated, if the server reported
+    /// its counts; a non-zero result means that some rows were silently skipped.
+    pub fn skipped(&self) -> Option<u64> {
+        let stored = self.inserted? + self.updated?;
+        Some((self.rows as u64).saturating_sub(stored))
+    }
+
+    /// The report of two requests sending parts of the same data; the counts are only known if
+    /// both reported them.
+    pub(crate) fn merge(self, other: SendReport) -> SendReport {
+        let sum = |a: Option<u64>, b: Option<u64>| Some(a? + b?);
+        SendReport {
+            rows: self.rows + other.rows,
+            message: other.message.or(self.message),
+            inserted: sum(self.inserted, other.inserted),
+            updated: sum(self.updated, other.updated),
+            errors: sum(self.errors, other.errors),
+            validation: match (self.validation, other.validation) {
+                (Some(a), Some(b)) => Some(a + "\n" + &b),
+                (a, b) => a.or(b),
+            },
+        }
+    }
+}
+
+/// Proof of a successful send, to record or correlate sends later.
+#[must_use = "a receipt records what happened to the data point"]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Receipt {
+    /// When the send completed.
+    pub timestamp: SystemTime,
+    /// Hash of the JSON payload that was sent (FNV-1a, stable across runs and platforms).
+    pub payload_hash: u64,
+    /// What happened to the data point.
+    pub outcome: Outcome,
+}
+
+impl Receipt {
+    pub(crate) fn new(payload: &str, outcome: Outcome) -> Self {
+        Self {
+            timestamp: SystemTime::now(),
+            payload_hash: fnv1a(payload.as_bytes()),
+            outcome,
+        }
+    }
+
+    /// Whether the data point was actually sent to the server.
+    pub fn was_sent(&self) -> bool {
+        matches!(self.outcome, Outcome::Sent(_))
+    }
+
+    /// What the server reported about the rows, if the data point was sent.
+    pub fn report(&self) -> Option<&SendReport> {
+        match &self.outcome {
+            Outcome::Sent(report) => Some(report),
+            _ => None,
+        }
+    }
+}
+
+fn fnv1a(bytes: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for &byte in bytes {
+        hash ^= u64::from(byte);
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    hash
+}
@@ -0,0 +1,43 @@
+use crate::AnalyticsError;
+
+/// What matches of the redaction patterns are replaced with.
+#[cfg(feature = "regex")]
+const REDACTED: &str = "<redacted>";
+
+/// Patterns masked in the error messages and fixtures produced by a client, see
+/// [`SyncClient::with_redaction`](crate::SyncClient::with_redaction).
+///
+/// Without the `regex` feature, there can't be any pattern and nothing is redacted.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Redactions {
+    #[cfg(feature = "regex")]
+    patterns: Vec<regex::Regex>,
+}
+
+impl Redactions {
+    #[cfg(feature = "regex")]
+    pub fn add(&mut self, pattern: regex::Regex) {
+        self.patterns.push(pattern);
+    }
+
+    /// Masks all the matches of the patterns in `text`.
+    pub fn apply(&self, text: &str) -> String {
+        #[allow(unused_mut)]
+        let mut text = text.to_string();
+        #[cfg(feature = "regex")]
+        for pattern in &self.patterns {
+            text = pattern.replace_all(&text, REDACTED).into_owned();
+        }
+        text
+    }
+
+    /// Masks the patterns in the message of `err`, keeping its class.
+    pub fn error(&self, err: AnalyticsError) -> AnalyticsError {
+        let message = err.to_string();
+        let redacted = self.apply(&message);
+        if redacted == message {
+            return err;
+        }
+        err.with_message(redacted)
+    }
+}
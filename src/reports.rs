@@ -0,0 +1,214 @@
+//! Reports and report groups, the folders of the Analytics navigation.
+//!
+//! Groups are reports of a special type, which other reports point to as their parent. Both use
+//! the Analytics app's own routes rather than its versioned API, so they may change between
+//! Analytics releases.
+
+use reqwest as http;
+use serde_json::{json, Value};
+
+use crate::as_u64;
+use crate::{AnalyticsError, SyncClient};
+
+/// Type of the reports which are groups.
+const GROUP_TYPE: u64 = 0;
+
+/// Type of the reports built on a dataset of the Analytics app.
+const DATASET_TYPE: u64 = 2;
+
+/// A group of reports, as listed by [`SyncClient::list_report_groups`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ReportGroup {
+    /// Identifier of the group.
+    pub id: u64,
+    /// Name of the group, as displayed in the navigation.
+    pub name: String,
+    /// Identifier of the group containing this one, if any.
+    pub parent: Option<u64>,
+}
+
+impl ReportGroup {
+    fn from_json(value: &Value) -> Option<Self> {
+        if as_u64(&value["type"]) != Some(GROUP_TYPE) {
+            return None;
+        }
+        Some(Self {
+            id: as_u64(&value["id"])?,
+            name: value["name"].as_str().unwrap_or_default().to_string(),
+            parent: parent(&value["parent"]),
+        })
+    }
+}
+
+/// A report, with its configuration, as listed by [`SyncClient::list_reports`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct Report {
+    /// Identifier of the report.
+    pub id: u64,
+    /// Name of the report.
+    pub name: String,
+    /// Subheader of the report, shown under its name.
+    pub subheader: String,
+    /// Identifier of the group containing the report, if any.
+    pub parent: Option<u64>,
+    /// Type of the data source of the report, e.g. 2 for a dataset of the Analytics app.
+    pub source_type: u64,
+    /// Identifier of the dataset the report is built on, if any.
+    pub dataset: Option<u64>,
+    /// How the data is shown: `table`, `chart` or `ct` for both.
+    pub visualization: String,
+    /// Type of the chart, e.g. `line`, `column` or `doughnut`.
+    pub chart: String,
+    /// Options of the chart, as configured in the report.
+    pub chart_options: Option<Value>,
+    /// Options of the data series, e.g. their colors.
+    pub data_options: Option<Value>,
+    /// Filters and drilldown of the report.
+    pub filters: Option<Value>,
+}
+
+impl Report {
+    fn from_json(value: &Value) -> Option<Self> {
+        let source_type = as_u64(&value["type"])?;
+        if source_type == GROUP_TYPE {
+            return None;
+        }
+        let text = |name: &str| value[name].as_str().unwrap_or_default().to_string();
+        Some(Self {
+            id: as_u64(&value["id"])?,
+            name: text("name"),
+            subheader: text("subheader"),
+            parent: parent(&value["parent"]),
+            source_type,
+            dataset: as_u64(&value["dataset"]).filter(|&id| id != 0),
+            visualization: text("visualization"),
+            chart: text("chart"),
+            chart_options: options(&value["chartoptions"]),
+            data_options: options(&value["dataoptions"]),
+            filters: options(&value["filteroptions"]),
+        })
+    }
+}
+
+/// Options are stored as JSON text, which the server returns as it is, or already parsed.
+fn options(value: &Value) -> Option<Value> {
+    match value {
+        Value::Null => None,
+        Value::String(text) if text.trim().is_empty() => None,
+        Value::String(text) => serde_json::from_str(text).ok(),
+        value => Some(value.clone()),
+    }
+}
+
+/// The top level is represented by a parent of 0.
+fn parent(value: &Value) -> Option<u64> {
+    as_u64(value).filter(|&id| id != 0)
+}
+
+impl SyncClient {
+    /// Lists the report groups the user has access to, at all levels.
+    pub fn list_report_groups(&self) -> Result<Vec<ReportGroup>, AnalyticsError> {
+        let resp = self.request_json(http::Method::GET, "apps/analytics/report", None)?;
+        Ok(resp
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(ReportGroup::from_json)
+            .collect())
+    }
+
+    /// Lists the reports the user has access to, with their configuration, e.g. to document
+    /// the dashboards built on the client's data. Groups aren't included, see
+    /// [`SyncClient::list_report_groups`].
+    pub fn list_reports(&self) -> Result<Vec<Report>, AnalyticsError> {
+        let resp = self.request_json(http::Method::GET, "apps/analytics/report", None)?;
+        Ok(resp
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(Report::from_json)
+            .collect())
+    }
+
+    /// Lists the reports built on the given dataset, see [`SyncClient::list_reports`].
+    pub fn list_reports_of_dataset(&self, dataset: u64) -> Result<Vec<Report>, AnalyticsError> {
+        let mut reports = self.list_reports()?;
+        reports.retain(|report| report.dataset == Some(dataset));
+        Ok(reports)
+    }
+
+    /// Fetches the configuration of a report.
+    pub fn get_report(&self, report: u64) -> Result<Report, AnalyticsError> {
+        let resp = self.request_json(
+            http::Method::GET,
+            &format!("apps/analytics/report/{}", report),
+            None,
+        )?;
+        Report::from_json(&resp).ok_or_else(|| {
+            AnalyticsError::Parse(format!("unexpected report configuration: {}", resp))
+        })
+    }
+
+    /// Creates a report group, at the top level or in the given parent group, and returns its
+    /// identifier.
+    pub fn create_report_group(
+        &self,
+        name: &str,
+        parent: Option<u64>,
+    ) -> Result<u64, AnalyticsError> {
+        let resp = self.request_json(
+            http::Method::POST,
+            "apps/analytics/report",
+            Some(json!({
+                "name": name,
+                "type": GROUP_TYPE,
+                "parent": parent.unwrap_or(0),
+            })),
+        )?;
+
+        as_u64(&resp).ok_or_else(|| {
+            AnalyticsError::Parse(format!(
+                "unexpected report group creation response: {}",
+                resp
+            ))
+        })
+    }
+
+    /// Creates a report at the top level, showing the data of the given dataset in a table and
+    /// a chart of type `chart`, e.g. `line`, and returns its identifier.
+    pub fn create_report(
+        &self,
+        name: &str,
+        dataset: u64,
+        chart: &str,
+    ) -> Result<u64, AnalyticsError> {
+        let resp = self.request_json(
+            http::Method::POST,
+            "apps/analytics/report",
+            Some(json!({
+                "name": name,
+                "subheader": "",
+                "parent": 0,
+                "type": DATASET_TYPE,
+                "dataset": dataset,
+                "link": "",
+                "visualization": "ct",
+                "chart": chart,
+            })),
+        )?;
+
+        as_u64(&resp).ok_or_else(|| {
+            AnalyticsError::Parse(format!("unexpected report creation response: {}", resp))
+        })
+    }
+
+    /// Moves a report, or a report group, into the given group, or to the top level.
+    pub fn move_report(&self, report: u64, group: Option<u64>) -> Result<(), AnalyticsError> {
+        self.request_json(
+            http::Method::POST,
+            &format!("apps/analytics/report/{}/group", report),
+            Some(json!({ "groupId": group.unwrap_or(0) })),
+        )?;
+        Ok(())
+    }
+}
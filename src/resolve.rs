@@ -0,0 +1,238 @@
+//! Falling back to the addresses the server was last reached at when its host name can't be
+//! resolved, see [`SyncClientBuilder::last_good_address`](crate::SyncClientBuilder::last_good_address).
+//!
+//! `reqwest` can't be told which address to connect to for a host. Instead, the requests sent
+//! during resolution failures go through a relay on the loopback interface, set up as the HTTP
+//! proxy of a second client: the relay connects to the remembered address, and the client
+//! tunnels TLS through it with the real host name, so that the certificate and SNI checks still
+//! apply.
+
+use std::collections::HashMap;
+use std::io::{self, Read, Write};
+use std::net::{Shutdown, SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::{Arc, Mutex, Weak};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use reqwest as http;
+
+use crate::transport::{Request, Response, Transport};
+use crate::AnalyticsError;
+
+/// A host name and port.
+type Authority = (String, u16);
+
+/// The addresses each host resolved to for the last request that got a response, and when it
+/// was resolved.
+type Addresses = Mutex<HashMap<Authority, (Vec<SocketAddr>, Instant)>>;
+
+/// Resolves a host name and port, see [`system_resolver`].
+pub(crate) type Resolver = Arc<dyn Fn(&str, u16) -> io::Result<Vec<SocketAddr>> + Send + Sync>;
+
+/// Request heads longer than this are refused by the relay.
+const MAX_HEAD_LEN: usize = 16 * 1024;
+
+/// The transport of clients built with
+/// [`SyncClientBuilder::last_good_address`](crate::SyncClientBuilder::last_good_address).
+pub(crate) struct LastGoodAddress {
+    client: http::blocking::Client,
+    /// Sends through the relay.
+    fallback: http::blocking::Client,
+    /// Dropped before the relay, for its thread to stop.
+    addresses: Arc<Addresses>,
+    relay: Relay,
+    ttl: Duration,
+    resolver: Resolver,
+}
+
+impl LastGoodAddress {
+    /// `fallback` is configured like `client`, and gets the relay as its proxy; the relay's
+    /// connections to the server time out after `connect_timeout`.
+    pub fn new(
+        client: http::blocking::Client,
+        fallback: http::blocking::ClientBuilder,
+        ttl: Duration,
+        connect_timeout: Duration,
+        resolver: Resolver,
+    ) -> Result<Self, AnalyticsError> {
+        let relay_error = |err| AnalyticsError::local(format!("can't start the relay: {}", err));
+        let listener = TcpListener::bind("127.0.0.1:0").map_err(relay_error)?;
+        let addr = listener.local_addr().map_err(relay_error)?;
+        let fallback = fallback
+            .proxy(http::Proxy::all(&format!("http://{}", addr))?)
+            .build()?;
+        Ok(Self {
+            client,
+            fallback,
+            addresses: Arc::default(),
+            relay: Relay {
+                addr,
+                listener: Mutex::new(Some(listener)),
+                connect_timeout,
+            },
+            ttl,
+            resolver,
+        })
+    }
+
+    /// The addresses `authority` resolved to less than the TTL ago, forgetting older ones.
+    fn remembers(&self, authority: &Authority) -> bool {
+        let mut addresses = self.addresses.lock().unwrap();
+        match addresses.get(authority) {
+            Some((_, resolved)) if resolved.elapsed() < self.ttl => true,
+            Some(_) => {
+                addresses.remove(authority);
+                false
+            }
+            None => false,
+        }
+    }
+}
+
+impl Transport for LastGoodAddress {
+    fn send(&self, request: Request) -> Result<Response, AnalyticsError> {
+        // Addresses need no resolution.
+        let authority = match authority(&request.url) {
+            Some(authority) => authority,
+            None => return self.client.send(request),
+        };
+
+        match (self.resolver)(&authority.0, authority.1) {
+            Ok(resolved) => {
+                let resp = self.client.send(request)?;
+                if !resolved.is_empty() {
+                    self.addresses
+                        .lock()
+                        .unwrap()
+                        .insert(authority, (resolved, Instant::now()));
+                }
+                Ok(resp)
+            }
+            Err(_) if self.remembers(&authority) => {
+                self.relay.start(&self.addresses);
+                self.fallback.send(request)
+            }
+            // Let the client report the resolution failure.
+            Err(_) => self.client.send(request),
+        }
+    }
+}
+
+/// Resolves host names as the `reqwest` client does.
+pub(crate) fn system_resolver() -> Resolver {
+    Arc::new(|host, port| Ok((host, port).to_socket_addrs()?.collect()))
+}
+
+/// The host name and port of `url`, unless its host is an IP address.
+fn authority(url: &str) -> Option<Authority> {
+    let url = http::Url::parse(url).ok()?;
+    Some((url.domain()?.to_string(), url.port_or_known_default()?))
+}
+
+/// Relays the connections of the fallback client to the remembered addresses, from a thread
+/// started at the first fallback.
+struct Relay {
+    addr: SocketAddr,
+    /// Taken by the thread once started.
+    listener: Mutex<Option<TcpListener>>,
+    connect_timeout: Duration,
+}
+
+impl Relay {
+    fn start(&self, addresses: &Arc<Addresses>) {
+        let listener = match self.listener.lock().unwrap().take() {
+            Some(listener) => listener,
+            None => return,
+        };
+        let addresses = Arc::downgrade(addresses);
+        let connect_timeout = self.connect_timeout;
+        thread::spawn(move || accept(listener, addresses, connect_timeout));
+    }
+}
+
+impl Drop for Relay {
+    fn drop(&mut self) {
+        // Wake up the thread, for it to see that the transport is gone.
+        if self.listener.get_mut().unwrap().is_none() {
+            let _ = TcpStream::connect(self.addr);
+        }
+    }
+}
+
+fn accept(listener: TcpListener, addresses: Weak<Addresses>, connect_timeout: Duration) {
+    for stream in listener.incoming() {
+        let addresses = match addresses.upgrade() {
+            Some(addresses) => addresses,
+            None => return,
+        };
+        if let Ok(stream) = stream {
+            thread::spawn(move || forward(stream, &addresses, connect_timeout));
+        }
+    }
+}
+
+/// Connects a connection of the fallback client to the remembered addresses of the host it
+/// asks for, and copies the bytes both ways until either end closes it.
+fn forward(
+    mut client: TcpStream,
+    addresses: &Addresses,
+    connect_timeout: Duration,
+) -> io::Result<()> {
+    // CONNECT requests for HTTPS, absolute URLs for plain HTTP, which servers must accept too.
+    let mut head = Vec::new();
+    let mut buf = [0; 1024];
+    while !head.windows(4).any(|window| window == b"\r\n\r\n") {
+        let read = client.read(&mut buf)?;
+        if read == 0 || head.len() + read > MAX_HEAD_LEN {
+            return Ok(());
+        }
+        head.extend_from_slice(&buf[..read]);
+    }
+    let line = String::from_utf8_lossy(head.split(|&b| b == b'\r').next().unwrap_or_default());
+    let mut parts = line.split(' ');
+    let (method, target) = (parts.next().unwrap_or(""), parts.next().unwrap_or(""));
+    let tunnel = method == "CONNECT";
+    let url = if tunnel {
+        format!("https://{}", target)
+    } else {
+        target.to_string()
+    };
+
+    let authority = authority(&url).unwrap_or_default();
+    let remembered = match addresses.lock().unwrap().get(&authority) {
+        Some((addrs, _)) => addrs.clone(),
+        None => Vec::new(),
+    };
+    let server = remembered.iter().enumerate().find_map(|(i, addr)| {
+        let server = TcpStream::connect_timeout(addr, connect_timeout).ok()?;
+        Some((i, server))
+    });
+    let mut server = match server {
+        Some((i, server)) => {
+            // Try the address that worked first next time.
+            if let Some((addrs, _)) = addresses.lock().unwrap().get_mut(&authority) {
+                if addrs.get(i) == Some(&remembered[i]) {
+                    addrs[..=i].rotate_right(1);
+                }
+            }
+            server
+        }
+        None if tunnel => return client.write_all(b"HTTP/1.1 502 Bad Gateway\r\n\r\n"),
+        None => return Ok(()),
+    };
+    if tunnel {
+        client.write_all(b"HTTP/1.1 200 Connection established\r\n\r\n")?;
+    } else {
+        server.write_all(&head)?;
+    }
+
+    let (mut upload_from, mut upload_to) = (client.try_clone()?, server.try_clone()?);
+    let upload = thread::spawn(move || {
+        let _ = io::copy(&mut upload_from, &mut upload_to);
+        let _ = upload_to.shutdown(Shutdown::Write);
+    });
+    let _ = io::copy(&mut server, &mut client);
+    let _ = client.shutdown(Shutdown::Write);
+    let _ = upload.join();
+    Ok(())
+}
@@ -0,0 +1,131 @@
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::time::Duration;
+
+use reqwest as http;
+use serde::{Serialize, Serializer};
+
+use crate::describe::secs;
+
+/// How long to wait between two attempts of a request, see [`RetryPolicy::backoff`].
+///
+/// It serializes with durations as seconds, e.g. `{"constant":1.5}`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Backoff {
+    /// Always wait the same time.
+    #[serde(serialize_with = "secs")]
+    Constant(Duration),
+    /// Wait `initial`, then twice as long after every failed attempt, up to `max`.
+    Exponential {
+        #[serde(rename = "initial_secs", serialize_with = "secs")]
+        initial: Duration,
+        #[serde(rename = "max_secs", serialize_with = "secs")]
+        max: Duration,
+    },
+}
+
+impl Backoff {
+    fn delay(self, attempt: u32) -> Duration {
+        match self {
+            Backoff::Constant(delay) => delay,
+            Backoff::Exponential { initial, max } => {
+                let factor = 1u32
+                    .checked_shl(attempt.saturating_sub(1))
+                    .unwrap_or(u32::MAX);
+                initial.checked_mul(factor).unwrap_or(max).min(max)
+            }
+        }
+    }
+}
+
+/// When and how to retry the requests that failed because of a transient error, see
+/// [`SyncClientBuilder::retry`](crate::SyncClientBuilder::retry).
+///
+/// A request is retried if it couldn't be sent or its response couldn't be read, e.g. because of
+/// a timeout, or if the server answered with one of the retryable status codes. Note that a data
+/// point whose response was lost may have been stored anyway, and is then stored twice.
+///
+/// It serializes with the retryable statuses as numbers, e.g. for
+/// [`ClientDescription`](crate::ClientDescription).
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct RetryPolicy {
+    max_attempts: u32,
+    backoff: Backoff,
+    #[serde(serialize_with = "status_codes")]
+    statuses: Vec<http::StatusCode>,
+    jitter: bool,
+}
+
+impl RetryPolicy {
+    /// Tries each request up to `max_attempts` times, including the first one, waiting 500
+    /// milliseconds after the first failure and up to 30 seconds after the next ones, with
+    /// jitter, and retrying on 429, 502, 503 and 504 statuses.
+    pub fn new(max_attempts: u32) -> Self {
+        Self {
+            max_attempts: max_attempts.max(1),
+            backoff: Backoff::Exponential {
+                initial: Duration::from_millis(500),
+                max: Duration::from_secs(30),
+            },
+            statuses: vec![
+                http::StatusCode::TOO_MANY_REQUESTS,
+                http::StatusCode::BAD_GATEWAY,
+                http::StatusCode::SERVICE_UNAVAILABLE,
+                http::StatusCode::GATEWAY_TIMEOUT,
+            ],
+            jitter: true,
+        }
+    }
+
+    /// Waiting strategy between two attempts.
+    pub fn backoff(mut self, backoff: Backoff) -> Self {
+        self.backoff = backoff;
+        self
+    }
+
+    /// Retry on the given status codes, instead of the default ones.
+    pub fn retry_on(mut self, statuses: &[http::StatusCode]) -> Self {
+        self.statuses = statuses.to_vec();
+        self
+    }
+
+    /// Wait a random time between half and the whole backoff delay, so that many clients failing
+    /// at the same time don't all retry at the same time. Enabled by default.
+    pub fn jitter(mut self, enabled: bool) -> Self {
+        self.jitter = enabled;
+        self
+    }
+
+    /// Maximum number of attempts of a request, including the first one.
+    pub fn max_attempts(&self) -> u32 {
+        self.max_attempts
+    }
+
+    pub(crate) fn retries_status(&self, status: http::StatusCode) -> bool {
+        self.statuses.contains(&status)
+    }
+
+    /// Time to wait after the given failed attempt, counting from 1.
+    pub(crate) fn delay(&self, attempt: u32) -> Duration {
+        let delay = self.backoff.delay(attempt);
+        if !self.jitter {
+            return delay;
+        }
+        delay.mul_f64(0.5 + random_fraction() / 2.0)
+    }
+}
+
+fn status_codes<S: Serializer>(
+    statuses: &[http::StatusCode],
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.collect_seq(statuses.iter().map(http::StatusCode::as_u16))
+}
+
+/// A random number between 0 and 1, for jitter.
+pub(crate) fn random_fraction() -> f64 {
+    // A freshly seeded hasher is a good enough source of randomness for jitter.
+    let random = RandomState::new().build_hasher().finish();
+    (random % 1000) as f64 / 1000.0
+}
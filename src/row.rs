@@ -0,0 +1,49 @@
+use crate::DataPoint;
+#[cfg(feature = "blocking")]
+use crate::{AnalyticsError, Receipt, SyncClient};
+
+/// A type that can be sent as a row of a dataset with [`SyncClient::send`].
+///
+/// With the `derive` feature, it can be derived for structs with named fields, annotating the
+/// fields to send with `#[dimension1]`, `#[dimension2]` and `#[value]`:
+///
+/// ```ignore
+/// #[derive(nextcloud_analytics_rs::ToAnalyticsRow)]
+/// struct Reading {
+///     #[dimension1]
+///     sensor: String,
+///     #[dimension2]
+///     room: &'static str,
+///     #[value]
+///     celsius: f32,
+///     // Other fields are ignored.
+///     firmware: u32,
+/// }
+/// ```
+///
+/// The dimensions are formatted with [`ToString`], and the value converted with `Into<f64>`.
+/// For timeline data, the second dimension must be a date in the RFC 2822 format.
+pub trait ToAnalyticsRow {
+    /// The data point for this row.
+    fn to_row(&self) -> DataPoint;
+}
+
+impl ToAnalyticsRow for DataPoint {
+    fn to_row(&self) -> DataPoint {
+        self.clone()
+    }
+}
+
+#[cfg(feature = "blocking")]
+impl SyncClient {
+    /// Sends a custom row, like [`SyncClient::send_point`].
+    pub fn send<T: ToAnalyticsRow + ?Sized>(&self, row: &T) -> Result<Receipt, AnalyticsError> {
+        self.send_point(row.to_row())
+    }
+
+    /// Sends many custom rows in a single request, like [`SyncClient::send_batch`].
+    pub fn send_rows<T: ToAnalyticsRow>(&self, rows: &[T]) -> Result<Receipt, AnalyticsError> {
+        let points: Vec<DataPoint> = rows.iter().map(ToAnalyticsRow::to_row).collect();
+        self.send_batch(&points)
+    }
+}
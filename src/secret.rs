@@ -0,0 +1,39 @@
+use zeroize::Zeroize;
+
+use crate::credentials::Secret;
+#[cfg(feature = "blocking")]
+use crate::login::{AppCredentials, LoginFlow};
+
+/// Wipes the password and the bearer token from memory when the last clone of the credentials,
+/// and so the last client using them, is dropped.
+///
+/// Requires the `zeroize` feature. Copies made by the HTTP client into the `Authorization`
+/// header of past requests, and the strings given to the builder, aren't wiped.
+impl Drop for Secret {
+    fn drop(&mut self) {
+        self.passwd.zeroize();
+        self.bearer_token.zeroize();
+    }
+}
+
+/// Wipes the app password from memory when the credentials obtained by logging in are dropped,
+/// e.g. once given to [`Credentials`](crate::Credentials).
+///
+/// Requires the `zeroize` feature.
+#[cfg(feature = "blocking")]
+impl Drop for AppCredentials {
+    fn drop(&mut self) {
+        self.app_password.zeroize();
+    }
+}
+
+/// Wipes the token polling for the app password, which could be used to obtain it until the
+/// flow expires.
+///
+/// Requires the `zeroize` feature.
+#[cfg(feature = "blocking")]
+impl Drop for LoginFlow {
+    fn drop(&mut self) {
+        self.poll_token.zeroize();
+    }
+}
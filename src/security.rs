@@ -0,0 +1,329 @@
+//! Transport security policy of a client: HTTPS only, minimum TLS version and public key pins.
+
+use std::any::Any;
+#[cfg(feature = "rustls")]
+use std::sync::Arc;
+
+use reqwest as http;
+
+use crate::builder::HttpOptions;
+use crate::AnalyticsError;
+
+/// A version of the TLS protocol, see [`Security::min_tls_version`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum TlsVersion {
+    Tls12,
+    Tls13,
+}
+
+/// Security policy of the connections of a client, see [`SyncClientBuilder::security`], e.g.
+/// for sensitive metrics sent across the internet.
+///
+/// By default, plain HTTP is allowed, and the TLS library negotiates any version it supports.
+///
+/// Requires the `native-tls` or the `rustls` feature. Certificate pinning only works with the
+/// rustls backend: with both features, clients with pins use rustls, and with `native-tls`
+/// alone, building a client with pins fails.
+///
+/// [`SyncClientBuilder::security`]: crate::SyncClientBuilder::security
+#[derive(Clone, Debug, Default)]
+pub struct Security {
+    https_only: bool,
+    min_tls_version: Option<TlsVersion>,
+    pins: Vec<String>,
+}
+
+impl Security {
+    /// A policy without any restriction.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Refuse plain `http://` Nextcloud URLs when building the client, and redirects to them.
+    pub fn https_only(mut self, enabled: bool) -> Self {
+        self.https_only = enabled;
+        self
+    }
+
+    /// Refuse to connect with a version of TLS older than `version`.
+    pub fn min_tls_version(mut self, version: TlsVersion) -> Self {
+        self.min_tls_version = Some(version);
+        self
+    }
+
+    /// Only accept servers whose certificate has the public key with the given pin: the
+    /// base64-encoded SHA-256 hash of its DER-encoded `SubjectPublicKeyInfo`, optionally
+    /// prefixed by `sha256/`, as printed by:
+    ///
+    /// ```sh
+    /// openssl x509 -in cert.pem -pubkey -noout | openssl pkey -pubin -outform der \
+    ///     | openssl dgst -sha256 -binary | base64
+    /// ```
+    ///
+    /// Pins can be given several times, e.g. for the current key and the next one; the
+    /// certificate must match one of them. The certificate chain is still verified, unless
+    /// [invalid certificates are accepted](crate::SyncClientBuilder::danger_accept_invalid_certs),
+    /// which makes it possible to pin a self-signed certificate.
+    ///
+    /// Only works with the rustls backend, which is used for clients with pins even if the
+    /// `native-tls` feature is enabled too: without the `rustls` feature, building a client
+    /// fails rather than connecting without checking the pins.
+    pub fn pin_spki_sha256<S: Into<String>>(mut self, pin: S) -> Self {
+        self.pins.push(pin.into());
+        self
+    }
+
+    /// Fails if the policy forbids the Nextcloud URL.
+    pub(crate) fn check_url(&self, url: &str) -> Result<(), AnalyticsError> {
+        if self.https_only && !url.starts_with("https://") {
+            return Err(AnalyticsError::local(format!(
+                "the security policy only allows HTTPS, not {}",
+                url
+            )));
+        }
+        Ok(())
+    }
+
+    /// Applies the policy to a client being built, after the other HTTP settings.
+    pub(crate) fn apply<B: TlsBuilder>(
+        &self,
+        mut builder: B,
+        options: &HttpOptions,
+    ) -> Result<B, AnalyticsError> {
+        if self.https_only {
+            builder = builder.https_only(true);
+        }
+
+        let pins = self
+            .pins
+            .iter()
+            .map(|pin| decode_pin(pin))
+            .collect::<Result<Vec<_>, _>>()?;
+        if self.min_tls_version.is_none() && pins.is_empty() {
+            return Ok(builder);
+        }
+
+        // The TLS configuration is then built here rather than by reqwest, and must include the
+        // other TLS settings.
+        #[cfg(feature = "rustls")]
+        if !pins.is_empty() || cfg!(not(feature = "native-tls")) {
+            return Ok(builder.use_preconfigured_tls(self.rustls(options, pins)?));
+        }
+        #[cfg(feature = "native-tls")]
+        {
+            if !pins.is_empty() {
+                return Err(AnalyticsError::local(
+                    "certificate pinning requires the rustls feature",
+                ));
+            }
+            Ok(builder.use_preconfigured_tls(self.native_tls(options)?))
+        }
+        #[cfg(not(feature = "native-tls"))]
+        unreachable!("the rustls configuration is always used without native-tls")
+    }
+
+    #[cfg(feature = "native-tls")]
+    fn native_tls(
+        &self,
+        options: &HttpOptions,
+    ) -> Result<native_tls::TlsConnector, AnalyticsError> {
+        let mut tls = native_tls::TlsConnector::builder();
+        tls.min_protocol_version(Some(match self.min_tls_version {
+            Some(TlsVersion::Tls13) => native_tls::Protocol::Tlsv13,
+            Some(TlsVersion::Tls12) | None => native_tls::Protocol::Tlsv12,
+        }));
+        tls.danger_accept_invalid_certs(options.accept_invalid_certs);
+        for pem in &options.root_certificates {
+            tls.add_root_certificate(
+                native_tls::Certificate::from_pem(pem).map_err(AnalyticsError::local)?,
+            );
+        }
+        tls.build().map_err(AnalyticsError::local)
+    }
+
+    #[cfg(feature = "rustls")]
+    fn rustls(
+        &self,
+        options: &HttpOptions,
+        pins: Vec<[u8; 32]>,
+    ) -> Result<rustls::ClientConfig, AnalyticsError> {
+        let mut tls = rustls::ClientConfig::new();
+        // Same protocols and roots as reqwest's own configuration.
+        tls.set_protocols(&["h2".into(), "http/1.1".into()]);
+        tls.root_store
+            .add_server_trust_anchors(&webpki_roots::TLS_SERVER_ROOTS);
+        tls.versions = match self.min_tls_version {
+            Some(TlsVersion::Tls13) => vec![rustls::ProtocolVersion::TLSv1_3],
+            Some(TlsVersion::Tls12) | None => vec![
+                rustls::ProtocolVersion::TLSv1_3,
+                rustls::ProtocolVersion::TLSv1_2,
+            ],
+        };
+
+        for pem in &options.root_certificates {
+            let certs = rustls::internal::pemfile::certs(&mut &pem[..])
+                .map_err(|()| AnalyticsError::local("invalid PEM root certificate"))?;
+            for cert in certs {
+                tls.root_store.add(&cert).map_err(|err| {
+                    AnalyticsError::local(format!("invalid root certificate: {:?}", err))
+                })?;
+            }
+        }
+
+        if options.accept_invalid_certs || !pins.is_empty() {
+            tls.dangerous()
+                .set_certificate_verifier(Arc::new(PinningVerifier {
+                    pins,
+                    verify_chain: !options.accept_invalid_certs,
+                }));
+        }
+        Ok(tls)
+    }
+}
+
+/// Verifies the certificate chain, unless invalid certificates are accepted, and that the
+/// server's public key matches one of the pins, if any.
+#[cfg(feature = "rustls")]
+struct PinningVerifier {
+    pins: Vec<[u8; 32]>,
+    verify_chain: bool,
+}
+
+#[cfg(feature = "rustls")]
+impl rustls::ServerCertVerifier for PinningVerifier {
+    fn verify_server_cert(
+        &self,
+        roots: &rustls::RootCertStore,
+        presented_certs: &[rustls::Certificate],
+        dns_name: webpki::DNSNameRef<'_>,
+        ocsp_response: &[u8],
+    ) -> Result<rustls::ServerCertVerified, rustls::TLSError> {
+        if self.verify_chain {
+            rustls::WebPKIVerifier::new().verify_server_cert(
+                roots,
+                presented_certs,
+                dns_name,
+                ocsp_response,
+            )?;
+        }
+        if self.pins.is_empty() {
+            return Ok(rustls::ServerCertVerified::assertion());
+        }
+
+        let spki = presented_certs
+            .first()
+            .and_then(|cert| subject_public_key_info(&cert.0))
+            .ok_or_else(|| {
+                rustls::TLSError::General("can't read the server's public key".to_string())
+            })?;
+        let hash = ring::digest::digest(&ring::digest::SHA256, spki);
+        if self.pins.iter().any(|pin| pin[..] == *hash.as_ref()) {
+            Ok(rustls::ServerCertVerified::assertion())
+        } else {
+            Err(rustls::TLSError::General(
+                "the server's public key doesn't match any pin".to_string(),
+            ))
+        }
+    }
+}
+
+/// The reqwest client builders, blocking or not, a policy applies to.
+pub(crate) trait TlsBuilder: Sized {
+    fn https_only(self, enabled: bool) -> Self;
+    fn use_preconfigured_tls(self, tls: impl Any) -> Self;
+}
+
+impl TlsBuilder for http::blocking::ClientBuilder {
+    fn https_only(self, enabled: bool) -> Self {
+        self.https_only(enabled)
+    }
+
+    fn use_preconfigured_tls(self, tls: impl Any) -> Self {
+        self.use_preconfigured_tls(tls)
+    }
+}
+
+#[cfg(feature = "async")]
+impl TlsBuilder for http::ClientBuilder {
+    fn https_only(self, enabled: bool) -> Self {
+        self.https_only(enabled)
+    }
+
+    fn use_preconfigured_tls(self, tls: impl Any) -> Self {
+        self.use_preconfigured_tls(tls)
+    }
+}
+
+/// Decodes a base64-encoded SHA-256 pin, with an optional `sha256/` prefix.
+pub(crate) fn decode_pin(pin: &str) -> Result<[u8; 32], AnalyticsError> {
+    let invalid = || AnalyticsError::local(format!("invalid SHA-256 public key pin: {:?}", pin));
+    let encoded = pin.trim();
+    let encoded = encoded.strip_prefix("sha256/").unwrap_or(encoded);
+
+    let mut bytes = Vec::with_capacity(32);
+    let (mut buffer, mut bits) = (0u32, 0);
+    for c in encoded.trim_end_matches('=').bytes() {
+        let value = match c {
+            b'A'..=b'Z' => c - b'A',
+            b'a'..=b'z' => c - b'a' + 26,
+            b'0'..=b'9' => c - b'0' + 52,
+            b'+' => 62,
+            b'/' => 63,
+            _ => return Err(invalid()),
+        };
+        buffer = buffer << 6 | u32::from(value);
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push((buffer >> bits) as u8);
+        }
+    }
+    let mut hash = [0; 32];
+    if bytes.len() != hash.len() {
+        return Err(invalid());
+    }
+    hash.copy_from_slice(&bytes);
+    Ok(hash)
+}
+
+/// Splits a DER element off `der`, returning its tag, its contents and what follows it.
+#[cfg(feature = "rustls")]
+pub(crate) fn der_element(der: &[u8]) -> Option<(u8, &[u8], &[u8])> {
+    let tag = *der.first()?;
+    let first = *der.get(1)?;
+    let (len, header) = if first < 0x80 {
+        (usize::from(first), 2)
+    } else {
+        let count = usize::from(first & 0x7f);
+        if count == 0 || count > 4 {
+            return None;
+        }
+        let len = der
+            .get(2..2 + count)?
+            .iter()
+            .fold(0, |len, &byte| len << 8 | usize::from(byte));
+        (len, 2 + count)
+    };
+    let end = header.checked_add(len)?;
+    let contents = der.get(header..end)?;
+    Some((tag, contents, &der[end..]))
+}
+
+/// The DER-encoded `SubjectPublicKeyInfo` of an X.509 certificate.
+#[cfg(feature = "rustls")]
+pub(crate) fn subject_public_key_info(cert: &[u8]) -> Option<&[u8]> {
+    let (_, certificate, _) = der_element(cert)?;
+    let (_, tbs, _) = der_element(certificate)?;
+
+    let mut rest = tbs;
+    // Optional explicit version.
+    if rest.first() == Some(&0xa0) {
+        rest = der_element(rest)?.2;
+    }
+    // Serial number, signature algorithm, issuer, validity and subject.
+    for _ in 0..5 {
+        rest = der_element(rest)?.2;
+    }
+    let (_, _, after) = der_element(rest)?;
+    Some(&rest[..rest.len() - after.len()])
+}
@@ -0,0 +1,150 @@
+//! Shares of reports with other users, groups, or anyone with a link.
+//!
+//! Analytics shares reports, not datasets: to give a group access to a dataset, share the report
+//! showing it, which for datasets created before Analytics 4 is the one with the same
+//! identifier. Shares use the Analytics app's own routes rather than its versioned API, so they
+//! may change between Analytics releases.
+
+use reqwest as http;
+use serde_json::{json, Value};
+
+use crate::as_u64;
+use crate::{AnalyticsError, SyncClient};
+
+/// Share types, as in Nextcloud's sharing API.
+const USER_TYPE: u64 = 0;
+const GROUP_TYPE: u64 = 1;
+const LINK_TYPE: u64 = 3;
+
+/// Who a report is shared with.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ShareTarget {
+    /// A Nextcloud user, by name.
+    User(String),
+    /// A Nextcloud group, by name.
+    Group(String),
+    /// Anyone with the link of the share.
+    Link,
+    /// A kind of share this crate doesn't know of, with its Analytics type.
+    Other(u64),
+}
+
+impl ShareTarget {
+    fn share_type(&self) -> u64 {
+        match self {
+            ShareTarget::User(_) => USER_TYPE,
+            ShareTarget::Group(_) => GROUP_TYPE,
+            ShareTarget::Link => LINK_TYPE,
+            ShareTarget::Other(share_type) => *share_type,
+        }
+    }
+
+    fn name(&self) -> &str {
+        match self {
+            ShareTarget::User(name) | ShareTarget::Group(name) => name,
+            ShareTarget::Link | ShareTarget::Other(_) => "",
+        }
+    }
+}
+
+/// A share of a report, as listed by [`SyncClient::list_shares`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Share {
+    /// Identifier of the share.
+    pub id: u64,
+    /// Who the report is shared with.
+    pub target: ShareTarget,
+    /// Token of the public URL of a link share.
+    pub token: Option<String>,
+}
+
+impl Share {
+    fn from_json(value: &Value) -> Option<Self> {
+        let with = value["uid"]
+            .as_str()
+            .or_else(|| value["shareWith"].as_str())
+            .unwrap_or_default()
+            .to_string();
+        let target = match as_u64(&value["type"])? {
+            USER_TYPE => ShareTarget::User(with),
+            GROUP_TYPE => ShareTarget::Group(with),
+            LINK_TYPE => ShareTarget::Link,
+            other => ShareTarget::Other(other),
+        };
+
+        Some(Self {
+            id: as_u64(&value["id"])?,
+            target,
+            token: value["token"]
+                .as_str()
+                .filter(|token| !token.is_empty())
+                .map(str::to_string),
+        })
+    }
+}
+
+impl SyncClient {
+    /// Lists the shares of the given report.
+    pub fn list_shares(&self, report: u64) -> Result<Vec<Share>, AnalyticsError> {
+        let resp = self.request_json(
+            http::Method::GET,
+            &format!("apps/analytics/share/{}", report),
+            None,
+        )?;
+        Ok(resp
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(Share::from_json)
+            .collect())
+    }
+
+    /// Shares the given report, read-only, and returns the identifier of the share, e.g. to
+    /// give a Nextcloud group access to a dataset created by a deployment script.
+    pub fn share_report(&self, report: u64, target: &ShareTarget) -> Result<u64, AnalyticsError> {
+        let resp = self.request_json(
+            http::Method::POST,
+            "apps/analytics/share",
+            Some(json!({
+                "reportId": report,
+                "type": target.share_type(),
+                "user": target.name(),
+            })),
+        )?;
+
+        as_u64(&resp).ok_or_else(|| {
+            AnalyticsError::Parse(format!("unexpected share creation response: {}", resp))
+        })
+    }
+
+    /// Creates a public link to the given report, and returns its URL.
+    pub fn create_share_link(&self, report: u64) -> Result<String, AnalyticsError> {
+        let id = self.share_report(report, &ShareTarget::Link)?;
+        let share = self
+            .list_shares(report)?
+            .into_iter()
+            .find(|share| share.id == id);
+        match share.and_then(|share| share.token) {
+            Some(token) => Ok(self.share_link(&token)),
+            None => Err(AnalyticsError::Parse(format!(
+                "the token of the new share {} isn't listed",
+                id
+            ))),
+        }
+    }
+
+    /// Public URL of a link share with the given token.
+    pub fn share_link(&self, token: &str) -> String {
+        format!("{}index.php/apps/analytics/p/{}", self.base_url, token)
+    }
+
+    /// Deletes the share with the given identifier.
+    pub fn delete_share(&self, id: u64) -> Result<(), AnalyticsError> {
+        self.request_json(
+            http::Method::DELETE,
+            &format!("apps/analytics/share/{}", id),
+            None,
+        )?;
+        Ok(())
+    }
+}
@@ -0,0 +1,1165 @@
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom, Write};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{AnalyticsError, DataPoint, Outcome, Receipt, SyncClient};
+
+/// Default maximum number of data points kept in a spool.
+const DEFAULT_MAX_POINTS: usize = 100_000;
+
+/// Length of the end of the spool searched for its last line when repairing it, far more than
+/// any data point takes.
+const MAX_LINE: u64 = 64 * 1024;
+
+/// Number of points of a spool whose number of points isn't known.
+const UNKNOWN_LEN: usize = usize::MAX;
+
+/// Number of seconds in a day, the span of each partition of the archive.
+const DAY: u64 = 24 * 60 * 60;
+
+/// The format of a spool file, see [`BufferedClient::with_format`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SpoolFormat {
+    /// One JSON object per line, easy to inspect and fix by hand, the default.
+    #[default]
+    JsonLines,
+    /// Binary records with a checksum, more compact, and whose corruption anywhere in the file
+    /// is detected, e.g. on flash storage losing power while writing.
+    ///
+    /// Each record is the bytes `A5 5A`, the length of the payload, the payload, and its
+    /// CRC32, the integers in 32 bits little-endian. The payload holds the key and the
+    /// timestamp, each as its length then its UTF-8 bytes, the value as a 64-bit float, and
+    /// when the point was spooled, in seconds since the Unix epoch in 64 bits or 0 if unknown,
+    /// all in little-endian.
+    ///
+    /// Corrupted records, e.g. with a wrong checksum or half-written, are moved to the
+    /// [quarantine file](BufferedClient::quarantine_path) when the spool is read, and the
+    /// reading goes on with the next record.
+    Binary,
+}
+
+/// A client queueing data points to a local file while the server is unreachable, and sending
+/// them in order once it's back, e.g. for a data logger on a flaky connection.
+///
+/// The spool is a JSON Lines file, one data point per line:
+///
+/// ```json
+/// {"dimension1":"temperature","dimension2":"2024-01-01 12:00:00","dimension3":"21.5","queued_at":1704110400}
+/// ```
+///
+/// The value is a string, or a number; `queued_at` is when the point was spooled, in seconds
+/// since the Unix epoch, and may be missing, e.g. for points spooled by older versions. The
+/// same format is used by [`BufferedClient::export_pending`] and
+/// [`BufferedClient::import_pending`], to inspect or repair a backlog, or to move it to
+/// another machine.
+///
+/// A data point is spooled when its send fails with a [network](crate::ErrorClass::Network) or
+/// [maintenance](crate::ErrorClass::Maintenance) error, or a [status](crate::ErrorClass::Http)
+/// which may not last: a server error, a timeout or a rate limit; other errors, e.g. refused
+/// credentials or a rejected request, are returned as usual. Every send first tries to flush the
+/// spool, so that points keep their order.
+///
+/// A last line which can't be parsed, e.g. half-written when the process was killed, is moved
+/// to the [quarantine file](BufferedClient::quarantine_path) before the spool is read; other
+/// invalid lines make the calls fail, as the file was then changed by something else.
+///
+/// The key and timestamp, i.e. the first two dimensions, of each spooled point sent are
+/// appended to the [delivery journal](BufferedClient::delivered_path) until the spool is
+/// rewritten without them. If the process crashes in between, the next flush or replay skips
+/// the points of the journal instead of sending them twice, matching them by key and
+/// timestamp in the order of the spool.
+///
+/// While the server is unreachable, sending a point only tries the oldest spooled one, then
+/// appends the new one to the spool, so that it doesn't get slower as the spool grows. When
+/// the spool is full, its oldest tenth is dropped to make room for new points, so that it isn't
+/// rewritten for every point. Spools grown large during a long outage are best sent with
+/// [`BufferedClient::replay`], which doesn't load them in memory.
+///
+/// The points delivered can also be kept in an [archive](BufferedClient::with_archive)
+/// partitioned by day, to send them again with [`BufferedClient::replay_range`] after the
+/// server was restored from a backup.
+pub struct BufferedClient {
+    client: SyncClient,
+    path: PathBuf,
+    max_points: usize,
+    format: SpoolFormat,
+    archive: Option<PathBuf>,
+    replay_speed: f64,
+    lock: Mutex<()>,
+    /// Number of points in the spool, if known, or [`UNKNOWN_LEN`]; only changed with the lock
+    /// held.
+    len: AtomicUsize,
+}
+
+impl BufferedClient {
+    /// Wraps a client, spooling data points to the file at `path`, which is created if needed.
+    /// Points spooled by a previous run are sent with the next ones.
+    pub fn new<P: Into<PathBuf>>(client: SyncClient, path: P) -> Self {
+        Self {
+            client,
+            path: path.into(),
+            max_points: DEFAULT_MAX_POINTS,
+            format: SpoolFormat::default(),
+            archive: None,
+            replay_speed: 0.0,
+            lock: Mutex::new(()),
+            len: AtomicUsize::new(UNKNOWN_LEN),
+        }
+    }
+
+    /// Maximum number of data points kept in the spool. Defaults to 100,000.
+    pub fn with_max_points(mut self, max_points: usize) -> Self {
+        self.max_points = max_points.max(1);
+        self
+    }
+
+    /// Format of the spool file, JSON Lines by default. A spool written in another format is
+    /// quarantined as corrupted, so flush or export the points of the previous one first.
+    /// [`BufferedClient::export_pending`] and [`BufferedClient::import_pending`] always use JSON
+    /// Lines.
+    pub fn with_format(mut self, format: SpoolFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Keep a copy of the data points delivered, whether sent right away or from the spool, in
+    /// the directory `dir`, which is created if needed, to send them again with
+    /// [`BufferedClient::replay_range`]. Disabled by default.
+    ///
+    /// The archive has a JSON Lines file per UTC day, named after it, e.g. `2024-01-01.jsonl`,
+    /// holding the points delivered that day in the format of the spool, with `sent_at`, when
+    /// they were delivered in seconds since the Unix epoch. Remove the files of the days which
+    /// don't need to be kept anymore. Failing to archive points makes the call fail, although
+    /// they were sent.
+    pub fn with_archive<P: Into<PathBuf>>(mut self, dir: P) -> Self {
+        self.archive = Some(dir.into());
+        self
+    }
+
+    /// Speed at which [`BufferedClient::replay_range`] sends the archived points again,
+    /// compared to the time elapsed between their deliveries, e.g. 60 to replay an hour of data
+    /// in a minute into a live report, for a demo or to try threshold notifications. Defaults
+    /// to 0, sending them as fast as possible; negative or invalid speeds count as 0.
+    pub fn with_replay_speed(mut self, speed: f64) -> Self {
+        self.replay_speed = if speed.is_finite() && speed > 0.0 {
+            speed
+        } else {
+            0.0
+        };
+        self
+    }
+
+    /// The wrapped client.
+    pub fn client(&self) -> &SyncClient {
+        &self.client
+    }
+
+    /// Sends some data, like [`SyncClient::send_data`], spooling it if the server can't be
+    /// reached.
+    pub fn send_data<S: Into<String>, F: Into<f64>>(
+        &self,
+        dimension1: S,
+        dimension2: S,
+        dimension3: F,
+    ) -> Result<Receipt, AnalyticsError> {
+        self.send_point(DataPoint::new(dimension1, dimension2, dimension3))
+    }
+
+    /// Sends timeline data at the current time, like [`SyncClient::send_timeline_now_data`],
+    /// spooling it if the server can't be reached. The time is the one of this call, not the one
+    /// of the actual send.
+    ///
+    /// Requires the `chrono` feature.
+    #[cfg(feature = "chrono")]
+    pub fn send_timeline_now_data<S: Into<String>, F: Into<f64>>(
+        &self,
+        key: S,
+        value: F,
+    ) -> Result<Receipt, AnalyticsError> {
+        let time = self.client.format_time(self.client.now())?;
+        self.send_data(key.into(), time, value.into())
+    }
+
+    /// Sends a single data point, like [`SyncClient::send_point`], spooling it if the server
+    /// can't be reached.
+    ///
+    /// Returns a receipt with the [`Outcome::Spooled`] outcome in that case.
+    pub fn send_point(&self, point: DataPoint) -> Result<Receipt, AnalyticsError> {
+        let _lock = self.lock.lock().unwrap();
+
+        if let Some(oldest) = self.oldest_pending()? {
+            // Only read the whole spool once the oldest point went through.
+            let mut delivered = self.read_delivered()?;
+            if self.send_in_order(std::slice::from_ref(&oldest), &mut delivered)? == 0 {
+                return self.spool(point);
+            }
+            let mut pending = self.read_spool()?;
+            let sent = match pending.len() {
+                0 => 0,
+                len => 1 + self.send_in_order(&pending[1..len], &mut delivered)?,
+            };
+            // Forget the points sent before spooling anything else, so that they're never
+            // sent twice.
+            pending.drain(..sent);
+            self.write_spool(&pending)?;
+            self.clear_delivered()?;
+            if !pending.is_empty() {
+                return self.spool(point);
+            }
+        }
+
+        match self.client.send_point(point.clone()) {
+            Err(err) if err.is_transient() => self.spool(point),
+            Ok(receipt) if receipt.was_sent() => {
+                self.archive(&[point])?;
+                Ok(receipt)
+            }
+            result => result,
+        }
+    }
+
+    /// Sends the spooled data points, in order, and returns how many were sent. Points the
+    /// server rejects for another reason than a transient failure are dropped.
+    pub fn flush(&self) -> Result<usize, AnalyticsError> {
+        let _lock = self.lock.lock().unwrap();
+
+        let mut pending = self.read_spool()?;
+        let sent = self.send_in_order(&pending, &mut self.read_delivered()?)?;
+        pending.drain(..sent);
+        self.write_spool(&pending)?;
+        self.clear_delivered()?;
+        Ok(sent)
+    }
+
+    /// Sends the spooled data points in batches of `batch_size` with [`SyncClient::send_batch`],
+    /// with up to `max_in_flight` requests at once, and returns how many were sent, e.g. after
+    /// a long outage.
+    ///
+    /// The spool is streamed from the disk, a window of `max_in_flight` batches at a time, so
+    /// that the memory used stays the same whatever its size; only the unsent part is kept
+    /// once done. Replay stops at the first batch failing with a transient error: batches of the
+    /// same window sent after it are sent again by the next replay, which the server counts as
+    /// updates of the same rows. Batches the server rejects for another reason are dropped.
+    /// Batches with different keys are sent at the same time, but the ones sharing a key one
+    /// after the other, in the order of the spool, see [`SyncClient::send_stream_with`].
+    ///
+    /// Points spooled with [`BufferedClient::send_point`] meanwhile wait for the replay to end.
+    pub fn replay(&self, batch_size: usize, max_in_flight: usize) -> Result<usize, AnalyticsError> {
+        let _lock = self.lock.lock().unwrap();
+
+        self.repair()?;
+        let file = match File::open(&self.path) {
+            Ok(file) => file,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(0),
+            Err(err) => return Err(err.into()),
+        };
+        let batch_size = batch_size.max(1);
+        let max_in_flight = max_in_flight.max(1);
+        let mut reader = BufReader::new(file);
+        let mut delivered = self.read_delivered()?;
+        // Offset in the spool of the first point not handled yet.
+        let mut handled = 0;
+        let mut read = 0;
+        let mut sent = 0;
+
+        // Whatever happens, only keep the part of the spool that wasn't handled.
+        let mut replay = || -> Result<(), AnalyticsError> {
+            loop {
+                // Offsets at the end of each batch of the window.
+                let mut ends = Vec::with_capacity(max_in_flight);
+                let mut window = Vec::with_capacity(batch_size * max_in_flight);
+                while ends.len() < max_in_flight {
+                    let start = window.len();
+                    while window.len() - start < batch_size {
+                        let point = match self.next_pending(&mut reader, &mut read)? {
+                            Some(pending) => pending.point,
+                            None => break,
+                        };
+                        if !take_delivered(&mut delivered, &point) {
+                            window.push(point);
+                        }
+                    }
+                    if window.len() == start {
+                        break;
+                    }
+                    ends.push((window.len() - start, read));
+                }
+                if ends.is_empty() {
+                    // Only points already delivered were left.
+                    handled = read;
+                    break;
+                }
+
+                let keys: Vec<_> = window.iter().map(Key::of).collect();
+                let points = match self.archive {
+                    Some(_) => window.clone(),
+                    None => Vec::new(),
+                };
+                let results = self
+                    .client
+                    .send_stream_with(window, batch_size, max_in_flight);
+                let mut journal = Vec::new();
+                let mut archived = Vec::new();
+                let mut start = 0;
+                let mut stopped = false;
+                for (result, (len, end)) in results.into_iter().zip(ends) {
+                    let batch = start..start + len;
+                    start += len;
+                    match result {
+                        Err(err) if err.is_transient() => {
+                            stopped = true;
+                            break;
+                        }
+                        Ok(receipt) if receipt.was_sent() => {
+                            sent += len;
+                            journal.extend_from_slice(&keys[batch.clone()]);
+                            archived.extend(points.get(batch).unwrap_or_default());
+                        }
+                        _ => {}
+                    }
+                    handled = end;
+                }
+                self.record_delivered(&journal)?;
+                self.archive(archived)?;
+                if stopped {
+                    return Ok(());
+                }
+            }
+            Ok(())
+        };
+        let result = replay();
+        self.truncate_spool(handled)?;
+        self.clear_delivered()?;
+        result.map(|()| sent)
+    }
+
+    /// Removes the first `offset` bytes of the spool, copying the rest to a new file.
+    fn truncate_spool(&self, offset: u64) -> Result<(), AnalyticsError> {
+        if offset == 0 {
+            return Ok(());
+        }
+        self.len.store(UNKNOWN_LEN, Ordering::Relaxed);
+        let mut spool = File::open(&self.path)?;
+        if offset >= spool.metadata()?.len() {
+            fs::remove_file(&self.path)?;
+            return Ok(());
+        }
+
+        // Write a new file then rename it, so that a crash never leaves a truncated spool.
+        let tmp = self.path.with_extension("tmp");
+        spool.seek(SeekFrom::Start(offset))?;
+        io::copy(&mut spool, &mut File::create(&tmp)?)?;
+        fs::rename(&tmp, &self.path)?;
+        Ok(())
+    }
+
+    /// Sends again the data points of the archive delivered during `range`, e.g. after the
+    /// server was restored from a backup older than its end, in batches like
+    /// [`BufferedClient::replay`], and returns how many were sent. Requires
+    /// [`BufferedClient::with_archive`].
+    ///
+    /// Only the partitions of the days of `range` are read, one at a time. The points are sent
+    /// again even if the server still has them, which it counts as updates of the same rows;
+    /// they aren't archived again. Sending stops at the first failing batch of a day, returning
+    /// its error: replaying the same range again is harmless. Invalid lines, e.g. left
+    /// half-written by a crash, are skipped.
+    ///
+    /// With a [replay speed](BufferedClient::with_replay_speed), the points delivered at the
+    /// same second are sent together, waiting the time elapsed since the previous ones, divided
+    /// by the speed, before sending them.
+    pub fn replay_range(
+        &self,
+        range: Range<SystemTime>,
+        batch_size: usize,
+        max_in_flight: usize,
+    ) -> Result<usize, AnalyticsError> {
+        let dir = self.archive.as_ref().ok_or_else(|| {
+            AnalyticsError::local("replaying a range requires an archive, see `with_archive`")
+        })?;
+        let since_epoch = |time: SystemTime| time.duration_since(UNIX_EPOCH).unwrap_or_default();
+        let (start, end) = (since_epoch(range.start), since_epoch(range.end));
+        if start >= end {
+            return Ok(0);
+        }
+
+        let _lock = self.lock.lock().unwrap();
+        let mut sent = 0;
+        let mut previous = None;
+        for day in start.as_secs() / DAY..=(end.as_secs().saturating_sub(1)) / DAY {
+            let content = match fs::read_to_string(dir.join(partition_name(day))) {
+                Ok(content) => content,
+                Err(err) if err.kind() == io::ErrorKind::NotFound => continue,
+                Err(err) => return Err(err.into()),
+            };
+            let points = content
+                .lines()
+                .filter_map(|line| serde_json::from_str::<SpooledPoint>(line).ok())
+                .filter(|point| {
+                    point
+                        .sent_at
+                        .map(Duration::from_secs)
+                        .is_none_or(|sent_at| start <= sent_at && sent_at < end)
+                })
+                .filter_map(|point| {
+                    let sent_at = point.sent_at;
+                    point.into_point().map(|point| (sent_at, point))
+                });
+
+            // Without a replay speed, the points of the day are sent at once.
+            let mut runs: Vec<(Option<u64>, Vec<DataPoint>)> = Vec::new();
+            for (sent_at, point) in points {
+                match runs.last_mut() {
+                    Some((at, run)) if self.replay_speed == 0.0 || *at == sent_at => {
+                        run.push(point)
+                    }
+                    _ => runs.push((sent_at, vec![point])),
+                }
+            }
+            for (sent_at, run) in runs {
+                if let (Some(previous), Some(sent_at)) = (previous, sent_at) {
+                    if self.replay_speed > 0.0 {
+                        let elapsed = sent_at.saturating_sub(previous) as f64;
+                        thread::sleep(
+                            Duration::try_from_secs_f64(elapsed / self.replay_speed)
+                                .unwrap_or(Duration::MAX),
+                        );
+                    }
+                }
+                previous = sent_at.or(previous);
+
+                let lengths: Vec<_> = run.chunks(batch_size.max(1)).map(<[_]>::len).collect();
+                let results =
+                    self.client
+                        .send_stream_with(run, batch_size.max(1), max_in_flight.max(1));
+                for (result, len) in results.into_iter().zip(lengths) {
+                    if result?.was_sent() {
+                        sent += len;
+                    }
+                }
+            }
+        }
+        Ok(sent)
+    }
+
+    /// Appends data points just delivered to the partition of the current day of the archive,
+    /// if any.
+    fn archive<'a, I>(&self, points: I) -> Result<(), AnalyticsError>
+    where
+        I: IntoIterator<Item = &'a DataPoint>,
+    {
+        let dir = match &self.archive {
+            Some(dir) => dir,
+            None => return Ok(()),
+        };
+        let now = unix_time();
+        let mut content = String::new();
+        for point in points {
+            content += &line(point, None, Some(now));
+            content.push('\n');
+        }
+        if content.is_empty() {
+            return Ok(());
+        }
+        fs::create_dir_all(dir)?;
+        let mut partition = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(dir.join(partition_name(now.as_secs() / DAY)))?;
+        partition.write_all(content.as_bytes())?;
+        Ok(())
+    }
+
+    /// Number of data points waiting in the spool.
+    pub fn spooled(&self) -> Result<usize, AnalyticsError> {
+        let _lock = self.lock.lock().unwrap();
+        Ok(self.read_spool()?.len())
+    }
+
+    /// How long the oldest data point of the spool has been waiting, e.g. to alert on a
+    /// backlog which isn't draining, or `None` if the spool is empty or no point has a
+    /// spooling time.
+    pub fn oldest_pending_age(&self) -> Result<Option<Duration>, AnalyticsError> {
+        let _lock = self.lock.lock().unwrap();
+        let oldest = self
+            .read_spool()?
+            .iter()
+            .filter_map(|pending| pending.queued_at)
+            .min();
+        Ok(oldest.map(|queued_at| unix_time().saturating_sub(queued_at)))
+    }
+
+    /// Copies the spooled data points to the JSON Lines file at `path`, replacing it, and
+    /// returns how many were copied. The spool itself is kept as it is.
+    pub fn export_pending<P: AsRef<Path>>(&self, path: P) -> Result<usize, AnalyticsError> {
+        let _lock = self.lock.lock().unwrap();
+        let pending = self.read_spool()?;
+        fs::write(path, spool_content(&pending))?;
+        Ok(pending.len())
+    }
+
+    /// Appends the data points of the JSON Lines file at `path`, e.g. as written by
+    /// [`BufferedClient::export_pending`] on another machine, to the spool, and returns how
+    /// many were added. They're sent after the points already spooled.
+    ///
+    /// The whole file is checked first: nothing is imported if a line is invalid, and the
+    /// error gives its number. Points without a spooling time are stamped with the current
+    /// time. When the spool gets full, the oldest points are dropped.
+    pub fn import_pending<P: AsRef<Path>>(&self, path: P) -> Result<usize, AnalyticsError> {
+        let path = path.as_ref();
+        let content = fs::read_to_string(path)?;
+        let now = unix_time();
+        let mut imported = Vec::new();
+        for (number, line) in content.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let mut pending = parse_line(line).map_err(|reason| {
+                AnalyticsError::local(format!(
+                    "invalid line {} of {}: {}",
+                    number + 1,
+                    path.display(),
+                    reason
+                ))
+            })?;
+            pending.queued_at.get_or_insert(now);
+            imported.push(pending);
+        }
+
+        let _lock = self.lock.lock().unwrap();
+        let mut pending = self.read_spool()?;
+        let count = imported.len();
+        pending.extend(imported);
+        let excess = pending.len().saturating_sub(self.max_points);
+        pending.drain(..excess);
+        self.write_spool(&pending)?;
+        Ok(count)
+    }
+
+    /// Sends points until one fails with a transient error, returning how many were handled,
+    /// skipping those the delivery journal says were already sent.
+    fn send_in_order(
+        &self,
+        points: &[Pending],
+        delivered: &mut Delivered,
+    ) -> Result<usize, AnalyticsError> {
+        for (i, Pending { point, .. }) in points.iter().enumerate() {
+            if take_delivered(delivered, point) {
+                continue;
+            }
+            match self.client.send_point(point.clone()) {
+                Err(err) if err.is_transient() => return Ok(i),
+                Ok(receipt) if receipt.was_sent() => {
+                    self.record_delivered(&[Key::of(point)])?;
+                    self.archive(Some(point))?;
+                }
+                _ => {}
+            }
+        }
+        Ok(points.len())
+    }
+
+    /// The journal of the keys and timestamps of the spooled points sent since the spool was
+    /// last rewritten: the spool's path, with the `delivered` extension. It's a JSON Lines file:
+    ///
+    /// ```json
+    /// {"dimension1":"temperature","dimension2":"2024-01-01 12:00:00"}
+    /// ```
+    pub fn delivered_path(&self) -> PathBuf {
+        self.path.with_extension("delivered")
+    }
+
+    /// How many times each key and timestamp of the delivery journal was sent. Invalid lines,
+    /// e.g. left half-written by a crash, are ignored.
+    fn read_delivered(&self) -> Result<Delivered, AnalyticsError> {
+        let content = match fs::read_to_string(self.delivered_path()) {
+            Ok(content) => content,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Delivered::new()),
+            Err(err) => return Err(err.into()),
+        };
+        let mut delivered = Delivered::new();
+        for key in content
+            .lines()
+            .filter_map(|line| serde_json::from_str::<Key>(line).ok())
+        {
+            *delivered.entry(key).or_default() += 1;
+        }
+        Ok(delivered)
+    }
+
+    /// Appends the keys and timestamps of sent points to the delivery journal, on the disk
+    /// before returning.
+    fn record_delivered(&self, keys: &[Key]) -> Result<(), AnalyticsError> {
+        if keys.is_empty() {
+            return Ok(());
+        }
+        let mut content = String::new();
+        for key in keys {
+            content += &serde_json::to_string(key)?;
+            content.push('\n');
+        }
+        let mut journal = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.delivered_path())?;
+        journal.write_all(content.as_bytes())?;
+        journal.sync_data()?;
+        Ok(())
+    }
+
+    /// Empties the delivery journal, once the points it lists are out of the spool.
+    fn clear_delivered(&self) -> Result<(), AnalyticsError> {
+        match fs::remove_file(self.delivered_path()) {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err.into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Appends a data point to the spool, dropping its oldest tenth if it's full.
+    fn spool(&self, point: DataPoint) -> Result<Receipt, AnalyticsError> {
+        let payload = point.to_json();
+        let point = Pending {
+            point,
+            queued_at: Some(unix_time()),
+        };
+        let mut len = self.len.load(Ordering::Relaxed);
+        if len == UNKNOWN_LEN {
+            len = self.read_spool()?.len();
+        }
+        if len + 1 > self.max_points {
+            let mut pending = self.read_spool()?;
+            let excess = (pending.len() + 1).saturating_sub(self.max_points - self.max_points / 10);
+            pending.drain(..excess.min(pending.len()));
+            pending.push(point);
+            self.write_spool(&pending)?;
+        } else {
+            let mut file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&self.path)?;
+            file.write_all(&self.spool_content(std::slice::from_ref(&point)))?;
+            self.len.store(len + 1, Ordering::Relaxed);
+        }
+        Ok(Receipt::new(&payload, Outcome::Spooled))
+    }
+
+    /// The oldest data point of the spool, without reading the others.
+    fn oldest_pending(&self) -> Result<Option<Pending>, AnalyticsError> {
+        if self.len.load(Ordering::Relaxed) == 0 {
+            return Ok(None);
+        }
+        if self.format == SpoolFormat::JsonLines {
+            self.repair_tail()?;
+        }
+        let file = match File::open(&self.path) {
+            Ok(file) => file,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                self.len.store(0, Ordering::Relaxed);
+                return Ok(None);
+            }
+            Err(err) => return Err(err.into()),
+        };
+        self.next_pending(&mut BufReader::new(file), &mut 0)
+    }
+
+    /// The content of a spool holding `points`, in its format.
+    fn spool_content(&self, points: &[Pending]) -> Vec<u8> {
+        match self.format {
+            SpoolFormat::JsonLines => spool_content(points).into_bytes(),
+            SpoolFormat::Binary => {
+                let mut content = Vec::new();
+                for point in points {
+                    point.write_record(&mut content);
+                }
+                content
+            }
+        }
+    }
+
+    /// Reads the next data point of the spool from `reader`, moving the offset `read` past it.
+    fn next_pending<R: Read + Seek>(
+        &self,
+        reader: &mut BufReader<R>,
+        read: &mut u64,
+    ) -> Result<Option<Pending>, AnalyticsError> {
+        match self.format {
+            SpoolFormat::JsonLines => {
+                let mut line = String::new();
+                loop {
+                    line.clear();
+                    let len = reader.read_line(&mut line)?;
+                    if len == 0 {
+                        return Ok(None);
+                    }
+                    *read += len as u64;
+                    if !line.trim().is_empty() {
+                        return self.parse_line(line.trim_end()).map(Some);
+                    }
+                }
+            }
+            SpoolFormat::Binary => Ok(next_record(reader, read)?.map(|(_, pending)| pending)),
+        }
+    }
+
+    fn read_spool(&self) -> Result<Vec<Pending>, AnalyticsError> {
+        let points = if self.format == SpoolFormat::Binary {
+            let mut points = Vec::new();
+            self.repair_records(|pending| points.push(pending))?;
+            points
+        } else {
+            self.repair_tail()?;
+            let content = match fs::read_to_string(&self.path) {
+                Ok(content) => content,
+                Err(err) if err.kind() == io::ErrorKind::NotFound => String::new(),
+                Err(err) => return Err(err.into()),
+            };
+            content
+                .lines()
+                .filter(|line| !line.trim().is_empty())
+                .map(|line| self.parse_line(line))
+                .collect::<Result<_, _>>()?
+        };
+        self.len.store(points.len(), Ordering::Relaxed);
+        Ok(points)
+    }
+
+    /// Moves the invalid parts of the spool to the quarantine file.
+    fn repair(&self) -> Result<(), AnalyticsError> {
+        match self.format {
+            SpoolFormat::JsonLines => self.repair_tail(),
+            SpoolFormat::Binary => self.repair_records(|_| {}),
+        }
+    }
+
+    /// Moves the corrupted bytes of a binary spool, e.g. a record half-written or damaged by a
+    /// power loss, to the quarantine file, keeping the valid records around them, which are
+    /// given to `visit` in order.
+    fn repair_records<F: FnMut(Pending)>(&self, mut visit: F) -> Result<(), AnalyticsError> {
+        let file = match File::open(&self.path) {
+            Ok(file) => file,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(()),
+            Err(err) => return Err(err.into()),
+        };
+        let len = file.metadata()?.len();
+        let mut reader = BufReader::new(file);
+        let mut corrupted = Vec::new();
+        let (mut offset, mut end) = (0, 0);
+        while let Some((start, pending)) = next_record(&mut reader, &mut offset)? {
+            if start > end {
+                corrupted.push(end..start);
+            }
+            end = offset;
+            visit(pending);
+        }
+        if len > end {
+            corrupted.push(end..len);
+        }
+        if corrupted.is_empty() {
+            return Ok(());
+        }
+        self.len.store(UNKNOWN_LEN, Ordering::Relaxed);
+
+        // Write a new file then rename it, so that a crash never leaves a truncated spool.
+        let mut spool = File::open(&self.path)?;
+        let tmp = self.path.with_extension("tmp");
+        let mut repaired = File::create(&tmp)?;
+        let mut quarantine = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.quarantine_path())?;
+        let mut position = 0;
+        for range in corrupted {
+            io::copy(
+                &mut (&mut spool).take(range.start - position),
+                &mut repaired,
+            )?;
+            io::copy(
+                &mut (&mut spool).take(range.end - range.start),
+                &mut quarantine,
+            )?;
+            position = range.end;
+        }
+        io::copy(&mut spool, &mut repaired)?;
+        fs::rename(&tmp, &self.path)?;
+        Ok(())
+    }
+
+    /// Moves an invalid last line of the spool, e.g. left half-written by a crash, to the
+    /// quarantine file, so that it doesn't block the points before it and new points aren't
+    /// appended to it.
+    fn repair_tail(&self) -> Result<(), AnalyticsError> {
+        let mut file = match OpenOptions::new().read(true).write(true).open(&self.path) {
+            Ok(file) => file,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(()),
+            Err(err) => return Err(err.into()),
+        };
+        let start = file.metadata()?.len().saturating_sub(MAX_LINE);
+        file.seek(SeekFrom::Start(start))?;
+        let mut tail = Vec::new();
+        file.read_to_end(&mut tail)?;
+
+        let end = tail
+            .iter()
+            .rposition(|byte| !byte.is_ascii_whitespace())
+            .map_or(0, |end| end + 1);
+        let line_start = match tail[..end].iter().rposition(|&byte| byte == b'\n') {
+            Some(newline) => newline + 1,
+            None if start == 0 => 0,
+            // A line too long to be a data point: reported as corrupted when read.
+            None => return Ok(()),
+        };
+        let line = &tail[line_start..end];
+        let valid = std::str::from_utf8(line).is_ok_and(|line| parse_line(line).is_ok());
+        if line.is_empty() || valid {
+            return Ok(());
+        }
+        self.len.store(UNKNOWN_LEN, Ordering::Relaxed);
+
+        let mut quarantine = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.quarantine_path())?;
+        quarantine.write_all(line)?;
+        quarantine.write_all(b"\n")?;
+        file.set_len(start + line_start as u64)?;
+        Ok(())
+    }
+
+    /// The file invalid lines found at the end of the spool, or the corrupted bytes of a binary
+    /// spool, are moved to: the spool's path, with the `corrupt` extension.
+    pub fn quarantine_path(&self) -> PathBuf {
+        self.path.with_extension("corrupt")
+    }
+
+    fn parse_line(&self, line: &str) -> Result<Pending, AnalyticsError> {
+        parse_line(line).map_err(|reason| {
+            AnalyticsError::local(format!(
+                "corrupted spool {}: {}",
+                self.path.display(),
+                reason
+            ))
+        })
+    }
+
+    fn write_spool(&self, points: &[Pending]) -> Result<(), AnalyticsError> {
+        self.len.store(UNKNOWN_LEN, Ordering::Relaxed);
+        if points.is_empty() {
+            match fs::remove_file(&self.path) {
+                Err(err) if err.kind() != io::ErrorKind::NotFound => return Err(err.into()),
+                _ => {}
+            }
+            self.len.store(0, Ordering::Relaxed);
+            return Ok(());
+        }
+
+        // Write a new file then rename it, so that a crash never leaves a truncated spool.
+        let tmp = self.path.with_extension("tmp");
+        fs::write(&tmp, self.spool_content(points))?;
+        fs::rename(&tmp, &self.path)?;
+        self.len.store(points.len(), Ordering::Relaxed);
+        Ok(())
+    }
+}
+
+/// Time elapsed since the Unix epoch.
+fn unix_time() -> Duration {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+}
+
+/// The key and timestamp of a data point, as listed in the delivery journal.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+struct Key {
+    dimension1: String,
+    dimension2: String,
+}
+
+impl Key {
+    fn of(point: &DataPoint) -> Self {
+        Self {
+            dimension1: point.dimension1.clone(),
+            dimension2: point.dimension2.clone(),
+        }
+    }
+}
+
+/// How many times each key and timestamp of the delivery journal was sent.
+type Delivered = HashMap<Key, usize>;
+
+/// Whether `point` was already sent according to the delivery journal, counting it as skipped.
+fn take_delivered(delivered: &mut Delivered, point: &DataPoint) -> bool {
+    if delivered.is_empty() {
+        return false;
+    }
+    match delivered.get_mut(&Key::of(point)) {
+        Some(count) if *count > 0 => {
+            *count -= 1;
+            true
+        }
+        _ => false,
+    }
+}
+
+/// A data point of the spool, with when it was spooled, if known.
+struct Pending {
+    point: DataPoint,
+    queued_at: Option<Duration>,
+}
+
+impl Pending {
+    fn to_line(&self) -> String {
+        line(&self.point, self.queued_at, None)
+    }
+}
+
+/// The line of the spool or archive holding `point`.
+fn line(point: &DataPoint, queued_at: Option<Duration>, sent_at: Option<Duration>) -> String {
+    let line = SpooledLine {
+        dimension1: &point.dimension1,
+        dimension2: &point.dimension2,
+        dimension3: point.dimension3.to_string(),
+        queued_at: queued_at.map(|time| time.as_secs()),
+        sent_at: sent_at.map(|time| time.as_secs()),
+    };
+    // Serializing strings and integers can't fail.
+    serde_json::to_string(&line).unwrap_or_default()
+}
+
+/// The name of the file of the archive partition of `day`, counted from the Unix epoch, e.g.
+/// `2024-01-01.jsonl`.
+fn partition_name(day: u64) -> String {
+    // Civil date from the number of days, see http://howardhinnant.github.io/date_algorithms.html.
+    let days = day + 719_468;
+    let era = days / 146_097;
+    let day_of_era = days % 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day_of_month = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = year_of_era + era * 400 + u64::from(month <= 2);
+    format!("{:04}-{:02}-{:02}.jsonl", year, month, day_of_month)
+}
+
+impl Pending {
+    /// Appends the binary record of the point to `out`, see [`SpoolFormat::Binary`].
+    fn write_record(&self, out: &mut Vec<u8>) {
+        let mut payload = Vec::new();
+        for text in [&self.point.dimension1, &self.point.dimension2] {
+            payload.extend_from_slice(&(text.len() as u32).to_le_bytes());
+            payload.extend_from_slice(text.as_bytes());
+        }
+        payload.extend_from_slice(&self.point.dimension3.to_le_bytes());
+        let queued_at = self.queued_at.map_or(0, |time| time.as_secs());
+        payload.extend_from_slice(&queued_at.to_le_bytes());
+
+        out.extend_from_slice(&RECORD_MAGIC);
+        out.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+        out.extend_from_slice(&payload);
+        out.extend_from_slice(&crc32(&payload).to_le_bytes());
+    }
+
+    /// Parses the payload of a binary record.
+    fn from_payload(mut payload: &[u8]) -> Option<Self> {
+        let dimension1 = take_text(&mut payload)?;
+        let dimension2 = take_text(&mut payload)?;
+        let value = f64::from_le_bytes(take(&mut payload)?);
+        let queued_at = u64::from_le_bytes(take(&mut payload)?);
+        if !payload.is_empty() {
+            return None;
+        }
+        Some(Self {
+            point: DataPoint::new(dimension1, dimension2, value),
+            queued_at: Some(Duration::from_secs(queued_at)).filter(|time| !time.is_zero()),
+        })
+    }
+}
+
+/// Removes the first `N` bytes of `bytes`.
+fn take<const N: usize>(bytes: &mut &[u8]) -> Option<[u8; N]> {
+    if bytes.len() < N {
+        return None;
+    }
+    let (head, rest) = bytes.split_at(N);
+    *bytes = rest;
+    let mut array = [0; N];
+    array.copy_from_slice(head);
+    Some(array)
+}
+
+/// Removes a string and its length from the start of `bytes`.
+fn take_text(bytes: &mut &[u8]) -> Option<String> {
+    let len = u32::from_le_bytes(take(bytes)?) as usize;
+    if bytes.len() < len {
+        return None;
+    }
+    let (text, rest) = bytes.split_at(len);
+    *bytes = rest;
+    String::from_utf8(text.to_vec()).ok()
+}
+
+/// Marks the start of each record of a binary spool.
+const RECORD_MAGIC: [u8; 2] = [0xA5, 0x5A];
+
+/// The CRC32 of `bytes`, as used by gzip. Records are small, so computing it bit by bit is
+/// fast enough.
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in bytes {
+        crc ^= u32::from(byte);
+        for _ in 0..8 {
+            crc = (crc >> 1) ^ (0xEDB8_8320 & (crc & 1).wrapping_neg());
+        }
+    }
+    !crc
+}
+
+/// Reads the next valid record of a binary spool from `reader`, at `offset`, skipping
+/// corrupted bytes; returns where it starts with its point, and moves `offset` past it.
+fn next_record<R: Read + Seek>(
+    reader: &mut BufReader<R>,
+    offset: &mut u64,
+) -> io::Result<Option<(u64, Pending)>> {
+    loop {
+        let (record, consumed) = read_record(reader)?;
+        match record {
+            Record::End => return Ok(None),
+            Record::Valid(pending) => {
+                let start = *offset;
+                *offset += consumed;
+                return Ok(Some((start, pending)));
+            }
+            Record::Corrupted => {
+                // Look for the next record from the following byte.
+                *offset += 1;
+                reader.seek_relative(1 - consumed as i64)?;
+            }
+        }
+    }
+}
+
+enum Record {
+    Valid(Pending),
+    Corrupted,
+    End,
+}
+
+/// Reads a record of a binary spool, returning the number of bytes consumed with it.
+fn read_record<R: Read>(reader: &mut R) -> io::Result<(Record, u64)> {
+    let mut header = [0; 6];
+    let read = read_full(reader, &mut header)?;
+    if read == 0 {
+        return Ok((Record::End, 0));
+    }
+    let mut consumed = read as u64;
+    if read < header.len() || header[..2] != RECORD_MAGIC {
+        return Ok((Record::Corrupted, consumed));
+    }
+    let len = u32::from_le_bytes([header[2], header[3], header[4], header[5]]) as u64;
+    if len > MAX_LINE {
+        return Ok((Record::Corrupted, consumed));
+    }
+
+    let mut rest = vec![0; len as usize + 4];
+    let read = read_full(reader, &mut rest)?;
+    consumed += read as u64;
+    if read < rest.len() {
+        return Ok((Record::Corrupted, consumed));
+    }
+    let (payload, crc) = rest.split_at(len as usize);
+    if crc32(payload).to_le_bytes() != crc {
+        return Ok((Record::Corrupted, consumed));
+    }
+    match Pending::from_payload(payload) {
+        Some(pending) => Ok((Record::Valid(pending), consumed)),
+        None => Ok((Record::Corrupted, consumed)),
+    }
+}
+
+/// Reads until `buf` is full or the end of `reader`, returning the number of bytes read.
+fn read_full<R: Read>(reader: &mut R, buf: &mut [u8]) -> io::Result<usize> {
+    let mut read = 0;
+    while read < buf.len() {
+        match reader.read(&mut buf[read..]) {
+            Ok(0) => break,
+            Ok(len) => read += len,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(read)
+}
+
+/// The lines of a spool holding `points`.
+fn spool_content(points: &[Pending]) -> String {
+    let mut content = String::new();
+    for point in points {
+        content += &point.to_line();
+        content.push('\n');
+    }
+    content
+}
+
+/// Parses a line of a spool, returning why it's invalid otherwise.
+fn parse_line(line: &str) -> Result<Pending, String> {
+    let point: SpooledPoint = serde_json::from_str(line).map_err(|err| err.to_string())?;
+    let queued_at = point.queued_at.map(Duration::from_secs);
+    let point = point
+        .into_point()
+        .ok_or_else(|| format!("invalid value in {}", line))?;
+    Ok(Pending { point, queued_at })
+}
+
+/// A line of the spool, as written.
+#[derive(Serialize)]
+struct SpooledLine<'a> {
+    dimension1: &'a str,
+    dimension2: &'a str,
+    dimension3: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    queued_at: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sent_at: Option<u64>,
+}
+
+/// A line of the spool, as read, with the value as a string or a number.
+#[derive(Deserialize)]
+struct SpooledPoint {
+    dimension1: String,
+    dimension2: String,
+    dimension3: SpooledValue,
+    #[serde(default)]
+    queued_at: Option<u64>,
+    #[serde(default)]
+    sent_at: Option<u64>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum SpooledValue {
+    Number(f64),
+    String(String),
+}
+
+impl SpooledPoint {
+    fn into_point(self) -> Option<DataPoint> {
+        let value = match self.dimension3 {
+            SpooledValue::Number(value) => value,
+            SpooledValue::String(value) => value.parse().ok()?,
+        };
+        Some(DataPoint::new(self.dimension1, self.dimension2, value))
+    }
+}
@@ -0,0 +1,149 @@
+use core::fmt;
+use std::error::Error;
+
+use crate::{AnalyticsError, DataPoint, SyncClient};
+
+/// A set of data points staged for sending, created with [`SyncClient::begin`].
+///
+/// Nothing hits the network until [`Staging::commit`], so the points can be reviewed and
+/// validated first.
+pub struct Staging<'a> {
+    client: &'a SyncClient,
+    points: Vec<DataPoint>,
+    batch_size: usize,
+}
+
+impl<'a> Staging<'a> {
+    pub(crate) fn new(client: &'a SyncClient) -> Self {
+        Self {
+            client,
+            points: Vec::new(),
+            batch_size: client.batch_size,
+        }
+    }
+
+    /// Stages a data point, with the same arguments as [`SyncClient::send_data`].
+    pub fn add<S: Into<String>, F: Into<f64>>(
+        &mut self,
+        dimension1: S,
+        dimension2: S,
+        dimension3: F,
+    ) -> &mut Self {
+        self.points
+            .push(DataPoint::new(dimension1, dimension2, dimension3));
+        self
+    }
+
+    /// Stages an existing data point.
+    pub fn add_point(&mut self, point: DataPoint) -> &mut Self {
+        self.points.push(point);
+        self
+    }
+
+    /// Returns the data points staged so far, in sending order.
+    pub fn preview(&self) -> Vec<DataPoint> {
+        self.points.clone()
+    }
+
+    /// Drops all the staged data points.
+    pub fn clear(&mut self) {
+        self.points.clear();
+    }
+
+    /// Number of data points sent per request by [`Staging::commit`]. Defaults to the
+    /// [batch size](crate::SyncClientBuilder::batch_size) of the client, 500 by default.
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    /// Sends all the staged data points, in order, in batches of
+    /// [`Staging::with_batch_size`] points sent with [`SyncClient::send_batch`], stopping at the
+    /// first failure.
+    ///
+    /// The commit is all or nothing, as far as possible: when a batch fails, the rows of the
+    /// batches already sent are deleted, in one request, and the returned [`CommitError`] tells
+    /// which points were sent and whether deleting them failed. Deleting a row also deletes the
+    /// value its point replaced, if any. The 1.0 API can't delete rows, and sends the points of
+    /// a batch one at a time: the ones of the failing batch sent before the failure are kept.
+    #[allow(clippy::result_large_err)]
+    pub fn commit(self) -> Result<(), CommitError> {
+        let mut batches = self.points.chunks(self.batch_size);
+        let mut sent = Vec::new();
+
+        while let Some(batch) = batches.next() {
+            if let Err(source) = self.client.send_batch(batch) {
+                let rollback = self.rollback(&sent);
+                return Err(CommitError {
+                    sent,
+                    failed: batch.to_vec(),
+                    unsent: batches.flatten().cloned().collect(),
+                    source,
+                    rollback,
+                });
+            }
+            sent.extend_from_slice(batch);
+        }
+
+        Ok(())
+    }
+
+    /// Deletes the rows of the data points already sent by a failed commit.
+    fn rollback(&self, sent: &[DataPoint]) -> Result<(), AnalyticsError> {
+        if sent.is_empty() {
+            return Ok(());
+        }
+        let collection = self.client.collection;
+        let rows = self.client.stored_rows(sent, collection)?;
+        let rows: Vec<_> = rows
+            .iter()
+            .map(|(dimension1, dimension2)| (dimension1.as_str(), dimension2.as_str()))
+            .collect();
+        self.client.delete_rows(&rows, collection)
+    }
+}
+
+/// Error of a failed [`Staging::commit`].
+pub struct CommitError {
+    /// The data points that were successfully sent before the failure, then deleted unless
+    /// `rollback` failed.
+    pub sent: Vec<DataPoint>,
+    /// The batch of data points that couldn't be sent.
+    pub failed: Vec<DataPoint>,
+    /// The data points that weren't tried because of the failure.
+    pub unsent: Vec<DataPoint>,
+    /// The error that happened when sending `failed`.
+    pub source: AnalyticsError,
+    /// The result of deleting the rows of `sent`: if it failed, they're still stored.
+    pub rollback: Result<(), AnalyticsError>,
+}
+
+impl fmt::Debug for CommitError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+impl fmt::Display for CommitError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "commit failed after sending {} data points, {} left unsent: {}",
+            self.sent.len(),
+            self.failed.len() + self.unsent.len(),
+            self.source
+        )?;
+        match &self.rollback {
+            Err(err) if !self.sent.is_empty() => {
+                write!(f, " (deleting the points sent failed: {})", err)
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+impl Error for CommitError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(&self.source)
+    }
+}
@@ -0,0 +1,82 @@
+use core::fmt;
+use std::sync::atomic::Ordering;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde_json::{json, Value};
+
+use crate::{RetryPolicy, SyncClient};
+
+/// A snapshot of the internal state of a client, as returned by
+/// [`SyncClient::dump_state`], e.g. to attach to a bug report.
+///
+/// Like [`ClientDescription`](crate::ClientDescription), it never contains secrets.
+#[derive(Clone, Debug)]
+pub struct ClientState {
+    /// Number of sends that failed in a row since the last successful one.
+    pub consecutive_failures: u32,
+    /// Maximum number of attempts of a request, 1 if failed requests aren't retried.
+    pub max_attempts: u32,
+    /// Keys with values held back by [`SyncClient::with_min_interval`], with the number of
+    /// values waiting.
+    pub held_back: Vec<(String, u32)>,
+    /// Keys successfully sent, with the time of their last send, sorted by key. Keys are the
+    /// ones sent to the server, after any key template is applied.
+    pub last_sends: Vec<(String, SystemTime)>,
+}
+
+impl ClientState {
+    /// Serializes the snapshot to a JSON value, with times as seconds since the Unix epoch.
+    pub fn to_json(&self) -> Value {
+        let held_back: serde_json::Map<String, Value> = self
+            .held_back
+            .iter()
+            .map(|(key, count)| (key.clone(), (*count).into()))
+            .collect();
+        let last_sends: serde_json::Map<String, Value> = self
+            .last_sends
+            .iter()
+            .map(|(key, time)| {
+                let secs = time
+                    .duration_since(UNIX_EPOCH)
+                    .map(|d| d.as_secs_f64())
+                    .unwrap_or(0.0);
+                (key.clone(), secs.into())
+            })
+            .collect();
+
+        json!({
+            "consecutive_failures": self.consecutive_failures,
+            "max_attempts": self.max_attempts,
+            "held_back": held_back,
+            "last_sends": last_sends,
+        })
+    }
+}
+
+impl fmt::Display for ClientState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.to_json())
+    }
+}
+
+impl SyncClient {
+    /// Takes a snapshot of the client's internal state: failure counter, retry settings, values
+    /// held back and last send time of every key.
+    pub fn dump_state(&self) -> ClientState {
+        let mut last_sends: Vec<(String, SystemTime)> = self
+            .last_sends
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(key, time)| (key.clone(), *time))
+            .collect();
+        last_sends.sort();
+
+        ClientState {
+            consecutive_failures: self.consecutive_failures.load(Ordering::Relaxed),
+            max_attempts: self.retry.as_ref().map_or(1, RetryPolicy::max_attempts),
+            held_back: self.coalescer.held_back(),
+            last_sends,
+        }
+    }
+}
@@ -0,0 +1,61 @@
+#[cfg(feature = "chrono")]
+use chrono::{DateTime, Utc};
+
+#[cfg(feature = "chrono")]
+use crate::{AnalyticsError, Receipt, SyncClient};
+
+/// Summary of the values of a metric over a window, see
+/// [`SyncClient::send_timeline_stats`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Stats {
+    pub min: f64,
+    pub avg: f64,
+    pub max: f64,
+}
+
+impl Stats {
+    /// Computes the statistics of the given samples, or `None` if there are none.
+    pub fn from_samples(samples: &[f64]) -> Option<Self> {
+        if samples.is_empty() {
+            return None;
+        }
+
+        let mut min = f64::INFINITY;
+        let mut max = f64::NEG_INFINITY;
+        let mut sum = 0.0;
+        for &sample in samples {
+            min = min.min(sample);
+            max = max.max(sample);
+            sum += sample;
+        }
+
+        Some(Self {
+            min,
+            avg: sum / samples.len() as f64,
+            max,
+        })
+    }
+}
+
+#[cfg(feature = "chrono")]
+impl SyncClient {
+    /// Sends the min, average and max of a metric for the given `time`, as three timeline keys
+    /// `{key}_min`, `{key}_avg` and `{key}_max`, so they can be displayed as a band.
+    ///
+    /// Returns the receipts of the three sends, in that order.
+    ///
+    /// Requires the `chrono` feature.
+    pub fn send_timeline_stats<S: Into<String>>(
+        &self,
+        key: S,
+        time: DateTime<Utc>,
+        stats: Stats,
+    ) -> Result<[Receipt; 3], AnalyticsError> {
+        let key = key.into();
+        Ok([
+            self.send_timeline_data(format!("{}_min", key), time, stats.min)?,
+            self.send_timeline_data(format!("{}_avg", key), time, stats.avg)?,
+            self.send_timeline_data(format!("{}_max", key), time, stats.max)?,
+        ])
+    }
+}
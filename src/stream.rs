@@ -0,0 +1,209 @@
+#[cfg(feature = "blocking")]
+use std::collections::HashSet;
+use std::ops::Range;
+#[cfg(feature = "blocking")]
+use std::sync::{mpsc, Condvar, Mutex};
+#[cfg(feature = "blocking")]
+use std::thread;
+use std::time::Duration;
+#[cfg(feature = "blocking")]
+use std::time::Instant;
+
+#[cfg(feature = "blocking")]
+use crate::SyncClient;
+use crate::{AnalyticsError, DataPoint, Receipt};
+
+/// Default number of data points per request of [`SyncClient::send_stream`].
+pub(crate) const DEFAULT_CHUNK_SIZE: usize = 500;
+
+/// Default number of concurrent requests of [`SyncClient::send_stream`].
+pub(crate) const DEFAULT_IN_FLIGHT: usize = 4;
+
+/// What happened to a large batch sent as several concurrent requests, see
+/// [`SyncClient::send_batch_parallel`].
+#[derive(Debug)]
+pub struct BatchReport {
+    /// Number of requests the batch was split into.
+    pub chunks: usize,
+    /// Number of data points of the chunks the server accepted.
+    pub rows_sent: usize,
+    /// Receipts of the chunks that didn't fail, in order.
+    pub receipts: Vec<Receipt>,
+    /// The chunks that failed, in order, by the positions of their data points in the batch.
+    pub failures: Vec<(Range<usize>, AnalyticsError)>,
+    /// How long sending the batch took, zero with the asynchronous client on `wasm32`, which
+    /// has no clock.
+    pub duration: Duration,
+}
+
+impl BatchReport {
+    /// Collects the results of the chunks of `chunk_size` points of a batch, in order.
+    pub(crate) fn new(
+        results: Vec<Result<Receipt, AnalyticsError>>,
+        chunk_size: usize,
+        len: usize,
+        duration: Duration,
+    ) -> Self {
+        let mut report = Self {
+            chunks: results.len(),
+            rows_sent: 0,
+            receipts: Vec::new(),
+            failures: Vec::new(),
+            duration,
+        };
+        for (index, result) in results.into_iter().enumerate() {
+            let rows = index * chunk_size..((index + 1) * chunk_size).min(len);
+            match result {
+                Ok(receipt) => {
+                    if receipt.was_sent() {
+                        report.rows_sent += rows.len();
+                    }
+                    report.receipts.push(receipt);
+                }
+                Err(err) => report.failures.push((rows, err)),
+            }
+        }
+        report
+    }
+
+    /// Whether no chunk failed.
+    pub fn is_complete(&self) -> bool {
+        self.failures.is_empty()
+    }
+
+    /// The data points of the chunks that failed, e.g. to send them again.
+    pub fn failed_points<'a>(&self, points: &'a [DataPoint]) -> Vec<&'a DataPoint> {
+        self.failures
+            .iter()
+            .flat_map(|(rows, _)| &points[rows.clone()])
+            .collect()
+    }
+}
+
+#[cfg(feature = "blocking")]
+impl SyncClient {
+    /// Sends a large batch, e.g. a backfill of historical rows, split into chunks of
+    /// `chunk_size` data points sent with [`SyncClient::send_batch`] by `workers` threads at
+    /// once, and reports what happened to each chunk.
+    ///
+    /// A failed chunk doesn't stop the others: the failures are collected in the order of the
+    /// batch. The server must accept concurrent writes to the dataset, which Analytics does; the
+    /// points of a key are still sent in order, see [`SyncClient::send_stream_with`].
+    pub fn send_batch_parallel(
+        &self,
+        points: &[DataPoint],
+        chunk_size: usize,
+        workers: usize,
+    ) -> BatchReport {
+        let started = Instant::now();
+        let chunk_size = chunk_size.max(1);
+        let results = self.send_stream_with(points.iter().cloned(), chunk_size, workers);
+        BatchReport::new(results, chunk_size, points.len(), started.elapsed())
+    }
+
+    /// Sends the data points of `points` as they're produced, e.g. by a lazy pipeline, in
+    /// batches of the [size of the client](crate::SyncClientBuilder::batch_size), 500 by
+    /// default, with up to 4 requests in flight; see [`SyncClient::send_stream_with`].
+    pub fn send_stream<I>(&self, points: I) -> Vec<Result<Receipt, AnalyticsError>>
+    where
+        I: IntoIterator<Item = DataPoint>,
+    {
+        self.send_stream_with(points, self.batch_size, DEFAULT_IN_FLIGHT)
+    }
+
+    /// Sends the data points of `points` in chunks of `chunk_size` with
+    /// [`SyncClient::send_batch`], with up to `max_in_flight` requests at once from as many
+    /// threads, and returns the result of each chunk, in order.
+    ///
+    /// The iterator is only advanced while a request slot is free, so that at most
+    /// `max_in_flight + 1` chunks are in memory at any time, however long it is. A failed chunk
+    /// doesn't stop the following ones.
+    ///
+    /// Chunks with different keys are sent at the same time, but a chunk isn't sent before the
+    /// requests of the earlier chunks with any of its keys are done: the points of a key reach
+    /// the server in the order of the iterator, e.g. so that the last value of a counter wins.
+    /// The following chunks with the keys of a failed chunk are still sent.
+    pub fn send_stream_with<I>(
+        &self,
+        points: I,
+        chunk_size: usize,
+        max_in_flight: usize,
+    ) -> Vec<Result<Receipt, AnalyticsError>>
+    where
+        I: IntoIterator<Item = DataPoint>,
+    {
+        let chunk_size = chunk_size.max(1);
+        // Handing chunks over without buffering makes the producer wait for a free sender.
+        let (chunks, pending) = mpsc::sync_channel::<(usize, Vec<DataPoint>)>(0);
+        let pending = Mutex::new(pending);
+        let (done, results) = mpsc::channel();
+        // Keys of the chunks being sent, and a notification when one is done.
+        let in_flight = (Mutex::new(HashSet::<String>::new()), Condvar::new());
+
+        thread::scope(|scope| {
+            for _ in 0..max_in_flight.max(1) {
+                let (pending, in_flight) = (&pending, &in_flight);
+                let done = done.clone();
+                scope.spawn(move || loop {
+                    let next = pending.lock().unwrap().recv();
+                    let (index, chunk) = match next {
+                        Ok(next) => next,
+                        Err(_) => break,
+                    };
+                    // Releases the keys even if sending panics, not to block the producer.
+                    let _sending = Sending {
+                        in_flight,
+                        chunk: &chunk,
+                    };
+                    let _ = done.send((index, self.send_batch(&chunk)));
+                });
+            }
+
+            let mut points = points.into_iter();
+            for index in 0.. {
+                let chunk: Vec<_> = points.by_ref().take(chunk_size).collect();
+                if chunk.is_empty() {
+                    break;
+                }
+                let mut keys = in_flight.0.lock().unwrap();
+                while chunk.iter().any(|point| keys.contains(&point.dimension1)) {
+                    keys = in_flight.1.wait(keys).unwrap();
+                }
+                keys.extend(chunk.iter().map(|point| point.dimension1.clone()));
+                drop(keys);
+                if chunks.send((index, chunk)).is_err() {
+                    break;
+                }
+            }
+            // Disconnecting the channel stops the senders once they're done.
+            drop(chunks);
+        });
+        drop(done);
+
+        let mut results: Vec<_> = results.into_iter().collect();
+        results.sort_by_key(|&(index, _)| index);
+        results.into_iter().map(|(_, result)| result).collect()
+    }
+}
+
+/// A chunk being sent by [`SyncClient::send_stream_with`], whose keys are released once done.
+#[cfg(feature = "blocking")]
+struct Sending<'a> {
+    in_flight: &'a (Mutex<HashSet<String>>, Condvar),
+    chunk: &'a [DataPoint],
+}
+
+#[cfg(feature = "blocking")]
+impl Drop for Sending<'_> {
+    fn drop(&mut self) {
+        let mut keys = self
+            .in_flight
+            .0
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        for point in self.chunk {
+            keys.remove(&point.dimension1);
+        }
+        self.in_flight.1.notify_all();
+    }
+}
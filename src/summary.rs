@@ -0,0 +1,116 @@
+use core::fmt;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::notify::Target;
+use crate::SyncClient;
+
+/// What a bulk operation changed, e.g. an import or a backfill, reported to the targets set with
+/// [`SyncClient::with_summary_target`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BulkSummary {
+    /// Human-readable name of the operation, e.g. `"import of readings.csv"`.
+    pub operation: String,
+    /// Number of rows added.
+    pub added: u64,
+    /// Number of rows updated.
+    pub updated: u64,
+    /// Number of rows deleted.
+    pub deleted: u64,
+    /// Number of rows or requests that failed.
+    pub failures: u64,
+    /// How long the operation took.
+    pub duration: Duration,
+}
+
+impl BulkSummary {
+    /// An empty summary for the given operation, to fill in.
+    pub fn new<S: Into<String>>(operation: S) -> Self {
+        Self {
+            operation: operation.into(),
+            added: 0,
+            updated: 0,
+            deleted: 0,
+            failures: 0,
+            duration: Duration::default(),
+        }
+    }
+}
+
+impl fmt::Display for BulkSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: {} added, {} updated, {} deleted, {} failures, in {:.1}s",
+            self.operation,
+            self.added,
+            self.updated,
+            self.deleted,
+            self.failures,
+            self.duration.as_secs_f64()
+        )
+    }
+}
+
+/// Where bulk operation summaries get delivered.
+#[derive(Clone, Debug)]
+pub enum SummaryTarget {
+    /// Printed on the standard output.
+    Stdout,
+    /// Appended as a line to the given file.
+    File(PathBuf),
+    /// Posted to the Nextcloud Talk room with the given token.
+    TalkRoom(String),
+    /// Sent as a Nextcloud notification to the given user. Requires the client's user to be an
+    /// administrator.
+    AdminNotification(String),
+}
+
+impl SyncClient {
+    /// Report a summary of each bulk operation to `target`, in addition to the previous targets.
+    ///
+    /// Imports through [`SyncClient::import_via_files`], data load executions and
+    /// [`BackfillPlan`](crate::BackfillPlan) runs are reported automatically; other operations
+    /// can be reported with [`SyncClient::report_summary`].
+    pub fn with_summary_target(mut self, target: SummaryTarget) -> Self {
+        self.summary_targets.push(target);
+        self
+    }
+
+    /// Delivers `summary` to all the summary targets.
+    ///
+    /// Reporting is best effort: delivery failures are ignored.
+    pub fn report_summary(&self, summary: &BulkSummary) {
+        let message = format!("nextcloud_analytics_rs: {}", summary);
+        for target in &self.summary_targets {
+            match target {
+                SummaryTarget::Stdout => println!("{}", message),
+                SummaryTarget::File(path) => {
+                    let _ = OpenOptions::new()
+                        .create(true)
+                        .append(true)
+                        .open(path)
+                        .and_then(|mut file| writeln!(file, "{}", message));
+                }
+                SummaryTarget::TalkRoom(token) => {
+                    let target = Target::TalkRoom(token.clone());
+                    let _ = self.post_notification(&target, &message);
+                }
+                SummaryTarget::AdminNotification(user) => {
+                    let target = Target::AdminNotification(user.clone());
+                    let _ = self.post_notification(&target, &message);
+                }
+            }
+        }
+    }
+
+    fn post_notification(
+        &self,
+        target: &Target,
+        message: &str,
+    ) -> Result<(), crate::AnalyticsError> {
+        target.post(self, message)
+    }
+}
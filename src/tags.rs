@@ -0,0 +1,117 @@
+use std::fmt;
+use std::sync::Arc;
+
+use crate::SyncClient;
+#[cfg(feature = "chrono")]
+use crate::{AnalyticsError, Receipt};
+
+type EncodeFn = Arc<dyn Fn(&str, &[(&str, &str)]) -> String + Send + Sync>;
+
+/// How the tags of a key are encoded into the first dimension, see
+/// [`SyncClient::with_tag_encoding`].
+///
+/// The tags are sorted by name first, so that a key and its tags always give the same first
+/// dimension, whatever the order they are given in.
+#[derive(Clone, Default)]
+pub enum TagEncoding {
+    /// The tags in braces after the key, e.g. `temp{floor=1,room=kitchen}`, as the default
+    /// mapping of Prometheus samples, see `prometheus::default_key` (with the `prometheus`
+    /// feature).
+    #[default]
+    Labels,
+    /// The values of the tags after the key, all joined by the given separator, e.g.
+    /// `temp.1.kitchen` with `"."`.
+    Values(String),
+    /// Encoded by a function, given the key and the sorted tags.
+    Custom(EncodeFn),
+}
+
+impl TagEncoding {
+    /// Encodes the tags with a function, given the key and the tags sorted by name.
+    pub fn custom<F>(encode: F) -> Self
+    where
+        F: Fn(&str, &[(&str, &str)]) -> String + Send + Sync + 'static,
+    {
+        TagEncoding::Custom(Arc::new(encode))
+    }
+
+    /// The first dimension for `key` with the given tags; `key` itself if there's no tag.
+    pub fn encode(&self, key: &str, tags: &[(&str, &str)]) -> String {
+        if tags.is_empty() {
+            return key.to_string();
+        }
+        let mut tags = tags.to_vec();
+        tags.sort();
+
+        match self {
+            TagEncoding::Labels => {
+                let labels = tags
+                    .iter()
+                    .map(|(name, value)| format!("{}={}", name, value))
+                    .collect::<Vec<_>>()
+                    .join(",");
+                format!("{}{{{}}}", key, labels)
+            }
+            TagEncoding::Values(separator) => {
+                let mut encoded = key.to_string();
+                for (_, value) in &tags {
+                    encoded += separator;
+                    encoded += value;
+                }
+                encoded
+            }
+            TagEncoding::Custom(encode) => encode(key, &tags),
+        }
+    }
+}
+
+impl fmt::Debug for TagEncoding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TagEncoding::Labels => f.write_str("Labels"),
+            TagEncoding::Values(separator) => f.debug_tuple("Values").field(separator).finish(),
+            TagEncoding::Custom(_) => f.write_str("Custom(..)"),
+        }
+    }
+}
+
+impl SyncClient {
+    /// Encode the tags given to [`SyncClient::send_tagged`] and [`SyncClient::tagged_key`]
+    /// with the given strategy. Defaults to [`TagEncoding::Labels`].
+    pub fn with_tag_encoding(mut self, encoding: TagEncoding) -> Self {
+        self.tag_encoding = encoding;
+        self
+    }
+
+    /// The first dimension for `key` with the given tags, according to the client's
+    /// [tag encoding](SyncClient::with_tag_encoding), e.g. to pass it to the other send
+    /// methods.
+    ///
+    /// The key filter and the key template apply to the encoded key: `{metric}` expands to
+    /// the key with its tags.
+    pub fn tagged_key(&self, key: &str, tags: &[(&str, &str)]) -> String {
+        self.tag_encoding.encode(key, tags)
+    }
+
+    /// Sends timeline data for `key` with the given tags at the current time, like
+    /// [`SyncClient::send_timeline_now_data`], e.g. a temperature per room:
+    ///
+    /// ```no_run
+    /// # use nextcloud_analytics_rs::SyncClient;
+    /// let client = SyncClient::new("https://example.com/nextcloud", 3, "myself", "hunter2");
+    /// // Sent as `temp{room=kitchen}`.
+    /// client.send_tagged("temp", &[("room", "kitchen")], 21.5)?;
+    /// # Ok::<(), nextcloud_analytics_rs::AnalyticsError>(())
+    /// ```
+    ///
+    /// Requires the `chrono` feature.
+    #[cfg(feature = "chrono")]
+    pub fn send_tagged<F: Into<f64>>(
+        &self,
+        key: &str,
+        tags: &[(&str, &str)],
+        value: F,
+    ) -> Result<Receipt, AnalyticsError> {
+        self.send_timeline_now_data(self.tagged_key(key, tags), value)
+    }
+}
@@ -0,0 +1,137 @@
+use core::fmt;
+use std::collections::HashMap;
+use std::error::Error;
+
+/// A naming template for the first dimension, e.g. `"{hostname}/{metric}"`.
+///
+/// Variables are expanded at send time:
+///
+/// - `{metric}` is the key passed to the send method.
+/// - `{hostname}` is the name of the machine running the client, with the `hostname` feature.
+/// - `{env:NAME}` is the value of the environment variable `NAME`.
+/// - any other `{name}` is looked up in the custom variables set with [`Template::var`].
+///
+/// Literal braces can be written as `{{` and `}}`; an unmatched brace is an error.
+#[derive(Clone, Debug)]
+pub struct Template {
+    pattern: String,
+    vars: HashMap<String, String>,
+}
+
+impl Template {
+    /// Create a new template from the given pattern.
+    pub fn new<S: Into<String>>(pattern: S) -> Self {
+        Self {
+            pattern: pattern.into(),
+            vars: HashMap::new(),
+        }
+    }
+
+    /// Define a custom variable, available as `{name}` in the pattern.
+    pub fn var<K: Into<String>, V: Into<String>>(mut self, name: K, value: V) -> Self {
+        self.vars.insert(name.into(), value.into());
+        self
+    }
+
+    /// Returns the pattern of this template.
+    pub fn pattern(&self) -> &str {
+        &self.pattern
+    }
+
+    /// Expands the pattern for the given metric name.
+    pub fn expand(&self, metric: &str) -> Result<String, TemplateError> {
+        let mut result = String::with_capacity(self.pattern.len() + metric.len());
+        let mut chars = self.pattern.chars().peekable();
+
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    result.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    result.push('}');
+                }
+                '{' => {
+                    let mut name = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(c) => name.push(c),
+                            None => {
+                                return Err(TemplateError(format!(
+                                    "unclosed variable in template {:?}",
+                                    self.pattern
+                                )))
+                            }
+                        }
+                    }
+                    result += &self.lookup(&name, metric)?;
+                }
+                '}' => {
+                    return Err(TemplateError(format!(
+                        "unmatched `}}` in template {:?}, write `}}}}` for a literal brace",
+                        self.pattern
+                    )))
+                }
+                c => result.push(c),
+            }
+        }
+
+        Ok(result)
+    }
+
+    fn lookup(&self, name: &str, metric: &str) -> Result<String, TemplateError> {
+        if name == "metric" {
+            return Ok(metric.to_string());
+        }
+
+        #[cfg(feature = "hostname")]
+        if name == "hostname" {
+            return hostname::get()
+                .map(|name| name.to_string_lossy().into_owned())
+                .map_err(|err| TemplateError(format!("hostname used in template: {}", err)));
+        }
+        #[cfg(not(feature = "hostname"))]
+        if name == "hostname" {
+            return Err(TemplateError(
+                "hostname used in template: requires the `hostname` feature".to_string(),
+            ));
+        }
+
+        if let Some(var) = name.strip_prefix("env:") {
+            return std::env::var(var).map_err(|err| {
+                TemplateError(format!(
+                    "environment variable {:?} used in template: {}",
+                    var, err
+                ))
+            });
+        }
+
+        match self.vars.get(name) {
+            Some(value) => Ok(value.clone()),
+            None => Err(TemplateError(format!(
+                "unknown template variable {:?}",
+                name
+            ))),
+        }
+    }
+}
+
+/// An error happening when expanding a template.
+pub struct TemplateError(String);
+
+impl fmt::Debug for TemplateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl fmt::Display for TemplateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl Error for TemplateError {}
@@ -0,0 +1,163 @@
+//! Helpers to unit-test code sending data with a [`SyncClient`], without a Nextcloud server.
+//!
+//! ```
+//! use nextcloud_analytics_rs::testing::MockTransport;
+//!
+//! let mock = MockTransport::new();
+//! let client = mock.client(3);
+//! assert!(client.send_data("temperature", "kitchen", 21.5)?.was_sent());
+//!
+//! let sent = mock.sent_points();
+//! assert_eq!(sent.len(), 1);
+//! assert_eq!(sent[0].dimension1, "temperature");
+//! # Ok::<(), nextcloud_analytics_rs::AnalyticsError>(())
+//! ```
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+use reqwest as http;
+use serde_json::Value;
+
+use crate::{AnalyticsError, DataPoint, Request, Response, SyncClient, Transport};
+
+/// Body of the default response, as sent by the Analytics app when data was added.
+const SUCCESS: &str = r#"{"success":true,"message":"Data update successfull"}"#;
+
+#[derive(Default)]
+struct State {
+    responses: VecDeque<Response>,
+    requests: Vec<Request>,
+}
+
+/// A [`Transport`] recording the requests it's given, and answering them with queued responses,
+/// or with a success once the queue is empty.
+///
+/// Clones share the same requests and responses: keep one to inspect what a client sent after
+/// handing it another with [`SyncClient::with_transport`].
+#[derive(Clone, Default)]
+pub struct MockTransport {
+    state: Arc<Mutex<State>>,
+}
+
+impl MockTransport {
+    /// A transport without queued responses.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A client sending to `collection` of a fake server through this transport, with the
+    /// default settings.
+    pub fn client(&self, collection: u32) -> SyncClient {
+        SyncClient::new("https://nextcloud.invalid/", collection, "user", "password")
+            .with_transport(self.clone())
+    }
+
+    /// Answers the next request without a queued response with the given status and body,
+    /// e.g. to simulate an error.
+    pub fn respond<S: Into<String>>(&self, status: http::StatusCode, body: S) -> &Self {
+        self.respond_with(Response::new(status, body))
+    }
+
+    /// Answers the next request without a queued response with `response`.
+    pub fn respond_with(&self, response: Response) -> &Self {
+        self.state.lock().unwrap().responses.push_back(response);
+        self
+    }
+
+    /// The requests received so far, in order.
+    pub fn requests(&self) -> Vec<Request> {
+        self.state.lock().unwrap().requests.clone()
+    }
+
+    /// The data points sent to the endpoints adding data so far, in order, whatever the
+    /// response to their requests.
+    pub fn sent_points(&self) -> Vec<DataPoint> {
+        self.state
+            .lock()
+            .unwrap()
+            .requests
+            .iter()
+            .filter(|request| request.url.contains("/adddata/") || request.url.ends_with("/add"))
+            .filter_map(|request| serde_json::from_str::<Value>(request.body_text()?).ok())
+            .flat_map(|body| match body["data"].as_array() {
+                Some(rows) => rows.iter().filter_map(|row| point(row, "value")).collect(),
+                None => point(&body, "dimension3").into_iter().collect::<Vec<_>>(),
+            })
+            .collect()
+    }
+
+    /// Forgets the requests received and the queued responses.
+    pub fn clear(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.requests.clear();
+        state.responses.clear();
+    }
+}
+
+impl Transport for MockTransport {
+    fn send(&self, request: Request) -> Result<Response, AnalyticsError> {
+        let mut state = self.state.lock().unwrap();
+        state.requests.push(request);
+        Ok(state
+            .responses
+            .pop_front()
+            .unwrap_or_else(|| Response::new(http::StatusCode::OK, SUCCESS)))
+    }
+}
+
+/// Internals of certificate pinning, see
+/// [`Security::pin_spki_sha256`](crate::Security::pin_spki_sha256), for the tests of the crate.
+#[doc(hidden)]
+#[cfg(any(feature = "native-tls", feature = "rustls"))]
+pub mod pinning {
+    use crate::{security, AnalyticsError};
+
+    /// Decodes a base64-encoded SHA-256 pin, with an optional `sha256/` prefix.
+    pub fn decode_pin(pin: &str) -> Result<[u8; 32], AnalyticsError> {
+        security::decode_pin(pin)
+    }
+
+    /// Splits a DER element off `der`, returning its tag, its contents and what follows it.
+    #[cfg(feature = "rustls")]
+    pub fn der_element(der: &[u8]) -> Option<(u8, &[u8], &[u8])> {
+        security::der_element(der)
+    }
+
+    /// The DER-encoded `SubjectPublicKeyInfo` of an X.509 certificate.
+    #[cfg(feature = "rustls")]
+    pub fn subject_public_key_info(cert: &[u8]) -> Option<&[u8]> {
+        security::subject_public_key_info(cert)
+    }
+}
+
+/// Host name resolution of [`SyncClientBuilder::last_good_address`](crate::SyncClientBuilder::last_good_address),
+/// for the tests of the crate.
+#[doc(hidden)]
+pub mod resolution {
+    use std::io;
+    use std::net::SocketAddr;
+    use std::sync::Arc;
+
+    use crate::SyncClientBuilder;
+
+    /// Resolves the host names of the built client with `resolver` instead of the system's, to
+    /// simulate resolution failures.
+    pub fn with_resolver<F>(mut builder: SyncClientBuilder, resolver: F) -> SyncClientBuilder
+    where
+        F: Fn(&str, u16) -> io::Result<Vec<SocketAddr>> + Send + Sync + 'static,
+    {
+        builder.resolver = Some(Arc::new(resolver));
+        builder
+    }
+}
+
+/// A data point from the JSON of a row, with its value under `value`.
+fn point(row: &Value, value: &str) -> Option<DataPoint> {
+    let value = &row[value];
+    Some(DataPoint::new(
+        row["dimension1"].as_str()?,
+        row["dimension2"].as_str()?,
+        value.as_f64().or_else(|| value.as_str()?.parse().ok())?,
+    ))
+}
@@ -0,0 +1,407 @@
+//! Thresholds, which color report values and trigger notifications.
+//!
+//! Thresholds use the Analytics app's own routes rather than its versioned API, so they may
+//! change between Analytics releases.
+
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use reqwest as http;
+use serde_json::{json, Value};
+
+use crate::as_u64;
+use crate::{AnalyticsError, DataPoint, NumberFormat, SyncClient};
+
+/// How a value is compared to a threshold.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Comparison {
+    Equal,
+    GreaterThan,
+    LessThan,
+}
+
+impl Comparison {
+    fn code(self) -> &'static str {
+        match self {
+            Comparison::Equal => "=",
+            Comparison::GreaterThan => ">",
+            Comparison::LessThan => "<",
+        }
+    }
+
+    /// Whether `value` compares to `threshold` this way.
+    pub fn holds(self, value: f64, threshold: f64) -> bool {
+        match self {
+            Comparison::Equal => value == threshold,
+            Comparison::GreaterThan => value > threshold,
+            Comparison::LessThan => value < threshold,
+        }
+    }
+
+    /// Whether `value` still compares to `threshold` this way, allowing it to be `margin` past
+    /// it.
+    fn holds_within(self, value: f64, threshold: f64, margin: f64) -> bool {
+        match self {
+            Comparison::Equal => (value - threshold).abs() <= margin,
+            Comparison::GreaterThan => value > threshold - margin,
+            Comparison::LessThan => value < threshold + margin,
+        }
+    }
+
+    fn from_code(code: &str) -> Option<Self> {
+        match code {
+            "=" => Some(Comparison::Equal),
+            ">" => Some(Comparison::GreaterThan),
+            "<" => Some(Comparison::LessThan),
+            _ => None,
+        }
+    }
+}
+
+/// What happens when a value crosses a threshold.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Severity {
+    /// A notification is sent to the report's owner.
+    Notification,
+    /// The value is shown in red.
+    Alert,
+    /// The value is shown in orange.
+    Warning,
+    /// The value is shown in green.
+    Good,
+}
+
+impl Severity {
+    fn code(self) -> u64 {
+        match self {
+            Severity::Notification => 1,
+            Severity::Alert => 2,
+            Severity::Warning => 3,
+            Severity::Good => 4,
+        }
+    }
+
+    fn from_code(code: u64) -> Option<Self> {
+        match code {
+            1 => Some(Severity::Notification),
+            2 => Some(Severity::Alert),
+            3 => Some(Severity::Warning),
+            4 => Some(Severity::Good),
+            _ => None,
+        }
+    }
+}
+
+/// A threshold of a report, as listed by [`SyncClient::list_thresholds`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct Threshold {
+    /// Identifier of the threshold.
+    pub id: u64,
+    /// Value of the first dimension the threshold applies to.
+    pub dimension1: String,
+    pub comparison: Comparison,
+    pub value: f64,
+    pub severity: Severity,
+}
+
+impl Threshold {
+    fn from_json(value: &Value) -> Option<Self> {
+        let threshold = &value["value"];
+        Some(Self {
+            id: as_u64(&value["id"])?,
+            dimension1: value["dimension1"].as_str().unwrap_or_default().to_string(),
+            comparison: Comparison::from_code(value["option"].as_str()?)?,
+            value: threshold
+                .as_f64()
+                .or_else(|| threshold.as_str()?.trim().parse().ok())?,
+            severity: Severity::from_code(as_u64(&value["severity"])?)?,
+        })
+    }
+}
+
+impl SyncClient {
+    /// Adds a threshold to a report: values of `dimension1` comparing to `value` as given, e.g.
+    /// greater than it, are highlighted or notified according to `severity`. Returns the
+    /// identifier of the new threshold.
+    pub fn set_threshold(
+        &self,
+        report: u64,
+        dimension1: &str,
+        comparison: Comparison,
+        value: f64,
+        severity: Severity,
+    ) -> Result<u64, AnalyticsError> {
+        let resp = self.request_json(
+            http::Method::POST,
+            "apps/analytics/threshold",
+            Some(json!({
+                "reportId": report,
+                "dimension1": dimension1,
+                "option": comparison.code(),
+                "value": value.to_string(),
+                "severity": severity.code(),
+            })),
+        )?;
+
+        as_u64(&resp).ok_or_else(|| {
+            AnalyticsError::Parse(format!("unexpected threshold creation response: {}", resp))
+        })
+    }
+
+    /// Lists the thresholds of a report. Thresholds of unknown kinds are skipped.
+    pub fn list_thresholds(&self, report: u64) -> Result<Vec<Threshold>, AnalyticsError> {
+        let resp = self.request_json(
+            http::Method::GET,
+            &format!("apps/analytics/threshold/{}", report),
+            None,
+        )?;
+        Ok(resp
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(Threshold::from_json)
+            .collect())
+    }
+
+    /// Deletes the threshold with the given identifier.
+    pub fn delete_threshold(&self, id: u64) -> Result<(), AnalyticsError> {
+        self.request_json(
+            http::Method::DELETE,
+            &format!("apps/analytics/threshold/{}", id),
+            None,
+        )?;
+        Ok(())
+    }
+}
+
+type WatchCallback = Box<dyn Fn(&ThresholdEvent) + Send>;
+type WatchErrorCallback = Box<dyn Fn(&AnalyticsError) + Send>;
+type WatchRecoveryCallback = Box<dyn Fn() + Send>;
+
+/// A threshold crossed or cleared, as reported by a [`ThresholdWatch`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct ThresholdEvent {
+    /// Key of the row whose value was checked.
+    pub dimension1: String,
+    pub comparison: Comparison,
+    /// Value of the threshold.
+    pub threshold: f64,
+    /// Value of the row.
+    pub value: f64,
+}
+
+/// A client-side threshold of a [`ThresholdWatch`], remembering whether it's breached.
+struct Rule {
+    dimension1: String,
+    comparison: Comparison,
+    value: f64,
+    breached: bool,
+}
+
+/// Thresholds evaluated on the client side by polling a report, see
+/// [`SyncClient::watch_thresholds`], e.g. for a device to react to a value that the server's
+/// notifications can't reach.
+#[derive(Default)]
+pub struct ThresholdWatch {
+    rules: Vec<Rule>,
+    format: NumberFormat,
+    hysteresis: f64,
+    on_breach: Option<WatchCallback>,
+    on_clear: Option<WatchCallback>,
+    on_error: Option<WatchErrorCallback>,
+    on_recovered: Option<WatchRecoveryCallback>,
+}
+
+impl ThresholdWatch {
+    /// A watch without thresholds nor callbacks.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Watch the latest value of `dimension1`: the threshold is breached while it compares to
+    /// `value` as given, e.g. is greater than it.
+    pub fn threshold<S: Into<String>>(
+        mut self,
+        dimension1: S,
+        comparison: Comparison,
+        value: f64,
+    ) -> Self {
+        self.rules.push(Rule {
+            dimension1: dimension1.into(),
+            comparison,
+            value,
+            breached: false,
+        });
+        self
+    }
+
+    /// Watch the given thresholds, e.g. the ones of the report as listed by
+    /// [`SyncClient::list_thresholds`], whatever their severity.
+    pub fn thresholds(self, thresholds: &[Threshold]) -> Self {
+        thresholds.iter().fold(self, |watch, threshold| {
+            watch.threshold(
+                threshold.dimension1.as_str(),
+                threshold.comparison,
+                threshold.value,
+            )
+        })
+    }
+
+    /// Format of the values of the report. Defaults to a decimal point.
+    pub fn number_format(mut self, format: NumberFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Only clear a breached threshold once the value is back past it by `margin`, e.g. with
+    /// a margin of 2, a threshold breached above 30 clears at 28 or below, and one breached at
+    /// 30 exactly clears more than 2 away from it, so that a value hovering around it doesn't
+    /// breach and clear on every poll. Defaults to 0.
+    pub fn hysteresis(mut self, margin: f64) -> Self {
+        self.hysteresis = if margin > 0.0 { margin } else { 0.0 };
+        self
+    }
+
+    /// Called from the watcher's thread when a threshold becomes breached.
+    pub fn on_breach<F: Fn(&ThresholdEvent) + Send + 'static>(mut self, callback: F) -> Self {
+        self.on_breach = Some(Box::new(callback));
+        self
+    }
+
+    /// Called from the watcher's thread when a breached threshold isn't anymore.
+    pub fn on_clear<F: Fn(&ThresholdEvent) + Send + 'static>(mut self, callback: F) -> Self {
+        self.on_clear = Some(Box::new(callback));
+        self
+    }
+
+    /// Called from the watcher's thread when the report can't be read. The state of the
+    /// thresholds is kept until the next successful poll.
+    pub fn on_error<F: Fn(&AnalyticsError) + Send + 'static>(mut self, callback: F) -> Self {
+        self.on_error = Some(Box::new(callback));
+        self
+    }
+
+    /// Called from the watcher's thread when the report is read again after one or more
+    /// errors, before the thresholds are evaluated.
+    pub fn on_recovered<F: Fn() + Send + 'static>(mut self, callback: F) -> Self {
+        self.on_recovered = Some(Box::new(callback));
+        self
+    }
+
+    /// Evaluates the thresholds against the rows of a report, calling the callbacks of the
+    /// thresholds whose state changed.
+    fn evaluate(&mut self, rows: &[DataPoint]) {
+        for rule in &mut self.rules {
+            // The last row of the key is its latest value, as long as the server returns
+            // timeline data in chronological order.
+            let latest = match rows
+                .iter()
+                .rev()
+                .find(|row| row.dimension1 == rule.dimension1)
+            {
+                Some(row) => row.dimension3,
+                None => continue,
+            };
+            let breached = if rule.breached {
+                rule.comparison
+                    .holds_within(latest, rule.value, self.hysteresis)
+            } else {
+                rule.comparison.holds(latest, rule.value)
+            };
+            if breached == rule.breached {
+                continue;
+            }
+            rule.breached = breached;
+
+            let callback = if breached {
+                &self.on_breach
+            } else {
+                &self.on_clear
+            };
+            if let Some(callback) = callback {
+                callback(&ThresholdEvent {
+                    dimension1: rule.dimension1.clone(),
+                    comparison: rule.comparison,
+                    threshold: rule.value,
+                    value: latest,
+                });
+            }
+        }
+    }
+}
+
+/// Polls a report on an interval from its own thread, for a [`ThresholdWatch`], see
+/// [`SyncClient::watch_thresholds`].
+///
+/// The watcher stops when dropped.
+pub struct ThresholdWatcher {
+    stop: Option<mpsc::Sender<()>>,
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+impl ThresholdWatcher {
+    /// Stops the watcher, waiting for an ongoing poll to finish. Same as dropping it.
+    pub fn stop(self) {}
+}
+
+impl Drop for ThresholdWatcher {
+    fn drop(&mut self) {
+        // Dropping the sender wakes the thread up.
+        self.stop.take();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl SyncClient {
+    /// Reads the data of `report` every `interval`, the first time right away, and evaluates
+    /// the thresholds of `watch` against the latest value of their key, calling its callbacks
+    /// when a threshold becomes breached or cleared.
+    ///
+    /// A threshold already breached at the first poll is reported as a breach; keys without
+    /// data are ignored.
+    pub fn watch_thresholds(
+        self: Arc<Self>,
+        report: u32,
+        interval: Duration,
+        mut watch: ThresholdWatch,
+    ) -> ThresholdWatcher {
+        let (stop, stopped) = mpsc::channel();
+
+        let thread = thread::spawn(move || {
+            let mut failing = false;
+            loop {
+                match self.get_report_data(report, watch.format) {
+                    Ok(rows) => {
+                        if failing {
+                            failing = false;
+                            if let Some(callback) = &watch.on_recovered {
+                                callback();
+                            }
+                        }
+                        watch.evaluate(&rows);
+                    }
+                    Err(err) => {
+                        failing = true;
+                        if let Some(callback) = &watch.on_error {
+                            callback(&err);
+                        }
+                    }
+                }
+
+                match stopped.recv_timeout(interval) {
+                    Err(RecvTimeoutError::Timeout) => {}
+                    Ok(()) | Err(RecvTimeoutError::Disconnected) => break,
+                }
+            }
+        });
+
+        ThresholdWatcher {
+            stop: Some(stop),
+            thread: Some(thread),
+        }
+    }
+}
@@ -0,0 +1,69 @@
+//! Per-call timeouts, overriding the client's for the requests of a single send.
+
+use std::cell::Cell;
+use std::time::Duration;
+
+use crate::{AnalyticsError, DataPoint, Receipt, SyncClient};
+
+thread_local! {
+    /// Timeout of the requests of the call in progress on this thread, if overridden.
+    static CALL_TIMEOUT: Cell<Option<Duration>> = const { Cell::new(None) };
+}
+
+/// Restores the previous timeout when the call ends, even by unwinding.
+struct Restore(Option<Duration>);
+
+impl Drop for Restore {
+    fn drop(&mut self) {
+        CALL_TIMEOUT.with(|timeout| timeout.set(self.0));
+    }
+}
+
+/// Runs `call` with the requests it sends on this thread timing out after `timeout`.
+fn with_timeout<T>(timeout: Duration, call: impl FnOnce() -> T) -> T {
+    let _restore = Restore(CALL_TIMEOUT.with(|current| current.replace(Some(timeout))));
+    call()
+}
+
+/// Timeout of the requests of the call in progress, if overridden.
+pub(crate) fn call_timeout() -> Option<Duration> {
+    CALL_TIMEOUT.with(Cell::get)
+}
+
+impl SyncClient {
+    /// Sends some data like [`SyncClient::send_data`], with each request timing out after
+    /// `timeout` instead of the [client's timeout](crate::SyncClientBuilder::timeout), e.g. to
+    /// give up quickly on a hung instance from a latency-sensitive loop.
+    ///
+    /// The timeout applies to each attempt when the client retries, and to the deletion of
+    /// existing rows in upsert mode.
+    pub fn send_data_with_timeout<S: Into<String>, F: Into<f64>>(
+        &self,
+        dimension1: S,
+        dimension2: S,
+        dimension3: F,
+        timeout: Duration,
+    ) -> Result<Receipt, AnalyticsError> {
+        self.send_point_with_timeout(DataPoint::new(dimension1, dimension2, dimension3), timeout)
+    }
+
+    /// Sends a single data point like [`SyncClient::send_point`], with the given timeout, see
+    /// [`SyncClient::send_data_with_timeout`].
+    pub fn send_point_with_timeout(
+        &self,
+        point: DataPoint,
+        timeout: Duration,
+    ) -> Result<Receipt, AnalyticsError> {
+        with_timeout(timeout, || self.send_point(point))
+    }
+
+    /// Sends many data points like [`SyncClient::send_batch`], with the given timeout, see
+    /// [`SyncClient::send_data_with_timeout`].
+    pub fn send_batch_with_timeout(
+        &self,
+        points: &[DataPoint],
+        timeout: Duration,
+    ) -> Result<Receipt, AnalyticsError> {
+        with_timeout(timeout, || self.send_batch(points))
+    }
+}
@@ -0,0 +1,149 @@
+//! Spans around the API calls, when the `tracing` feature is enabled; without it, the traces
+//! compile down to nothing.
+
+use reqwest as http;
+
+#[cfg(feature = "tracing")]
+use std::sync::atomic::{AtomicU64, Ordering};
+#[cfg(feature = "tracing")]
+use std::sync::Arc;
+
+use crate::redact::Redactions;
+use crate::transport::Response;
+use crate::{AnalyticsError, Receipt, SyncClient};
+
+/// Sampling of the events of successful sends, see [`SyncClient::with_log_sampling`].
+#[cfg(feature = "tracing")]
+#[derive(Clone, Default)]
+pub(crate) struct LogSampling {
+    /// One successful send out of `every` is logged; none if zero.
+    every: u32,
+    successes: Arc<AtomicU64>,
+}
+
+#[cfg(feature = "tracing")]
+impl LogSampling {
+    /// The number of successful sends so far if this one should be logged.
+    fn sample(&self) -> Option<u64> {
+        if self.every == 0 {
+            return None;
+        }
+        let previous = self.successes.fetch_add(1, Ordering::Relaxed);
+        previous
+            .is_multiple_of(u64::from(self.every))
+            .then(|| previous + 1)
+    }
+}
+
+impl SyncClient {
+    /// Log one successful send out of `every` at the info level, with the collection, the
+    /// number of rows and the number of successful sends so far, to show that data keeps
+    /// flowing without an event per send. The first successful send is always logged; failed
+    /// sends are all logged at the warn level regardless. Zero, the default, logs no success.
+    ///
+    /// Requires the `tracing` feature.
+    #[cfg(feature = "tracing")]
+    pub fn with_log_sampling(mut self, every: u32) -> Self {
+        self.log_sampling = LogSampling {
+            every,
+            successes: Arc::default(),
+        };
+        self
+    }
+}
+
+/// Span of a single HTTP request, including its body at the debug level.
+pub(crate) struct RequestTrace {
+    #[cfg(feature = "tracing")]
+    span: tracing::Span,
+    #[cfg(feature = "tracing")]
+    started: std::time::Instant,
+}
+
+impl RequestTrace {
+    #[cfg_attr(not(feature = "tracing"), allow(unused_variables))]
+    pub fn start(req: &http::blocking::Request, redactions: &Redactions) -> Self {
+        #[cfg(feature = "tracing")]
+        {
+            let span = tracing::debug_span!(
+                "nextcloud_analytics.request",
+                method = %req.method(),
+                url = %redactions.apply(req.url().as_str()),
+                status = tracing::field::Empty,
+                latency_ms = tracing::field::Empty,
+            );
+            if let Some(body) = req.body().and_then(|body| body.as_bytes()) {
+                let body = redactions.apply(&String::from_utf8_lossy(body));
+                span.in_scope(|| tracing::debug!(body = %body, "request body"));
+            }
+            Self {
+                span,
+                started: std::time::Instant::now(),
+            }
+        }
+        #[cfg(not(feature = "tracing"))]
+        Self {}
+    }
+
+    #[cfg_attr(not(feature = "tracing"), allow(unused_variables))]
+    pub fn finish(self, result: &Result<Response, AnalyticsError>) {
+        #[cfg(feature = "tracing")]
+        {
+            self.span
+                .record("latency_ms", self.started.elapsed().as_millis() as u64);
+            match result {
+                Ok(resp) => {
+                    self.span.record("status", resp.status.as_u16());
+                }
+                Err(err) => self
+                    .span
+                    .in_scope(|| tracing::debug!(error = %err, "request failed")),
+            }
+        }
+    }
+}
+
+/// Span of a send of data points to a collection, possibly made of several requests.
+pub(crate) struct SendTrace {
+    #[cfg(feature = "tracing")]
+    span: tracing::span::EnteredSpan,
+}
+
+impl SendTrace {
+    #[cfg_attr(not(feature = "tracing"), allow(unused_variables))]
+    pub fn start(collection: u32, rows: usize) -> Self {
+        #[cfg(feature = "tracing")]
+        {
+            let span = tracing::info_span!(
+                "nextcloud_analytics.send",
+                collection,
+                rows,
+                outcome = tracing::field::Empty,
+            );
+            Self {
+                span: span.entered(),
+            }
+        }
+        #[cfg(not(feature = "tracing"))]
+        Self {}
+    }
+
+    /// Records the outcome of the send, logging failures and the successes sampled by the
+    /// client's [`LogSampling`].
+    #[cfg_attr(not(feature = "tracing"), allow(unused_variables))]
+    pub fn finish(self, result: &Result<Receipt, AnalyticsError>, client: &SyncClient) {
+        #[cfg(feature = "tracing")]
+        match result {
+            Ok(_) => {
+                self.span.record("outcome", "sent");
+                if let Some(successes) = client.log_sampling.sample() {
+                    tracing::info!(successes, "send succeeded");
+                }
+            }
+            Err(err) => {
+                self.span.record("outcome", err.code());
+                tracing::warn!(error = %err, "send failed");
+            }
+        }
+    }
+}
@@ -0,0 +1,100 @@
+use std::time::Duration;
+
+use reqwest as http;
+
+use crate::AnalyticsError;
+
+/// An HTTP request of a [`SyncClient`](crate::SyncClient), as handed to its [`Transport`], with
+/// the credentials and headers already set.
+#[derive(Clone, Debug)]
+pub struct Request {
+    pub method: http::Method,
+    pub url: String,
+    pub headers: http::header::HeaderMap,
+    pub body: Option<Vec<u8>>,
+    /// Timeout of this request, if it overrides the client's, see
+    /// [`SyncClient::send_data_with_timeout`](crate::SyncClient::send_data_with_timeout).
+    pub timeout: Option<Duration>,
+}
+
+impl Request {
+    /// The body of the request as text, if it has one and it's valid UTF-8.
+    pub fn body_text(&self) -> Option<&str> {
+        std::str::from_utf8(self.body.as_ref()?).ok()
+    }
+
+    pub(crate) fn from_reqwest(req: http::blocking::Request) -> Self {
+        Self {
+            method: req.method().clone(),
+            url: req.url().to_string(),
+            headers: req.headers().clone(),
+            // The client never streams request bodies.
+            body: req
+                .body()
+                .and_then(|body| body.as_bytes())
+                .map(<[u8]>::to_vec),
+            timeout: req.timeout().copied(),
+        }
+    }
+
+    /// The request, to be sent by `client`.
+    pub(crate) fn into_reqwest(
+        self,
+        client: &http::blocking::Client,
+    ) -> http::blocking::RequestBuilder {
+        let mut req = client.request(self.method, &self.url).headers(self.headers);
+        if let Some(body) = self.body {
+            req = req.body(body);
+        }
+        if let Some(timeout) = self.timeout {
+            req = req.timeout(timeout);
+        }
+        req
+    }
+}
+
+/// The response to a [`Request`], as returned by a [`Transport`].
+#[derive(Clone, Debug)]
+pub struct Response {
+    pub status: http::StatusCode,
+    pub headers: http::header::HeaderMap,
+    pub body: String,
+}
+
+impl Response {
+    /// A response with the given status and body, and no headers.
+    pub fn new<S: Into<String>>(status: http::StatusCode, body: S) -> Self {
+        Self {
+            status,
+            headers: http::header::HeaderMap::new(),
+            body: body.into(),
+        }
+    }
+}
+
+/// The HTTP layer of a [`SyncClient`](crate::SyncClient), sending its requests and returning
+/// the responses, see [`SyncClient::with_transport`](crate::SyncClient::with_transport).
+///
+/// The default transport is the `reqwest` blocking client configured by the
+/// [`SyncClientBuilder`](crate::SyncClientBuilder); replacing it is mostly useful for tests,
+/// see [`testing::MockTransport`](crate::testing::MockTransport). Retries, rate limits and
+/// fixture recording happen around the transport, and still apply.
+pub trait Transport: Send + Sync {
+    /// Sends a request, returning its response whatever its status, or an error if no response
+    /// was received.
+    fn send(&self, request: Request) -> Result<Response, AnalyticsError>;
+}
+
+impl Transport for http::blocking::Client {
+    fn send(&self, request: Request) -> Result<Response, AnalyticsError> {
+        let resp = request.into_reqwest(self).send()?;
+        let status = resp.status();
+        let headers = resp.headers().clone();
+        let body = resp.text()?;
+        Ok(Response {
+            status,
+            headers,
+            body,
+        })
+    }
+}
@@ -0,0 +1,135 @@
+use core::fmt;
+use std::error::Error;
+
+use crate::DataPoint;
+#[cfg(feature = "blocking")]
+use crate::SyncClient;
+
+/// Default maximum length of the dimensions, in characters, the size of the columns of the
+/// Analytics app's tables.
+const DEFAULT_MAX_LEN: usize = 256;
+
+/// How data points are checked before being sent, see [`SyncClient::with_validation`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Validation {
+    max_len: usize,
+    lenient: bool,
+}
+
+impl Default for Validation {
+    fn default() -> Self {
+        Self::strict()
+    }
+}
+
+impl Validation {
+    /// Reject data points with an empty key, a value that isn't a finite number, or a
+    /// dimension longer than 256 characters. This is the default.
+    pub fn strict() -> Self {
+        Self {
+            max_len: DEFAULT_MAX_LEN,
+            lenient: false,
+        }
+    }
+
+    /// Fix what can be fixed instead of rejecting it: infinite values are clamped to the
+    /// largest finite numbers, and dimensions too long are truncated. Empty keys and values
+    /// that aren't numbers at all are still rejected.
+    pub fn lenient() -> Self {
+        Self {
+            lenient: true,
+            ..Self::strict()
+        }
+    }
+
+    /// Maximum length of the dimensions, in characters, e.g. for an Analytics install with
+    /// larger columns.
+    pub fn max_len(mut self, max_len: usize) -> Self {
+        self.max_len = max_len;
+        self
+    }
+
+    /// Checks a data point, as it will be sent, fixing it in lenient mode.
+    pub(crate) fn apply(&self, point: &mut DataPoint) -> Result<(), ValidationError> {
+        if point.dimension1.is_empty() {
+            return Err(ValidationError::EmptyKey);
+        }
+
+        let value = point.dimension3;
+        if value.is_nan() || (value.is_infinite() && !self.lenient) {
+            return Err(ValidationError::NonFinite(value));
+        }
+        point.dimension3 = value.clamp(f64::MIN, f64::MAX);
+
+        for (dimension, text) in [(1, &mut point.dimension1), (2, &mut point.dimension2)] {
+            let len = text.chars().count();
+            if len <= self.max_len {
+                continue;
+            }
+            if !self.lenient {
+                return Err(ValidationError::TooLong {
+                    dimension,
+                    len,
+                    max: self.max_len,
+                });
+            }
+            if let Some((end, _)) = text.char_indices().nth(self.max_len) {
+                text.truncate(end);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A data point was rejected before sending, see [`Validation`].
+#[derive(Clone, Debug, PartialEq)]
+pub enum ValidationError {
+    /// The first dimension is empty.
+    EmptyKey,
+    /// The value is NaN or infinite.
+    NonFinite(f64),
+    /// A dimension, 1 or 2, is `len` characters long, more than the `max` allowed.
+    TooLong {
+        dimension: u8,
+        len: usize,
+        max: usize,
+    },
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ValidationError::EmptyKey => write!(f, "invalid data point: empty key"),
+            ValidationError::NonFinite(value) => {
+                write!(f, "invalid data point: the value isn't finite: {}", value)
+            }
+            ValidationError::TooLong {
+                dimension,
+                len,
+                max,
+            } => write!(
+                f,
+                "invalid data point: dimension{} is {} characters long, more than {}",
+                dimension, len, max
+            ),
+        }
+    }
+}
+
+impl Error for ValidationError {}
+
+#[cfg(feature = "blocking")]
+impl SyncClient {
+    /// Check the data points with `validation` before sending them, instead of
+    /// [`Validation::strict`].
+    ///
+    /// The data points are checked as they would be sent, after the key template and the
+    /// enrichments; rejected data points fail with [`AnalyticsError::Validation`] without
+    /// any request.
+    ///
+    /// [`AnalyticsError::Validation`]: crate::AnalyticsError::Validation
+    pub fn with_validation(mut self, validation: Validation) -> Self {
+        self.validation = validation;
+        self
+    }
+}
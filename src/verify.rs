@@ -0,0 +1,59 @@
+use crate::{AnalyticsError, DataPoint, NumberFormat, Receipt, SyncClient};
+
+impl SyncClient {
+    /// Sends a single data point, then reads the collection back to check the server stored the
+    /// same value, within `epsilon`, e.g. for data that must not be lost or rounded silently.
+    ///
+    /// The point goes through the key filter, key template and enrichments like with
+    /// [`SyncClient::send_point`], but is never held back by a minimum interval. It is looked up
+    /// by its two dimensions in the report with the same index as the collection; values are
+    /// expected with a decimal point, see [`SyncClient::send_verified_with`] otherwise.
+    ///
+    /// Returns an [`AnalyticsError::Unverified`] error if the stored value differs or the row
+    /// can't be found.
+    pub fn send_verified(&self, point: DataPoint, epsilon: f64) -> Result<Receipt, AnalyticsError> {
+        self.send_verified_with(point, epsilon, NumberFormat::default())
+    }
+
+    /// Like [`SyncClient::send_verified`], parsing read values according to `format`.
+    pub fn send_verified_with(
+        &self,
+        point: DataPoint,
+        epsilon: f64,
+        format: NumberFormat,
+    ) -> Result<Receipt, AnalyticsError> {
+        if !self.allows_key(&point.dimension1) {
+            return self.send_point(point);
+        }
+
+        let point = self.prepare_point(point)?;
+        let receipt = self.post_point(point.clone(), self.collection)?;
+
+        // The last matching row wins, in case the dataset doesn't overwrite existing rows.
+        let stored = self
+            .get_report_data(self.collection, format)?
+            .into_iter()
+            .rev()
+            .find(|row| row.dimension1 == point.dimension1 && row.dimension2 == point.dimension2)
+            .map(|row| row.dimension3);
+
+        match stored {
+            Some(value) if (value - point.dimension3).abs() <= epsilon => Ok(receipt),
+            stored => {
+                let message = match stored {
+                    Some(value) => format!(
+                        "{}/{} was stored as {} instead of {}",
+                        point.dimension1, point.dimension2, value, point.dimension3
+                    ),
+                    None => format!(
+                        "{}/{} couldn't be found after being sent",
+                        point.dimension1, point.dimension2
+                    ),
+                };
+                Err(self
+                    .redactions
+                    .error(AnalyticsError::Unverified { stored, message }))
+            }
+        }
+    }
+}
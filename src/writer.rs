@@ -0,0 +1,100 @@
+use crate::{AnalyticsError, DataPoint, SyncClient};
+
+/// Separator between a dimension and the writer identity appended to it.
+const SEPARATOR: char = '@';
+
+/// Dimension carrying the identity of the writer, see [`SyncClient::with_writer_id`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WriterTag {
+    /// Append the writer identity to the key, e.g. `temperature@sensor-3`, so that each device
+    /// gets its own series.
+    Dimension1,
+    /// Append the writer identity to the second dimension, e.g. `kitchen@sensor-3`. Don't use
+    /// it for timeline data, whose second dimension must be a date.
+    Dimension2,
+}
+
+impl WriterTag {
+    /// Identity of the writer of a data point read back from a collection, as set by
+    /// [`SyncClient::with_writer_id`] with this tag.
+    pub fn writer_of(self, point: &DataPoint) -> Option<&str> {
+        split(self.dimension(point)).map(|(_, writer)| writer)
+    }
+
+    fn dimension(self, point: &DataPoint) -> &String {
+        match self {
+            WriterTag::Dimension1 => &point.dimension1,
+            WriterTag::Dimension2 => &point.dimension2,
+        }
+    }
+
+    fn dimension_mut(self, point: &mut DataPoint) -> &mut String {
+        match self {
+            WriterTag::Dimension1 => &mut point.dimension1,
+            WriterTag::Dimension2 => &mut point.dimension2,
+        }
+    }
+}
+
+/// Identity of the writer of a client's data points.
+#[derive(Clone, Debug)]
+pub(crate) struct Writer {
+    id: String,
+    tag: WriterTag,
+}
+
+impl Writer {
+    pub fn tag(&self) -> WriterTag {
+        self.tag
+    }
+
+    pub fn apply(&self, point: &mut DataPoint) {
+        let dimension = self.tag.dimension_mut(point);
+        dimension.push(SEPARATOR);
+        dimension.push_str(&self.id);
+    }
+}
+
+/// Splits the writer identity off `dimension`, if it has one.
+fn split(dimension: &str) -> Option<(&str, &str)> {
+    let at = dimension.rfind(SEPARATOR)?;
+    Some((&dimension[..at], &dimension[at + SEPARATOR.len_utf8()..]))
+}
+
+impl SyncClient {
+    /// Append `id`, e.g. a device's hostname or serial number, to a dimension of every data
+    /// point sent by this client, after the key template and enrichments have been applied, so
+    /// that rows written by many devices to the same collection can be traced back to the one
+    /// that produced them.
+    ///
+    /// The identity is separated from the dimension by a `@`, and shouldn't itself contain one.
+    /// Use [`SyncClient::get_data_by_writer`] to read back the data of a single writer, and
+    /// [`WriterTag::writer_of`] to find who wrote a row.
+    pub fn with_writer_id<S: Into<String>>(mut self, id: S, tag: WriterTag) -> Self {
+        self.writer = Some(Writer { id: id.into(), tag });
+        self
+    }
+
+    /// Reads back the data of the client's collection written by `writer`, as with
+    /// [`SyncClient::get_data`], with the writer identity removed from the dimension given by
+    /// `tag`.
+    pub fn get_data_by_writer(
+        &self,
+        writer: &str,
+        tag: WriterTag,
+    ) -> Result<Vec<DataPoint>, AnalyticsError> {
+        Ok(self
+            .get_data()?
+            .into_iter()
+            .filter_map(|mut point| {
+                let dimension = tag.dimension_mut(&mut point);
+                let (untagged, id) = split(dimension)?;
+                if id != writer {
+                    return None;
+                }
+                *dimension = untagged.to_string();
+                Some(point)
+            })
+            .collect())
+    }
+}
@@ -0,0 +1,151 @@
+//! Aggregation of high-frequency values before sending them, through a mock transport.
+
+#![cfg(feature = "chrono")]
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use nextcloud_analytics_rs::testing::MockTransport;
+use nextcloud_analytics_rs::{Aggregation, Aggregator};
+use reqwest::StatusCode;
+
+/// Long enough for the aggregators not to flush by themselves during the tests.
+const INTERVAL: Duration = Duration::from_secs(3600);
+
+/// The values sent by the last flush, by key.
+fn flushed(mock: &MockTransport) -> BTreeMap<String, f64> {
+    let values = mock
+        .sent_points()
+        .into_iter()
+        .map(|point| (point.dimension1, point.dimension3))
+        .collect();
+    mock.clear();
+    values
+}
+
+fn summary(mock: &MockTransport, aggregations: &[Aggregation]) -> Aggregator {
+    Aggregator::start_summary(Arc::new(mock.client(3)), INTERVAL, aggregations)
+}
+
+#[test]
+fn percentiles_use_the_nearest_rank() {
+    let mock = MockTransport::new();
+    let aggregator = summary(
+        &mock,
+        &[
+            Aggregation::Percentile(0),
+            Aggregation::Percentile(50),
+            Aggregation::Percentile(95),
+            Aggregation::Percentile(99),
+            Aggregation::Percentile(100),
+            Aggregation::Percentile(200),
+        ],
+    );
+    // 1 to 100, out of order.
+    for i in (1..=100).rev() {
+        aggregator.add("latency_ms", (i * 37 % 101) as f64);
+    }
+    assert!(aggregator.flush().unwrap().is_some());
+    let values = flushed(&mock);
+    assert_eq!(values["latency_ms.p0"], 1.0);
+    assert_eq!(values["latency_ms.p50"], 50.0);
+    assert_eq!(values["latency_ms.p95"], 95.0);
+    assert_eq!(values["latency_ms.p99"], 99.0);
+    assert_eq!(values["latency_ms.p100"], 100.0);
+    assert_eq!(values["latency_ms.p200"], 100.0);
+
+    for value in [3.0, 1.0, 2.0, 4.0] {
+        aggregator.add("small", value);
+    }
+    aggregator.add("single", 7.5);
+    let _ = aggregator.flush().unwrap();
+    let values = flushed(&mock);
+    assert_eq!(values["small.p50"], 2.0);
+    assert_eq!(values["small.p95"], 4.0);
+    assert_eq!(values["single.p0"], 7.5);
+    assert_eq!(values["single.p99"], 7.5);
+}
+
+#[test]
+fn summarizes_each_key_since_the_last_flush() {
+    let mock = MockTransport::new();
+    let aggregator = summary(
+        &mock,
+        &[
+            Aggregation::Min,
+            Aggregation::Max,
+            Aggregation::Mean,
+            Aggregation::Sum,
+            Aggregation::Count,
+        ],
+    );
+    for value in [4.0, -2.0, 10.0] {
+        aggregator.add("a", value);
+    }
+    aggregator.add("b", 1);
+
+    let _ = aggregator.flush().unwrap();
+    let values = flushed(&mock);
+    assert_eq!(values.len(), 10);
+    assert_eq!(values["a.min"], -2.0);
+    assert_eq!(values["a.max"], 10.0);
+    assert_eq!(values["a.mean"], 4.0);
+    assert_eq!(values["a.sum"], 12.0);
+    assert_eq!(values["a.count"], 3.0);
+    assert_eq!(values["b.mean"], 1.0);
+
+    // Nothing to send without new contributions.
+    assert!(aggregator.flush().unwrap().is_none());
+    assert!(mock.requests().is_empty());
+}
+
+#[test]
+fn combines_the_contributions_of_many_threads() {
+    let mock = MockTransport::new();
+    let aggregator = Aggregator::start(Arc::new(mock.client(3)), INTERVAL, Aggregation::Sum);
+    thread::scope(|scope| {
+        for _ in 0..4 {
+            let aggregator = aggregator.clone();
+            scope.spawn(move || {
+                for _ in 0..250 {
+                    aggregator.add("requests", 1);
+                }
+            });
+        }
+    });
+    let _ = aggregator.flush().unwrap();
+    // Without a suffix.
+    assert_eq!(flushed(&mock)["requests"], 1000.0);
+}
+
+#[test]
+fn failed_flushes_keep_the_contributions() {
+    let mock = MockTransport::new();
+    let aggregator = summary(&mock, &[Aggregation::Max, Aggregation::Percentile(50)]);
+    aggregator.add("a", 5.0);
+    aggregator.add("a", 1.0);
+
+    mock.respond(StatusCode::INTERNAL_SERVER_ERROR, "");
+    assert!(aggregator.flush().is_err());
+    mock.clear();
+    aggregator.add("a", 9.0);
+    let _ = aggregator.flush().unwrap();
+    let values = flushed(&mock);
+    assert_eq!(values["a.max"], 9.0);
+    assert_eq!(values["a.p50"], 5.0);
+}
+
+#[test]
+fn flushes_when_the_last_handle_is_dropped() {
+    let mock = MockTransport::new();
+    let aggregator = Aggregator::start(Arc::new(mock.client(3)), INTERVAL, Aggregation::Mean);
+    let other = aggregator.clone();
+    aggregator.add("temp", 20.0);
+    drop(aggregator);
+    assert!(mock.requests().is_empty());
+    other.add("temp", 22.0);
+    drop(other);
+    assert_eq!(flushed(&mock)["temp"], 21.0);
+}
@@ -0,0 +1,162 @@
+//! Aligning timeline timestamps to the grouping of reports, in the time zone of the dataset.
+
+#![cfg(feature = "chrono")]
+
+use chrono::{DateTime, FixedOffset, TimeZone, Utc};
+use nextcloud_analytics_rs::testing::MockTransport;
+use nextcloud_analytics_rs::{
+    AnalyticsError, DatasetTimeZone, Resolution, SyncClient, TimestampFormat,
+};
+use reqwest::StatusCode;
+use serde_json::json;
+
+fn utc(text: &str) -> DateTime<Utc> {
+    DateTime::parse_from_rfc3339(text)
+        .unwrap()
+        .with_timezone(&Utc)
+}
+
+fn east(hours: i32, minutes: i32) -> DatasetTimeZone {
+    DatasetTimeZone::Fixed(FixedOffset::east_opt(hours * 3600 + minutes * 60).unwrap())
+}
+
+#[test]
+fn aligns_in_utc() {
+    let time = utc("2024-01-01T23:34:56Z");
+    assert_eq!(Resolution::Minute.align(time), utc("2024-01-01T23:34:00Z"));
+    assert_eq!(Resolution::Hour.align(time), utc("2024-01-01T23:00:00Z"));
+    assert_eq!(Resolution::Day.align(time), utc("2024-01-01T00:00:00Z"));
+    assert_eq!(
+        Resolution::Day.align(utc("1969-12-31T12:00:00Z")),
+        utc("1969-12-31T00:00:00Z")
+    );
+}
+
+#[test]
+fn days_start_at_midnight_in_the_dataset_time_zone() {
+    // 01:34 on January 2nd in UTC+2.
+    let time = utc("2024-01-01T23:34:56Z");
+    assert_eq!(
+        Resolution::Day.align_in(time, east(2, 0)),
+        utc("2024-01-01T22:00:00Z")
+    );
+    assert_eq!(
+        Resolution::Day.align_in(time, east(-5, 0)),
+        utc("2024-01-01T05:00:00Z")
+    );
+    // Hours of zones with a half-hour offset start at half past in UTC.
+    assert_eq!(
+        Resolution::Hour.align_in(utc("2024-01-01T10:50:00Z"), east(5, 30)),
+        utc("2024-01-01T10:30:00Z")
+    );
+    assert_eq!(
+        Resolution::Day.align_in(time, DatasetTimeZone::Utc),
+        Resolution::Day.align(time)
+    );
+}
+
+#[test]
+fn timeline_sends_are_aligned_in_the_dataset_time_zone() {
+    let mock = MockTransport::new();
+    let client = mock
+        .client(3)
+        .with_time_zone(east(2, 0))
+        .align_to(Resolution::Day);
+
+    let _ = client
+        .send_timeline_data_with_format(
+            "temp",
+            utc("2024-01-01T23:34:56Z"),
+            21.5,
+            &TimestampFormat::UnixEpoch,
+        )
+        .unwrap();
+    let midnight = Utc.with_ymd_and_hms(2024, 1, 1, 22, 0, 0).unwrap();
+    assert_eq!(
+        mock.sent_points()[0].dimension2,
+        midnight.timestamp().to_string()
+    );
+}
+
+/// A report of the Analytics app, as returned by its routes, with the given options.
+fn report(chart_options: &str, filter_options: &str) -> String {
+    json!({
+        "id": 7,
+        "name": "Temperatures",
+        "type": 2,
+        "dataset": 3,
+        "visualization": "ct",
+        "chart": "line",
+        "chartoptions": chart_options,
+        "filteroptions": filter_options,
+    })
+    .to_string()
+}
+
+#[test]
+fn reads_the_resolution_of_reports() {
+    let cases = [
+        (
+            r#"{"scales":{"x":{"type":"time","time":{"unit":"hour"}}}}"#,
+            "",
+            Some(Resolution::Hour),
+        ),
+        (
+            r#"{"scales":{"xAxes":[{"time":{"unit":"day"}}]}}"#,
+            "",
+            Some(Resolution::Day),
+        ),
+        (
+            "",
+            r#"{"timeAggregation":"minute"}"#,
+            Some(Resolution::Minute),
+        ),
+        (r#"{"scales":{"x":{"time":{"unit":"week"}}}}"#, "", None),
+        ("", "", None),
+    ];
+    for (chart, filters, expected) in cases {
+        let mock = MockTransport::new();
+        mock.respond(StatusCode::OK, report(chart, filters));
+        let report = mock.client(3).get_report(7).unwrap();
+        assert_eq!(
+            Resolution::of_report(&report),
+            expected,
+            "{} {}",
+            chart,
+            filters
+        );
+    }
+}
+
+#[test]
+fn aligns_to_the_grouping_of_a_report() {
+    let mock = MockTransport::new();
+    mock.respond(
+        StatusCode::OK,
+        report(r#"{"scales":{"x":{"time":{"unit":"hour"}}}}"#, ""),
+    );
+    let client = mock.client(3).align_to_report(7).unwrap();
+    assert!(mock.requests()[0].url.ends_with("/apps/analytics/report/7"));
+
+    mock.clear();
+    let _ = client
+        .send_timeline_data_with_format(
+            "temp",
+            utc("2024-01-01T23:34:56Z"),
+            21.5,
+            &TimestampFormat::Rfc3339,
+        )
+        .unwrap();
+    assert_eq!(
+        mock.sent_points()[0].dimension2,
+        "2024-01-01T23:00:00+00:00"
+    );
+
+    mock.respond(StatusCode::OK, report("", ""));
+    let err: AnalyticsError = SyncClient::new("https://nextcloud.invalid/", 3, "user", "password")
+        .with_transport(mock.clone())
+        .align_to_report(7)
+        .err()
+        .unwrap();
+    assert!(matches!(err, AnalyticsError::Local(_)));
+}
@@ -0,0 +1,88 @@
+//! Endpoints used by each version of the Analytics API.
+
+use nextcloud_analytics_rs::testing::MockTransport;
+use nextcloud_analytics_rs::{ApiVersion, DataPoint, ErrorClass, SyncClient};
+use reqwest::StatusCode;
+
+fn with_api(api_version: ApiVersion) -> (SyncClient, MockTransport) {
+    let mock = MockTransport::new();
+    let client = SyncClient::builder("https://nextcloud.example/", 3, "user", "password")
+        .api_version(api_version)
+        .build()
+        .unwrap()
+        .with_transport(mock.clone());
+    (client, mock)
+}
+
+fn points() -> Vec<DataPoint> {
+    vec![
+        DataPoint::new("temperature", "kitchen", 21.5),
+        DataPoint::new("temperature", "garage", 12.0),
+        DataPoint::new("temperature", "attic", 30.0),
+    ]
+}
+
+fn urls(mock: &MockTransport) -> Vec<String> {
+    mock.requests()
+        .into_iter()
+        .map(|request| request.url)
+        .collect()
+}
+
+#[test]
+fn batches_use_the_bulk_endpoint() {
+    for (api_version, url) in [
+        (
+            ApiVersion::V2,
+            "https://nextcloud.example/apps/analytics/api/2.0/adddata/3",
+        ),
+        (
+            ApiVersion::V3,
+            "https://nextcloud.example/apps/analytics/api/3.0/data/3/add",
+        ),
+    ] {
+        let (client, mock) = with_api(api_version);
+        let receipt = client.send_batch(&points()).unwrap();
+        assert_eq!(receipt.report().unwrap().rows, 3);
+        assert_eq!(urls(&mock), [url]);
+        assert_eq!(mock.sent_points(), points());
+    }
+}
+
+#[test]
+fn batches_are_sent_one_point_at_a_time_with_v1() {
+    let (client, mock) = with_api(ApiVersion::V1);
+    let receipt = client.send_batch(&points()).unwrap();
+    assert_eq!(receipt.report().unwrap().rows, 3);
+    assert_eq!(
+        urls(&mock),
+        ["https://nextcloud.example/apps/analytics/api/1.0/adddata/3"; 3]
+    );
+    assert_eq!(mock.sent_points(), points());
+}
+
+#[test]
+fn v1_batches_stop_at_the_first_failure() {
+    let (client, mock) = with_api(ApiVersion::V1);
+    mock.respond(StatusCode::OK, r#"{"success":true}"#)
+        .respond(StatusCode::INTERNAL_SERVER_ERROR, "");
+    let err = client.send_batch(&points()).unwrap_err();
+    assert_eq!(err.class(), ErrorClass::Http);
+    assert_eq!(mock.sent_points(), points()[..2]);
+}
+
+#[test]
+fn deleting_data_needs_v2() {
+    let (client, mock) = with_api(ApiVersion::V1);
+    let err = client.delete_data("temperature", "kitchen").unwrap_err();
+    assert_eq!(err.class(), ErrorClass::Local);
+    assert!(mock.requests().is_empty());
+
+    let (client, mock) = with_api(ApiVersion::V2);
+    mock.respond(StatusCode::OK, r#"{"success":true}"#);
+    client.delete_data("temperature", "kitchen").unwrap();
+    assert_eq!(
+        urls(&mock),
+        ["https://nextcloud.example/apps/analytics/api/2.0/deletedata/3"]
+    );
+}
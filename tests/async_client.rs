@@ -0,0 +1,284 @@
+//! Requests of the asynchronous client, to a local fake server.
+#![cfg(feature = "async")]
+
+use std::collections::VecDeque;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use nextcloud_analytics_rs::testing::MockTransport;
+use nextcloud_analytics_rs::{
+    AnalyticsError, ApiVersion, AsyncClient, AsyncClientBuilder, Backoff, DataPoint, ErrorClass,
+    Outcome, RetryPolicy, ValueFormat,
+};
+
+const SUCCESS: &str = r#"{"success":true,"message":"Data update successfull"}"#;
+
+/// A request received by the fake server: its request line and body.
+#[derive(Clone, Debug)]
+struct Received {
+    line: String,
+    body: String,
+}
+
+/// A status and body to answer with, or `None` to never answer.
+type Answer = Option<(u16, &'static str)>;
+
+/// A fake Nextcloud server answering each request with the next queued answer, or a success.
+#[derive(Clone, Default)]
+struct FakeServer {
+    responses: Arc<Mutex<VecDeque<Answer>>>,
+    received: Arc<Mutex<Vec<Received>>>,
+}
+
+impl FakeServer {
+    /// Serves on a random port, returning the Nextcloud URL.
+    fn start(&self) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        let server = self.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let server = server.clone();
+                thread::spawn(move || server.answer(stream.unwrap()));
+            }
+        });
+        url
+    }
+
+    fn respond(&self, response: Answer) -> &Self {
+        self.responses.lock().unwrap().push_back(response);
+        self
+    }
+
+    fn received(&self) -> Vec<Received> {
+        self.received.lock().unwrap().clone()
+    }
+
+    fn answer(&self, mut stream: TcpStream) {
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+        let mut line = String::new();
+        reader.read_line(&mut line).unwrap();
+        let mut content_length = 0;
+        loop {
+            let mut header = String::new();
+            reader.read_line(&mut header).unwrap();
+            if header.trim().is_empty() {
+                break;
+            }
+            if let Some((name, value)) = header.split_once(':') {
+                if name.eq_ignore_ascii_case("content-length") {
+                    content_length = value.trim().parse().unwrap();
+                }
+            }
+        }
+        let mut body = vec![0; content_length];
+        reader.read_exact(&mut body).unwrap();
+        self.received.lock().unwrap().push(Received {
+            line: line.trim().to_string(),
+            body: String::from_utf8(body).unwrap(),
+        });
+
+        let response = self.responses.lock().unwrap().pop_front();
+        let (status, body) = match response {
+            Some(Some(response)) => response,
+            Some(None) => {
+                thread::sleep(Duration::from_secs(10));
+                return;
+            }
+            None => (200, SUCCESS),
+        };
+        let _ = write!(
+            stream,
+            "HTTP/1.1 {} Status\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            status,
+            body.len(),
+            body
+        );
+    }
+}
+
+fn builder(url: &str) -> AsyncClientBuilder {
+    AsyncClient::builder(url, 7, "user", "password")
+}
+
+#[tokio::test]
+async fn sends_with_the_api_version() {
+    let server = FakeServer::default();
+    let client = builder(&server.start())
+        .api_version(ApiVersion::V3)
+        .build()
+        .unwrap();
+
+    assert!(client
+        .send_data("temp", "kitchen", 21.5)
+        .await
+        .unwrap()
+        .was_sent());
+    let points = [
+        DataPoint::new("temp", "kitchen", 21.5),
+        DataPoint::new("temp", "garage", 12.0),
+    ];
+    assert!(client.send_batch(&points).await.unwrap().was_sent());
+
+    let received = server.received();
+    assert_eq!(received.len(), 2);
+    for request in &received {
+        assert_eq!(
+            request.line,
+            "POST /apps/analytics/api/3.0/data/7/add HTTP/1.1"
+        );
+        assert!(request.body.starts_with(r#"{"data":["#), "{}", request.body);
+    }
+}
+
+#[tokio::test]
+async fn retries_transient_failures() {
+    let server = FakeServer::default();
+    server.respond(Some((503, ""))).respond(Some((502, "")));
+    let policy = RetryPolicy::new(3)
+        .backoff(Backoff::Constant(Duration::from_millis(1)))
+        .jitter(false);
+    let client = builder(&server.start()).retry(policy).build().unwrap();
+
+    assert!(client
+        .send_data("temp", "kitchen", 21.5)
+        .await
+        .unwrap()
+        .was_sent());
+    assert_eq!(server.received().len(), 3);
+}
+
+#[tokio::test]
+async fn gives_up_after_the_last_attempt() {
+    let server = FakeServer::default();
+    server.respond(Some((503, ""))).respond(Some((503, "")));
+    let policy = RetryPolicy::new(2)
+        .backoff(Backoff::Constant(Duration::from_millis(1)))
+        .jitter(false);
+    let client = builder(&server.start()).retry(policy).build().unwrap();
+
+    let err = client.send_data("temp", "kitchen", 21.5).await.unwrap_err();
+    assert_eq!(err.class(), ErrorClass::Http);
+    assert_eq!(server.received().len(), 2);
+}
+
+#[tokio::test]
+async fn sends_values_in_the_value_format() {
+    let server = FakeServer::default();
+    let client = builder(&server.start())
+        .build()
+        .unwrap()
+        .with_value_format(ValueFormat::Number);
+
+    assert!(client
+        .send_data("temp", "kitchen", 21.5)
+        .await
+        .unwrap()
+        .was_sent());
+    assert_eq!(
+        server.received()[0].body,
+        r#"{"dimension1":"temp","dimension2":"kitchen","dimension3":21.5}"#
+    );
+}
+
+#[cfg(feature = "chrono")]
+#[tokio::test]
+async fn formats_timeline_dates_with_the_timestamp_format() {
+    use chrono::{TimeZone, Utc};
+    use nextcloud_analytics_rs::TimestampFormat;
+
+    let server = FakeServer::default();
+    let client = builder(&server.start())
+        .build()
+        .unwrap()
+        .with_timestamp_format(TimestampFormat::Custom("%Y-%m-%d %H:%M".to_string()));
+    let time = Utc.with_ymd_and_hms(2020, 1, 2, 3, 4, 5).unwrap();
+
+    let receipt = client.send_timeline_data("temp", time, 21.5).await.unwrap();
+    assert!(receipt.was_sent());
+    assert!(
+        server.received()[0]
+            .body
+            .contains(r#""dimension2":"2020-01-02 03:04""#),
+        "{}",
+        server.received()[0].body
+    );
+}
+
+/// Checks that `result` failed locally, because it was stopped.
+fn assert_stopped(result: Result<nextcloud_analytics_rs::Receipt, AnalyticsError>, what: &str) {
+    match result {
+        Err(err) => {
+            assert_eq!(err.class(), ErrorClass::Local);
+            assert!(err.to_string().contains(what), "{}", err);
+        }
+        Ok(receipt) => panic!("sent: {:?}", receipt.outcome),
+    }
+}
+
+#[tokio::test]
+async fn stops_at_the_deadline() {
+    let server = FakeServer::default();
+    server.respond(None);
+    let client = builder(&server.start()).build().unwrap();
+
+    let start = Instant::now();
+    let deadline = start + Duration::from_millis(100);
+    let point = DataPoint::new("temp", "kitchen", 21.5);
+    assert_stopped(
+        client.send_point_before(point, deadline).await,
+        "deadline passed",
+    );
+    assert!(start.elapsed() < Duration::from_secs(5));
+}
+
+#[tokio::test]
+async fn stops_when_cancelled() {
+    let server = FakeServer::default();
+    server.respond(None);
+    let client = builder(&server.start()).build().unwrap();
+
+    let start = Instant::now();
+    let cancel = tokio::time::delay_for(Duration::from_millis(50));
+    let points = [DataPoint::new("temp", "kitchen", 21.5)];
+    assert_stopped(client.send_batch_until(&points, cancel).await, "cancelled");
+    assert!(start.elapsed() < Duration::from_secs(5));
+}
+
+#[cfg(any(feature = "native-tls", feature = "rustls"))]
+#[test]
+fn applies_the_security_policy() {
+    use nextcloud_analytics_rs::Security;
+
+    let result = builder("http://nextcloud.invalid/")
+        .security(Security::new().https_only(true))
+        .build();
+    assert!(matches!(result, Err(AnalyticsError::Local(_))));
+
+    let result = builder("https://nextcloud.invalid/")
+        .security(Security::new().pin_spki_sha256("not base64!"))
+        .build();
+    assert!(matches!(result, Err(AnalyticsError::Local(_))));
+}
+
+#[test]
+fn empty_batches_match_the_sync_client() {
+    // Built outside the runtime, since the blocking client mustn't be dropped within one.
+    let sync = MockTransport::new().client(7);
+    let sync_receipt = sync.send_batch(&[]).unwrap();
+    assert!(matches!(sync_receipt.outcome, Outcome::Filtered));
+
+    let server = FakeServer::default();
+    let client = builder(&server.start()).build().unwrap();
+    let mut runtime = tokio::runtime::Builder::new()
+        .basic_scheduler()
+        .enable_all()
+        .build()
+        .unwrap();
+    let receipt = runtime.block_on(client.send_batch(&[])).unwrap();
+    assert!(matches!(receipt.outcome, Outcome::Filtered));
+    assert!(server.received().is_empty());
+}
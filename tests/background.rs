@@ -0,0 +1,233 @@
+//! Data points queued for a `BackgroundSender`, sent through a mock transport.
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use nextcloud_analytics_rs::testing::MockTransport;
+use nextcloud_analytics_rs::{
+    AdaptiveBatching, BackgroundSender, DataPoint, EnqueueError, Overflow, SenderOptions,
+    SenderStats,
+};
+use reqwest::StatusCode;
+
+fn point(i: u32) -> DataPoint {
+    DataPoint::new("counter", &i.to_string(), f64::from(i))
+}
+
+/// The values sent, in order.
+fn sent_values(mock: &MockTransport) -> Vec<f64> {
+    mock.sent_points().iter().map(|p| p.dimension3).collect()
+}
+
+fn values(range: std::ops::Range<u32>) -> Vec<f64> {
+    range.map(f64::from).collect()
+}
+
+/// A sender which only sends when stopped, or its queue holds a full batch.
+fn idle_sender(mock: &MockTransport, capacity: usize, overflow: Overflow) -> BackgroundSender {
+    BackgroundSender::start_with(
+        Arc::new(mock.client(3)),
+        SenderOptions::new(capacity, Duration::from_secs(3600)).overflow(overflow),
+    )
+}
+
+#[test]
+fn sends_queued_points_when_stopped() {
+    let mock = MockTransport::new();
+    let sender = BackgroundSender::start(Arc::new(mock.client(3)), 100, Duration::from_secs(60));
+    let handle = sender.handle();
+    for i in 0..10 {
+        handle.enqueue_point(point(i)).unwrap();
+    }
+    sender.stop();
+
+    assert_eq!(sent_values(&mock), values(0..10));
+    assert!(matches!(
+        handle.enqueue_point(point(10)),
+        Err(EnqueueError::Stopped(_))
+    ));
+}
+
+#[test]
+fn full_queue_refuses_new_points() {
+    let mock = MockTransport::new();
+    let sender = idle_sender(&mock, 3, Overflow::DropNewest);
+    let handle = sender.handle();
+    for i in 0..3 {
+        handle.enqueue_point(point(i)).unwrap();
+    }
+    for i in 3..5 {
+        let err = handle.enqueue_point(point(i)).unwrap_err();
+        assert_eq!(err.into_point(), Some(point(i)));
+    }
+    assert_eq!(
+        sender.stats(),
+        SenderStats {
+            queued: 3,
+            dropped: 2,
+            ..SenderStats::default()
+        }
+    );
+    sender.stop();
+
+    assert_eq!(sent_values(&mock), values(0..3));
+    assert_eq!(handle.stats().sent, 3);
+    assert_eq!(handle.stats().queued, 0);
+}
+
+#[test]
+fn full_queue_drops_oldest_points() {
+    let mock = MockTransport::new();
+    let sender = idle_sender(&mock, 3, Overflow::DropOldest);
+    let handle = sender.handle();
+    for i in 0..5 {
+        handle.enqueue_point(point(i)).unwrap();
+    }
+    assert_eq!(sender.stats().dropped, 2);
+    sender.stop();
+
+    assert_eq!(sent_values(&mock), values(2..5));
+}
+
+#[test]
+fn full_queue_blocks_until_room() {
+    let mock = MockTransport::new();
+    let sender = BackgroundSender::start_with(
+        Arc::new(mock.client(3)),
+        SenderOptions::new(2, Duration::from_millis(5)).overflow(Overflow::Block),
+    );
+    let handle = sender.handle();
+    let producer = std::thread::spawn(move || {
+        for i in 0..50 {
+            handle.enqueue_point(point(i)).unwrap();
+        }
+    });
+    producer.join().unwrap();
+    let stats = sender.stats();
+    sender.stop();
+
+    assert_eq!(stats.dropped, 0);
+    assert_eq!(sent_values(&mock), values(0..50));
+}
+
+/// Waits until the stats of `sender` match `done`, for up to 5 seconds.
+fn wait_for(sender: &BackgroundSender, done: impl Fn(SenderStats) -> bool) {
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while !done(sender.stats()) {
+        assert!(Instant::now() < deadline, "{:?}", sender.stats());
+        std::thread::sleep(Duration::from_millis(1));
+    }
+}
+
+#[test]
+fn transient_failures_are_sent_again_in_order() {
+    let mock = MockTransport::new();
+    mock.respond(StatusCode::INTERNAL_SERVER_ERROR, "");
+    // Sends as soon as 3 points are queued.
+    let sender = BackgroundSender::start_with(
+        Arc::new(mock.client(3)),
+        SenderOptions::new(10, Duration::from_secs(3600))
+            .adaptive(AdaptiveBatching::new(Duration::from_secs(1)).batch_size(3, 3)),
+    );
+    let handle = sender.handle();
+    for i in 0..3 {
+        handle.enqueue_point(point(i)).unwrap();
+    }
+    wait_for(&sender, |stats| stats.retries == 1);
+    for i in 3..5 {
+        handle.enqueue_point(point(i)).unwrap();
+    }
+    sender.stop();
+
+    // The 1.0 API sends the points of a batch one by one, up to the first failure; the whole
+    // batch is sent again, with the points queued since.
+    assert_eq!(sent_values(&mock), [vec![0.0], values(0..5)].concat());
+    let stats = handle.stats();
+    assert_eq!((stats.sent, stats.retries, stats.failed), (5, 1, 0));
+}
+
+#[test]
+fn points_past_their_ttl_are_dropped() {
+    let mock = MockTransport::new();
+    let sender = BackgroundSender::start_with(
+        Arc::new(mock.client(3)),
+        SenderOptions::new(10, Duration::from_secs(3600)).ttl(Duration::from_secs(3600)),
+    );
+    let handle = sender.handle();
+    handle.enqueue_point(point(0)).unwrap();
+    handle
+        .enqueue_point_with_ttl(point(1), Duration::from_millis(1))
+        .unwrap();
+    handle.enqueue_point(point(2)).unwrap();
+    std::thread::sleep(Duration::from_millis(10));
+    sender.stop();
+
+    assert_eq!(sent_values(&mock), vec![0.0, 2.0]);
+    let stats = handle.stats();
+    assert_eq!((stats.sent, stats.expired), (2, 1));
+}
+
+#[test]
+fn expired_points_make_room_in_a_full_queue() {
+    let mock = MockTransport::new();
+    let sender = BackgroundSender::start_with(
+        Arc::new(mock.client(3)),
+        SenderOptions::new(2, Duration::from_secs(3600)).ttl(Duration::from_millis(1)),
+    );
+    let handle = sender.handle();
+    handle.enqueue_point(point(0)).unwrap();
+    handle.enqueue_point(point(1)).unwrap();
+    std::thread::sleep(Duration::from_millis(10));
+    handle
+        .enqueue_point_with_ttl(point(2), Duration::from_secs(3600))
+        .unwrap();
+    sender.stop();
+
+    assert_eq!(sent_values(&mock), vec![2.0]);
+    let stats = handle.stats();
+    assert_eq!((stats.dropped, stats.expired, stats.sent), (0, 2, 1));
+}
+
+#[test]
+fn rejected_and_unsent_batches_are_counted_as_failed() {
+    let mock = MockTransport::new();
+    mock.respond(StatusCode::BAD_REQUEST, "invalid data");
+    let sender = BackgroundSender::start_with(
+        Arc::new(mock.client(3)),
+        SenderOptions::new(10, Duration::from_secs(3600))
+            .adaptive(AdaptiveBatching::new(Duration::from_secs(1)).batch_size(2, 2)),
+    );
+    let handle = sender.handle();
+    handle.enqueue_point(point(0)).unwrap();
+    handle.enqueue_point(point(1)).unwrap();
+    wait_for(&sender, |stats| stats.failed == 2);
+
+    // The server is down for the last flush.
+    mock.respond(StatusCode::SERVICE_UNAVAILABLE, "");
+    handle.enqueue_point(point(2)).unwrap();
+    sender.stop();
+
+    // The rejected batch, which failed at its first point, isn't sent again; the last one is
+    // only tried once.
+    assert_eq!(sent_values(&mock), vec![0.0, 2.0]);
+    let stats = handle.stats();
+    assert_eq!((stats.sent, stats.failed, stats.retries), (0, 3, 1));
+}
+
+#[cfg(feature = "chrono")]
+#[test]
+fn enqueue_fails_on_invalid_time_format() {
+    use nextcloud_analytics_rs::TimestampFormat;
+
+    let mock = MockTransport::new();
+    let client = mock
+        .client(3)
+        .with_timestamp_format(TimestampFormat::Custom("%Q".to_string()));
+    let sender = BackgroundSender::start(Arc::new(client), 100, Duration::from_secs(60));
+
+    let err = sender.handle().enqueue("temperature", 21.5).unwrap_err();
+    assert!(matches!(err, EnqueueError::Time(_)));
+    assert!(err.into_point().is_none());
+    sender.stop();
+    assert!(mock.requests().is_empty());
+}
@@ -0,0 +1,151 @@
+//! Conditional requests and the response cache, through a mock transport.
+
+use std::sync::Arc;
+
+use nextcloud_analytics_rs::testing::MockTransport;
+use nextcloud_analytics_rs::{CacheStore, CachedResponse, MemoryCache, Response, SyncClient};
+use reqwest::header::{HeaderValue, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED};
+use reqwest::StatusCode;
+use serde_json::json;
+
+const LAST_MODIFIED_AT: &str = "Mon, 01 Jan 2024 10:00:00 GMT";
+
+/// A cache shared with the test, to look into it.
+#[derive(Clone, Default)]
+struct Shared(Arc<MemoryCache>);
+
+impl CacheStore for Shared {
+    fn get(&self, url: &str) -> Option<CachedResponse> {
+        self.0.get(url)
+    }
+
+    fn put(&self, url: &str, response: CachedResponse) {
+        self.0.put(url, response)
+    }
+
+    fn clear(&self) {
+        self.0.clear()
+    }
+}
+
+fn report(name: &str) -> String {
+    json!({"id": 7, "name": name, "type": 2, "dataset": 3}).to_string()
+}
+
+/// A report named `name`, with validators.
+fn validated(name: &str) -> Response {
+    let mut response = Response::new(StatusCode::OK, report(name));
+    response
+        .headers
+        .insert(ETAG, HeaderValue::from_static("\"v1\""));
+    response
+        .headers
+        .insert(LAST_MODIFIED, HeaderValue::from_static(LAST_MODIFIED_AT));
+    response
+}
+
+fn client(mock: &MockTransport, cache: &Shared) -> SyncClient {
+    mock.client(3).with_response_cache(cache.clone())
+}
+
+#[test]
+fn sends_the_validators_of_the_cached_response() {
+    let mock = MockTransport::new();
+    let cache = Shared::default();
+    let client = client(&mock, &cache);
+
+    mock.respond_with(validated("Temperatures"));
+    assert_eq!(client.get_report(7).unwrap().name, "Temperatures");
+    let first = &mock.requests()[0];
+    assert!(!first.headers.contains_key(IF_NONE_MATCH));
+    assert!(!first.headers.contains_key(IF_MODIFIED_SINCE));
+    assert_eq!(cache.0.len(), 1);
+
+    mock.respond(StatusCode::NOT_MODIFIED, "");
+    assert_eq!(client.get_report(7).unwrap().name, "Temperatures");
+    let second = &mock.requests()[1];
+    assert_eq!(second.headers[IF_NONE_MATCH], "\"v1\"");
+    assert_eq!(second.headers[IF_MODIFIED_SINCE], LAST_MODIFIED_AT);
+}
+
+#[test]
+fn changed_responses_replace_the_cached_one() {
+    let mock = MockTransport::new();
+    let cache = Shared::default();
+    let client = client(&mock, &cache);
+
+    mock.respond_with(validated("Temperatures"));
+    let _ = client.get_report(7).unwrap();
+    mock.respond_with(validated("Renamed"));
+    assert_eq!(client.get_report(7).unwrap().name, "Renamed");
+    mock.respond(StatusCode::NOT_MODIFIED, "");
+    assert_eq!(client.get_report(7).unwrap().name, "Renamed");
+}
+
+#[test]
+fn responses_without_validators_are_not_cached() {
+    let mock = MockTransport::new();
+    let cache = Shared::default();
+    let client = client(&mock, &cache);
+
+    mock.respond(StatusCode::OK, report("Temperatures"));
+    let _ = client.get_report(7).unwrap();
+    assert!(cache.0.is_empty());
+    mock.respond(StatusCode::OK, report("Temperatures"));
+    let _ = client.get_report(7).unwrap();
+    assert!(!mock.requests()[1].headers.contains_key(IF_NONE_MATCH));
+}
+
+#[test]
+fn writes_invalidate_the_cache() {
+    let mock = MockTransport::new();
+    let cache = Shared::default();
+    let client = client(&mock, &cache);
+
+    mock.respond_with(validated("Temperatures"));
+    let _ = client.get_report(7).unwrap();
+
+    // A failed write keeps the cache.
+    mock.respond(StatusCode::INTERNAL_SERVER_ERROR, "");
+    assert!(client.send_data("temp", "kitchen", 21.5).is_err());
+    assert_eq!(cache.0.len(), 1);
+
+    let _ = client.send_data("temp", "kitchen", 21.5).unwrap();
+    assert!(cache.0.is_empty());
+
+    // The next read isn't conditional.
+    mock.respond(StatusCode::OK, report("Temperatures"));
+    let _ = client.get_report(7).unwrap();
+    assert!(!mock.requests()[3].headers.contains_key(IF_NONE_MATCH));
+}
+
+fn cached(body: &str) -> CachedResponse {
+    CachedResponse {
+        body: body.to_string(),
+        etag: Some("\"v1\"".to_string()),
+        last_modified: None,
+    }
+}
+
+#[test]
+fn memory_caches_forget_the_least_recently_used_response() {
+    let cache = MemoryCache::with_capacity(2);
+    cache.put("a", cached("a"));
+    cache.put("b", cached("b"));
+    assert_eq!(cache.get("a").unwrap().body, "a");
+    cache.put("c", cached("c"));
+    assert_eq!(cache.len(), 2);
+    assert!(cache.get("b").is_none());
+    assert!(cache.get("a").is_some());
+    assert!(cache.get("c").is_some());
+
+    // Replacing a response doesn't evict another one.
+    cache.put("c", cached("c2"));
+    assert_eq!(cache.len(), 2);
+    assert_eq!(cache.get("c").unwrap().body, "c2");
+
+    let empty = MemoryCache::with_capacity(0);
+    empty.put("a", cached("a"));
+    assert!(empty.is_empty());
+    assert_eq!(MemoryCache::new().len(), 0);
+}
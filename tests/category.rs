@@ -0,0 +1,91 @@
+//! Snapshots of categorical datasets, through a mock transport.
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use nextcloud_analytics_rs::testing::MockTransport;
+use nextcloud_analytics_rs::{ApiVersion, SyncClient};
+use reqwest::StatusCode;
+
+const NO_ROWS: &str = r#"{"header":[],"data":[]}"#;
+
+fn client(mock: &MockTransport) -> SyncClient {
+    SyncClient::builder("https://nextcloud.invalid/", 3, "user", "password")
+        .api_version(ApiVersion::V3)
+        .build()
+        .unwrap()
+        .with_transport(mock.clone())
+}
+
+fn snapshot(firefox: f64) -> HashMap<String, f64> {
+    let mut snapshot = HashMap::new();
+    snapshot.insert("firefox".to_string(), firefox);
+    snapshot.insert("nextcloud".to_string(), 512.0);
+    snapshot
+}
+
+#[test]
+fn skips_unchanged_snapshots() {
+    let mock = MockTransport::new();
+    let client = client(&mock).with_snapshot_change_detection(Duration::from_secs(3600));
+
+    mock.respond(StatusCode::OK, NO_ROWS);
+    let changes = client
+        .replace_category_snapshot("memory", &snapshot(1024.0))
+        .unwrap();
+    assert_eq!((changes.added, changes.skipped), (2, false));
+    assert_eq!(mock.requests().len(), 2);
+
+    // A clone shares the hashes of the snapshots pushed.
+    let changes = client
+        .clone()
+        .replace_category_snapshot("memory", &snapshot(1024.0))
+        .unwrap();
+    assert!(changes.skipped);
+    assert_eq!(mock.requests().len(), 2);
+
+    // Another group, or a changed snapshot, is pushed.
+    mock.respond(StatusCode::OK, NO_ROWS);
+    assert!(
+        !client
+            .replace_category_snapshot("cpu", &snapshot(1024.0))
+            .unwrap()
+            .skipped
+    );
+    mock.respond(StatusCode::OK, NO_ROWS);
+    assert!(
+        !client
+            .replace_category_snapshot("memory", &snapshot(2048.0))
+            .unwrap()
+            .skipped
+    );
+    assert_eq!(mock.requests().len(), 6);
+}
+
+#[test]
+fn pushes_unchanged_snapshots_after_the_heartbeat() {
+    let mock = MockTransport::new();
+    let client = client(&mock).with_snapshot_change_detection(Duration::from_millis(20));
+
+    for _ in 0..2 {
+        mock.respond(StatusCode::OK, NO_ROWS);
+        let changes = client
+            .replace_category_snapshot("memory", &snapshot(1024.0))
+            .unwrap();
+        assert!(!changes.skipped);
+        std::thread::sleep(Duration::from_millis(30));
+    }
+    assert_eq!(mock.requests().len(), 4);
+}
+
+#[test]
+fn compares_every_snapshot_without_detection() {
+    let mock = MockTransport::new();
+    let rows = r#"{"header":[],"data":[["firefox","memory","1024"],["nextcloud","memory","512"]]}"#;
+    mock.respond(StatusCode::OK, rows);
+    let changes = client(&mock)
+        .replace_category_snapshot("memory", &snapshot(1024.0))
+        .unwrap();
+    assert_eq!(changes, Default::default());
+    assert_eq!(mock.requests().len(), 1);
+}
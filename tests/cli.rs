@@ -0,0 +1,376 @@
+//! Exit codes and JSON output of the `nca` binary, against a local fake server.
+#![cfg(feature = "cli")]
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpListener;
+use std::process::{Command, Output, Stdio};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use serde_json::Value;
+
+const SUCCESS: &str = r#"{"success":true,"message":"Data update successfull"}"#;
+
+/// Serves every request with `status` and `body`, returning the Nextcloud URL.
+fn serve(status: u16, body: &'static str) -> String {
+    serve_recording(status, body, Arc::default())
+}
+
+/// Like `serve`, recording the bodies of the requests in `received`.
+fn serve_recording(status: u16, body: &'static str, received: Arc<Mutex<Vec<String>>>) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/", listener.local_addr().unwrap());
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = stream.unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut content_length = 0;
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if line.trim().is_empty() {
+                    break;
+                }
+                if let Some((name, value)) = line.split_once(':') {
+                    if name.eq_ignore_ascii_case("content-length") {
+                        content_length = value.trim().parse().unwrap();
+                    }
+                }
+            }
+            let mut request_body = vec![0; content_length];
+            reader.read_exact(&mut request_body).unwrap();
+            received
+                .lock()
+                .unwrap()
+                .push(String::from_utf8(request_body).unwrap());
+            let _ = write!(
+                stream,
+                "HTTP/1.1 {} Status\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                status,
+                body.len(),
+                body
+            );
+        }
+    });
+    url
+}
+
+/// A URL on which nothing listens.
+fn unreachable() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    format!("http://{}/", listener.local_addr().unwrap())
+}
+
+fn command(url: &str, args: &[&str]) -> Command {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_nca"));
+    command
+        .args(args)
+        .env("NEXTCLOUD_ANALYTICS_URL", url)
+        .env("NEXTCLOUD_ANALYTICS_USER", "user")
+        .env("NEXTCLOUD_ANALYTICS_PASSWORD", "password")
+        .env("NEXTCLOUD_ANALYTICS_COLLECTION", "3");
+    command
+}
+
+fn nca(url: &str, args: &[&str]) -> Output {
+    command(url, args).output().unwrap()
+}
+
+/// Runs `nca` with `stdin` as its input.
+fn nca_with_input(url: &str, args: &[&str], stdin: &str) -> Output {
+    let mut child = command(url, args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(stdin.as_bytes())
+        .unwrap();
+    child.wait_with_output().unwrap()
+}
+
+fn json(output: &Output) -> Value {
+    serde_json::from_slice(&output.stdout).unwrap()
+}
+
+const SEND: &[&str] = &[
+    "--output",
+    "json",
+    "send",
+    "--key",
+    "temp",
+    "--value",
+    "21.5",
+    "--dimension2",
+    "kitchen",
+];
+
+#[test]
+fn prints_the_outcome_of_a_send() {
+    let output = nca(&serve(200, SUCCESS), SEND);
+
+    assert_eq!(output.status.code(), Some(0));
+    let result = json(&output);
+    assert_eq!(result["success"], true);
+    assert_eq!(result["outcome"], "sent");
+}
+
+#[test]
+fn exits_with_the_class_of_the_error() {
+    let output = nca(&unreachable(), SEND);
+    assert_eq!(output.status.code(), Some(3));
+    let result = json(&output);
+    assert_eq!(result["success"], false);
+    assert_eq!(result["error"]["code"], "E_NETWORK");
+
+    let output = nca(&serve(401, ""), SEND);
+    assert_eq!(output.status.code(), Some(4));
+    assert_eq!(json(&output)["error"]["code"], "E_AUTH");
+
+    let output = nca(&serve(404, ""), SEND);
+    assert_eq!(output.status.code(), Some(5));
+    assert_eq!(json(&output)["error"]["code"], "E_NOT_FOUND");
+}
+
+#[test]
+fn prints_errors_as_text_on_stderr() {
+    let output = nca(&unreachable(), &SEND[2..]);
+
+    assert_eq!(output.status.code(), Some(3));
+    assert!(output.stdout.is_empty());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.starts_with("nca: "), "{}", stderr);
+}
+
+#[test]
+fn prints_failed_checks_as_json() {
+    let output = nca(&unreachable(), &["--output", "json", "check"]);
+
+    assert_eq!(output.status.code(), Some(1));
+    let result = json(&output);
+    assert_eq!(result["success"], false);
+    assert_eq!(result["checks"][0]["name"], "server");
+    assert_eq!(result["checks"][0]["status"], "failed");
+    assert_eq!(result["checks"][1]["status"], "skipped");
+}
+
+#[test]
+fn pipes_the_lines_of_stdin() {
+    let received = Arc::default();
+    let url = serve_recording(200, SUCCESS, Arc::clone(&received));
+    let input = "temp 21.5\noops\n\nhumidity 40 1577934245\nhumidity 41 2020-01-02T04:04:05Z\n";
+    let output = nca_with_input(&url, &["--output", "json", "pipe"], input);
+
+    assert_eq!(output.status.code(), Some(0));
+    let result = json(&output);
+    assert_eq!(result["read"], 4);
+    assert_eq!(result["invalid"], 1);
+    assert_eq!(result["sent"], 3);
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        stderr.contains("line 2: expected `key value [time]`"),
+        "{}",
+        stderr
+    );
+
+    let received = received.lock().unwrap();
+    assert_eq!(received.len(), 3);
+    assert!(received[0].contains(r#""dimension1":"temp""#));
+    assert!(
+        received[1].contains(r#""dimension2":"Thu, 2 Jan 2020 03:04:05 +0000""#),
+        "{:?}",
+        received
+    );
+    assert!(received[2].contains(r#""dimension2":"Thu, 2 Jan 2020 04:04:05 +0000""#));
+}
+
+#[test]
+fn refuses_invalid_durations() {
+    let output = nca(&unreachable(), &["pipe", "--flush-interval", "1y"]);
+
+    assert_eq!(output.status.code(), Some(2));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("unknown unit"), "{}", stderr);
+}
+
+#[test]
+fn prints_the_progress_of_imports() {
+    let path = std::env::temp_dir().join(format!("nca-test-import-{}.csv", std::process::id()));
+    std::fs::write(&path, "key,room,value\ntemp,kitchen,21.5\ntemp,garage,12\n").unwrap();
+    let output = nca(
+        &serve(200, SUCCESS),
+        &["import", path.to_str().unwrap(), "--progress"],
+    );
+    let _ = std::fs::remove_file(&path);
+
+    assert_eq!(output.status.code(), Some(0));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert_eq!(stderr, "nca: 2 rows sent, 100%, 0s left\n");
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "2 rows sent\n");
+}
+
+const METRICS: &str =
+    "# TYPE node_load1 gauge\nnode_load1 0.5\nnode_cpu_seconds_total{cpu=\"0\",mode=\"idle\"} 42\n";
+
+#[test]
+fn scrapes_a_prometheus_endpoint() {
+    let metrics = serve(200, METRICS) + "metrics";
+    let received = Arc::default();
+    let url = serve_recording(200, SUCCESS, Arc::clone(&received));
+    let output = nca(&url, &["--output", "json", "scrape", &metrics]);
+
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(json(&output)["sent"], 2);
+    let received = received.lock().unwrap();
+    assert_eq!(received.len(), 2);
+    assert!(received[0].contains(r#""dimension1":"node_load1""#));
+    assert!(received[1].contains(r#""dimension1":"node_cpu_seconds_total{cpu=0,mode=idle}""#));
+}
+
+#[test]
+fn exits_with_the_class_of_a_failed_scrape() {
+    let output = nca(
+        &serve(200, SUCCESS),
+        &["--output", "json", "scrape", &unreachable()],
+    );
+
+    assert_eq!(output.status.code(), Some(3));
+    assert_eq!(json(&output)["error"]["code"], "E_NETWORK");
+}
+
+/// A temporary directory for the platform directories of `nca`, removed when dropped.
+struct Home(std::path::PathBuf);
+
+impl Home {
+    fn new(name: &str) -> Self {
+        let path = std::env::temp_dir().join(format!("nca-test-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&path);
+        std::fs::create_dir_all(&path).unwrap();
+        Home(path)
+    }
+
+    /// `nca` with its directories in this one, and without the `NEXTCLOUD_ANALYTICS_*`
+    /// variables.
+    fn nca(&self, args: &[&str]) -> Output {
+        command("", args)
+            .env_remove("NEXTCLOUD_ANALYTICS_URL")
+            .env_remove("NEXTCLOUD_ANALYTICS_USER")
+            .env_remove("NEXTCLOUD_ANALYTICS_PASSWORD")
+            .env_remove("NEXTCLOUD_ANALYTICS_COLLECTION")
+            .env("HOME", &self.0)
+            .env("XDG_CONFIG_HOME", self.0.join("config"))
+            .env("XDG_DATA_HOME", self.0.join("data"))
+            .output()
+            .unwrap()
+    }
+}
+
+impl Drop for Home {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn reads_the_settings_from_the_default_config_file() {
+    let home = Home::new("config");
+    let url = serve(200, SUCCESS);
+    let dir = home.0.join("config/nca");
+    std::fs::create_dir_all(&dir).unwrap();
+    let settings = format!(
+        "url = \"{}\"\ncollection = 3\nuser = \"user\"\npassword = \"password\"\n",
+        url
+    );
+    std::fs::write(dir.join("config.toml"), settings).unwrap();
+
+    let output = home.nca(SEND);
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(json(&output)["outcome"], "sent");
+
+    let output = home.nca(&["--output", "json", "paths"]);
+    let paths = json(&output);
+    assert_eq!(paths["config"]["exists"], true);
+    assert_eq!(
+        paths["config"]["path"],
+        dir.join("config.toml").display().to_string()
+    );
+    assert_eq!(paths["spool"]["exists"], false);
+    assert_eq!(
+        paths["spool"]["path"],
+        home.0.join("data/nca/spool.jsonl").display().to_string()
+    );
+}
+
+#[test]
+fn spools_values_until_the_server_is_back() {
+    let home = Home::new("spool");
+    let spool = home.0.join("spool.jsonl");
+    let spool = spool.to_str().unwrap();
+    let args = |value: &'static str| {
+        let mut args = SEND[..SEND.len() - 4].to_vec();
+        args.extend(["--value", value, "--spool", spool]);
+        args
+    };
+
+    let output = nca(&unreachable(), &args("1"));
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(json(&output)["outcome"], "spooled");
+
+    let received = Arc::default();
+    let url = serve_recording(200, SUCCESS, Arc::clone(&received));
+    let output = nca(&url, &args("2"));
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(json(&output)["outcome"], "sent");
+    let received = received.lock().unwrap();
+    assert_eq!(received.len(), 2);
+    assert!(
+        received[0].contains(r#""dimension3":"1""#),
+        "{:?}",
+        received
+    );
+    assert!(
+        received[1].contains(r#""dimension3":"2""#),
+        "{:?}",
+        received
+    );
+}
+
+/// The step of `doctor` named `name`.
+fn doctor_step(result: &Value, name: &str) -> Value {
+    result["checks"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|step| step["name"] == name)
+        .cloned()
+        .unwrap()
+}
+
+#[test]
+fn doctor_shakes_hands_over_tls() {
+    let url = serve(200, SUCCESS).replace("http://", "https://");
+    let output = nca(&url, &["--output", "json", "doctor"]);
+
+    assert_eq!(output.status.code(), Some(1));
+    let tls = doctor_step(&json(&output), "tls");
+    assert_eq!(tls["status"], "failed");
+    let reason = tls["reason"].as_str().unwrap();
+    assert!(reason.starts_with("the handshake failed: "), "{}", reason);
+
+    let output = nca(&serve(200, SUCCESS), &["--output", "json", "doctor"]);
+    let result = json(&output);
+    let tls = doctor_step(&result, "tls");
+    assert_eq!(tls["reason"], "not used, the URL is plain HTTP");
+    let payload = result["payload"].as_str().unwrap();
+    let year = chrono::Utc::now().format("%Y").to_string();
+    assert!(
+        payload.contains(r#""dimension1":"nca_doctor""#) && payload.contains(&year),
+        "{}",
+        payload
+    );
+}
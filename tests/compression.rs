@@ -0,0 +1,132 @@
+//! Compressed batches: their bodies decompress back to the JSON sent, and the client falls
+//! back to uncompressed bodies only when the server can't read them.
+
+#![cfg(feature = "compression")]
+
+use std::io::Read;
+
+use flate2::read::{GzDecoder, ZlibDecoder};
+use nextcloud_analytics_rs::testing::MockTransport;
+use nextcloud_analytics_rs::{ApiVersion, Compression, DataPoint, Request, SyncClient};
+use reqwest::StatusCode;
+
+/// Enough points for a body worth compressing.
+fn points() -> Vec<DataPoint> {
+    (0..100)
+        .map(|i| DataPoint::new("temperature", &format!("room {}", i), f64::from(i) / 2.0))
+        .collect()
+}
+
+fn compressing(compression: Compression) -> (SyncClient, MockTransport) {
+    let mock = MockTransport::new();
+    let client = SyncClient::builder("https://nextcloud.example/", 3, "user", "password")
+        .api_version(ApiVersion::V2)
+        .compression(compression)
+        .build()
+        .unwrap()
+        .with_transport(mock.clone());
+    (client, mock)
+}
+
+fn encoding(request: &Request) -> Option<&str> {
+    request
+        .headers
+        .get("content-encoding")
+        .map(|value| value.to_str().unwrap())
+}
+
+fn decompressed(request: &Request) -> String {
+    let body = request.body.as_deref().unwrap();
+    let mut text = String::new();
+    match encoding(request) {
+        Some("gzip") => GzDecoder::new(body).read_to_string(&mut text),
+        Some("deflate") => ZlibDecoder::new(body).read_to_string(&mut text),
+        other => panic!("unexpected encoding {:?}", other),
+    }
+    .unwrap();
+    text
+}
+
+/// The body of the same batch sent without compression.
+fn uncompressed_body() -> String {
+    let mock = MockTransport::new();
+    let client = SyncClient::builder("https://nextcloud.example/", 3, "user", "password")
+        .api_version(ApiVersion::V2)
+        .build()
+        .unwrap()
+        .with_transport(mock.clone());
+    assert!(client.send_batch(&points()).unwrap().was_sent());
+    mock.requests()[0].body_text().unwrap().to_string()
+}
+
+#[test]
+fn gzip_round_trip() {
+    let (client, mock) = compressing(Compression::Gzip);
+    assert!(client.send_batch(&points()).unwrap().was_sent());
+    let requests = mock.requests();
+    assert_eq!(requests.len(), 1);
+    assert_eq!(decompressed(&requests[0]), uncompressed_body());
+    assert!(requests[0].body.as_ref().unwrap().len() < uncompressed_body().len());
+}
+
+#[test]
+fn deflate_round_trip() {
+    let (client, mock) = compressing(Compression::Deflate);
+    assert!(client.send_batch(&points()).unwrap().was_sent());
+    let requests = mock.requests();
+    assert_eq!(requests.len(), 1);
+    assert_eq!(decompressed(&requests[0]), uncompressed_body());
+}
+
+#[test]
+fn small_batches_are_not_compressed() {
+    let (client, mock) = compressing(Compression::Gzip);
+    let point = DataPoint::new("temperature", "kitchen", 21.5);
+    assert!(client.send_batch(&[point]).unwrap().was_sent());
+    assert_eq!(encoding(&mock.requests()[0]), None);
+}
+
+#[test]
+fn falls_back_on_unsupported_media_type() {
+    let (client, mock) = compressing(Compression::Gzip);
+    mock.respond(StatusCode::UNSUPPORTED_MEDIA_TYPE, "");
+    assert!(client.send_batch(&points()).unwrap().was_sent());
+    assert!(client.send_batch(&points()).unwrap().was_sent());
+
+    let requests = mock.requests();
+    assert_eq!(requests.len(), 3);
+    assert_eq!(encoding(&requests[0]), Some("gzip"));
+    // Sent again uncompressed, and never compressed afterwards.
+    assert_eq!(encoding(&requests[1]), None);
+    assert_eq!(encoding(&requests[2]), None);
+}
+
+#[test]
+fn falls_back_on_bad_request_without_body() {
+    let (client, mock) = compressing(Compression::Deflate);
+    mock.respond(StatusCode::BAD_REQUEST, "");
+    assert!(client.send_batch(&points()).unwrap().was_sent());
+
+    let requests = mock.requests();
+    assert_eq!(requests.len(), 2);
+    assert_eq!(encoding(&requests[1]), None);
+}
+
+#[test]
+fn keeps_errors_about_the_data() {
+    let (client, mock) = compressing(Compression::Gzip);
+    mock.respond(
+        StatusCode::BAD_REQUEST,
+        r#"{"message":"invalid dimension"}"#,
+    );
+    assert!(client.send_batch(&points()).is_err());
+    mock.respond(StatusCode::UNPROCESSABLE_ENTITY, "");
+    assert!(client.send_batch(&points()).is_err());
+
+    // Both batches were compressed, and not sent again.
+    let requests = mock.requests();
+    assert_eq!(requests.len(), 2);
+    assert!(requests
+        .iter()
+        .all(|request| encoding(request) == Some("gzip")));
+}
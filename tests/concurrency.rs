@@ -0,0 +1,150 @@
+//! Many threads sending through a `BackgroundSender`, a `BufferedClient` and a client queueing
+//! points during maintenance at once, to a server failing now and then: every point reaches it
+//! exactly once.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use nextcloud_analytics_rs::{
+    AnalyticsError, ApiVersion, BackgroundSender, BufferedClient, DataPoint, Overflow, Request,
+    Response, SenderOptions, SyncClient, SyncClientBuilder, Transport,
+};
+use reqwest::header::{HeaderMap, HeaderValue};
+use reqwest::StatusCode;
+use serde_json::Value;
+
+const SUCCESS: &str = r#"{"success":true,"message":"Data update successfull"}"#;
+
+const THREADS: usize = 4;
+const POINTS: u32 = 100;
+
+/// A server in maintenance mode for every third request until it's healthy, counting the data
+/// points it accepts by (dimension1, dimension2).
+#[derive(Clone, Default)]
+struct FlakyServer {
+    received: Arc<Mutex<HashMap<(String, String), usize>>>,
+    requests: Arc<AtomicUsize>,
+    failures: Arc<AtomicUsize>,
+    healthy: Arc<AtomicBool>,
+}
+
+impl FlakyServer {
+    fn client(&self) -> SyncClient {
+        self.client_with(|builder| builder)
+    }
+
+    fn client_with<F>(&self, configure: F) -> SyncClient
+    where
+        F: FnOnce(SyncClientBuilder) -> SyncClientBuilder,
+    {
+        let builder = SyncClient::builder("https://nextcloud.invalid/", 3, "user", "password")
+            .api_version(ApiVersion::V3);
+        configure(builder)
+            .build()
+            .unwrap()
+            .with_transport(self.clone())
+    }
+}
+
+impl Transport for FlakyServer {
+    fn send(&self, request: Request) -> Result<Response, AnalyticsError> {
+        let n = self.requests.fetch_add(1, Ordering::SeqCst);
+        if !self.healthy.load(Ordering::SeqCst) && n.is_multiple_of(3) {
+            let mut response = Response::new(StatusCode::SERVICE_UNAVAILABLE, "");
+            let mut headers = HeaderMap::new();
+            headers.insert(
+                "X-Nextcloud-Maintenance-Mode",
+                HeaderValue::from_static("1"),
+            );
+            response.headers = headers;
+            self.failures.fetch_add(1, Ordering::SeqCst);
+            return Ok(response);
+        }
+
+        let body: Value = serde_json::from_str(request.body_text().unwrap()).unwrap();
+        let mut received = self.received.lock().unwrap();
+        for row in body["data"].as_array().unwrap() {
+            let key = (
+                row["dimension1"].as_str().unwrap().to_string(),
+                row["dimension2"].as_str().unwrap().to_string(),
+            );
+            *received.entry(key).or_default() += 1;
+        }
+        Ok(Response::new(StatusCode::OK, SUCCESS))
+    }
+}
+
+fn point(prefix: &str, thread: usize, i: u32) -> DataPoint {
+    DataPoint::new(
+        &format!("{}-{}", prefix, thread),
+        &i.to_string(),
+        f64::from(i),
+    )
+}
+
+/// Spawns a thread for each producer, sending its points with `send`.
+fn produce<F>(prefix: &'static str, send: F) -> Vec<thread::JoinHandle<()>>
+where
+    F: Fn(DataPoint) + Clone + Send + 'static,
+{
+    (0..THREADS)
+        .map(|thread| {
+            let send = send.clone();
+            thread::spawn(move || {
+                for i in 0..POINTS {
+                    send(point(prefix, thread, i));
+                }
+            })
+        })
+        .collect()
+}
+
+#[test]
+fn no_point_is_lost_or_duplicated() {
+    let server = FlakyServer::default();
+    let path: PathBuf =
+        std::env::temp_dir().join(format!("nca-test-concurrency-{}.jsonl", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+
+    let sender = BackgroundSender::start_with(
+        Arc::new(server.client()),
+        SenderOptions::new(16, Duration::from_millis(1)).overflow(Overflow::Block),
+    );
+    let buffered = Arc::new(BufferedClient::new(server.client(), &path));
+    let queueing =
+        Arc::new(server.client_with(|builder| builder.queue_during_maintenance(Duration::ZERO)));
+
+    let handle = sender.handle();
+    let mut producers = produce("background", move |point| {
+        handle.enqueue_point(point).unwrap();
+    });
+    let spool = Arc::clone(&buffered);
+    producers.extend(produce("buffered", move |point| {
+        let _ = spool.send_point(point).unwrap();
+    }));
+    let client = Arc::clone(&queueing);
+    producers.extend(produce("maintenance", move |point| {
+        let _ = client.send_point(point).unwrap();
+    }));
+    for producer in producers {
+        producer.join().unwrap();
+    }
+
+    assert!(server.failures.load(Ordering::SeqCst) > 0);
+    server.healthy.store(true, Ordering::SeqCst);
+    sender.stop();
+    buffered.flush().unwrap();
+    queueing.flush_maintenance_queue();
+    assert_eq!(buffered.spooled().unwrap(), 0);
+    assert_eq!(queueing.maintenance_queued(), 0);
+
+    let received = server.received.lock().unwrap();
+    assert_eq!(received.len(), 3 * THREADS * POINTS as usize);
+    let duplicated: Vec<_> = received.iter().filter(|(_, &count)| count > 1).collect();
+    assert!(duplicated.is_empty(), "{:?}", duplicated);
+    let _ = std::fs::remove_file(&path);
+}
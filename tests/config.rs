@@ -0,0 +1,118 @@
+//! Clients configured by TOML and YAML files.
+#![cfg(all(feature = "toml", feature = "yaml"))]
+
+use std::path::PathBuf;
+
+use nextcloud_analytics_rs::{AnalyticsError, ErrorClass, SyncClient, SyncClientBuilder};
+
+/// A file named `name` with `contents`, removed when dropped.
+struct ConfigFile(PathBuf);
+
+impl ConfigFile {
+    fn new(name: &str, contents: &str) -> Self {
+        let dir = std::env::temp_dir().join(format!("nca-test-config-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(name);
+        std::fs::write(&path, contents).unwrap();
+        Self(path)
+    }
+}
+
+impl Drop for ConfigFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
+
+fn config_error(result: Result<SyncClient, AnalyticsError>) -> String {
+    match result {
+        Err(err) => {
+            assert_eq!(err.class(), ErrorClass::Local);
+            err.to_string()
+        }
+        Ok(_) => panic!("the configuration was accepted"),
+    }
+}
+
+#[test]
+fn reads_toml_files() {
+    let secret = ConfigFile::new("secret", "s3cret\n");
+    let file = ConfigFile::new(
+        "client.toml",
+        &format!(
+            r#"
+            # The home server.
+            url = "https://example.com/nextcloud"
+            collection = 3
+            user = "myself"
+            password_file = '{}'
+            api_version = "v3"
+            timeout = 10
+            connect_timeout = 2.5
+            retries = 3
+            no_proxy = ["localhost", "10.0.0.1"]
+            "#,
+            secret.0.display()
+        ),
+    );
+
+    let client = SyncClient::from_config_file(&file.0).unwrap();
+    let description = client.describe();
+    assert_eq!(description.collection, 3);
+    assert_eq!(description.api_version, "3.0");
+    assert_eq!(description.user, "myself");
+}
+
+#[test]
+fn reads_yaml_files() {
+    let file = ConfigFile::new(
+        "client.yml",
+        "---\nurl: https://example.com/nextcloud\nuser: myself\ntoken: abc\napi_version: 2.0\nno_proxy: localhost,10.0.0.1\n",
+    );
+
+    let builder = SyncClientBuilder::from_config_file(&file.0).unwrap();
+    let description = builder.collection(5).build().unwrap().describe();
+    assert_eq!(description.collection, 5);
+    assert_eq!(description.api_version, "2.0");
+    assert_eq!(description.user, "myself");
+}
+
+#[test]
+fn rejects_invalid_settings() {
+    let file = ConfigFile::new(
+        "unknown.toml",
+        "url = \"https://example.com\"\nuser_name = \"me\"\n",
+    );
+    let err = config_error(SyncClient::from_config_file(&file.0));
+    assert!(err.contains("unknown field `user_name`"), "{}", err);
+
+    let file = ConfigFile::new("typed.yaml", "collection: three\n");
+    let err = config_error(SyncClient::from_config_file(&file.0));
+    assert!(err.contains("typed.yaml"), "{}", err);
+    assert!(err.contains("collection"), "{}", err);
+
+    let file = ConfigFile::new("section.toml", "[client]\nurl = \"https://example.com\"\n");
+    let err = config_error(SyncClient::from_config_file(&file.0));
+    assert!(err.contains("unknown field `client`"), "{}", err);
+
+    let file = ConfigFile::new(
+        "missing.toml",
+        "url = \"https://example.com\"\nuser = \"me\"\npassword = \"pw\"\n",
+    );
+    let err = config_error(SyncClient::from_config_file(&file.0));
+    assert!(err.contains("missing setting \"collection\""), "{}", err);
+}
+
+#[test]
+fn chooses_the_format_by_extension() {
+    // Valid TOML, but not YAML.
+    let file = ConfigFile::new(
+        "client.yaml",
+        "url = \"https://example.com\"\ncollection = 3\nuser = \"me\"\npassword = \"pw\"\n",
+    );
+    assert!(SyncClient::from_config_file(&file.0).is_err());
+
+    let file = ConfigFile::new("client.ini", "url = https://example.com\n");
+    let err = config_error(SyncClient::from_config_file(&file.0));
+    assert!(err.contains("unknown format"), "{}", err);
+}
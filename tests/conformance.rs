@@ -0,0 +1,346 @@
+//! Behavior of the client against responses of the Analytics app 4.x, recorded in `fixtures/` in
+//! the format written by `SyncClient::record_fixtures`.
+//!
+//! Each fixture's response is replayed through a mock transport, and the test checks how the
+//! client interprets it.
+
+use std::fs;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use nextcloud_analytics_rs::datasets::DatasetKind;
+use nextcloud_analytics_rs::testing::MockTransport;
+use nextcloud_analytics_rs::{
+    AnalyticsError, ApiVersion, CheckStatus, DataPoint, ErrorClass, NumberFormat, Outcome, Receipt,
+    Response, SendReport, SyncClient,
+};
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use reqwest::StatusCode;
+use serde_json::Value;
+
+/// The response recorded in `fixtures/<name>.json`.
+fn fixture(name: &str) -> Response {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures")
+        .join(format!("{}.json", name));
+    let fixture: Value = serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+    let response = &fixture["response"];
+
+    let mut headers = HeaderMap::new();
+    for (name, value) in response["headers"].as_object().unwrap() {
+        headers.insert(
+            HeaderName::from_bytes(name.as_bytes()).unwrap(),
+            HeaderValue::from_str(value.as_str().unwrap()).unwrap(),
+        );
+    }
+    Response {
+        status: StatusCode::from_u16(response["status"].as_u64().unwrap() as u16).unwrap(),
+        headers,
+        body: response["body"].as_str().unwrap().to_string(),
+    }
+}
+
+/// A client whose server answers the next request with the response of a fixture.
+fn replaying(name: &str, api_version: ApiVersion) -> (SyncClient, MockTransport) {
+    let mock = MockTransport::new();
+    mock.respond_with(fixture(name));
+    let client = SyncClient::builder("https://nextcloud.example/", 3, "user", "password")
+        .api_version(api_version)
+        .build()
+        .unwrap()
+        .with_transport(mock.clone());
+    (client, mock)
+}
+
+fn send(name: &str) -> Result<Receipt, AnalyticsError> {
+    let (client, _) = replaying(name, ApiVersion::V1);
+    client.send_data("temperature", "kitchen", 21.5)
+}
+
+fn send_batch(name: &str) -> Result<Receipt, AnalyticsError> {
+    let (client, _) = replaying(name, ApiVersion::V2);
+    client.send_batch(&[
+        DataPoint::new("temperature", "kitchen", 21.5),
+        DataPoint::new("temperature", "garage", 12.0),
+    ])
+}
+
+fn report(receipt: &Receipt) -> &SendReport {
+    receipt.report().expect("the data point was sent")
+}
+
+#[test]
+fn add_success() {
+    let receipt = send("add-success").unwrap();
+    let report = report(&receipt);
+    assert_eq!(report.rows, 1);
+    assert_eq!(report.message.as_deref(), Some("Data update successfull"));
+    assert_eq!(report.inserted, None);
+    assert_eq!(report.skipped(), None);
+}
+
+#[test]
+fn add_success_with_string_flag() {
+    assert!(send("add-success-string-flag").unwrap().was_sent());
+}
+
+#[test]
+fn add_success_with_numeric_flag() {
+    assert!(send("add-success-numeric-flag").unwrap().was_sent());
+}
+
+#[test]
+fn add_success_without_any_field() {
+    let receipt = send("add-missing-fields").unwrap();
+    assert_eq!(report(&receipt).message, None);
+}
+
+#[test]
+fn add_without_any_field_in_strict_mode() {
+    let (client, _) = replaying("add-missing-fields", ApiVersion::V1);
+    let err = client
+        .with_strict_responses(true)
+        .send_data("temperature", "kitchen", 21.5)
+        .unwrap_err();
+    assert_eq!(err.class(), ErrorClass::InvalidResponse);
+}
+
+#[test]
+fn add_counts() {
+    let receipt = send_batch("add-counts").unwrap();
+    let report = report(&receipt);
+    assert_eq!(report.rows, 2);
+    assert_eq!(report.inserted, Some(1));
+    assert_eq!(report.updated, Some(1));
+    assert_eq!(report.errors, Some(0));
+    assert_eq!(report.validation, None);
+    assert_eq!(report.skipped(), Some(0));
+}
+
+#[test]
+fn add_validation_warning() {
+    let receipt = send_batch("add-validation").unwrap();
+    let report = report(&receipt);
+    assert_eq!(report.errors, Some(1));
+    assert_eq!(report.skipped(), Some(1));
+    assert_eq!(
+        report.validation.as_deref(),
+        Some("Dimension 2: invalid date")
+    );
+}
+
+#[test]
+fn add_failure_object() {
+    let err = send("add-failure-object").unwrap_err();
+    assert_eq!(err.class(), ErrorClass::Api);
+    assert!(err.to_string().contains("dataset not found"), "{}", err);
+}
+
+#[test]
+fn add_failure_message() {
+    let err = send("add-failure-message").unwrap_err();
+    assert_eq!(err.class(), ErrorClass::Api);
+    assert!(err.to_string().contains("missing data"), "{}", err);
+}
+
+#[test]
+fn add_invalid_success_flag() {
+    let err = send("add-invalid-flag").unwrap_err();
+    assert_eq!(err.class(), ErrorClass::InvalidResponse);
+}
+
+#[test]
+fn add_failure_with_string_flag() {
+    let err = send("add-failure-string-flag").unwrap_err();
+    assert_eq!(err.class(), ErrorClass::Api);
+    assert!(err.to_string().contains("dataset not found"), "{}", err);
+}
+
+fn delete(name: &str) -> Result<(), AnalyticsError> {
+    let (client, _) = replaying(name, ApiVersion::V2);
+    client.delete_data("temperature", "kitchen")
+}
+
+#[test]
+fn delete_success_with_string_flag() {
+    delete("delete-success-string-flag").unwrap();
+}
+
+#[test]
+fn delete_success_without_any_field() {
+    delete("delete-missing-fields").unwrap();
+}
+
+#[test]
+fn delete_failure_with_numeric_flag() {
+    let err = delete("delete-failure-numeric-flag").unwrap_err();
+    assert_eq!(err.class(), ErrorClass::Api);
+    assert!(err.to_string().contains("dataset not found"), "{}", err);
+}
+
+#[test]
+fn add_unauthorized() {
+    let err = send("add-unauthorized").unwrap_err();
+    assert_eq!(err.class(), ErrorClass::Auth);
+}
+
+#[test]
+fn add_with_app_disabled() {
+    let err = send("add-app-disabled").unwrap_err();
+    assert_eq!(err.class(), ErrorClass::NotFound);
+}
+
+#[test]
+fn add_during_maintenance() {
+    let err = send("add-maintenance").unwrap_err();
+    assert_eq!(err.class(), ErrorClass::Maintenance);
+    assert!(err.to_string().contains("maintenance mode"), "{}", err);
+    match err {
+        AnalyticsError::Maintenance { retry_after, .. } => assert_eq!(retry_after, None),
+        err => panic!("unexpected error: {}", err),
+    }
+}
+
+#[test]
+fn add_queued_during_maintenance() {
+    let mock = MockTransport::new();
+    mock.respond_with(fixture("add-maintenance"));
+    let client = SyncClient::builder("https://nextcloud.example/", 3, "user", "password")
+        .queue_during_maintenance(Duration::ZERO)
+        .build()
+        .unwrap()
+        .with_transport(mock.clone());
+
+    let receipt = client.send_data("temperature", "kitchen", 21.5).unwrap();
+    assert_eq!(receipt.outcome, Outcome::Queued);
+    assert_eq!(client.maintenance_queued(), 1);
+
+    mock.respond_with(fixture("add-success"));
+    mock.respond_with(fixture("add-success"));
+    assert!(client
+        .send_data("temperature", "garage", 12.0)
+        .unwrap()
+        .was_sent());
+    assert_eq!(client.maintenance_queued(), 0);
+    let dimensions = mock
+        .sent_points()
+        .into_iter()
+        .map(|point| point.dimension2)
+        .collect::<Vec<_>>();
+    assert_eq!(dimensions, ["kitchen", "kitchen", "garage"]);
+}
+
+#[test]
+fn add_answered_by_login_page() {
+    let err = send("add-login-page").unwrap_err();
+    assert_eq!(err.class(), ErrorClass::InvalidResponse);
+    assert!(err.to_string().contains("login page"), "{}", err);
+}
+
+#[test]
+fn add_request() {
+    let (client, mock) = replaying("add-success", ApiVersion::V1);
+    assert!(client
+        .send_data("temperature", "kitchen", 21.5)
+        .unwrap()
+        .was_sent());
+
+    let requests = mock.requests();
+    assert_eq!(requests.len(), 1);
+    assert_eq!(
+        requests[0].url,
+        "https://nextcloud.example/apps/analytics/api/1.0/adddata/3"
+    );
+    assert_eq!(
+        requests[0].body_text(),
+        Some(r#"{"dimension1":"temperature","dimension2":"kitchen","dimension3":"21.5"}"#)
+    );
+    assert_eq!(
+        mock.sent_points(),
+        vec![DataPoint::new("temperature", "kitchen", 21.5)]
+    );
+}
+
+#[test]
+fn datasets_wrapped_in_data() {
+    let (client, _) = replaying("datasets-wrapped", ApiVersion::V2);
+    let datasets = client.list_datasets().unwrap();
+    let ids: Vec<_> = datasets.iter().map(|dataset| dataset.id).collect();
+    assert_eq!(ids, [3, 7]);
+    assert_eq!(datasets[0].name, "Weather station");
+}
+
+#[test]
+fn datasets_as_array() {
+    let (client, _) = replaying("datasets-array", ApiVersion::V3);
+    let datasets = client.list_datasets().unwrap();
+    // The dataset without identifier is skipped.
+    assert_eq!(datasets.len(), 1);
+    assert_eq!(datasets[0].id, 3);
+}
+
+#[test]
+fn datasets_with_external_source() {
+    let (client, _) = replaying("datasets-external", ApiVersion::V2);
+    let datasets = client.list_datasets().unwrap();
+    let kinds: Vec<_> = datasets.iter().map(|dataset| dataset.kind).collect();
+    assert_eq!(
+        kinds,
+        [DatasetKind::InternalDatabase, DatasetKind::External(6)]
+    );
+}
+
+#[test]
+fn send_to_external_dataset() {
+    let (client, mock) = replaying("add-success", ApiVersion::V1);
+    let client = client.with_dataset_kind(DatasetKind::External(6));
+    let err = client
+        .send_data("temperature", "kitchen", 21.5)
+        .unwrap_err();
+    assert_eq!(err.class(), ErrorClass::Local);
+    assert!(mock.requests().is_empty());
+}
+
+#[test]
+fn data_rows() {
+    let (client, _) = replaying("data-rows", ApiVersion::V1);
+    let rows = client.get_data().unwrap();
+    assert_eq!(
+        rows,
+        vec![
+            DataPoint::new("temperature", "2024-03-01 10:00:00", 21.5),
+            DataPoint::new("temperature", "2024-03-01 11:00:00", 22.0),
+            DataPoint::new("humidity", "2024-03-01 10:00:00", 40.0),
+        ]
+    );
+}
+
+#[test]
+fn data_with_decimal_comma() {
+    let (client, _) = replaying("data-comma", ApiVersion::V1);
+    let rows = client
+        .get_report_data(3, NumberFormat::DecimalComma)
+        .unwrap();
+    assert_eq!(rows, vec![DataPoint::new("temperature", "kitchen", 21.5)]);
+}
+
+#[test]
+fn data_with_invalid_row() {
+    let (client, _) = replaying("data-invalid-row", ApiVersion::V1);
+    let err = client.get_data().unwrap_err();
+    assert_eq!(err.class(), ErrorClass::InvalidResponse);
+}
+
+#[test]
+fn check_during_maintenance() {
+    let (client, mock) = replaying("status-maintenance", ApiVersion::V1);
+    let diagnosis = client.check();
+    assert_eq!(
+        diagnosis.server,
+        CheckStatus::Failed("the server is in maintenance mode".to_string())
+    );
+    assert_eq!(diagnosis.server_version.as_deref(), Some("27.1.4"));
+    assert_eq!(diagnosis.credentials, CheckStatus::Skipped);
+    assert!(!diagnosis.is_healthy());
+    assert_eq!(mock.requests().len(), 1);
+}
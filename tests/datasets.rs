@@ -0,0 +1,137 @@
+//! Rewriting and purging the data of datasets, through a mock transport.
+
+use std::time::Duration;
+
+use nextcloud_analytics_rs::testing::MockTransport;
+use nextcloud_analytics_rs::{ApiVersion, DataPoint, ErrorClass, PurgePlan, SyncClient};
+use reqwest::StatusCode;
+use serde_json::Value;
+
+const ROWS: &str = r#"{"header":["Sensor","Date","Value"],"data":[
+    ["temp","2024-01-01","21.5"],
+    ["temperature","2024-01-01","20"],
+    ["temp","2024-01-02","22"]
+]}"#;
+
+fn client(mock: &MockTransport, version: ApiVersion) -> SyncClient {
+    SyncClient::builder("https://nextcloud.invalid/", 3, "user", "password")
+        .api_version(version)
+        .build()
+        .unwrap()
+        .with_transport(mock.clone())
+}
+
+#[test]
+fn renames_the_rows_of_a_key() {
+    let mock = MockTransport::new();
+    let client = client(&mock, ApiVersion::V3);
+    mock.respond(StatusCode::OK, ROWS);
+
+    assert_eq!(client.rename_key(5, "temp", "temperature").unwrap(), 2);
+    let requests = mock.requests();
+    assert_eq!(requests.len(), 3);
+    assert!(requests[0]
+        .url
+        .starts_with("https://nextcloud.invalid/apps/analytics/api/3.0/data/5?filteroptions="));
+    assert_eq!(
+        mock.sent_points(),
+        [
+            DataPoint::new("temperature", "2024-01-01", 21.5),
+            DataPoint::new("temperature", "2024-01-02", 22.0),
+        ]
+    );
+    assert!(requests[1].url.ends_with("/api/3.0/data/5/add"));
+    assert!(requests[2].url.ends_with("/api/3.0/data/5/delete"));
+    let delete: Value = serde_json::from_str(requests[2].body_text().unwrap()).unwrap();
+    assert_eq!(
+        delete,
+        serde_json::json!({"delete": [
+            {"dimension1": "temp", "dimension2": "2024-01-01"},
+            {"dimension1": "temp", "dimension2": "2024-01-02"},
+        ]})
+    );
+}
+
+#[test]
+fn keeps_the_old_rows_if_they_cant_be_rewritten() {
+    let mock = MockTransport::new();
+    let client = client(&mock, ApiVersion::V2);
+    mock.respond(StatusCode::OK, ROWS)
+        .respond(StatusCode::INTERNAL_SERVER_ERROR, "");
+    let err = client.rename_key(5, "temp", "temperature").unwrap_err();
+    assert_eq!(err.class(), ErrorClass::Http);
+    assert_eq!(mock.requests().len(), 2);
+
+    mock.clear();
+    mock.respond(StatusCode::OK, r#"{"header":[],"data":[]}"#);
+    assert_eq!(client.rename_key(5, "humidity", "moisture").unwrap(), 0);
+    assert_eq!(mock.requests().len(), 1);
+}
+
+#[test]
+fn renaming_needs_v2() {
+    let mock = MockTransport::new();
+    let err = client(&mock, ApiVersion::V1)
+        .rename_key(5, "temp", "temperature")
+        .unwrap_err();
+    assert_eq!(err.class(), ErrorClass::Local);
+    assert!(mock.requests().is_empty());
+}
+
+/// The number of rows of each deletion request.
+fn deleted_batches(mock: &MockTransport) -> Vec<usize> {
+    mock.requests()
+        .iter()
+        .map(|request| {
+            let body: Value = serde_json::from_str(request.body_text().unwrap()).unwrap();
+            body["delete"].as_array().unwrap().len()
+        })
+        .collect()
+}
+
+#[test]
+fn purges_rows_in_batches() {
+    let mock = MockTransport::new();
+    let client = client(&mock, ApiVersion::V3);
+    let rows: Vec<_> = (1..=5)
+        .map(|day| ("temp", format!("2024-01-0{}", day)))
+        .collect();
+    let plan = PurgePlan::new()
+        .batch_size(2)
+        .pause(Duration::from_millis(1));
+
+    let mut progress = Vec::new();
+    let deleted = plan
+        .run_with_progress(&client, rows, |update| {
+            progress.push((update.rows, update.total_rows));
+        })
+        .unwrap();
+    assert_eq!(deleted, 5);
+    assert_eq!(deleted_batches(&mock), [2, 2, 1]);
+    assert_eq!(progress, [(2, Some(5)), (4, Some(5)), (5, Some(5))]);
+}
+
+#[test]
+fn purges_in_smaller_batches_after_a_timeout() {
+    let mock = MockTransport::new();
+    let client = client(&mock, ApiVersion::V3);
+    let rows = (1..=8).map(|day| ("temp".to_string(), format!("2024-01-0{}", day)));
+    let plan = PurgePlan::new().batch_size(4).pause(Duration::ZERO);
+
+    mock.respond(StatusCode::OK, r#"{"success":true}"#)
+        .respond(StatusCode::GATEWAY_TIMEOUT, "");
+    assert_eq!(plan.run(&client, rows).unwrap(), 8);
+    assert_eq!(deleted_batches(&mock), [4, 4, 2, 2]);
+
+    mock.clear();
+    mock.respond(StatusCode::GATEWAY_TIMEOUT, "")
+        .respond(StatusCode::GATEWAY_TIMEOUT, "");
+    let err = plan
+        .run(
+            &client,
+            vec![("temp", "2024-01-01"), ("temp", "2024-01-02")],
+        )
+        .unwrap_err();
+    assert_eq!(err.class(), ErrorClass::Http);
+    assert_eq!(deleted_batches(&mock), [2, 1]);
+}
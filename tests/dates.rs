@@ -0,0 +1,108 @@
+//! Timeline dates at the edges of the years the timestamp formats can represent.
+
+#![cfg(feature = "chrono")]
+
+use chrono::{DateTime, TimeZone, Utc};
+use nextcloud_analytics_rs::testing::MockTransport;
+use nextcloud_analytics_rs::{
+    format_timeline_date, parse_timeline_date, ErrorClass, TimestampFormat,
+};
+
+fn at_year(year: i32) -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(year, 6, 15, 12, 30, 0).unwrap()
+}
+
+fn formats() -> Vec<TimestampFormat> {
+    vec![
+        TimestampFormat::Rfc2822,
+        TimestampFormat::Rfc3339,
+        TimestampFormat::UnixEpoch,
+        TimestampFormat::Custom("%Y-%m-%d %H:%M:%S".to_string()),
+    ]
+}
+
+/// What each format gives for a year, in the order of [`formats`]; `None` for a refused date.
+fn expected(year: i32) -> [Option<&'static str>; 4] {
+    match year {
+        -1 => [
+            None,
+            None,
+            Some("-62184454200"),
+            Some("-0001-06-15 12:30:00"),
+        ],
+        0 => [
+            Some("Thu, 15 Jun 0000 12:30:00 +0000"),
+            Some("0000-06-15T12:30:00+00:00"),
+            Some("-62152831800"),
+            Some("0000-06-15 12:30:00"),
+        ],
+        9999 => [
+            Some("Tue, 15 Jun 9999 12:30:00 +0000"),
+            Some("9999-06-15T12:30:00+00:00"),
+            Some("253385065800"),
+            Some("9999-06-15 12:30:00"),
+        ],
+        10000 => [
+            None,
+            None,
+            Some("253416688200"),
+            Some("+10000-06-15 12:30:00"),
+        ],
+        _ => unreachable!(),
+    }
+}
+
+#[test]
+fn formats_at_the_edges_of_the_range() {
+    for year in [-1, 0, 9999, 10000] {
+        let time = at_year(year);
+        for (format, expected) in formats().iter().zip(expected(year)) {
+            let formatted = format_timeline_date(time, format);
+            match expected {
+                Some(text) => {
+                    let formatted = formatted.unwrap();
+                    assert_eq!(formatted, text, "{:?} in {}", format, year);
+                    assert_eq!(parse_timeline_date(&formatted, format), Some(time));
+                }
+                None => {
+                    let err = formatted.unwrap_err();
+                    assert_eq!(err.class(), ErrorClass::Local, "{:?} in {}", format, year);
+                    assert!(err.to_string().contains("years 0 to 9999"));
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn sends_at_the_edges_of_the_range() {
+    for year in [-1, 0, 9999, 10000] {
+        for (format, expected) in formats().into_iter().zip(expected(year)) {
+            let mock = MockTransport::new();
+            let client = mock.client(3).with_timestamp_format(format.clone());
+            let result = client.send_timeline_data("temperature", at_year(year), 21.5);
+            match expected {
+                Some(text) => {
+                    assert!(result.unwrap().was_sent());
+                    let sent = mock.sent_points();
+                    assert_eq!(sent.len(), 1);
+                    assert_eq!(sent[0].dimension2, text, "{:?} in {}", format, year);
+                }
+                None => {
+                    assert_eq!(result.unwrap_err().class(), ErrorClass::Local);
+                    assert!(mock.requests().is_empty(), "{:?} in {}", format, year);
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn range_is_checked_in_the_time_zone_of_the_date() {
+    let late = Utc.with_ymd_and_hms(9999, 12, 31, 23, 0, 0).unwrap();
+    let offset = chrono::FixedOffset::east_opt(2 * 3600).unwrap();
+    assert!(TimestampFormat::Rfc2822.format(late).is_ok());
+    assert!(TimestampFormat::Rfc2822
+        .format_in(&late.with_timezone(&offset))
+        .is_err());
+}
@@ -0,0 +1,152 @@
+//! Skipping duplicate data points, through a mock transport.
+
+use std::sync::Barrier;
+use std::thread;
+use std::time::Duration;
+
+use nextcloud_analytics_rs::testing::MockTransport;
+use nextcloud_analytics_rs::{
+    AnalyticsError, ApiVersion, DataPoint, Outcome, Receipt, Request, Response, SyncClient,
+    Transport,
+};
+use reqwest::StatusCode;
+
+fn client(mock: &MockTransport, capacity: usize, window: Duration) -> SyncClient {
+    mock.client(3).with_dedupe(capacity, window)
+}
+
+fn is_duplicate(result: Result<Receipt, AnalyticsError>) -> bool {
+    matches!(result.unwrap().outcome, Outcome::Duplicate)
+}
+
+#[test]
+fn skips_duplicates_within_the_window() {
+    let mock = MockTransport::new();
+    let client = client(&mock, 10, Duration::from_millis(200));
+
+    assert!(client
+        .send_data("temp", "kitchen", 21.5)
+        .unwrap()
+        .was_sent());
+    assert!(is_duplicate(client.send_data("temp", "kitchen", 21.5)));
+    // Another value or dimension isn't a duplicate.
+    assert!(client
+        .send_data("temp", "kitchen", 21.6)
+        .unwrap()
+        .was_sent());
+    assert!(client.send_data("temp", "garage", 21.5).unwrap().was_sent());
+    assert_eq!(mock.requests().len(), 3);
+}
+
+#[test]
+fn forgets_data_points_after_the_window() {
+    let mock = MockTransport::new();
+    let client = client(&mock, 10, Duration::from_millis(50));
+
+    let _ = client.send_data("temp", "kitchen", 21.5).unwrap();
+    assert!(is_duplicate(client.send_data("temp", "kitchen", 21.5)));
+    thread::sleep(Duration::from_millis(100));
+    assert!(client
+        .send_data("temp", "kitchen", 21.5)
+        .unwrap()
+        .was_sent());
+    assert_eq!(mock.requests().len(), 2);
+}
+
+#[test]
+fn forgets_the_oldest_data_points_beyond_the_capacity() {
+    let mock = MockTransport::new();
+    let client = client(&mock, 2, Duration::from_secs(60));
+
+    for room in ["kitchen", "garage", "attic"] {
+        let _ = client.send_data("temp", room, 21.5).unwrap();
+    }
+    assert!(is_duplicate(client.send_data("temp", "attic", 21.5)));
+    assert!(client
+        .send_data("temp", "kitchen", 21.5)
+        .unwrap()
+        .was_sent());
+}
+
+#[test]
+fn failed_sends_are_not_remembered() {
+    let mock = MockTransport::new();
+    let client = client(&mock, 10, Duration::from_secs(60));
+
+    mock.respond(StatusCode::INTERNAL_SERVER_ERROR, "");
+    assert!(client.send_data("temp", "kitchen", 21.5).is_err());
+    assert!(client
+        .send_data("temp", "kitchen", 21.5)
+        .unwrap()
+        .was_sent());
+
+    mock.respond(StatusCode::INTERNAL_SERVER_ERROR, "");
+    let points = [DataPoint::new("temp", "garage", 20.0)];
+    assert!(client.send_batch(&points).is_err());
+    assert!(client.send_batch(&points).unwrap().was_sent());
+    assert_eq!(mock.requests().len(), 4);
+}
+
+#[test]
+fn skips_duplicates_in_batches() {
+    let mock = MockTransport::new();
+    let client = SyncClient::builder("https://nextcloud.invalid/", 3, "user", "password")
+        .api_version(ApiVersion::V3)
+        .build()
+        .unwrap()
+        .with_transport(mock.clone())
+        .with_dedupe(10, Duration::from_secs(60));
+
+    let _ = client.send_data("temp", "kitchen", 21.5).unwrap();
+    let points = [
+        DataPoint::new("temp", "kitchen", 21.5),
+        DataPoint::new("temp", "garage", 20.0),
+        DataPoint::new("temp", "garage", 20.0),
+    ];
+    assert!(client.send_batch(&points).unwrap().was_sent());
+    let sent = mock.sent_points();
+    assert_eq!(sent.len(), 2);
+    assert_eq!(sent[1].dimension2, "garage");
+
+    assert!(is_duplicate(client.send_batch(&points[1..])));
+}
+
+/// A mock transport answering after a delay, so that sends overlap.
+struct Slow(MockTransport);
+
+impl Transport for Slow {
+    fn send(&self, request: Request) -> Result<Response, AnalyticsError> {
+        thread::sleep(Duration::from_millis(50));
+        self.0.send(request)
+    }
+}
+
+#[test]
+fn concurrent_sends_of_a_data_point_send_it_once() {
+    let mock = MockTransport::new();
+    let client = SyncClient::new("https://nextcloud.invalid/", 3, "user", "password")
+        .with_transport(Slow(mock.clone()))
+        .with_dedupe(10, Duration::from_secs(60));
+
+    let barrier = Barrier::new(8);
+    let sent = thread::scope(|scope| {
+        let threads: Vec<_> = (0..8)
+            .map(|_| {
+                scope.spawn(|| {
+                    barrier.wait();
+                    client
+                        .send_data("temp", "kitchen", 21.5)
+                        .unwrap()
+                        .was_sent()
+                })
+            })
+            .collect();
+        threads
+            .into_iter()
+            .map(|thread| thread.join().unwrap())
+            .filter(|sent| *sent)
+            .count()
+    });
+    assert_eq!(sent, 1);
+    assert_eq!(mock.requests().len(), 1);
+}
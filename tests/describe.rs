@@ -0,0 +1,105 @@
+//! Descriptions of the configuration of clients, as logged or attached to bug reports.
+
+use std::time::Duration;
+
+use nextcloud_analytics_rs::{Backoff, Profile, RetryPolicy, SyncClient};
+use reqwest::StatusCode;
+use serde_json::{json, Value};
+
+const PASSWORD: &str = "hunter2-app-password";
+const TOKEN: &str = "oidc-bearer-token-value";
+const PROXY_PASSWORD: &str = "proxy-secret";
+
+#[test]
+fn serializes_the_configuration() {
+    let policy = RetryPolicy::new(4)
+        .backoff(Backoff::Constant(Duration::from_millis(1500)))
+        .retry_on(&[StatusCode::SERVICE_UNAVAILABLE])
+        .jitter(false);
+    let description = SyncClient::builder("https://nextcloud.invalid", 3, "user", PASSWORD)
+        .timeout(Duration::from_secs(20))
+        .retry(Some(policy.clone()))
+        .build()
+        .unwrap()
+        .describe();
+    assert_eq!(description.retry, Some(policy));
+
+    let value = serde_json::to_value(&description).unwrap();
+    assert_eq!(value, description.to_json());
+    assert_eq!(value["base_url"], "https://nextcloud.invalid/");
+    assert_eq!(value["collection"], 3);
+    assert_eq!(value["auth"], "basic");
+    assert_eq!(value["timeout_secs"], 20.0);
+    assert_eq!(value["connect_timeout_secs"], Value::Null);
+    assert_eq!(value["max_attempts"], 4);
+    assert_eq!(
+        value["retry"],
+        json!({
+            "max_attempts": 4,
+            "backoff": {"constant": 1.5},
+            "statuses": [503],
+            "jitter": false,
+        })
+    );
+    assert_eq!(value["batch_size"], 500);
+    assert_eq!(value["rate_limit"], Value::Null);
+}
+
+#[test]
+fn describes_the_profile_defaults() {
+    let value = SyncClient::builder("https://nextcloud.invalid", 3, "user", PASSWORD)
+        .profile(Profile::Iot)
+        .build()
+        .unwrap()
+        .describe()
+        .to_json();
+    assert_eq!(value["profile"], "iot");
+    assert_eq!(
+        value["retry"]["backoff"],
+        json!({"exponential": {"initial_secs": 0.5, "max_secs": 30.0}})
+    );
+    assert_eq!(value["retry"]["statuses"], json!([429, 502, 503, 504]));
+    assert_eq!(value["rate_limit"], json!({"rate": 1.0, "burst": 10}));
+}
+
+#[test]
+fn lists_the_compiled_features() {
+    let features = SyncClient::new("https://nextcloud.invalid", 3, "user", PASSWORD)
+        .describe()
+        .features;
+    assert_eq!(features.contains(&"chrono"), cfg!(feature = "chrono"));
+    assert_eq!(features.contains(&"rustls"), cfg!(feature = "rustls"));
+    assert_eq!(features.contains(&"async"), cfg!(feature = "async"));
+    assert!(!features.contains(&"default"));
+}
+
+#[test]
+fn never_contains_secrets() {
+    let password = SyncClient::builder("https://nextcloud.invalid", 3, "user", PASSWORD)
+        .profile(Profile::Backfill)
+        .proxy(format!(
+            "http://proxy-user:{}@proxy.invalid:3128",
+            PROXY_PASSWORD
+        ))
+        .build()
+        .unwrap();
+    let token = SyncClient::builder("https://nextcloud.invalid", 3, "user", PASSWORD)
+        .bearer_token(TOKEN)
+        .build()
+        .unwrap();
+    assert_eq!(token.describe().auth, "bearer");
+
+    for client in [&password, &token] {
+        let description = client.describe();
+        for output in [
+            description.to_string(),
+            format!("{:?}", description),
+            serde_json::to_string_pretty(&description).unwrap(),
+        ] {
+            assert!(output.contains("nextcloud.invalid"), "{}", output);
+            for secret in [PASSWORD, TOKEN, PROXY_PASSWORD] {
+                assert!(!output.contains(secret), "{} in {}", secret, output);
+            }
+        }
+    }
+}
@@ -0,0 +1,70 @@
+//! Allow and deny lists of keys.
+
+use nextcloud_analytics_rs::testing::MockTransport;
+use nextcloud_analytics_rs::{KeyFilter, KeyPattern, Outcome};
+
+fn exact(key: &str) -> KeyPattern {
+    KeyPattern::Exact(key.to_string())
+}
+
+fn prefix(prefix: &str) -> KeyPattern {
+    KeyPattern::Prefix(prefix.to_string())
+}
+
+#[test]
+fn an_empty_filter_allows_everything() {
+    let filter = KeyFilter::new();
+    assert!(filter.allows("temp"));
+    assert!(filter.allows(""));
+}
+
+#[test]
+fn allowed_patterns_are_alternatives() {
+    let filter = KeyFilter::new()
+        .allow(exact("temp"))
+        .allow(prefix("power/"));
+    assert!(filter.allows("temp"));
+    assert!(filter.allows("power/solar"));
+    assert!(!filter.allows("temperature"));
+    assert!(!filter.allows("humidity"));
+    assert!(!filter.allows("solar/power/"));
+}
+
+#[test]
+fn deny_takes_precedence_over_allow() {
+    let filter = KeyFilter::new()
+        .allow(prefix("power/"))
+        .deny(exact("power/debug"))
+        .allow(exact("power/debug"));
+    assert!(filter.allows("power/solar"));
+    assert!(!filter.allows("power/debug"));
+    // Only denied patterns: everything else passes.
+    let filter = KeyFilter::new().deny(prefix("debug."));
+    assert!(filter.allows("temp"));
+    assert!(!filter.allows("debug.latency"));
+}
+
+#[cfg(feature = "regex")]
+#[test]
+fn matches_regular_expressions_anywhere_unless_anchored() {
+    let filter = KeyFilter::new()
+        .allow(KeyPattern::Regex(regex::Regex::new(r"_c$").unwrap()))
+        .deny(KeyPattern::Regex(regex::Regex::new("test").unwrap()));
+    assert!(filter.allows("kitchen_temp_c"));
+    assert!(!filter.allows("kitchen_temp_c_raw"));
+    assert!(!filter.allows("latest_c"));
+}
+
+#[test]
+fn filtered_keys_are_not_sent() {
+    let mock = MockTransport::new();
+    let client = mock
+        .client(3)
+        .with_key_filter(KeyFilter::new().deny(prefix("debug.")));
+
+    let receipt = client.send_data("debug.latency", "now", 3.0).unwrap();
+    assert!(matches!(receipt.outcome, Outcome::Filtered));
+    assert!(mock.requests().is_empty());
+    assert!(client.send_data("temp", "now", 21.5).unwrap().was_sent());
+    assert_eq!(mock.sent_points()[0].dimension1, "temp");
+}
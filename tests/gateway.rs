@@ -0,0 +1,175 @@
+//! Requests to the ingestion gateway, forwarded through a mock transport.
+#![cfg(feature = "gateway")]
+
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use nextcloud_analytics_rs::gateway::Gateway;
+use nextcloud_analytics_rs::testing::MockTransport;
+use nextcloud_analytics_rs::{ApiVersion, BackgroundSender, SyncClient};
+
+const POINTS: &str = r#"[
+    {"dimension1": "temp_c", "dimension2": "kitchen", "dimension3": 21.5},
+    {"dimension1": "temp_c", "dimension2": "garage", "dimension3": "12"}
+]"#;
+
+/// A client of the 3.0 API, which sends batches in a single request.
+fn client(mock: &MockTransport) -> SyncClient {
+    SyncClient::builder("https://nextcloud.invalid/", 3, "user", "password")
+        .api_version(ApiVersion::V3)
+        .build()
+        .unwrap()
+        .with_transport(mock.clone())
+}
+
+/// Serves `gateway` on a thread, and returns its address.
+fn serve(gateway: Gateway) -> SocketAddr {
+    let addr = gateway.local_addr().unwrap();
+    thread::spawn(move || gateway.serve());
+    addr
+}
+
+fn gateway(mock: &MockTransport) -> Gateway {
+    Gateway::bind("127.0.0.1:0", Arc::new(client(mock))).unwrap()
+}
+
+/// Sends `request` as it is, and returns the whole response.
+fn exchange(addr: SocketAddr, request: &[u8]) -> String {
+    let mut stream = TcpStream::connect(addr).unwrap();
+    stream.write_all(request).unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    response
+}
+
+fn post(addr: SocketAddr, authorization: Option<&str>, body: &str) -> String {
+    let authorization = authorization
+        .map(|value| format!("Authorization: {}\r\n", value))
+        .unwrap_or_default();
+    let request = format!(
+        "POST /points HTTP/1.1\r\n{}Content-Length: {}\r\n\r\n{}",
+        authorization,
+        body.len(),
+        body
+    );
+    exchange(addr, request.as_bytes())
+}
+
+#[test]
+fn forwards_the_points_of_a_request_in_one_batch() {
+    let mock = MockTransport::new();
+    let addr = serve(gateway(&mock));
+
+    let response = post(addr, None, POINTS);
+    assert!(response.starts_with("HTTP/1.1 200 OK"), "{}", response);
+    assert!(
+        response.ends_with(r#"{"sent":2,"success":true}"#),
+        "{}",
+        response
+    );
+    assert_eq!(mock.requests().len(), 1);
+    assert_eq!(mock.sent_points().len(), 2);
+}
+
+#[test]
+fn refuses_requests_without_the_token() {
+    let mock = MockTransport::new();
+    let addr = serve(gateway(&mock).with_token("s3cret"));
+
+    for authorization in [None, Some("Bearer s3crex"), Some("Bearer s3cret2")] {
+        let response = post(addr, authorization, POINTS);
+        assert!(response.starts_with("HTTP/1.1 401"), "{}", response);
+    }
+    let response = post(addr, Some("Bearer s3cret"), POINTS);
+    assert!(response.starts_with("HTTP/1.1 200"), "{}", response);
+    assert_eq!(mock.requests().len(), 1);
+}
+
+#[test]
+fn refuses_overlong_header_lines() {
+    let mock = MockTransport::new();
+    let addr = serve(gateway(&mock));
+
+    let request = format!(
+        "POST /points HTTP/1.1\r\nX-Padding: {}\r\n\r\n",
+        "a".repeat(9000)
+    );
+    let response = exchange(addr, request.as_bytes());
+    assert!(response.starts_with("HTTP/1.1 400"), "{}", response);
+    assert!(response.contains("longer than 8192 bytes"), "{}", response);
+    assert!(mock.requests().is_empty());
+}
+
+#[test]
+fn drops_requests_trickling_past_the_deadline() {
+    let mock = MockTransport::new();
+    let addr = serve(gateway(&mock).with_timeout(Duration::from_millis(200)));
+
+    let start = Instant::now();
+    let mut stream = TcpStream::connect(addr).unwrap();
+    stream.write_all(b"POST /points HTTP/1.1\r\n").unwrap();
+    let mut writer = stream.try_clone().unwrap();
+    // A byte every 20 ms, each within any per-read timeout.
+    thread::spawn(move || {
+        for _ in 0..100 {
+            if writer.write_all(b"X").is_err() {
+                break;
+            }
+            thread::sleep(Duration::from_millis(20));
+        }
+    });
+    let mut response = String::new();
+    let _ = stream.read_to_string(&mut response);
+
+    assert!(response.starts_with("HTTP/1.1 408"), "{}", response);
+    assert!(start.elapsed() < Duration::from_secs(1));
+}
+
+#[test]
+fn serves_as_many_requests_at_once_as_workers() {
+    let mock = MockTransport::new();
+    let addr = serve(
+        gateway(&mock)
+            .with_workers(1)
+            .with_timeout(Duration::from_millis(300)),
+    );
+
+    // An idle connection holds the only worker until its deadline.
+    let idle = TcpStream::connect(addr).unwrap();
+    thread::sleep(Duration::from_millis(50));
+    let start = Instant::now();
+    let response = post(addr, None, POINTS);
+    assert!(response.starts_with("HTTP/1.1 200"), "{}", response);
+    assert!(start.elapsed() >= Duration::from_millis(200));
+    drop(idle);
+}
+
+#[test]
+fn queues_points_for_a_background_sender() {
+    let mock = MockTransport::new();
+    let client = Arc::new(client(&mock));
+    let sender = BackgroundSender::start(Arc::clone(&client), 100, Duration::from_secs(3600));
+    let addr = serve(
+        Gateway::bind("127.0.0.1:0", client)
+            .unwrap()
+            .with_sender(sender.handle()),
+    );
+
+    let response = post(addr, None, POINTS);
+    assert!(response.starts_with("HTTP/1.1 202"), "{}", response);
+    assert!(
+        response.ends_with(r#"{"queued":2,"success":true}"#),
+        "{}",
+        response
+    );
+    assert!(mock.requests().is_empty());
+
+    sender.stop();
+    assert_eq!(mock.sent_points().len(), 2);
+    let response = post(addr, None, POINTS);
+    assert!(response.starts_with("HTTP/1.1 503"), "{}", response);
+    assert!(response.contains("E_BUSY"), "{}", response);
+}
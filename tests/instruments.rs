@@ -0,0 +1,94 @@
+//! Gauges and counters flushed as timeline data, through a mock transport.
+
+#![cfg(feature = "chrono")]
+
+use std::collections::BTreeMap;
+
+use nextcloud_analytics_rs::testing::MockTransport;
+use reqwest::StatusCode;
+
+/// The values sent by the last flush, by key.
+fn flushed(mock: &MockTransport) -> BTreeMap<String, f64> {
+    let values = mock
+        .sent_points()
+        .into_iter()
+        .map(|point| (point.dimension1, point.dimension3))
+        .collect();
+    mock.clear();
+    values
+}
+
+#[test]
+fn counters_count_the_events_since_the_last_flush() {
+    let mock = MockTransport::new();
+    let client = mock.client(3);
+    let requests = client.counter("requests");
+    requests.inc();
+    requests.inc_by(4);
+    client.counter("requests").inc_by(0.5);
+
+    let _ = client.flush_metrics().unwrap();
+    assert_eq!(flushed(&mock)["requests"], 5.5);
+
+    // Reset by the flush: 0 without events.
+    let _ = client.flush_metrics().unwrap();
+    assert_eq!(flushed(&mock)["requests"], 0.0);
+    requests.inc();
+    let _ = client.flush_metrics().unwrap();
+    assert_eq!(flushed(&mock)["requests"], 1.0);
+}
+
+#[test]
+fn counters_only_go_up() {
+    let mock = MockTransport::new();
+    let client = mock.client(3);
+    let requests = client.counter("requests");
+    requests.inc_by(3);
+    requests.inc_by(-2);
+    requests.inc_by(f64::NAN);
+
+    let _ = client.flush_metrics().unwrap();
+    assert_eq!(flushed(&mock)["requests"], 3.0);
+
+    // A negative count on a fresh counter doesn't create it.
+    client.counter("other").inc_by(-1);
+    let _ = client.flush_metrics().unwrap();
+    assert_eq!(flushed(&mock)["other"], 0.0);
+}
+
+#[test]
+fn failed_flushes_keep_the_counts() {
+    let mock = MockTransport::new();
+    let client = mock.client(3);
+    let requests = client.counter("requests");
+    requests.inc_by(2);
+
+    mock.respond(StatusCode::INTERNAL_SERVER_ERROR, "");
+    assert!(client.flush_metrics().is_err());
+    mock.clear();
+    requests.inc();
+    let _ = client.flush_metrics().unwrap();
+    assert_eq!(flushed(&mock)["requests"], 3.0);
+}
+
+#[test]
+fn gauges_send_their_last_value_at_every_flush() {
+    let mock = MockTransport::new();
+    let client = mock.client(3);
+    let temp = client.gauge("temp");
+    temp.set(20.0);
+    temp.set(21.5);
+
+    let _ = client.flush_metrics().unwrap();
+    assert_eq!(flushed(&mock)["temp"], 21.5);
+    let _ = client.flush_metrics().unwrap();
+    assert_eq!(flushed(&mock)["temp"], 21.5);
+    temp.set(-3);
+    let _ = client.flush_metrics().unwrap();
+    assert_eq!(flushed(&mock)["temp"], -3.0);
+
+    // Using the key for a counter replaces the gauge.
+    client.counter("temp").inc();
+    let _ = client.flush_metrics().unwrap();
+    assert_eq!(flushed(&mock)["temp"], 1.0);
+}
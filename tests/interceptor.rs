@@ -0,0 +1,164 @@
+//! Interceptors around the requests of a client, through a mock transport.
+
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use nextcloud_analytics_rs::testing::MockTransport;
+use nextcloud_analytics_rs::{
+    AnalyticsError, Backoff, Interceptor, Request, Response, RetryPolicy, SyncClient,
+};
+use reqwest::header::HeaderValue;
+use reqwest::{Method, StatusCode};
+
+/// What the interceptors saw, in order.
+type Log = Arc<Mutex<Vec<String>>>;
+
+/// Logs the requests and responses it sees, and adds its name to the `X-Seen-By` header.
+struct Logger {
+    name: &'static str,
+    log: Log,
+}
+
+impl Interceptor for Logger {
+    fn on_request(&self, request: &mut Request) -> Result<(), AnalyticsError> {
+        let seen = match request.headers.get("X-Seen-By") {
+            Some(seen) => format!("{},{}", seen.to_str().unwrap(), self.name),
+            None => self.name.to_string(),
+        };
+        self.log.lock().unwrap().push(format!(
+            "{} request {} {} seen by {}",
+            self.name, request.method, request.url, seen
+        ));
+        request
+            .headers
+            .insert("X-Seen-By", HeaderValue::from_str(&seen).unwrap());
+        Ok(())
+    }
+
+    fn on_response(&self, request: &Request, response: &Response) {
+        self.log.lock().unwrap().push(format!(
+            "{} response {} {}",
+            self.name,
+            response.status.as_u16(),
+            request.headers["X-Seen-By"].to_str().unwrap()
+        ));
+    }
+}
+
+fn logged(client: SyncClient, log: &Log, names: &[&'static str]) -> SyncClient {
+    names.iter().fold(client, |client, name| {
+        client.with_interceptor(Logger {
+            name,
+            log: log.clone(),
+        })
+    })
+}
+
+#[test]
+fn sees_every_request_in_order() {
+    let mock = MockTransport::new();
+    let log = Log::default();
+    let client = logged(mock.client(3), &log, &["first", "second"]);
+
+    let _ = client.send_data("temp", "kitchen", 21.5).unwrap();
+    mock.respond(StatusCode::OK, "[]");
+    let _ = client.list_datasets();
+
+    let requests = mock.requests();
+    assert_eq!(requests.len(), 2);
+    for request in &requests {
+        assert_eq!(request.headers["X-Seen-By"], "first,second");
+    }
+    let log = log.lock().unwrap();
+    assert_eq!(log.len(), 8);
+    assert_eq!(
+        log[0],
+        format!("first request POST {} seen by first", requests[0].url)
+    );
+    assert!(log[1].starts_with("second request POST"));
+    assert!(log[1].ends_with("seen by first,second"));
+    assert_eq!(log[2], "first response 200 first,second");
+    assert_eq!(log[3], "second response 200 first,second");
+    assert!(log[4].starts_with(&format!("first request {}", Method::GET)));
+}
+
+/// Moves the requests to another server, and replaces their body.
+struct Rewrite;
+
+impl Interceptor for Rewrite {
+    fn on_request(&self, request: &mut Request) -> Result<(), AnalyticsError> {
+        request.url = request.url.replace("nextcloud.invalid", "mirror.invalid");
+        request.body = Some(b"rewritten".to_vec());
+        request.timeout = Some(Duration::from_secs(7));
+        Ok(())
+    }
+}
+
+#[test]
+fn modifications_reach_the_transport() {
+    let mock = MockTransport::new();
+    let client = mock.client(3).with_interceptor(Rewrite);
+
+    let _ = client.send_data("temp", "kitchen", 21.5).unwrap();
+    let request = &mock.requests()[0];
+    assert!(
+        request.url.starts_with("https://mirror.invalid/"),
+        "{}",
+        request.url
+    );
+    assert_eq!(request.body_text(), Some("rewritten"));
+    assert_eq!(request.timeout, Some(Duration::from_secs(7)));
+}
+
+/// Refuses every request.
+struct Refuse;
+
+impl Interceptor for Refuse {
+    fn on_request(&self, _: &mut Request) -> Result<(), AnalyticsError> {
+        Err(AnalyticsError::Local("refused by the interceptor".into()))
+    }
+}
+
+#[test]
+fn errors_abort_the_send() {
+    let mock = MockTransport::new();
+    let log = Log::default();
+    let client = logged(mock.client(3), &log, &["before"]).with_interceptor(Refuse);
+    let client = logged(client, &log, &["after"]);
+
+    let err = client.send_data("temp", "kitchen", 21.5).unwrap_err();
+    assert!(
+        err.to_string().contains("refused by the interceptor"),
+        "{}",
+        err
+    );
+    assert!(mock.requests().is_empty());
+    // The interceptors after the failing one, and the response hooks, aren't called.
+    let log = log.lock().unwrap();
+    assert_eq!(log.len(), 1);
+    assert!(log[0].starts_with("before request"));
+}
+
+#[test]
+fn sees_error_responses_and_every_attempt() {
+    let mock = MockTransport::new();
+    let log = Log::default();
+    let policy = RetryPolicy::new(2)
+        .backoff(Backoff::Constant(Duration::from_millis(1)))
+        .retry_on(&[StatusCode::SERVICE_UNAVAILABLE])
+        .jitter(false);
+    let client = SyncClient::builder("https://nextcloud.invalid/", 3, "user", "password")
+        .retry(Some(policy))
+        .build()
+        .unwrap()
+        .with_transport(mock.clone());
+    let client = logged(client, &log, &["only"]);
+
+    mock.respond(StatusCode::SERVICE_UNAVAILABLE, "");
+    let _ = client.send_data("temp", "kitchen", 21.5).unwrap();
+    assert_eq!(mock.requests().len(), 2);
+    let log = log.lock().unwrap();
+    assert_eq!(log.len(), 4);
+    assert_eq!(log[1], "only response 503 only");
+    assert_eq!(log[3], "only response 200 only");
+}
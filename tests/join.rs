@@ -0,0 +1,105 @@
+//! Aligning two timeline series on their timestamps.
+
+#![cfg(feature = "chrono")]
+
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use nextcloud_analytics_rs::{join_series, JoinKind};
+
+fn at(seconds: i64) -> DateTime<Utc> {
+    Utc.timestamp_opt(1_700_000_000 + seconds, 0).unwrap()
+}
+
+fn series(points: &[(i64, f64)]) -> Vec<(DateTime<Utc>, f64)> {
+    points
+        .iter()
+        .map(|&(time, value)| (at(time), value))
+        .collect()
+}
+
+type Joined = Vec<(i64, Option<f64>, Option<f64>)>;
+
+fn join(a: &[(i64, f64)], b: &[(i64, f64)], kind: JoinKind, tolerance: i64) -> Joined {
+    join_series(&series(a), &series(b), kind, Duration::seconds(tolerance))
+        .into_iter()
+        .map(|(time, a, b)| ((time - at(0)).num_seconds(), a, b))
+        .collect()
+}
+
+const PRODUCED: &[(i64, f64)] = &[(0, 1.0), (60, 2.0), (180, 4.0), (240, 5.0)];
+const CONSUMED: &[(i64, f64)] = &[(0, 10.0), (120, 30.0), (180, 40.0), (300, 60.0)];
+
+#[test]
+fn inner_joins_keep_the_common_timestamps() {
+    assert_eq!(
+        join(PRODUCED, CONSUMED, JoinKind::Inner, 0),
+        [(0, Some(1.0), Some(10.0)), (180, Some(4.0), Some(40.0))]
+    );
+    assert!(join(PRODUCED, &[], JoinKind::Inner, 0).is_empty());
+}
+
+#[test]
+fn outer_joins_fill_the_missing_timestamps() {
+    assert_eq!(
+        join(PRODUCED, CONSUMED, JoinKind::Outer, 0),
+        [
+            (0, Some(1.0), Some(10.0)),
+            (60, Some(2.0), None),
+            (120, None, Some(30.0)),
+            (180, Some(4.0), Some(40.0)),
+            (240, Some(5.0), None),
+            (300, None, Some(60.0)),
+        ]
+    );
+    assert_eq!(
+        join(&[], &[(5, 1.0)], JoinKind::Outer, 0),
+        [(5, None, Some(1.0))]
+    );
+}
+
+#[test]
+fn matches_timestamps_within_the_tolerance() {
+    // Sampled a few seconds apart, with the timestamps of `a`.
+    let a = [(0, 1.0), (60, 2.0), (120, 3.0)];
+    let b = [(2, 10.0), (57, 20.0), (130, 30.0)];
+    assert_eq!(
+        join(&a, &b, JoinKind::Inner, 5),
+        [(0, Some(1.0), Some(10.0)), (60, Some(2.0), Some(20.0))]
+    );
+    assert_eq!(join(&a, &b, JoinKind::Inner, 10).len(), 3);
+    assert_eq!(
+        join(&a, &b, JoinKind::Outer, 5)[2..],
+        [(120, Some(3.0), None), (130, None, Some(30.0))]
+    );
+}
+
+#[test]
+fn each_point_matches_the_closest_one_once() {
+    // Both points of `b` are within the tolerance of the point of `a`: the closer one matches.
+    assert_eq!(
+        join(&[(10, 1.0)], &[(6, 10.0), (9, 20.0)], JoinKind::Outer, 5),
+        [(6, None, Some(10.0)), (10, Some(1.0), Some(20.0))]
+    );
+    assert_eq!(
+        join(&[(6, 1.0), (9, 2.0)], &[(10, 10.0)], JoinKind::Outer, 5),
+        [(6, Some(1.0), None), (9, Some(2.0), Some(10.0))]
+    );
+    // A point isn't matched twice.
+    assert_eq!(
+        join(&[(0, 1.0), (1, 2.0)], &[(0, 10.0)], JoinKind::Inner, 5),
+        [(0, Some(1.0), Some(10.0))]
+    );
+}
+
+#[test]
+fn sorts_the_series() {
+    let a = [(120, 3.0), (0, 1.0), (60, 2.0)];
+    let b = [(60, 20.0), (120, 30.0), (0, 10.0)];
+    assert_eq!(
+        join(&a, &b, JoinKind::Inner, 0),
+        [
+            (0, Some(1.0), Some(10.0)),
+            (60, Some(2.0), Some(20.0)),
+            (120, Some(3.0), Some(30.0)),
+        ]
+    );
+}
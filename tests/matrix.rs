@@ -0,0 +1,68 @@
+//! Sending cross-tab datasets, through a mock transport.
+
+use nextcloud_analytics_rs::testing::MockTransport;
+use nextcloud_analytics_rs::{ApiVersion, DataPoint, ErrorClass, SyncClient};
+use reqwest::StatusCode;
+
+fn client(mock: &MockTransport) -> SyncClient {
+    SyncClient::builder("https://nextcloud.invalid/", 3, "user", "password")
+        .api_version(ApiVersion::V3)
+        .build()
+        .unwrap()
+        .with_transport(mock.clone())
+}
+
+fn cell(row: &str, column: &str, value: f64) -> (String, String, f64) {
+    (row.to_string(), column.to_string(), value)
+}
+
+#[test]
+fn deduplicates_the_cells_in_one_request() {
+    let mock = MockTransport::new();
+    let rows = vec![
+        cell("alice", "age", 30.0),
+        cell("bob", "age", 25.0),
+        cell("alice", "height", 170.0),
+        cell("alice", "age", 31.0),
+        cell("bob", "age", 26.0),
+    ];
+
+    assert_eq!(client(&mock).send_matrix(rows).unwrap(), 3);
+    assert_eq!(mock.requests().len(), 1);
+    assert_eq!(
+        mock.sent_points(),
+        [
+            DataPoint::new("alice", "age", 31.0),
+            DataPoint::new("bob", "age", 26.0),
+            DataPoint::new("alice", "height", 170.0),
+        ]
+    );
+}
+
+#[test]
+fn large_matrices_are_sent_in_chunks() {
+    let mock = MockTransport::new();
+    let rows = (0..1200).map(|i| {
+        cell(
+            &format!("row-{}", i % 400),
+            &format!("col-{}", i / 400),
+            1.0,
+        )
+    });
+
+    assert_eq!(client(&mock).send_matrix(rows).unwrap(), 1200);
+    assert_eq!(mock.requests().len(), 3);
+    assert_eq!(mock.sent_points().len(), 1200);
+}
+
+#[test]
+fn returns_the_error_of_a_failed_chunk() {
+    let mock = MockTransport::new();
+    mock.respond(StatusCode::BAD_REQUEST, "");
+
+    let err = client(&mock)
+        .send_matrix(vec![cell("alice", "age", 30.0)])
+        .unwrap_err();
+    assert_eq!(err.class(), ErrorClass::Http);
+    assert_eq!(mock.requests().len(), 1);
+}
@@ -0,0 +1,97 @@
+//! Metrics of the `metrics` façade pushed as timeline data by an `AnalyticsRecorder`.
+#![cfg(feature = "metrics-exporter")]
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use metrics::{counter, gauge, histogram};
+use nextcloud_analytics_rs::metrics_exporter::AnalyticsRecorder;
+use nextcloud_analytics_rs::testing::MockTransport;
+use nextcloud_analytics_rs::{ApiVersion, SyncClient};
+
+fn client(mock: &MockTransport) -> SyncClient {
+    SyncClient::builder("https://nextcloud.invalid/", 3, "user", "password")
+        .api_version(ApiVersion::V3)
+        .build()
+        .unwrap()
+        .with_transport(mock.clone())
+}
+
+/// The values sent, by key.
+fn sent(mock: &MockTransport) -> HashMap<String, f64> {
+    mock.sent_points()
+        .into_iter()
+        .map(|point| (point.dimension1, point.dimension3))
+        .collect()
+}
+
+#[test]
+fn pushes_counters_gauges_and_histogram_summaries() {
+    let mock = MockTransport::new();
+    let client = client(&mock);
+    let recorder = AnalyticsRecorder::new();
+    metrics::with_local_recorder(&recorder, || {
+        counter!("jobs_done", "queue" => "emails").increment(2);
+        counter!("jobs_done", "queue" => "emails").increment(1);
+        gauge!("temperature").set(21.5);
+        gauge!("temperature").decrement(1.0);
+        histogram!("request_seconds").record(0.5);
+        histogram!("request_seconds").record(1.5);
+        histogram!("request_seconds").record(1.0);
+    });
+
+    assert!(recorder.push(&client).unwrap().was_sent());
+    assert_eq!(mock.requests().len(), 1);
+    let expected: HashMap<_, _> = [
+        ("jobs_done{queue=emails}", 3.0),
+        ("temperature", 20.5),
+        ("request_seconds_count", 3.0),
+        ("request_seconds_sum", 3.0),
+        ("request_seconds_min", 0.5),
+        ("request_seconds_max", 1.5),
+    ]
+    .iter()
+    .map(|&(key, value)| (key.to_string(), value))
+    .collect();
+    assert_eq!(sent(&mock), expected);
+
+    // Histograms are summarized since the previous push, counters and gauges aren't reset.
+    mock.clear();
+    metrics::with_local_recorder(&recorder, || {
+        counter!("jobs_done", "queue" => "emails").increment(1);
+    });
+    let _ = recorder.push(&client).unwrap();
+    let expected: HashMap<_, _> = [("jobs_done{queue=emails}", 4.0), ("temperature", 20.5)]
+        .iter()
+        .map(|&(key, value)| (key.to_string(), value))
+        .collect();
+    assert_eq!(sent(&mock), expected);
+}
+
+#[test]
+fn skips_values_analytics_cant_store() {
+    let mock = MockTransport::new();
+    let recorder = AnalyticsRecorder::new();
+    metrics::with_local_recorder(&recorder, || gauge!("ratio").set(f64::NAN));
+
+    assert!(!recorder.push(&client(&mock)).unwrap().was_sent());
+    assert!(mock.requests().is_empty());
+}
+
+#[test]
+fn exporter_pushes_on_an_interval_and_when_stopped() {
+    let mock = MockTransport::new();
+    let recorder = AnalyticsRecorder::new();
+    let exporter = recorder.start_exporting(Arc::new(client(&mock)), Duration::from_secs(3600));
+    metrics::with_local_recorder(&recorder, || counter!("started").increment(1));
+
+    exporter.stop();
+    assert_eq!(sent(&mock)["started"], 1.0);
+
+    let mock = MockTransport::new();
+    let exporter = recorder.start_exporting(Arc::new(client(&mock)), Duration::from_millis(10));
+    std::thread::sleep(Duration::from_millis(200));
+    assert!(mock.requests().len() > 1);
+    drop(exporter);
+}
@@ -0,0 +1,106 @@
+//! Rows mirrored into SQLite by the clients sending them, through a mock transport.
+#![cfg(feature = "mirror")]
+
+use std::sync::Arc;
+
+use nextcloud_analytics_rs::mirror::Mirror;
+use nextcloud_analytics_rs::testing::MockTransport;
+use nextcloud_analytics_rs::{DataPoint, SyncClient};
+use reqwest::StatusCode;
+
+const DATA: &str = r#"{"header":["Sensor","Room","Value"],"data":[["temp","kitchen","21.5"]]}"#;
+
+fn client(mock: &MockTransport, mirror: &Arc<Mirror>) -> SyncClient {
+    mock.client(3).with_mirror(Arc::clone(mirror))
+}
+
+#[test]
+fn mirrors_the_rows_sent() {
+    let mock = MockTransport::new();
+    let mirror = Arc::new(Mirror::in_memory().unwrap());
+    let client = client(&mock, &mirror);
+
+    let _ = client.send_data("temp", "kitchen", 21.5).unwrap();
+    let _ = client
+        .send_batch(&[
+            DataPoint::new("temp", "garage", 12.0),
+            DataPoint::new("temp", "kitchen", 22.0),
+        ])
+        .unwrap();
+
+    assert_eq!(
+        mirror.rows(3).unwrap(),
+        vec![
+            DataPoint::new("temp", "garage", 12.0),
+            DataPoint::new("temp", "kitchen", 22.0),
+        ]
+    );
+    assert!(mirror.rows(4).unwrap().is_empty());
+
+    let connection = mirror.connection();
+    let total: f64 = connection
+        .query_row(
+            "SELECT SUM(value) FROM facts WHERE dataset = 3",
+            [],
+            |row| row.get(0),
+        )
+        .unwrap();
+    assert_eq!(total, 34.0);
+}
+
+#[test]
+fn skips_the_rows_not_sent() {
+    let mock = MockTransport::new();
+    let mirror = Arc::new(Mirror::in_memory().unwrap());
+    let client = client(&mock, &mirror);
+
+    mock.respond(StatusCode::BAD_REQUEST, r#"{"success":false}"#);
+    assert!(client.send_data("temp", "kitchen", 21.5).is_err());
+    let dry_run = client.clone().with_dry_run(true);
+    let _ = dry_run.send_data("temp", "garage", 12.0).unwrap();
+
+    assert!(mirror.rows(3).unwrap().is_empty());
+}
+
+#[test]
+fn finds_the_rows_missing_remotely() {
+    let mock = MockTransport::new();
+    let mirror = Arc::new(Mirror::in_memory().unwrap());
+    let client = client(&mock, &mirror);
+    let _ = client
+        .send_batch(&[
+            DataPoint::new("temp", "kitchen", 21.5),
+            DataPoint::new("temp", "garage", 12.0),
+        ])
+        .unwrap();
+
+    mock.respond(StatusCode::OK, DATA);
+    assert_eq!(
+        mirror.missing_remotely(&client).unwrap(),
+        vec![DataPoint::new("temp", "garage", 12.0)]
+    );
+    // The rows read back don't linger in the mirror.
+    mock.respond(StatusCode::OK, r#"{"header":[],"data":[]}"#);
+    assert_eq!(mirror.missing_remotely(&client).unwrap().len(), 2);
+}
+
+#[test]
+fn keeps_the_rows_in_the_database_file() {
+    let path = std::env::temp_dir().join(format!("nca-test-mirror-{}.db", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+
+    let mock = MockTransport::new();
+    let mirror = Arc::new(Mirror::open(&path).unwrap());
+    let _ = client(&mock, &mirror)
+        .send_data("temp", "kitchen", 21.5)
+        .unwrap();
+    drop(mirror);
+
+    let mirror = Mirror::open(&path).unwrap();
+    assert_eq!(
+        mirror.rows(3).unwrap(),
+        vec![DataPoint::new("temp", "kitchen", 21.5)]
+    );
+    drop(mirror);
+    let _ = std::fs::remove_file(&path);
+}
@@ -0,0 +1,211 @@
+//! Rotation of the credentials and worker pool of a `MultiClient`, through mock transports.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use nextcloud_analytics_rs::login::AppCredentials;
+use nextcloud_analytics_rs::testing::MockTransport;
+use nextcloud_analytics_rs::{
+    AnalyticsError, DataPoint, ErrorClass, MultiClient, Request, Response, SyncClient, Transport,
+};
+use reqwest::header::AUTHORIZATION;
+
+fn client(mock: &MockTransport) -> SyncClient {
+    SyncClient::new("https://nextcloud.invalid/", 3, "user", "password")
+        .with_transport(mock.clone())
+}
+
+/// The `Authorization` header of the last request.
+fn last_authorization(mock: &MockTransport) -> String {
+    let requests = mock.requests();
+    let request = requests.last().unwrap();
+    request.headers[AUTHORIZATION].to_str().unwrap().to_string()
+}
+
+fn app_credentials(password: &str) -> AppCredentials {
+    AppCredentials {
+        server: "https://nextcloud.invalid".to_string(),
+        login_name: "bot".to_string(),
+        app_password: password.to_string(),
+    }
+}
+
+#[test]
+fn rotates_the_credentials_of_an_instance() {
+    let (primary, replica) = (MockTransport::new(), MockTransport::new());
+    let multi = MultiClient::mirror(vec![client(&primary), client(&replica)])
+        .with_credential_provider(1, |_: &SyncClient| Ok(app_credentials("rotated")));
+    let worker = multi.clients().nth(1).unwrap().clone();
+
+    let _ = multi.send_data("temp", "kitchen", 21.5).unwrap();
+    let before = last_authorization(&replica);
+    multi.rotate_now(1).unwrap();
+    let _ = multi.send_data("temp", "kitchen", 21.5).unwrap();
+    let after = last_authorization(&replica);
+    assert_ne!(before, after);
+    assert_eq!(before, last_authorization(&primary));
+
+    // Clones of the client, e.g. queueing points, use the new credentials too.
+    let _ = worker.send_data("temp", "garage", 12.0).unwrap();
+    assert_eq!(last_authorization(&replica), after);
+    assert_eq!(worker.credentials().user(), "bot");
+}
+
+#[test]
+fn keeps_the_credentials_if_the_provider_fails() {
+    let mock = MockTransport::new();
+    let multi = MultiClient::failover(vec![client(&mock), client(&MockTransport::new())])
+        .with_credential_provider(0, |_: &SyncClient| {
+            Err(AnalyticsError::Local("login flow expired".into()))
+        });
+
+    assert!(multi.rotate_now(0).is_err());
+    assert_eq!(multi.clients().next().unwrap().credentials().user(), "user");
+    let err = multi.rotate_now(1).unwrap_err();
+    assert_eq!(err.class(), ErrorClass::Local);
+    assert!(multi.rotate_now(2).is_err());
+}
+
+#[test]
+fn rotates_on_schedule() {
+    let rotations = Arc::new(AtomicUsize::new(0));
+    let counter = Arc::clone(&rotations);
+    let multi = MultiClient::failover(vec![
+        client(&MockTransport::new()),
+        client(&MockTransport::new()),
+    ])
+    .with_credential_provider(0, move |_: &SyncClient| {
+        counter.fetch_add(1, Ordering::SeqCst);
+        Ok(app_credentials("rotated"))
+    })
+    .with_rotation_schedule(0, Duration::from_millis(50));
+
+    assert!(multi.rotate_due().is_empty());
+    std::thread::sleep(Duration::from_millis(60));
+    let rotated = multi.rotate_due();
+    assert_eq!(rotated.len(), 1);
+    assert_eq!(rotated[0].0, 0);
+    assert!(rotated[0].1.is_ok());
+    assert!(multi.rotate_due().is_empty());
+    assert_eq!(rotations.load(Ordering::SeqCst), 1);
+}
+
+/// A mock transport answering after a delay, counting the requests in flight, and logging the
+/// name of the instance of each answered request.
+#[derive(Clone)]
+struct Slow {
+    name: &'static str,
+    delay: Duration,
+    mock: MockTransport,
+    in_flight: Arc<AtomicUsize>,
+    max_in_flight: Arc<AtomicUsize>,
+    log: Arc<Mutex<Vec<&'static str>>>,
+}
+
+impl Transport for Slow {
+    fn send(&self, request: Request) -> Result<Response, AnalyticsError> {
+        let now = self.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+        self.max_in_flight.fetch_max(now, Ordering::SeqCst);
+        thread::sleep(self.delay);
+        self.in_flight.fetch_sub(1, Ordering::SeqCst);
+        self.log.lock().unwrap().push(self.name);
+        self.mock.send(request)
+    }
+}
+
+fn slow(name: &'static str, millis: u64, log: &Arc<Mutex<Vec<&'static str>>>) -> Slow {
+    Slow {
+        name,
+        delay: Duration::from_millis(millis),
+        mock: MockTransport::new(),
+        in_flight: Arc::new(AtomicUsize::new(0)),
+        max_in_flight: Arc::new(AtomicUsize::new(0)),
+        log: log.clone(),
+    }
+}
+
+fn batch(sensor: &str) -> Vec<DataPoint> {
+    vec![DataPoint::new("temp", sensor, 21.5)]
+}
+
+#[test]
+fn a_slow_instance_does_not_starve_the_others() {
+    let log = Arc::new(Mutex::new(Vec::new()));
+    let (slow, fast) = (slow("slow", 100, &log), slow("fast", 5, &log));
+    let multi = MultiClient::mirror(vec![
+        SyncClient::new("https://slow.invalid/", 3, "user", "password")
+            .with_transport(slow.clone()),
+        SyncClient::new("https://fast.invalid/", 3, "user", "password")
+            .with_transport(fast.clone()),
+    ])
+    .with_worker_pool(2, 1);
+
+    let deliveries = (0..8)
+        .map(|i| (i % 2, batch(&format!("sensor-{}", i))))
+        .collect::<Vec<_>>();
+    let results = multi.deliver(deliveries);
+    assert_eq!(results.len(), 8);
+    assert!(results.iter().all(Result::is_ok));
+
+    // The fast instance got all its batches while the slow one was still busy.
+    let log = log.lock().unwrap();
+    assert_eq!(log.len(), 8);
+    let last_fast = log.iter().rposition(|name| *name == "fast").unwrap();
+    assert!(
+        log[..=last_fast]
+            .iter()
+            .filter(|name| **name == "slow")
+            .count()
+            < 4
+    );
+    assert_eq!(log[4..], ["slow"; 4]);
+
+    // Each instance had a single request at once, and got its batches in order.
+    assert_eq!(slow.max_in_flight.load(Ordering::SeqCst), 1);
+    assert_eq!(fast.max_in_flight.load(Ordering::SeqCst), 1);
+    let keys = |mock: &MockTransport| {
+        mock.sent_points()
+            .into_iter()
+            .map(|point| point.dimension2)
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(
+        keys(&fast.mock),
+        ["sensor-1", "sensor-3", "sensor-5", "sensor-7"]
+    );
+    assert_eq!(
+        keys(&slow.mock),
+        ["sensor-0", "sensor-2", "sensor-4", "sensor-6"]
+    );
+}
+
+#[test]
+fn caps_the_requests_in_flight_per_instance() {
+    let log = Arc::new(Mutex::new(Vec::new()));
+    let transport = slow("only", 20, &log);
+    let multi = MultiClient::mirror(vec![SyncClient::new(
+        "https://only.invalid/",
+        3,
+        "user",
+        "password",
+    )
+    .with_transport(transport.clone())])
+    .with_worker_pool(4, 2);
+
+    let results = multi.deliver((0..6).map(|i| (0, batch(&i.to_string()))).collect());
+    assert!(results.iter().all(Result::is_ok));
+    assert_eq!(transport.max_in_flight.load(Ordering::SeqCst), 2);
+    assert_eq!(transport.mock.sent_points().len(), 6);
+}
+
+#[test]
+fn rejects_deliveries_to_unknown_instances() {
+    let mock = MockTransport::new();
+    let multi = MultiClient::failover(vec![client(&mock)]);
+    let results = multi.deliver(vec![(0, batch("kitchen")), (1, batch("garage"))]);
+    assert!(results[0].is_ok());
+    assert!(matches!(results[1], Err(AnalyticsError::Local(_))));
+    assert_eq!(mock.sent_points().len(), 1);
+}
@@ -0,0 +1,105 @@
+//! Failure notifications, through a mock transport.
+
+use std::sync::{Arc, Mutex};
+
+use nextcloud_analytics_rs::testing::MockTransport;
+use nextcloud_analytics_rs::{AnalyticsError, FailureNotifier, Interceptor, Request, SyncClient};
+use reqwest::header::AUTHORIZATION;
+use reqwest::{Method, StatusCode};
+use serde_json::Value;
+
+fn client(mock: &MockTransport, notifier: FailureNotifier) -> SyncClient {
+    mock.client(3).with_failure_notifier(notifier)
+}
+
+/// Fails the next `count` sends of `client`.
+fn fail(client: &SyncClient, mock: &MockTransport, count: usize) {
+    for _ in 0..count {
+        mock.respond(StatusCode::INTERNAL_SERVER_ERROR, "");
+        assert!(client.send_data("temp", "kitchen", 21.5).is_err());
+    }
+}
+
+#[test]
+fn posts_to_talk_after_the_threshold() {
+    let mock = MockTransport::new();
+    let client = client(&mock, FailureNotifier::talk_room("room token/1", 2));
+
+    fail(&client, &mock, 1);
+    assert_eq!(mock.requests().len(), 1);
+    fail(&client, &mock, 1);
+    let requests = mock.requests();
+    assert_eq!(requests.len(), 3);
+
+    let post = &requests[2];
+    assert_eq!(post.method, Method::POST);
+    assert_eq!(
+        post.url,
+        "https://nextcloud.invalid/ocs/v2.php/apps/spreed/api/v1/chat/room%20token%2F1"
+    );
+    assert_eq!(post.headers["OCS-APIRequest"], "true");
+    assert!(post.headers.contains_key(AUTHORIZATION));
+    let body: Value = serde_json::from_str(post.body_text().unwrap()).unwrap();
+    assert!(body["message"]
+        .as_str()
+        .unwrap()
+        .contains("2 consecutive failures"));
+
+    // Once per streak of failures.
+    fail(&client, &mock, 1);
+    assert_eq!(mock.requests().len(), 4);
+}
+
+#[test]
+fn notifies_an_encoded_user() {
+    let mock = MockTransport::new();
+    let client = client(&mock, FailureNotifier::admin_notification("jane doe?", 1));
+
+    fail(&client, &mock, 1);
+    let requests = mock.requests();
+    assert_eq!(requests.len(), 2);
+    assert!(requests[1]
+        .url
+        .ends_with("/ocs/v2.php/apps/notifications/api/v2/admin_notifications/jane%20doe%3F"));
+    let body: Value = serde_json::from_str(requests[1].body_text().unwrap()).unwrap();
+    assert!(body["shortMessage"].is_string());
+}
+
+#[test]
+fn a_threshold_of_zero_notifies_on_the_first_failure() {
+    let notifier = FailureNotifier::talk_room("room", 0);
+    assert_eq!(notifier.threshold(), 1);
+
+    let mock = MockTransport::new();
+    fail(&client(&mock, notifier), &mock, 1);
+    assert_eq!(mock.requests().len(), 2);
+    assert!(mock.requests()[1].url.ends_with("/chat/room"));
+}
+
+/// Records the URLs of the requests, and marks them with a header.
+#[derive(Clone, Default)]
+struct Recorder(Arc<Mutex<Vec<String>>>);
+
+impl Interceptor for Recorder {
+    fn on_request(&self, request: &mut Request) -> Result<(), AnalyticsError> {
+        self.0.lock().unwrap().push(request.url.clone());
+        request
+            .headers
+            .insert("X-Intercepted", "yes".parse().unwrap());
+        Ok(())
+    }
+}
+
+#[test]
+fn notifications_go_through_the_interceptors() {
+    let mock = MockTransport::new();
+    let recorder = Recorder::default();
+    let client =
+        client(&mock, FailureNotifier::talk_room("room", 1)).with_interceptor(recorder.clone());
+
+    fail(&client, &mock, 1);
+    let urls = recorder.0.lock().unwrap().clone();
+    assert_eq!(urls.len(), 2);
+    assert!(urls[1].ends_with("/chat/room"));
+    assert_eq!(mock.requests()[1].headers["X-Intercepted"], "yes");
+}
@@ -0,0 +1,163 @@
+//! The points of a key reach the server in order, even when they're sent concurrently.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use nextcloud_analytics_rs::testing::MockTransport;
+use nextcloud_analytics_rs::{
+    BackgroundSender, BufferedClient, DataPoint, Overflow, Request, Response, SenderOptions,
+    SyncClient, Transport,
+};
+use reqwest::StatusCode;
+use serde_json::Value;
+
+const SUCCESS: &str = r#"{"success":true,"message":"Data update successfull"}"#;
+
+#[derive(Default)]
+struct State {
+    /// The points received, as (key, value), in the order their requests were answered.
+    done: Vec<(String, f64)>,
+    in_flight: usize,
+    max_in_flight: usize,
+}
+
+/// A transport answering the points of every other group of 3 after a delay, so that a later
+/// point of a key could overtake an earlier one; or failing while the server is down.
+#[derive(Clone, Default)]
+struct SlowTransport {
+    state: Arc<Mutex<State>>,
+    down: Arc<AtomicBool>,
+}
+
+impl SlowTransport {
+    fn client(&self) -> SyncClient {
+        SyncClient::new("https://nextcloud.invalid/", 3, "user", "password")
+            .with_transport(self.clone())
+    }
+
+    fn done(&self) -> Vec<(String, f64)> {
+        self.state.lock().unwrap().done.clone()
+    }
+
+    fn max_in_flight(&self) -> usize {
+        self.state.lock().unwrap().max_in_flight
+    }
+}
+
+impl Transport for SlowTransport {
+    fn send(&self, request: Request) -> Result<Response, nextcloud_analytics_rs::AnalyticsError> {
+        if self.down.load(Ordering::SeqCst) {
+            return Ok(Response::new(StatusCode::SERVICE_UNAVAILABLE, ""));
+        }
+        let body: Value = serde_json::from_str(request.body_text().unwrap()).unwrap();
+        let key = body["dimension1"].as_str().unwrap().to_string();
+        let value: f64 = body["dimension3"].as_str().unwrap().parse().unwrap();
+        {
+            let mut state = self.state.lock().unwrap();
+            state.in_flight += 1;
+            state.max_in_flight = state.max_in_flight.max(state.in_flight);
+        }
+        if (value as u32 / 3).is_multiple_of(2) {
+            thread::sleep(Duration::from_millis(10));
+        }
+        let mut state = self.state.lock().unwrap();
+        state.in_flight -= 1;
+        state.done.push((key, value));
+        Ok(Response::new(StatusCode::OK, SUCCESS))
+    }
+}
+
+/// Points for keys a, b and c in turn, with increasing values.
+fn points(len: u32) -> Vec<DataPoint> {
+    (0..len)
+        .map(|i| {
+            let key = ["a", "b", "c"][i as usize % 3];
+            DataPoint::new(key, &i.to_string(), f64::from(i))
+        })
+        .collect()
+}
+
+/// Checks that the values of each key were received in increasing order, and returns how many
+/// were received.
+fn assert_ordered_per_key(received: &[(String, f64)]) -> usize {
+    let mut last = HashMap::new();
+    for (key, value) in received {
+        if let Some(previous) = last.insert(key.clone(), *value) {
+            assert!(
+                previous < *value,
+                "{} after {} for {}",
+                value,
+                previous,
+                key
+            );
+        }
+    }
+    received.len()
+}
+
+#[test]
+fn concurrent_chunks_keep_the_order_of_each_key() {
+    let transport = SlowTransport::default();
+    let results = transport.client().send_stream_with(points(30), 1, 4);
+
+    assert!(results.iter().all(Result::is_ok));
+    assert_eq!(assert_ordered_per_key(&transport.done()), 30);
+    // The keys were still sent at the same time.
+    assert!(transport.max_in_flight() > 1);
+}
+
+#[test]
+fn replay_keeps_the_order_of_each_key() {
+    let path: PathBuf =
+        std::env::temp_dir().join(format!("nca-test-ordering-{}.jsonl", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let transport = SlowTransport::default();
+    let buffered = BufferedClient::new(transport.client(), &path);
+
+    transport.down.store(true, Ordering::SeqCst);
+    for point in points(30) {
+        assert!(!buffered.send_point(point).unwrap().was_sent());
+    }
+    transport.down.store(false, Ordering::SeqCst);
+    assert_eq!(buffered.replay(1, 4).unwrap(), 30);
+
+    assert_eq!(assert_ordered_per_key(&transport.done()), 30);
+    assert!(transport.max_in_flight() > 1);
+    let _ = std::fs::remove_file(&path);
+}
+
+#[test]
+fn background_sender_keeps_the_order_of_each_key() {
+    let mock = MockTransport::new();
+    let sender = BackgroundSender::start_with(
+        Arc::new(mock.client(3)),
+        SenderOptions::new(4, Duration::from_millis(1)).overflow(Overflow::Block),
+    );
+    let producers: Vec<_> = ["a", "b", "c", "d"]
+        .iter()
+        .map(|key| {
+            let handle = sender.handle();
+            thread::spawn(move || {
+                for i in 0..50 {
+                    let point = DataPoint::new(*key, &i.to_string(), f64::from(i));
+                    handle.enqueue_point(point).unwrap();
+                }
+            })
+        })
+        .collect();
+    for producer in producers {
+        producer.join().unwrap();
+    }
+    sender.stop();
+
+    let received: Vec<_> = mock
+        .sent_points()
+        .into_iter()
+        .map(|point| (point.dimension1, point.dimension3))
+        .collect();
+    assert_eq!(assert_ordered_per_key(&received), 200);
+}
@@ -0,0 +1,97 @@
+//! Settings of the builder profiles, as described by the clients.
+
+use std::time::Duration;
+
+use nextcloud_analytics_rs::testing::MockTransport;
+use nextcloud_analytics_rs::{
+    ApiVersion, ClientDescription, DataPoint, Profile, RateLimit, SyncClient, SyncClientBuilder,
+};
+
+fn builder() -> SyncClientBuilder {
+    SyncClient::builder("https://nextcloud.invalid/", 3, "user", "password")
+}
+
+fn describe(profile: Profile) -> ClientDescription {
+    builder().profile(profile).build().unwrap().describe()
+}
+
+#[test]
+fn iot_profile() {
+    let description = describe(Profile::Iot);
+    assert_eq!(description.profile, Some("iot"));
+    assert_eq!(description.timeout, Duration::from_secs(30));
+    assert_eq!(description.connect_timeout, Some(Duration::from_secs(15)));
+    assert_eq!(description.max_attempts, 5);
+    assert_eq!(description.batch_size, 50);
+    assert_eq!(description.rate_limit, Some((1.0, 10)));
+}
+
+#[test]
+fn backfill_profile() {
+    let description = describe(Profile::Backfill);
+    assert_eq!(description.profile, Some("backfill"));
+    assert_eq!(description.timeout, Duration::from_secs(120));
+    assert_eq!(description.connect_timeout, Some(Duration::from_secs(30)));
+    assert_eq!(description.max_attempts, 3);
+    assert_eq!(description.batch_size, 1000);
+    assert_eq!(description.rate_limit, Some((5.0, 10)));
+}
+
+#[test]
+fn interactive_profile() {
+    let description = describe(Profile::Interactive);
+    assert_eq!(description.profile, Some("interactive"));
+    assert_eq!(description.timeout, Duration::from_secs(10));
+    assert_eq!(description.connect_timeout, Some(Duration::from_secs(3)));
+    assert_eq!(description.max_attempts, 1);
+    assert_eq!(description.batch_size, 100);
+    assert_eq!(description.rate_limit, None);
+}
+
+#[test]
+fn without_profile() {
+    let description = builder().build().unwrap().describe();
+    assert_eq!(description.profile, None);
+    assert_eq!(description.max_attempts, 1);
+    assert_eq!(description.batch_size, 500);
+    assert_eq!(description.rate_limit, None);
+}
+
+#[test]
+fn explicit_settings_override_the_profile() {
+    let description = builder()
+        .rate_limit(None)
+        .batch_size(10)
+        .profile(Profile::Backfill)
+        .build()
+        .unwrap()
+        .describe();
+    assert_eq!(description.batch_size, 10);
+    assert_eq!(description.rate_limit, None);
+
+    let description = builder()
+        .profile(Profile::Interactive)
+        .rate_limit(Some(RateLimit::new(2.5, 4)))
+        .build()
+        .unwrap()
+        .describe();
+    assert_eq!(description.rate_limit, Some((2.5, 4)));
+}
+
+#[test]
+fn streams_are_sent_in_batches_of_the_profile() {
+    let mock = MockTransport::new();
+    let client = builder()
+        .profile(Profile::Iot)
+        .api_version(ApiVersion::V3)
+        .build()
+        .unwrap()
+        .with_transport(mock.clone());
+
+    let points = (0..120).map(|i| DataPoint::new("temp".to_string(), i.to_string(), 1.0));
+    let results = client.send_stream(points);
+    assert_eq!(results.len(), 3);
+    assert!(results.iter().all(Result::is_ok));
+    assert_eq!(mock.requests().len(), 3);
+    assert_eq!(mock.sent_points().len(), 120);
+}
@@ -0,0 +1,104 @@
+//! Progress of CSV imports and backfills, through a mock transport.
+
+use std::sync::mpsc;
+use std::time::Duration;
+
+use nextcloud_analytics_rs::testing::MockTransport;
+use nextcloud_analytics_rs::{CsvMapping, Progress};
+
+const CSV: &str = "key,room,value\ntemp,kitchen,21.5\ntemp,garage,12\ntemp,attic,30\nhumidity,kitchen,40\nhumidity,garage,60\n";
+
+#[test]
+fn reports_the_progress_of_csv_files() {
+    let path = std::env::temp_dir().join(format!("nca-test-progress-{}.csv", std::process::id()));
+    std::fs::write(&path, CSV).unwrap();
+    let mock = MockTransport::new();
+    let client = mock.client(3);
+    let mapping = CsvMapping::new("key", "room", "value").batch_size(2);
+
+    let (sender, receiver) = mpsc::channel();
+    let rows = client
+        .send_csv_file(&path, &mapping, move |progress| {
+            let _ = sender.send(progress.clone());
+        })
+        .unwrap();
+    let _ = std::fs::remove_file(&path);
+
+    assert_eq!(rows, 5);
+    let reports: Vec<Progress> = receiver.iter().collect();
+    let rows: Vec<_> = reports.iter().map(|progress| progress.rows).collect();
+    assert_eq!(rows, [2, 4, 5]);
+    let bytes: Vec<_> = reports.iter().map(|progress| progress.bytes).collect();
+    assert!(
+        bytes.windows(2).all(|pair| pair[0] < pair[1]),
+        "{:?}",
+        bytes
+    );
+    let last = reports.last().unwrap();
+    assert_eq!(last.bytes, CSV.len() as u64);
+    assert_eq!(last.total_bytes, Some(CSV.len() as u64));
+    assert_eq!(last.fraction(), Some(1.0));
+    assert_eq!(last.eta(), Some(Duration::ZERO));
+}
+
+#[test]
+fn reports_the_rows_of_readers_without_a_total() {
+    let client = MockTransport::new().client(3);
+    let mapping = CsvMapping::new("key", "room", "value").batch_size(3);
+
+    let mut reports = Vec::new();
+    client
+        .send_csv(CSV.as_bytes(), &mapping, |progress| {
+            reports.push(progress.clone())
+        })
+        .unwrap();
+
+    assert_eq!(reports.len(), 2);
+    assert_eq!(reports[1].rows, 5);
+    assert_eq!(reports[1].total_bytes, None);
+    assert_eq!(reports[1].fraction(), None);
+    assert_eq!(reports[1].eta(), None);
+}
+
+#[test]
+fn estimates_the_time_left_at_the_average_rate() {
+    let progress = Progress {
+        rows: 250,
+        total_rows: Some(1000),
+        bytes: 0,
+        total_bytes: None,
+        elapsed: Duration::from_secs(60),
+    };
+    assert_eq!(progress.fraction(), Some(0.25));
+    assert_eq!(progress.eta(), Some(Duration::from_secs(180)));
+
+    let started = Progress {
+        rows: 0,
+        ..progress
+    };
+    assert_eq!(started.eta(), None);
+}
+
+#[cfg(feature = "chrono")]
+#[test]
+fn reports_the_progress_of_backfills() {
+    use nextcloud_analytics_rs::{BackfillPlan, DataPoint};
+
+    let client = MockTransport::new().client(3);
+    let points: Vec<_> = (0..5)
+        .map(|i| DataPoint::new("temp", &i.to_string(), f64::from(i)))
+        .collect();
+
+    let mut reports = Vec::new();
+    let sent = BackfillPlan::new()
+        .batch_size(2)
+        .run_with_progress(&client, points, |progress| reports.push(progress.clone()))
+        .unwrap();
+
+    assert_eq!(sent, 5);
+    let rows: Vec<_> = reports
+        .iter()
+        .map(|progress| (progress.rows, progress.total_rows))
+        .collect();
+    assert_eq!(rows, [(2, Some(5)), (4, Some(5)), (5, Some(5))]);
+}
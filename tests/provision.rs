@@ -0,0 +1,190 @@
+//! Collections created on the first send, through a mock transport.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use nextcloud_analytics_rs::testing::MockTransport;
+use nextcloud_analytics_rs::{
+    AnalyticsError, ApiVersion, DataPoint, ErrorClass, ProvisionTemplate, Request, Response,
+    SyncClient, Transport,
+};
+use reqwest::StatusCode;
+use serde_json::Value;
+
+fn template() -> ProvisionTemplate {
+    ProvisionTemplate::new("Sensors")
+        .dimensions("Sensor", "Date", "Reading")
+        .chart("column")
+}
+
+/// The paths of the requests, relative to the Nextcloud URL.
+fn paths(mock: &MockTransport) -> Vec<String> {
+    mock.requests()
+        .iter()
+        .map(|request| {
+            request
+                .url
+                .trim_start_matches("https://nextcloud.invalid/")
+                .to_string()
+        })
+        .collect()
+}
+
+#[test]
+fn provisions_a_missing_collection() {
+    let mock = MockTransport::new();
+    let client = mock.client(3).with_auto_provision(template());
+    mock.respond(StatusCode::NOT_FOUND, "")
+        .respond(StatusCode::OK, "12")
+        .respond(StatusCode::OK, "34");
+
+    assert!(client
+        .send_data("temp", "kitchen", 21.5)
+        .unwrap()
+        .was_sent());
+    assert_eq!(client.provisioned_collection(), Some(34));
+    assert!(client
+        .clone()
+        .send_data("temp", "garage", 12.0)
+        .unwrap()
+        .was_sent());
+
+    assert_eq!(
+        paths(&mock),
+        [
+            "apps/analytics/api/1.0/adddata/3",
+            "apps/analytics/dataset",
+            "apps/analytics/report",
+            "apps/analytics/api/1.0/adddata/34",
+            "apps/analytics/api/1.0/adddata/34",
+        ]
+    );
+    let requests = mock.requests();
+    let dataset: Value = serde_json::from_str(requests[1].body_text().unwrap()).unwrap();
+    assert_eq!(dataset["name"], "Sensors");
+    assert_eq!(dataset["dimension1"], "Sensor");
+    assert_eq!(dataset["value"], "Reading");
+    let report: Value = serde_json::from_str(requests[2].body_text().unwrap()).unwrap();
+    assert_eq!(report["dataset"], 12);
+    assert_eq!(report["chart"], "column");
+}
+
+#[test]
+fn sends_batches_to_the_provisioned_dataset() {
+    let mock = MockTransport::new();
+    let client = SyncClient::builder("https://nextcloud.invalid/", 3, "user", "password")
+        .api_version(ApiVersion::V3)
+        .build()
+        .unwrap()
+        .with_transport(mock.clone())
+        .with_auto_provision(template());
+    mock.respond(StatusCode::NOT_FOUND, "")
+        .respond(StatusCode::OK, "12")
+        .respond(StatusCode::OK, "34");
+
+    let points = [
+        DataPoint::new("temp", "kitchen", 21.5),
+        DataPoint::new("temp", "garage", 12.0),
+    ];
+    assert!(client.send_batch(&points).unwrap().was_sent());
+    assert_eq!(client.provisioned_collection(), Some(12));
+    assert_eq!(
+        paths(&mock).last().unwrap(),
+        "apps/analytics/api/3.0/data/12/add"
+    );
+}
+
+#[test]
+fn only_provisions_missing_collections() {
+    let mock = MockTransport::new();
+    let client = mock.client(3).with_auto_provision(template());
+    mock.respond(StatusCode::INTERNAL_SERVER_ERROR, "");
+    let err = client.send_data("temp", "kitchen", 21.5).unwrap_err();
+    assert_eq!(err.class(), ErrorClass::Http);
+    assert_eq!(client.provisioned_collection(), None);
+
+    let client = mock.client(3);
+    mock.respond(StatusCode::NOT_FOUND, "");
+    let err = client.send_data("temp", "kitchen", 21.5).unwrap_err();
+    assert_eq!(err.class(), ErrorClass::NotFound);
+    assert_eq!(mock.requests().len(), 2);
+}
+
+#[test]
+fn fails_if_the_dataset_cant_be_created() {
+    let mock = MockTransport::new();
+    let client = mock.client(3).with_auto_provision(template());
+    mock.respond(StatusCode::NOT_FOUND, "")
+        .respond(StatusCode::FORBIDDEN, "");
+
+    assert!(client.send_data("temp", "kitchen", 21.5).is_err());
+    assert_eq!(client.provisioned_collection(), None);
+    assert_eq!(mock.requests().len(), 2);
+}
+
+/// A server without collection 3, creating dataset 12 and report 34, counting the datasets
+/// created.
+#[derive(Clone, Default)]
+struct Server {
+    created: Arc<AtomicUsize>,
+}
+
+impl Transport for Server {
+    fn send(&self, request: Request) -> Result<Response, AnalyticsError> {
+        let response = if request.url.ends_with("/adddata/3") {
+            Response::new(StatusCode::NOT_FOUND, "")
+        } else if request.url.ends_with("/dataset") {
+            self.created.fetch_add(1, Ordering::SeqCst);
+            // Slow enough for the other processes to try provisioning meanwhile.
+            thread::sleep(Duration::from_millis(20));
+            Response::new(StatusCode::OK, "12")
+        } else if request.url.ends_with("/report") {
+            Response::new(StatusCode::OK, "34")
+        } else {
+            Response::new(StatusCode::OK, r#"{"success":true}"#)
+        };
+        Ok(response)
+    }
+}
+
+#[test]
+fn shares_the_provisioned_collections_of_a_mapping_file() {
+    let path = std::env::temp_dir().join(format!("nca-test-provision-{}.json", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let server = Server::default();
+
+    // Clients with their own state, like separate processes.
+    let senders: Vec<_> = (0..4)
+        .map(|i| {
+            let client = SyncClient::new("https://nextcloud.invalid/", 3, "user", "password")
+                .with_transport(server.clone())
+                .with_auto_provision(template().mapping_file(&path));
+            thread::spawn(move || {
+                let receipt = client.send_data("temp", &i.to_string(), 21.5).unwrap();
+                assert!(receipt.was_sent());
+                client.provisioned_collection()
+            })
+        })
+        .collect();
+    for sender in senders {
+        assert_eq!(sender.join().unwrap(), Some(34));
+    }
+    assert_eq!(server.created.load(Ordering::SeqCst), 1);
+
+    let mapping: Value = serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+    assert_eq!(mapping, serde_json::json!({"Sensors": 34}));
+
+    // A restarted process sends to the mapped collection right away.
+    let mock = MockTransport::new();
+    let client = mock
+        .client(3)
+        .with_auto_provision(template().mapping_file(&path));
+    assert!(client
+        .send_data("temp", "kitchen", 21.5)
+        .unwrap()
+        .was_sent());
+    assert_eq!(paths(&mock), ["apps/analytics/api/1.0/adddata/34"]);
+    let _ = std::fs::remove_file(&path);
+}
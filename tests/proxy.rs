@@ -0,0 +1,119 @@
+//! Requests sent through proxies, against local fake proxies.
+
+#[cfg(feature = "socks")]
+use std::io::{BufRead, BufReader, Read, Write};
+#[cfg(feature = "socks")]
+use std::net::{TcpListener, TcpStream};
+#[cfg(feature = "socks")]
+use std::thread;
+
+use nextcloud_analytics_rs::SyncClient;
+
+#[cfg(feature = "socks")]
+const SUCCESS: &str = r#"{"success":true,"message":"Data update successfull"}"#;
+
+/// Answers an HTTP request read from `stream` with a successful send, returning its request
+/// line.
+#[cfg(feature = "socks")]
+fn answer_http(stream: &mut TcpStream) -> String {
+    let mut reader = BufReader::new(stream.try_clone().unwrap());
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line).unwrap();
+    let mut content_length = 0;
+    loop {
+        let mut header = String::new();
+        reader.read_line(&mut header).unwrap();
+        if header.trim().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.eq_ignore_ascii_case("content-length") {
+                content_length = value.trim().parse().unwrap();
+            }
+        }
+    }
+    let mut body = vec![0; content_length];
+    reader.read_exact(&mut body).unwrap();
+
+    write!(
+        stream,
+        "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        SUCCESS.len(),
+        SUCCESS
+    )
+    .unwrap();
+    request_line.trim_end().to_string()
+}
+
+/// A SOCKS5 proxy accepting one connection without authentication, and answering the request
+/// tunneled through it itself, as if it were the target. Returns its port, and the target and
+/// request line it received.
+#[cfg(feature = "socks")]
+fn socks5_proxy() -> (u16, thread::JoinHandle<(String, String)>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let handle = thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+
+        // Greeting: version, then the authentication methods offered.
+        let mut header = [0; 2];
+        stream.read_exact(&mut header).unwrap();
+        assert_eq!(header[0], 5);
+        let mut methods = vec![0; usize::from(header[1])];
+        stream.read_exact(&mut methods).unwrap();
+        assert!(methods.contains(&0));
+        stream.write_all(&[5, 0]).unwrap();
+
+        // Connection request, to a domain name as the proxy resolves it with `socks5h`.
+        let mut request = [0; 4];
+        stream.read_exact(&mut request).unwrap();
+        assert_eq!(request[..3], [5, 1, 0]);
+        assert_eq!(request[3], 3);
+        let mut len = [0; 1];
+        stream.read_exact(&mut len).unwrap();
+        let mut host = vec![0; usize::from(len[0])];
+        stream.read_exact(&mut host).unwrap();
+        let mut port = [0; 2];
+        stream.read_exact(&mut port).unwrap();
+        stream.write_all(&[5, 0, 0, 1, 127, 0, 0, 1, 0, 0]).unwrap();
+
+        let target = format!(
+            "{}:{}",
+            String::from_utf8(host).unwrap(),
+            u16::from_be_bytes(port)
+        );
+        (target, answer_http(&mut stream))
+    });
+    (port, handle)
+}
+
+#[cfg(feature = "socks")]
+#[test]
+fn sends_through_socks5_proxy() {
+    let (port, proxy) = socks5_proxy();
+    let client = SyncClient::builder("http://nextcloud.invalid/", 3, "user", "password")
+        .proxy(format!("socks5h://127.0.0.1:{}", port))
+        .system_proxy(false)
+        .build()
+        .unwrap();
+
+    assert!(client
+        .send_data("temperature", "kitchen", 21.5)
+        .unwrap()
+        .was_sent());
+    let (target, request_line) = proxy.join().unwrap();
+    assert_eq!(target, "nextcloud.invalid:80");
+    assert!(request_line.starts_with("POST /apps/analytics/"));
+}
+
+#[cfg(not(feature = "socks"))]
+#[test]
+fn socks5_proxy_needs_feature() {
+    let built = SyncClient::builder("http://nextcloud.invalid/", 3, "user", "password")
+        .proxy("socks5://127.0.0.1:1080")
+        .build();
+    match built {
+        Err(err) => assert!(err.to_string().contains("`socks` feature")),
+        Ok(_) => panic!("a SOCKS proxy was accepted without the `socks` feature"),
+    }
+}
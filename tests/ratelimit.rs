@@ -0,0 +1,126 @@
+//! Rate limits of the requests of clients, through mock transports.
+//!
+//! The bounds on the durations leave a wide margin over the ones the token buckets guarantee,
+//! so that slow or loaded machines don't fail them.
+
+use std::fs;
+use std::path::PathBuf;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use nextcloud_analytics_rs::testing::MockTransport;
+use nextcloud_analytics_rs::{RateLimit, SharedRateLimit, SyncClient};
+
+/// How long sending `count` data points with `client` takes.
+fn time_sends(client: &SyncClient, count: usize) -> Duration {
+    let started = Instant::now();
+    for i in 0..count {
+        let _ = client.send_data("temp", &i.to_string(), 21.5).unwrap();
+    }
+    started.elapsed()
+}
+
+#[test]
+fn bursts_then_waits_for_the_rate() {
+    let mock = MockTransport::new();
+    let client = mock.client(3).with_rate_limit(RateLimit::new(20.0, 5));
+
+    // The bucket starts full.
+    let burst = time_sends(&client, 5);
+    assert!(burst < Duration::from_millis(200), "{:?}", burst);
+
+    // Then 10 requests at 20 per second.
+    let limited = time_sends(&client, 10);
+    assert!(limited >= Duration::from_millis(450), "{:?}", limited);
+    assert!(limited < Duration::from_secs(3), "{:?}", limited);
+    assert_eq!(mock.requests().len(), 15);
+}
+
+#[test]
+fn refills_while_idle_up_to_the_burst() {
+    let mock = MockTransport::new();
+    let client = mock.client(3).with_rate_limit(RateLimit::new(50.0, 3));
+
+    let _ = time_sends(&client, 3);
+    thread::sleep(Duration::from_millis(300));
+    // Idling refilled the 3 tokens of the burst, not 15.
+    let burst = time_sends(&client, 3);
+    assert!(burst < Duration::from_millis(50), "{:?}", burst);
+    let limited = time_sends(&client, 5);
+    assert!(limited >= Duration::from_millis(80), "{:?}", limited);
+}
+
+#[test]
+fn clones_share_the_bucket() {
+    let limit = RateLimit::new(20.0, 2);
+    assert_eq!((limit.rate(), limit.burst()), (20.0, 2));
+    let mock = MockTransport::new();
+    let first = mock.client(3).with_rate_limit(limit.clone());
+    let second = mock.client(4).with_rate_limit(limit);
+
+    let started = Instant::now();
+    thread::scope(|scope| {
+        scope.spawn(|| time_sends(&first, 5));
+        scope.spawn(|| time_sends(&second, 5));
+    });
+    // 2 requests of the burst, then 8 at 20 per second.
+    let elapsed = started.elapsed();
+    assert!(elapsed >= Duration::from_millis(350), "{:?}", elapsed);
+    assert_eq!(mock.requests().len(), 10);
+}
+
+fn bucket_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!(
+        "nca-test-ratelimit-{}-{}",
+        name,
+        std::process::id()
+    ))
+}
+
+#[test]
+fn clients_sharing_a_file_respect_the_combined_limit() {
+    let path = bucket_path("combined");
+    let _ = fs::remove_file(&path);
+
+    // Separate limits on the same file, like the ones of two processes, and of a third one
+    // started later.
+    let mock = MockTransport::new();
+    let [first, second, third] = [(); 3].map(|()| {
+        mock.client(3)
+            .with_shared_rate_limit(SharedRateLimit::new(&path, 20.0, 2))
+    });
+
+    let started = Instant::now();
+    thread::scope(|scope| {
+        scope.spawn(|| time_sends(&first, 5));
+        scope.spawn(|| time_sends(&second, 5));
+    });
+    // 2 requests of the burst, then 8 at 20 per second.
+    let elapsed = started.elapsed();
+    assert!(elapsed >= Duration::from_millis(350), "{:?}", elapsed);
+    assert!(elapsed < Duration::from_secs(5), "{:?}", elapsed);
+    assert_eq!(mock.requests().len(), 10);
+
+    // The file keeps the state: the third client starts with the bucket the others emptied.
+    let limited = time_sends(&third, 2);
+    assert!(limited >= Duration::from_millis(50), "{:?}", limited);
+
+    let _ = fs::remove_file(&path);
+    let _ = fs::remove_file(path.with_extension("lock"));
+}
+
+#[test]
+fn an_unreadable_shared_state_is_a_full_bucket() {
+    let path = bucket_path("garbage");
+    fs::write(&path, "not a bucket").unwrap();
+
+    let mock = MockTransport::new();
+    let client = mock
+        .client(3)
+        .with_shared_rate_limit(SharedRateLimit::new(&path, 1.0, 3));
+    let burst = time_sends(&client, 3);
+    assert!(burst < Duration::from_millis(500), "{:?}", burst);
+
+    let _ = fs::remove_file(&path);
+    let _ = fs::remove_file(path.with_extension("lock"));
+}
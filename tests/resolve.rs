@@ -0,0 +1,128 @@
+//! Falling back to the last good address of the server when its host name can't be resolved,
+//! against a local HTTP server and a resolver failing on demand.
+
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpListener};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use nextcloud_analytics_rs::testing::resolution;
+use nextcloud_analytics_rs::SyncClient;
+
+const SUCCESS: &str = r#"{"success":true,"message":"Data update successfull"}"#;
+
+/// A server answering every request with a success, recording their request lines.
+fn serve() -> (SocketAddr, Arc<Mutex<Vec<String>>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let lines = Arc::new(Mutex::new(Vec::new()));
+    let seen = lines.clone();
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = BufReader::new(stream.unwrap());
+            let mut line = String::new();
+            stream.read_line(&mut line).unwrap();
+            let mut len = 0;
+            loop {
+                let mut header = String::new();
+                stream.read_line(&mut header).unwrap();
+                if header.trim().is_empty() {
+                    break;
+                }
+                if let Some((name, value)) = header.split_once(':') {
+                    if name.eq_ignore_ascii_case("content-length") {
+                        len = value.trim().parse().unwrap();
+                    }
+                }
+            }
+            stream.read_exact(&mut vec![0; len]).unwrap();
+            seen.lock().unwrap().push(line.trim_end().to_string());
+            write!(
+                stream.get_mut(),
+                "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                SUCCESS.len(),
+                SUCCESS
+            )
+            .unwrap();
+        }
+    });
+    (addr, lines)
+}
+
+/// A client of the server at `localhost`, whose resolution fails once `dns_up` is false.
+fn client(addr: SocketAddr, ttl: Duration, dns_up: &Arc<AtomicBool>) -> SyncClient {
+    let url = format!("http://localhost:{}/", addr.port());
+    let builder = SyncClient::builder(&url, 3, "user", "password").last_good_address(ttl);
+    let dns_up = dns_up.clone();
+    resolution::with_resolver(builder, move |host, port| {
+        assert_eq!((host, port), ("localhost", addr.port()));
+        if dns_up.load(Ordering::SeqCst) {
+            Ok(vec![addr])
+        } else {
+            Err(io::Error::other("temporary failure in name resolution"))
+        }
+    })
+    .build()
+    .unwrap()
+}
+
+#[test]
+fn connects_to_the_last_good_address_when_resolution_fails() {
+    let (addr, lines) = serve();
+    let dns_up = Arc::new(AtomicBool::new(true));
+    let client = client(addr, Duration::from_secs(60), &dns_up);
+
+    assert!(client
+        .send_data("temp", "kitchen", 21.5)
+        .unwrap()
+        .was_sent());
+    dns_up.store(false, Ordering::SeqCst);
+    assert!(client
+        .send_data("temp", "kitchen", 22.0)
+        .unwrap()
+        .was_sent());
+    dns_up.store(true, Ordering::SeqCst);
+    assert!(client
+        .send_data("temp", "kitchen", 22.5)
+        .unwrap()
+        .was_sent());
+
+    let lines = lines.lock().unwrap();
+    assert_eq!(lines.len(), 3);
+    assert!(
+        lines[0].starts_with("POST /apps/analytics/"),
+        "{}",
+        lines[0]
+    );
+    // Relayed, with the URL of the request.
+    let relayed = format!("POST http://localhost:{}/apps/analytics/", addr.port());
+    assert!(lines[1].starts_with(&relayed), "{}", lines[1]);
+    assert!(
+        lines[2].starts_with("POST /apps/analytics/"),
+        "{}",
+        lines[2]
+    );
+}
+
+#[test]
+fn forgets_addresses_after_their_ttl() {
+    let (addr, lines) = serve();
+    let dns_up = Arc::new(AtomicBool::new(false));
+    let client = client(addr, Duration::from_millis(100), &dns_up);
+
+    // Nothing to fall back to yet: the client resolves the host name itself.
+    let _ = client.send_data("temp", "kitchen", 21.5).unwrap();
+    dns_up.store(true, Ordering::SeqCst);
+    let _ = client.send_data("temp", "kitchen", 22.0).unwrap();
+    dns_up.store(false, Ordering::SeqCst);
+    thread::sleep(Duration::from_millis(200));
+    let _ = client.send_data("temp", "kitchen", 22.5).unwrap();
+
+    let lines = lines.lock().unwrap();
+    assert_eq!(lines.len(), 3);
+    for line in lines.iter() {
+        assert!(line.starts_with("POST /apps/analytics/"), "{}", line);
+    }
+}
@@ -0,0 +1,80 @@
+//! Decoding of certificate pins, and extraction of the public keys they pin.
+
+#![cfg(any(feature = "native-tls", feature = "rustls"))]
+
+use nextcloud_analytics_rs::testing::pinning::decode_pin;
+
+/// The pin of `tests/fixtures/cert-rsa.der`, as printed by the command of
+/// `Security::pin_spki_sha256`.
+const RSA_PIN: &str = "DmSwzc4SOgnV9wbDdw1cRgTLH9NnDzjrbX58FIT/PXY=";
+
+#[test]
+fn decodes_pins() {
+    let expected = decode_pin(RSA_PIN).unwrap();
+    assert_eq!(expected[..4], [0x0e, 0x64, 0xb0, 0xcd]);
+    assert_eq!(
+        decode_pin(&format!("sha256/{}", RSA_PIN)).unwrap(),
+        expected
+    );
+    assert_eq!(decode_pin(&format!(" {} ", RSA_PIN)).unwrap(), expected);
+    assert_eq!(decode_pin(RSA_PIN.trim_end_matches('=')).unwrap(), expected);
+}
+
+#[test]
+fn refuses_invalid_pins() {
+    for pin in [
+        "",
+        "sha1/DmSwzc4SOgnV9wbDdw1cRgTLH9NnDzjrbX58FIT/PXY=",
+        "DmSwzc4SOgnV9wbDdw1cRgTLH9NnDzjrbX58FIT-PXY=",
+        "DmSwzc4SOgnV9wbDdw1cRgTLH9NnDzjrbX58FIT/",
+        "DmSwzc4SOgnV9wbDdw1cRgTLH9NnDzjrbX58FIT/PXYAAAA=",
+    ] {
+        assert!(decode_pin(pin).is_err(), "{:?}", pin);
+    }
+}
+
+#[cfg(feature = "rustls")]
+mod certificates {
+    use nextcloud_analytics_rs::testing::pinning::{
+        decode_pin, der_element, subject_public_key_info,
+    };
+
+    use super::RSA_PIN;
+
+    /// Self-signed RSA and P-256 certificates, their `SubjectPublicKeyInfo` as extracted by
+    /// `openssl pkey`, and the pin of the P-256 one.
+    const RSA_CERT: &[u8] = include_bytes!("fixtures/cert-rsa.der");
+    const RSA_SPKI: &[u8] = include_bytes!("fixtures/spki-rsa.der");
+    const EC_CERT: &[u8] = include_bytes!("fixtures/cert-ec.der");
+    const EC_SPKI: &[u8] = include_bytes!("fixtures/spki-ec.der");
+    const EC_PIN: &str = "FE2g216IDjI+ZcAO5BEwSY2i3w/YJ5IbjnRYYi6bcRw=";
+
+    #[test]
+    fn finds_the_public_key_of_certificates() {
+        for (cert, spki, pin) in [(RSA_CERT, RSA_SPKI, RSA_PIN), (EC_CERT, EC_SPKI, EC_PIN)] {
+            let found = subject_public_key_info(cert).unwrap();
+            assert_eq!(found, spki);
+            let hash = ring::digest::digest(&ring::digest::SHA256, found);
+            assert_eq!(hash.as_ref(), decode_pin(pin).unwrap());
+        }
+    }
+
+    #[test]
+    fn refuses_truncated_certificates() {
+        for len in 0..RSA_CERT.len() {
+            assert_eq!(subject_public_key_info(&RSA_CERT[..len]), None, "{}", len);
+        }
+    }
+
+    #[test]
+    fn refuses_lengths_past_the_end() {
+        assert_eq!(der_element(&[0x30, 0x84, 0xff, 0xff, 0xff, 0xff, 0]), None);
+        assert_eq!(der_element(&[0x30, 0x85, 1, 0, 0, 0, 0, 0]), None);
+        assert_eq!(der_element(&[0x30, 0x80, 0]), None);
+        assert_eq!(der_element(&[0x30, 0x03, 1, 2]), None);
+        assert_eq!(
+            der_element(&[0x02, 0x81, 0x01, 7, 0x05]),
+            Some((0x02, &[7][..], &[0x05][..]))
+        );
+    }
+}
@@ -0,0 +1,405 @@
+//! Spooling of data points by `BufferedClient` while the server fails, and recovery of spools
+//! left damaged by a crash.
+
+use std::fs;
+use std::path::PathBuf;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use nextcloud_analytics_rs::testing::MockTransport;
+use nextcloud_analytics_rs::{BufferedClient, Outcome, SpoolFormat};
+use reqwest::StatusCode;
+
+const SUCCESS: &str = r#"{"success":true,"message":"Data update successfull"}"#;
+
+/// A path for the spool of a test, removed if it exists.
+fn spool_path(name: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("nca-test-{}-{}.jsonl", name, std::process::id()));
+    let _ = fs::remove_file(&path);
+    let _ = fs::remove_file(path.with_extension("corrupt"));
+    let _ = fs::remove_file(path.with_extension("delivered"));
+    path
+}
+
+/// The keys of the points sent, in order.
+fn sent_keys(mock: &MockTransport) -> Vec<String> {
+    mock.sent_points()
+        .into_iter()
+        .map(|point| point.dimension1)
+        .collect()
+}
+
+#[test]
+fn points_are_sent_exactly_once() {
+    let path = spool_path("exactly-once");
+    let mock = MockTransport::new();
+    let buffered = BufferedClient::new(mock.client(3), &path);
+
+    // The server fails: a is spooled, then b, as a still can't be sent.
+    mock.respond(StatusCode::INTERNAL_SERVER_ERROR, "");
+    let receipt = buffered.send_data("a", "", 1).unwrap();
+    assert_eq!(receipt.outcome, Outcome::Spooled);
+    mock.respond(StatusCode::INTERNAL_SERVER_ERROR, "");
+    let receipt = buffered.send_data("b", "", 2).unwrap();
+    assert_eq!(receipt.outcome, Outcome::Spooled);
+    assert_eq!(buffered.spooled().unwrap(), 2);
+
+    // The server is back for a only: b stays spooled, followed by c.
+    mock.respond(StatusCode::OK, SUCCESS)
+        .respond(StatusCode::INTERNAL_SERVER_ERROR, "");
+    let receipt = buffered.send_data("c", "", 3).unwrap();
+    assert_eq!(receipt.outcome, Outcome::Spooled);
+    assert_eq!(buffered.spooled().unwrap(), 2);
+
+    // The server is back: the rest is sent, in order.
+    let receipt = buffered.send_data("d", "", 4).unwrap();
+    assert!(receipt.was_sent());
+    assert_eq!(buffered.spooled().unwrap(), 0);
+
+    // a, a and b failed; every point then succeeded once, and was never sent again.
+    assert_eq!(sent_keys(&mock), ["a", "a", "a", "b", "b", "c", "d"]);
+    let _ = fs::remove_file(&path);
+}
+
+#[test]
+fn flush_after_partial_success() {
+    let path = spool_path("flush");
+    let mock = MockTransport::new();
+    let buffered = BufferedClient::new(mock.client(3), &path);
+
+    for key in ["a", "b", "c"] {
+        mock.respond(StatusCode::SERVICE_UNAVAILABLE, "");
+        let receipt = buffered.send_data(key, "", 1).unwrap();
+        assert_eq!(receipt.outcome, Outcome::Spooled);
+    }
+    mock.clear();
+
+    mock.respond(StatusCode::OK, SUCCESS)
+        .respond(StatusCode::INTERNAL_SERVER_ERROR, "");
+    assert_eq!(buffered.flush().unwrap(), 1);
+    assert_eq!(buffered.flush().unwrap(), 2);
+    assert_eq!(buffered.flush().unwrap(), 0);
+    assert_eq!(sent_keys(&mock), ["a", "b", "b", "c"]);
+    let _ = fs::remove_file(&path);
+}
+
+/// Spools points with the keys `keys` and the same timestamp, while the server fails.
+fn spool_all(buffered: &BufferedClient, mock: &MockTransport, keys: &[&str]) {
+    for key in keys {
+        mock.respond(StatusCode::SERVICE_UNAVAILABLE, "");
+        let receipt = buffered.send_data(*key, "2024-01-01", 1).unwrap();
+        assert_eq!(receipt.outcome, Outcome::Spooled);
+    }
+    mock.clear();
+}
+
+#[test]
+fn points_delivered_before_a_crash_are_not_sent_again() {
+    let path = spool_path("crash-flush");
+    let mock = MockTransport::new();
+    let buffered = BufferedClient::new(mock.client(3), &path);
+    spool_all(&buffered, &mock, &["a", "b", "a", "c"]);
+
+    // A crash after sending the first a, before rewriting the spool.
+    let delivered = buffered.delivered_path();
+    fs::write(
+        &delivered,
+        "{\"dimension1\":\"a\",\"dimension2\":\"2024-01-01\"}\n{\"dimension1\":\"b\",",
+    )
+    .unwrap();
+
+    assert_eq!(buffered.flush().unwrap(), 4);
+    assert_eq!(sent_keys(&mock), ["b", "a", "c"]);
+    assert!(!delivered.exists());
+    let _ = fs::remove_file(&path);
+}
+
+#[test]
+fn points_delivered_before_a_crash_are_not_replayed() {
+    let path = spool_path("crash-replay");
+    let mock = MockTransport::new();
+    let buffered = BufferedClient::new(mock.client(3), &path);
+    spool_all(&buffered, &mock, &["a", "b", "c", "d"]);
+
+    // The first batch is sent, then the server fails.
+    mock.respond(StatusCode::OK, SUCCESS)
+        .respond(StatusCode::OK, SUCCESS)
+        .respond(StatusCode::SERVICE_UNAVAILABLE, "");
+    assert_eq!(buffered.replay(2, 1).unwrap(), 2);
+    assert!(!buffered.delivered_path().exists());
+    assert_eq!(buffered.spooled().unwrap(), 2);
+
+    // A crash after sending c, before truncating the spool.
+    mock.clear();
+    fs::write(
+        buffered.delivered_path(),
+        "{\"dimension1\":\"c\",\"dimension2\":\"2024-01-01\"}\n",
+    )
+    .unwrap();
+    assert_eq!(buffered.replay(2, 1).unwrap(), 1);
+    assert_eq!(sent_keys(&mock), ["d"]);
+    assert_eq!(buffered.spooled().unwrap(), 0);
+    assert!(!buffered.delivered_path().exists());
+    let _ = fs::remove_file(&path);
+}
+
+/// An empty directory for the archive of a test.
+fn archive_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("nca-test-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    dir
+}
+
+#[test]
+fn replays_the_points_delivered_during_a_range() {
+    let path = spool_path("archive");
+    let dir = archive_dir("archive");
+    let mock = MockTransport::new();
+    let buffered = BufferedClient::new(mock.client(3), &path).with_archive(&dir);
+
+    let before = SystemTime::now() - Duration::from_secs(1);
+    spool_all(&buffered, &mock, &["a"]);
+    mock.respond(StatusCode::SERVICE_UNAVAILABLE, "");
+    assert_eq!(buffered.replay(10, 1).unwrap(), 0);
+    assert!(buffered.send_data("b", "2024-01-01", 2).unwrap().was_sent());
+    let after = SystemTime::now() + Duration::from_secs(1);
+    assert_eq!(sent_keys(&mock), ["a", "a", "b"]);
+
+    // The server lost everything since before the sends.
+    mock.clear();
+    assert_eq!(buffered.replay_range(before..after, 10, 2).unwrap(), 2);
+    assert_eq!(sent_keys(&mock), ["a", "b"]);
+    // Points sent again aren't archived again.
+    mock.clear();
+    assert_eq!(buffered.replay_range(before..after, 10, 2).unwrap(), 2);
+    assert_eq!(buffered.replay_range(after..after, 10, 2).unwrap(), 0);
+
+    let _ = fs::remove_dir_all(&dir);
+    let _ = fs::remove_file(&path);
+}
+
+#[test]
+fn replays_only_the_partitions_of_the_range() {
+    let path = spool_path("partitions");
+    let dir = archive_dir("partitions");
+    fs::create_dir_all(&dir).unwrap();
+    // 2024-01-01 12:00 and 23:00 UTC, and 2024-01-02 01:00.
+    fs::write(
+        dir.join("2024-01-01.jsonl"),
+        concat!(
+            "{\"dimension1\":\"noon\",\"dimension2\":\"\",\"dimension3\":\"1\",\"sent_at\":1704110400}\n",
+            "{\"dimension1\":\"evening\",\"dimension2\":\"\",\"dimension3\":\"2\",\"sent_at\":1704150000}\n",
+            "{\"dimension1\":\"half-wri",
+        ),
+    )
+    .unwrap();
+    fs::write(
+        dir.join("2024-01-02.jsonl"),
+        "{\"dimension1\":\"night\",\"dimension2\":\"\",\"dimension3\":3,\"sent_at\":1704157200}\n",
+    )
+    .unwrap();
+    let mock = MockTransport::new();
+    let buffered = BufferedClient::new(mock.client(3), &path).with_archive(&dir);
+
+    let time = |secs| UNIX_EPOCH + Duration::from_secs(secs);
+    assert_eq!(
+        buffered
+            .replay_range(time(1_704_140_000)..time(1_704_160_000), 10, 1)
+            .unwrap(),
+        2
+    );
+    assert_eq!(sent_keys(&mock), ["evening", "night"]);
+
+    let unarchived = BufferedClient::new(mock.client(3), &path);
+    assert!(unarchived
+        .replay_range(time(0)..time(1_704_160_000), 10, 1)
+        .is_err());
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn replays_at_the_pace_of_the_deliveries() {
+    let path = spool_path("pace");
+    let dir = archive_dir("pace");
+    fs::create_dir_all(&dir).unwrap();
+    // Delivered at 0, 10 and 20 seconds past midnight, two points at 10.
+    fs::write(
+        dir.join("1970-01-01.jsonl"),
+        concat!(
+            "{\"dimension1\":\"first\",\"dimension2\":\"\",\"dimension3\":\"1\",\"sent_at\":0}\n",
+            "{\"dimension1\":\"second\",\"dimension2\":\"\",\"dimension3\":\"2\",\"sent_at\":10}\n",
+            "{\"dimension1\":\"third\",\"dimension2\":\"\",\"dimension3\":\"3\",\"sent_at\":10}\n",
+            "{\"dimension1\":\"fourth\",\"dimension2\":\"\",\"dimension3\":\"4\",\"sent_at\":20}\n",
+        ),
+    )
+    .unwrap();
+    let mock = MockTransport::new();
+    let range = UNIX_EPOCH..UNIX_EPOCH + Duration::from_secs(60);
+
+    // 20 seconds at 100 times the speed: 200 ms.
+    let paced = BufferedClient::new(mock.client(3), &path)
+        .with_archive(&dir)
+        .with_replay_speed(100.0);
+    let started = Instant::now();
+    assert_eq!(paced.replay_range(range.clone(), 10, 1).unwrap(), 4);
+    assert!(started.elapsed() >= Duration::from_millis(200));
+    assert_eq!(sent_keys(&mock), ["first", "second", "third", "fourth"]);
+
+    // A speed of 0 sends them as fast as possible.
+    mock.clear();
+    let unpaced = BufferedClient::new(mock.client(3), &path)
+        .with_archive(&dir)
+        .with_replay_speed(0.0);
+    let started = Instant::now();
+    assert_eq!(unpaced.replay_range(range, 10, 1).unwrap(), 4);
+    assert!(started.elapsed() < Duration::from_secs(5));
+    assert_eq!(sent_keys(&mock), ["first", "second", "third", "fourth"]);
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn binary_spools_skip_corrupted_records() {
+    let path = spool_path("binary");
+    let mock = MockTransport::new();
+    let buffered = BufferedClient::new(mock.client(3), &path).with_format(SpoolFormat::Binary);
+    spool_all(&buffered, &mock, &["a", "b", "c"]);
+    let records = fs::read(&path).unwrap();
+    assert_eq!(&records[..2], [0xA5, 0x5A]);
+    assert!(!records.contains(&b'{'));
+
+    // A bit flipped in b, and a half-written record after c.
+    let mut damaged = records.clone();
+    let record_len = records.len() / 3;
+    damaged[record_len + 8] ^= 0x01;
+    damaged.extend_from_slice(&records[..record_len / 2]);
+    fs::write(&path, &damaged).unwrap();
+
+    assert_eq!(buffered.spooled().unwrap(), 2);
+    let quarantined = fs::read(buffered.quarantine_path()).unwrap();
+    assert_eq!(quarantined.len(), record_len + record_len / 2);
+    spool_all(&buffered, &mock, &["d"]);
+    assert_eq!(buffered.flush().unwrap(), 3);
+    assert_eq!(sent_keys(&mock), ["a", "c", "d"]);
+    assert!(!path.exists());
+    let _ = fs::remove_file(buffered.quarantine_path());
+}
+
+#[test]
+fn binary_spools_are_replayed() {
+    let path = spool_path("binary-replay");
+    let mock = MockTransport::new();
+    let buffered = BufferedClient::new(mock.client(3), &path).with_format(SpoolFormat::Binary);
+    spool_all(&buffered, &mock, &["a", "b", "c"]);
+    let mut damaged = fs::read(&path).unwrap();
+    damaged.truncate(damaged.len() - 1);
+    fs::write(&path, &damaged).unwrap();
+
+    assert_eq!(buffered.replay(2, 2).unwrap(), 2);
+    assert_eq!(sent_keys(&mock), ["a", "b"]);
+    assert_eq!(buffered.spooled().unwrap(), 0);
+    let _ = fs::remove_file(buffered.quarantine_path());
+}
+
+#[test]
+fn truncated_last_line_is_quarantined() {
+    let path = spool_path("truncated");
+    fs::write(
+        &path,
+        "{\"dimension1\":\"a\",\"dimension2\":\"\",\"dimension3\":\"1\"}\n{\"dimension1\":\"b\",\"dimen",
+    )
+    .unwrap();
+    let mock = MockTransport::new();
+    let buffered = BufferedClient::new(mock.client(3), &path);
+
+    assert_eq!(buffered.spooled().unwrap(), 1);
+    assert_eq!(
+        fs::read_to_string(buffered.quarantine_path()).unwrap(),
+        "{\"dimension1\":\"b\",\"dimen\n"
+    );
+
+    // New points are appended after the valid ones, not to the half-written line.
+    mock.respond(StatusCode::INTERNAL_SERVER_ERROR, "");
+    let receipt = buffered.send_data("c", "", 3).unwrap();
+    assert_eq!(receipt.outcome, Outcome::Spooled);
+    assert_eq!(buffered.spooled().unwrap(), 2);
+    assert_eq!(buffered.flush().unwrap(), 2);
+    assert_eq!(sent_keys(&mock), ["a", "a", "c"]);
+
+    let _ = fs::remove_file(&path);
+    let _ = fs::remove_file(buffered.quarantine_path());
+}
+
+#[test]
+fn truncated_last_line_before_replay() {
+    let path = spool_path("replay");
+    fs::write(
+        &path,
+        "{\"dimension1\":\"a\",\"dimension2\":\"\",\"dimension3\":1}\n{\"dimension1\"",
+    )
+    .unwrap();
+    let mock = MockTransport::new();
+    let buffered = BufferedClient::new(mock.client(3), &path);
+
+    assert_eq!(buffered.replay(10, 1).unwrap(), 1);
+    assert_eq!(buffered.spooled().unwrap(), 0);
+    assert!(buffered.quarantine_path().exists());
+    let _ = fs::remove_file(buffered.quarantine_path());
+}
+
+#[test]
+fn corrupted_line_in_the_middle_is_an_error() {
+    let path = spool_path("corrupted");
+    fs::write(
+        &path,
+        "not json\n{\"dimension1\":\"a\",\"dimension2\":\"\",\"dimension3\":1}\n",
+    )
+    .unwrap();
+    let buffered = BufferedClient::new(MockTransport::new().client(3), &path);
+
+    assert!(buffered.spooled().is_err());
+    assert!(!buffered.quarantine_path().exists());
+    let _ = fs::remove_file(&path);
+}
+
+#[test]
+fn spooling_only_reads_the_oldest_point() {
+    let path = spool_path("append");
+    let mock = MockTransport::new();
+    let buffered = BufferedClient::new(mock.client(3), &path);
+    spool_all(&buffered, &mock, &["a", "b", "c"]);
+
+    // Damage the middle of the spool: while the server fails, new points are still appended,
+    // as only the oldest one is read to try it again.
+    let content = fs::read_to_string(&path).unwrap();
+    let mut lines: Vec<_> = content.lines().collect();
+    lines[1] = "not json";
+    fs::write(&path, lines.join("\n") + "\n").unwrap();
+    mock.respond(StatusCode::SERVICE_UNAVAILABLE, "");
+    let receipt = buffered.send_data("d", "2024-01-01", 1).unwrap();
+    assert_eq!(receipt.outcome, Outcome::Spooled);
+    assert_eq!(sent_keys(&mock), ["a"]);
+    assert_eq!(fs::read_to_string(&path).unwrap().lines().count(), 4);
+
+    // It's read once the server is back.
+    assert!(buffered.send_data("e", "2024-01-01", 1).is_err());
+    let _ = fs::remove_file(&path);
+    let _ = fs::remove_file(buffered.delivered_path());
+}
+
+#[test]
+fn full_spools_drop_their_oldest_tenth() {
+    let path = spool_path("full");
+    let mock = MockTransport::new();
+    let buffered = BufferedClient::new(mock.client(3), &path).with_max_points(10);
+    let keys: Vec<String> = (0..12).map(|i| i.to_string()).collect();
+    let keys: Vec<&str> = keys.iter().map(String::as_str).collect();
+
+    spool_all(&buffered, &mock, &keys[..10]);
+    assert_eq!(buffered.spooled().unwrap(), 10);
+    // The 11th point drops the 2 oldest ones, and the next one fits.
+    spool_all(&buffered, &mock, &keys[10..]);
+    assert_eq!(buffered.spooled().unwrap(), 10);
+
+    assert_eq!(buffered.flush().unwrap(), 10);
+    assert_eq!(sent_keys(&mock), &keys[2..]);
+    let _ = fs::remove_file(&path);
+}
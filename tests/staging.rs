@@ -0,0 +1,118 @@
+//! Committing staged data points, and rolling back failed commits, through a mock transport.
+
+use nextcloud_analytics_rs::testing::MockTransport;
+use nextcloud_analytics_rs::{ApiVersion, DataPoint, ErrorClass, SyncClient};
+use reqwest::StatusCode;
+use serde_json::{json, Value};
+
+const SUCCESS: &str = r#"{"success":true,"message":"Data update successfull"}"#;
+
+fn client(mock: &MockTransport, version: ApiVersion) -> SyncClient {
+    SyncClient::builder("https://nextcloud.invalid/", 3, "user", "password")
+        .api_version(version)
+        .build()
+        .unwrap()
+        .with_transport(mock.clone())
+}
+
+fn point(i: usize) -> DataPoint {
+    DataPoint::new("temp", &format!("2024-01-0{}", i), i as f64)
+}
+
+#[test]
+fn commits_in_batches() {
+    let mock = MockTransport::new();
+    let client = client(&mock, ApiVersion::V3);
+    let mut staging = client.begin().with_batch_size(2);
+    for i in 1..=5 {
+        staging.add_point(point(i));
+    }
+    assert_eq!(staging.preview().len(), 5);
+    assert!(mock.requests().is_empty());
+
+    staging.commit().unwrap();
+    let requests = mock.requests();
+    assert_eq!(requests.len(), 3);
+    assert!(requests
+        .iter()
+        .all(|request| request.url.ends_with("/data/3/add")));
+    assert_eq!(mock.sent_points(), (1..=5).map(point).collect::<Vec<_>>());
+}
+
+#[test]
+fn deletes_the_points_sent_when_a_batch_fails() {
+    let mock = MockTransport::new();
+    let client = client(&mock, ApiVersion::V3);
+    let mut staging = client.begin().with_batch_size(2);
+    for i in 1..=5 {
+        staging.add_point(point(i));
+    }
+    mock.respond(StatusCode::OK, SUCCESS)
+        .respond(StatusCode::BAD_REQUEST, "");
+
+    let err = staging.commit().unwrap_err();
+    assert_eq!(err.sent, [point(1), point(2)]);
+    assert_eq!(err.failed, [point(3), point(4)]);
+    assert_eq!(err.unsent, [point(5)]);
+    assert_eq!(err.source.class(), ErrorClass::Http);
+    assert!(err.rollback.is_ok());
+
+    let requests = mock.requests();
+    assert_eq!(requests.len(), 3);
+    assert!(requests[2].url.ends_with("/data/3/delete"));
+    let delete: Value = serde_json::from_str(requests[2].body_text().unwrap()).unwrap();
+    assert_eq!(
+        delete,
+        json!({"delete": [
+            {"dimension1": "temp", "dimension2": "2024-01-01"},
+            {"dimension1": "temp", "dimension2": "2024-01-02"},
+        ]})
+    );
+}
+
+#[test]
+fn reports_failed_rollbacks() {
+    let mock = MockTransport::new();
+    let client = client(&mock, ApiVersion::V3);
+    let mut staging = client.begin().with_batch_size(1);
+    staging.add_point(point(1)).add_point(point(2));
+    mock.respond(StatusCode::OK, SUCCESS)
+        .respond(StatusCode::BAD_REQUEST, "")
+        .respond(StatusCode::INTERNAL_SERVER_ERROR, "");
+
+    let err = staging.commit().unwrap_err();
+    assert_eq!(err.sent, [point(1)]);
+    assert_eq!(err.rollback.as_ref().unwrap_err().class(), ErrorClass::Http);
+    assert!(err.to_string().contains("deleting the points sent failed"));
+    assert_eq!(mock.requests().len(), 3);
+}
+
+#[test]
+fn nothing_to_roll_back_when_the_first_batch_fails() {
+    let mock = MockTransport::new();
+    let client = client(&mock, ApiVersion::V3);
+    let mut staging = client.begin();
+    staging.add_point(point(1)).add_point(point(2));
+    mock.respond(StatusCode::BAD_REQUEST, "");
+
+    let err = staging.commit().unwrap_err();
+    assert!(err.sent.is_empty());
+    assert_eq!(err.failed.len(), 2);
+    assert!(err.rollback.is_ok());
+    assert_eq!(mock.requests().len(), 1);
+}
+
+#[test]
+fn the_v1_api_cant_roll_back() {
+    let mock = MockTransport::new();
+    let client = client(&mock, ApiVersion::V1);
+    let mut staging = client.begin().with_batch_size(1);
+    staging.add_point(point(1)).add_point(point(2));
+    mock.respond(StatusCode::OK, SUCCESS)
+        .respond(StatusCode::BAD_REQUEST, "");
+
+    let err = staging.commit().unwrap_err();
+    assert_eq!(err.sent, [point(1)]);
+    assert!(err.rollback.is_err());
+    assert_eq!(mock.requests().len(), 2);
+}
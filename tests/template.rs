@@ -0,0 +1,101 @@
+//! Expansion of key templates.
+
+use nextcloud_analytics_rs::testing::MockTransport;
+use nextcloud_analytics_rs::{AnalyticsError, Template};
+
+fn error(template: &Template) -> String {
+    template.expand("temp").unwrap_err().to_string()
+}
+
+#[test]
+fn expands_the_metric() {
+    let template = Template::new("sensors/{metric}/raw");
+    assert_eq!(template.pattern(), "sensors/{metric}/raw");
+    assert_eq!(template.expand("temp").unwrap(), "sensors/temp/raw");
+    assert_eq!(Template::new("{metric}{metric}").expand("a").unwrap(), "aa");
+    assert_eq!(Template::new("static").expand("temp").unwrap(), "static");
+}
+
+#[test]
+fn expands_custom_variables() {
+    let template = Template::new("{site}/{room}/{metric}")
+        .var("site", "paris")
+        .var("room", "kitchen");
+    assert_eq!(template.expand("temp").unwrap(), "paris/kitchen/temp");
+
+    // A variable named like a built-in one doesn't override it.
+    let template = Template::new("{metric}").var("metric", "other");
+    assert_eq!(template.expand("temp").unwrap(), "temp");
+}
+
+#[test]
+fn expands_environment_variables() {
+    std::env::set_var("NCA_TEMPLATE_TEST_SITE", "lyon");
+    let template = Template::new("{env:NCA_TEMPLATE_TEST_SITE}/{metric}");
+    assert_eq!(template.expand("temp").unwrap(), "lyon/temp");
+
+    let message = error(&Template::new("{env:NCA_TEMPLATE_TEST_UNSET}"));
+    assert!(message.contains("NCA_TEMPLATE_TEST_UNSET"), "{}", message);
+}
+
+#[cfg(feature = "hostname")]
+#[test]
+fn expands_the_hostname() {
+    let expanded = Template::new("{hostname}/{metric}").expand("temp").unwrap();
+    let hostname = expanded.strip_suffix("/temp").unwrap();
+    assert!(!hostname.is_empty());
+    assert!(!hostname.contains('{'));
+}
+
+#[cfg(not(feature = "hostname"))]
+#[test]
+fn the_hostname_requires_its_feature() {
+    let message = error(&Template::new("{hostname}/{metric}"));
+    assert!(message.contains("`hostname` feature"), "{}", message);
+}
+
+#[test]
+fn doubled_braces_are_literal() {
+    let template = Template::new("{{{metric}}}/{{raw}}").var("raw", "unused");
+    assert_eq!(template.expand("temp").unwrap(), "{temp}/{raw}");
+    assert_eq!(Template::new("}}{{").expand("temp").unwrap(), "}{");
+}
+
+#[test]
+fn unknown_variables_are_errors() {
+    let message = error(&Template::new("{site}/{metric}"));
+    assert!(
+        message.contains("unknown template variable \"site\""),
+        "{}",
+        message
+    );
+    assert!(Template::new("{}").expand("temp").is_err());
+}
+
+#[test]
+fn unmatched_braces_are_errors() {
+    for pattern in ["{metric", "sensors/{", "{metric}}", "}", "a}b"] {
+        assert!(
+            Template::new(pattern).expand("temp").is_err(),
+            "{:?} expanded",
+            pattern
+        );
+    }
+    assert!(error(&Template::new("{metric")).contains("unclosed variable"));
+    assert!(error(&Template::new("a}b")).contains("unmatched `}`"));
+}
+
+#[test]
+fn templates_name_the_data_points_sent() {
+    let mock = MockTransport::new();
+    let client = mock
+        .client(3)
+        .with_key_template(Template::new("{site}/{metric}").var("site", "paris"));
+    let _ = client.send_data("temp", "kitchen", 21.5).unwrap();
+    assert_eq!(mock.sent_points()[0].dimension1, "paris/temp");
+
+    let client = mock.client(3).with_key_template(Template::new("{site}"));
+    let err = client.send_data("temp", "kitchen", 21.5).unwrap_err();
+    assert!(matches!(err, AnalyticsError::Local(_)), "{:?}", err);
+    assert_eq!(mock.requests().len(), 1);
+}
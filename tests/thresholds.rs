@@ -0,0 +1,228 @@
+//! Report thresholds, and client-side thresholds watched by polling a report, through a mock
+//! transport.
+
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use nextcloud_analytics_rs::testing::MockTransport;
+use nextcloud_analytics_rs::thresholds::{Comparison, Severity, Threshold, ThresholdWatch};
+use reqwest::{Method, StatusCode};
+use serde_json::{json, Value};
+
+#[test]
+fn sets_lists_and_deletes_thresholds() {
+    let mock = MockTransport::new();
+    let client = mock.client(3);
+
+    mock.respond(StatusCode::OK, "12");
+    let id = client
+        .set_threshold(7, "temp", Comparison::GreaterThan, 30.5, Severity::Alert)
+        .unwrap();
+    assert_eq!(id, 12);
+    let request = &mock.requests()[0];
+    assert_eq!(request.method, Method::POST);
+    assert!(request.url.ends_with("/apps/analytics/threshold"));
+    let body: Value = serde_json::from_str(request.body_text().unwrap()).unwrap();
+    assert_eq!(
+        body,
+        json!({"reportId": 7, "dimension1": "temp", "option": ">", "value": "30.5", "severity": 2})
+    );
+
+    mock.respond(
+        StatusCode::OK,
+        json!([
+            {"id": 12, "dimension1": "temp", "option": ">", "value": "30.5", "severity": 2},
+            {"id": "13", "dimension1": "rain", "option": "=", "value": 0, "severity": 4},
+            {"id": 14, "dimension1": "temp", "option": "between", "value": 1, "severity": 2},
+        ])
+        .to_string(),
+    );
+    let thresholds = client.list_thresholds(7).unwrap();
+    assert!(mock.requests()[1]
+        .url
+        .ends_with("/apps/analytics/threshold/7"));
+    assert_eq!(
+        thresholds,
+        [
+            Threshold {
+                id: 12,
+                dimension1: "temp".to_string(),
+                comparison: Comparison::GreaterThan,
+                value: 30.5,
+                severity: Severity::Alert,
+            },
+            Threshold {
+                id: 13,
+                dimension1: "rain".to_string(),
+                comparison: Comparison::Equal,
+                value: 0.0,
+                severity: Severity::Good,
+            },
+        ]
+    );
+
+    mock.respond(StatusCode::OK, "true");
+    client.delete_threshold(12).unwrap();
+    let request = &mock.requests()[2];
+    assert_eq!(request.method, Method::DELETE);
+    assert!(request.url.ends_with("/apps/analytics/threshold/12"));
+}
+
+#[test]
+fn compares_values() {
+    assert!(Comparison::GreaterThan.holds(31.0, 30.0));
+    assert!(!Comparison::GreaterThan.holds(30.0, 30.0));
+    assert!(Comparison::LessThan.holds(-1.0, 0.0));
+    assert!(!Comparison::LessThan.holds(0.0, 0.0));
+    assert!(Comparison::Equal.holds(2.5, 2.5));
+    assert!(!Comparison::Equal.holds(2.5, 2.6));
+}
+
+/// The data of a report, with a value of `temp` per poll, older ones first.
+fn report(values: &[f64]) -> String {
+    let rows: Vec<Value> = values
+        .iter()
+        .enumerate()
+        .map(|(i, value)| json!(["temp", format!("2024-01-01 00:0{}:00", i), value]))
+        .collect();
+    json!({ "data": rows }).to_string()
+}
+
+/// What the callbacks of a watch were called with, in order.
+type Events = Arc<Mutex<Vec<String>>>;
+
+fn logged(watch: ThresholdWatch, events: &Events) -> ThresholdWatch {
+    let (breach, clear, error, recovered) = (
+        events.clone(),
+        events.clone(),
+        events.clone(),
+        events.clone(),
+    );
+    watch
+        .on_breach(move |event| {
+            assert_eq!(event.dimension1, "temp");
+            breach
+                .lock()
+                .unwrap()
+                .push(format!("breach {}", event.value));
+        })
+        .on_clear(move |event| clear.lock().unwrap().push(format!("clear {}", event.value)))
+        .on_error(move |_| error.lock().unwrap().push("error".to_string()))
+        .on_recovered(move || recovered.lock().unwrap().push("recovered".to_string()))
+}
+
+/// Watches `watch` while the report answers with each of `responses` in turn, returning what
+/// its callbacks were called with.
+fn watch(watch: ThresholdWatch, responses: &[(StatusCode, String)]) -> Vec<String> {
+    let mock = MockTransport::new();
+    for (status, body) in responses {
+        mock.respond(*status, body.clone());
+    }
+    let client = Arc::new(mock.client(3));
+    let events = Events::default();
+    let watcher = client.watch_thresholds(7, Duration::from_millis(5), logged(watch, &events));
+
+    let started = Instant::now();
+    while mock.requests().len() < responses.len() && started.elapsed() < Duration::from_secs(10) {
+        thread::sleep(Duration::from_millis(5));
+    }
+    // Let the last poll be evaluated.
+    thread::sleep(Duration::from_millis(50));
+    watcher.stop();
+
+    assert!(mock.requests()[0]
+        .url
+        .ends_with("/apps/analytics/api/3.0/data/7"));
+    let events = events.lock().unwrap().clone();
+    events
+}
+
+fn ok(values: &[f64]) -> (StatusCode, String) {
+    (StatusCode::OK, report(values))
+}
+
+#[test]
+fn reports_crossings_of_the_latest_value() {
+    let events = watch(
+        ThresholdWatch::new().threshold("temp", Comparison::GreaterThan, 30.0),
+        &[
+            ok(&[29.0]),
+            // Only the latest value of the key counts.
+            ok(&[35.0, 29.5]),
+            ok(&[29.5, 31.0]),
+            ok(&[31.0, 32.0]),
+            ok(&[30.0]),
+            ok(&[]),
+            ok(&[25.0]),
+        ],
+    );
+    assert_eq!(events, ["breach 31", "clear 30"]);
+}
+
+#[test]
+fn a_threshold_breached_at_the_first_poll_is_reported() {
+    let events = watch(
+        ThresholdWatch::new().threshold("temp", Comparison::LessThan, 0.0),
+        &[ok(&[-2.0])],
+    );
+    assert_eq!(events, ["breach -2"]);
+}
+
+#[test]
+fn hysteresis_delays_clearing() {
+    let events = watch(
+        ThresholdWatch::new()
+            .threshold("temp", Comparison::GreaterThan, 30.0)
+            .hysteresis(2.0),
+        &[
+            ok(&[31.0]),
+            // Within the margin: still breached.
+            ok(&[29.0]),
+            ok(&[30.5]),
+            ok(&[28.5]),
+            ok(&[27.5]),
+            // Breaching again takes the threshold itself, not the margin.
+            ok(&[29.5]),
+            ok(&[30.5]),
+        ],
+    );
+    assert_eq!(events, ["breach 31", "clear 27.5", "breach 30.5"]);
+
+    let events = watch(
+        ThresholdWatch::new()
+            .threshold("temp", Comparison::LessThan, 0.0)
+            .hysteresis(1.0),
+        &[ok(&[-1.0]), ok(&[0.5]), ok(&[1.5])],
+    );
+    assert_eq!(events, ["breach -1", "clear 1.5"]);
+}
+
+#[test]
+fn recovers_from_errors_keeping_the_state() {
+    let failure = (StatusCode::INTERNAL_SERVER_ERROR, String::new());
+    let events = watch(
+        ThresholdWatch::new().threshold("temp", Comparison::GreaterThan, 30.0),
+        &[
+            ok(&[31.0]),
+            failure.clone(),
+            failure.clone(),
+            // Still breached after the errors: not reported again.
+            ok(&[32.0]),
+            failure,
+            ok(&[20.0]),
+        ],
+    );
+    assert_eq!(
+        events,
+        [
+            "breach 31",
+            "error",
+            "error",
+            "recovered",
+            "error",
+            "recovered",
+            "clear 20"
+        ]
+    );
+}